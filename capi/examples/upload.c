@@ -211,7 +211,7 @@ int main(int argc, char *argv[]) {
 
     // Hookup the IO
     hyper_io *io = hyper_io_new();
-    hyper_io_set_userdata(io, (void *)conn);
+    hyper_io_set_userdata(io, (void *)conn, NULL);
     hyper_io_set_read(io, read_cb);
     hyper_io_set_write(io, write_cb);
 
@@ -225,7 +225,7 @@ int main(int argc, char *argv[]) {
     hyper_clientconn_options_exec(opts, exec);
 
     hyper_task *handshake = hyper_clientconn_handshake(io, opts);
-    hyper_task_set_userdata(handshake, (void *)EXAMPLE_HANDSHAKE);
+    hyper_task_set_userdata(handshake, (void *)EXAMPLE_HANDSHAKE, NULL);
 
     // Let's wait for the handshake to finish...
     hyper_executor_push(exec, handshake);
@@ -280,13 +280,13 @@ int main(int argc, char *argv[]) {
 
                 // Prepare the req body
                 hyper_body *body = hyper_body_new();
-                hyper_body_set_userdata(body, &upload);
+                hyper_body_set_userdata(body, &upload, NULL);
                 hyper_body_set_data_func(body, poll_req_upload);
                 hyper_request_set_body(req, body);
 
                 // Send it!
                 hyper_task *send = hyper_clientconn_send(client, req);
-                hyper_task_set_userdata(send, (void *)EXAMPLE_SEND);
+                hyper_task_set_userdata(send, (void *)EXAMPLE_SEND, NULL);
                 printf("sending ...\n");
                 hyper_executor_push(exec, send);
 
@@ -318,7 +318,7 @@ int main(int argc, char *argv[]) {
 
                 // Set us up to peel data from the body a chunk at a time
                 hyper_task *body_data = hyper_body_data(resp_body);
-                hyper_task_set_userdata(body_data, (void *)EXAMPLE_RESP_BODY);
+                hyper_task_set_userdata(body_data, (void *)EXAMPLE_RESP_BODY, NULL);
                 hyper_executor_push(exec, body_data);
 
                 // No longer need the response
@@ -339,7 +339,7 @@ int main(int argc, char *argv[]) {
                     hyper_task_free(task);
 
                     hyper_task *body_data = hyper_body_data(resp_body);
-                    hyper_task_set_userdata(body_data, (void *)EXAMPLE_RESP_BODY);
+                    hyper_task_set_userdata(body_data, (void *)EXAMPLE_RESP_BODY, NULL);
                     hyper_executor_push(exec, body_data);
 
                     break;
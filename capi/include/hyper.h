@@ -105,8 +105,73 @@ typedef enum hyper_code {
    The peer sent an HTTP message that could not be parsed.
    */
   HYPERE_INVALID_PEER_MESSAGE,
+  /*
+   The operation can't be done yet. Wait to be told it is ready, and
+   try again.
+   */
+  HYPERE_NOT_READY,
+  /*
+   The receiving side is gone, so nothing more can be sent.
+   */
+  HYPERE_CLOSED,
 } hyper_code;
 
+/*
+ The kind of failure a `hyper_error` represents.
+
+ The values are stable, new kinds are only ever added to the end.
+ */
+typedef enum hyper_error_kind {
+  /*
+   An error that doesn't fit any other kind.
+   */
+  HYPER_ERROR_KIND_OTHER = 0,
+  /*
+   The peer sent an HTTP message that could not be parsed.
+   */
+  HYPER_ERROR_KIND_PARSE = 1,
+  /*
+   An error caused by the user, such as a callback or an outgoing body.
+   */
+  HYPER_ERROR_KIND_USER = 2,
+  /*
+   A message reached EOF, but is not complete.
+   */
+  HYPER_ERROR_KIND_INCOMPLETE_MESSAGE = 3,
+  /*
+   The connection received a message when not waiting for one.
+   */
+  HYPER_ERROR_KIND_UNEXPECTED_MESSAGE = 4,
+  /*
+   A pending request was dropped before ever being processed.
+   */
+  HYPER_ERROR_KIND_CANCELED = 5,
+  /*
+   The connection, or a body channel, is closed.
+   */
+  HYPER_ERROR_KIND_CLOSED = 6,
+  /*
+   Reading from or writing to the IO transport failed.
+   */
+  HYPER_ERROR_KIND_IO = 7,
+  /*
+   Reading a body from the connection failed.
+   */
+  HYPER_ERROR_KIND_BODY = 8,
+  /*
+   Writing a body to the connection failed.
+   */
+  HYPER_ERROR_KIND_BODY_WRITE = 9,
+  /*
+   Shutting down the IO transport failed.
+   */
+  HYPER_ERROR_KIND_SHUTDOWN = 10,
+  /*
+   An error from the HTTP/2 protocol.
+   */
+  HYPER_ERROR_KIND_HTTP2 = 11,
+} hyper_error_kind;
+
 /*
  A descriptor for what type a `hyper_task` value is.
  */
@@ -131,6 +196,10 @@ typedef enum hyper_task_return_type {
    The value of this task is `hyper_buf *`.
    */
   HYPER_TASK_BUF,
+  /*
+   The value of this task is `hyper_upgraded *`.
+   */
+  HYPER_TASK_UPGRADED,
 } hyper_task_return_type;
 
 /*
@@ -138,6 +207,11 @@ typedef enum hyper_task_return_type {
  */
 typedef struct hyper_body hyper_body;
 
+/*
+ Pushes chunks of data into a `hyper_body`.
+ */
+typedef struct hyper_body_writer hyper_body_writer;
+
 /*
  A buffer of bytes that is sent or received on a `hyper_body`.
  */
@@ -149,6 +223,9 @@ typedef struct hyper_buf hyper_buf;
  These are used to send a request on a single connection. It's possible to
  send multiple requests on a single connection, such as when HTTP/1
  keep-alive or HTTP/2 is used.
+
+ An HTTP/2 connection can have many requests in flight at once, and can
+ have many handles, made with `hyper_clientconn_clone`.
  */
 typedef struct hyper_clientconn hyper_clientconn;
 
@@ -199,15 +276,35 @@ typedef struct hyper_response hyper_response;
  */
 typedef struct hyper_task hyper_task;
 
+/*
+ A timer driven by the C event loop.
+
+ hyper uses it to schedule the wakeups its timeouts need, instead of
+ spawning a thread for each one.
+ */
+typedef struct hyper_timer hyper_timer;
+
+/*
+ A connection taken over by an HTTP upgrade.
+
+ This is the `hyper_io` the connection was made with, along with any
+ bytes hyper read from it that weren't part of the HTTP response.
+ */
+typedef struct hyper_upgraded hyper_upgraded;
+
 /*
  A waker that is saved and used to waken a pending task.
  */
 typedef struct hyper_waker hyper_waker;
 
+typedef void (*hyper_userdata_drop)(void*);
+
 typedef int (*hyper_body_foreach_callback)(void*, const struct hyper_buf*);
 
 typedef int (*hyper_body_data_callback)(void*, struct hyper_context*, struct hyper_buf**);
 
+typedef void (*hyper_body_writer_ready_callback)(void*);
+
 typedef void (*hyper_request_on_informational_callback)(void*, struct hyper_response*);
 
 typedef int (*hyper_headers_foreach_callback)(void*, const uint8_t*, size_t, const uint8_t*, size_t);
@@ -216,6 +313,12 @@ typedef size_t (*hyper_io_read_callback)(void*, struct hyper_context*, uint8_t*,
 
 typedef size_t (*hyper_io_write_callback)(void*, struct hyper_context*, const uint8_t*, size_t);
 
+typedef void (*hyper_executor_wakeup_callback)(void*);
+
+typedef uint64_t (*hyper_timer_schedule_callback)(void*, uint64_t, struct hyper_waker*);
+
+typedef void (*hyper_timer_cancel_callback)(void*, uint64_t);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -231,7 +334,8 @@ const char *hyper_version(void);
  If not configured, this body acts as an empty payload.
 
  To avoid a memory leak, the body must eventually be consumed by
- `hyper_body_free`, `hyper_body_foreach`, or `hyper_request_set_body`.
+ `hyper_body_free`, `hyper_body_foreach`, `hyper_body_foreach_trailer`,
+ or `hyper_request_set_body`.
  */
 struct hyper_body *hyper_body_new(void);
 
@@ -252,6 +356,9 @@ void hyper_body_free(struct hyper_body *body);
  - `HYPER_TASK_ERROR`: An error retrieving the data.
  - `HYPER_TASK_EMPTY`: The body has finished streaming data.
 
+ Any trailers received after the data are kept, and can be read with
+ `hyper_body_foreach_trailer`.
+
  To avoid a memory leak, the task must eventually be consumed by
  `hyper_task_free`, or taken ownership of by `hyper_executor_push`
  without subsequently being given back by `hyper_executor_poll`.
@@ -270,7 +377,8 @@ struct hyper_task *hyper_body_data(struct hyper_body *body);
  without subsequently being given back by `hyper_executor_poll`.
 
  The `hyper_buf` pointer is only a borrowed reference, it cannot live outside
- the execution of the callback. You must make a copy to retain it.
+ the execution of the callback. To retain the chunk, take a reference to
+ it with `hyper_buf_clone`, which doesn't copy the bytes.
 
  The callback should return `HYPER_ITER_CONTINUE` to continue iterating
  chunks as they are received, or `HYPER_ITER_BREAK` to cancel.
@@ -281,10 +389,59 @@ struct hyper_task *hyper_body_foreach(struct hyper_body *body,
                                       hyper_body_foreach_callback func,
                                       void *userdata);
 
+/*
+ Return a task that will poll the body to its end, and execute the
+ callback with each trailer name and value pair.
+
+ Trailers already received by `hyper_body_data` are passed first. Any
+ data still left in the body is discarded.
+
+ Trailers are received on HTTP/2 connections. HTTP/1 connections
+ skip the trailers of a chunked body, so the task completes without
+ calling the callback, as it does if the body had none.
+
+ To avoid a memory leak, the task must eventually be consumed by
+ `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+ without subsequently being given back by `hyper_executor_poll`.
+
+ The name and value pointers are only borrowed, they cannot live outside
+ the execution of the callback. You must make a copy to retain them.
+
+ The callback should return `HYPER_ITER_CONTINUE` to keep iterating, or
+ `HYPER_ITER_BREAK` to stop.
+
+ This will consume the `hyper_body *`, you shouldn't use it anymore or free it.
+ */
+struct hyper_task *hyper_body_foreach_trailer(struct hyper_body *body,
+                                              hyper_headers_foreach_callback func,
+                                              void *userdata);
+
+/*
+ Add a trailer to send once all of the data of this body is sent.
+
+ If there were already trailers with the same name, this appends the
+ new value to them.
+
+ On HTTP/2 connections, all trailers are sent. On HTTP/1 connections,
+ they are only sent with a chunked request body, and only the fields
+ named in the request's `Trailer` header; the others are discarded.
+
+ This must be called before the body is passed to
+ `hyper_request_set_body`.
+ */
+enum hyper_code hyper_body_add_trailer(struct hyper_body *body,
+                                       const uint8_t *name,
+                                       size_t name_len,
+                                       const uint8_t *value,
+                                       size_t value_len);
+
 /*
  Set userdata on this body, which will be passed to callback functions.
+
+ If `drop` isn't `NULL`, it is called with the userdata once the body
+ is dropped, or given another userdata.
  */
-void hyper_body_set_userdata(struct hyper_body *body, void *userdata);
+void hyper_body_set_userdata(struct hyper_body *body, void *userdata, hyper_userdata_drop drop);
 
 /*
  Set the data callback for this body.
@@ -309,6 +466,66 @@ void hyper_body_set_userdata(struct hyper_body *body, void *userdata);
  */
 void hyper_body_set_data_func(struct hyper_body *body, hyper_body_data_callback func);
 
+/*
+ Create a writer to push chunks of data into this body.
+
+ Once a writer is created, the body sends the chunks passed to
+ `hyper_body_write_chunk`, instead of calling the data callback set
+ with `hyper_body_set_data_func`.
+
+ This does not consume the `hyper_body *`, which can still be passed
+ to `hyper_request_set_body`.
+
+ To avoid a memory leak, the writer must eventually be consumed by
+ `hyper_body_writer_finish` or `hyper_body_writer_free`.
+ */
+struct hyper_body_writer *hyper_body_writer_new(struct hyper_body *body);
+
+/*
+ Set a callback to be told when the writer can accept another chunk.
+
+ The callback is called with `userdata` each time the connection takes
+ the chunk waiting in the writer, and once when the body is dropped. It
+ is called from within `hyper_executor_poll`, and may call
+ `hyper_body_write_chunk` itself.
+ */
+void hyper_body_writer_set_ready_func(struct hyper_body_writer *writer,
+                                      hyper_body_writer_ready_callback func,
+                                      void *userdata);
+
+/*
+ Push a chunk of data into the body, by copying the provided bytes.
+
+ Only one chunk is buffered at a time. If the previous chunk hasn't
+ been taken by the connection yet, this returns `HYPERE_NOT_READY`
+ without copying anything; wait for the callback set with
+ `hyper_body_writer_set_ready_func` and try again.
+
+ Returns `HYPERE_CLOSED` if the body has been dropped, such as when the
+ connection closed.
+
+ An empty chunk is ignored, and `buf` may be `NULL` if `len` is 0.
+ */
+enum hyper_code hyper_body_write_chunk(struct hyper_body_writer *writer,
+                                       const uint8_t *buf,
+                                       size_t len);
+
+/*
+ End the body, once any chunk still waiting in the writer is sent.
+
+ This consumes the `hyper_body_writer *`, you shouldn't use it anymore
+ or free it.
+ */
+enum hyper_code hyper_body_writer_finish(struct hyper_body_writer *writer);
+
+/*
+ Free a body writer.
+
+ If the writer wasn't finished with `hyper_body_writer_finish`, the
+ body is aborted with an error.
+ */
+void hyper_body_writer_free(struct hyper_body_writer *writer);
+
 /*
  Create a new `hyper_buf *` by copying the provided bytes.
 
@@ -322,6 +539,33 @@ void hyper_body_set_data_func(struct hyper_body *body, hyper_body_data_callback
  */
 struct hyper_buf *hyper_buf_copy(const uint8_t *buf, size_t len);
 
+/*
+ Create a new `hyper_buf *` referencing the same bytes as `buf`.
+
+ The bytes aren't copied. They are reference counted, and stay valid
+ until every `hyper_buf` referencing them has been freed, so the new
+ buffer can outlive `buf`. This is the cheap way to keep the chunks
+ passed to a `hyper_body_foreach` callback.
+
+ To avoid a memory leak, the new buffer must eventually be consumed by
+ `hyper_buf_free`.
+ */
+struct hyper_buf *hyper_buf_clone(const struct hyper_buf *buf);
+
+/*
+ Create a new `hyper_buf *` referencing `len` bytes of `buf`, starting
+ at `offset`.
+
+ Like `hyper_buf_clone`, the bytes aren't copied, and the new buffer
+ can outlive `buf`.
+
+ To avoid a memory leak, the new buffer must eventually be consumed by
+ `hyper_buf_free`.
+
+ This returns `NULL` if the range is out of bounds of `buf`.
+ */
+struct hyper_buf *hyper_buf_slice(const struct hyper_buf *buf, size_t offset, size_t len);
+
 /*
  Get a pointer to the bytes in this buffer.
 
@@ -371,12 +615,86 @@ struct hyper_task *hyper_clientconn_handshake(struct hyper_io *io,
  Returns a task that needs to be polled until it is ready. When ready, the
  task yields a `hyper_response *`.
 
+ On an HTTP/2 connection, this can be called again before earlier tasks
+ are ready, and the requests are multiplexed on the connection. Each
+ task is independent, and can be polled, canceled or freed separately.
+ On an HTTP/1 connection, only one request can be in flight at a time,
+ and a request sent while `hyper_clientconn_is_ready` is false fails.
+
  To avoid a memory leak, the task must eventually be consumed by
  `hyper_task_free`, or taken ownership of by `hyper_executor_push`
  without subsequently being given back by `hyper_executor_poll`.
  */
 struct hyper_task *hyper_clientconn_send(struct hyper_clientconn *conn, struct hyper_request *req);
 
+/*
+ Returns the number of bytes written to the IO transport of the
+ connection so far.
+ */
+uint64_t hyper_clientconn_bytes_sent(const struct hyper_clientconn *conn);
+
+/*
+ Returns the number of bytes read from the IO transport of the
+ connection so far.
+ */
+uint64_t hyper_clientconn_bytes_received(const struct hyper_clientconn *conn);
+
+/*
+ Returns the number of requests sent on the connection that received a
+ response.
+ */
+uint64_t hyper_clientconn_requests_completed(const struct hyper_clientconn *conn);
+
+/*
+ Returns true if the connection can send another request right now.
+
+ A connection that isn't ready may still become ready later, such as
+ once the response to an earlier HTTP/1 request is received, unless
+ `hyper_clientconn_is_closed` returns true.
+ */
+bool hyper_clientconn_is_ready(const struct hyper_clientconn *conn);
+
+/*
+ Returns true if the connection is closed, and can't send any more
+ requests.
+ */
+bool hyper_clientconn_is_closed(const struct hyper_clientconn *conn);
+
+/*
+ Create another handle to an HTTP/2 connection.
+
+ Both handles send requests on the same connection, and share its
+ metrics. The connection is closed once every handle has been freed,
+ and every request sent on it has completed. This lets separate parts
+ of a program send requests concurrently, without sharing a handle.
+
+ To avoid a memory leak, the new handle must eventually be consumed by
+ `hyper_clientconn_free`.
+
+ This returns `NULL` for an HTTP/1 connection, which can only send one
+ request at a time.
+ */
+struct hyper_clientconn *hyper_clientconn_clone(const struct hyper_clientconn *conn);
+
+/*
+ Gracefully shut down the connection, returning a task that completes
+ once it has closed.
+
+ This consumes the `conn`, you shouldn't use it anymore or free it.
+ Requests already sent on it are completed first, then the connection
+ is closed. Any other handles made with `hyper_clientconn_clone` keep
+ an HTTP/2 connection open until they are freed as well.
+
+ The task yields `HYPER_TASK_EMPTY` once the connection has closed, or
+ once its background task has been dropped, such as when its executor
+ is freed.
+
+ To avoid a memory leak, the task must eventually be consumed by
+ `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+ without subsequently being given back by `hyper_executor_poll`.
+ */
+struct hyper_task *hyper_clientconn_graceful_shutdown(struct hyper_clientconn *conn);
+
 /*
  Free a `hyper_clientconn *`.
 
@@ -424,6 +742,39 @@ void hyper_clientconn_options_free(struct hyper_clientconn_options *opts);
 void hyper_clientconn_options_exec(struct hyper_clientconn_options *opts,
                                    const struct hyper_executor *exec);
 
+/*
+ Set the timer used for timeouts, such as HTTP/2 keep-alive.
+
+ By default, hyper spawns a thread for each timeout it waits on.
+
+ This consumes the `timer`, you shouldn't use it anymore or free it.
+ */
+void hyper_clientconn_options_timer(struct hyper_clientconn_options *opts,
+                                    struct hyper_timer *timer);
+
+/*
+ Set an informational (1xx) response callback for every request sent on
+ the connection.
+
+ The callback is called each time hyper receives an informational (1xx)
+ response, such as `103 Early Hints`, before the final response of a
+ request. Requests with their own callback, set with
+ `hyper_request_on_informational`, use that one instead.
+
+ The callback is passed the `data` pointer, and a `hyper_response *`
+ with the status and headers of the informational response. Its body
+ is always empty.
+
+ NOTE: The `hyper_response *` is just borrowed data, and will not
+ be valid after the callback finishes. You must copy any data you wish
+ to persist.
+
+ Only HTTP/1 connections report informational responses.
+ */
+void hyper_clientconn_options_on_informational(struct hyper_clientconn_options *opts,
+                                               hyper_request_on_informational_callback callback,
+                                               void *data);
+
 /*
  Set the whether to use HTTP2.
 
@@ -441,6 +792,80 @@ enum hyper_code hyper_clientconn_options_http2(struct hyper_clientconn_options *
 enum hyper_code hyper_clientconn_options_http1_allow_multiline_headers(struct hyper_clientconn_options *opts,
                                                                        int enabled);
 
+/*
+ Set whether HTTP/1 connections are kept open after a response, so more
+ requests can be sent on them.
+
+ When disabled, each request is sent with a `connection: close` header,
+ unless it already has a `connection` header, and the connection is
+ closed once its response is received.
+
+ Pass `0` to disable, `1` to enable (default).
+ */
+enum hyper_code hyper_clientconn_options_http1_keep_alive(struct hyper_clientconn_options *opts,
+                                                          int enabled);
+
+/*
+ Set whether HTTP/2 connections use adaptive flow control.
+
+ Enabling this overrides the window sizes set with
+ `hyper_clientconn_options_http2_initial_stream_window_size` and
+ `hyper_clientconn_options_http2_initial_connection_window_size`.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_clientconn_options_http2_adaptive_window(struct hyper_clientconn_options *opts,
+                                                               int enabled);
+
+/*
+ Set the initial HTTP/2 stream-level flow control window, in bytes.
+
+ If not set, hyper will use a default.
+ */
+enum hyper_code hyper_clientconn_options_http2_initial_stream_window_size(struct hyper_clientconn_options *opts,
+                                                                          uint32_t size);
+
+/*
+ Set the initial HTTP/2 connection-level flow control window, in bytes.
+
+ If not set, hyper will use a default.
+ */
+enum hyper_code hyper_clientconn_options_http2_initial_connection_window_size(struct hyper_clientconn_options *opts,
+                                                                              uint32_t size);
+
+/*
+ Set the interval, in milliseconds, at which HTTP/2 PING frames are sent
+ to keep the connection alive.
+
+ Pass `0` to disable keep-alive (default).
+
+ Unless a timer is set with `hyper_clientconn_options_timer`, each
+ pending ping wakes its connection task from a background thread, but
+ the ping is only sent the next time the executor is polled. An
+ application that waits on IO between polls should wake up at least
+ this often.
+ */
+enum hyper_code hyper_clientconn_options_http2_keep_alive_interval(struct hyper_clientconn_options *opts,
+                                                                   uint64_t interval_ms);
+
+/*
+ Set how long to wait, in milliseconds, for an HTTP/2 keep-alive ping to
+ be acknowledged before closing the connection.
+
+ Does nothing unless a keep-alive interval is set. Default is 20 seconds.
+ */
+enum hyper_code hyper_clientconn_options_http2_keep_alive_timeout(struct hyper_clientconn_options *opts,
+                                                                  uint64_t timeout_ms);
+
+/*
+ Set the maximum number of locally reset HTTP/2 streams to keep track
+ of at once.
+
+ If not set, the default is determined by the `h2` crate.
+ */
+enum hyper_code hyper_clientconn_options_http2_max_concurrent_reset_streams(struct hyper_clientconn_options *opts,
+                                                                            size_t max);
+
 /*
  Frees a `hyper_error`.
 
@@ -463,6 +888,89 @@ enum hyper_code hyper_error_code(const struct hyper_error *err);
  */
 size_t hyper_error_print(const struct hyper_error *err, uint8_t *dst, size_t dst_len);
 
+/*
+ Get the kind of this error.
+
+ This is more detailed than `hyper_error_code`, and its values are
+ stable across releases.
+ */
+enum hyper_error_kind hyper_error_get_kind(const struct hyper_error *err);
+
+/*
+ Returns true if this was an HTTP parse error.
+ */
+bool hyper_error_is_parse(const struct hyper_error *err);
+
+/*
+ Returns true if this was an HTTP parse error caused by a message that
+ was too large.
+ */
+bool hyper_error_is_parse_too_large(const struct hyper_error *err);
+
+/*
+ Returns true if this was an HTTP parse error caused by an invalid
+ response status code or reason phrase.
+ */
+bool hyper_error_is_parse_status(const struct hyper_error *err);
+
+/*
+ Returns true if this error was caused by user code, such as a
+ callback.
+ */
+bool hyper_error_is_user(const struct hyper_error *err);
+
+/*
+ Returns true if this was about a request that was canceled.
+ */
+bool hyper_error_is_canceled(const struct hyper_error *err);
+
+/*
+ Returns true if the connection was closed.
+ */
+bool hyper_error_is_closed(const struct hyper_error *err);
+
+/*
+ Returns true if the connection closed before a message could complete.
+ */
+bool hyper_error_is_incomplete_message(const struct hyper_error *err);
+
+/*
+ Returns true if writing a body was aborted.
+ */
+bool hyper_error_is_body_write_aborted(const struct hyper_error *err);
+
+/*
+ Returns true if the error was caused by a timeout.
+
+ This includes IO transport errors reported as timeouts.
+ */
+bool hyper_error_is_timeout(const struct hyper_error *err);
+
+/*
+ Get the number of errors in the chain of causes of this error.
+
+ Use `hyper_error_print_source` to print each of them.
+ */
+size_t hyper_error_source_count(const struct hyper_error *err);
+
+/*
+ Print the details of a cause of this error to a buffer.
+
+ The `index` selects the cause, starting at `0` for the error that
+ directly caused this one, and going up to one less than
+ `hyper_error_source_count`.
+
+ The `dst_len` value must be the maximum length that the buffer can
+ store.
+
+ The return value is number of bytes that were written to `dst`, and
+ is `0` if there is no cause at `index`.
+ */
+size_t hyper_error_print_source(const struct hyper_error *err,
+                                size_t index,
+                                uint8_t *dst,
+                                size_t dst_len);
+
 /*
  Construct a new HTTP request.
 
@@ -606,6 +1114,20 @@ const uint8_t *hyper_response_reason_phrase(const struct hyper_response *resp);
  */
 size_t hyper_response_reason_phrase_len(const struct hyper_response *resp);
 
+/*
+ Set the reason-phrase of this response.
+
+ It replaces the reason-phrase the response was received with, or the
+ canonical reason of its status code, as returned by
+ `hyper_response_reason_phrase`. The bytes are copied.
+
+ Returns `HYPERE_INVALID_ARG` if the reason-phrase contains bytes that
+ aren't allowed in one, such as `\r` or `\n`.
+ */
+enum hyper_code hyper_response_set_reason_phrase(struct hyper_response *resp,
+                                                 const uint8_t *reason,
+                                                 size_t reason_len);
+
 /*
  Get the HTTP version used by this response.
 
@@ -618,6 +1140,48 @@ size_t hyper_response_reason_phrase_len(const struct hyper_response *resp);
  */
 int hyper_response_version(const struct hyper_response *resp);
 
+/*
+ Get the address of the peer this response was received from.
+
+ On success, `*addr` and `*addr_len` are set to the address given to
+ `hyper_io_set_peer_addr`. It is borrowed data, and not valid once the
+ response has been freed.
+
+ Returns `HYPERE_INVALID_ARG` if the IO of the connection had no peer
+ address set.
+ */
+enum hyper_code hyper_response_peer_addr(const struct hyper_response *resp,
+                                         const uint8_t **addr,
+                                         size_t *addr_len);
+
+/*
+ Get the TLS version of the connection this response was received on.
+
+ On success, `*version` and `*version_len` are set to the version given
+ to `hyper_io_set_tls_info`. It is borrowed data, and not valid once the
+ response has been freed.
+
+ Returns `HYPERE_INVALID_ARG` if the IO of the connection had no TLS
+ version set.
+ */
+enum hyper_code hyper_response_tls_version(const struct hyper_response *resp,
+                                           const uint8_t **version,
+                                           size_t *version_len);
+
+/*
+ Get the ALPN protocol of the connection this response was received on.
+
+ On success, `*alpn` and `*alpn_len` are set to the protocol given to
+ `hyper_io_set_tls_info`. It is borrowed data, and not valid once the
+ response has been freed.
+
+ Returns `HYPERE_INVALID_ARG` if the IO of the connection had no ALPN
+ protocol set.
+ */
+enum hyper_code hyper_response_alpn_protocol(const struct hyper_response *resp,
+                                             const uint8_t **alpn,
+                                             size_t *alpn_len);
+
 /*
  Gets a reference to the HTTP headers of this response.
 
@@ -632,10 +1196,36 @@ struct hyper_headers *hyper_response_headers(struct hyper_response *resp);
  It is safe to free the response even after taking ownership of its body.
 
  To avoid a memory leak, the body must eventually be consumed by
- `hyper_body_free`, `hyper_body_foreach`, or `hyper_request_set_body`.
+ `hyper_body_free`, `hyper_body_foreach`, `hyper_body_foreach_trailer`,
+ or `hyper_request_set_body`.
  */
 struct hyper_body *hyper_response_body(struct hyper_response *resp);
 
+/*
+ Return a task that waits for the connection of this response to be
+ upgraded.
+
+ This is for responses that switch the connection to another protocol,
+ such as a `101 Switching Protocols` response, or a `2xx` response to a
+ `CONNECT` request. Check the response before calling this. Once the
+ response is received, the connection can't be used to send more
+ requests.
+
+ The task value may have different types depending on the outcome:
+
+ - `HYPER_TASK_UPGRADED`: Success, the value is a `hyper_upgraded *`.
+ - `HYPER_TASK_ERROR`: The connection was not upgraded, or was not
+   an HTTP/1 connection.
+
+ To avoid a memory leak, the task must eventually be consumed by
+ `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+ without subsequently being given back by `hyper_executor_poll`.
+
+ This does not consume the `hyper_response *`, which must still be
+ freed.
+ */
+struct hyper_task *hyper_response_upgrade(struct hyper_response *resp);
+
 /*
  Iterates the headers passing each name and value pair to the callback.
 
@@ -671,6 +1261,70 @@ enum hyper_code hyper_headers_add(struct hyper_headers *headers,
                                   const uint8_t *value,
                                   size_t value_len);
 
+/*
+ Removes all values of the provided name.
+
+ This does nothing if there are no values for the name.
+ */
+enum hyper_code hyper_headers_remove(struct hyper_headers *headers,
+                                     const uint8_t *name,
+                                     size_t name_len);
+
+/*
+ Gets a value of the provided name.
+
+ The `index` selects which value, when there are several for the name,
+ in the order they were received or added, starting at `0`. On success,
+ `*value` and `*value_len` are set to the bytes of the value.
+
+ The value is borrowed data, and not valid once the headers are changed
+ or freed.
+
+ Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+ */
+enum hyper_code hyper_headers_get(const struct hyper_headers *headers,
+                                  const uint8_t *name,
+                                  size_t name_len,
+                                  size_t index,
+                                  const uint8_t **value,
+                                  size_t *value_len);
+
+/*
+ Gets the name of a value of the provided name, in its original case.
+
+ The `index` selects the value as in `hyper_headers_get`. On success,
+ `*orig_name` and `*orig_name_len` are set to the bytes of the name, as
+ it was received or added. This is the name `hyper_headers_foreach`
+ passes along with the value. If its case wasn't recorded, such as when
+ the connection doesn't preserve header case, it is lowercase.
+
+ The name is borrowed data, and not valid once the headers are changed
+ or freed.
+
+ Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+ */
+enum hyper_code hyper_headers_get_original_name(const struct hyper_headers *headers,
+                                                const uint8_t *name,
+                                                size_t name_len,
+                                                size_t index,
+                                                const uint8_t **orig_name,
+                                                size_t *orig_name_len);
+
+/*
+ Replaces a value of the provided name.
+
+ The `index` selects the value as in `hyper_headers_get`. The original
+ case and order of the header are kept.
+
+ Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+ */
+enum hyper_code hyper_headers_replace(struct hyper_headers *headers,
+                                      const uint8_t *name,
+                                      size_t name_len,
+                                      size_t index,
+                                      const uint8_t *value,
+                                      size_t value_len);
+
 /*
  Create a new IO type used to represent a transport.
 
@@ -694,8 +1348,11 @@ void hyper_io_free(struct hyper_io *io);
  Set the user data pointer for this IO to some value.
 
  This value is passed as an argument to the read and write callbacks.
+
+ If `drop` isn't `NULL`, it is called with the value once the IO is
+ freed, or given another user data pointer.
  */
-void hyper_io_set_userdata(struct hyper_io *io, void *data);
+void hyper_io_set_userdata(struct hyper_io *io, void *data, hyper_userdata_drop drop);
 
 /*
  Set the read function for this IO transport.
@@ -734,6 +1391,55 @@ void hyper_io_set_read(struct hyper_io *io, hyper_io_read_callback func);
  */
 void hyper_io_set_write(struct hyper_io *io, hyper_io_write_callback func);
 
+/*
+ Set the address of the peer this IO transport is connected to.
+
+ The address is opaque bytes to hyper, such as `192.0.2.1:443`. It is
+ copied, and made available on each response received on a connection
+ made with this IO, with `hyper_response_peer_addr`.
+ */
+enum hyper_code hyper_io_set_peer_addr(struct hyper_io *io, const uint8_t *addr, size_t addr_len);
+
+/*
+ Set details of the TLS session this IO transport runs over.
+
+ The `version` is the negotiated protocol version, such as `TLSv1.3`,
+ and `alpn` is the protocol chosen by ALPN, such as `h2`. Either may be
+ `NULL` if unknown. Both are copied, and made available on each response
+ received on a connection made with this IO, with
+ `hyper_response_tls_version` and `hyper_response_alpn_protocol`.
+ */
+enum hyper_code hyper_io_set_tls_info(struct hyper_io *io,
+                                      const uint8_t *version,
+                                      size_t version_len,
+                                      const uint8_t *alpn,
+                                      size_t alpn_len);
+
+/*
+ Take back the IO transport of an upgraded connection.
+
+ This returns the `hyper_io *` originally passed to
+ `hyper_clientconn_handshake`, with the same callbacks and userdata,
+ so the new protocol can be spoken directly over the transport.
+
+ hyper may have read bytes of the new protocol along with the
+ response. If so, `*read_buf` is set to a `hyper_buf *` of them, which
+ must be handled before reading from the transport, and eventually
+ freed with `hyper_buf_free`. Otherwise, it is set to `NULL`.
+
+ This consumes the `hyper_upgraded *`, you shouldn't use it anymore or
+ free it. To avoid a memory leak, the returned IO must eventually be
+ freed with `hyper_io_free`.
+ */
+struct hyper_io *hyper_upgraded_into_io(struct hyper_upgraded *upgraded,
+                                        struct hyper_buf **read_buf);
+
+/*
+ Free an upgraded connection, along with its IO transport and any
+ buffered bytes.
+ */
+void hyper_upgraded_free(struct hyper_upgraded *upgraded);
+
 /*
  Creates a new task executor.
 
@@ -757,6 +1463,28 @@ void hyper_executor_free(const struct hyper_executor *exec);
  */
 enum hyper_code hyper_executor_push(const struct hyper_executor *exec, struct hyper_task *task);
 
+/*
+ Set a callback to be told when the executor should be polled again.
+
+ The callback is passed `userdata`. It is called whenever a task is
+ pushed or woken up while the executor isn't being polled, such as once
+ a `hyper_waker` is woken. The event loop should then call
+ `hyper_executor_poll` until it returns `NULL`. Until the callback is
+ called again, there is no need to poll the executor, so the event loop
+ can wait for IO and timers instead.
+
+ The callback may be called from any thread that wakes a task, so it
+ should only do something thread-safe and quick, like writing to an
+ eventfd or a pipe the event loop is waiting on. It must not call back
+ into the executor. It may be called more than once before the
+ executor is polled, and spuriously.
+
+ Passing a `NULL` callback removes any callback previously set.
+ */
+enum hyper_code hyper_executor_set_wakeup(const struct hyper_executor *exec,
+                                          hyper_executor_wakeup_callback func,
+                                          void *userdata);
+
 /*
  Polls the executor, trying to make progress on any tasks that have notified
  that they are ready again.
@@ -780,6 +1508,21 @@ struct hyper_task *hyper_executor_poll(const struct hyper_executor *exec);
  */
 void hyper_task_free(struct hyper_task *task);
 
+/*
+ Cancel a task.
+
+ The future of the task is dropped the next time its executor is polled,
+ which aborts whatever the task was doing, such as sending a request or
+ driving a connection. The task is then given back by
+ `hyper_executor_poll`, with a `HYPER_TASK_ERROR` value. From then on,
+ hyper won't call any callback of the task with its userdata.
+
+ This may be called while the task is owned by an executor, including
+ from within a callback of the task itself. It does nothing if the task
+ has already completed.
+ */
+enum hyper_code hyper_task_cancel(struct hyper_task *task);
+
 /*
  Takes the output value of this task.
 
@@ -805,8 +1548,11 @@ enum hyper_task_return_type hyper_task_type(struct hyper_task *task);
 
  This value will be passed to task callbacks, and can be checked later
  with `hyper_task_userdata`.
+
+ If `drop` isn't `NULL`, it is called with the value once the task is
+ freed, or given another user data pointer.
  */
-void hyper_task_set_userdata(struct hyper_task *task, void *userdata);
+void hyper_task_set_userdata(struct hyper_task *task, void *userdata, hyper_userdata_drop drop);
 
 /*
  Retrieve the userdata that has been set via `hyper_task_set_userdata`.
@@ -836,6 +1582,36 @@ void hyper_waker_free(struct hyper_waker *waker);
  */
 void hyper_waker_wake(struct hyper_waker *waker);
 
+/*
+ Create a timer from a pair of callbacks.
+
+ The schedule callback is passed `userdata`, a delay in milliseconds,
+ and a `hyper_waker *`. Once the delay has passed, the event loop must
+ call `hyper_waker_wake` with the waker. It returns an id for the
+ scheduled wakeup, of the event loop's choosing.
+
+ The cancel callback is passed `userdata` and the id of a scheduled
+ wakeup that is no longer needed. If it hasn't fired yet, its waker
+ should be freed with `hyper_waker_free`. The cancel callback may be
+ passed the id of a wakeup that already fired, which it should ignore.
+
+ Both callbacks are called from within `hyper_executor_poll`.
+
+ To avoid a memory leak, the timer must eventually be consumed by
+ `hyper_timer_free` or `hyper_clientconn_options_timer`.
+ */
+struct hyper_timer *hyper_timer_new(hyper_timer_schedule_callback schedule,
+                                    hyper_timer_cancel_callback cancel,
+                                    void *userdata);
+
+/*
+ Free a timer.
+
+ This should only be used if the timer isn't consumed by
+ `hyper_clientconn_options_timer`.
+ */
+void hyper_timer_free(struct hyper_timer *timer);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
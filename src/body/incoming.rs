@@ -1,21 +1,17 @@
 use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use bytes::Bytes;
-use futures_channel::mpsc;
-use futures_channel::oneshot;
-use futures_util::{stream::FusedStream, Stream}; // for mpsc::Receiver
+use futures_util::task::AtomicWaker;
 use http::HeaderMap;
 use http_body::{Body, Frame, SizeHint};
 
 use super::DecodedLength;
-use crate::common::Future;
-use crate::common::{task, watch, Pin, Poll};
+use crate::common::{task, Pin, Poll};
 #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
 use crate::proto::h2::ping;
 
-type BodySender = mpsc::Sender<Result<Bytes, crate::Error>>;
-type TrailersSender = oneshot::Sender<HeaderMap>;
-
 /// A stream of `Bytes`, used when receiving bodies from the network.
 #[must_use = "streams do nothing unless polled"]
 pub struct Incoming {
@@ -27,14 +23,14 @@ enum Kind {
     Empty,
     Chan {
         content_length: DecodedLength,
-        want_tx: watch::Sender,
-        data_rx: mpsc::Receiver<Result<Bytes, crate::Error>>,
-        trailers_rx: oneshot::Receiver<HeaderMap>,
+        rx: Receiver,
     },
     #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
     H2 {
         content_length: DecodedLength,
         data_done: bool,
+        // Capacity not to release, to keep the stream's window smaller.
+        withheld: u32,
         ping: ping::Recorder,
         recv: h2::RecvStream,
     },
@@ -57,14 +53,72 @@ enum Kind {
 /// [`Sender::abort()`]: struct.Sender.html#method.abort
 #[must_use = "Sender does nothing unless sent on"]
 pub(crate) struct Sender {
-    want_rx: watch::Receiver,
-    data_tx: BodySender,
-    trailers_tx: Option<TrailersSender>,
+    chan: Arc<Chan>,
+}
+
+/// The receiving half of a channel, held by `Kind::Chan`.
+struct Receiver {
+    chan: Arc<Chan>,
+}
+
+/// The state shared by both halves of a body channel.
+///
+/// It buffers a single chunk, like a bounded channel of size 0 with one
+/// sender, and is allocated once, so a `ChannelArena` can reuse it.
+struct Chan {
+    state: Mutex<State>,
+    rx_task: AtomicWaker,
+    tx_task: AtomicWaker,
 }
 
+struct State {
+    want: usize,
+    data: Option<Bytes>,
+    error: Option<crate::Error>,
+    trailers: Option<HeaderMap>,
+    trailers_sent: bool,
+    tx_closed: bool,
+}
+
+const WANT_CLOSED: usize = 0;
 const WANT_PENDING: usize = 1;
 const WANT_READY: usize = 2;
 
+/// Per-connection storage for the channels of received HTTP/1 bodies.
+///
+/// HTTP/1 receives one body at a time, so once both halves of the last
+/// channel have been dropped, its allocation is reset and handed out again,
+/// instead of allocating new channel state for every message.
+#[cfg(feature = "http1")]
+#[derive(Default)]
+pub(crate) struct ChannelArena {
+    spare: Option<Arc<Chan>>,
+}
+
+#[cfg(feature = "http1")]
+impl ChannelArena {
+    pub(crate) fn channel(
+        &mut self,
+        content_length: DecodedLength,
+        wanter: bool,
+    ) -> (Sender, Incoming) {
+        match self.spare.as_mut().and_then(Arc::get_mut) {
+            Some(chan) => *chan = Chan::new(wanter),
+            // If the user still holds the previous body, leave it be.
+            None => self.spare = Some(Arc::new(Chan::new(wanter))),
+        }
+        let chan = self.spare.as_ref().expect("spare was just set").clone();
+        Incoming::chan(chan, content_length)
+    }
+}
+
+#[cfg(feature = "http1")]
+impl fmt::Debug for ChannelArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelArena").finish()
+    }
+}
+
 impl Incoming {
     /// Create a `Body` stream with an associated sender half.
     ///
@@ -76,25 +130,14 @@ impl Incoming {
     }
 
     pub(crate) fn new_channel(content_length: DecodedLength, wanter: bool) -> (Sender, Incoming) {
-        let (data_tx, data_rx) = mpsc::channel(0);
-        let (trailers_tx, trailers_rx) = oneshot::channel();
-
-        // If wanter is true, `Sender::poll_ready()` won't becoming ready
-        // until the `Body` has been polled for data once.
-        let want = if wanter { WANT_PENDING } else { WANT_READY };
-
-        let (want_tx, want_rx) = watch::channel(want);
+        Self::chan(Arc::new(Chan::new(wanter)), content_length)
+    }
 
-        let tx = Sender {
-            want_rx,
-            data_tx,
-            trailers_tx: Some(trailers_tx),
-        };
+    fn chan(chan: Arc<Chan>, content_length: DecodedLength) -> (Sender, Incoming) {
+        let tx = Sender { chan: chan.clone() };
         let rx = Incoming::new(Kind::Chan {
             content_length,
-            want_tx,
-            data_rx,
-            trailers_rx,
+            rx: Receiver { chan },
         });
 
         (tx, rx)
@@ -127,6 +170,7 @@ impl Incoming {
         }
         let body = Incoming::new(Kind::H2 {
             data_done: false,
+            withheld: 0,
             ping,
             content_length,
             recv,
@@ -135,6 +179,18 @@ impl Incoming {
         body
     }
 
+    /// Keeps the first `size` bytes of data received from being released,
+    /// so the stream's receive window ends up that much smaller.
+    #[cfg(all(feature = "http2", feature = "client"))]
+    pub(crate) fn h2_withhold_capacity(&mut self, size: u32) {
+        if let Kind::H2 {
+            ref mut withheld, ..
+        } = self.kind
+        {
+            *withheld = size;
+        }
+    }
+
     #[cfg(feature = "ffi")]
     pub(crate) fn as_ffi_mut(&mut self) -> &mut crate::ffi::UserBody {
         match self.kind {
@@ -163,32 +219,12 @@ impl Body for Incoming {
             Kind::Empty => Poll::Ready(None),
             Kind::Chan {
                 content_length: ref mut len,
-                ref mut data_rx,
-                ref mut want_tx,
-                ref mut trailers_rx,
-            } => {
-                want_tx.send(WANT_READY);
-
-                if !data_rx.is_terminated() {
-                    match ready!(Pin::new(data_rx).poll_next(cx)?) {
-                        Some(chunk) => {
-                            len.sub_if(chunk.len() as u64);
-                            return Poll::Ready(Some(Ok(Frame::data(chunk))));
-                        }
-                        // fall through to trailers
-                        None => (),
-                    }
-                }
-
-                // check trailers after data is terminated
-                match ready!(Pin::new(trailers_rx).poll(cx)) {
-                    Ok(t) => Poll::Ready(Some(Ok(Frame::trailers(t)))),
-                    Err(_) => Poll::Ready(None),
-                }
-            }
+                ref rx,
+            } => rx.poll_frame(len, cx),
             #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
             Kind::H2 {
                 ref mut data_done,
+                ref mut withheld,
                 ref ping,
                 recv: ref mut h2,
                 content_length: ref mut len,
@@ -196,7 +232,11 @@ impl Body for Incoming {
                 if !*data_done {
                     match ready!(h2.poll_data(cx)) {
                         Some(Ok(bytes)) => {
-                            let _ = h2.flow_control().release_capacity(bytes.len());
+                            let withhold = bytes.len().min(*withheld as usize);
+                            *withheld -= withhold as u32;
+                            if bytes.len() > withhold {
+                                let _ = h2.flow_control().release_capacity(bytes.len() - withhold);
+                            }
                             len.sub_if(bytes.len() as u64);
                             ping.record_data(bytes.len());
                             return Poll::Ready(Some(Ok(Frame::data(bytes))));
@@ -288,19 +328,15 @@ impl fmt::Debug for Incoming {
 impl Sender {
     /// Check to see if this `Sender` can send more data.
     pub(crate) fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
-        // Check if the receiver end has tried polling for the body yet
-        ready!(self.poll_want(cx)?);
-        self.data_tx
-            .poll_ready(cx)
-            .map_err(|_| crate::Error::new_closed())
-    }
-
-    fn poll_want(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
-        match self.want_rx.load(cx) {
-            WANT_READY => Poll::Ready(Ok(())),
+        self.chan.tx_task.register(cx.waker());
+        let state = self.chan.lock();
+        match state.want {
+            WANT_CLOSED => Poll::Ready(Err(crate::Error::new_closed())),
+            // Check if the receiver end has tried polling for the body yet,
+            // and has taken the chunk sent before.
             WANT_PENDING => Poll::Pending,
-            watch::CLOSED => Poll::Ready(Err(crate::Error::new_closed())),
-            unexpected => unreachable!("want_rx value: {}", unexpected),
+            _ if state.data.is_some() => Poll::Pending,
+            _ => Poll::Ready(Ok(())),
         }
     }
 
@@ -312,19 +348,19 @@ impl Sender {
     #[allow(unused)]
     pub(crate) async fn send_data(&mut self, chunk: Bytes) -> crate::Result<()> {
         self.ready().await?;
-        self.data_tx
-            .try_send(Ok(chunk))
-            .map_err(|_| crate::Error::new_closed())
+        self.try_send(chunk).map_err(|_| crate::Error::new_closed())
     }
 
     /// Send trailers on trailers channel.
     #[allow(unused)]
-    pub(crate) async fn send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
-        let tx = match self.trailers_tx.take() {
-            Some(tx) => tx,
-            None => return Err(crate::Error::new_closed()),
-        };
-        tx.send(trailers).map_err(|_| crate::Error::new_closed())
+    pub(crate) fn send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+        let mut state = self.chan.lock();
+        if state.trailers_sent || state.want == WANT_CLOSED {
+            return Err(crate::Error::new_closed());
+        }
+        state.trailers_sent = true;
+        state.trailers = Some(trailers);
+        Ok(())
     }
 
     /// Try to send data on this channel.
@@ -339,11 +375,20 @@ impl Sender {
     /// This is mostly useful for when trying to send from some other thread
     /// that doesn't have an async context. If in an async context, prefer
     /// `send_data()` instead.
-    #[cfg(feature = "http1")]
+    #[cfg(any(feature = "http1", feature = "http3"))]
     pub(crate) fn try_send_data(&mut self, chunk: Bytes) -> Result<(), Bytes> {
-        self.data_tx
-            .try_send(Ok(chunk))
-            .map_err(|err| err.into_inner().expect("just sent Ok"))
+        self.try_send(chunk)
+    }
+
+    fn try_send(&mut self, chunk: Bytes) -> Result<(), Bytes> {
+        let mut state = self.chan.lock();
+        if state.want == WANT_CLOSED || state.data.is_some() {
+            return Err(chunk);
+        }
+        state.data = Some(chunk);
+        drop(state);
+        self.chan.rx_task.wake();
+        Ok(())
     }
 
     #[allow(unused)]
@@ -351,12 +396,22 @@ impl Sender {
         self.send_error(crate::Error::new_body_write_aborted());
     }
 
+    /// Send an error, even if a chunk is already buffered.
     pub(crate) fn send_error(&mut self, err: crate::Error) {
-        let _ = self
-            .data_tx
-            // clone so the send works even if buffer is full
-            .clone()
-            .try_send(Err(err));
+        let mut state = self.chan.lock();
+        if state.want == WANT_CLOSED || state.error.is_some() {
+            return;
+        }
+        state.error = Some(err);
+        drop(state);
+        self.chan.rx_task.wake();
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        self.chan.lock().tx_closed = true;
+        self.chan.rx_task.wake();
     }
 }
 
@@ -368,8 +423,8 @@ impl fmt::Debug for Sender {
         struct Closed;
 
         let mut builder = f.debug_tuple("Sender");
-        match self.want_rx.peek() {
-            watch::CLOSED => builder.field(&Closed),
+        match self.chan.lock().want {
+            WANT_CLOSED => builder.field(&Closed),
             _ => builder.field(&Open),
         };
 
@@ -377,11 +432,82 @@ impl fmt::Debug for Sender {
     }
 }
 
+impl Receiver {
+    fn poll_frame(
+        &self,
+        len: &mut DecodedLength,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+        self.chan.rx_task.register(cx.waker());
+        let mut state = self.chan.lock();
+        let mut wake_tx = mem::replace(&mut state.want, WANT_READY) != WANT_READY;
+
+        let ret = if let Some(chunk) = state.data.take() {
+            // taking the chunk makes room for the next one
+            wake_tx = true;
+            len.sub_if(chunk.len() as u64);
+            Poll::Ready(Some(Ok(Frame::data(chunk))))
+        } else if let Some(err) = state.error.take() {
+            Poll::Ready(Some(Err(err)))
+        } else if !state.tx_closed {
+            Poll::Pending
+        } else {
+            // check trailers after data is terminated
+            Poll::Ready(state.trailers.take().map(|t| Ok(Frame::trailers(t))))
+        };
+
+        drop(state);
+        if wake_tx {
+            self.chan.tx_task.wake();
+        }
+        ret
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let mut state = self.chan.lock();
+        state.want = WANT_CLOSED;
+        // Don't hold on to buffers until the channel is reused.
+        let unread = (state.data.take(), state.error.take(), state.trailers.take());
+        drop(state);
+        self.chan.tx_task.wake();
+        drop(unread);
+    }
+}
+
+impl Chan {
+    fn new(wanter: bool) -> Chan {
+        // If wanter is true, `Sender::poll_ready()` won't becoming ready
+        // until the `Body` has been polled for data once.
+        let want = if wanter { WANT_PENDING } else { WANT_READY };
+
+        Chan {
+            state: Mutex::new(State {
+                want,
+                data: None,
+                error: None,
+                trailers: None,
+                trailers_sent: false,
+                tx_closed: false,
+            }),
+            rx_task: AtomicWaker::new(),
+            tx_task: AtomicWaker::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem;
     use std::task::Poll;
 
+    #[cfg(feature = "http1")]
+    use super::ChannelArena;
     use super::{Body, DecodedLength, Incoming, Sender, SizeHint};
     use http_body_util::BodyExt;
 
@@ -401,11 +527,7 @@ mod tests {
 
         //assert_eq!(body_size, mem::size_of::<Option<Incoming>>(), "Option<Incoming>");
 
-        assert_eq!(
-            mem::size_of::<Sender>(),
-            mem::size_of::<usize>() * 5,
-            "Sender"
-        );
+        assert_eq!(mem::size_of::<Sender>(), mem::size_of::<usize>(), "Sender");
 
         assert_eq!(
             mem::size_of::<Sender>(),
@@ -538,4 +660,28 @@ mod tests {
             unexpected => panic!("tx poll ready unexpected: {:?}", unexpected),
         }
     }
+
+    #[cfg(all(not(miri), feature = "http1"))]
+    #[tokio::test]
+    async fn channel_arena_reuses_dropped_channels() {
+        let mut arena = ChannelArena::default();
+
+        let (tx, rx) = arena.channel(DecodedLength::CHUNKED, /*wanter = */ false);
+        let first = std::sync::Arc::as_ptr(&tx.chan);
+        drop((tx, rx));
+
+        let (mut tx, mut rx) = arena.channel(DecodedLength::new(5), /*wanter = */ false);
+        assert_eq!(std::sync::Arc::as_ptr(&tx.chan), first, "reused");
+        assert_eq!(rx.size_hint().exact(), Some(5), "reset");
+
+        // a body still held by the user isn't reused
+        let (tx2, _rx2) = arena.channel(DecodedLength::CHUNKED, /*wanter = */ false);
+        assert_ne!(std::sync::Arc::as_ptr(&tx2.chan), first, "in use");
+
+        tx.try_send_data("hello".into()).expect("send");
+        drop(tx);
+        let chunk = rx.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(chunk, "hello");
+        assert!(rx.frame().await.is_none());
+    }
 }
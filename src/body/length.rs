@@ -3,7 +3,7 @@ use std::fmt;
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub(crate) struct DecodedLength(u64);
 
-#[cfg(any(feature = "http1", feature = "http2"))]
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 impl From<Option<u64>> for DecodedLength {
     fn from(len: Option<u64>) -> Self {
         len.and_then(|len| {
@@ -14,7 +14,7 @@ impl From<Option<u64>> for DecodedLength {
     }
 }
 
-#[cfg(any(feature = "http1", feature = "http2", test))]
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3", test))]
 const MAX_LEN: u64 = std::u64::MAX - 2;
 
 impl DecodedLength {
@@ -48,7 +48,7 @@ impl DecodedLength {
     }
 
     /// Checks the `u64` is within the maximum allowed for content-length.
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     pub(crate) fn checked_new(len: u64) -> Result<Self, crate::error::Parse> {
         if len <= MAX_LEN {
             Ok(DecodedLength(len))
@@ -27,6 +27,8 @@ pub use http_body::SizeHint;
 pub use self::incoming::Incoming;
 
 #[cfg(feature = "http1")]
+pub(crate) use self::incoming::ChannelArena;
+#[cfg(any(feature = "http1", feature = "http3"))]
 pub(crate) use self::incoming::Sender;
 pub(crate) use self::length::DecodedLength;
 
@@ -2,16 +2,28 @@
 
 use std::error::Error as StdError;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::rt::{Read, Write};
 use bytes::Bytes;
-use http::{Request, Response};
+use http::header::HOST;
+use http::uri::Authority;
+use http::{HeaderValue, Request, Response};
 use httparse::ParserConfig;
 
 use super::super::dispatch;
+use super::{tunnel, ByteCounts, TrySendError, TunnelError};
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::buf::BufPool;
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
+use crate::common::time::Time;
 use crate::common::{task, Future, Pin, Poll};
+use crate::ext::Deadline;
 use crate::proto;
+use crate::rt::{BufferPool, ConnectionEvent, Metrics, Sleep, Timer};
 use crate::upgrade::Upgraded;
 
 type Dispatcher<T, B> =
@@ -20,6 +32,8 @@ type Dispatcher<T, B> =
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::Sender<Request<B>, Response<IncomingBody>>,
+    keep_alive: Arc<AtomicBool>,
+    timer: Time,
 }
 
 /// Deconstructed parts of a `Connection`.
@@ -53,6 +67,25 @@ where
     B: Body + 'static,
 {
     inner: Option<Dispatcher<T, B>>,
+    bytes: ByteCounts,
+}
+
+/// A future that processes all HTTP state for an IO object that may not be
+/// `Send`, returned from [`Builder::handshake_local`].
+///
+/// This is the same as a [`Connection`], except that upgrades aren't
+/// supported, since an upgraded IO must be `Send`. A response accepting an
+/// upgrade finishes the connection, and the response's `OnUpgrade` resolves
+/// to an error; use [`into_parts`](LocalConnection::into_parts) to take the
+/// IO back instead.
+#[must_use = "futures do nothing unless polled"]
+pub struct LocalConnection<T, B>
+where
+    T: Read + Write + 'static,
+    B: Body + 'static,
+{
+    inner: Dispatcher<T, B>,
+    bytes: ByteCounts,
 }
 
 impl<T, B> Connection<T, B>
@@ -83,13 +116,65 @@ where
     ///
     /// Use [`poll_fn`](https://docs.rs/futures/0.1.25/futures/future/fn.poll_fn.html)
     /// and [`try_ready!`](https://docs.rs/futures/0.1.25/futures/macro.try_ready.html)
-    /// to work with this function; or use the `without_shutdown` wrapper.
+    /// to work with this function; or use the [`without_shutdown`](Connection::without_shutdown)
+    /// wrapper.
     pub fn poll_without_shutdown(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         self.inner
             .as_mut()
             .expect("already upgraded")
             .poll_without_shutdown(cx)
     }
+
+    /// Run the connection until it's done, then take it apart without
+    /// calling `shutdown` on the underlying IO.
+    ///
+    /// The connection is done once its `SendRequest` has been dropped and
+    /// the last response body has been read to the end, or after an HTTP
+    /// upgrade. The IO can then be kept open and reused, such as by caching
+    /// it for another connection to the same server. Any bytes the server
+    /// sent past the last response are in the returned `read_buf`.
+    ///
+    /// This is a convenience wrapper over `poll_without_shutdown` and
+    /// `into_parts`.
+    pub fn without_shutdown(self) -> impl Future<Output = crate::Result<Parts<T>>> {
+        let mut zelf = Some(self);
+        futures_util::future::poll_fn(move |cx| {
+            ready!(zelf.as_mut().unwrap().poll_without_shutdown(cx))?;
+            Poll::Ready(Ok(zelf.take().unwrap().into_parts()))
+        })
+    }
+
+    /// Returns a handle to the counts of bytes read from and written to the
+    /// IO by this connection.
+    ///
+    /// The handle keeps being updated after the connection is spawned, so
+    /// it can be read once the connection is done.
+    pub fn byte_counts(&self) -> ByteCounts {
+        self.bytes.clone()
+    }
+}
+
+/// What to do with bytes received after the end of a response body, that
+/// no request is waiting for.
+///
+/// Some broken servers send a few more bytes than the `Content-Length` of
+/// a response says. See [`Builder::excess_body`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExcessBody {
+    /// Fail the connection with an error, once the response was read.
+    ///
+    /// This is the default.
+    Reject,
+    /// Discard the bytes, and close the connection without an error, once
+    /// the response was read, instead of reusing it.
+    Discard,
+    /// Fail the body of the response with an error, and close the
+    /// connection.
+    ///
+    /// Only bytes received along with the end of the body can be noticed
+    /// then. If they come later, the connection fails like with
+    /// [`Reject`](ExcessBody::Reject).
+    FailResponse,
 }
 
 /// A builder to configure an HTTP connection.
@@ -97,15 +182,29 @@ where
 /// After setting options, the builder is used to create a handshake future.
 #[derive(Clone, Debug)]
 pub struct Builder {
+    timer: Time,
+    metrics: MetricsSink,
+    events: EventSink,
     h09_responses: bool,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
     h1_writev: Option<bool>,
+    h1_write_coalesce_threshold: Option<usize>,
     h1_title_case_headers: bool,
+    h1_absolute_form: bool,
+    h1_expect_continue_timeout: Option<Duration>,
+    h1_header_read_timeout: Option<Duration>,
+    h1_pipeline_depth: usize,
+    h1_response_timings: bool,
+    h1_excess_body: ExcessBody,
+    h1_malformed_reason_phrases: Option<bool>,
     h1_preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     h1_preserve_header_order: bool,
     h1_read_buf_exact_size: Option<usize>,
     h1_max_buf_size: Option<usize>,
+    h1_buffer_pool: BufPool,
+    h1_poll_budget: usize,
+    h1_write_timeout: Option<Duration>,
 }
 
 /// Returns a handshake future over some IO.
@@ -116,7 +215,6 @@ pub async fn handshake<T, B>(io: T) -> crate::Result<(SendRequest<B>, Connection
 where
     T: Read + Write + Unpin + Send + 'static,
     B: Body + 'static,
-    B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
     Builder::new().handshake(io).await
@@ -154,6 +252,33 @@ impl<B> SendRequest<B> {
     pub fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
+
+    /// Waits until the connection side has been closed.
+    ///
+    /// This resolves once the connection can't send any more requests,
+    /// such as after the server closes it, or the `Connection` future
+    /// finishes or is dropped. The returned future doesn't borrow the
+    /// `SendRequest`, or keep the connection open, so a connection pool can
+    /// wait on it in the background to evict the connection promptly.
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.dispatch.closed()
+    }
+
+    /// Checks if the connection will be kept open for another request once
+    /// the current response has been read.
+    ///
+    /// This is false once the connection is closed, or known to be closing,
+    /// such as when the server's response has `Connection: close`, or is
+    /// HTTP/1.0 without `Connection: keep-alive`. A connection pool can check
+    /// this after a response to decide whether to keep the connection.
+    ///
+    /// # Note
+    ///
+    /// Like [`is_ready`](SendRequest::is_ready), this is only a hint, since
+    /// the server may close the connection at any time.
+    pub fn is_keep_alive(&self) -> bool {
+        self.keep_alive.load(Ordering::Relaxed) && !self.is_closed()
+    }
 }
 
 impl<B> SendRequest<B>
@@ -176,19 +301,36 @@ where
     ///   before calling this method.
     /// - Since absolute-form `Uri`s are not required, if received, they will
     ///   be serialized as-is.
+    ///
+    /// # Trailers
+    ///
+    /// Trailers from the request body are only sent if the body is chunked,
+    /// and then only the fields declared in the request's `Trailer` header.
+    /// Any others are discarded.
+    ///
+    /// # Deadline
+    ///
+    /// If the request has a [`Deadline`](crate::ext::Deadline) extension,
+    /// and it passes before the response head is received, the connection
+    /// is closed and a timeout error is returned.
     pub fn send_request(
         &mut self,
         req: Request<B>,
     ) -> impl Future<Output = crate::Result<Response<IncomingBody>>> {
+        let deadline = self.deadline(&req);
         let sent = self.dispatch.send(req);
 
         async move {
             match sent {
-                Ok(rx) => match rx.await {
-                    Ok(Ok(resp)) => Ok(resp),
-                    Ok(Err(err)) => Err(err),
+                Ok(rx) => match dispatch::until_deadline(rx, deadline).await {
+                    Some(Ok(Ok(resp))) => Ok(resp),
+                    Some(Ok(Err(err))) => Err(err),
                     // this is definite bug if it happens, but it shouldn't happen!
-                    Err(_canceled) => panic!("dispatch dropped without returning error"),
+                    Some(Err(_canceled)) => panic!("dispatch dropped without returning error"),
+                    None => {
+                        debug!("request deadline elapsed");
+                        Err(crate::Error::new_request_timeout())
+                    }
                 },
                 Err(_req) => {
                     debug!("connection was not ready");
@@ -198,33 +340,90 @@ where
         }
     }
 
-    /*
-    pub(super) fn send_request_retryable(
+    /// Sends a `Request` on the associated connection, giving it back if
+    /// the connection can't send it.
+    ///
+    /// This is like `send_request`, except that if the connection is not
+    /// ready, or fails before the request is written, the error returned
+    /// holds the request, so it can be retried on another connection.
+    ///
+    /// See [`TrySendError`] for when the request can be recovered.
+    pub fn try_send_request(
         &mut self,
         req: Request<B>,
-    ) -> impl Future<Output = Result<Response<Body>, (crate::Error, Option<Request<B>>)>> + Unpin
-    where
-        B: Send,
-    {
-        match self.dispatch.try_send(req) {
-            Ok(rx) => {
-                Either::Left(rx.then(move |res| {
-                    match res {
-                        Ok(Ok(res)) => future::ok(res),
-                        Ok(Err(err)) => future::err(err),
-                        // this is definite bug if it happens, but it shouldn't happen!
-                        Err(_) => panic!("dispatch dropped without returning error"),
+    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> {
+        let deadline = self.deadline(&req);
+        let sent = self.dispatch.try_send(req);
+
+        async move {
+            match sent {
+                Ok(rx) => match dispatch::until_deadline(rx, deadline).await {
+                    Some(Ok(Ok(res))) => Ok(res),
+                    Some(Ok(Err((error, message)))) => Err(TrySendError { error, message }),
+                    // this is definite bug if it happens, but it shouldn't happen!
+                    Some(Err(_canceled)) => panic!("dispatch dropped without returning error"),
+                    None => {
+                        debug!("request deadline elapsed");
+                        Err(TrySendError {
+                            error: crate::Error::new_request_timeout(),
+                            message: None,
+                        })
                     }
-                }))
-            }
-            Err(req) => {
-                debug!("connection was not ready");
-                let err = crate::Error::new_canceled().with("connection was not ready");
-                Either::Right(future::err((err, Some(req))))
+                },
+                Err(req) => {
+                    debug!("connection was not ready");
+                    let error = crate::Error::new_canceled().with("connection was not ready");
+                    Err(TrySendError {
+                        error,
+                        message: Some(req),
+                    })
+                }
             }
         }
     }
-    */
+
+    /// Opens a tunnel to `authority` with a `CONNECT` request, such as
+    /// through a proxy.
+    ///
+    /// The request has a `Host` header for `authority`, and an empty body.
+    /// Once the server accepts it with a `2xx` response, the connection is
+    /// upgraded, and this yields the tunneled IO. The `Connection` must be
+    /// polled until then; a [`LocalConnection`] can't be upgraded, so the
+    /// tunnel fails.
+    ///
+    /// To add headers, such as `Proxy-Authorization`, use
+    /// [`send_connect`](SendRequest::send_connect) instead.
+    pub fn connect(
+        &mut self,
+        authority: Authority,
+    ) -> impl Future<Output = Result<Upgraded, TunnelError>>
+    where
+        B: Default,
+    {
+        let mut req = tunnel::request(authority.clone());
+        let host = HeaderValue::from_str(authority.as_str()).expect("authority is a valid header");
+        req.headers_mut().insert(HOST, host);
+        self.send_connect(req)
+    }
+
+    /// Opens a tunnel with a `CONNECT` request.
+    ///
+    /// This is like [`connect`](SendRequest::connect), but sends `req` as
+    /// it is, so it must have the `CONNECT` method, and its `Host` header
+    /// set. If the server refuses the tunnel, the [`TunnelError`] holds its
+    /// response.
+    pub fn send_connect(
+        &mut self,
+        req: Request<B>,
+    ) -> impl Future<Output = Result<Upgraded, TunnelError>> {
+        tunnel::upgrade(self.send_request(req))
+    }
+
+    fn deadline(&self, req: &Request<B>) -> Option<Pin<Box<dyn Sleep>>> {
+        req.extensions()
+            .get::<Deadline>()
+            .map(|deadline| deadline.sleep(&self.timer))
+    }
 }
 
 impl<B> fmt::Debug for SendRequest<B> {
@@ -249,7 +448,6 @@ impl<T, B> Future for Connection<T, B>
 where
     T: Read + Write + Unpin + Send + 'static,
     B: Body + 'static,
-    B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
     type Output = crate::Result<()>;
@@ -272,6 +470,84 @@ where
     }
 }
 
+// ===== impl LocalConnection
+
+impl<T, B> LocalConnection<T, B>
+where
+    T: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Return the inner IO object, and additional information.
+    pub fn into_parts(self) -> Parts<T> {
+        let (io, read_buf, _) = self.inner.into_inner();
+        Parts {
+            io,
+            read_buf,
+            _inner: (),
+        }
+    }
+
+    /// Poll the connection for completion, but without calling `shutdown`
+    /// on the underlying IO.
+    ///
+    /// See [`Connection::poll_without_shutdown`].
+    pub fn poll_without_shutdown(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        self.inner.poll_without_shutdown(cx)
+    }
+
+    /// Run the connection until it's done, then take it apart without
+    /// calling `shutdown` on the underlying IO.
+    ///
+    /// See [`Connection::without_shutdown`].
+    pub fn without_shutdown(self) -> impl Future<Output = crate::Result<Parts<T>>> {
+        let mut zelf = Some(self);
+        futures_util::future::poll_fn(move |cx| {
+            ready!(zelf.as_mut().unwrap().poll_without_shutdown(cx))?;
+            Poll::Ready(Ok(zelf.take().unwrap().into_parts()))
+        })
+    }
+
+    /// Returns a handle to the counts of bytes read from and written to the
+    /// IO by this connection.
+    ///
+    /// See [`Connection::byte_counts`].
+    pub fn byte_counts(&self) -> ByteCounts {
+        self.bytes.clone()
+    }
+}
+
+impl<T, B> fmt::Debug for LocalConnection<T, B>
+where
+    T: Read + Write + fmt::Debug + 'static,
+    B: Body + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalConnection").finish()
+    }
+}
+
+impl<T, B> Future for LocalConnection<T, B>
+where
+    T: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        match ready!(Pin::new(&mut self.inner).poll(cx))? {
+            proto::Dispatched::Shutdown => Poll::Ready(Ok(())),
+            proto::Dispatched::Upgrade(pending) => {
+                // Without a `Send` IO, the upgrade can't be fulfilled, so
+                // let whoever is waiting on it know why.
+                pending.manual();
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
 // ===== impl Builder
 
 impl Builder {
@@ -279,15 +555,29 @@ impl Builder {
     #[inline]
     pub fn new() -> Builder {
         Builder {
+            timer: Time::Empty,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
             h09_responses: false,
             h1_writev: None,
+            h1_write_coalesce_threshold: None,
             h1_read_buf_exact_size: None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_title_case_headers: false,
+            h1_absolute_form: false,
+            h1_expect_continue_timeout: None,
+            h1_header_read_timeout: None,
+            h1_pipeline_depth: 1,
+            h1_response_timings: false,
+            h1_excess_body: ExcessBody::Reject,
+            h1_malformed_reason_phrases: None,
             h1_preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             h1_preserve_header_order: false,
             h1_max_buf_size: None,
+            h1_buffer_pool: BufPool::default(),
+            h1_poll_budget: proto::h1::dispatch::DEFAULT_POLL_BUDGET,
+            h1_write_timeout: None,
         }
     }
 
@@ -360,6 +650,31 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will accept multiple spaces between the
+    /// parts of the status line of responses.
+    ///
+    /// [RFC 9112 Section 4] allows this:
+    ///
+    /// > Although the status-line grammar rule requires that each of the
+    /// > component elements be separated by a single SP octet, recipients MAY
+    /// > instead parse on whitespace-delimited word boundaries and, aside from
+    /// > the line terminator, treat any form of whitespace as the SP separator
+    /// > while ignoring preceding or trailing whitespace.
+    ///
+    /// Only spaces are accepted, not the other forms of whitespace.
+    ///
+    /// Default is false.
+    ///
+    /// [RFC 9112 Section 4]: https://www.rfc-editor.org/rfc/rfc9112#section-4
+    pub fn allow_multiple_spaces_in_response_status_delimiters(
+        &mut self,
+        enabled: bool,
+    ) -> &mut Builder {
+        self.h1_parser_config
+            .allow_multiple_spaces_in_response_status_delimiters(enabled);
+        self
+    }
+
     /// Set whether HTTP/1 connections will silently ignored malformed header lines.
     ///
     /// If this is enabled and and a header line does not start with a valid header
@@ -373,6 +688,97 @@ impl Builder {
         self
     }
 
+    /// Set whether HTTP/1 connections will accept status lines with a
+    /// malformed reason phrase.
+    ///
+    /// If enabled, status lines without a reason phrase, like
+    /// `HTTP/1.1 200`, are accepted, and so are control characters in the
+    /// reason phrase, other than CR. If disabled, both are rejected. The
+    /// reason phrase is given as received in the
+    /// [`ReasonPhrase`](crate::ext::ReasonPhrase) extension of the response,
+    /// unless it's the canonical one.
+    ///
+    /// Default is to accept status lines without a reason phrase, but not
+    /// control characters in it.
+    pub fn allow_malformed_reason_phrases_in_responses(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_malformed_reason_phrases = Some(enabled);
+        self
+    }
+
+    /// Parse responses strictly, rejecting anything that doesn't follow the
+    /// grammar of [RFC 9112].
+    ///
+    /// This turns off all of `allow_spaces_after_header_name_in_responses`,
+    /// `allow_obsolete_multiline_headers_in_responses`,
+    /// `allow_multiple_spaces_in_response_status_delimiters`, and
+    /// `ignore_invalid_headers_in_responses`. It is the default.
+    ///
+    /// Like the other presets, it doesn't change
+    /// [`http09_responses`](Builder::http09_responses), and the individual
+    /// options can still be changed after it.
+    ///
+    /// [RFC 9112]: https://www.rfc-editor.org/rfc/rfc9112
+    pub fn strict(&mut self) -> &mut Builder {
+        self.parser_leniency(false, false, false, false)
+    }
+
+    /// Parse responses the way [RFC 9112] asks of a user agent.
+    ///
+    /// Obsolete line folding in header values is accepted, and replaced
+    /// with spaces, as user agents are required to, and multiple spaces are
+    /// accepted between the parts of the status line, as recipients are
+    /// allowed to. Anything else that doesn't follow the grammar is still
+    /// rejected.
+    ///
+    /// [RFC 9112]: https://www.rfc-editor.org/rfc/rfc9112
+    pub fn rfc(&mut self) -> &mut Builder {
+        self.parser_leniency(true, false, true, false)
+    }
+
+    /// Parse responses as leniently as possible, the way browsers do.
+    ///
+    /// On top of what [`rfc`](Builder::rfc) accepts, spaces between header
+    /// names and their colons are allowed, and malformed header lines are
+    /// silently ignored. This is meant for clients that have to talk to
+    /// arbitrary, possibly broken, servers, and shouldn't be used by a proxy
+    /// that forwards responses on.
+    pub fn lenient(&mut self) -> &mut Builder {
+        self.parser_leniency(true, true, true, true)
+    }
+
+    fn parser_leniency(
+        &mut self,
+        obsolete_multiline_headers: bool,
+        spaces_after_header_name: bool,
+        multiple_spaces_in_status: bool,
+        ignore_invalid_headers: bool,
+    ) -> &mut Builder {
+        self.h1_parser_config
+            .allow_obsolete_multiline_headers_in_responses(obsolete_multiline_headers)
+            .allow_spaces_after_header_name_in_responses(spaces_after_header_name)
+            .allow_multiple_spaces_in_response_status_delimiters(multiple_spaces_in_status)
+            .ignore_invalid_headers_in_responses(ignore_invalid_headers);
+        self
+    }
+
+    /// Set the maximum number of headers.
+    ///
+    /// When a response is received, the parser will reserve a buffer to store
+    /// headers for optimal performance.
+    ///
+    /// If client receives more headers than the buffer size, the error
+    /// "message head is too large" is returned.
+    ///
+    /// Up to 100 headers are parsed on the stack. A larger limit allocates
+    /// the buffer on the heap for each response parsed, which has some
+    /// overhead.
+    ///
+    /// Default is 100.
+    pub fn max_headers(&mut self, val: usize) -> &mut Builder {
+        self.h1_max_headers = Some(val);
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -390,6 +796,25 @@ impl Builder {
         self
     }
 
+    /// Set the size at or below which body frames are copied into the
+    /// connection's write buffer, instead of being queued to be written on
+    /// their own.
+    ///
+    /// Copied frames go out in the same write as the message head and each
+    /// other. That is cheaper for transports where every write has a fixed
+    /// cost, such as TLS, which seals each write into at least one record,
+    /// and may be worth a much larger threshold there than for plain TCP.
+    /// Frames are only copied while nothing is queued ahead of them.
+    ///
+    /// This only applies when vectored writes are used, see `writev`, since
+    /// every frame is copied otherwise.
+    ///
+    /// Default is 0, so frames are never copied.
+    pub fn write_coalesce_threshold(&mut self, len: usize) -> &mut Builder {
+        self.h1_write_coalesce_threshold = Some(len);
+        self
+    }
+
     /// Set whether HTTP/1 connections will write header names as title case at
     /// the socket level.
     ///
@@ -399,6 +824,21 @@ impl Builder {
         self
     }
 
+    /// Set whether requests are written with absolute-form targets, such as
+    /// `GET http://example.com/path HTTP/1.1`, as a forward proxy expects.
+    ///
+    /// Requests with an absolute `Uri` are always written in absolute-form.
+    /// With this enabled, requests with an origin-form `Uri` are too, using
+    /// the `http` scheme and their `Host` header as the authority. Sending
+    /// one without a `Host` header fails the request, and the connection.
+    /// `CONNECT` and `OPTIONS *` requests keep their target.
+    ///
+    /// Default is false.
+    pub fn absolute_form(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_absolute_form = enabled;
+        self
+    }
+
     /// Set whether to support preserving original header cases.
     ///
     /// Currently, this will record the original cases received, and store them
@@ -418,11 +858,10 @@ impl Builder {
     /// Set whether to support preserving original header order.
     ///
     /// Currently, this will record the order in which headers are received, and store this
-    /// ordering in a private extension on the `Response`. It will also look for and use
-    /// such an extension in any provided `Request`.
+    /// ordering in an [`OriginalHeaderOrder`](crate::ext::OriginalHeaderOrder) extension
+    /// on the `Response`.
     ///
     /// Default is false.
-    #[cfg(feature = "ffi")]
     pub fn preserve_header_order(&mut self, enabled: bool) -> &mut Builder {
         self.h1_preserve_header_order = enabled;
         self
@@ -441,7 +880,7 @@ impl Builder {
 
     /// Set the maximum buffer size for the connection.
     ///
-    /// Default is ~400kb.
+    /// Default is ~400kb, or 16kb with the `embedded` feature.
     ///
     /// Note that setting this option unsets the `read_exact_buf_size` option.
     ///
@@ -459,6 +898,174 @@ impl Builder {
         self
     }
 
+    /// Set a pool to take the buffers connections read into from.
+    ///
+    /// Connections then never allocate a read buffer themselves, and a
+    /// response head must fit in one of the pool's buffers. See
+    /// [`BufferPool`] for how the buffers are used.
+    ///
+    /// Default is for each connection to allocate its own.
+    pub fn buffer_pool<P>(&mut self, pool: P) -> &mut Builder
+    where
+        P: BufferPool + Send + Sync + 'static,
+    {
+        self.h1_buffer_pool = BufPool::new(pool);
+        self
+    }
+
+    /// Set how long to wait for a `100 Continue` before sending the body of
+    /// a request with an `Expect: 100-continue` header.
+    ///
+    /// The request head is sent right away, and its body is held back until
+    /// the server responds with `100 Continue`, or the timeout elapses. If
+    /// the server sends a final response first, such as `417 Expectation
+    /// Failed` or `401 Unauthorized`, the body is never sent, and the
+    /// connection is closed once the response has been read. Requires a
+    /// [`timer`](Builder::timer).
+    ///
+    /// Default is None, to send the body without waiting.
+    pub fn expect_continue_timeout(&mut self, timeout: Duration) -> &mut Builder {
+        self.h1_expect_continue_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for reading the head of a response.
+    ///
+    /// The timeout starts once a request, including its body, has been
+    /// written. If the server doesn't send the whole head of the response
+    /// within this time, the connection is closed, and the request fails
+    /// with an error for which [`Error::is_timeout`](crate::Error::is_timeout)
+    /// returns true. Informational responses, like `100 Continue`, don't
+    /// reset it. Requires a [`timer`](Builder::timer).
+    ///
+    /// Default is None.
+    pub fn header_read_timeout(&mut self, read_timeout: Duration) -> &mut Builder {
+        self.h1_header_read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Set how many requests may be sent on the connection before the
+    /// response to the first has been read.
+    ///
+    /// With a depth over 1, the connection pipelines requests: once a
+    /// request has been written, the next one is written right away,
+    /// instead of after its response, so [`SendRequest`] is ready again
+    /// before any responses come back. The responses are still read in
+    /// order.
+    ///
+    /// A request is only pipelined once the server is known to keep the
+    /// connection alive: never after an HTTP/1.0 response, or one with
+    /// `Connection: close`. If the connection closes, or the server asks
+    /// for it to be closed, before every pipelined request was answered,
+    /// those requests fail with an error for which
+    /// [`Error::is_incomplete_message`](crate::Error::is_incomplete_message)
+    /// returns true. They were sent, so retrying them is only safe if they
+    /// are idempotent. Requests that upgrade the connection, like `CONNECT`,
+    /// shouldn't be pipelined.
+    ///
+    /// Default is 1, to not pipeline.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the passed `depth` is 0.
+    pub fn pipeline_depth(&mut self, depth: usize) -> &mut Builder {
+        assert!(depth > 0, "the pipeline_depth must be greater than 0");
+        self.h1_pipeline_depth = depth;
+        self
+    }
+
+    /// Set whether to record when each request was written and its response
+    /// received.
+    ///
+    /// If enabled, each response has a [`ResponseTimings`] extension, with
+    /// when the request head was flushed, the first byte of the response
+    /// read, and its head parsed. They are taken from the
+    /// [`timer`](Builder::timer) if one is set, or else the system clock.
+    ///
+    /// Default is false.
+    ///
+    /// [`ResponseTimings`]: crate::ext::ResponseTimings
+    pub fn response_timings(&mut self, enabled: bool) -> &mut Builder {
+        self.h1_response_timings = enabled;
+        self
+    }
+
+    /// Set what to do with bytes received after the end of a response body,
+    /// that no request is waiting for.
+    ///
+    /// By default, the connection fails with an error once the response was
+    /// read, and can't be used anymore. Bytes belonging to the responses to
+    /// pipelined requests aren't excess.
+    ///
+    /// Default is [`ExcessBody::Reject`].
+    pub fn excess_body(&mut self, excess: ExcessBody) -> &mut Builder {
+        self.h1_excess_body = excess;
+        self
+    }
+
+    /// Set a timeout for flushing writes to the connection.
+    ///
+    /// If a flush stays pending for longer than this, such as when the peer
+    /// stopped reading and the TCP window is full, the connection is closed
+    /// with a timeout error. Requires a [`timer`](Builder::timer),
+    /// and is ignored without one.
+    ///
+    /// Default is None.
+    pub fn write_timeout(&mut self, write_timeout: Duration) -> &mut Builder {
+        self.h1_write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// Provide a timer to be used for timeouts.
+    pub fn timer<M>(&mut self, timer: M) -> &mut Builder
+    where
+        M: Timer + Send + Sync + 'static,
+    {
+        self.timer = Time::Timer(Arc::new(timer));
+        self
+    }
+
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Builder
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Builder
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
+    /// Set the maximum number of read/write iterations a connection makes
+    /// each time it is polled, before yielding back to the executor.
+    ///
+    /// A connection that is always ready, such as one receiving many
+    /// pipelined requests, could otherwise starve other tasks on the same
+    /// executor. Lower values favor fairness, higher values favor throughput.
+    ///
+    /// Default is 16.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the passed `budget` is 0.
+    pub fn poll_budget(&mut self, budget: usize) -> &mut Builder {
+        assert!(budget > 0, "the poll_budget must be greater than 0");
+        self.h1_poll_budget = budget;
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -471,7 +1078,6 @@ impl Builder {
     where
         T: Read + Write + Unpin + Send + 'static,
         B: Body + 'static,
-        B::Data: Send,
         B::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
         let opts = self.clone();
@@ -479,44 +1085,176 @@ impl Builder {
         async move {
             trace!("client handshake HTTP/1");
 
-            let (tx, rx) = dispatch::channel();
-            let mut conn = proto::Conn::new(io);
-            conn.set_h1_parser_config(opts.h1_parser_config);
-            if let Some(writev) = opts.h1_writev {
-                if writev {
-                    conn.set_write_strategy_queue();
-                } else {
-                    conn.set_write_strategy_flatten();
-                }
-            }
-            if opts.h1_title_case_headers {
-                conn.set_title_case_headers();
-            }
-            if opts.h1_preserve_header_case {
-                conn.set_preserve_header_case();
-            }
-            #[cfg(feature = "ffi")]
-            if opts.h1_preserve_header_order {
-                conn.set_preserve_header_order();
-            }
+            let (tx, proto, bytes) = opts.dispatcher(io, Bytes::new());
+            Ok((
+                tx,
+                Connection {
+                    inner: Some(proto),
+                    bytes,
+                },
+            ))
+        }
+    }
 
-            if opts.h09_responses {
-                conn.set_h09_responses();
-            }
+    /// Constructs a connection with the configured options and IO, where
+    /// some bytes have already been read from the IO.
+    ///
+    /// The bytes in `read_buf` are read before anything else, as the start
+    /// of the first response, like the `read_buf` of
+    /// [`upgrade::Parts`](crate::upgrade::Parts). This is useful if the IO
+    /// was peeked at before the handshake, for protocol detection.
+    ///
+    /// Note, if [`Connection`] is not `await`-ed, [`SendRequest`] will
+    /// do nothing.
+    pub fn handshake_with_read_buf<T, B>(
+        &self,
+        io: T,
+        read_buf: Bytes,
+    ) -> impl Future<Output = crate::Result<(SendRequest<B>, Connection<T, B>)>>
+    where
+        T: Read + Write + Unpin + Send + 'static,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let opts = self.clone();
 
-            if let Some(sz) = opts.h1_read_buf_exact_size {
-                conn.set_read_buf_exact_size(sz);
-            }
-            if let Some(max) = opts.h1_max_buf_size {
-                conn.set_max_buf_size(max);
-            }
-            let cd = proto::h1::dispatch::Client::new(rx);
-            let proto = proto::h1::Dispatcher::new(cd, conn);
+        async move {
+            trace!("client handshake HTTP/1 ({} bytes read)", read_buf.len());
 
+            let (tx, proto, bytes) = opts.dispatcher(io, read_buf);
             Ok((
-                SendRequest { dispatch: tx },
-                Connection { inner: Some(proto) },
+                tx,
+                Connection {
+                    inner: Some(proto),
+                    bytes,
+                },
             ))
         }
     }
+
+    /// Constructs a connection that doesn't support upgrades, with the
+    /// configured options and IO.
+    ///
+    /// Unlike with [`handshake`](Builder::handshake), the IO doesn't need to
+    /// be `Send`, and neither does the returned [`LocalConnection`], so it
+    /// can be polled on a single-threaded executor, such as on
+    /// `wasm32` targets. [`SendRequest`] and response bodies are only `Send`
+    /// if the request body is.
+    ///
+    /// Note, if [`LocalConnection`] is not `await`-ed, [`SendRequest`] will
+    /// do nothing.
+    pub fn handshake_local<T, B>(
+        &self,
+        io: T,
+    ) -> impl Future<Output = crate::Result<(SendRequest<B>, LocalConnection<T, B>)>>
+    where
+        T: Read + Write + Unpin + 'static,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let opts = self.clone();
+
+        async move {
+            trace!("client handshake HTTP/1 (local)");
+
+            let (tx, proto, bytes) = opts.dispatcher(io, Bytes::new());
+            Ok((
+                tx,
+                LocalConnection {
+                    inner: proto,
+                    bytes,
+                },
+            ))
+        }
+    }
+
+    fn dispatcher<T, B>(
+        self,
+        io: T,
+        read_buf: Bytes,
+    ) -> (SendRequest<B>, Dispatcher<T, B>, ByteCounts)
+    where
+        T: Read + Write + Unpin,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let (tx, rx) = dispatch::channel();
+        let bytes = ByteCounts::new();
+        let mut conn = proto::Conn::new(io);
+        conn.set_timer(self.timer.clone());
+        conn.set_metrics(self.metrics.with_byte_counter(bytes.0.clone()));
+        conn.set_h1_parser_config(self.h1_parser_config);
+        if let Some(max) = self.h1_max_headers {
+            conn.set_h1_max_headers(max);
+        }
+        if let Some(writev) = self.h1_writev {
+            if writev {
+                conn.set_write_strategy_queue();
+            } else {
+                conn.set_write_strategy_flatten();
+            }
+        }
+        if self.h1_title_case_headers {
+            conn.set_title_case_headers();
+        }
+        if self.h1_absolute_form {
+            conn.set_absolute_form();
+        }
+        if let Some(timeout) = self.h1_expect_continue_timeout {
+            conn.set_expect_continue_timeout(timeout);
+        }
+        if let Some(timeout) = self.h1_header_read_timeout {
+            conn.set_response_header_timeout(timeout);
+        }
+        conn.set_pipeline_depth(self.h1_pipeline_depth);
+        if self.h1_response_timings {
+            conn.set_response_timings();
+        }
+        conn.set_excess_body(self.h1_excess_body);
+        if let Some(enabled) = self.h1_malformed_reason_phrases {
+            conn.set_malformed_reason_phrases(enabled);
+        }
+        if self.h1_preserve_header_case {
+            conn.set_preserve_header_case();
+        }
+        if self.h1_preserve_header_order {
+            conn.set_preserve_header_order();
+        }
+
+        if self.h09_responses {
+            conn.set_h09_responses();
+        }
+
+        if let Some(sz) = self.h1_read_buf_exact_size {
+            conn.set_read_buf_exact_size(sz);
+        }
+        if let Some(len) = self.h1_write_coalesce_threshold {
+            conn.set_write_coalesce_threshold(len);
+        }
+        if let Some(max) = self.h1_max_buf_size {
+            conn.set_max_buf_size(max);
+        }
+        conn.set_buffer_pool(self.h1_buffer_pool);
+        conn.set_read_buf(read_buf);
+        if let Some(write_timeout) = self.h1_write_timeout {
+            conn.set_write_timeout(write_timeout);
+        }
+        let opened = self.events.opened();
+        let mut cd = proto::h1::dispatch::Client::new(rx, self.metrics, opened.lifecycle().clone());
+        cd.set_pipeline_depth(self.h1_pipeline_depth);
+        let keep_alive = cd.keep_alive_handle();
+        let mut proto = proto::h1::Dispatcher::new(cd, conn);
+        proto.set_lifecycle(opened);
+        proto.set_poll_budget(self.h1_poll_budget);
+
+        (
+            SendRequest {
+                dispatch: tx,
+                keep_alive,
+                timer: self.timer,
+            },
+            proto,
+            bytes,
+        )
+    }
 }
@@ -3,29 +3,50 @@
 use std::error::Error;
 use std::fmt;
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::rt::{Read, Write};
+use http::uri::Authority;
 use http::{Request, Response};
 
 use super::super::dispatch;
+use super::{tunnel, ByteCounts, TrySendError, TunnelError};
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
 use crate::common::time::Time;
 use crate::common::{task, Future, Pin, Poll};
+use crate::ext::Deadline;
 use crate::proto;
+use crate::proto::h2::client::OnKeepAliveTimeout;
+use crate::proto::h2::ping::{Pinger, Recorder};
+use crate::proto::h2::{OpenStreams, Reservation};
 use crate::rt::bounds::ExecutorClient;
-use crate::rt::Timer;
+use crate::rt::{ConnectionEvent, Metrics, Sleep, Timer};
+use crate::upgrade::Upgraded;
 
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::UnboundedSender<Request<B>, Response<IncomingBody>>,
+    pinger: Pinger,
+    peer_settings: Arc<Mutex<PeerSettings>>,
+    // Only when the frames are recorded.
+    open_streams: Arc<OpenStreams>,
+    // The stream slot held by `poll_ready` for the next request.
+    reserved: Option<Reservation>,
+    timer: Time,
 }
 
 impl<B> Clone for SendRequest<B> {
     fn clone(&self) -> SendRequest<B> {
         SendRequest {
             dispatch: self.dispatch.clone(),
+            pinger: self.pinger.clone(),
+            peer_settings: self.peer_settings.clone(),
+            open_streams: self.open_streams.clone(),
+            reserved: None,
+            timer: self.timer.clone(),
         }
     }
 }
@@ -43,6 +64,58 @@ where
     B::Error: Into<Box<dyn Error + Send + Sync>>,
 {
     inner: (PhantomData<T>, proto::h2::ClientTask<B, E, T>),
+    bytes: ByteCounts,
+}
+
+/// The requests a server promised to push alongside a response.
+///
+/// When push is enabled with [`Builder::enable_push`], this is put in the
+/// extensions of each response received on the connection, and yields the
+/// requests the server promised to push while sending that response,
+/// together with their pushed responses.
+pub struct PushPromises {
+    inner: h2::client::PushPromises,
+    ping: Recorder,
+}
+
+/// A request pushed by the server, and the response to it.
+pub struct PushPromise {
+    request: Request<()>,
+    response: PushedResponse,
+}
+
+/// A future resolving to the response of a pushed request.
+#[must_use = "futures do nothing unless polled"]
+pub struct PushedResponse {
+    inner: h2::client::PushedResponseFuture,
+    ping: Recorder,
+}
+
+/// The `SETTINGS` parameters received from the server that `h2` exposes.
+///
+/// `h2` applies the server's other settings, such as
+/// `SETTINGS_MAX_HEADER_LIST_SIZE`, without reporting them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PeerSettings {
+    pub(crate) max_concurrent_streams: Option<u32>,
+}
+
+/// A `GOAWAY` frame received from the server.
+///
+/// Get it from an error of the connection, or of one of its requests, with
+/// [`Error::h2_go_away`](crate::Error::h2_go_away).
+#[derive(Clone, Copy, Debug)]
+pub struct GoAway {
+    pub(crate) error_code: u32,
+}
+
+/// A keep-alive ping that timed out, passed to the callback set with
+/// [`Builder::on_keep_alive_timeout`].
+#[derive(Clone, Debug)]
+pub struct KeepAliveTimeout {
+    pub(crate) recent_rtts: Vec<Duration>,
+    pub(crate) interval: Duration,
+    pub(crate) timeout: Duration,
 }
 
 /// A builder to configure an HTTP connection.
@@ -52,6 +125,8 @@ where
 pub struct Builder<Ex> {
     pub(super) exec: Ex,
     pub(super) timer: Time,
+    metrics: MetricsSink,
+    events: EventSink,
     h2_builder: proto::h2::client::Config,
 }
 
@@ -66,7 +141,6 @@ pub async fn handshake<E, T, B>(
 where
     T: Read + Write + Unpin + 'static,
     B: Body + 'static,
-    B::Data: Send,
     B::Error: Into<Box<dyn Error + Send + Sync>>,
     E: ExecutorClient<B, T> + Unpin + Clone,
 {
@@ -78,16 +152,34 @@ where
 impl<B> SendRequest<B> {
     /// Polls to determine whether this sender can be used yet for a request.
     ///
-    /// If the associated connection is closed, this returns an Error.
-    pub fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+    /// Once ready, a stream is reserved for the next request sent with this
+    /// `SendRequest`. This is pending while the
+    /// [`open_streams`](SendRequest::open_streams) and those reserved on the
+    /// connection are at the server's
+    /// [`max_concurrent_streams`](SendRequest::max_concurrent_streams), so
+    /// callers can hold off, or pick another connection. The reservation
+    /// isn't shared with clones, and is released if this is dropped.
+    ///
+    /// If the associated connection is closed, or shutting down, this
+    /// returns an Error.
+    pub fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         if self.is_closed() {
-            Poll::Ready(Err(crate::Error::new_closed()))
-        } else {
-            Poll::Ready(Ok(()))
+            return Poll::Ready(Err(crate::Error::new_closed()));
         }
+        if self.reserved.is_none() {
+            let limit = self.peer_settings.lock().unwrap().max_concurrent_streams;
+            match ready!(self.open_streams.poll_reserve(cx, limit)) {
+                Ok(reservation) => self.reserved = Some(reservation),
+                Err(()) => return Poll::Ready(Err(crate::Error::new_closed())),
+            }
+        }
+        Poll::Ready(Ok(()))
     }
 
-    /// Waits until the dispatcher is ready
+    /// Waits until the dispatcher is ready, reserving a stream for the next
+    /// request.
+    ///
+    /// See [`poll_ready`](SendRequest::poll_ready) for the details.
     ///
     /// If the associated connection is closed, this returns an Error.
     pub async fn ready(&mut self) -> crate::Result<()> {
@@ -109,6 +201,67 @@ impl<B> SendRequest<B> {
     pub fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
+
+    /// Waits until the connection side has been closed.
+    ///
+    /// This resolves once the connection can't send any more requests,
+    /// such as after the server closes it, or the `Connection` future
+    /// finishes or is dropped. The returned future doesn't borrow the
+    /// `SendRequest`, or keep the connection open, so a connection pool can
+    /// wait on it in the background to evict the connection promptly.
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.dispatch.closed()
+    }
+
+    /// Sends a PING frame, and waits for the server to acknowledge it.
+    ///
+    /// Resolves to the round-trip time, such as for liveness checks or
+    /// latency measurements. Only one PING can be in flight at a time, so
+    /// if one already is, including those sent for
+    /// [`keep_alive_interval`](Builder::keep_alive_interval) or
+    /// [`adaptive_window`](Builder::adaptive_window), this waits for its
+    /// acknowledgement instead, and resolves to its round-trip time.
+    ///
+    /// The connection must be polled for the PING to be sent and its
+    /// acknowledgement read. If the connection closes first, this resolves
+    /// to an error.
+    pub fn ping(&self) -> impl Future<Output = crate::Result<Duration>> + Send + 'static {
+        self.pinger.ping()
+    }
+
+    /// Returns the settings most recently received from the server.
+    ///
+    /// Each `SETTINGS` frame only changes the parameters it includes, so
+    /// this reflects all of them received so far. The connection must be
+    /// polled for them to be read.
+    ///
+    /// Whether the extended CONNECT protocol is enabled is given by
+    /// [`Connection::is_extended_connect_protocol_enabled`] instead.
+    pub fn peer_settings(&self) -> PeerSettings {
+        *self.peer_settings.lock().unwrap()
+    }
+
+    /// Returns the number of streams open for requests sent on this
+    /// connection.
+    ///
+    /// A stream is counted from when the connection takes its request, even
+    /// if it must wait for the server to allow more concurrent streams,
+    /// until both its request body has been sent and its response head
+    /// received, or the stream is reset. A CONNECT tunnel is counted until
+    /// its upgraded IO is dropped. Streams still receiving a response body
+    /// aren't counted, so this may be lower than the streams open in `h2`.
+    pub fn open_streams(&self) -> usize {
+        self.open_streams.count()
+    }
+
+    /// Returns the most streams the server allows to be open at once, if it
+    /// has set a limit.
+    ///
+    /// This is its `SETTINGS_MAX_CONCURRENT_STREAMS` parameter, as in
+    /// [`peer_settings`](SendRequest::peer_settings).
+    pub fn max_concurrent_streams(&self) -> Option<u32> {
+        self.peer_settings().max_concurrent_streams()
+    }
 }
 
 impl<B> SendRequest<B>
@@ -131,19 +284,42 @@ where
     ///   before calling this method.
     /// - Since absolute-form `Uri`s are not required, if received, they will
     ///   be serialized as-is.
+    ///
+    /// # Deadline
+    ///
+    /// If the request has a [`Deadline`](crate::ext::Deadline) extension,
+    /// and it passes before the response head is received, the stream is
+    /// reset and a timeout error is returned.
+    ///
+    /// # Extended CONNECT
+    ///
+    /// A `CONNECT` request with a [`Protocol`](crate::ext::Protocol)
+    /// extension is sent as an [extended CONNECT][rfc8441] request, such as
+    /// to open a WebSocket. If the server accepts it with a `200 OK`, the
+    /// stream can be taken with [`upgrade::on`](crate::upgrade::on) on the
+    /// response. Servers advertise support for it in their settings, see
+    /// [`Connection::is_extended_connect_protocol_enabled`].
+    ///
+    /// [rfc8441]: https://datatracker.ietf.org/doc/html/rfc8441
     pub fn send_request(
         &mut self,
         req: Request<B>,
     ) -> impl Future<Output = crate::Result<Response<IncomingBody>>> {
+        let deadline = self.deadline(&req);
+        let req = self.with_reservation(req);
         let sent = self.dispatch.send(req);
 
         async move {
             match sent {
-                Ok(rx) => match rx.await {
-                    Ok(Ok(resp)) => Ok(resp),
-                    Ok(Err(err)) => Err(err),
+                Ok(rx) => match dispatch::until_deadline(rx, deadline).await {
+                    Some(Ok(Ok(resp))) => Ok(resp),
+                    Some(Ok(Err(err))) => Err(err),
                     // this is definite bug if it happens, but it shouldn't happen!
-                    Err(_canceled) => panic!("dispatch dropped without returning error"),
+                    Some(Err(_canceled)) => panic!("dispatch dropped without returning error"),
+                    None => {
+                        debug!("request deadline elapsed");
+                        Err(crate::Error::new_request_timeout())
+                    }
                 },
                 Err(_req) => {
                     debug!("connection was not ready");
@@ -154,33 +330,97 @@ where
         }
     }
 
-    /*
-    pub(super) fn send_request_retryable(
+    /// Sends a `Request` on the associated connection, giving it back if
+    /// the connection can't send it.
+    ///
+    /// This is like `send_request`, except that if the connection is not
+    /// ready, or fails before the request is written, the error returned
+    /// holds the request, so it can be retried on another connection.
+    ///
+    /// See [`TrySendError`] for when the request can be recovered.
+    pub fn try_send_request(
         &mut self,
         req: Request<B>,
-    ) -> impl Future<Output = Result<Response<Body>, (crate::Error, Option<Request<B>>)>> + Unpin
-    where
-        B: Send,
-    {
-        match self.dispatch.try_send(req) {
-            Ok(rx) => {
-                Either::Left(rx.then(move |res| {
-                    match res {
-                        Ok(Ok(res)) => future::ok(res),
-                        Ok(Err(err)) => future::err(err),
-                        // this is definite bug if it happens, but it shouldn't happen!
-                        Err(_) => panic!("dispatch dropped without returning error"),
+    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> {
+        let deadline = self.deadline(&req);
+        let req = self.with_reservation(req);
+        let sent = self.dispatch.try_send(req);
+
+        async move {
+            match sent {
+                Ok(rx) => match dispatch::until_deadline(rx, deadline).await {
+                    Some(Ok(Ok(res))) => Ok(res),
+                    Some(Ok(Err((error, message)))) => Err(TrySendError {
+                        error,
+                        message: message.map(without_reservation),
+                    }),
+                    // this is definite bug if it happens, but it shouldn't happen!
+                    Some(Err(_canceled)) => panic!("dispatch dropped without returning error"),
+                    None => {
+                        debug!("request deadline elapsed");
+                        Err(TrySendError {
+                            error: crate::Error::new_request_timeout(),
+                            message: None,
+                        })
                     }
-                }))
-            }
-            Err(req) => {
-                debug!("connection was not ready");
-                let err = crate::Error::new_canceled().with("connection was not ready");
-                Either::Right(future::err((err, Some(req))))
+                },
+                Err(req) => {
+                    debug!("connection was not ready");
+                    let error = crate::Error::new_canceled().with("connection was not ready");
+                    Err(TrySendError {
+                        error,
+                        message: Some(without_reservation(req)),
+                    })
+                }
             }
         }
     }
-    */
+
+    // Hands the reservation from `poll_ready`, if any, over to the request,
+    // to be released once its stream is open.
+    fn with_reservation(&mut self, mut req: Request<B>) -> Request<B> {
+        if let Some(reservation) = self.reserved.take() {
+            req.extensions_mut().insert(reservation);
+        }
+        req
+    }
+
+    /// Opens a tunnel to `authority` with a `CONNECT` request, such as
+    /// through a proxy.
+    ///
+    /// The request has an empty body. Once the server accepts it with a
+    /// `200 OK`, this yields the stream as the tunneled IO. Other requests
+    /// can still be sent on the connection.
+    ///
+    /// To add headers, such as `Proxy-Authorization`, use
+    /// [`send_connect`](SendRequest::send_connect) instead.
+    pub fn connect(
+        &mut self,
+        authority: Authority,
+    ) -> impl Future<Output = Result<Upgraded, TunnelError>>
+    where
+        B: Default,
+    {
+        self.send_connect(tunnel::request(authority))
+    }
+
+    /// Opens a tunnel with a `CONNECT` request.
+    ///
+    /// This is like [`connect`](SendRequest::connect), but sends `req` as
+    /// it is, so it must have the `CONNECT` method. If the server refuses
+    /// the tunnel, the [`TunnelError`] holds its response.
+    pub fn send_connect(
+        &mut self,
+        req: Request<B>,
+    ) -> impl Future<Output = Result<Upgraded, TunnelError>> {
+        tunnel::upgrade(self.send_request(req))
+    }
+
+    fn deadline(&self, req: &Request<B>) -> Option<Pin<Box<dyn Sleep>>> {
+        req.extensions()
+            .get::<Deadline>()
+            .map(|deadline| deadline.sleep(&self.timer))
+    }
 }
 
 impl<B> fmt::Debug for SendRequest<B> {
@@ -189,13 +429,18 @@ impl<B> fmt::Debug for SendRequest<B> {
     }
 }
 
+// A request given back must not keep holding a slot on this connection.
+fn without_reservation<B>(mut req: Request<B>) -> Request<B> {
+    req.extensions_mut().remove::<Reservation>();
+    req
+}
+
 // ===== impl Connection
 
 impl<T, B, E> Connection<T, B, E>
 where
     T: Read + Write + Unpin + 'static,
     B: Body + Unpin + 'static,
-    B::Data: Send,
     B::Error: Into<Box<dyn Error + Send + Sync>>,
     E: ExecutorClient<B, T> + Unpin,
 {
@@ -211,6 +456,33 @@ where
     pub fn is_extended_connect_protocol_enabled(&self) -> bool {
         self.inner.1.is_extended_connect_protocol_enabled()
     }
+
+    /// Returns a handle to the counts of bytes read from and written to the
+    /// IO by this connection.
+    ///
+    /// The handle keeps being updated after the connection is spawned, so
+    /// it can be read once the connection is done. The counts start with
+    /// the bytes of the HTTP/2 handshake.
+    pub fn byte_counts(&self) -> ByteCounts {
+        self.bytes.clone()
+    }
+
+    /// Start a graceful shutdown process for this connection.
+    ///
+    /// Requests already sent on the connection keep going, but no new ones
+    /// are accepted, so [`SendRequest`] reports that it is closed. Once the
+    /// streams are done, a `GOAWAY` is sent, the connection is closed, and
+    /// this `Connection` resolves, so it should continue to be polled
+    /// until then.
+    ///
+    /// # Note
+    ///
+    /// This should only be called while the `Connection` future is still
+    /// pending. If called after `Connection::poll` has resolved, this does
+    /// nothing.
+    pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+        self.inner.1.graceful_shutdown();
+    }
 }
 
 impl<T, B, E> fmt::Debug for Connection<T, B, E>
@@ -229,10 +501,9 @@ impl<T, B, E> Future for Connection<T, B, E>
 where
     T: Read + Write + Unpin + 'static,
     B: Body + 'static + Unpin,
-    B::Data: Send,
     E: Unpin,
     B::Error: Into<Box<dyn Error + Send + Sync>>,
-    E: ExecutorClient<B, T> + 'static + Send + Sync + Unpin,
+    E: ExecutorClient<B, T> + 'static + Unpin,
 {
     type Output = crate::Result<()>;
 
@@ -245,6 +516,145 @@ where
     }
 }
 
+// ===== impl PeerSettings
+
+impl PeerSettings {
+    /// The `SETTINGS_MAX_CONCURRENT_STREAMS` parameter, the number of
+    /// requests that may be in flight at once.
+    ///
+    /// `None` means there is no limit.
+    pub fn max_concurrent_streams(&self) -> Option<u32> {
+        self.max_concurrent_streams
+    }
+}
+
+// ===== impl GoAway
+
+impl GoAway {
+    /// The HTTP/2 error code of why the connection is closing.
+    ///
+    /// `0`, or `NO_ERROR`, for a graceful shutdown.
+    pub fn error_code(&self) -> u32 {
+        self.error_code
+    }
+}
+
+// ===== impl KeepAliveTimeout
+
+impl KeepAliveTimeout {
+    /// The round-trip times of the last few pings acknowledged on the
+    /// connection, oldest first.
+    ///
+    /// These include pings sent for keep-alive, for
+    /// [`adaptive_window`](Builder::adaptive_window), and with
+    /// [`SendRequest::ping`]. It is empty if none were acknowledged.
+    pub fn recent_rtts(&self) -> &[Duration] {
+        &self.recent_rtts
+    }
+
+    /// The [`keep_alive_interval`](Builder::keep_alive_interval) of the
+    /// connection.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The [`keep_alive_timeout`](Builder::keep_alive_timeout) that passed
+    /// without the ping being acknowledged.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+// ===== impl PushPromises
+
+impl PushPromises {
+    pub(crate) fn new(inner: h2::client::PushPromises, ping: Recorder) -> PushPromises {
+        PushPromises { inner, ping }
+    }
+
+    /// Polls for the next request pushed by the server.
+    ///
+    /// Resolves to `None` once the response's stream is done, and no more
+    /// requests can be pushed on it.
+    pub fn poll_push_promise(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<crate::Result<PushPromise>>> {
+        match ready!(self.inner.poll_push_promise(cx)) {
+            Some(Ok(promise)) => {
+                self.ping.record_non_data();
+                let (request, response) = promise.into_parts();
+                Poll::Ready(Some(Ok(PushPromise {
+                    request,
+                    response: PushedResponse {
+                        inner: response,
+                        ping: self.ping.clone(),
+                    },
+                })))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(crate::Error::new_h2(err)))),
+            None => Poll::Ready(None),
+        }
+    }
+
+    /// Waits for the next request pushed by the server.
+    ///
+    /// Resolves to `None` once the response's stream is done, and no more
+    /// requests can be pushed on it.
+    pub async fn push_promise(&mut self) -> Option<crate::Result<PushPromise>> {
+        futures_util::future::poll_fn(|cx| self.poll_push_promise(cx)).await
+    }
+}
+
+impl fmt::Debug for PushPromises {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushPromises").finish()
+    }
+}
+
+// ===== impl PushPromise
+
+impl PushPromise {
+    /// Returns a reference to the pushed request.
+    pub fn request(&self) -> &Request<()> {
+        &self.request
+    }
+
+    /// Consumes the push promise, returning the pushed request and the
+    /// future of its response.
+    pub fn into_parts(self) -> (Request<()>, PushedResponse) {
+        (self.request, self.response)
+    }
+}
+
+impl fmt::Debug for PushPromise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushPromise")
+            .field("request", &self.request)
+            .finish()
+    }
+}
+
+// ===== impl PushedResponse
+
+impl Future for PushedResponse {
+    type Output = crate::Result<Response<IncomingBody>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let res = ready!(Pin::new(&mut self.inner).poll(cx)).map_err(crate::Error::new_h2)?;
+        Poll::Ready(Ok(proto::h2::client::pushed_response(
+            res,
+            self.ping.clone(),
+        )))
+    }
+}
+
+impl fmt::Debug for PushedResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushedResponse").finish()
+    }
+}
+
 // ===== impl Builder
 
 impl<Ex> Builder<Ex>
@@ -257,6 +667,8 @@ where
         Builder {
             exec,
             timer: Time::Empty,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
             h2_builder: Default::default(),
         }
     }
@@ -270,6 +682,29 @@ where
         self
     }
 
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Builder<Ex>
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Builder<Ex>
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
     /// Sets the [`SETTINGS_INITIAL_WINDOW_SIZE`][spec] option for HTTP2
     /// stream-level flow control.
     ///
@@ -349,6 +784,21 @@ where
         self
     }
 
+    /// Set a callback for when a keep-alive ping times out.
+    ///
+    /// It is called before the connection is closed, with the recent
+    /// round-trip times, such as to record metrics, or to open a
+    /// replacement connection early. It shouldn't block, since it is called
+    /// from the connection's task. Does nothing if `keep_alive_interval`
+    /// is disabled.
+    pub fn on_keep_alive_timeout<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&KeepAliveTimeout) + Send + Sync + 'static,
+    {
+        self.h2_builder.on_keep_alive_timeout = OnKeepAliveTimeout(Some(Arc::new(callback)));
+        self
+    }
+
     /// Sets whether HTTP2 keep-alive should apply while the connection is idle.
     ///
     /// If disabled, keep-alive pings are only sent while there are open
@@ -362,6 +812,19 @@ where
         self
     }
 
+    /// Sets a timeout for writes to the connection to make progress.
+    ///
+    /// If a write or flush to the underlying IO stays pending for longer
+    /// than this, such as when the peer stopped reading, the connection is
+    /// closed with a timeout error. Requires a [`timer`](Builder::timer),
+    /// and is ignored without one.
+    ///
+    /// Default is no timeout.
+    pub fn write_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.h2_builder.write_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the maximum number of HTTP2 concurrent locally reset streams.
     ///
     /// See the documentation of [`h2::client::Builder::max_concurrent_reset_streams`] for more
@@ -388,6 +851,19 @@ where
         self
     }
 
+    /// Sets whether the server is allowed to push responses.
+    ///
+    /// When enabled, each response has a [`PushPromises`] in its
+    /// extensions, to receive the requests pushed alongside it.
+    /// Enabling it also advertises a limit of 100 concurrently pushed
+    /// streams, since some servers won't push without a limit.
+    ///
+    /// Default is false.
+    pub fn enable_push(&mut self, enabled: bool) -> &mut Self {
+        self.h2_builder.enable_push = enabled;
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -400,7 +876,6 @@ where
     where
         T: Read + Write + Unpin + 'static,
         B: Body + 'static,
-        B::Data: Send,
         B::Error: Into<Box<dyn Error + Send + Sync>>,
         Ex: ExecutorClient<B, T> + Unpin,
     {
@@ -410,14 +885,29 @@ where
             trace!("client handshake HTTP/1");
 
             let (tx, rx) = dispatch::channel();
-            let h2 = proto::h2::client::handshake(io, rx, &opts.h2_builder, opts.exec, opts.timer)
-                .await?;
+            let bytes = ByteCounts::new();
+            let h2 = proto::h2::client::handshake(
+                io,
+                rx,
+                &opts.h2_builder,
+                opts.exec,
+                opts.timer.clone(),
+                opts.metrics.with_byte_counter(bytes.0.clone()),
+                opts.events,
+            )
+            .await?;
             Ok((
                 SendRequest {
                     dispatch: tx.unbound(),
+                    pinger: h2.pinger(),
+                    peer_settings: h2.peer_settings(),
+                    open_streams: h2.open_streams(),
+                    reserved: None,
+                    timer: opts.timer,
                 },
                 Connection {
                     inner: (PhantomData, h2),
+                    bytes,
                 },
             ))
         }
@@ -0,0 +1,295 @@
+//! HTTP/3 client connections
+
+use std::error::Error;
+use std::fmt;
+
+use http::{Request, Response};
+
+use super::super::dispatch;
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Future, Pin, Poll};
+use crate::proto;
+use crate::rt::bounds::Http3ClientExec;
+use crate::rt::quic;
+use crate::rt::{ConnectionEvent, Metrics};
+
+/// The sender side of an established connection.
+pub struct SendRequest<B> {
+    dispatch: dispatch::UnboundedSender<Request<B>, Response<IncomingBody>>,
+}
+
+impl<B> Clone for SendRequest<B> {
+    fn clone(&self) -> SendRequest<B> {
+        SendRequest {
+            dispatch: self.dispatch.clone(),
+        }
+    }
+}
+
+/// A future that processes all HTTP state for the QUIC connection.
+///
+/// In most cases, this should just be spawned into an executor, so that it
+/// can process incoming and outgoing messages, notice hangups, and the like.
+#[must_use = "futures do nothing unless polled"]
+pub struct Connection<C, B, E>
+where
+    C: quic::Connection,
+{
+    inner: proto::h3::ClientTask<C, B, E>,
+}
+
+/// A builder to configure an HTTP/3 connection.
+///
+/// After setting options, the builder is used to create a handshake future.
+#[derive(Clone, Debug)]
+pub struct Builder<Ex> {
+    exec: Ex,
+    metrics: MetricsSink,
+    events: EventSink,
+    h3_builder: proto::h3::Config,
+}
+
+/// Returns a handshake future over an established QUIC connection.
+///
+/// This is a shortcut for `Builder::new(exec).handshake(conn)`.
+/// See [`client::conn`](crate::client::conn) for more.
+pub async fn handshake<E, C, B>(
+    exec: E,
+    conn: C,
+) -> crate::Result<(SendRequest<B>, Connection<C, B, E>)>
+where
+    C: quic::Connection + Unpin,
+    B: Body + Unpin + 'static,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+    E: Http3ClientExec<B, C::BidiStream> + Unpin + Clone,
+{
+    Builder::new(exec).handshake(conn).await
+}
+
+// ===== impl SendRequest
+
+impl<B> SendRequest<B> {
+    /// Polls to determine whether this sender can be used yet for a request.
+    ///
+    /// If the associated connection is closed, this returns an Error.
+    pub fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        if self.is_closed() {
+            Poll::Ready(Err(crate::Error::new_closed()))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Waits until the dispatcher is ready
+    ///
+    /// If the associated connection is closed, this returns an Error.
+    pub async fn ready(&mut self) -> crate::Result<()> {
+        futures_util::future::poll_fn(|cx| self.poll_ready(cx)).await
+    }
+
+    /// Checks if the connection is currently ready to send a request.
+    ///
+    /// # Note
+    ///
+    /// This is mostly a hint. Due to inherent latency of networks, it is
+    /// possible that even after checking this is ready, sending a request
+    /// may still fail because the connection was closed in the meantime.
+    pub fn is_ready(&self) -> bool {
+        self.dispatch.is_ready()
+    }
+
+    /// Checks if the connection side has been closed.
+    ///
+    /// This is also the case once the server sent a GOAWAY frame.
+    pub fn is_closed(&self) -> bool {
+        self.dispatch.is_closed()
+    }
+}
+
+impl<B> SendRequest<B>
+where
+    B: Body + 'static,
+{
+    /// Sends a `Request` on the associated connection.
+    ///
+    /// Returns a future that if successful, yields the `Response`.
+    ///
+    /// # Note
+    ///
+    /// The `:scheme` of a request defaults to `https`, and its `:authority`
+    /// is only sent when the `Uri` has one. Since a `Host` header isn't
+    /// added from the `Uri`, requests should use absolute-form `Uri`s.
+    pub fn send_request(
+        &mut self,
+        req: Request<B>,
+    ) -> impl Future<Output = crate::Result<Response<IncomingBody>>> {
+        let sent = self.dispatch.send(req);
+
+        async move {
+            match sent {
+                Ok(rx) => match rx.await {
+                    Ok(Ok(resp)) => Ok(resp),
+                    Ok(Err(err)) => Err(err),
+                    // this is definite bug if it happens, but it shouldn't happen!
+                    Err(_canceled) => panic!("dispatch dropped without returning error"),
+                },
+                Err(_req) => {
+                    debug!("connection was not ready");
+
+                    Err(crate::Error::new_canceled().with("connection was not ready"))
+                }
+            }
+        }
+    }
+}
+
+impl<B> fmt::Debug for SendRequest<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendRequest").finish()
+    }
+}
+
+// ===== impl Connection
+
+impl<C, B, E> fmt::Debug for Connection<C, B, E>
+where
+    C: quic::Connection,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection").finish()
+    }
+}
+
+impl<C, B, E> Future for Connection<C, B, E>
+where
+    C: quic::Connection + Unpin,
+    B: Body + Unpin + 'static,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+    E: Http3ClientExec<B, C::BidiStream> + Unpin,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+// ===== impl Builder
+
+impl<Ex> Builder<Ex>
+where
+    Ex: Clone,
+{
+    /// Creates a new connection builder.
+    #[inline]
+    pub fn new(exec: Ex) -> Builder<Ex> {
+        Builder {
+            exec,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
+            h3_builder: Default::default(),
+        }
+    }
+
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Builder<Ex>
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Builder<Ex>
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
+    /// Sets the [`SETTINGS_MAX_FIELD_SECTION_SIZE`][spec] option, the max
+    /// size of received header sections.
+    ///
+    /// Default is currently ~16MB, but may change.
+    ///
+    /// [spec]: https://www.rfc-editor.org/rfc/rfc9114.html#section-4.2.2
+    pub fn max_field_section_size(&mut self, max: u32) -> &mut Self {
+        self.h3_builder.max_field_section_size = max.into();
+        self
+    }
+
+    /// Enables [WebTransport] sessions.
+    ///
+    /// An extended CONNECT request with the `webtransport`
+    /// [`Protocol`](crate::ext::Protocol) then establishes a session, and a
+    /// 2xx response to it carries a
+    /// [`WebTransportSession`](crate::ext::WebTransportSession) extension.
+    ///
+    /// [WebTransport]: https://datatracker.ietf.org/doc/html/draft-ietf-webtrans-http3-02
+    pub fn enable_webtransport(&mut self) -> &mut Self {
+        self.h3_builder.enable_webtransport = true;
+        self
+    }
+
+    /// Enables [HTTP datagrams], sent in QUIC datagrams.
+    ///
+    /// The 2xx response to a CONNECT request then carries an
+    /// [`H3Datagrams`](crate::ext::H3Datagrams) extension, to send and
+    /// receive the datagrams associated with it. The QUIC connection needs
+    /// to support datagrams, through
+    /// [`Connection::datagrams`](crate::rt::quic::Connection::datagrams).
+    ///
+    /// [HTTP datagrams]: https://www.rfc-editor.org/rfc/rfc9297.html
+    pub fn enable_datagrams(&mut self) -> &mut Self {
+        self.h3_builder.enable_datagrams = true;
+        self
+    }
+
+    /// Constructs a connection with the configured options and QUIC
+    /// connection.
+    ///
+    /// Nothing is exchanged before the returned `Connection` is polled,
+    /// since the QUIC handshake already happened, and HTTP/3 needs none of
+    /// its own.
+    pub fn handshake<C, B>(
+        &self,
+        conn: C,
+    ) -> impl Future<Output = crate::Result<(SendRequest<B>, Connection<C, B, Ex>)>>
+    where
+        C: quic::Connection + Unpin,
+        B: Body + Unpin + 'static,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+        Ex: Http3ClientExec<B, C::BidiStream> + Unpin,
+    {
+        let opts = self.clone();
+
+        async move {
+            trace!("client handshake HTTP/3");
+
+            let (tx, rx) = dispatch::channel();
+            let task = proto::h3::ClientTask::new(
+                conn,
+                rx,
+                &opts.h3_builder,
+                opts.exec,
+                opts.metrics,
+                &opts.events,
+            );
+            Ok((
+                SendRequest {
+                    dispatch: tx.unbound(),
+                },
+                Connection { inner: task },
+            ))
+        }
+    }
+}
@@ -53,8 +53,243 @@
 //! # }
 //! # }
 //! ```
+//!
+//! ## Single-threaded executors
+//!
+//! The HTTP/1 and HTTP/2 connections don't require the IO or request body
+//! to be `Send`, and are only `Send` themselves if those are. The exception
+//! is [`http1::Connection`], which must be able to hand its IO off in an
+//! upgrade. On single-threaded targets such as `wasm32`, use
+//! [`http1::Builder::handshake_local`] to get a connection without that
+//! requirement, and poll it with a local spawner. HTTP/2 connections only
+//! need an executor that spawns onto the current thread.
 
 #[cfg(feature = "http1")]
 pub mod http1;
 #[cfg(feature = "http2")]
 pub mod http2;
+#[cfg(feature = "http3")]
+pub mod http3;
+
+#[cfg(any(feature = "http1", feature = "http2"))]
+pub use self::byte_counts::ByteCounts;
+#[cfg(any(feature = "http1", feature = "http2"))]
+pub use self::try_send::TrySendError;
+#[cfg(any(feature = "http1", feature = "http2"))]
+pub use self::tunnel::TunnelError;
+
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod byte_counts {
+    use std::fmt;
+    use std::sync::Arc;
+
+    use crate::common::metrics::ByteCounter;
+
+    /// The number of bytes read from and written to a connection's IO.
+    ///
+    /// Get one from an HTTP/1 or HTTP/2 `Connection` with its `byte_counts`
+    /// method. It is a handle to counters the connection keeps updating, so
+    /// it can be kept after the connection has been spawned, and read again
+    /// once it's done. The counts include everything hyper reads and writes,
+    /// such as message heads, body framing, and HTTP/2 control frames, but
+    /// not anything written to an upgraded IO.
+    #[derive(Clone)]
+    pub struct ByteCounts(pub(crate) Arc<ByteCounter>);
+
+    impl ByteCounts {
+        pub(crate) fn new() -> ByteCounts {
+            ByteCounts(Arc::new(ByteCounter::default()))
+        }
+
+        /// The number of bytes read from the IO so far.
+        pub fn bytes_read(&self) -> u64 {
+            self.0.read()
+        }
+
+        /// The number of bytes written to the IO so far.
+        pub fn bytes_written(&self) -> u64 {
+            self.0.written()
+        }
+    }
+
+    impl fmt::Debug for ByteCounts {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ByteCounts")
+                .field("bytes_read", &self.bytes_read())
+                .field("bytes_written", &self.bytes_written())
+                .finish()
+        }
+    }
+}
+
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod try_send {
+    use std::error::Error as StdError;
+    use std::fmt;
+
+    /// An error returned by `try_send_request`.
+    ///
+    /// A connection can fail in between when a request is queued on it and
+    /// when the request is written. Since the request was never sent, it is
+    /// handed back, so it can be retried on another connection.
+    pub struct TrySendError<T> {
+        pub(crate) error: crate::Error,
+        pub(crate) message: Option<T>,
+    }
+
+    impl<T> TrySendError<T> {
+        /// Take the unsent message back out of this error.
+        ///
+        /// This is `None` if the message had already started being written
+        /// when the error occurred, or was already taken.
+        pub fn take_message(&mut self) -> Option<T> {
+            self.message.take()
+        }
+
+        /// Returns a reference to the unsent message, if any.
+        pub fn message(&self) -> Option<&T> {
+            self.message.as_ref()
+        }
+
+        /// Returns a reference to the error.
+        pub fn error(&self) -> &crate::Error {
+            &self.error
+        }
+
+        /// Consumes this, returning the error.
+        pub fn into_error(self) -> crate::Error {
+            self.error
+        }
+    }
+
+    impl<T> fmt::Debug for TrySendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("TrySendError")
+                .field("error", &self.error)
+                .field("message", &self.message.as_ref().map(|_| ..))
+                .finish()
+        }
+    }
+
+    impl<T> fmt::Display for TrySendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Display::fmt(&self.error, f)
+        }
+    }
+
+    impl<T> StdError for TrySendError<T> {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            self.error.source()
+        }
+    }
+}
+
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod tunnel {
+    use std::error::Error as StdError;
+    use std::fmt;
+
+    use http::uri::Authority;
+    use http::{Request, Response};
+
+    use crate::body::Incoming;
+    use crate::common::Future;
+    use crate::upgrade::Upgraded;
+
+    /// An error returned by `connect`, when a tunnel couldn't be
+    /// established.
+    ///
+    /// Either the server refused the `CONNECT` request, in which case its
+    /// response is kept, or the request or the upgrade failed.
+    pub struct TunnelError {
+        kind: Kind,
+    }
+
+    enum Kind {
+        Refused(Response<Incoming>),
+        Failed(crate::Error),
+    }
+
+    impl TunnelError {
+        /// Returns the response, if the server refused the tunnel.
+        ///
+        /// Such as a `407 Proxy Authentication Required` from a proxy.
+        pub fn response(&self) -> Option<&Response<Incoming>> {
+            match self.kind {
+                Kind::Refused(ref res) => Some(res),
+                Kind::Failed(_) => None,
+            }
+        }
+
+        /// Consumes this, returning the response, if the server refused the
+        /// tunnel.
+        pub fn into_response(self) -> Option<Response<Incoming>> {
+            match self.kind {
+                Kind::Refused(res) => Some(res),
+                Kind::Failed(_) => None,
+            }
+        }
+
+        /// Returns the error, if the request or the upgrade failed.
+        pub fn error(&self) -> Option<&crate::Error> {
+            match self.kind {
+                Kind::Refused(_) => None,
+                Kind::Failed(ref err) => Some(err),
+            }
+        }
+    }
+
+    impl fmt::Debug for TunnelError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut f = f.debug_struct("TunnelError");
+            match self.kind {
+                Kind::Refused(ref res) => f.field("status", &res.status()),
+                Kind::Failed(ref err) => f.field("error", err),
+            };
+            f.finish()
+        }
+    }
+
+    impl fmt::Display for TunnelError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.kind {
+                Kind::Refused(ref res) => write!(f, "tunnel refused: {}", res.status()),
+                Kind::Failed(ref err) => write!(f, "tunnel failed: {}", err),
+            }
+        }
+    }
+
+    impl StdError for TunnelError {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            match self.kind {
+                Kind::Refused(_) => None,
+                Kind::Failed(ref err) => Some(err),
+            }
+        }
+    }
+
+    /// A `CONNECT` request for `authority`, with an empty body.
+    pub(super) fn request<B: Default>(authority: Authority) -> Request<B> {
+        let mut req = Request::new(B::default());
+        *req.method_mut() = http::Method::CONNECT;
+        *req.uri_mut() = authority.into();
+        req
+    }
+
+    /// Waits for the response to a `CONNECT` request, and then the tunnel.
+    pub(super) async fn upgrade<F>(sent: F) -> Result<Upgraded, TunnelError>
+    where
+        F: Future<Output = crate::Result<Response<Incoming>>>,
+    {
+        let failed = |err| TunnelError {
+            kind: Kind::Failed(err),
+        };
+        let res = sent.await.map_err(failed)?;
+        if !res.status().is_success() {
+            return Err(TunnelError {
+                kind: Kind::Refused(res),
+            });
+        }
+        crate::upgrade::on(res).await.map_err(failed)
+    }
+}
@@ -1,31 +1,52 @@
-#[cfg(feature = "http2")]
+use std::fmt;
 use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use futures_util::task::AtomicWaker;
 use http::{Request, Response};
 use http_body::Body;
 use pin_project_lite::pin_project;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, watch};
 
+#[cfg(feature = "http2")]
+use crate::proto::h2::client::ResponseFutMap;
 use crate::{
     body::Incoming,
-    common::{task, Poll},
+    common::{task, Pin, Poll},
 };
-#[cfg(feature = "http2")]
-use crate::{common::Pin, proto::h2::client::ResponseFutMap};
 
-#[cfg(test)]
-pub(crate) type RetryPromise<T, U> = oneshot::Receiver<Result<U, (crate::Error, Option<T>)>>;
-pub(crate) type Promise<T> = oneshot::Receiver<Result<T, crate::Error>>;
+/// Resolves to the response to a request sent with `try_send`, or to the
+/// error, along with the request if it wasn't sent.
+#[cfg(any(test, feature = "http1", feature = "http2"))]
+pub(crate) struct RetryPromise<T, U>(Reply<T, U>);
+
+/// Resolves to the response to a request sent with `send`, or to the error.
+pub(crate) struct Promise<T, U>(Reply<T, U>);
+
+/// The response to a request, or the error, along with the request if it
+/// can be retried.
+pub(crate) type Outcome<T, U> = Result<U, (crate::Error, Option<T>)>;
+
+/// The `Callback` of a promise was dropped without sending anything.
+#[derive(Debug)]
+pub(crate) struct Canceled;
 
 pub(crate) fn channel<T, U>() -> (Sender<T, U>, Receiver<T, U>) {
     let (tx, rx) = mpsc::unbounded_channel();
     let (giver, taker) = want::new();
+    let (closed_tx, closed_rx) = watch::channel(());
     let tx = Sender {
         buffered_once: false,
         giver,
         inner: tx,
+        closed: closed_rx,
+        spare: None,
+    };
+    let rx = Receiver {
+        inner: rx,
+        taker,
+        closed: Some(closed_tx),
     };
-    let rx = Receiver { inner: rx, taker };
     (tx, rx)
 }
 
@@ -45,17 +66,23 @@ pub(crate) struct Sender<T, U> {
     giver: want::Giver,
     /// Actually bounded by the Giver, plus `buffered_once`.
     inner: mpsc::UnboundedSender<Envelope<T, U>>,
+    /// Sees the Receiver close, without keeping the channel open.
+    closed: watch::Receiver<()>,
+    /// The slot of the last promise, reused for the next one if both of
+    /// its halves have been dropped by then.
+    spare: Option<Arc<Slot<T, U>>>,
 }
 
 /// An unbounded version.
 ///
 /// Cannot poll the Giver, but can still use it to determine if the Receiver
 /// has been dropped. However, this version can be cloned.
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 pub(crate) struct UnboundedSender<T, U> {
     /// Only used for `is_closed`, since mpsc::UnboundedSender cannot be checked.
     giver: want::SharedGiver,
     inner: mpsc::UnboundedSender<Envelope<T, U>>,
+    closed: watch::Receiver<()>,
 }
 
 impl<T, U> Sender<T, U> {
@@ -73,6 +100,11 @@ impl<T, U> Sender<T, U> {
         self.giver.is_canceled()
     }
 
+    #[cfg(feature = "http1")]
+    pub(crate) fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        wait_closed(self.closed.clone())
+    }
+
     fn can_send(&mut self) -> bool {
         if self.giver.give() || !self.buffered_once {
             // If the receiver is ready *now*, then of course we can send.
@@ -86,39 +118,51 @@ impl<T, U> Sender<T, U> {
         }
     }
 
-    #[cfg(test)]
+    /// Takes the spare slot for a new promise, or allocates one if the
+    /// last promise or its callback is still around, such as when requests
+    /// are pipelined.
+    fn slot(&mut self) -> Arc<Slot<T, U>> {
+        match self.spare.as_mut().and_then(Arc::get_mut) {
+            Some(slot) => *slot = Slot::new(),
+            None => self.spare = Some(Arc::new(Slot::new())),
+        }
+        self.spare.as_ref().expect("spare was just set").clone()
+    }
+
+    #[cfg(any(test, feature = "http1"))]
     pub(crate) fn try_send(&mut self, val: T) -> Result<RetryPromise<T, U>, T> {
         if !self.can_send() {
             return Err(val);
         }
-        let (tx, rx) = oneshot::channel();
+        let (tx, rx) = reply(self.slot());
         self.inner
             .send(Envelope(Some((val, Callback::Retry(Some(tx))))))
-            .map(move |_| rx)
+            .map(move |_| RetryPromise(rx))
             .map_err(|mut e| (e.0).0.take().expect("envelope not dropped").0)
     }
 
-    pub(crate) fn send(&mut self, val: T) -> Result<Promise<U>, T> {
+    pub(crate) fn send(&mut self, val: T) -> Result<Promise<T, U>, T> {
         if !self.can_send() {
             return Err(val);
         }
-        let (tx, rx) = oneshot::channel();
+        let (tx, rx) = reply(self.slot());
         self.inner
             .send(Envelope(Some((val, Callback::NoRetry(Some(tx))))))
-            .map(move |_| rx)
+            .map(move |_| Promise(rx))
             .map_err(|mut e| (e.0).0.take().expect("envelope not dropped").0)
     }
 
-    #[cfg(feature = "http2")]
+    #[cfg(any(feature = "http2", feature = "http3"))]
     pub(crate) fn unbound(self) -> UnboundedSender<T, U> {
         UnboundedSender {
             giver: self.giver.shared(),
             inner: self.inner,
+            closed: self.closed,
         }
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl<T, U> UnboundedSender<T, U> {
     pub(crate) fn is_ready(&self) -> bool {
         !self.giver.is_canceled()
@@ -128,37 +172,72 @@ impl<T, U> UnboundedSender<T, U> {
         self.giver.is_canceled()
     }
 
-    #[cfg(test)]
+    #[cfg(feature = "http2")]
+    pub(crate) fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        wait_closed(self.closed.clone())
+    }
+
+    #[cfg(feature = "http2")]
     pub(crate) fn try_send(&mut self, val: T) -> Result<RetryPromise<T, U>, T> {
-        let (tx, rx) = oneshot::channel();
+        let (tx, rx) = reply(Arc::new(Slot::new()));
         self.inner
             .send(Envelope(Some((val, Callback::Retry(Some(tx))))))
-            .map(move |_| rx)
+            .map(move |_| RetryPromise(rx))
             .map_err(|mut e| (e.0).0.take().expect("envelope not dropped").0)
     }
 
-    pub(crate) fn send(&mut self, val: T) -> Result<Promise<U>, T> {
-        let (tx, rx) = oneshot::channel();
+    pub(crate) fn send(&mut self, val: T) -> Result<Promise<T, U>, T> {
+        let (tx, rx) = reply(Arc::new(Slot::new()));
         self.inner
             .send(Envelope(Some((val, Callback::NoRetry(Some(tx))))))
-            .map(move |_| rx)
+            .map(move |_| Promise(rx))
             .map_err(|mut e| (e.0).0.take().expect("envelope not dropped").0)
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl<T, U> Clone for UnboundedSender<T, U> {
     fn clone(&self) -> Self {
         UnboundedSender {
             giver: self.giver.clone(),
             inner: self.inner.clone(),
+            closed: self.closed.clone(),
         }
     }
 }
 
+/// Resolves once the Receiver has been closed or dropped.
+#[cfg(any(feature = "http1", feature = "http2"))]
+async fn wait_closed(mut closed: watch::Receiver<()>) {
+    // Nothing is ever sent, so this only returns once the sender is gone.
+    while closed.changed().await.is_ok() {}
+}
+
+/// Resolves to the output of `fut`, or `None` if `deadline` elapses first.
+#[cfg(any(feature = "http1", feature = "http2"))]
+pub(crate) async fn until_deadline<F: Future>(
+    fut: F,
+    deadline: Option<Pin<Box<dyn crate::rt::Sleep>>>,
+) -> Option<F::Output> {
+    use futures_util::future::{self, Either};
+
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Some(fut.await),
+    };
+    futures_util::pin_mut!(fut);
+    match future::select(fut, deadline).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
+
 pub(crate) struct Receiver<T, U> {
     inner: mpsc::UnboundedReceiver<Envelope<T, U>>,
     taker: want::Taker,
+    /// Dropped to notify the senders' `closed` futures.
+    #[cfg_attr(not(feature = "http1"), allow(unused))]
+    closed: Option<watch::Sender<()>>,
 }
 
 impl<T, U> Receiver<T, U> {
@@ -177,10 +256,11 @@ impl<T, U> Receiver<T, U> {
         }
     }
 
-    #[cfg(feature = "http1")]
+    #[cfg(any(feature = "http1", feature = "http2"))]
     pub(crate) fn close(&mut self) {
         self.taker.cancel();
         self.inner.close();
+        self.closed.take();
     }
 
     #[cfg(feature = "http1")]
@@ -216,8 +296,8 @@ impl<T, U> Drop for Envelope<T, U> {
 
 pub(crate) enum Callback<T, U> {
     #[allow(unused)]
-    Retry(Option<oneshot::Sender<Result<U, (crate::Error, Option<T>)>>>),
-    NoRetry(Option<oneshot::Sender<Result<U, crate::Error>>>),
+    Retry(Option<Responder<T, U>>),
+    NoRetry(Option<Responder<T, U>>),
 }
 
 impl<T, U> Drop for Callback<T, U> {
@@ -230,14 +310,9 @@ impl<T, U> Drop for Callback<T, U> {
         });
 
         match self {
-            Callback::Retry(tx) => {
+            Callback::Retry(tx) | Callback::NoRetry(tx) => {
                 if let Some(tx) = tx.take() {
-                    let _ = tx.send(Err((error, None)));
-                }
-            }
-            Callback::NoRetry(tx) => {
-                if let Some(tx) = tx.take() {
-                    let _ = tx.send(Err(error));
+                    tx.send(Err((error, None)));
                 }
             }
         }
@@ -245,7 +320,7 @@ impl<T, U> Drop for Callback<T, U> {
 }
 
 impl<T, U> Callback<T, U> {
-    #[cfg(feature = "http2")]
+    #[cfg(any(feature = "http2", feature = "http3"))]
     pub(crate) fn is_canceled(&self) -> bool {
         match *self {
             Callback::Retry(Some(ref tx)) => tx.is_closed(),
@@ -262,18 +337,163 @@ impl<T, U> Callback<T, U> {
         }
     }
 
-    pub(crate) fn send(mut self, val: Result<U, (crate::Error, Option<T>)>) {
+    pub(crate) fn send(mut self, val: Outcome<T, U>) {
         match self {
             Callback::Retry(ref mut tx) => {
-                let _ = tx.take().unwrap().send(val);
+                tx.take().unwrap().send(val);
             }
             Callback::NoRetry(ref mut tx) => {
-                let _ = tx.take().unwrap().send(val.map_err(|e| e.0));
+                // The request can't be retried, so don't hold on to it.
+                tx.take().unwrap().send(val.map_err(|(e, _)| (e, None)));
             }
         }
     }
 }
 
+// ===== impl Promise
+
+#[cfg(any(test, feature = "http1", feature = "http2"))]
+impl<T, U> Future for RetryPromise<T, U> {
+    type Output = Result<Outcome<T, U>, Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_recv(cx)
+    }
+}
+
+impl<T, U> Future for Promise<T, U> {
+    type Output = Result<Result<U, crate::Error>, Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.0
+            .poll_recv(cx)
+            .map(|res| res.map(|val| val.map_err(|(e, _)| e)))
+    }
+}
+
+#[cfg(any(test, feature = "http1", feature = "http2"))]
+impl<T, U> fmt::Debug for RetryPromise<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPromise").finish()
+    }
+}
+
+impl<T, U> fmt::Debug for Promise<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Promise").finish()
+    }
+}
+
+/// The state shared by a `Callback` and its promise.
+///
+/// It holds a single response, like a oneshot channel, but is allocated
+/// apart from the halves, so a bounded `Sender` can reuse it for the next
+/// request instead of allocating new state for every one.
+struct Slot<T, U> {
+    state: Mutex<SlotState<T, U>>,
+    rx_task: AtomicWaker,
+    tx_task: AtomicWaker,
+}
+
+struct SlotState<T, U> {
+    value: Option<Outcome<T, U>>,
+    tx_closed: bool,
+    rx_closed: bool,
+}
+
+impl<T, U> Slot<T, U> {
+    fn new() -> Slot<T, U> {
+        Slot {
+            state: Mutex::new(SlotState {
+                value: None,
+                tx_closed: false,
+                rx_closed: false,
+            }),
+            rx_task: AtomicWaker::new(),
+            tx_task: AtomicWaker::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotState<T, U>> {
+        self.state.lock().unwrap()
+    }
+}
+
+fn reply<T, U>(slot: Arc<Slot<T, U>>) -> (Responder<T, U>, Reply<T, U>) {
+    let tx = Responder { slot: slot.clone() };
+    let rx = Reply { slot };
+    (tx, rx)
+}
+
+/// The sending half of a promise, held by its `Callback`.
+pub(crate) struct Responder<T, U> {
+    slot: Arc<Slot<T, U>>,
+}
+
+/// The receiving half of a promise.
+struct Reply<T, U> {
+    slot: Arc<Slot<T, U>>,
+}
+
+impl<T, U> Responder<T, U> {
+    fn send(self, val: Outcome<T, U>) {
+        let mut state = self.slot.lock();
+        // If nobody is waiting for it, drop it now instead of leaving it in
+        // a slot that may be spare.
+        if !state.rx_closed {
+            state.value = Some(val);
+        }
+        // Dropping `self` notifies the receiver.
+    }
+
+    #[cfg(any(feature = "http2", feature = "http3"))]
+    fn is_closed(&self) -> bool {
+        self.slot.lock().rx_closed
+    }
+
+    fn poll_closed(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        self.slot.tx_task.register(cx.waker());
+        if self.slot.lock().rx_closed {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, U> Drop for Responder<T, U> {
+    fn drop(&mut self) {
+        self.slot.lock().tx_closed = true;
+        self.slot.rx_task.wake();
+    }
+}
+
+impl<T, U> Reply<T, U> {
+    fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<Outcome<T, U>, Canceled>> {
+        self.slot.rx_task.register(cx.waker());
+        let mut state = self.slot.lock();
+        if let Some(val) = state.value.take() {
+            Poll::Ready(Ok(val))
+        } else if state.tx_closed {
+            Poll::Ready(Err(Canceled))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, U> Drop for Reply<T, U> {
+    fn drop(&mut self) {
+        let value = {
+            let mut state = self.slot.lock();
+            state.rx_closed = true;
+            state.value.take()
+        };
+        self.slot.tx_task.wake();
+        drop(value);
+    }
+}
+
 #[cfg(feature = "http2")]
 pin_project! {
     pub struct SendWhen<B>
@@ -333,8 +553,11 @@ mod tests {
 
     use std::future::Future;
     use std::pin::Pin;
+    use std::sync::Arc;
     use std::task::{Context, Poll};
 
+    use futures_util::future;
+
     use super::{channel, Callback, Receiver};
 
     #[derive(Debug)]
@@ -388,6 +611,22 @@ mod tests {
         }
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn sender_closed_resolves_when_receiver_closes() {
+        let (tx, mut rx) = channel::<Custom, ()>();
+
+        let mut closed = Box::pin(tx.closed());
+        assert!(PollOnce(&mut closed).await.is_none(), "not closed yet");
+
+        rx.close();
+        closed.await;
+
+        // a new future after closing resolves right away
+        tx.closed().await;
+        assert!(tx.is_closed());
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn sender_checks_for_want_on_send() {
@@ -408,6 +647,46 @@ mod tests {
         let _ = tx.try_send(Custom(2)).expect("2 ready");
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn sender_reuses_promise_slot() {
+        let (mut tx, mut rx) = channel::<Custom, Custom>();
+
+        assert!(PollOnce(&mut rx).await.is_none(), "rx empty");
+        let promise = tx.send(Custom(1)).unwrap();
+        let slot = Arc::as_ptr(tx.spare.as_ref().unwrap());
+        let (_, cb) = (&mut rx).await.unwrap();
+        cb.send(Ok(Custom(2)));
+        let res = promise.await.expect("fulfilled").expect("response");
+        assert_eq!(res.0, 2);
+
+        assert!(PollOnce(&mut rx).await.is_none(), "rx empty");
+        let promise = tx.send(Custom(3)).unwrap();
+        assert_eq!(Arc::as_ptr(tx.spare.as_ref().unwrap()), slot);
+
+        // while the last promise is held, a new slot is needed
+        assert!(PollOnce(&mut rx).await.is_some(), "rx once");
+        assert!(PollOnce(&mut rx).await.is_none(), "rx empty");
+        let _promise2 = tx.send(Custom(4)).unwrap();
+        assert_ne!(Arc::as_ptr(tx.spare.as_ref().unwrap()), slot);
+        drop(promise);
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn dropped_promise_cancels_callback() {
+        let (mut tx, mut rx) = channel::<Custom, Custom>();
+
+        assert!(PollOnce(&mut rx).await.is_none(), "rx empty");
+        let promise = tx.send(Custom(1)).unwrap();
+        let (_, mut cb) = (&mut rx).await.unwrap();
+        let mut canceled = future::poll_fn(|cx| cb.poll_canceled(cx));
+        assert!(PollOnce(&mut canceled).await.is_none(), "not canceled yet");
+
+        drop(promise);
+        canceled.await;
+    }
+
     #[cfg(feature = "http2")]
     #[test]
     fn unbounded_sender_doesnt_bound_on_want() {
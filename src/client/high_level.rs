@@ -0,0 +1,117 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use http::{Request, Response, Uri};
+
+use super::pool::{self, Connect, Pool};
+use crate::body::{Body, Incoming};
+use crate::common::{Future, Pin};
+use crate::rt::Executor;
+
+/// A small HTTP/1 client, which manages its connections.
+///
+/// Connections are opened with a [`Connect`] implementation, kept in a
+/// [`Pool`], and their tasks spawned on an [`Executor`], so the `Client`
+/// isn't tied to any runtime. It doesn't follow redirects, nor resolve or
+/// proxy anything the connector doesn't.
+///
+/// Cloning a `Client` is cheap, and the clones share their connections.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "high-level-client")]
+/// # mod rt {
+/// use bytes::Bytes;
+/// use http_body_util::Empty;
+/// use hyper::client::pool::Connect;
+/// use hyper::client::Client;
+/// # use hyper::rt::Executor;
+/// # use std::future::Future;
+/// # use std::pin::Pin;
+/// # type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+/// # async fn run<C, E>(connector: C, executor: E) -> Result<(), Box<dyn std::error::Error>>
+/// # where
+/// #     C: Connect,
+/// #     E: Executor<BoxFuture> + Send + Sync + 'static,
+/// # {
+/// let client = Client::<_, Empty<Bytes>>::new(connector, executor);
+/// let res = client.get("http://example.com/".parse()?).await?;
+/// println!("status: {}", res.status());
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+pub struct Client<C, B> {
+    pool: Pool<C, B>,
+}
+
+impl<C, B> Client<C, B>
+where
+    C: Connect,
+{
+    /// Creates a client with the default pool options, opening connections
+    /// with `connector` and spawning their tasks on `exec`.
+    pub fn new<E>(connector: C, exec: E) -> Client<C, B>
+    where
+        E: Executor<Pin<Box<dyn Future<Output = ()> + Send>>> + Send + Sync + 'static,
+    {
+        Client::from_pool(pool::Builder::new(exec).build(connector))
+    }
+
+    /// Creates a client sending its requests through `pool`.
+    ///
+    /// Use this to configure the pool, or the HTTP/1 options of its
+    /// connections, with a [`pool::Builder`].
+    pub fn from_pool(pool: Pool<C, B>) -> Client<C, B> {
+        Client { pool }
+    }
+}
+
+impl<C, B> Client<C, B>
+where
+    C: Connect,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Sends a `GET` request to `uri`, with an empty body.
+    ///
+    /// The URI must be absolute.
+    pub fn get(
+        &self,
+        uri: Uri,
+    ) -> impl Future<Output = Result<Response<Incoming>, pool::Error>> + '_
+    where
+        B: Default,
+    {
+        let mut req = Request::new(B::default());
+        *req.uri_mut() = uri;
+        self.request(req)
+    }
+
+    /// Sends a request, on an idle connection to its URI's scheme and
+    /// authority, or on a new one.
+    ///
+    /// See [`Pool::send_request`] for how the request is sent.
+    pub fn request(
+        &self,
+        req: Request<B>,
+    ) -> impl Future<Output = Result<Response<Incoming>, pool::Error>> + '_ {
+        self.pool.send_request(req)
+    }
+}
+
+impl<C, B> Clone for Client<C, B> {
+    fn clone(&self) -> Self {
+        Client {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<C, B> fmt::Debug for Client<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client").field("pool", &self.pool).finish()
+    }
+}
@@ -2,6 +2,10 @@
 //!
 //! hyper provides HTTP over a single connection. See the [`conn`](conn) module.
 //!
+//! With the `pool` feature, connections can be reused across requests with
+//! a `pool::Pool`, and with the `high-level-client` feature, a `Client`
+//! manages them for you.
+//!
 //! ## Example
 //!
 //! For a small example program simply fetching a URL, take a look at the
@@ -11,8 +15,21 @@
 mod tests;
 
 cfg_feature! {
-    #![any(feature = "http1", feature = "http2")]
+    #![any(feature = "http1", feature = "http2", feature = "http3")]
 
     pub mod conn;
     pub(super) mod dispatch;
 }
+
+cfg_feature! {
+    #![feature = "pool"]
+
+    pub mod pool;
+}
+
+cfg_feature! {
+    #![feature = "high-level-client"]
+
+    mod high_level;
+    pub use self::high_level::Client;
+}
@@ -0,0 +1,434 @@
+//! A minimal pool of HTTP/1 connections.
+//!
+//! A [`Pool`] keeps connections open between requests, keyed by the scheme
+//! and authority of the request URI, and opens new ones through a
+//! [`Connect`] implementation when none are idle. Each connection is driven
+//! by a [`client::conn::http1`](super::conn::http1) handshake, and its task
+//! is spawned on an [`Executor`].
+//!
+//! This is deliberately small: there's no HTTP/2, proxying, or DNS
+//! resolution, only what the connector provides. For a full featured client,
+//! consider `hyper-util`.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! # #[cfg(feature = "pool")]
+//! # mod rt {
+//! use bytes::Bytes;
+//! use http::Request;
+//! use http_body_util::Empty;
+//! use hyper::client::pool::{Builder, Connect};
+//! # use hyper::rt::Executor;
+//! # use std::future::Future;
+//! # use std::pin::Pin;
+//! # type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+//! # async fn run<C, E>(connector: C, executor: E) -> Result<(), Box<dyn std::error::Error>>
+//! # where
+//! #     C: Connect,
+//! #     E: Executor<BoxFuture> + Send + Sync + 'static,
+//! # {
+//! let pool = Builder::new(executor).build::<_, Empty<Bytes>>(connector);
+//!
+//! for _ in 0..2 {
+//!     // The second request reuses the connection of the first.
+//!     let req = Request::get("http://example.com/").body(Empty::new())?;
+//!     let res = pool.send_request(req).await?;
+//!     println!("status: {}", res.status());
+//! }
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::{HeaderValue, HOST};
+use http::uri::{Authority, Scheme};
+use http::{Method, Request, Response, Uri};
+
+use super::conn::http1;
+use crate::body::{Body, Incoming};
+use crate::common::{Future, Pin};
+use crate::rt::{Executor, Read, Write};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Opens the connections of a [`Pool`].
+///
+/// This is implemented for any `Fn(&Uri) -> Future` returning the IO, such
+/// as a closure opening a TCP connection to the URI's host and port.
+pub trait Connect {
+    /// The IO of a connection.
+    type Io: Read + Write + Unpin + Send + 'static;
+    /// The error when a connection couldn't be opened.
+    type Error: Into<Box<dyn StdError + Send + Sync>>;
+    /// The future opening a connection.
+    type Future: Future<Output = Result<Self::Io, Self::Error>>;
+
+    /// Opens a connection to the scheme and authority of `dst`.
+    fn connect(&self, dst: &Uri) -> Self::Future;
+}
+
+impl<F, Fut, T, E> Connect for F
+where
+    F: Fn(&Uri) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    T: Read + Write + Unpin + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Io = T;
+    type Error = E;
+    type Future = Fut;
+
+    fn connect(&self, dst: &Uri) -> Self::Future {
+        (self)(dst)
+    }
+}
+
+/// A builder to configure a [`Pool`].
+#[derive(Clone)]
+pub struct Builder {
+    exec: Arc<dyn Executor<BoxFuture> + Send + Sync>,
+    http1: http1::Builder,
+    max_idle_per_host: usize,
+    idle_timeout: Option<Duration>,
+}
+
+/// A pool of HTTP/1 connections, keyed by scheme and authority.
+///
+/// Cloning a `Pool` is cheap, and the clones share their idle connections.
+pub struct Pool<C, B> {
+    connector: Arc<C>,
+    exec: Arc<dyn Executor<BoxFuture> + Send + Sync>,
+    http1: http1::Builder,
+    idle: Arc<Mutex<Idle<B>>>,
+}
+
+/// An error returned by [`Pool::send_request`].
+pub struct Error {
+    kind: Kind,
+}
+
+enum Kind {
+    Destination,
+    Connect(Box<dyn StdError + Send + Sync>),
+    Http(crate::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    scheme: Scheme,
+    authority: Authority,
+}
+
+struct Idle<B> {
+    conns: HashMap<Key, Vec<Entry<B>>>,
+    max_per_host: usize,
+    timeout: Option<Duration>,
+}
+
+struct Entry<B> {
+    tx: http1::SendRequest<B>,
+    since: Instant,
+}
+
+// ===== impl Builder
+
+impl Builder {
+    /// Creates a new pool builder, spawning connection tasks on `exec`.
+    pub fn new<E>(exec: E) -> Builder
+    where
+        E: Executor<BoxFuture> + Send + Sync + 'static,
+    {
+        Builder {
+            exec: Arc::new(exec),
+            http1: http1::Builder::new(),
+            max_idle_per_host: usize::MAX,
+            idle_timeout: Some(Duration::from_secs(90)),
+        }
+    }
+
+    /// Sets the options of the HTTP/1 handshake of each connection.
+    ///
+    /// Default is `http1::Builder::new()`.
+    pub fn http1(&mut self, builder: http1::Builder) -> &mut Builder {
+        self.http1 = builder;
+        self
+    }
+
+    /// Sets the most idle connections kept for each scheme and authority.
+    ///
+    /// Connections past this are closed once their response is read.
+    ///
+    /// Default is no limit.
+    pub fn max_idle_per_host(&mut self, max: usize) -> &mut Builder {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Sets how long a connection may be idle before it's closed, rather
+    /// than reused.
+    ///
+    /// Pass `None` to keep idle connections until the server closes them.
+    ///
+    /// Default is 90 seconds.
+    pub fn idle_timeout(&mut self, timeout: Option<Duration>) -> &mut Builder {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Builds a pool opening its connections with `connector`.
+    pub fn build<C, B>(&self, connector: C) -> Pool<C, B>
+    where
+        C: Connect,
+    {
+        Pool {
+            connector: Arc::new(connector),
+            exec: self.exec.clone(),
+            http1: self.http1.clone(),
+            idle: Arc::new(Mutex::new(Idle {
+                conns: HashMap::new(),
+                max_per_host: self.max_idle_per_host,
+                timeout: self.idle_timeout,
+            })),
+        }
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("http1", &self.http1)
+            .field("max_idle_per_host", &self.max_idle_per_host)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+// ===== impl Pool
+
+impl<C, B> Pool<C, B>
+where
+    C: Connect,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Sends a request on an idle connection to its URI's scheme and
+    /// authority, or on a new one.
+    ///
+    /// The request URI must be absolute. It's sent in origin-form, with a
+    /// `Host` header for its authority unless one is set already. Once the
+    /// response head is received, the connection goes back to the pool after
+    /// the response body has been read, if the server keeps it open.
+    ///
+    /// If an idle connection closes before the request could be written, the
+    /// request is sent on a new connection instead.
+    pub async fn send_request(&self, mut req: Request<B>) -> Result<Response<Incoming>, Error> {
+        let key = Key::new(req.uri())?;
+        let dst = req.uri().clone();
+        origin_form(&mut req, &key);
+
+        while let Some(mut tx) = self.checkout(&key) {
+            match tx.try_send_request(req).await {
+                Ok(res) => {
+                    self.checkin(key, tx);
+                    return Ok(res);
+                }
+                Err(mut err) => match err.take_message() {
+                    Some(unsent) => {
+                        debug!("pooled connection closed, retrying: {}", err.error());
+                        req = unsent;
+                    }
+                    None => return Err(Error::http(err.into_error())),
+                },
+            }
+        }
+
+        let mut tx = self.connect(&dst).await?;
+        let res = tx.send_request(req).await.map_err(Error::http)?;
+        self.checkin(key, tx);
+        Ok(res)
+    }
+
+    async fn connect(&self, dst: &Uri) -> Result<http1::SendRequest<B>, Error> {
+        let io = self
+            .connector
+            .connect(dst)
+            .await
+            .map_err(|err| Error::new(Kind::Connect(err.into())))?;
+        let (tx, conn) = self.http1.handshake(io).await.map_err(Error::http)?;
+        self.exec
+            .try_execute(Box::pin(async move {
+                if let Err(_err) = conn.await {
+                    debug!("pooled connection error: {}", _err);
+                }
+            }))
+            .map_err(|err| Error::http(crate::Error::new_execute(err)))?;
+        Ok(tx)
+    }
+
+    /// Takes the most recently used idle connection to `key`, if any.
+    fn checkout(&self, key: &Key) -> Option<http1::SendRequest<B>> {
+        let mut idle = self.idle.lock().unwrap();
+        let timeout = idle.timeout;
+        let conns = idle.conns.get_mut(key)?;
+        let mut found = None;
+        while let Some(entry) = conns.pop() {
+            if entry.tx.is_closed() || timeout.map_or(false, |t| entry.since.elapsed() > t) {
+                trace!("dropping expired pooled connection");
+                continue;
+            }
+            found = Some(entry.tx);
+            break;
+        }
+        if conns.is_empty() {
+            idle.conns.remove(key);
+        }
+        found
+    }
+
+    /// Returns the connection to the pool once it's ready for another
+    /// request, if it's kept open.
+    fn checkin(&self, key: Key, mut tx: http1::SendRequest<B>) {
+        if !tx.is_keep_alive() {
+            return;
+        }
+        let idle = self.idle.clone();
+        // If the executor refuses, the connection is just closed.
+        let _ = self.exec.try_execute(Box::pin(async move {
+            if tx.ready().await.is_err() || !tx.is_keep_alive() {
+                return;
+            }
+            let mut idle = idle.lock().unwrap();
+            let max = idle.max_per_host;
+            let conns = idle.conns.entry(key).or_default();
+            if conns.len() < max {
+                conns.push(Entry {
+                    tx,
+                    since: Instant::now(),
+                });
+            }
+        }));
+    }
+}
+
+impl<C, B> Clone for Pool<C, B> {
+    fn clone(&self) -> Self {
+        Pool {
+            connector: self.connector.clone(),
+            exec: self.exec.clone(),
+            http1: self.http1.clone(),
+            idle: self.idle.clone(),
+        }
+    }
+}
+
+impl<C, B> fmt::Debug for Pool<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("http1", &self.http1)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends `req` in origin-form, with a `Host` header for its authority.
+fn origin_form<B>(req: &mut Request<B>, key: &Key) {
+    if !req.headers().contains_key(HOST) {
+        let host =
+            HeaderValue::from_str(key.authority.as_str()).expect("authority is a valid header");
+        req.headers_mut().insert(HOST, host);
+    }
+    if req.method() == Method::CONNECT {
+        *req.uri_mut() = key.authority.clone().into();
+        return;
+    }
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str())
+        .parse()
+        .expect("path is a valid uri");
+    *req.uri_mut() = path;
+}
+
+// ===== impl Key
+
+impl Key {
+    fn new(uri: &Uri) -> Result<Key, Error> {
+        match (uri.scheme(), uri.authority()) {
+            (Some(scheme), Some(authority)) => Ok(Key {
+                scheme: scheme.clone(),
+                authority: authority.clone(),
+            }),
+            _ => Err(Error::new(Kind::Destination)),
+        }
+    }
+}
+
+// ===== impl Error
+
+impl Error {
+    fn new(kind: Kind) -> Error {
+        Error { kind }
+    }
+
+    fn http(err: crate::Error) -> Error {
+        Error::new(Kind::Http(err))
+    }
+
+    /// Returns true if the request URI has no scheme or authority.
+    pub fn is_destination(&self) -> bool {
+        matches!(self.kind, Kind::Destination)
+    }
+
+    /// Returns true if a connection couldn't be opened.
+    pub fn is_connect(&self) -> bool {
+        matches!(self.kind, Kind::Connect(_))
+    }
+
+    /// Returns the HTTP error, if the handshake or the request failed.
+    pub fn error(&self) -> Option<&crate::Error> {
+        match self.kind {
+            Kind::Http(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple("pool::Error");
+        match self.kind {
+            Kind::Destination => f.field(&"Destination"),
+            Kind::Connect(ref err) => f.field(&"Connect").field(err),
+            Kind::Http(ref err) => f.field(err),
+        };
+        f.finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Kind::Destination => f.write_str("request URI has no scheme or authority"),
+            Kind::Connect(ref err) => write!(f, "error opening connection: {}", err),
+            Kind::Http(ref err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self.kind {
+            Kind::Destination => None,
+            Kind::Connect(ref err) => Some(&**err),
+            Kind::Http(ref err) => Some(err),
+        }
+    }
+}
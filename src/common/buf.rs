@@ -1,8 +1,53 @@
 use std::collections::VecDeque;
+#[cfg(feature = "http1")]
+use std::fmt;
 use std::io::IoSlice;
+#[cfg(feature = "http1")]
+use std::sync::Arc;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+#[cfg(feature = "http1")]
+use crate::rt::BufferPool;
+
+/// A user-provided pool of HTTP/1 read buffers.
+///
+/// Without one, connections allocate their own.
+#[cfg(feature = "http1")]
+#[derive(Clone, Default)]
+pub(crate) struct BufPool(Option<Arc<dyn BufferPool + Send + Sync>>);
+
+#[cfg(feature = "http1")]
+impl fmt::Debug for BufPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufPool").finish()
+    }
+}
+
+#[cfg(feature = "http1")]
+impl BufPool {
+    pub(crate) fn new<P>(pool: P) -> BufPool
+    where
+        P: BufferPool + Send + Sync + 'static,
+    {
+        BufPool(Some(Arc::new(pool)))
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn acquire(&self) -> Option<BytesMut> {
+        self.0.as_ref().and_then(|pool| pool.acquire())
+    }
+
+    pub(crate) fn release(&self, buf: BytesMut) {
+        if let Some(ref pool) = self.0 {
+            pool.release(buf);
+        }
+    }
+}
+
 pub(crate) struct BufList<T> {
     bufs: VecDeque<T>,
 }
@@ -1,12 +1,14 @@
 use std::cell::RefCell;
 use std::fmt::{self, Write};
 use std::str;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-#[cfg(feature = "http2")]
 use http::header::HeaderValue;
 use httpdate::HttpDate;
 
+use crate::rt::Clock;
+
 // "Sun, 06 Nov 1994 08:49:37 GMT".len()
 pub(crate) const DATE_VALUE_LENGTH: usize = 29;
 
@@ -24,7 +26,6 @@ pub(crate) fn update() {
     })
 }
 
-#[cfg(feature = "http2")]
 pub(crate) fn update_and_header_value() -> HeaderValue {
     CACHED.with(|cache| {
         let mut cache = cache.borrow_mut();
@@ -33,10 +34,72 @@ pub(crate) fn update_and_header_value() -> HeaderValue {
     })
 }
 
+/// Where a server connection gets the time for `Date` headers from: the
+/// user's clock, if set, or else the cached system time.
+#[derive(Clone, Default)]
+pub(crate) struct DateHeader(Option<Arc<dyn Clock + Send + Sync>>);
+
+impl DateHeader {
+    pub(crate) fn new<C>(clock: C) -> DateHeader
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        DateHeader(Some(Arc::new(clock)))
+    }
+
+    /// Writes a `Date` header line, without the CRLF, calling `name` to
+    /// write its name. Returns false if there's no time to put in one.
+    #[cfg(feature = "http1")]
+    pub(crate) fn extend<F>(&self, dst: &mut Vec<u8>, name: F) -> bool
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        match self.0 {
+            // embedded builds don't assume they have a clock to read it from
+            None if cfg!(feature = "embedded") => false,
+            None => {
+                dst.reserve(DATE_VALUE_LENGTH + 8);
+                name(dst);
+                extend(dst);
+                true
+            }
+            Some(ref clock) => match clock.now() {
+                Some(now) => {
+                    dst.reserve(DATE_VALUE_LENGTH + 8);
+                    name(dst);
+                    // Formatted straight into the connection's headers
+                    // buffer, instead of a new `String` per response.
+                    std::io::Write::write_fmt(dst, format_args!("{}", HttpDate::from(now)))
+                        .expect("writing to a Vec never fails");
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// The value for a `Date` header, if there's a time to put in one.
+    #[cfg(any(feature = "http2", feature = "http3"))]
+    pub(crate) fn header_value(&self) -> Option<HeaderValue> {
+        match self.0 {
+            None => Some(update_and_header_value()),
+            Some(ref clock) => clock.now().map(|now| {
+                HeaderValue::from_str(&HttpDate::from(now).to_string())
+                    .expect("Date format should be valid HeaderValue")
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for DateHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DateHeader").finish()
+    }
+}
+
 struct CachedDate {
     bytes: [u8; DATE_VALUE_LENGTH],
     pos: usize,
-    #[cfg(feature = "http2")]
     header_value: HeaderValue,
     next_update: SystemTime,
 }
@@ -48,7 +111,6 @@ impl CachedDate {
         let mut cache = CachedDate {
             bytes: [0; DATE_VALUE_LENGTH],
             pos: 0,
-            #[cfg(feature = "http2")]
             header_value: HeaderValue::from_static(""),
             next_update: SystemTime::now(),
         };
@@ -76,17 +138,9 @@ impl CachedDate {
         self.pos = 0;
         let _ = write!(self, "{}", HttpDate::from(now));
         debug_assert!(self.pos == DATE_VALUE_LENGTH);
-        self.render_http2();
-    }
-
-    #[cfg(feature = "http2")]
-    fn render_http2(&mut self) {
         self.header_value = HeaderValue::from_bytes(self.buffer())
             .expect("Date format should be valid HeaderValue");
     }
-
-    #[cfg(not(feature = "http2"))]
-    fn render_http2(&mut self) {}
 }
 
 impl fmt::Write for CachedDate {
@@ -110,6 +164,17 @@ mod tests {
         assert_eq!(DATE_VALUE_LENGTH, "Sun, 06 Nov 1994 08:49:37 GMT".len());
     }
 
+    #[test]
+    fn test_header_value_is_http_date() {
+        let value = update_and_header_value();
+        assert_eq!(value.len(), DATE_VALUE_LENGTH);
+        value
+            .to_str()
+            .unwrap()
+            .parse::<HttpDate>()
+            .expect("valid HTTP-date");
+    }
+
     #[cfg(feature = "nightly")]
     #[bench]
     fn bench_date_check(b: &mut Bencher) {
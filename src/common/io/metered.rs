@@ -0,0 +1,74 @@
+use std::io;
+
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Pin, Poll};
+use crate::rt::{Read, ReadBufCursor, Write};
+
+/// Reports the bytes read from and written to the wrapped IO.
+///
+/// This is used for HTTP/2, where the IO is driven by `h2`. HTTP/1 counts
+/// bytes in its own buffering instead.
+#[derive(Debug)]
+pub(crate) struct Metered<T> {
+    inner: T,
+    metrics: MetricsSink,
+}
+
+impl<T> Metered<T> {
+    pub(crate) fn new(inner: T, metrics: MetricsSink) -> Self {
+        Metered { inner, metrics }
+    }
+}
+
+impl<T> Read for Metered<T>
+where
+    T: Read + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.remaining();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf.reborrow()))?;
+        self.metrics.bytes_read(before - buf.remaining());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Write for Metered<T>
+where
+    T: Write + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.metrics.bytes_written(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_write_vectored(cx, bufs))?;
+        self.metrics.bytes_written(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
@@ -1,7 +1,17 @@
 #[cfg(any(feature = "http2", test))]
 mod compat;
+#[cfg(feature = "http2")]
+mod metered;
 mod rewind;
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod write_timeout;
 
 #[cfg(any(feature = "http2", test))]
 pub(crate) use self::compat::{compat, Compat};
+#[cfg(feature = "http2")]
+pub(crate) use self::metered::Metered;
 pub(crate) use self::rewind::Rewind;
+#[cfg(feature = "http1")]
+pub(crate) use self::write_timeout::WriteTimer;
+#[cfg(feature = "http2")]
+pub(crate) use self::write_timeout::{restore_timed_out, WriteTimeout};
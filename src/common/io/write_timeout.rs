@@ -0,0 +1,166 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::common::time::Time;
+use crate::common::{task, Pin, Poll};
+use crate::rt::Sleep;
+#[cfg(feature = "http2")]
+use crate::rt::{Read, ReadBufCursor, Write};
+
+/// Fails writes that stay pending for longer than a timeout.
+///
+/// The timer starts when a write first returns `Pending`, and is cleared
+/// whenever a write makes progress. Without a timer, writes never time out.
+pub(crate) struct WriteTimer {
+    timeout: Option<Duration>,
+    sleep: Option<Pin<Box<dyn Sleep>>>,
+}
+
+impl WriteTimer {
+    pub(crate) fn new(timeout: Option<Duration>) -> WriteTimer {
+        WriteTimer {
+            timeout,
+            sleep: None,
+        }
+    }
+
+    pub(crate) fn poll<R>(
+        &mut self,
+        timer: &Time,
+        cx: &mut task::Context<'_>,
+        res: Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        if res.is_ready() {
+            self.sleep = None;
+            return res;
+        }
+
+        // Without a timer, there is nothing to time the writes with.
+        if let (Some(timeout), &Time::Timer(_)) = (self.timeout, timer) {
+            let sleep = self.sleep.get_or_insert_with(|| timer.sleep(timeout));
+            if sleep.as_mut().poll(cx).is_ready() {
+                self.sleep = None;
+                debug!("write timed out after {:?}", timeout);
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, WriteTimedOut)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl fmt::Debug for WriteTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteTimer")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Applies a `WriteTimer` to every write of the wrapped IO.
+///
+/// This is used for HTTP/2, where writes are driven by `h2`.
+#[cfg(feature = "http2")]
+#[derive(Debug)]
+pub(crate) struct WriteTimeout<T> {
+    inner: T,
+    time: Time,
+    write_timer: WriteTimer,
+}
+
+#[cfg(feature = "http2")]
+impl<T> WriteTimeout<T> {
+    pub(crate) fn new(inner: T, time: Time, timeout: Option<Duration>) -> Self {
+        WriteTimeout {
+            inner,
+            time,
+            write_timer: WriteTimer::new(timeout),
+        }
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<T> Read for WriteTimeout<T>
+where
+    T: Read + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<T> Write for WriteTimeout<T>
+where
+    T: Write + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let me = &mut *self;
+        let res = Pin::new(&mut me.inner).poll_write(cx, buf);
+        me.write_timer.poll(&me.time, cx, res)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let me = &mut *self;
+        let res = Pin::new(&mut me.inner).poll_write_vectored(cx, bufs);
+        me.write_timer.poll(&me.time, cx, res)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        let me = &mut *self;
+        let res = Pin::new(&mut me.inner).poll_flush(cx);
+        me.write_timer.poll(&me.time, cx, res)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Restores the source of a write timeout from an IO error `h2` returned.
+///
+/// `h2` only keeps the kind and message of the IO errors it sees, so a
+/// timeout of `WriteTimeout` is recognized by those.
+#[cfg(feature = "http2")]
+pub(crate) fn restore_timed_out(err: io::Error) -> io::Error {
+    let is_timed_out = err.kind() == io::ErrorKind::TimedOut
+        && err.get_ref().map_or(false, |inner| {
+            inner.to_string() == WriteTimedOut.to_string()
+        });
+    if is_timed_out {
+        io::Error::new(io::ErrorKind::TimedOut, WriteTimedOut)
+    } else {
+        err
+    }
+}
+
+#[derive(Debug)]
+struct WriteTimedOut;
+
+impl fmt::Display for WriteTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("write timed out")
+    }
+}
+
+impl std::error::Error for WriteTimedOut {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&crate::error::TimedOut)
+    }
+}
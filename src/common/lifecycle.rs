@@ -0,0 +1,139 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::rt::{CloseReason, ConnectionEvent};
+
+type Callback = dyn Fn(ConnectionEvent<'_>) + Send + Sync;
+
+/// A user-provided callback for connection lifecycle events.
+#[derive(Clone, Default)]
+pub(crate) struct EventSink(Option<Arc<Callback>>);
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventSink").finish()
+    }
+}
+
+impl EventSink {
+    pub(crate) fn new<F>(callback: F) -> EventSink
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        EventSink(Some(Arc::new(callback)))
+    }
+
+    /// Report a connection as opened.
+    pub(crate) fn opened(&self) -> OpenConnection {
+        OpenConnection(Lifecycle(self.0.as_ref().map(|callback| {
+            callback(ConnectionEvent::Opened);
+            Arc::new(Shared {
+                callback: callback.clone(),
+                in_flight: AtomicUsize::new(0),
+                started: AtomicBool::new(false),
+                closing: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
+            })
+        })))
+    }
+}
+
+/// Reports the events of a single connection.
+#[derive(Clone, Default)]
+pub(crate) struct Lifecycle(Option<Arc<Shared>>);
+
+struct Shared {
+    callback: Arc<Callback>,
+    in_flight: AtomicUsize,
+    started: AtomicBool,
+    #[cfg_attr(
+        not(any(feature = "server", feature = "http2", feature = "http3")),
+        allow(unused)
+    )]
+    closing: AtomicBool,
+    closed: AtomicBool,
+}
+
+impl Lifecycle {
+    /// Report a request as started, and as done once the returned guard is
+    /// dropped.
+    pub(crate) fn request_started(&self) -> InFlight {
+        if let Some(ref shared) = self.0 {
+            if shared.in_flight.fetch_add(1, Ordering::AcqRel) == 0 {
+                if shared.started.swap(true, Ordering::AcqRel) {
+                    shared.emit(ConnectionEvent::Resumed);
+                } else {
+                    shared.emit(ConnectionEvent::FirstRequest);
+                }
+            }
+        }
+        InFlight(self.0.clone())
+    }
+
+    #[cfg_attr(
+        not(any(feature = "server", feature = "http2", feature = "http3")),
+        allow(unused)
+    )]
+    pub(crate) fn closing(&self) {
+        if let Some(ref shared) = self.0 {
+            if !shared.closing.swap(true, Ordering::AcqRel) {
+                shared.emit(ConnectionEvent::Closing);
+            }
+        }
+    }
+
+    /// Report the connection as closed. Only the first report counts.
+    pub(crate) fn closed(&self, reason: CloseReason<'_>) {
+        if let Some(ref shared) = self.0 {
+            if !shared.closed.swap(true, Ordering::AcqRel) {
+                (shared.callback)(ConnectionEvent::Closed(reason));
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// Emit an event, unless the connection already closed.
+    fn emit(&self, event: ConnectionEvent<'_>) {
+        if !self.closed.load(Ordering::Acquire) {
+            (self.callback)(event);
+        }
+    }
+}
+
+impl fmt::Debug for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lifecycle").finish()
+    }
+}
+
+/// A request in flight on a connection.
+pub(crate) struct InFlight(Option<Arc<Shared>>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(ref shared) = self.0 {
+            if shared.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+                shared.emit(ConnectionEvent::Idle);
+            }
+        }
+    }
+}
+
+/// Held by the future driving a connection, to report it as dropped if it
+/// never reported closing.
+#[derive(Default)]
+pub(crate) struct OpenConnection(Lifecycle);
+
+impl OpenConnection {
+    pub(crate) fn lifecycle(&self) -> &Lifecycle {
+        &self.0
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.closed(CloseReason::Dropped);
+    }
+}
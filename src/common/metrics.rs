@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::rt::Metrics;
+
+/// A user-provided sink for connection and request metrics.
+///
+/// Without one, every method does nothing, and time isn't even read.
+///
+/// Client connections also keep their own count of the bytes read and
+/// written, which is shared with their `ByteCounts`.
+#[derive(Clone, Default)]
+pub(crate) struct MetricsSink {
+    metrics: Option<Arc<dyn Metrics + Send + Sync>>,
+    bytes: Option<Arc<ByteCounter>>,
+}
+
+impl fmt::Debug for MetricsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsSink").finish()
+    }
+}
+
+impl MetricsSink {
+    pub(crate) fn new<M>(metrics: M) -> MetricsSink
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        MetricsSink {
+            metrics: Some(Arc::new(metrics)),
+            bytes: None,
+        }
+    }
+
+    /// A copy of this sink that also counts bytes into `bytes`.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(crate) fn with_byte_counter(&self, bytes: Arc<ByteCounter>) -> MetricsSink {
+        MetricsSink {
+            metrics: self.metrics.clone(),
+            bytes: Some(bytes),
+        }
+    }
+
+    /// Report a connection as opened, and as closed once the returned guard
+    /// is dropped.
+    pub(crate) fn connection_opened(&self) -> Elapsed {
+        if let Some(ref metrics) = self.metrics {
+            metrics.connection_opened();
+        }
+        Elapsed::start(self, |metrics, lifetime| {
+            metrics.connection_closed(lifetime)
+        })
+    }
+
+    /// Start timing a request, reported once the returned guard is dropped.
+    pub(crate) fn request_started(&self) -> Elapsed {
+        Elapsed::start(self, |metrics, duration| {
+            metrics.request_completed(duration)
+        })
+    }
+
+    /// Start timing a handshake, to pass to `handshake_completed`.
+    #[cfg(feature = "http2")]
+    pub(crate) fn handshake_started(&self) -> Option<Instant> {
+        self.metrics.as_ref().map(|_| Instant::now())
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn handshake_completed(&self, started: Option<Instant>) {
+        if let (Some(ref metrics), Some(started)) = (&self.metrics, started) {
+            metrics.handshake_completed(started.elapsed());
+        }
+    }
+
+    pub(crate) fn bytes_read(&self, n: usize) {
+        if let Some(ref bytes) = self.bytes {
+            bytes.read.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        if let Some(ref metrics) = self.metrics {
+            metrics.bytes_read(n);
+        }
+    }
+
+    pub(crate) fn bytes_written(&self, n: usize) {
+        if let Some(ref bytes) = self.bytes {
+            bytes.written.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        if let Some(ref metrics) = self.metrics {
+            metrics.bytes_written(n);
+        }
+    }
+}
+
+/// The running totals of bytes read and written on a single connection.
+#[derive(Debug, Default)]
+pub(crate) struct ByteCounter {
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+#[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+impl ByteCounter {
+    pub(crate) fn read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+}
+
+type Report = fn(&(dyn Metrics + Send + Sync), std::time::Duration);
+
+/// Reports the time since it was created to a `MetricsSink` when dropped.
+pub(crate) struct Elapsed(Option<(Arc<dyn Metrics + Send + Sync>, Instant, Report)>);
+
+impl Elapsed {
+    fn start(sink: &MetricsSink, report: Report) -> Elapsed {
+        Elapsed(
+            sink.metrics
+                .as_ref()
+                .map(|metrics| (metrics.clone(), Instant::now(), report)),
+        )
+    }
+}
+
+impl Drop for Elapsed {
+    fn drop(&mut self) {
+        if let Some((ref metrics, started, report)) = self.0 {
+            report(&**metrics, started.elapsed());
+        }
+    }
+}
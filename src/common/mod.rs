@@ -8,15 +8,21 @@ macro_rules! ready {
 }
 
 pub(crate) mod buf;
-#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+#[cfg(all(
+    feature = "server",
+    any(feature = "http1", feature = "http2", feature = "http3")
+))]
 pub(crate) mod date;
 #[cfg(not(feature = "http2"))]
 pub(crate) mod exec;
 pub(crate) mod io;
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+pub(crate) mod lifecycle;
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+pub(crate) mod metrics;
 pub(crate) mod task;
 #[cfg(any(feature = "http1", feature = "http2", feature = "server"))]
 pub(crate) mod time;
-pub(crate) mod watch;
 
 pub(crate) use self::task::Poll;
 
@@ -81,4 +81,23 @@ impl Time {
             Time::Timer(ref t) => t.reset(sleep, new_deadline),
         }
     }
+
+    /// The timer's current instant, or the system clock's, without a timer.
+    #[cfg(all(feature = "client", feature = "http1"))]
+    pub(crate) fn now_or_system(&self) -> Instant {
+        match *self {
+            Time::Empty => Instant::now(),
+            Time::Timer(ref t) => t.now(),
+        }
+    }
+
+    #[cfg(all(feature = "server", feature = "http1"))]
+    pub(crate) fn now(&self) -> Instant {
+        match *self {
+            Time::Empty => {
+                panic!("You must supply a timer.")
+            }
+            Time::Timer(ref t) => t.now(),
+        }
+    }
 }
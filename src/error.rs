@@ -2,6 +2,9 @@
 use std::error::Error as StdError;
 use std::fmt;
 
+#[cfg(all(feature = "http2", feature = "client"))]
+use crate::client::conn::http2::GoAway;
+
 /// Result type often returned from methods that can have hyper `Error`s.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -52,16 +55,22 @@ pub(super) enum Kind {
     /// Indicates a channel (client or body sender) is closed.
     ChannelClosed,
     /// An `io::Error` that occurred while trying to read or write to a network stream.
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     Io,
     /// User took too long to send headers
     #[cfg(all(feature = "http1", feature = "server"))]
     HeaderTimeout,
+    /// Server took too long to send response headers
+    #[cfg(all(feature = "http1", feature = "client"))]
+    ResponseHeaderTimeout,
+    /// A request's deadline passed before its response was received
+    #[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+    RequestTimeout,
     /// Error while reading a body from connection.
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     Body,
     /// Error while writing a body to connection.
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     BodyWrite,
     /// Error calling AsyncWrite::shutdown()
     #[cfg(feature = "http1")]
@@ -70,6 +79,10 @@ pub(super) enum Kind {
     /// A general error from h2.
     #[cfg(feature = "http2")]
     Http2,
+
+    /// An HTTP/3 error, with its error code as the cause.
+    #[cfg(feature = "http3")]
+    Http3,
 }
 
 #[derive(Debug)]
@@ -84,6 +97,8 @@ pub(super) enum Parse {
     Header(Header),
     TooLarge,
     Status,
+    #[cfg(all(feature = "http1", feature = "server"))]
+    Violation(crate::server::conn::http1::Violation),
     #[cfg_attr(debug_assertions, allow(unused))]
     Internal,
 }
@@ -102,12 +117,16 @@ pub(super) enum Header {
 #[derive(Debug)]
 pub(super) enum User {
     /// Error calling user's Body::poll_data().
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     Body,
     /// The user aborted writing of the outgoing body.
     BodyWriteAborted,
     /// Error from future of user's Service.
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(
+        feature = "http1",
+        feature = "http2",
+        all(feature = "http3", feature = "server")
+    ))]
     Service,
     /// User tried to send a certain header in an unexpected context.
     ///
@@ -119,6 +138,16 @@ pub(super) enum User {
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     UnsupportedStatusCode,
+    /// User tried to send an extended CONNECT request with a protocol the
+    /// server doesn't support.
+    #[cfg(feature = "http3")]
+    #[cfg(feature = "client")]
+    UnsupportedProtocol,
+    /// User tried to send a request in absolute-form with neither an
+    /// absolute `Uri` nor a `Host` header.
+    #[cfg(feature = "http1")]
+    #[cfg(feature = "client")]
+    AbsoluteFormWithoutHost,
 
     /// User tried polling for an upgrade that doesn't exist.
     NoUpgrade,
@@ -131,6 +160,15 @@ pub(super) enum User {
     #[cfg(feature = "client")]
     DispatchGone,
 
+    /// The executor failed to spawn a task.
+    #[cfg(any(
+        feature = "http2",
+        feature = "http3",
+        feature = "ffi",
+        feature = "pool"
+    ))]
+    Execute,
+
     /// User aborted in an FFI callback.
     #[cfg(feature = "ffi")]
     AbortedByCallback,
@@ -185,11 +223,46 @@ impl Error {
         matches!(self.inner.kind, Kind::User(User::BodyWriteAborted))
     }
 
+    /// Returns true if the executor failed to spawn a task.
+    #[cfg(any(
+        feature = "http2",
+        feature = "http3",
+        feature = "ffi",
+        feature = "pool"
+    ))]
+    pub fn is_execute(&self) -> bool {
+        matches!(self.inner.kind, Kind::User(User::Execute))
+    }
+
     /// Returns true if the error was caused by a timeout.
     pub fn is_timeout(&self) -> bool {
         self.find_source::<TimedOut>().is_some()
     }
 
+    /// The `GOAWAY` frame that caused this error, if any.
+    ///
+    /// Errors of HTTP/2 client connections, and of their requests, have it
+    /// when the server sent one. `h2` keeps the last stream ID and the debug
+    /// data of the frame to itself, so only its error code is known.
+    #[cfg(all(feature = "http2", feature = "client"))]
+    pub fn h2_go_away(&self) -> Option<GoAway> {
+        self.find_source::<h2::Error>()
+            .filter(|err| err.is_go_away() && err.is_remote())
+            .and_then(h2::Error::reason)
+            .map(|reason| GoAway {
+                error_code: reason.into(),
+            })
+    }
+
+    /// The framing violation a request was rejected for, if any.
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn violation(&self) -> Option<crate::server::conn::http1::Violation> {
+        match self.inner.kind {
+            Kind::Parse(Parse::Violation(violation)) => Some(violation),
+            _ => None,
+        }
+    }
+
     pub(super) fn new(kind: Kind) -> Error {
         Error {
             inner: Box::new(ErrorImpl { kind, cause: None }),
@@ -228,6 +301,16 @@ impl Error {
             .unwrap_or(h2::Reason::INTERNAL_ERROR)
     }
 
+    #[cfg(feature = "http3")]
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn h3_code(&self) -> crate::proto::h3::Code {
+        // Find an HTTP/3 error code somewhere in the cause stack, if it
+        // exists, otherwise assume an INTERNAL_ERROR.
+        self.find_source::<crate::proto::h3::Code>()
+            .copied()
+            .unwrap_or(crate::proto::h3::Code::INTERNAL_ERROR)
+    }
+
     pub(super) fn new_canceled() -> Error {
         Error::new(Kind::Canceled)
     }
@@ -252,7 +335,7 @@ impl Error {
         Error::new(Kind::UnexpectedMessage)
     }
 
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     pub(super) fn new_io(cause: std::io::Error) -> Error {
         Error::new(Kind::Io).with(cause)
     }
@@ -261,12 +344,12 @@ impl Error {
         Error::new(Kind::ChannelClosed)
     }
 
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     pub(super) fn new_body<E: Into<Cause>>(cause: E) -> Error {
         Error::new(Kind::Body).with(cause)
     }
 
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     pub(super) fn new_body_write<E: Into<Cause>>(cause: E) -> Error {
         Error::new(Kind::BodyWrite).with(cause)
     }
@@ -285,17 +368,44 @@ impl Error {
         Error::new_user(User::UnexpectedHeader)
     }
 
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(crate) fn is_header_timeout(&self) -> bool {
+        matches!(self.inner.kind, Kind::HeaderTimeout)
+    }
+
     #[cfg(all(feature = "http1", feature = "server"))]
     pub(super) fn new_header_timeout() -> Error {
         Error::new(Kind::HeaderTimeout)
     }
 
+    #[cfg(all(feature = "http1", feature = "client"))]
+    pub(super) fn new_response_header_timeout() -> Error {
+        Error::new(Kind::ResponseHeaderTimeout).with(TimedOut)
+    }
+
+    #[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+    pub(super) fn new_request_timeout() -> Error {
+        Error::new(Kind::RequestTimeout).with(TimedOut)
+    }
+
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     pub(super) fn new_user_unsupported_status_code() -> Error {
         Error::new_user(User::UnsupportedStatusCode)
     }
 
+    #[cfg(feature = "http3")]
+    #[cfg(feature = "client")]
+    pub(super) fn new_user_unsupported_protocol() -> Error {
+        Error::new_user(User::UnsupportedProtocol)
+    }
+
+    #[cfg(feature = "http1")]
+    #[cfg(feature = "client")]
+    pub(super) fn new_user_absolute_form() -> Error {
+        Error::new_user(User::AbsoluteFormWithoutHost)
+    }
+
     pub(super) fn new_user_no_upgrade() -> Error {
         Error::new_user(User::NoUpgrade)
     }
@@ -305,12 +415,16 @@ impl Error {
         Error::new_user(User::ManualUpgrade)
     }
 
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(
+        feature = "http1",
+        feature = "http2",
+        all(feature = "http3", feature = "server")
+    ))]
     pub(super) fn new_user_service<E: Into<Cause>>(cause: E) -> Error {
         Error::new_user(User::Service).with(cause)
     }
 
-    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
     pub(super) fn new_user_body<E: Into<Cause>>(cause: E) -> Error {
         Error::new_user(User::Body).with(cause)
     }
@@ -330,15 +444,32 @@ impl Error {
         Error::new(Kind::User(User::DispatchGone))
     }
 
+    #[cfg(any(
+        feature = "http2",
+        feature = "http3",
+        feature = "ffi",
+        feature = "pool"
+    ))]
+    pub(super) fn new_execute<E: Into<Cause>>(cause: E) -> Error {
+        Error::new_user(User::Execute).with(cause)
+    }
+
     #[cfg(feature = "http2")]
     pub(super) fn new_h2(cause: ::h2::Error) -> Error {
         if cause.is_io() {
-            Error::new_io(cause.into_io().expect("h2::Error::is_io"))
+            let io = cause.into_io().expect("h2::Error::is_io");
+            Error::new_io(crate::common::io::restore_timed_out(io))
         } else {
             Error::new(Kind::Http2).with(cause)
         }
     }
 
+    #[cfg(feature = "http3")]
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn new_h3(code: crate::proto::h3::Code) -> Error {
+        Error::new(Kind::Http3).with(code)
+    }
+
     fn description(&self) -> &str {
         match self.inner.kind {
             Kind::Parse(Parse::Method) => "invalid HTTP method parsed",
@@ -362,6 +493,8 @@ impl Error {
             }
             Kind::Parse(Parse::TooLarge) => "message head is too large",
             Kind::Parse(Parse::Status) => "invalid HTTP status-code parsed",
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::Parse(Parse::Violation(violation)) => violation.description(),
             Kind::Parse(Parse::Internal) => {
                 "internal error inside Hyper and/or its dependencies, please report"
             }
@@ -372,21 +505,31 @@ impl Error {
             Kind::Canceled => "operation was canceled",
             #[cfg(all(feature = "http1", feature = "server"))]
             Kind::HeaderTimeout => "read header from client timeout",
-            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(all(feature = "http1", feature = "client"))]
+            Kind::ResponseHeaderTimeout => "read header from server timeout",
+            #[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+            Kind::RequestTimeout => "request deadline elapsed before response",
+            #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
             Kind::Body => "error reading a body from connection",
-            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
             Kind::BodyWrite => "error writing a body to connection",
             #[cfg(feature = "http1")]
             Kind::Shutdown => "error shutting down connection",
             #[cfg(feature = "http2")]
             Kind::Http2 => "http2 error",
-            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(feature = "http3")]
+            Kind::Http3 => "http3 error",
+            #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
             Kind::Io => "connection error",
 
-            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
             Kind::User(User::Body) => "error from user's Body stream",
             Kind::User(User::BodyWriteAborted) => "user body write aborted",
-            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(any(
+                feature = "http1",
+                feature = "http2",
+                all(feature = "http3", feature = "server")
+            ))]
             Kind::User(User::Service) => "error from user's Service",
             #[cfg(any(feature = "http1", feature = "http2"))]
             #[cfg(feature = "server")]
@@ -396,11 +539,28 @@ impl Error {
             Kind::User(User::UnsupportedStatusCode) => {
                 "response has 1xx status code, not supported by server"
             }
+            #[cfg(feature = "http3")]
+            #[cfg(feature = "client")]
+            Kind::User(User::UnsupportedProtocol) => {
+                "extended CONNECT protocol not supported by server"
+            }
+            #[cfg(feature = "http1")]
+            #[cfg(feature = "client")]
+            Kind::User(User::AbsoluteFormWithoutHost) => {
+                "absolute-form request has no authority or Host header"
+            }
             Kind::User(User::NoUpgrade) => "no upgrade available",
             #[cfg(feature = "http1")]
             Kind::User(User::ManualUpgrade) => "upgrade expected but low level API in use",
             #[cfg(feature = "client")]
             Kind::User(User::DispatchGone) => "dispatch task is gone",
+            #[cfg(any(
+                feature = "http2",
+                feature = "http3",
+                feature = "ffi",
+                feature = "pool"
+            ))]
+            Kind::User(User::Execute) => "executor failed to spawn task",
             #[cfg(feature = "ffi")]
             Kind::User(User::AbortedByCallback) => "operation aborted by an application callback",
         }
@@ -541,4 +701,13 @@ mod tests {
         let svc_err = Error::new_user_service(recvd);
         assert_eq!(svc_err.h2_reason(), h2::Reason::HTTP_1_1_REQUIRED);
     }
+
+    #[cfg(all(feature = "http2", feature = "client"))]
+    #[test]
+    fn h2_go_away_only_from_peer() {
+        // A reason of our own, not from a received GOAWAY.
+        let err = Error::new_h2(h2::Reason::ENHANCE_YOUR_CALM.into());
+        assert_eq!(err.h2_reason(), h2::Reason::ENHANCE_YOUR_CALM);
+        assert!(err.h2_go_away().is_none());
+    }
 }
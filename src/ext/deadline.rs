@@ -0,0 +1,64 @@
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use crate::common::time::Time;
+use crate::rt::Sleep;
+
+/// A deadline for getting the response to a request.
+///
+/// Put it in the extensions of a request sent with a client connection's
+/// `send_request` or `try_send_request`, and if the response hasn't been
+/// received by then, the request is canceled, and fails with an error for
+/// which [`Error::is_timeout`](crate::Error::is_timeout) returns true. An
+/// HTTP/2 stream is reset, and an HTTP/1 connection is closed, since it
+/// can't be used for anything else while the response is outstanding.
+///
+/// The deadline covers writing the request and reading the head of the
+/// response, not reading the response body. It is enforced with the timer
+/// the connection was built with, so the connection's builder must have
+/// one.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "client"))]
+/// # fn doc() {
+/// use std::time::Duration;
+///
+/// use http_body_util::Empty;
+/// use hyper::body::Bytes;
+/// use hyper::ext::Deadline;
+/// use hyper::Request;
+///
+/// let mut req = Request::new(Empty::<Bytes>::new());
+/// req.extensions_mut().insert(Deadline::after(Duration::from_secs(10)));
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(Kind);
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    At(Instant),
+    After(Duration),
+}
+
+impl Deadline {
+    /// A deadline at the given instant.
+    pub fn at(deadline: Instant) -> Deadline {
+        Deadline(Kind::At(deadline))
+    }
+
+    /// A deadline the given duration after the request is sent.
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline(Kind::After(timeout))
+    }
+
+    pub(crate) fn sleep(&self, timer: &Time) -> Pin<Box<dyn Sleep>> {
+        match self.0 {
+            Kind::At(deadline) => timer.sleep_until(deadline),
+            Kind::After(timeout) => timer.sleep(timeout),
+        }
+    }
+}
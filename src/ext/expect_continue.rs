@@ -0,0 +1,117 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::task::AtomicWaker;
+
+const UNDECIDED: u8 = 0;
+const SEND: u8 = 1;
+const SKIP: u8 = 2;
+
+/// Decides whether to send `100 Continue` for a request with
+/// `Expect: 100-continue`.
+///
+/// HTTP/1 server connections built with
+/// [`http1::Builder::manual_expect_continue`](crate::server::conn::http1::Builder::manual_expect_continue)
+/// enabled put it in the extensions of such requests, instead of sending
+/// `100 Continue` as soon as the body is read.
+///
+/// Calling [`send`](ExpectContinue::send) lets the `100 Continue` be
+/// written when the body is read. Dropping it without calling `send`, such
+/// as to answer with a `401 Unauthorized` or `413 Content Too Large`
+/// right away, means no `100 Continue` is sent. Reading the body while
+/// still holding it waits until one or the other happens.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc(mut req: hyper::Request<hyper::body::Incoming>, allowed: bool) {
+/// use hyper::ext::ExpectContinue;
+///
+/// if let Some(expect) = req.extensions_mut().remove::<ExpectContinue>() {
+///     if allowed {
+///         expect.send();
+///     }
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+pub struct ExpectContinue {
+    shared: Arc<Shared>,
+}
+
+/// The connection's side of an [`ExpectContinue`].
+pub(crate) struct PendingContinue {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    decision: AtomicU8,
+    task: AtomicWaker,
+}
+
+impl ExpectContinue {
+    pub(crate) fn new() -> (ExpectContinue, PendingContinue) {
+        let shared = Arc::new(Shared {
+            decision: AtomicU8::new(UNDECIDED),
+            task: AtomicWaker::new(),
+        });
+        (
+            ExpectContinue {
+                shared: shared.clone(),
+            },
+            PendingContinue { shared },
+        )
+    }
+
+    /// Send `100 Continue`, once the body is read.
+    pub fn send(self) {
+        self.decide(SEND);
+    }
+
+    fn decide(&self, decision: u8) {
+        if self
+            .shared
+            .decision
+            .compare_exchange(UNDECIDED, decision, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.shared.task.wake();
+        }
+    }
+}
+
+impl Drop for ExpectContinue {
+    fn drop(&mut self) {
+        self.decide(SKIP);
+    }
+}
+
+impl fmt::Debug for ExpectContinue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpectContinue").finish()
+    }
+}
+
+impl PendingContinue {
+    /// Whether `100 Continue` should be sent, once decided.
+    pub(crate) fn poll_decision(&self, cx: &mut Context<'_>) -> Poll<bool> {
+        match self.shared.decision.load(Ordering::Acquire) {
+            UNDECIDED => {}
+            decision => return Poll::Ready(decision == SEND),
+        }
+        self.shared.task.register(cx.waker());
+        match self.shared.decision.load(Ordering::Acquire) {
+            UNDECIDED => Poll::Pending,
+            decision => Poll::Ready(decision == SEND),
+        }
+    }
+}
+
+impl fmt::Debug for PendingContinue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingContinue").finish()
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use bytes::Bytes;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::common::{task, Poll};
+
+/// The [HTTP datagrams] associated with an HTTP/3 CONNECT request.
+///
+/// # Servers
+///
+/// Once [enabled](crate::server::conn::http3::Builder::enable_datagrams) on
+/// a connection, an `H3Datagrams` will be present in the extensions of
+/// CONNECT requests.
+///
+/// # Clients
+///
+/// Once [enabled](crate::client::conn::http3::Builder::enable_datagrams) on
+/// a connection, an `H3Datagrams` will be present in the extensions of the
+/// 2xx response to a CONNECT request.
+///
+/// Datagrams are sent in QUIC datagrams, so they need a transport that
+/// supports them, through [`Connection::datagrams`], and a peer that enabled
+/// them too. Otherwise, or once too many are queued, datagrams are dropped,
+/// just as they may be lost on the way.
+///
+/// [HTTP datagrams]: https://www.rfc-editor.org/rfc/rfc9297.html
+/// [`Connection::datagrams`]: crate::rt::quic::Connection::datagrams
+pub struct H3Datagrams {
+    stream_id: u64,
+    incoming: mpsc::Receiver<Bytes>,
+    outgoing: mpsc::Sender<(u64, Bytes)>,
+}
+
+impl H3Datagrams {
+    pub(crate) fn new(
+        stream_id: u64,
+        incoming: mpsc::Receiver<Bytes>,
+        outgoing: mpsc::Sender<(u64, Bytes)>,
+    ) -> Self {
+        H3Datagrams {
+            stream_id,
+            incoming,
+            outgoing,
+        }
+    }
+
+    /// The ID of the stream of the request the datagrams are associated
+    /// with.
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
+    /// Sends a datagram with `payload`.
+    ///
+    /// Returns an error if the connection is closed.
+    pub fn send(&self, payload: Bytes) -> crate::Result<()> {
+        match self.outgoing.try_send((self.stream_id, payload)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                trace!("datagram queue full, dropping one");
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(crate::Error::new_closed()),
+        }
+    }
+
+    /// Polls for the payload of the next datagram received.
+    ///
+    /// Returns `Poll::Ready(None)` once the connection is closed.
+    pub fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> Poll<Option<Bytes>> {
+        self.incoming.poll_recv(cx)
+    }
+
+    /// Receives the payload of the next datagram.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        futures_util::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+impl fmt::Debug for H3Datagrams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H3Datagrams")
+            .field("stream_id", &self.stream_id)
+            .finish()
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::sync::Arc;
+
+use http::{HeaderMap, Request, Response};
+
+/// Add a callback for 1xx informational responses to a request.
+///
+/// HTTP/1 client connections call it with the head of each `100 Continue`,
+/// `102 Processing`, `103 Early Hints`, or other interim response received
+/// for the request, before the final response is returned. The response it
+/// is passed never has a body.
+///
+/// HTTP/2 client connections never call it: `h2` skips interim responses
+/// without passing them on.
+///
+/// The callback may be called from whichever task is driving the
+/// connection, so it should not block.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "client"))]
+/// # fn doc() {
+/// use http_body_util::Empty;
+/// use hyper::body::Bytes;
+/// use hyper::Request;
+///
+/// let mut req = Request::new(Empty::<Bytes>::new());
+/// hyper::ext::on_informational(&mut req, |res| {
+///     // 103 Early Hints
+///     if res.status().as_u16() == 103 {
+///         for link in res.headers().get_all("link") {
+///             println!("early hint: {:?}", link);
+///         }
+///     }
+/// });
+/// # }
+/// # fn main() {}
+/// ```
+pub fn on_informational<B, F>(req: &mut Request<B>, callback: F)
+where
+    F: Fn(&Response<()>) + Send + Sync + 'static,
+{
+    on_informational_raw(req, OnInformationalClosure(callback));
+}
+
+pub(crate) fn on_informational_raw<B, C>(req: &mut Request<B>, callback: C)
+where
+    C: OnInformationalCallback + Send + Sync + 'static,
+{
+    req.extensions_mut()
+        .insert(OnInformational(Arc::new(callback)));
+}
+
+/// Receives the heads of informational responses.
+///
+/// This is separate from the closure taken by `on_informational` so the C
+/// API can register its callbacks the same way.
+pub(crate) trait OnInformationalCallback {
+    fn on_informational(&self, res: Response<()>);
+}
+
+/// The callback registered on a request, kept in its extensions.
+#[derive(Clone)]
+pub(crate) struct OnInformational(Arc<dyn OnInformationalCallback + Send + Sync>);
+
+impl OnInformational {
+    pub(crate) fn call(&self, res: Response<()>) {
+        self.0.on_informational(res);
+    }
+}
+
+impl fmt::Debug for OnInformational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnInformational").finish()
+    }
+}
+
+struct OnInformationalClosure<F>(F);
+
+impl<F> OnInformationalCallback for OnInformationalClosure<F>
+where
+    F: Fn(&Response<()>),
+{
+    fn on_informational(&self, res: Response<()>) {
+        (self.0)(&res);
+    }
+}
+
+/// The headers of `103 Early Hints` responses received before a final
+/// response.
+///
+/// HTTP/1 client connections put this in the extensions of the final
+/// response, if any `103 Early Hints` responses preceded it. A server may
+/// send several, and the headers of each are appended, in order, so the
+/// `Link` headers to preload can be read from a single map.
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "client"))]
+/// # fn doc(res: hyper::Response<hyper::body::Incoming>) {
+/// use hyper::ext::EarlyHints;
+///
+/// if let Some(hints) = res.extensions().get::<EarlyHints>() {
+///     for link in hints.headers().get_all("link") {
+///         println!("preload: {:?}", link);
+///     }
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// To act on the hints before the final response arrives, use
+/// [`on_informational`] instead.
+#[derive(Clone, Debug, Default)]
+pub struct EarlyHints {
+    headers: HeaderMap,
+}
+
+impl EarlyHints {
+    /// The headers of all the `103 Early Hints` responses received.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Consumes the hints, returning their headers.
+    pub fn into_headers(self) -> HeaderMap {
+        self.headers
+    }
+
+    pub(crate) fn extend(&mut self, headers: HeaderMap) {
+        let mut name = None;
+        for (next, value) in headers {
+            if next.is_some() {
+                name = next;
+            }
+            if let Some(ref name) = name {
+                self.headers.append(name, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_util::task::AtomicWaker;
+use http::{Response, StatusCode};
+
+/// Sends informational (1xx) responses ahead of the final response to a
+/// request.
+///
+/// HTTP/1 server connections put it in the extensions of each HTTP/1.1
+/// request they receive. Responses sent with it, such as `103 Early Hints`
+/// or `102 Processing`, are written before the final response returned by
+/// the service. HTTP/1.0 requests don't get one, since HTTP/1.0 clients
+/// don't expect informational responses.
+///
+/// HTTP/2 server connections don't support sending them yet.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc(req: hyper::Request<hyper::body::Incoming>) {
+/// use hyper::ext::InformationalSender;
+/// use hyper::{Response, StatusCode};
+///
+/// if let Some(sender) = req.extensions().get::<InformationalSender>() {
+///     let hints = Response::builder()
+///         .status(StatusCode::from_u16(103).unwrap())
+///         .header("link", "</style.css>; rel=preload; as=style")
+///         .body(())
+///         .unwrap();
+///     let _ = sender.send(hints);
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone)]
+pub struct InformationalSender {
+    shared: Arc<Shared>,
+    request: u64,
+}
+
+/// The connection's side of the [`InformationalSender`]s given to its
+/// requests.
+pub(crate) struct InformationalQueue {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    task: AtomicWaker,
+}
+
+struct State {
+    /// The request whose final response hasn't been started yet, if any.
+    current: Option<u64>,
+    next: u64,
+    queued: Vec<Response<()>>,
+}
+
+impl InformationalSender {
+    /// Send an informational response.
+    ///
+    /// It is written by the task driving the connection, after any sent
+    /// before it. The response is given back if it can't be sent: its
+    /// status isn't 1xx, or is `101 Switching Protocols`, which upgrades
+    /// use instead; the final response to the request was already started;
+    /// or the connection is gone.
+    pub fn send(&self, res: Response<()>) -> Result<(), Response<()>> {
+        if !res.status().is_informational() || res.status() == StatusCode::SWITCHING_PROTOCOLS {
+            return Err(res);
+        }
+        let mut state = self.shared.state.lock().unwrap();
+        if state.current != Some(self.request) {
+            return Err(res);
+        }
+        state.queued.push(res);
+        drop(state);
+        self.shared.task.wake();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for InformationalSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InformationalSender").finish()
+    }
+}
+
+impl InformationalQueue {
+    pub(crate) fn new() -> InformationalQueue {
+        InformationalQueue {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    current: None,
+                    next: 0,
+                    queued: Vec::new(),
+                }),
+                task: AtomicWaker::new(),
+            }),
+        }
+    }
+
+    /// Starts accepting responses for a new request.
+    pub(crate) fn start_request(&mut self) -> InformationalSender {
+        let mut state = self.shared.state.lock().unwrap();
+        let request = state.next;
+        state.next += 1;
+        state.current = Some(request);
+        state.queued.clear();
+        InformationalSender {
+            shared: self.shared.clone(),
+            request,
+        }
+    }
+
+    /// Takes the responses sent so far, registering to be woken when more
+    /// are.
+    pub(crate) fn poll_queued(&mut self, cx: &mut Context<'_>) -> Poll<Vec<Response<()>>> {
+        self.shared.task.register(cx.waker());
+        let mut state = self.shared.state.lock().unwrap();
+        if state.queued.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(mem::take(&mut state.queued))
+        }
+    }
+
+    /// Stops accepting responses for the current request, since its final
+    /// response is being written, and takes those still queued.
+    pub(crate) fn finish_request(&mut self) -> Vec<Response<()>> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.current = None;
+        mem::take(&mut state.queued)
+    }
+}
+
+impl Drop for InformationalQueue {
+    fn drop(&mut self) {
+        self.finish_request();
+    }
+}
+
+impl fmt::Debug for InformationalQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InformationalQueue").finish()
+    }
+}
@@ -1,79 +1,171 @@
 //! HTTP extensions.
 
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 use bytes::Bytes;
 #[cfg(any(feature = "http1", feature = "ffi"))]
 use http::header::HeaderName;
 #[cfg(feature = "http1")]
-use http::header::{IntoHeaderName, ValueIter};
+use http::header::{IntoHeaderName, InvalidHeaderName, ValueIter};
+#[cfg(feature = "http1")]
 use http::HeaderMap;
-#[cfg(feature = "ffi")]
+#[cfg(any(
+    all(feature = "http1", any(feature = "client", feature = "server")),
+    feature = "ffi"
+))]
 use std::collections::HashMap;
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 use std::fmt;
 
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+mod deadline;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "client"))]
+pub use deadline::Deadline;
+#[cfg(all(feature = "http1", feature = "server"))]
+mod expect_continue;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub use expect_continue::ExpectContinue;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(crate) use expect_continue::PendingContinue;
 #[cfg(any(feature = "http1", feature = "ffi"))]
 mod h1_reason_phrase;
 #[cfg(any(feature = "http1", feature = "ffi"))]
 pub use h1_reason_phrase::ReasonPhrase;
+#[cfg(all(feature = "http3", any(feature = "client", feature = "server")))]
+mod h3_datagrams;
+#[cfg(all(feature = "http3", any(feature = "client", feature = "server")))]
+pub use h3_datagrams::H3Datagrams;
+#[cfg(all(feature = "http1", feature = "server"))]
+mod informational_sender;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(crate) use informational_sender::InformationalQueue;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub use informational_sender::InformationalSender;
+#[cfg(all(feature = "http1", feature = "client"))]
+mod informational;
+#[cfg(all(feature = "http1", feature = "client"))]
+pub(crate) use informational::OnInformational;
+#[cfg(all(feature = "http1", feature = "client"))]
+pub use informational::{on_informational, EarlyHints};
+#[cfg(feature = "ffi")]
+pub(crate) use informational::{on_informational_raw, OnInformationalCallback};
+#[cfg(all(feature = "http2", feature = "client"))]
+mod priority;
+#[cfg(all(feature = "http2", feature = "client"))]
+pub use priority::Priority;
+#[cfg(all(feature = "http2", feature = "client"))]
+pub(crate) use priority::PRIORITY;
+#[cfg(all(feature = "http1", feature = "server"))]
+mod raw_request_head;
+#[cfg(all(feature = "http1", feature = "server"))]
+pub use raw_request_head::RawRequestHead;
+#[cfg(all(feature = "http2", feature = "client"))]
+mod stream_window;
+#[cfg(all(feature = "http2", feature = "client"))]
+pub use stream_window::StreamWindow;
+#[cfg(all(feature = "http1", feature = "client"))]
+mod timings;
+#[cfg(all(feature = "http1", feature = "client"))]
+pub use timings::ResponseTimings;
+#[cfg(all(feature = "http3", any(feature = "client", feature = "server")))]
+mod webtransport;
+#[cfg(all(feature = "http3", any(feature = "client", feature = "server")))]
+pub use webtransport::WebTransportSession;
+
+/// Returns the current time as an HTTP-date, such as
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+///
+/// This is the value server connections put in `Date` headers when no
+/// [`Clock`](crate::rt::Clock) is set. It is rendered at most once a second
+/// and cached per thread, so services can use it, such as in access logs,
+/// without formatting the time for each request. Cloning it is cheap.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "server")]
+/// # fn doc() {
+/// let date = hyper::ext::cached_date();
+/// println!("[{}] GET /", date.to_str().unwrap());
+/// # }
+/// # fn main() {}
+/// ```
+#[cfg(all(
+    feature = "server",
+    any(feature = "http1", feature = "http2", feature = "http3")
+))]
+pub fn cached_date() -> http::HeaderValue {
+    crate::common::date::update_and_header_value()
+}
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 /// Represents the `:protocol` pseudo-header used by
 /// the [Extended CONNECT Protocol].
 ///
 /// [Extended CONNECT Protocol]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
 #[derive(Clone, Eq, PartialEq)]
 pub struct Protocol {
-    inner: h2::ext::Protocol,
+    // always valid UTF-8
+    inner: Bytes,
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl Protocol {
     /// Converts a static string to a protocol name.
     pub const fn from_static(value: &'static str) -> Self {
         Self {
-            inner: h2::ext::Protocol::from_static(value),
+            inner: Bytes::from_static(value.as_bytes()),
         }
     }
 
     /// Returns a str representation of the header.
     pub fn as_str(&self) -> &str {
-        self.inner.as_str()
+        // SAFETY: only ever constructed from a str
+        unsafe { std::str::from_utf8_unchecked(&self.inner) }
     }
 
-    #[cfg(feature = "server")]
+    #[cfg(all(feature = "http2", feature = "server"))]
     pub(crate) fn from_inner(inner: h2::ext::Protocol) -> Self {
-        Self { inner }
+        Self::from(inner.as_str())
     }
 
+    #[cfg(feature = "http2")]
     pub(crate) fn into_inner(self) -> h2::ext::Protocol {
-        self.inner
+        h2::ext::Protocol::from(self.as_str())
+    }
+
+    #[cfg(all(feature = "http3", feature = "server"))]
+    pub(crate) fn from_utf8(value: Bytes) -> Option<Self> {
+        std::str::from_utf8(&value).ok()?;
+        Some(Self { inner: value })
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl<'a> From<&'a str> for Protocol {
     fn from(value: &'a str) -> Self {
         Self {
-            inner: h2::ext::Protocol::from(value),
+            inner: Bytes::copy_from_slice(value.as_bytes()),
         }
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl AsRef<[u8]> for Protocol {
     fn as_ref(&self) -> &[u8] {
-        self.inner.as_ref()
+        &self.inner
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 impl fmt::Debug for Protocol {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.inner.fmt(f)
+        self.as_str().fmt(f)
     }
 }
 
-/// A map from header names to their original casing as received in an HTTP message.
+/// A map from header names to their original casing.
+///
+/// # Receiving
 ///
 /// If an HTTP/1 response `res` is parsed on a connection whose option
 /// [`preserve_header_case`] was set to true and the response included
@@ -93,15 +185,71 @@ impl fmt::Debug for Protocol {
 /// })
 /// ```
 ///
-/// [`preserve_header_case`]: /client/struct.Client.html#method.preserve_header_case
-#[derive(Clone, Debug)]
-pub(crate) struct HeaderCaseMap(HeaderMap<Bytes>);
+/// # Sending
+///
+/// HTTP/1 connections write the headers of an outgoing message with the
+/// casing in its `HeaderCaseMap` extension, if it has one, for servers that
+/// expect a header spelled exactly. The spellings of a header name are used
+/// for its values in order. Any values without a spelling are written in
+/// lowercase, or title case if the connection's `title_case_headers` option
+/// is set.
+///
+/// ```
+/// # #[cfg(feature = "http1")]
+/// # fn doc() -> Result<(), Box<dyn std::error::Error>> {
+/// use http_body_util::Empty;
+/// use hyper::body::Bytes;
+/// use hyper::ext::HeaderCaseMap;
+/// use hyper::Request;
+///
+/// let mut casing = HeaderCaseMap::new();
+/// casing.insert("SOAPAction")?;
+///
+/// let mut req = Request::post("/service")
+///     .header("soapaction", "\"urn:example#GetQuote\"")
+///     .body(Empty::<Bytes>::new())?;
+/// req.extensions_mut().insert(casing);
+/// // written as `SOAPAction: "urn:example#GetQuote"`
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// [`preserve_header_case`]: crate::client::conn::http1::Builder::preserve_header_case
+#[cfg(feature = "http1")]
+#[derive(Clone, Debug, Default)]
+pub struct HeaderCaseMap(HeaderMap<Bytes>);
 
 #[cfg(feature = "http1")]
 impl HeaderCaseMap {
+    /// Creates an empty map.
+    pub fn new() -> HeaderCaseMap {
+        HeaderCaseMap::default()
+    }
+
+    /// Sets `spelling` as the casing of the header name it spells, ignoring
+    /// case, replacing any it had.
+    ///
+    /// Returns an error if `spelling` isn't a valid header name.
+    pub fn insert(&mut self, spelling: &str) -> Result<(), InvalidHeaderName> {
+        let name = HeaderName::from_bytes(spelling.as_bytes())?;
+        self.insert_raw(name, Bytes::copy_from_slice(spelling.as_bytes()));
+        Ok(())
+    }
+
+    /// Adds `spelling` as the casing of the next value of the header name
+    /// it spells, ignoring case.
+    ///
+    /// Returns an error if `spelling` isn't a valid header name.
+    pub fn append(&mut self, spelling: &str) -> Result<(), InvalidHeaderName> {
+        let name = HeaderName::from_bytes(spelling.as_bytes())?;
+        self.append_raw(name, Bytes::copy_from_slice(spelling.as_bytes()));
+        Ok(())
+    }
+
     /// Returns a view of all spellings associated with that header name,
     /// in the order they were found.
-    pub(crate) fn get_all<'a>(
+    pub fn get_all<'a>(
         &'a self,
         name: &HeaderName,
     ) -> impl Iterator<Item = impl AsRef<[u8]> + 'a> + 'a {
@@ -114,27 +262,61 @@ impl HeaderCaseMap {
         self.0.get_all(name).into_iter()
     }
 
-    pub(crate) fn default() -> Self {
-        Self(Default::default())
+    /// Creates a map with room for `capacity` headers, so parsing a message
+    /// allocates it once.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self(HeaderMap::with_capacity(capacity))
     }
 
-    #[cfg(any(test, feature = "ffi"))]
-    pub(crate) fn insert(&mut self, name: HeaderName, orig: Bytes) {
+    pub(crate) fn insert_raw(&mut self, name: HeaderName, orig: Bytes) {
         self.0.insert(name, orig);
     }
 
-    pub(crate) fn append<N>(&mut self, name: N, orig: Bytes)
+    pub(crate) fn append_raw<N>(&mut self, name: N, orig: Bytes)
     where
         N: IntoHeaderName,
     {
         self.0.append(name, orig);
     }
+
+    #[cfg(feature = "ffi")]
+    pub(crate) fn remove(&mut self, name: &HeaderName) {
+        self.0.remove(name);
+    }
 }
 
-#[cfg(feature = "ffi")]
+/// The order in which the headers of an HTTP/1 message were received.
+///
+/// A `HeaderMap` keeps the values of each header name in order, but not the
+/// order of headers with different names relative to each other. If a
+/// client connection's [`preserve_header_order`] option is set, each
+/// response it receives has this extension, recording that order, and
+/// likewise for the requests a server connection receives with its
+/// [`preserve_header_order`](crate::server::conn::http1::Builder::preserve_header_order)
+/// option.
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "client"))]
+/// # fn doc(res: hyper::Response<hyper::body::Incoming>) {
+/// use hyper::ext::OriginalHeaderOrder;
+///
+/// if let Some(order) = res.extensions().get::<OriginalHeaderOrder>() {
+///     for (name, idx) in order.get_in_order() {
+///         let value = res.headers().get_all(name).iter().nth(*idx).unwrap();
+///         println!("{}: {:?}", name, value);
+///     }
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// [`preserve_header_order`]: crate::client::conn::http1::Builder::preserve_header_order
+#[cfg(any(
+    all(feature = "http1", any(feature = "client", feature = "server")),
+    feature = "ffi"
+))]
 #[derive(Clone, Debug)]
-/// Hashmap<Headername, numheaders with that name>
-pub(crate) struct OriginalHeaderOrder {
+pub struct OriginalHeaderOrder {
     /// Stores how many entries a Headername maps to. This is used
     /// for accounting.
     num_entries: HashMap<HeaderName, usize>,
@@ -146,8 +328,12 @@ pub(crate) struct OriginalHeaderOrder {
     entry_order: Vec<(HeaderName, usize)>,
 }
 
-#[cfg(all(feature = "http1", feature = "ffi"))]
+#[cfg(all(
+    feature = "http1",
+    any(feature = "client", feature = "server", feature = "ffi")
+))]
 impl OriginalHeaderOrder {
+    #[cfg(feature = "ffi")]
     pub(crate) fn default() -> Self {
         OriginalHeaderOrder {
             num_entries: HashMap::new(),
@@ -155,6 +341,14 @@ impl OriginalHeaderOrder {
         }
     }
 
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        OriginalHeaderOrder {
+            num_entries: HashMap::with_capacity(capacity),
+            entry_order: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[cfg(feature = "ffi")]
     pub(crate) fn insert(&mut self, name: HeaderName) {
         if !self.num_entries.contains_key(&name) {
             let idx = 0;
@@ -182,47 +376,19 @@ impl OriginalHeaderOrder {
         self.entry_order.push((name, idx));
     }
 
-    // No doc test is run here because `RUSTFLAGS='--cfg hyper_unstable_ffi'`
-    // is needed to compile. Once ffi is stablized `no_run` should be removed
-    // here.
-    /// This returns an iterator that provides header names and indexes
-    /// in the original order received.
-    ///
-    /// # Examples
-    /// ```no_run
-    /// use hyper::ext::OriginalHeaderOrder;
-    /// use hyper::header::{HeaderName, HeaderValue, HeaderMap};
-    ///
-    /// let mut h_order = OriginalHeaderOrder::default();
-    /// let mut h_map = Headermap::new();
-    ///
-    /// let name1 = b"Set-CookiE";
-    /// let value1 = b"a=b";
-    /// h_map.append(name1);
-    /// h_order.append(name1);
-    ///
-    /// let name2 = b"Content-Encoding";
-    /// let value2 = b"gzip";
-    /// h_map.append(name2, value2);
-    /// h_order.append(name2);
-    ///
-    /// let name3 = b"SET-COOKIE";
-    /// let value3 = b"c=d";
-    /// h_map.append(name3, value3);
-    /// h_order.append(name3)
-    ///
-    /// let mut iter = h_order.get_in_order()
-    ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"a=b", h_map.get_all(name).nth(idx).unwrap());
-    ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"gzip", h_map.get_all(name).nth(idx).unwrap());
+    #[cfg(feature = "ffi")]
+    pub(crate) fn remove(&mut self, name: &HeaderName) {
+        if self.num_entries.remove(name).is_some() {
+            self.entry_order.retain(|(n, _)| n != name);
+        }
+    }
+
+    /// Returns an iterator over the headers in the order they were
+    /// received, as their name and the index of their value among those
+    /// with the same name.
     ///
-    /// let (name, idx) = iter.next();
-    /// assert_eq!(b"c=d", h_map.get_all(name).nth(idx).unwrap());
-    /// ```
-    pub(crate) fn get_in_order(&self) -> impl Iterator<Item = &(HeaderName, usize)> {
+    /// The value of each is `headers.get_all(name).iter().nth(idx)`.
+    pub fn get_in_order(&self) -> impl Iterator<Item = &(HeaderName, usize)> {
         self.entry_order.iter()
     }
 }
@@ -0,0 +1,81 @@
+use http::header::{HeaderName, HeaderValue};
+
+/// The `priority` header field, from RFC 9218.
+pub(crate) const PRIORITY: HeaderName = HeaderName::from_static("priority");
+
+/// Priority signals for a request, as defined by [RFC 9218][rfc9218].
+///
+/// Put it in the extensions of a request sent on an HTTP/2 client
+/// connection, and it is sent as the `priority` header field, unless the
+/// request already has one. Servers use it to decide which responses to
+/// send first, but are free to ignore it.
+///
+/// The legacy priority scheme of RFC 7540, with stream weights and
+/// dependencies, is deprecated, and not supported.
+///
+/// [rfc9218]: https://www.rfc-editor.org/rfc/rfc9218.html
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http2", feature = "client"))]
+/// # fn doc() {
+/// use http_body_util::Empty;
+/// use hyper::body::Bytes;
+/// use hyper::ext::Priority;
+/// use hyper::Request;
+///
+/// let mut req = Request::new(Empty::<Bytes>::new());
+/// req.extensions_mut().insert(Priority::new(1, true));
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Priority {
+    urgency: u8,
+    incremental: bool,
+}
+
+impl Priority {
+    /// Priority signals with the given urgency, and whether the response
+    /// can be processed incrementally.
+    ///
+    /// Urgency goes from 0, the highest priority, to 7, the lowest.
+    ///
+    /// # Panics
+    ///
+    /// The urgency must be no larger than 7.
+    pub fn new(urgency: u8, incremental: bool) -> Priority {
+        assert!(urgency <= 7, "urgency must be 0 to 7");
+        Priority {
+            urgency,
+            incremental,
+        }
+    }
+
+    /// The urgency, from 0, the highest priority, to 7, the lowest.
+    pub fn urgency(&self) -> u8 {
+        self.urgency
+    }
+
+    /// Whether the response can be processed incrementally.
+    pub fn is_incremental(&self) -> bool {
+        self.incremental
+    }
+
+    pub(crate) fn to_header_value(self) -> HeaderValue {
+        let value = if self.incremental {
+            format!("u={}, i", self.urgency)
+        } else {
+            format!("u={}", self.urgency)
+        };
+        HeaderValue::from_str(&value).expect("priority is a valid header value")
+    }
+}
+
+impl Default for Priority {
+    /// The default priority of RFC 9218: urgency 3, and not incremental.
+    fn default() -> Priority {
+        Priority::new(3, false)
+    }
+}
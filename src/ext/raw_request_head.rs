@@ -0,0 +1,45 @@
+use bytes::Bytes;
+
+/// The raw bytes of an HTTP/1 request's head, as received.
+///
+/// If a server connection's
+/// [`preserve_raw_head`](crate::server::conn::http1::Builder::preserve_raw_head)
+/// option is set, each request it receives has this extension, holding its
+/// request line and headers, up to and including the empty line ending
+/// them. This is useful for logging requests the service rejects exactly as
+/// the client sent them. It shares the buffer the request was parsed from,
+/// so it isn't copied, and is never longer than the connection's
+/// `max_buf_size`.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc(req: hyper::Request<hyper::body::Incoming>) {
+/// use hyper::ext::RawRequestHead;
+///
+/// if let Some(raw) = req.extensions().get::<RawRequestHead>() {
+///     eprintln!("rejected: {:?}", String::from_utf8_lossy(raw.as_bytes()));
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawRequestHead(Bytes);
+
+impl RawRequestHead {
+    pub(crate) fn new(head: Bytes) -> RawRequestHead {
+        RawRequestHead(head)
+    }
+
+    /// Gets the head as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<RawRequestHead> for Bytes {
+    fn from(head: RawRequestHead) -> Bytes {
+        head.0
+    }
+}
@@ -0,0 +1,47 @@
+/// The flow-control window for receiving the response to a request.
+///
+/// Put it in the extensions of a request sent on an HTTP/2 client
+/// connection to give the response's stream a smaller receive window than
+/// the connection's [`initial_stream_window_size`][initial], such as for
+/// small RPCs sharing a connection with large downloads.
+///
+/// The window of a single stream can't be made larger than the initial
+/// window: `h2` only sends a WINDOW_UPDATE for data already received, so
+/// a larger size is ignored. Configure the connection with the largest
+/// window its requests need, and use this to lower it for the others. The window shrinks as
+/// response data is received, by withholding updates to it, so a server
+/// may still send up to the initial window at first. It isn't applied
+/// when the connection uses an [adaptive window][adaptive].
+///
+/// [initial]: crate::client::conn::http2::Builder::initial_stream_window_size
+/// [adaptive]: crate::client::conn::http2::Builder::adaptive_window
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http2", feature = "client"))]
+/// # fn doc() {
+/// use http_body_util::Empty;
+/// use hyper::body::Bytes;
+/// use hyper::ext::StreamWindow;
+/// use hyper::Request;
+///
+/// let mut req = Request::new(Empty::<Bytes>::new());
+/// req.extensions_mut().insert(StreamWindow::new(64 * 1024));
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamWindow(u32);
+
+impl StreamWindow {
+    /// A receive window of the given size, in bytes.
+    pub fn new(size: u32) -> StreamWindow {
+        StreamWindow(size)
+    }
+
+    /// The size of the window, in bytes.
+    pub fn size(&self) -> u32 {
+        self.0
+    }
+}
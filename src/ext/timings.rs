@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+/// When the steps of an HTTP/1 request-response exchange happened.
+///
+/// Client connections built with
+/// [`http1::Builder::response_timings`](crate::client::conn::http1::Builder::response_timings)
+/// enabled put it in the extensions of each response they receive.
+///
+/// The instants are read from the timer the connection was built with, if
+/// any, or else from the system clock.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "client"))]
+/// # fn doc(res: hyper::Response<hyper::body::Incoming>) {
+/// use hyper::ext::ResponseTimings;
+///
+/// if let Some(timings) = res.extensions().get::<ResponseTimings>() {
+///     println!("time to first byte: {:?}", timings.time_to_first_byte());
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseTimings {
+    pub(crate) request_written: Instant,
+    pub(crate) first_byte: Instant,
+    pub(crate) headers_received: Instant,
+}
+
+impl ResponseTimings {
+    /// When the head of the request was flushed to the transport.
+    ///
+    /// The body may still have been being written afterwards.
+    pub fn request_written(&self) -> Instant {
+        self.request_written
+    }
+
+    /// When the first byte of the response was read, including any 1xx
+    /// informational responses before the final one.
+    pub fn first_byte(&self) -> Instant {
+        self.first_byte
+    }
+
+    /// When the head of the final response was parsed.
+    pub fn headers_received(&self) -> Instant {
+        self.headers_received
+    }
+
+    /// The time from writing the request to reading the first byte of the
+    /// response.
+    pub fn time_to_first_byte(&self) -> Duration {
+        self.first_byte
+            .saturating_duration_since(self.request_written)
+    }
+
+    /// The time from reading the first byte of the response to having
+    /// parsed its head.
+    pub fn header_duration(&self) -> Duration {
+        self.headers_received
+            .saturating_duration_since(self.first_byte)
+    }
+}
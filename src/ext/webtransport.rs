@@ -0,0 +1,102 @@
+use std::fmt;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::common::{task, Future, Poll};
+use crate::proto::h3::{OpenStream, StreamRef};
+use crate::upgrade::Upgraded;
+
+/// A [WebTransport] session, established by an HTTP/3 extended CONNECT
+/// request.
+///
+/// # Servers
+///
+/// Once [enabled](crate::server::conn::http3::Builder::enable_webtransport)
+/// on a connection, a `WebTransportSession` will be present in the
+/// extensions of requests whose [`Protocol`](super::Protocol) is
+/// `webtransport`. Responding with a 2xx status code establishes it.
+///
+/// # Clients
+///
+/// Once [enabled](crate::client::conn::http3::Builder::enable_webtransport)
+/// on a connection, a `WebTransportSession` will be present in the
+/// extensions of the 2xx response to a CONNECT request whose `Protocol` is
+/// `webtransport`.
+///
+/// The streams of a session are [`Upgraded`], just like the stream of its
+/// CONNECT request, which is available through
+/// [`upgrade::on`](crate::upgrade::on) and ends the session when closed.
+/// Dropping a `WebTransportSession` only refuses the streams the peer opens
+/// in it afterwards.
+///
+/// [WebTransport]: https://datatracker.ietf.org/doc/html/draft-ietf-webtrans-http3-02
+pub struct WebTransportSession {
+    id: u64,
+    incoming: mpsc::UnboundedReceiver<Upgraded>,
+    opener: mpsc::UnboundedSender<OpenStream>,
+    _stream: StreamRef,
+}
+
+impl WebTransportSession {
+    pub(crate) fn new(
+        id: u64,
+        incoming: mpsc::UnboundedReceiver<Upgraded>,
+        opener: mpsc::UnboundedSender<OpenStream>,
+        stream: StreamRef,
+    ) -> Self {
+        WebTransportSession {
+            id,
+            incoming,
+            opener,
+            _stream: stream,
+        }
+    }
+
+    /// The ID of the session, which is that of the stream of its CONNECT
+    /// request.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Polls for the next bidirectional stream the peer opened in this
+    /// session.
+    ///
+    /// Returns `Poll::Ready(None)` once the connection is closed.
+    pub fn poll_accept_bi(&mut self, cx: &mut task::Context<'_>) -> Poll<Option<Upgraded>> {
+        self.incoming.poll_recv(cx)
+    }
+
+    /// Accepts the next bidirectional stream the peer opened in this
+    /// session.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub async fn accept_bi(&mut self) -> Option<Upgraded> {
+        futures_util::future::poll_fn(|cx| self.poll_accept_bi(cx)).await
+    }
+
+    /// Opens a bidirectional stream in this session.
+    ///
+    /// The peer only learns of the stream once something is written to it.
+    pub fn open_bi(&self) -> impl Future<Output = crate::Result<Upgraded>> {
+        let (tx, rx) = oneshot::channel();
+        let sent = self.opener.send(OpenStream {
+            session_id: self.id,
+            tx,
+        });
+
+        async move {
+            if sent.is_err() {
+                return Err(crate::Error::new_closed());
+            }
+            rx.await.unwrap_or_else(|_| Err(crate::Error::new_closed()))
+        }
+    }
+}
+
+impl fmt::Debug for WebTransportSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebTransportSession")
+            .field("id", &self.id)
+            .finish()
+    }
+}
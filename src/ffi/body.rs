@@ -1,24 +1,65 @@
 use std::ffi::c_void;
 use std::mem::ManuallyDrop;
 use std::ptr;
-use std::task::{Context, Poll};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
 use http_body_util::BodyExt as _;
 use libc::{c_int, size_t};
 
+use super::error::hyper_code;
+use super::http_types::{hyper_headers_foreach_callback, raw_name_value};
 use super::task::{hyper_context, hyper_task, hyper_task_return_type, AsTaskType};
-use super::{UserDataPointer, HYPER_ITER_CONTINUE};
+use super::{hyper_userdata_drop, UserDataPointer, Userdata, HYPER_ITER_CONTINUE};
 use crate::body::{Bytes, Frame, Incoming as IncomingBody};
+use crate::HeaderMap;
 
 /// A streaming HTTP body.
-pub struct hyper_body(pub(super) IncomingBody);
+pub struct hyper_body(
+    pub(super) IncomingBody,
+    // Trailers received by `hyper_body_data`, kept until they are asked for
+    // with `hyper_body_foreach_trailer`.
+    Option<HeaderMap>,
+);
 
 /// A buffer of bytes that is sent or received on a `hyper_body`.
 pub struct hyper_buf(pub(crate) Bytes);
 
+/// Pushes chunks of data into a `hyper_body`.
+pub struct hyper_body_writer {
+    chan: Arc<Mutex<BodyChannel>>,
+}
+
 pub(crate) struct UserBody {
     data_func: hyper_body_data_callback,
-    userdata: *mut c_void,
+    userdata: Userdata,
+    // Boxed, as it is rarely used, to keep `Incoming` small.
+    extra: Option<Box<Extra>>,
+}
+
+/// The less common state of a `UserBody`.
+#[derive(Default)]
+struct Extra {
+    chan: Option<Arc<Mutex<BodyChannel>>>,
+    trailers: Option<HeaderMap>,
+}
+
+/// State shared by a `hyper_body_writer` and the body it writes to.
+struct BodyChannel {
+    /// The chunk waiting to be sent. Only one is buffered at a time.
+    chunk: Option<Bytes>,
+    writer: WriterState,
+    /// Set once the body has been dropped, so chunks can't be sent anymore.
+    body_closed: bool,
+    body_waker: Option<Waker>,
+    ready_func: Option<(hyper_body_writer_ready_callback, UserDataPointer)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WriterState {
+    Open,
+    Finished,
+    Aborted,
 }
 
 // ===== Body =====
@@ -28,15 +69,18 @@ type hyper_body_foreach_callback = extern "C" fn(*mut c_void, *const hyper_buf)
 type hyper_body_data_callback =
     extern "C" fn(*mut c_void, *mut hyper_context<'_>, *mut *mut hyper_buf) -> c_int;
 
+type hyper_body_writer_ready_callback = extern "C" fn(*mut c_void);
+
 ffi_fn! {
     /// Create a new "empty" body.
     ///
     /// If not configured, this body acts as an empty payload.
     ///
     /// To avoid a memory leak, the body must eventually be consumed by
-    /// `hyper_body_free`, `hyper_body_foreach`, or `hyper_request_set_body`.
+    /// `hyper_body_free`, `hyper_body_foreach`, `hyper_body_foreach_trailer`,
+    /// or `hyper_request_set_body`.
     fn hyper_body_new() -> *mut hyper_body {
-        Box::into_raw(Box::new(hyper_body(IncomingBody::ffi())))
+        Box::into_raw(Box::new(hyper_body::new(IncomingBody::ffi())))
     } ?= ptr::null_mut()
 }
 
@@ -59,6 +103,9 @@ ffi_fn! {
     /// - `HYPER_TASK_ERROR`: An error retrieving the data.
     /// - `HYPER_TASK_EMPTY`: The body has finished streaming data.
     ///
+    /// Any trailers received after the data are kept, and can be read with
+    /// `hyper_body_foreach_trailer`.
+    ///
     /// To avoid a memory leak, the task must eventually be consumed by
     /// `hyper_task_free`, or taken ownership of by `hyper_executor_push`
     /// without subsequently being given back by `hyper_executor_poll`.
@@ -73,10 +120,14 @@ ffi_fn! {
             loop {
                 match body.0.frame().await {
                     Some(Ok(frame)) => {
-                        if let Ok(data) = frame.into_data() {
-                            return Ok(Some(hyper_buf(data)));
-                        } else {
-                            continue;
+                        match frame.into_data() {
+                            Ok(data) => return Ok(Some(hyper_buf(data))),
+                            Err(frame) => {
+                                if let Ok(trailers) = frame.into_trailers() {
+                                    body.1 = Some(trailers);
+                                }
+                                continue;
+                            }
                         }
                     },
                     Some(Err(e)) => return Err(e),
@@ -96,7 +147,8 @@ ffi_fn! {
     /// without subsequently being given back by `hyper_executor_poll`.
     ///
     /// The `hyper_buf` pointer is only a borrowed reference, it cannot live outside
-    /// the execution of the callback. You must make a copy to retain it.
+    /// the execution of the callback. To retain the chunk, take a reference to
+    /// it with `hyper_buf_clone`, which doesn't copy the bytes.
     ///
     /// The callback should return `HYPER_ITER_CONTINUE` to continue iterating
     /// chunks as they are received, or `HYPER_ITER_BREAK` to cancel.
@@ -120,11 +172,88 @@ ffi_fn! {
     } ?= ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Return a task that will poll the body to its end, and execute the
+    /// callback with each trailer name and value pair.
+    ///
+    /// Trailers already received by `hyper_body_data` are passed first. Any
+    /// data still left in the body is discarded.
+    ///
+    /// Trailers are received on HTTP/2 connections. HTTP/1 connections
+    /// skip the trailers of a chunked body, so the task completes without
+    /// calling the callback, as it does if the body had none.
+    ///
+    /// To avoid a memory leak, the task must eventually be consumed by
+    /// `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+    /// without subsequently being given back by `hyper_executor_poll`.
+    ///
+    /// The name and value pointers are only borrowed, they cannot live outside
+    /// the execution of the callback. You must make a copy to retain them.
+    ///
+    /// The callback should return `HYPER_ITER_CONTINUE` to keep iterating, or
+    /// `HYPER_ITER_BREAK` to stop.
+    ///
+    /// This will consume the `hyper_body *`, you shouldn't use it anymore or free it.
+    fn hyper_body_foreach_trailer(body: *mut hyper_body, func: hyper_headers_foreach_callback, userdata: *mut c_void) -> *mut hyper_task {
+        let mut body = non_null!(Box::from_raw(body) ?= ptr::null_mut());
+        let userdata = UserDataPointer(userdata);
+
+        Box::into_raw(hyper_task::boxed(async move {
+            let mut trailers = body.1.take();
+            while let Some(item) = body.0.frame().await {
+                if let Ok(t) = item?.into_trailers() {
+                    trailers = Some(t);
+                }
+            }
+
+            for (name, value) in trailers.iter().flatten() {
+                let name = name.as_str().as_bytes();
+                let value = value.as_bytes();
+                if HYPER_ITER_CONTINUE != func(userdata.0, name.as_ptr(), name.len(), value.as_ptr(), value.len()) {
+                    break;
+                }
+            }
+            Ok(())
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Add a trailer to send once all of the data of this body is sent.
+    ///
+    /// If there were already trailers with the same name, this appends the
+    /// new value to them.
+    ///
+    /// On HTTP/2 connections, all trailers are sent. On HTTP/1 connections,
+    /// they are only sent with a chunked request body, and only the fields
+    /// named in the request's `Trailer` header; the others are discarded.
+    ///
+    /// This must be called before the body is passed to
+    /// `hyper_request_set_body`.
+    fn hyper_body_add_trailer(body: *mut hyper_body, name: *const u8, name_len: size_t, value: *const u8, value_len: size_t) -> hyper_code {
+        let b = non_null!(&mut *body ?= hyper_code::HYPERE_INVALID_ARG);
+        match unsafe { raw_name_value(name, name_len, value, value_len) } {
+            Ok((name, value, _)) => {
+                b.0.as_ffi_mut()
+                    .extra_mut()
+                    .trailers
+                    .get_or_insert_with(HeaderMap::new)
+                    .append(name, value);
+                hyper_code::HYPERE_OK
+            }
+            Err(code) => code,
+        }
+    }
+}
+
 ffi_fn! {
     /// Set userdata on this body, which will be passed to callback functions.
-    fn hyper_body_set_userdata(body: *mut hyper_body, userdata: *mut c_void) {
+    ///
+    /// If `drop` isn't `NULL`, it is called with the userdata once the body
+    /// is dropped, or given another userdata.
+    fn hyper_body_set_userdata(body: *mut hyper_body, userdata: *mut c_void, drop: Option<hyper_userdata_drop>) {
         let b = non_null!(&mut *body ?= ());
-        b.0.as_ffi_mut().userdata = userdata;
+        b.0.as_ffi_mut().userdata = Userdata::new(userdata, drop);
     }
 }
 
@@ -154,22 +283,176 @@ ffi_fn! {
     }
 }
 
+impl hyper_body {
+    pub(super) fn new(body: IncomingBody) -> hyper_body {
+        hyper_body(body, None)
+    }
+}
+
+// ===== Body writer =====
+
+ffi_fn! {
+    /// Create a writer to push chunks of data into this body.
+    ///
+    /// Once a writer is created, the body sends the chunks passed to
+    /// `hyper_body_write_chunk`, instead of calling the data callback set
+    /// with `hyper_body_set_data_func`.
+    ///
+    /// This does not consume the `hyper_body *`, which can still be passed
+    /// to `hyper_request_set_body`.
+    ///
+    /// To avoid a memory leak, the writer must eventually be consumed by
+    /// `hyper_body_writer_finish` or `hyper_body_writer_free`.
+    fn hyper_body_writer_new(body: *mut hyper_body) -> *mut hyper_body_writer {
+        let b = non_null!(&mut *body ?= ptr::null_mut());
+        let chan = Arc::new(Mutex::new(BodyChannel {
+            chunk: None,
+            writer: WriterState::Open,
+            body_closed: false,
+            body_waker: None,
+            ready_func: None,
+        }));
+        b.0.as_ffi_mut().extra_mut().chan = Some(chan.clone());
+        Box::into_raw(Box::new(hyper_body_writer { chan }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Set a callback to be told when the writer can accept another chunk.
+    ///
+    /// The callback is called with `userdata` each time the connection takes
+    /// the chunk waiting in the writer, and once when the body is dropped. It
+    /// is called from within `hyper_executor_poll`, and may call
+    /// `hyper_body_write_chunk` itself.
+    fn hyper_body_writer_set_ready_func(writer: *mut hyper_body_writer, func: hyper_body_writer_ready_callback, userdata: *mut c_void) {
+        let w = non_null!(&mut *writer ?= ());
+        w.chan.lock().unwrap().ready_func = Some((func, UserDataPointer(userdata)));
+    }
+}
+
+ffi_fn! {
+    /// Push a chunk of data into the body, by copying the provided bytes.
+    ///
+    /// Only one chunk is buffered at a time. If the previous chunk hasn't
+    /// been taken by the connection yet, this returns `HYPERE_NOT_READY`
+    /// without copying anything; wait for the callback set with
+    /// `hyper_body_writer_set_ready_func` and try again.
+    ///
+    /// Returns `HYPERE_CLOSED` if the body has been dropped, such as when the
+    /// connection closed.
+    ///
+    /// An empty chunk is ignored, and `buf` may be `NULL` if `len` is 0.
+    fn hyper_body_write_chunk(writer: *mut hyper_body_writer, buf: *const u8, len: size_t) -> hyper_code {
+        let w = non_null!(&mut *writer ?= hyper_code::HYPERE_INVALID_ARG);
+        let mut chan = w.chan.lock().unwrap();
+        if chan.body_closed {
+            return hyper_code::HYPERE_CLOSED;
+        }
+        if len == 0 {
+            return hyper_code::HYPERE_OK;
+        }
+        if buf.is_null() {
+            return hyper_code::HYPERE_INVALID_ARG;
+        }
+        if chan.chunk.is_some() {
+            return hyper_code::HYPERE_NOT_READY;
+        }
+        let slice = unsafe {
+            std::slice::from_raw_parts(buf, len)
+        };
+        chan.chunk = Some(Bytes::copy_from_slice(slice));
+        if let Some(waker) = chan.body_waker.take() {
+            drop(chan);
+            waker.wake();
+        }
+        hyper_code::HYPERE_OK
+    } ?= hyper_code::HYPERE_ERROR
+}
+
+ffi_fn! {
+    /// End the body, once any chunk still waiting in the writer is sent.
+    ///
+    /// This consumes the `hyper_body_writer *`, you shouldn't use it anymore
+    /// or free it.
+    fn hyper_body_writer_finish(writer: *mut hyper_body_writer) -> hyper_code {
+        let w = non_null!(Box::from_raw(writer) ?= hyper_code::HYPERE_INVALID_ARG);
+        w.close(WriterState::Finished);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Free a body writer.
+    ///
+    /// If the writer wasn't finished with `hyper_body_writer_finish`, the
+    /// body is aborted with an error.
+    fn hyper_body_writer_free(writer: *mut hyper_body_writer) {
+        drop(non_null!(Box::from_raw(writer) ?= ()));
+    }
+}
+
+impl hyper_body_writer {
+    fn close(&self, state: WriterState) {
+        let mut chan = self.chan.lock().unwrap();
+        if chan.writer != WriterState::Open {
+            return;
+        }
+        chan.writer = state;
+        if let Some(waker) = chan.body_waker.take() {
+            drop(chan);
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for hyper_body_writer {
+    fn drop(&mut self) {
+        self.close(WriterState::Aborted);
+    }
+}
+
 // ===== impl UserBody =====
 
 impl UserBody {
     pub(crate) fn new() -> UserBody {
         UserBody {
             data_func: data_noop,
-            userdata: std::ptr::null_mut(),
+            userdata: Userdata::empty(),
+            extra: None,
         }
     }
 
+    fn extra_mut(&mut self) -> &mut Extra {
+        self.extra.get_or_insert_with(Default::default)
+    }
+
+    fn chan(&self) -> Option<&Arc<Mutex<BodyChannel>>> {
+        self.extra.as_ref().and_then(|extra| extra.chan.as_ref())
+    }
+
     pub(crate) fn poll_data(
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+        match ready!(self.poll_user_data(cx)) {
+            None => {
+                let trailers = self.extra.as_mut().and_then(|extra| extra.trailers.take());
+                Poll::Ready(trailers.map(|t| Ok(Frame::trailers(t))))
+            }
+            frame => Poll::Ready(frame),
+        }
+    }
+
+    fn poll_user_data(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+        if let Some(chan) = self.chan() {
+            return poll_chan(chan, cx);
+        }
+
         let mut out = std::ptr::null_mut();
-        match (self.data_func)(self.userdata, hyper_context::wrap(cx), &mut out) {
+        match (self.data_func)(self.userdata.as_ptr(), hyper_context::wrap(cx), &mut out) {
             super::task::HYPER_POLL_READY => {
                 if out.is_null() {
                     Poll::Ready(None)
@@ -190,6 +473,45 @@ impl UserBody {
     }
 }
 
+impl Drop for UserBody {
+    fn drop(&mut self) {
+        if let Some(chan) = self.chan() {
+            let mut chan = chan.lock().unwrap();
+            chan.body_closed = true;
+            // Tell the writer, which may be waiting to send a chunk.
+            let ready_func = chan.ready_func.take().map(|(func, ud)| (func, ud.0));
+            drop(chan);
+            if let Some((func, userdata)) = ready_func {
+                func(userdata);
+            }
+        }
+    }
+}
+
+fn poll_chan(
+    chan: &Mutex<BodyChannel>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+    let mut chan = chan.lock().unwrap();
+    if let Some(chunk) = chan.chunk.take() {
+        let ready_func = chan.ready_func.as_ref().map(|(func, ud)| (*func, ud.0));
+        // The callback may write another chunk, so it can't hold the lock.
+        drop(chan);
+        if let Some((func, userdata)) = ready_func {
+            func(userdata);
+        }
+        return Poll::Ready(Some(Ok(Frame::data(chunk))));
+    }
+    match chan.writer {
+        WriterState::Open => {
+            chan.body_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+        WriterState::Finished => Poll::Ready(None),
+        WriterState::Aborted => Poll::Ready(Some(Err(crate::Error::new_body_write_aborted()))),
+    }
+}
+
 /// cbindgen:ignore
 extern "C" fn data_noop(
     _userdata: *mut c_void,
@@ -222,6 +544,44 @@ ffi_fn! {
     } ?= ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Create a new `hyper_buf *` referencing the same bytes as `buf`.
+    ///
+    /// The bytes aren't copied. They are reference counted, and stay valid
+    /// until every `hyper_buf` referencing them has been freed, so the new
+    /// buffer can outlive `buf`. This is the cheap way to keep the chunks
+    /// passed to a `hyper_body_foreach` callback.
+    ///
+    /// To avoid a memory leak, the new buffer must eventually be consumed by
+    /// `hyper_buf_free`.
+    fn hyper_buf_clone(buf: *const hyper_buf) -> *mut hyper_buf {
+        let buf = non_null!(&*buf ?= ptr::null_mut());
+        Box::into_raw(Box::new(hyper_buf(buf.0.clone())))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Create a new `hyper_buf *` referencing `len` bytes of `buf`, starting
+    /// at `offset`.
+    ///
+    /// Like `hyper_buf_clone`, the bytes aren't copied, and the new buffer
+    /// can outlive `buf`.
+    ///
+    /// To avoid a memory leak, the new buffer must eventually be consumed by
+    /// `hyper_buf_free`.
+    ///
+    /// This returns `NULL` if the range is out of bounds of `buf`.
+    fn hyper_buf_slice(buf: *const hyper_buf, offset: size_t, len: size_t) -> *mut hyper_buf {
+        let buf = non_null!(&*buf ?= ptr::null_mut());
+        match offset.checked_add(len) {
+            Some(end) if end <= buf.0.len() => {
+                Box::into_raw(Box::new(hyper_buf(buf.0.slice(offset..end))))
+            }
+            _ => ptr::null_mut(),
+        }
+    } ?= ptr::null_mut()
+}
+
 ffi_fn! {
     /// Get a pointer to the bytes in this buffer.
     ///
@@ -256,3 +616,141 @@ unsafe impl AsTaskType for hyper_buf {
         hyper_task_return_type::HYPER_TASK_BUF
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::super::task::{
+        hyper_executor_free, hyper_executor_new, hyper_executor_poll, hyper_executor_push,
+        hyper_task_free, hyper_task_type,
+    };
+    use super::*;
+
+    extern "C" fn count_ready(userdata: *mut c_void) {
+        let count = unsafe { &*(userdata as *const AtomicUsize) };
+        count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[tokio::test]
+    async fn body_writer_one_chunk_at_a_time() {
+        let body = hyper_body_new();
+        let writer = hyper_body_writer_new(body);
+        let ready = AtomicUsize::new(0);
+        hyper_body_writer_set_ready_func(writer, count_ready, &ready as *const _ as *mut c_void);
+        let mut body = unsafe { Box::from_raw(body) };
+
+        let code = hyper_body_write_chunk(writer, b"hello".as_ptr(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        let code = hyper_body_write_chunk(writer, b"world".as_ptr(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_NOT_READY));
+
+        let frame = body.0.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "hello");
+        assert_eq!(ready.load(Ordering::SeqCst), 1);
+
+        let code = hyper_body_write_chunk(writer, b"world".as_ptr(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert!(matches!(
+            hyper_body_writer_finish(writer),
+            hyper_code::HYPERE_OK
+        ));
+
+        let frame = body.0.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "world");
+        assert!(body.0.frame().await.is_none());
+    }
+
+    extern "C" fn collect_trailer(
+        userdata: *mut c_void,
+        name: *const u8,
+        name_len: size_t,
+        value: *const u8,
+        value_len: size_t,
+    ) -> c_int {
+        let all = unsafe { &mut *(userdata as *mut Vec<(Vec<u8>, Vec<u8>)>) };
+        let (name, value) = unsafe {
+            (
+                std::slice::from_raw_parts(name, name_len),
+                std::slice::from_raw_parts(value, value_len),
+            )
+        };
+        all.push((name.to_vec(), value.to_vec()));
+        HYPER_ITER_CONTINUE
+    }
+
+    #[test]
+    fn body_trailers_round_trip() {
+        let body = hyper_body_new();
+        let code = hyper_body_add_trailer(body, b"grpc-status".as_ptr(), 11, b"0".as_ptr(), 1);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+
+        let mut all = Vec::<(Vec<u8>, Vec<u8>)>::new();
+        let exec = hyper_executor_new();
+        let task =
+            hyper_body_foreach_trailer(body, collect_trailer, &mut all as *mut _ as *mut c_void);
+        assert!(matches!(
+            hyper_executor_push(exec, task),
+            hyper_code::HYPERE_OK
+        ));
+
+        let task = hyper_executor_poll(exec);
+        assert!(!task.is_null());
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_EMPTY
+        ));
+        hyper_task_free(task);
+        hyper_executor_free(exec);
+
+        assert_eq!(all, vec![(b"grpc-status".to_vec(), b"0".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn body_writer_free_aborts() {
+        let body = hyper_body_new();
+        let writer = hyper_body_writer_new(body);
+        let mut body = unsafe { Box::from_raw(body) };
+
+        hyper_body_writer_free(writer);
+        assert!(body.0.frame().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn body_writer_closed_when_body_dropped() {
+        let body = hyper_body_new();
+        let writer = hyper_body_writer_new(body);
+        let ready = AtomicUsize::new(0);
+        hyper_body_writer_set_ready_func(writer, count_ready, &ready as *const _ as *mut c_void);
+
+        let code = hyper_body_write_chunk(writer, ptr::null(), 0);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        let code = hyper_body_write_chunk(writer, ptr::null(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_INVALID_ARG));
+        let code = hyper_body_write_chunk(writer, b"hello".as_ptr(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+
+        hyper_body_free(body);
+        assert_eq!(ready.load(Ordering::SeqCst), 1);
+        let code = hyper_body_write_chunk(writer, b"world".as_ptr(), 5);
+        assert!(matches!(code, hyper_code::HYPERE_CLOSED));
+        hyper_body_writer_free(writer);
+    }
+
+    #[test]
+    fn buf_views_share_bytes() {
+        let buf = hyper_buf_copy(b"hello world".as_ptr(), 11);
+        let clone = hyper_buf_clone(buf);
+        let slice = hyper_buf_slice(buf, 6, 5);
+        assert_eq!(hyper_buf_bytes(clone), hyper_buf_bytes(buf));
+        assert!(hyper_buf_slice(buf, 6, 6).is_null());
+        hyper_buf_free(buf);
+
+        let bytes =
+            unsafe { std::slice::from_raw_parts(hyper_buf_bytes(slice), hyper_buf_len(slice)) };
+        assert_eq!(bytes, b"world");
+        assert_eq!(hyper_buf_len(clone), 11);
+        hyper_buf_free(clone);
+        hyper_buf_free(slice);
+    }
+}
@@ -1,24 +1,51 @@
 use std::ptr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+#[cfg(feature = "http2")]
+use std::time::Duration;
 
-use libc::c_int;
+use std::ffi::c_void;
+
+use libc::{c_int, size_t};
 
 use crate::client::conn;
+use crate::header::{HeaderValue, CONNECTION};
 use crate::rt::Executor as _;
 
 use super::error::hyper_code;
-use super::http_types::{hyper_request, hyper_response};
-use super::io::hyper_io;
+use super::http_types::{
+    hyper_request, hyper_request_on_informational_callback, hyper_response, OnInformational,
+};
+use super::io::{hyper_io, IoCounters, TransportInfo};
 use super::task::{hyper_executor, hyper_task, hyper_task_return_type, AsTaskType, WeakExec};
+use super::time::{hyper_timer, FfiTimer};
 
 /// An options builder to configure an HTTP client connection.
 pub struct hyper_clientconn_options {
     http1_allow_obsolete_multiline_headers_in_responses: bool,
+    http1_keep_alive: bool,
     http1_preserve_header_case: bool,
     http1_preserve_header_order: bool,
     http2: bool,
+    #[cfg(feature = "http2")]
+    http2_options: Http2Options,
     /// Use a `Weak` to prevent cycles.
     exec: WeakExec,
+    timer: Option<Arc<hyper_timer>>,
+    on_informational: Option<OnInformational>,
+}
+
+/// HTTP/2 settings, applied to the builder during the handshake.
+#[cfg(feature = "http2")]
+#[derive(Default)]
+struct Http2Options {
+    adaptive_window: bool,
+    initial_stream_window_size: Option<u32>,
+    initial_connection_window_size: Option<u32>,
+    keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    max_concurrent_reset_streams: Option<usize>,
 }
 
 /// An HTTP client connection handle.
@@ -26,8 +53,20 @@ pub struct hyper_clientconn_options {
 /// These are used to send a request on a single connection. It's possible to
 /// send multiple requests on a single connection, such as when HTTP/1
 /// keep-alive or HTTP/2 is used.
+///
+/// An HTTP/2 connection can have many requests in flight at once, and can
+/// have many handles, made with `hyper_clientconn_clone`.
 pub struct hyper_clientconn {
     tx: Tx,
+    /// Given to requests that don't have their own informational callback.
+    on_informational: Option<OnInformational>,
+    /// Whether requests ask to keep the connection open after the response.
+    keep_alive: bool,
+    io_counters: Arc<IoCounters>,
+    requests_completed: Arc<AtomicU64>,
+    closed: Arc<ConnClosed>,
+    /// Given to every response received on the connection.
+    info: TransportInfo,
 }
 
 enum Tx {
@@ -37,6 +76,18 @@ enum Tx {
     Http2(conn::http2::SendRequest<crate::body::Incoming>),
 }
 
+/// Tells `hyper_clientconn_graceful_shutdown` tasks once the connection has
+/// closed.
+#[derive(Default)]
+struct ConnClosed {
+    closed: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// Held by the background connection task, so the connection is counted as
+/// closed even if the task is dropped before it completes.
+struct CloseOnDrop(Arc<ConnClosed>);
+
 // ===== impl hyper_clientconn =====
 
 ffi_fn! {
@@ -55,34 +106,75 @@ ffi_fn! {
     fn hyper_clientconn_handshake(io: *mut hyper_io, options: *mut hyper_clientconn_options) -> *mut hyper_task {
         let options = non_null! { Box::from_raw(options) ?= ptr::null_mut() };
         let io = non_null! { Box::from_raw(io) ?= ptr::null_mut() };
+        let io_counters = io.counters();
+        let info = io.info();
+        let closed = Arc::<ConnClosed>::default();
+        let close_on_drop = CloseOnDrop(closed.clone());
 
         Box::into_raw(hyper_task::boxed(async move {
             #[cfg(feature = "http2")]
             {
             if options.http2 {
-                return conn::http2::Builder::new(options.exec.clone())
+                let mut builder = conn::http2::Builder::new(options.exec.clone());
+                let h2 = &options.http2_options;
+                builder
+                    .initial_stream_window_size(h2.initial_stream_window_size)
+                    .initial_connection_window_size(h2.initial_connection_window_size);
+                if h2.adaptive_window {
+                    builder.adaptive_window(true);
+                }
+                builder.timer(options.timer());
+                if let Some(interval) = h2.keep_alive_interval {
+                    builder.keep_alive_interval(interval);
+                }
+                if let Some(timeout) = h2.keep_alive_timeout {
+                    builder.keep_alive_timeout(timeout);
+                }
+                if let Some(max) = h2.max_concurrent_reset_streams {
+                    builder.max_concurrent_reset_streams(max);
+                }
+                return builder
                     .handshake::<_, crate::body::Incoming>(io)
                     .await
-                    .map(|(tx, conn)| {
-                        options.exec.execute(Box::pin(async move {
+                    .and_then(|(tx, conn)| {
+                        options.exec.try_execute(Box::pin(async move {
+                            let _close_on_drop = close_on_drop;
                             let _ = conn.await;
-                        }));
-                        hyper_clientconn { tx: Tx::Http2(tx) }
+                        })).map_err(crate::Error::new_execute)?;
+                        Ok(hyper_clientconn {
+                            tx: Tx::Http2(tx),
+                            on_informational: None,
+                            keep_alive: true,
+                            io_counters,
+                            requests_completed: Arc::default(),
+                            closed,
+                            info,
+                        })
                     });
                 }
             }
 
             conn::http1::Builder::new()
+                .timer(options.timer())
                 .allow_obsolete_multiline_headers_in_responses(options.http1_allow_obsolete_multiline_headers_in_responses)
                 .preserve_header_case(options.http1_preserve_header_case)
                 .preserve_header_order(options.http1_preserve_header_order)
                 .handshake::<_, crate::body::Incoming>(io)
                 .await
-                .map(|(tx, conn)| {
-                    options.exec.execute(Box::pin(async move {
+                .and_then(|(tx, conn)| {
+                    options.exec.try_execute(Box::pin(async move {
+                        let _close_on_drop = close_on_drop;
                         let _ = conn.await;
-                    }));
-                    hyper_clientconn { tx: Tx::Http1(tx) }
+                    })).map_err(crate::Error::new_execute)?;
+                    Ok(hyper_clientconn {
+                        tx: Tx::Http1(tx),
+                        on_informational: options.on_informational.clone(),
+                        keep_alive: options.http1_keep_alive,
+                        io_counters,
+                        requests_completed: Arc::default(),
+                        closed,
+                        info,
+                    })
                 })
         }))
     } ?= std::ptr::null_mut()
@@ -97,28 +189,160 @@ ffi_fn! {
     /// Returns a task that needs to be polled until it is ready. When ready, the
     /// task yields a `hyper_response *`.
     ///
+    /// On an HTTP/2 connection, this can be called again before earlier tasks
+    /// are ready, and the requests are multiplexed on the connection. Each
+    /// task is independent, and can be polled, canceled or freed separately.
+    /// On an HTTP/1 connection, only one request can be in flight at a time,
+    /// and a request sent while `hyper_clientconn_is_ready` is false fails.
+    ///
     /// To avoid a memory leak, the task must eventually be consumed by
     /// `hyper_task_free`, or taken ownership of by `hyper_executor_push`
     /// without subsequently being given back by `hyper_executor_poll`.
     fn hyper_clientconn_send(conn: *mut hyper_clientconn, req: *mut hyper_request) -> *mut hyper_task {
         let mut req = non_null! { Box::from_raw(req) ?= ptr::null_mut() };
 
+        let conn = non_null! { &mut *conn ?= ptr::null_mut() };
+
         // Update request with original-case map of headers
         req.finalize_request();
 
-        let fut = match non_null! { &mut *conn ?= ptr::null_mut() }.tx {
+        if let Some(ref on_informational) = conn.on_informational {
+            if req.0.extensions().get::<crate::ext::OnInformational>().is_none() {
+                crate::ext::on_informational_raw(&mut req.0, on_informational.clone());
+            }
+        }
+
+        if !conn.keep_alive && !req.0.headers().contains_key(CONNECTION) {
+            req.0.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+
+        let fut = match conn.tx {
             Tx::Http1(ref mut tx) => futures_util::future::Either::Left(tx.send_request(req.0)),
             Tx::Http2(ref mut tx) => futures_util::future::Either::Right(tx.send_request(req.0)),
         };
 
+        let requests_completed = conn.requests_completed.clone();
+        let info = conn.info.clone();
         let fut = async move {
-            fut.await.map(hyper_response::wrap)
+            let mut res = fut.await?;
+            requests_completed.fetch_add(1, Ordering::Relaxed);
+            res.extensions_mut().insert(info);
+            Ok(hyper_response::wrap(res))
         };
 
         Box::into_raw(hyper_task::boxed(fut))
     } ?= std::ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Returns the number of bytes written to the IO transport of the
+    /// connection so far.
+    fn hyper_clientconn_bytes_sent(conn: *const hyper_clientconn) -> u64 {
+        non_null! { &*conn ?= 0 }.io_counters.written.load(Ordering::Relaxed)
+    }
+}
+
+ffi_fn! {
+    /// Returns the number of bytes read from the IO transport of the
+    /// connection so far.
+    fn hyper_clientconn_bytes_received(conn: *const hyper_clientconn) -> u64 {
+        non_null! { &*conn ?= 0 }.io_counters.read.load(Ordering::Relaxed)
+    }
+}
+
+ffi_fn! {
+    /// Returns the number of requests sent on the connection that received a
+    /// response.
+    fn hyper_clientconn_requests_completed(conn: *const hyper_clientconn) -> u64 {
+        non_null! { &*conn ?= 0 }.requests_completed.load(Ordering::Relaxed)
+    }
+}
+
+ffi_fn! {
+    /// Returns true if the connection can send another request right now.
+    ///
+    /// A connection that isn't ready may still become ready later, such as
+    /// once the response to an earlier HTTP/1 request is received, unless
+    /// `hyper_clientconn_is_closed` returns true.
+    fn hyper_clientconn_is_ready(conn: *const hyper_clientconn) -> bool {
+        match non_null! { &*conn ?= false }.tx {
+            Tx::Http1(ref tx) => tx.is_ready(),
+            Tx::Http2(ref tx) => tx.is_ready(),
+        }
+    }
+}
+
+ffi_fn! {
+    /// Returns true if the connection is closed, and can't send any more
+    /// requests.
+    fn hyper_clientconn_is_closed(conn: *const hyper_clientconn) -> bool {
+        match non_null! { &*conn ?= true }.tx {
+            Tx::Http1(ref tx) => tx.is_closed(),
+            Tx::Http2(ref tx) => tx.is_closed(),
+        }
+    }
+}
+
+ffi_fn! {
+    /// Create another handle to an HTTP/2 connection.
+    ///
+    /// Both handles send requests on the same connection, and share its
+    /// metrics. The connection is closed once every handle has been freed,
+    /// and every request sent on it has completed. This lets separate parts
+    /// of a program send requests concurrently, without sharing a handle.
+    ///
+    /// To avoid a memory leak, the new handle must eventually be consumed by
+    /// `hyper_clientconn_free`.
+    ///
+    /// This returns `NULL` for an HTTP/1 connection, which can only send one
+    /// request at a time.
+    fn hyper_clientconn_clone(conn: *const hyper_clientconn) -> *mut hyper_clientconn {
+        let conn = non_null! { &*conn ?= ptr::null_mut() };
+        let tx = match conn.tx {
+            Tx::Http1(_) => return ptr::null_mut(),
+            Tx::Http2(ref tx) => Tx::Http2(tx.clone()),
+        };
+
+        Box::into_raw(Box::new(hyper_clientconn {
+            tx,
+            on_informational: conn.on_informational.clone(),
+            keep_alive: conn.keep_alive,
+            io_counters: conn.io_counters.clone(),
+            requests_completed: conn.requests_completed.clone(),
+            closed: conn.closed.clone(),
+            info: conn.info.clone(),
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Gracefully shut down the connection, returning a task that completes
+    /// once it has closed.
+    ///
+    /// This consumes the `conn`, you shouldn't use it anymore or free it.
+    /// Requests already sent on it are completed first, then the connection
+    /// is closed. Any other handles made with `hyper_clientconn_clone` keep
+    /// an HTTP/2 connection open until they are freed as well.
+    ///
+    /// The task yields `HYPER_TASK_EMPTY` once the connection has closed, or
+    /// once its background task has been dropped, such as when its executor
+    /// is freed.
+    ///
+    /// To avoid a memory leak, the task must eventually be consumed by
+    /// `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+    /// without subsequently being given back by `hyper_executor_poll`.
+    fn hyper_clientconn_graceful_shutdown(conn: *mut hyper_clientconn) -> *mut hyper_task {
+        let conn = non_null! { Box::from_raw(conn) ?= ptr::null_mut() };
+        let closed = conn.closed.clone();
+        // Dropping the sender lets the connection close once it is idle.
+        drop(conn);
+
+        Box::into_raw(hyper_task::boxed(futures_util::future::poll_fn(move |cx| {
+            closed.poll_closed(cx)
+        })))
+    } ?= ptr::null_mut()
+}
+
 ffi_fn! {
     /// Free a `hyper_clientconn *`.
     ///
@@ -134,8 +358,49 @@ unsafe impl AsTaskType for hyper_clientconn {
     }
 }
 
+// ===== impl ConnClosed =====
+
+impl ConnClosed {
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        for waker in self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn poll_closed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.wakers.lock().unwrap();
+        // Checked again with the lock held, so `close` can't be missed.
+        if self.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
 // ===== impl hyper_clientconn_options =====
 
+impl hyper_clientconn_options {
+    fn timer(&self) -> FfiTimer {
+        match self.timer {
+            Some(ref timer) => FfiTimer::Callback(timer.clone()),
+            None => FfiTimer::Thread,
+        }
+    }
+}
+
 ffi_fn! {
     /// Creates a new set of HTTP clientconn options to be used in a handshake.
     ///
@@ -144,10 +409,15 @@ ffi_fn! {
     fn hyper_clientconn_options_new() -> *mut hyper_clientconn_options {
         Box::into_raw(Box::new(hyper_clientconn_options {
             http1_allow_obsolete_multiline_headers_in_responses: false,
+            http1_keep_alive: true,
             http1_preserve_header_case: false,
             http1_preserve_header_order: false,
             http2: false,
+            #[cfg(feature = "http2")]
+            http2_options: Http2Options::default(),
             exec: WeakExec::new(),
+            timer: None,
+            on_informational: None,
         }))
     } ?= std::ptr::null_mut()
 }
@@ -197,6 +467,43 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set the timer used for timeouts, such as HTTP/2 keep-alive.
+    ///
+    /// By default, hyper spawns a thread for each timeout it waits on.
+    ///
+    /// This consumes the `timer`, you shouldn't use it anymore or free it.
+    fn hyper_clientconn_options_timer(opts: *mut hyper_clientconn_options, timer: *mut hyper_timer) {
+        let opts = non_null! { &mut *opts ?= () };
+        let timer = non_null! { Box::from_raw(timer) ?= () };
+        opts.timer = Some(Arc::from(timer));
+    }
+}
+
+ffi_fn! {
+    /// Set an informational (1xx) response callback for every request sent on
+    /// the connection.
+    ///
+    /// The callback is called each time hyper receives an informational (1xx)
+    /// response, such as `103 Early Hints`, before the final response of a
+    /// request. Requests with their own callback, set with
+    /// `hyper_request_on_informational`, use that one instead.
+    ///
+    /// The callback is passed the `data` pointer, and a `hyper_response *`
+    /// with the status and headers of the informational response. Its body
+    /// is always empty.
+    ///
+    /// NOTE: The `hyper_response *` is just borrowed data, and will not
+    /// be valid after the callback finishes. You must copy any data you wish
+    /// to persist.
+    ///
+    /// Only HTTP/1 connections report informational responses.
+    fn hyper_clientconn_options_on_informational(opts: *mut hyper_clientconn_options, callback: hyper_request_on_informational_callback, data: *mut c_void) {
+        let opts = non_null! { &mut *opts ?= () };
+        opts.on_informational = Some(OnInformational::new(callback, data));
+    }
+}
+
 ffi_fn! {
     /// Set the whether to use HTTP2.
     ///
@@ -230,3 +537,369 @@ ffi_fn! {
         hyper_code::HYPERE_OK
     }
 }
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections are kept open after a response, so more
+    /// requests can be sent on them.
+    ///
+    /// When disabled, each request is sent with a `connection: close` header,
+    /// unless it already has a `connection` header, and the connection is
+    /// closed once its response is received.
+    ///
+    /// Pass `0` to disable, `1` to enable (default).
+    fn hyper_clientconn_options_http1_keep_alive(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1_keep_alive = enabled != 0;
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/2 connections use adaptive flow control.
+    ///
+    /// Enabling this overrides the window sizes set with
+    /// `hyper_clientconn_options_http2_initial_stream_window_size` and
+    /// `hyper_clientconn_options_http2_initial_connection_window_size`.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_clientconn_options_http2_adaptive_window(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.adaptive_window = enabled != 0;
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(enabled);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the initial HTTP/2 stream-level flow control window, in bytes.
+    ///
+    /// If not set, hyper will use a default.
+    fn hyper_clientconn_options_http2_initial_stream_window_size(opts: *mut hyper_clientconn_options, size: u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.initial_stream_window_size = Some(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the initial HTTP/2 connection-level flow control window, in bytes.
+    ///
+    /// If not set, hyper will use a default.
+    fn hyper_clientconn_options_http2_initial_connection_window_size(opts: *mut hyper_clientconn_options, size: u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.initial_connection_window_size = Some(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the interval, in milliseconds, at which HTTP/2 PING frames are sent
+    /// to keep the connection alive.
+    ///
+    /// Pass `0` to disable keep-alive (default).
+    ///
+    /// Unless a timer is set with `hyper_clientconn_options_timer`, each
+    /// pending ping wakes its connection task from a background thread, but
+    /// the ping is only sent the next time the executor is polled. An
+    /// application that waits on IO between polls should wake up at least
+    /// this often.
+    fn hyper_clientconn_options_http2_keep_alive_interval(opts: *mut hyper_clientconn_options, interval_ms: u64) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.keep_alive_interval = if interval_ms == 0 {
+                None
+            } else {
+                Some(Duration::from_millis(interval_ms))
+            };
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(interval_ms);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set how long to wait, in milliseconds, for an HTTP/2 keep-alive ping to
+    /// be acknowledged before closing the connection.
+    ///
+    /// Does nothing unless a keep-alive interval is set. Default is 20 seconds.
+    fn hyper_clientconn_options_http2_keep_alive_timeout(opts: *mut hyper_clientconn_options, timeout_ms: u64) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.keep_alive_timeout = Some(Duration::from_millis(timeout_ms));
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(timeout_ms);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the maximum number of locally reset HTTP/2 streams to keep track
+    /// of at once.
+    ///
+    /// If not set, the default is determined by the `h2` crate.
+    fn hyper_clientconn_options_http2_max_concurrent_reset_streams(opts: *mut hyper_clientconn_options, max: size_t) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_options.max_concurrent_reset_streams = Some(max);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            drop(opts);
+            drop(max);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_void;
+
+    use super::super::http_types::{
+        hyper_request_new, hyper_response_alpn_protocol, hyper_response_free,
+        hyper_response_peer_addr, hyper_response_status, hyper_response_tls_version,
+    };
+    use super::super::io::{
+        hyper_io_new, hyper_io_set_peer_addr, hyper_io_set_read, hyper_io_set_tls_info,
+        hyper_io_set_userdata, hyper_io_set_write, HYPER_IO_PENDING,
+    };
+    use super::super::task::{
+        hyper_context, hyper_context_waker, hyper_executor_free, hyper_executor_new,
+        hyper_executor_poll, hyper_executor_push, hyper_task_free, hyper_task_type,
+        hyper_task_value, hyper_waker, hyper_waker_free, hyper_waker_wake,
+    };
+    use super::*;
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+
+    /// Sends `RESPONSE` once a request has been written.
+    struct MockServer {
+        written: Vec<u8>,
+        response: &'static [u8],
+        reader: *mut hyper_waker,
+    }
+
+    impl MockServer {
+        fn new() -> MockServer {
+            MockServer {
+                written: Vec::new(),
+                response: RESPONSE,
+                reader: ptr::null_mut(),
+            }
+        }
+    }
+
+    impl Drop for MockServer {
+        fn drop(&mut self) {
+            if !self.reader.is_null() {
+                hyper_waker_free(self.reader);
+            }
+        }
+    }
+
+    extern "C" fn read_response(
+        userdata: *mut c_void,
+        cx: *mut hyper_context<'_>,
+        buf: *mut u8,
+        buf_len: size_t,
+    ) -> size_t {
+        let server = unsafe { &mut *(userdata as *mut MockServer) };
+        if server.written.is_empty() || server.response.is_empty() {
+            if server.reader.is_null() {
+                server.reader = hyper_context_waker(cx);
+            }
+            return HYPER_IO_PENDING;
+        }
+        let n = server.response.len().min(buf_len);
+        unsafe { ptr::copy_nonoverlapping(server.response.as_ptr(), buf, n) };
+        server.response = &server.response[n..];
+        n
+    }
+
+    extern "C" fn write_request(
+        userdata: *mut c_void,
+        _: *mut hyper_context<'_>,
+        buf: *const u8,
+        buf_len: size_t,
+    ) -> size_t {
+        let server = unsafe { &mut *(userdata as *mut MockServer) };
+        server
+            .written
+            .extend_from_slice(unsafe { std::slice::from_raw_parts(buf, buf_len) });
+        if !server.reader.is_null() {
+            hyper_waker_wake(std::mem::replace(&mut server.reader, ptr::null_mut()));
+        }
+        buf_len
+    }
+
+    fn poll_until_done(exec: *const hyper_executor) -> *mut hyper_task {
+        for _ in 0..100 {
+            let task = hyper_executor_poll(exec);
+            if !task.is_null() {
+                return task;
+            }
+        }
+        panic!("task never completed");
+    }
+
+    fn mock_io(server: &mut MockServer) -> *mut hyper_io {
+        let io = hyper_io_new();
+        hyper_io_set_userdata(io, server as *mut _ as *mut c_void, None);
+        hyper_io_set_read(io, read_response);
+        hyper_io_set_write(io, write_request);
+        io
+    }
+
+    fn handshake(
+        exec: *const hyper_executor,
+        opts: *mut hyper_clientconn_options,
+        io: *mut hyper_io,
+    ) -> *mut hyper_clientconn {
+        hyper_clientconn_options_exec(opts, exec);
+        hyper_executor_push(exec, hyper_clientconn_handshake(io, opts));
+        let task = poll_until_done(exec);
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_CLIENTCONN
+        ));
+        let conn = hyper_task_value(task) as *mut hyper_clientconn;
+        hyper_task_free(task);
+        conn
+    }
+
+    fn send(exec: *const hyper_executor, conn: *mut hyper_clientconn) -> *mut hyper_response {
+        hyper_executor_push(exec, hyper_clientconn_send(conn, hyper_request_new()));
+        let task = poll_until_done(exec);
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_RESPONSE
+        ));
+        let resp = hyper_task_value(task) as *mut hyper_response;
+        hyper_task_free(task);
+        assert_eq!(hyper_response_status(resp), 200);
+        resp
+    }
+
+    #[test]
+    fn clientconn_metrics() {
+        let mut server = MockServer::new();
+        let exec = hyper_executor_new();
+        let conn = handshake(exec, hyper_clientconn_options_new(), mock_io(&mut server));
+
+        hyper_response_free(send(exec, conn));
+        assert!(hyper_clientconn_bytes_sent(conn) > 0);
+        assert_eq!(hyper_clientconn_bytes_received(conn), RESPONSE.len() as u64);
+        assert_eq!(hyper_clientconn_requests_completed(conn), 1);
+        assert!(!hyper_clientconn_is_closed(conn));
+        assert!(hyper_clientconn_clone(conn).is_null());
+
+        hyper_clientconn_free(conn);
+        hyper_executor_free(exec);
+    }
+
+    #[test]
+    fn graceful_shutdown_completes_once_closed() {
+        let mut server = MockServer::new();
+        let exec = hyper_executor_new();
+        let conn = handshake(exec, hyper_clientconn_options_new(), mock_io(&mut server));
+        hyper_response_free(send(exec, conn));
+
+        hyper_executor_push(exec, hyper_clientconn_graceful_shutdown(conn));
+        let task = poll_until_done(exec);
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_EMPTY
+        ));
+        hyper_task_free(task);
+        hyper_executor_free(exec);
+    }
+
+    #[test]
+    fn http1_keep_alive_disabled() {
+        let mut server = MockServer::new();
+        let exec = hyper_executor_new();
+        let opts = hyper_clientconn_options_new();
+        hyper_clientconn_options_http1_keep_alive(opts, 0);
+        let conn = handshake(exec, opts, mock_io(&mut server));
+
+        hyper_response_free(send(exec, conn));
+        let written = String::from_utf8_lossy(&server.written).to_lowercase();
+        assert!(written.contains("connection: close\r\n"), "{}", written);
+
+        hyper_clientconn_free(conn);
+        hyper_executor_free(exec);
+    }
+
+    #[test]
+    fn transport_info_on_responses() {
+        let mut server = MockServer::new();
+        let exec = hyper_executor_new();
+        let io = mock_io(&mut server);
+        let addr = b"192.0.2.1:443";
+        hyper_io_set_peer_addr(io, addr.as_ptr(), addr.len());
+        let version = b"TLSv1.3";
+        hyper_io_set_tls_info(io, version.as_ptr(), version.len(), ptr::null(), 0);
+        let conn = handshake(exec, hyper_clientconn_options_new(), io);
+
+        let resp = send(exec, conn);
+        let (mut bytes, mut len) = (ptr::null(), 0);
+        let code = hyper_response_peer_addr(resp, &mut bytes, &mut len);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(unsafe { std::slice::from_raw_parts(bytes, len) }, addr);
+        let code = hyper_response_tls_version(resp, &mut bytes, &mut len);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(unsafe { std::slice::from_raw_parts(bytes, len) }, version);
+        let code = hyper_response_alpn_protocol(resp, &mut bytes, &mut len);
+        assert!(matches!(code, hyper_code::HYPERE_INVALID_ARG));
+
+        hyper_response_free(resp);
+        hyper_clientconn_free(conn);
+        hyper_executor_free(exec);
+    }
+}
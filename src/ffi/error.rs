@@ -1,3 +1,6 @@
+use std::error::Error as StdError;
+use std::fmt;
+
 use libc::size_t;
 
 /// A more detailed error object returned by some hyper functions.
@@ -24,6 +27,43 @@ pub enum hyper_code {
     HYPERE_FEATURE_NOT_ENABLED,
     /// The peer sent an HTTP message that could not be parsed.
     HYPERE_INVALID_PEER_MESSAGE,
+    /// The operation can't be done yet. Wait to be told it is ready, and
+    /// try again.
+    HYPERE_NOT_READY,
+    /// The receiving side is gone, so nothing more can be sent.
+    HYPERE_CLOSED,
+}
+
+/// The kind of failure a `hyper_error` represents.
+///
+/// The values are stable, new kinds are only ever added to the end.
+#[repr(C)]
+pub enum hyper_error_kind {
+    /// An error that doesn't fit any other kind.
+    HYPER_ERROR_KIND_OTHER = 0,
+    /// The peer sent an HTTP message that could not be parsed.
+    HYPER_ERROR_KIND_PARSE = 1,
+    /// An error caused by the user, such as a callback or an outgoing body.
+    HYPER_ERROR_KIND_USER = 2,
+    /// A message reached EOF, but is not complete.
+    HYPER_ERROR_KIND_INCOMPLETE_MESSAGE = 3,
+    /// The connection received a message when not waiting for one.
+    HYPER_ERROR_KIND_UNEXPECTED_MESSAGE = 4,
+    /// A pending request was dropped before ever being processed.
+    HYPER_ERROR_KIND_CANCELED = 5,
+    /// The connection, or a body channel, is closed.
+    HYPER_ERROR_KIND_CLOSED = 6,
+    /// Reading from or writing to the IO transport failed.
+    HYPER_ERROR_KIND_IO = 7,
+    /// Reading a body from the connection failed.
+    HYPER_ERROR_KIND_BODY = 8,
+    /// Writing a body to the connection failed.
+    HYPER_ERROR_KIND_BODY_WRITE = 9,
+    /// Shutting down the IO transport failed.
+    HYPER_ERROR_KIND_SHUTDOWN = 10,
+    /// An error from the HTTP/2 protocol.
+    #[cfg_attr(not(feature = "http2"), allow(unused))]
+    HYPER_ERROR_KIND_HTTP2 = 11,
 }
 
 // ===== impl hyper_error =====
@@ -42,19 +82,54 @@ impl hyper_error {
         }
     }
 
-    fn print_to(&self, dst: &mut [u8]) -> usize {
-        use std::io::Write;
+    fn kind(&self) -> hyper_error_kind {
+        use crate::error::Kind as ErrorKind;
+
+        match self.0.kind() {
+            ErrorKind::Parse(_) => hyper_error_kind::HYPER_ERROR_KIND_PARSE,
+            ErrorKind::User(_) => hyper_error_kind::HYPER_ERROR_KIND_USER,
+            ErrorKind::IncompleteMessage => hyper_error_kind::HYPER_ERROR_KIND_INCOMPLETE_MESSAGE,
+            ErrorKind::UnexpectedMessage => hyper_error_kind::HYPER_ERROR_KIND_UNEXPECTED_MESSAGE,
+            ErrorKind::Canceled => hyper_error_kind::HYPER_ERROR_KIND_CANCELED,
+            ErrorKind::ChannelClosed => hyper_error_kind::HYPER_ERROR_KIND_CLOSED,
+            ErrorKind::Io => hyper_error_kind::HYPER_ERROR_KIND_IO,
+            ErrorKind::Body => hyper_error_kind::HYPER_ERROR_KIND_BODY,
+            ErrorKind::BodyWrite => hyper_error_kind::HYPER_ERROR_KIND_BODY_WRITE,
+            ErrorKind::Shutdown => hyper_error_kind::HYPER_ERROR_KIND_SHUTDOWN,
+            #[cfg(feature = "http2")]
+            ErrorKind::Http2 => hyper_error_kind::HYPER_ERROR_KIND_HTTP2,
+            #[allow(unreachable_patterns)]
+            _ => hyper_error_kind::HYPER_ERROR_KIND_OTHER,
+        }
+    }
 
-        let mut dst = std::io::Cursor::new(dst);
+    /// The source at `index` in the chain of causes, starting at 0 for the
+    /// direct cause.
+    fn source(&self, index: usize) -> Option<&(dyn StdError + 'static)> {
+        let mut source = self.0.source();
+        for _ in 0..index {
+            source = source?.source();
+        }
+        source
+    }
 
-        // A write! error doesn't matter. As much as possible will have been
-        // written, and the Cursor position will know how far that is (even
-        // if that is zero).
-        let _ = write!(dst, "{}", &self.0);
-        dst.position() as usize
+    fn print_to(&self, dst: &mut [u8]) -> usize {
+        print_to(&self.0, dst)
     }
 }
 
+fn print_to(err: &dyn fmt::Display, dst: &mut [u8]) -> usize {
+    use std::io::Write;
+
+    let mut dst = std::io::Cursor::new(dst);
+
+    // A write! error doesn't matter. As much as possible will have been
+    // written, and the Cursor position will know how far that is (even
+    // if that is zero).
+    let _ = write!(dst, "{}", err);
+    dst.position() as usize
+}
+
 ffi_fn! {
     /// Frees a `hyper_error`.
     ///
@@ -85,3 +160,149 @@ ffi_fn! {
         non_null!(&*err ?= 0).print_to(dst)
     }
 }
+
+ffi_fn! {
+    /// Get the kind of this error.
+    ///
+    /// This is more detailed than `hyper_error_code`, and its values are
+    /// stable across releases.
+    fn hyper_error_get_kind(err: *const hyper_error) -> hyper_error_kind {
+        non_null!(&*err ?= hyper_error_kind::HYPER_ERROR_KIND_OTHER).kind()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if this was an HTTP parse error.
+    fn hyper_error_is_parse(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_parse()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if this was an HTTP parse error caused by a message that
+    /// was too large.
+    fn hyper_error_is_parse_too_large(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_parse_too_large()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if this was an HTTP parse error caused by an invalid
+    /// response status code or reason phrase.
+    fn hyper_error_is_parse_status(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_parse_status()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if this error was caused by user code, such as a
+    /// callback.
+    fn hyper_error_is_user(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_user()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if this was about a request that was canceled.
+    fn hyper_error_is_canceled(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_canceled()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if the connection was closed.
+    fn hyper_error_is_closed(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_closed()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if the connection closed before a message could complete.
+    fn hyper_error_is_incomplete_message(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_incomplete_message()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if writing a body was aborted.
+    fn hyper_error_is_body_write_aborted(err: *const hyper_error) -> bool {
+        non_null!(&*err ?= false).0.is_body_write_aborted()
+    }
+}
+
+ffi_fn! {
+    /// Returns true if the error was caused by a timeout.
+    ///
+    /// This includes IO transport errors reported as timeouts.
+    fn hyper_error_is_timeout(err: *const hyper_error) -> bool {
+        let err = &non_null!(&*err ?= false).0;
+        err.is_timeout()
+            || err
+                .find_source::<std::io::Error>()
+                .map_or(false, |io| io.kind() == std::io::ErrorKind::TimedOut)
+    }
+}
+
+ffi_fn! {
+    /// Get the number of errors in the chain of causes of this error.
+    ///
+    /// Use `hyper_error_print_source` to print each of them.
+    fn hyper_error_source_count(err: *const hyper_error) -> size_t {
+        let err = non_null!(&*err ?= 0);
+        let mut count = 0;
+        while err.source(count).is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+ffi_fn! {
+    /// Print the details of a cause of this error to a buffer.
+    ///
+    /// The `index` selects the cause, starting at `0` for the error that
+    /// directly caused this one, and going up to one less than
+    /// `hyper_error_source_count`.
+    ///
+    /// The `dst_len` value must be the maximum length that the buffer can
+    /// store.
+    ///
+    /// The return value is number of bytes that were written to `dst`, and
+    /// is `0` if there is no cause at `index`.
+    fn hyper_error_print_source(err: *const hyper_error, index: size_t, dst: *mut u8, dst_len: size_t) -> size_t {
+        let dst = unsafe {
+            std::slice::from_raw_parts_mut(dst, dst_len)
+        };
+        match non_null!(&*err ?= 0).source(index) {
+            Some(source) => print_to(source, dst),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_kind_and_sources() {
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "deadline passed");
+        let err = hyper_error(crate::Error::new_io(io));
+
+        assert!(matches!(
+            hyper_error_get_kind(&err),
+            hyper_error_kind::HYPER_ERROR_KIND_IO
+        ));
+        assert!(hyper_error_is_timeout(&err));
+        assert!(!hyper_error_is_parse(&err));
+
+        assert_eq!(hyper_error_source_count(&err), 1);
+        let mut buf = [0u8; 64];
+        let len = hyper_error_print_source(&err, 0, buf.as_mut_ptr(), buf.len());
+        assert_eq!(&buf[..len], b"deadline passed");
+        assert_eq!(
+            hyper_error_print_source(&err, 1, buf.as_mut_ptr(), buf.len()),
+            0
+        );
+    }
+}
@@ -1,14 +1,16 @@
 use bytes::Bytes;
 use libc::{c_int, size_t};
+use std::convert::TryFrom;
 use std::ffi::c_void;
 
 use super::body::hyper_body;
 use super::error::hyper_code;
-use super::task::{hyper_task_return_type, AsTaskType};
+use super::io::{hyper_io, hyper_upgraded, TransportInfo};
+use super::task::{hyper_task, hyper_task_return_type, AsTaskType};
 use super::{UserDataPointer, HYPER_ITER_CONTINUE};
 use crate::body::Incoming as IncomingBody;
 use crate::ext::{HeaderCaseMap, OriginalHeaderOrder, ReasonPhrase};
-use crate::header::{HeaderName, HeaderValue};
+use crate::header::{Entry, HeaderName, HeaderValue};
 use crate::{HeaderMap, Method, Request, Response, Uri};
 
 /// An HTTP request.
@@ -31,7 +33,8 @@ pub(crate) struct OnInformational {
     data: UserDataPointer,
 }
 
-type hyper_request_on_informational_callback = extern "C" fn(*mut c_void, *mut hyper_response);
+pub(super) type hyper_request_on_informational_callback =
+    extern "C" fn(*mut c_void, *mut hyper_response);
 
 // ===== impl hyper_request =====
 
@@ -221,12 +224,9 @@ ffi_fn! {
     /// be valid after the callback finishes. You must copy any data you wish
     /// to persist.
     fn hyper_request_on_informational(req: *mut hyper_request, callback: hyper_request_on_informational_callback, data: *mut c_void) -> hyper_code {
-        let ext = OnInformational {
-            func: callback,
-            data: UserDataPointer(data),
-        };
+        let ext = OnInformational::new(callback, data);
         let req = non_null!(&mut *req ?= hyper_code::HYPERE_INVALID_ARG);
-        req.0.extensions_mut().insert(ext);
+        crate::ext::on_informational_raw(&mut req.0, ext);
         hyper_code::HYPERE_OK
     }
 }
@@ -285,6 +285,30 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set the reason-phrase of this response.
+    ///
+    /// It replaces the reason-phrase the response was received with, or the
+    /// canonical reason of its status code, as returned by
+    /// `hyper_response_reason_phrase`. The bytes are copied.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the reason-phrase contains bytes that
+    /// aren't allowed in one, such as `\r` or `\n`.
+    fn hyper_response_set_reason_phrase(resp: *mut hyper_response, reason: *const u8, reason_len: size_t) -> hyper_code {
+        let resp = non_null!(&mut *resp ?= hyper_code::HYPERE_INVALID_ARG);
+        let bytes = unsafe {
+            std::slice::from_raw_parts(reason, reason_len)
+        };
+        match ReasonPhrase::try_from(bytes) {
+            Ok(reason) => {
+                resp.0.extensions_mut().insert(reason);
+                hyper_code::HYPERE_OK
+            }
+            Err(_) => hyper_code::HYPERE_INVALID_ARG,
+        }
+    }
+}
+
 ffi_fn! {
     /// Get the HTTP version used by this response.
     ///
@@ -306,6 +330,54 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Get the address of the peer this response was received from.
+    ///
+    /// On success, `*addr` and `*addr_len` are set to the address given to
+    /// `hyper_io_set_peer_addr`. It is borrowed data, and not valid once the
+    /// response has been freed.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the IO of the connection had no peer
+    /// address set.
+    fn hyper_response_peer_addr(resp: *const hyper_response, addr: *mut *const u8, addr_len: *mut size_t) -> hyper_code {
+        let resp = non_null!(&*resp ?= hyper_code::HYPERE_INVALID_ARG);
+        let info = resp.0.extensions().get::<TransportInfo>();
+        unsafe { borrow_bytes(info.and_then(|info| info.peer_addr.as_ref()), addr, addr_len) }
+    }
+}
+
+ffi_fn! {
+    /// Get the TLS version of the connection this response was received on.
+    ///
+    /// On success, `*version` and `*version_len` are set to the version given
+    /// to `hyper_io_set_tls_info`. It is borrowed data, and not valid once the
+    /// response has been freed.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the IO of the connection had no TLS
+    /// version set.
+    fn hyper_response_tls_version(resp: *const hyper_response, version: *mut *const u8, version_len: *mut size_t) -> hyper_code {
+        let resp = non_null!(&*resp ?= hyper_code::HYPERE_INVALID_ARG);
+        let info = resp.0.extensions().get::<TransportInfo>();
+        unsafe { borrow_bytes(info.and_then(|info| info.tls_version.as_ref()), version, version_len) }
+    }
+}
+
+ffi_fn! {
+    /// Get the ALPN protocol of the connection this response was received on.
+    ///
+    /// On success, `*alpn` and `*alpn_len` are set to the protocol given to
+    /// `hyper_io_set_tls_info`. It is borrowed data, and not valid once the
+    /// response has been freed.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the IO of the connection had no ALPN
+    /// protocol set.
+    fn hyper_response_alpn_protocol(resp: *const hyper_response, alpn: *mut *const u8, alpn_len: *mut size_t) -> hyper_code {
+        let resp = non_null!(&*resp ?= hyper_code::HYPERE_INVALID_ARG);
+        let info = resp.0.extensions().get::<TransportInfo>();
+        unsafe { borrow_bytes(info.and_then(|info| info.alpn_protocol.as_ref()), alpn, alpn_len) }
+    }
+}
+
 ffi_fn! {
     /// Gets a reference to the HTTP headers of this response.
     ///
@@ -322,10 +394,51 @@ ffi_fn! {
     /// It is safe to free the response even after taking ownership of its body.
     ///
     /// To avoid a memory leak, the body must eventually be consumed by
-    /// `hyper_body_free`, `hyper_body_foreach`, or `hyper_request_set_body`.
+    /// `hyper_body_free`, `hyper_body_foreach`, `hyper_body_foreach_trailer`,
+    /// or `hyper_request_set_body`.
     fn hyper_response_body(resp: *mut hyper_response) -> *mut hyper_body {
         let body = std::mem::replace(non_null!(&mut *resp ?= std::ptr::null_mut()).0.body_mut(), IncomingBody::empty());
-        Box::into_raw(Box::new(hyper_body(body)))
+        Box::into_raw(Box::new(hyper_body::new(body)))
+    } ?= std::ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Return a task that waits for the connection of this response to be
+    /// upgraded.
+    ///
+    /// This is for responses that switch the connection to another protocol,
+    /// such as a `101 Switching Protocols` response, or a `2xx` response to a
+    /// `CONNECT` request. Check the response before calling this. Once the
+    /// response is received, the connection can't be used to send more
+    /// requests.
+    ///
+    /// The task value may have different types depending on the outcome:
+    ///
+    /// - `HYPER_TASK_UPGRADED`: Success, the value is a `hyper_upgraded *`.
+    /// - `HYPER_TASK_ERROR`: The connection was not upgraded, or was not
+    ///   an HTTP/1 connection.
+    ///
+    /// To avoid a memory leak, the task must eventually be consumed by
+    /// `hyper_task_free`, or taken ownership of by `hyper_executor_push`
+    /// without subsequently being given back by `hyper_executor_poll`.
+    ///
+    /// This does not consume the `hyper_response *`, which must still be
+    /// freed.
+    fn hyper_response_upgrade(resp: *mut hyper_response) -> *mut hyper_task {
+        let resp = non_null!(&mut *resp ?= std::ptr::null_mut());
+        let on_upgrade = crate::upgrade::on(&mut resp.0);
+
+        Box::into_raw(hyper_task::boxed(async move {
+            let upgraded = on_upgrade.await?;
+            match upgraded.downcast::<Box<hyper_io>>() {
+                Ok(parts) => Ok(hyper_upgraded {
+                    io: parts.io,
+                    read_buf: parts.read_buf,
+                }),
+                // Only HTTP/1 connections hand back the original IO.
+                Err(_) => Err(crate::Error::new_user_no_upgrade()),
+            }
+        }))
     } ?= std::ptr::null_mut()
 }
 
@@ -370,7 +483,7 @@ unsafe impl AsTaskType for hyper_response {
 
 // ===== impl Headers =====
 
-type hyper_headers_foreach_callback =
+pub(super) type hyper_headers_foreach_callback =
     extern "C" fn(*mut c_void, *const u8, size_t, *const u8, size_t) -> c_int;
 
 impl hyper_headers {
@@ -458,7 +571,7 @@ ffi_fn! {
         match unsafe { raw_name_value(name, name_len, value, value_len) } {
             Ok((name, value, orig_name)) => {
                 headers.headers.insert(&name, value);
-                headers.orig_casing.insert(name.clone(), orig_name.clone());
+                headers.orig_casing.insert_raw(name.clone(), orig_name.clone());
                 headers.orig_order.insert(name);
                 hyper_code::HYPERE_OK
             }
@@ -478,7 +591,7 @@ ffi_fn! {
         match unsafe { raw_name_value(name, name_len, value, value_len) } {
             Ok((name, value, orig_name)) => {
                 headers.headers.append(&name, value);
-                headers.orig_casing.append(&name, orig_name.clone());
+                headers.orig_casing.append_raw(&name, orig_name.clone());
                 headers.orig_order.append(name);
                 hyper_code::HYPERE_OK
             }
@@ -487,6 +600,122 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Removes all values of the provided name.
+    ///
+    /// This does nothing if there are no values for the name.
+    fn hyper_headers_remove(headers: *mut hyper_headers, name: *const u8, name_len: size_t) -> hyper_code {
+        let headers = non_null!(&mut *headers ?= hyper_code::HYPERE_INVALID_ARG);
+
+        match unsafe { raw_name(name, name_len) } {
+            Ok(name) => {
+                headers.headers.remove(&name);
+                headers.orig_casing.remove(&name);
+                headers.orig_order.remove(&name);
+                hyper_code::HYPERE_OK
+            }
+            Err(code) => code,
+        }
+    }
+}
+
+ffi_fn! {
+    /// Gets a value of the provided name.
+    ///
+    /// The `index` selects which value, when there are several for the name,
+    /// in the order they were received or added, starting at `0`. On success,
+    /// `*value` and `*value_len` are set to the bytes of the value.
+    ///
+    /// The value is borrowed data, and not valid once the headers are changed
+    /// or freed.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+    fn hyper_headers_get(headers: *const hyper_headers, name: *const u8, name_len: size_t, index: size_t, value: *mut *const u8, value_len: *mut size_t) -> hyper_code {
+        let headers = non_null!(&*headers ?= hyper_code::HYPERE_INVALID_ARG);
+        let value = non_null!(&mut *value ?= hyper_code::HYPERE_INVALID_ARG);
+        let value_len = non_null!(&mut *value_len ?= hyper_code::HYPERE_INVALID_ARG);
+
+        let name = match unsafe { raw_name(name, name_len) } {
+            Ok(name) => name,
+            Err(code) => return code,
+        };
+        match headers.headers.get_all(&name).iter().nth(index) {
+            Some(val) => {
+                *value = val.as_bytes().as_ptr();
+                *value_len = val.as_bytes().len();
+                hyper_code::HYPERE_OK
+            }
+            None => hyper_code::HYPERE_INVALID_ARG,
+        }
+    }
+}
+
+ffi_fn! {
+    /// Gets the name of a value of the provided name, in its original case.
+    ///
+    /// The `index` selects the value as in `hyper_headers_get`. On success,
+    /// `*orig_name` and `*orig_name_len` are set to the bytes of the name, as
+    /// it was received or added. This is the name `hyper_headers_foreach`
+    /// passes along with the value. If its case wasn't recorded, such as when
+    /// the connection doesn't preserve header case, it is lowercase.
+    ///
+    /// The name is borrowed data, and not valid once the headers are changed
+    /// or freed.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+    fn hyper_headers_get_original_name(headers: *const hyper_headers, name: *const u8, name_len: size_t, index: size_t, orig_name: *mut *const u8, orig_name_len: *mut size_t) -> hyper_code {
+        let headers = non_null!(&*headers ?= hyper_code::HYPERE_INVALID_ARG);
+        let orig_name = non_null!(&mut *orig_name ?= hyper_code::HYPERE_INVALID_ARG);
+        let orig_name_len = non_null!(&mut *orig_name_len ?= hyper_code::HYPERE_INVALID_ARG);
+
+        let name = match unsafe { raw_name(name, name_len) } {
+            Ok(name) => name,
+            Err(code) => return code,
+        };
+        if headers.headers.get_all(&name).iter().nth(index).is_none() {
+            return hyper_code::HYPERE_INVALID_ARG;
+        }
+        let bytes = match headers.orig_casing.get_all_internal(&name).nth(index) {
+            Some(orig) => orig.as_ref(),
+            // Borrowed from the map, since `name` is dropped on return.
+            None => match headers.headers.keys().find(|key| **key == name) {
+                Some(key) => key.as_str().as_bytes(),
+                None => return hyper_code::HYPERE_INVALID_ARG,
+            },
+        };
+        *orig_name = bytes.as_ptr();
+        *orig_name_len = bytes.len();
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Replaces a value of the provided name.
+    ///
+    /// The `index` selects the value as in `hyper_headers_get`. The original
+    /// case and order of the header are kept.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if there is no value at `index`.
+    fn hyper_headers_replace(headers: *mut hyper_headers, name: *const u8, name_len: size_t, index: size_t, value: *const u8, value_len: size_t) -> hyper_code {
+        let headers = non_null!(&mut *headers ?= hyper_code::HYPERE_INVALID_ARG);
+
+        let (name, value, _) = match unsafe { raw_name_value(name, name_len, value, value_len) } {
+            Ok(parts) => parts,
+            Err(code) => return code,
+        };
+        match headers.headers.entry(name) {
+            Entry::Occupied(mut entry) => match entry.iter_mut().nth(index) {
+                Some(val) => {
+                    *val = value;
+                    hyper_code::HYPERE_OK
+                }
+                None => hyper_code::HYPERE_INVALID_ARG,
+            },
+            Entry::Vacant(_) => hyper_code::HYPERE_INVALID_ARG,
+        }
+    }
+}
+
 impl Default for hyper_headers {
     fn default() -> Self {
         Self {
@@ -497,7 +726,23 @@ impl Default for hyper_headers {
     }
 }
 
-unsafe fn raw_name_value(
+unsafe fn borrow_bytes(bytes: Option<&Bytes>, ptr: *mut *const u8, len: *mut size_t) -> hyper_code {
+    match bytes {
+        Some(bytes) if !ptr.is_null() && !len.is_null() => {
+            *ptr = bytes.as_ptr();
+            *len = bytes.len();
+            hyper_code::HYPERE_OK
+        }
+        _ => hyper_code::HYPERE_INVALID_ARG,
+    }
+}
+
+unsafe fn raw_name(name: *const u8, name_len: size_t) -> Result<HeaderName, hyper_code> {
+    let name = std::slice::from_raw_parts(name, name_len);
+    HeaderName::from_bytes(name).map_err(|_| hyper_code::HYPERE_INVALID_ARG)
+}
+
+pub(super) unsafe fn raw_name_value(
     name: *const u8,
     name_len: size_t,
     value: *const u8,
@@ -520,9 +765,24 @@ unsafe fn raw_name_value(
 
 // ===== impl OnInformational =====
 
+impl Clone for OnInformational {
+    fn clone(&self) -> Self {
+        OnInformational::new(self.func, self.data.0)
+    }
+}
+
 impl OnInformational {
-    pub(crate) fn call(&mut self, resp: Response<IncomingBody>) {
-        let mut resp = hyper_response::wrap(resp);
+    pub(super) fn new(func: hyper_request_on_informational_callback, data: *mut c_void) -> Self {
+        OnInformational {
+            func,
+            data: UserDataPointer(data),
+        }
+    }
+}
+
+impl crate::ext::OnInformationalCallback for OnInformational {
+    fn on_informational(&self, resp: Response<()>) {
+        let mut resp = hyper_response::wrap(resp.map(|()| IncomingBody::empty()));
         (self.func)(self.data.0, &mut resp);
     }
 }
@@ -580,6 +840,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_headers_remove_get_replace() {
+        let mut headers = hyper_headers::default();
+        for (name, value) in [
+            (&b"Set-CookiE"[..], &b"a=b"[..]),
+            (b"Content-Encoding", b"gzip"),
+            (b"SET-COOKIE", b"c=d"),
+        ] {
+            hyper_headers_add(
+                &mut headers,
+                name.as_ptr(),
+                name.len(),
+                value.as_ptr(),
+                value.len(),
+            );
+        }
+
+        let name = b"set-cookie";
+        let value = b"e=f";
+        let code = hyper_headers_replace(
+            &mut headers,
+            name.as_ptr(),
+            name.len(),
+            1,
+            value.as_ptr(),
+            value.len(),
+        );
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+
+        let (mut ptr, mut len) = (std::ptr::null(), 0);
+        let code = hyper_headers_get(&headers, name.as_ptr(), name.len(), 1, &mut ptr, &mut len);
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"e=f");
+        let code = hyper_headers_get(&headers, name.as_ptr(), name.len(), 2, &mut ptr, &mut len);
+        assert!(matches!(code, hyper_code::HYPERE_INVALID_ARG));
+
+        let name = b"content-encoding";
+        let code = hyper_headers_remove(&mut headers, name.as_ptr(), name.len());
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+
+        let mut vec = Vec::<u8>::new();
+        hyper_headers_foreach(&headers, concat, &mut vec as *mut _ as *mut c_void);
+        assert_eq!(vec, b"Set-CookiE: a=b\r\nSET-COOKIE: e=f\r\n");
+
+        extern "C" fn concat(
+            vec: *mut c_void,
+            name: *const u8,
+            name_len: usize,
+            value: *const u8,
+            value_len: usize,
+        ) -> c_int {
+            unsafe {
+                let vec = &mut *(vec as *mut Vec<u8>);
+                let name = std::slice::from_raw_parts(name, name_len);
+                let value = std::slice::from_raw_parts(value, value_len);
+                vec.extend(name);
+                vec.extend(b": ");
+                vec.extend(value);
+                vec.extend(b"\r\n");
+            }
+            HYPER_ITER_CONTINUE
+        }
+    }
+
     #[cfg(all(feature = "http1", feature = "ffi"))]
     #[test]
     fn test_headers_foreach_order_preserved() {
@@ -643,4 +967,73 @@ mod tests {
             HYPER_ITER_CONTINUE
         }
     }
+
+    #[test]
+    fn test_response_set_reason_phrase() {
+        let mut resp = hyper_response::wrap(Response::new(IncomingBody::empty()));
+        assert_eq!(resp.reason_phrase(), b"OK");
+
+        let reason = b"Fine";
+        let code = hyper_response_set_reason_phrase(&mut resp, reason.as_ptr(), reason.len());
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(resp.reason_phrase(), b"Fine");
+
+        let reason = b"Not\r\nFine";
+        let code = hyper_response_set_reason_phrase(&mut resp, reason.as_ptr(), reason.len());
+        assert!(matches!(code, hyper_code::HYPERE_INVALID_ARG));
+        assert_eq!(resp.reason_phrase(), b"Fine");
+    }
+
+    #[test]
+    fn test_headers_get_original_name() {
+        let mut headers = hyper_headers::default();
+        let (name, value) = (b"X-Custom", b"a");
+        hyper_headers_add(
+            &mut headers,
+            name.as_ptr(),
+            name.len(),
+            value.as_ptr(),
+            value.len(),
+        );
+        headers
+            .headers
+            .insert("x-uncased", HeaderValue::from_static("b"));
+
+        let (mut ptr, mut len) = (std::ptr::null(), 0);
+        let name = b"x-custom";
+        let code = hyper_headers_get_original_name(
+            &headers,
+            name.as_ptr(),
+            name.len(),
+            0,
+            &mut ptr,
+            &mut len,
+        );
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"X-Custom");
+        let code = hyper_headers_get_original_name(
+            &headers,
+            name.as_ptr(),
+            name.len(),
+            1,
+            &mut ptr,
+            &mut len,
+        );
+        assert!(matches!(code, hyper_code::HYPERE_INVALID_ARG));
+
+        let name = b"X-Uncased";
+        let code = hyper_headers_get_original_name(
+            &headers,
+            name.as_ptr(),
+            name.len(),
+            0,
+            &mut ptr,
+            &mut len,
+        );
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(ptr, len) },
+            b"x-uncased"
+        );
+    }
 }
@@ -1,11 +1,18 @@
 use std::ffi::c_void;
 use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use crate::rt::{Read, Write};
+use bytes::Bytes;
 use libc::size_t;
 
-use super::task::hyper_context;
+use super::body::hyper_buf;
+use super::error::hyper_code;
+use super::task::{hyper_context, hyper_task_return_type, AsTaskType};
+use super::{hyper_userdata_drop, Userdata};
 
 /// Sentinel value to return from a read or write callback that the operation
 /// is pending.
@@ -23,7 +30,34 @@ type hyper_io_write_callback =
 pub struct hyper_io {
     read: hyper_io_read_callback,
     write: hyper_io_write_callback,
-    userdata: *mut c_void,
+    userdata: Userdata,
+    counters: Arc<IoCounters>,
+    info: TransportInfo,
+}
+
+/// Details of the transport under a `hyper_io`, set by the user and given to
+/// the responses received on it.
+#[derive(Clone, Default)]
+pub(super) struct TransportInfo {
+    pub(super) peer_addr: Option<Bytes>,
+    pub(super) tls_version: Option<Bytes>,
+    pub(super) alpn_protocol: Option<Bytes>,
+}
+
+/// Counts the bytes passed through a `hyper_io`.
+#[derive(Default)]
+pub(super) struct IoCounters {
+    pub(super) read: AtomicU64,
+    pub(super) written: AtomicU64,
+}
+
+/// A connection taken over by an HTTP upgrade.
+///
+/// This is the `hyper_io` the connection was made with, along with any
+/// bytes hyper read from it that weren't part of the HTTP response.
+pub struct hyper_upgraded {
+    pub(super) io: Box<hyper_io>,
+    pub(super) read_buf: Bytes,
 }
 
 ffi_fn! {
@@ -38,7 +72,9 @@ ffi_fn! {
         Box::into_raw(Box::new(hyper_io {
             read: read_noop,
             write: write_noop,
-            userdata: std::ptr::null_mut(),
+            userdata: Userdata::empty(),
+            counters: Arc::default(),
+            info: TransportInfo::default(),
         }))
     } ?= std::ptr::null_mut()
 }
@@ -57,8 +93,11 @@ ffi_fn! {
     /// Set the user data pointer for this IO to some value.
     ///
     /// This value is passed as an argument to the read and write callbacks.
-    fn hyper_io_set_userdata(io: *mut hyper_io, data: *mut c_void) {
-        non_null!(&mut *io ?= ()).userdata = data;
+    ///
+    /// If `drop` isn't `NULL`, it is called with the value once the IO is
+    /// freed, or given another user data pointer.
+    fn hyper_io_set_userdata(io: *mut hyper_io, data: *mut c_void, drop: Option<hyper_userdata_drop>) {
+        non_null!(&mut *io ?= ()).userdata = Userdata::new(data, drop);
     }
 }
 
@@ -103,6 +142,56 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set the address of the peer this IO transport is connected to.
+    ///
+    /// The address is opaque bytes to hyper, such as `192.0.2.1:443`. It is
+    /// copied, and made available on each response received on a connection
+    /// made with this IO, with `hyper_response_peer_addr`.
+    fn hyper_io_set_peer_addr(io: *mut hyper_io, addr: *const u8, addr_len: size_t) -> hyper_code {
+        let io = non_null!(&mut *io ?= hyper_code::HYPERE_INVALID_ARG);
+        let addr = unsafe { std::slice::from_raw_parts(addr, addr_len) };
+        io.info.peer_addr = Some(Bytes::copy_from_slice(addr));
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set details of the TLS session this IO transport runs over.
+    ///
+    /// The `version` is the negotiated protocol version, such as `TLSv1.3`,
+    /// and `alpn` is the protocol chosen by ALPN, such as `h2`. Either may be
+    /// `NULL` if unknown. Both are copied, and made available on each response
+    /// received on a connection made with this IO, with
+    /// `hyper_response_tls_version` and `hyper_response_alpn_protocol`.
+    fn hyper_io_set_tls_info(io: *mut hyper_io, version: *const u8, version_len: size_t, alpn: *const u8, alpn_len: size_t) -> hyper_code {
+        let io = non_null!(&mut *io ?= hyper_code::HYPERE_INVALID_ARG);
+        io.info.tls_version = unsafe { copy_optional(version, version_len) };
+        io.info.alpn_protocol = unsafe { copy_optional(alpn, alpn_len) };
+        hyper_code::HYPERE_OK
+    }
+}
+
+unsafe fn copy_optional(bytes: *const u8, len: size_t) -> Option<Bytes> {
+    if bytes.is_null() {
+        None
+    } else {
+        Some(Bytes::copy_from_slice(std::slice::from_raw_parts(
+            bytes, len,
+        )))
+    }
+}
+
+impl hyper_io {
+    pub(super) fn counters(&self) -> Arc<IoCounters> {
+        self.counters.clone()
+    }
+
+    pub(super) fn info(&self) -> TransportInfo {
+        self.info.clone()
+    }
+}
+
 /// cbindgen:ignore
 extern "C" fn read_noop(
     _userdata: *mut c_void,
@@ -132,7 +221,12 @@ impl Read for hyper_io {
         let buf_ptr = unsafe { buf.as_mut() }.as_mut_ptr() as *mut u8;
         let buf_len = buf.remaining();
 
-        match (self.read)(self.userdata, hyper_context::wrap(cx), buf_ptr, buf_len) {
+        match (self.read)(
+            self.userdata.as_ptr(),
+            hyper_context::wrap(cx),
+            buf_ptr,
+            buf_len,
+        ) {
             HYPER_IO_PENDING => Poll::Pending,
             HYPER_IO_ERROR => Poll::Ready(Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
@@ -142,6 +236,7 @@ impl Read for hyper_io {
                 // We have to trust that the user's read callback actually
                 // filled in that many bytes... :(
                 unsafe { buf.advance(ok) };
+                self.counters.read.fetch_add(ok as u64, Ordering::Relaxed);
                 Poll::Ready(Ok(()))
             }
         }
@@ -157,13 +252,23 @@ impl Write for hyper_io {
         let buf_ptr = buf.as_ptr();
         let buf_len = buf.len();
 
-        match (self.write)(self.userdata, hyper_context::wrap(cx), buf_ptr, buf_len) {
+        match (self.write)(
+            self.userdata.as_ptr(),
+            hyper_context::wrap(cx),
+            buf_ptr,
+            buf_len,
+        ) {
             HYPER_IO_PENDING => Poll::Pending,
             HYPER_IO_ERROR => Poll::Ready(Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "io error",
             ))),
-            ok => Poll::Ready(Ok(ok)),
+            ok => {
+                self.counters
+                    .written
+                    .fetch_add(ok as u64, Ordering::Relaxed);
+                Poll::Ready(Ok(ok))
+            }
         }
     }
 
@@ -178,3 +283,75 @@ impl Write for hyper_io {
 
 unsafe impl Send for hyper_io {}
 unsafe impl Sync for hyper_io {}
+
+// ===== impl hyper_upgraded =====
+
+ffi_fn! {
+    /// Take back the IO transport of an upgraded connection.
+    ///
+    /// This returns the `hyper_io *` originally passed to
+    /// `hyper_clientconn_handshake`, with the same callbacks and userdata,
+    /// so the new protocol can be spoken directly over the transport.
+    ///
+    /// hyper may have read bytes of the new protocol along with the
+    /// response. If so, `*read_buf` is set to a `hyper_buf *` of them, which
+    /// must be handled before reading from the transport, and eventually
+    /// freed with `hyper_buf_free`. Otherwise, it is set to `NULL`.
+    ///
+    /// This consumes the `hyper_upgraded *`, you shouldn't use it anymore or
+    /// free it. To avoid a memory leak, the returned IO must eventually be
+    /// freed with `hyper_io_free`.
+    fn hyper_upgraded_into_io(upgraded: *mut hyper_upgraded, read_buf: *mut *mut hyper_buf) -> *mut hyper_io {
+        let read_buf = non_null!(&mut *read_buf ?= ptr::null_mut());
+        let upgraded = non_null!(Box::from_raw(upgraded) ?= ptr::null_mut());
+        let hyper_upgraded { io, read_buf: buf } = *upgraded;
+        *read_buf = if buf.is_empty() {
+            ptr::null_mut()
+        } else {
+            Box::into_raw(Box::new(hyper_buf(buf)))
+        };
+        Box::into_raw(io)
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Free an upgraded connection, along with its IO transport and any
+    /// buffered bytes.
+    fn hyper_upgraded_free(upgraded: *mut hyper_upgraded) {
+        drop(non_null!(Box::from_raw(upgraded) ?= ()));
+    }
+}
+
+unsafe impl AsTaskType for hyper_upgraded {
+    fn as_task_type(&self) -> hyper_task_return_type {
+        hyper_task_return_type::HYPER_TASK_UPGRADED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    extern "C" fn count_drop(userdata: *mut c_void) {
+        let count = unsafe { &*(userdata as *const AtomicUsize) };
+        count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn io_userdata_dropped_once() {
+        let first = AtomicUsize::new(0);
+        let second = AtomicUsize::new(0);
+
+        let io = hyper_io_new();
+        hyper_io_set_userdata(io, &first as *const _ as *mut c_void, Some(count_drop));
+        hyper_io_set_userdata(io, &second as *const _ as *mut c_void, Some(count_drop));
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+        assert_eq!(second.load(Ordering::SeqCst), 0);
+
+        hyper_io_free(io);
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+        assert_eq!(second.load(Ordering::SeqCst), 1);
+    }
+}
@@ -53,6 +53,7 @@ mod error;
 mod http_types;
 mod io;
 mod task;
+mod time;
 
 pub use self::body::*;
 pub use self::client::*;
@@ -60,6 +61,7 @@ pub use self::error::*;
 pub use self::http_types::*;
 pub use self::io::*;
 pub use self::task::*;
+pub use self::time::*;
 
 /// Return in iter functions to continue iterating.
 pub const HYPER_ITER_CONTINUE: libc::c_int = 0;
@@ -83,6 +85,44 @@ struct UserDataPointer(*mut std::ffi::c_void);
 unsafe impl Send for UserDataPointer {}
 unsafe impl Sync for UserDataPointer {}
 
+/// A callback to free a userdata pointer, once hyper no longer uses it.
+type hyper_userdata_drop = extern "C" fn(*mut std::ffi::c_void);
+
+/// A userdata pointer owned by a hyper object.
+///
+/// The drop callback, if any, is called when this is dropped, which is when
+/// the owning object is dropped or given another userdata.
+struct Userdata {
+    data: *mut std::ffi::c_void,
+    drop: Option<hyper_userdata_drop>,
+}
+
+impl Userdata {
+    fn new(data: *mut std::ffi::c_void, drop: Option<hyper_userdata_drop>) -> Userdata {
+        Userdata { data, drop }
+    }
+
+    fn empty() -> Userdata {
+        Userdata::new(std::ptr::null_mut(), None)
+    }
+
+    fn as_ptr(&self) -> *mut std::ffi::c_void {
+        self.data
+    }
+}
+
+impl Drop for Userdata {
+    fn drop(&mut self) {
+        if let Some(drop) = self.drop {
+            drop(self.data);
+        }
+    }
+}
+
+// Same as `UserDataPointer`.
+unsafe impl Send for Userdata {}
+unsafe impl Sync for Userdata {}
+
 /// cbindgen:ignore
 static VERSION_CSTR: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
 
@@ -9,10 +9,11 @@ use std::sync::{
 use std::task::{Context, Poll};
 
 use futures_util::stream::{FuturesUnordered, Stream};
+use futures_util::task::AtomicWaker;
 use libc::c_int;
 
 use super::error::hyper_code;
-use super::UserDataPointer;
+use super::{hyper_userdata_drop, UserDataPointer, Userdata};
 
 type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 type BoxAny = Box<dyn AsTaskType + Send + Sync>;
@@ -52,13 +53,26 @@ pub struct hyper_executor {
 #[derive(Clone)]
 pub(crate) struct WeakExec(Weak<hyper_executor>);
 
-struct ExecWaker(AtomicBool);
+struct ExecWaker {
+    woken: AtomicBool,
+    /// Set while within `hyper_executor::poll_next`, which notices wakeups
+    /// by itself.
+    polling: AtomicBool,
+    wakeup: Mutex<Option<(hyper_executor_wakeup_callback, UserDataPointer)>>,
+}
+
+type hyper_executor_wakeup_callback = extern "C" fn(*mut c_void);
 
 /// An async task.
 pub struct hyper_task {
     future: BoxFuture<BoxAny>,
     output: Option<BoxAny>,
-    userdata: UserDataPointer,
+    userdata: Userdata,
+    /// Set by `hyper_task_cancel`, which may be called while the task is
+    /// owned by an executor.
+    canceled: AtomicBool,
+    /// Wakes the task once canceled, so the executor drops its future.
+    cancel_waker: AtomicWaker,
 }
 
 struct TaskFuture {
@@ -70,7 +84,7 @@ pub struct hyper_context<'a>(Context<'a>);
 
 /// A waker that is saved and used to waken a pending task.
 pub struct hyper_waker {
-    waker: std::task::Waker,
+    pub(super) waker: std::task::Waker,
 }
 
 /// A descriptor for what type a `hyper_task` value is.
@@ -86,6 +100,8 @@ pub enum hyper_task_return_type {
     HYPER_TASK_RESPONSE,
     /// The value of this task is `hyper_buf *`.
     HYPER_TASK_BUF,
+    /// The value of this task is `hyper_upgraded *`.
+    HYPER_TASK_UPGRADED,
 }
 
 pub(crate) unsafe trait AsTaskType {
@@ -103,7 +119,11 @@ impl hyper_executor {
         Arc::new(hyper_executor {
             driver: Mutex::new(FuturesUnordered::new()),
             spawn_queue: Mutex::new(Vec::new()),
-            is_woken: Arc::new(ExecWaker(AtomicBool::new(false))),
+            is_woken: Arc::new(ExecWaker {
+                woken: AtomicBool::new(false),
+                polling: AtomicBool::new(false),
+                wakeup: Mutex::new(None),
+            }),
         })
     }
 
@@ -116,9 +136,12 @@ impl hyper_executor {
             .lock()
             .unwrap()
             .push(TaskFuture { task: Some(task) });
+        futures_util::task::ArcWake::wake_by_ref(&self.is_woken);
     }
 
     fn poll_next(&self) -> Option<Box<hyper_task>> {
+        self.is_woken.polling.store(true, Ordering::SeqCst);
+
         // Drain the queue first.
         self.drain_queue();
 
@@ -127,7 +150,10 @@ impl hyper_executor {
 
         loop {
             match Pin::new(&mut *self.driver.lock().unwrap()).poll_next(&mut cx) {
-                Poll::Ready(val) => return val,
+                Poll::Ready(val) => {
+                    self.is_woken.polling.store(false, Ordering::SeqCst);
+                    return val;
+                }
                 Poll::Pending => {
                     // Check if any of the pending tasks tried to spawn
                     // some new tasks. If so, drain into the driver and loop.
@@ -135,9 +161,14 @@ impl hyper_executor {
                         continue;
                     }
 
+                    // Stop polling before checking for wakeups, so a wake
+                    // that comes after the check calls the wakeup callback.
+                    self.is_woken.polling.store(false, Ordering::SeqCst);
+
                     // If the driver called `wake` while we were polling,
                     // we should poll again immediately!
-                    if self.is_woken.0.swap(false, Ordering::SeqCst) {
+                    if self.is_woken.woken.swap(false, Ordering::SeqCst) {
+                        self.is_woken.polling.store(true, Ordering::SeqCst);
                         continue;
                     }
 
@@ -165,7 +196,20 @@ impl hyper_executor {
 
 impl futures_util::task::ArcWake for ExecWaker {
     fn wake_by_ref(me: &Arc<ExecWaker>) {
-        me.0.store(true, Ordering::SeqCst);
+        me.woken.store(true, Ordering::SeqCst);
+        if me.polling.load(Ordering::SeqCst) {
+            return;
+        }
+        // Copied out, so the callback may replace itself.
+        let wakeup = me
+            .wakeup
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|&(func, ref userdata)| (func, userdata.0));
+        if let Some((func, userdata)) = wakeup {
+            func(userdata);
+        }
     }
 }
 
@@ -183,8 +227,16 @@ where
     F::Output: Send + Sync + AsTaskType,
 {
     fn execute(&self, fut: F) {
-        if let Some(exec) = self.0.upgrade() {
-            exec.spawn(hyper_task::boxed(fut));
+        let _ = self.try_execute(fut);
+    }
+
+    fn try_execute(&self, fut: F) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.0.upgrade() {
+            Some(exec) => {
+                exec.spawn(hyper_task::boxed(fut));
+                Ok(())
+            }
+            None => Err("executor has been freed".into()),
         }
     }
 }
@@ -221,6 +273,30 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set a callback to be told when the executor should be polled again.
+    ///
+    /// The callback is passed `userdata`. It is called whenever a task is
+    /// pushed or woken up while the executor isn't being polled, such as once
+    /// a `hyper_waker` is woken. The event loop should then call
+    /// `hyper_executor_poll` until it returns `NULL`. Until the callback is
+    /// called again, there is no need to poll the executor, so the event loop
+    /// can wait for IO and timers instead.
+    ///
+    /// The callback may be called from any thread that wakes a task, so it
+    /// should only do something thread-safe and quick, like writing to an
+    /// eventfd or a pipe the event loop is waiting on. It must not call back
+    /// into the executor. It may be called more than once before the
+    /// executor is polled, and spuriously.
+    ///
+    /// Passing a `NULL` callback removes any callback previously set.
+    fn hyper_executor_set_wakeup(exec: *const hyper_executor, func: Option<hyper_executor_wakeup_callback>, userdata: *mut c_void) -> hyper_code {
+        let exec = non_null!(&*exec ?= hyper_code::HYPERE_INVALID_ARG);
+        *exec.is_woken.wakeup.lock().unwrap() = func.map(|func| (func, UserDataPointer(userdata)));
+        hyper_code::HYPERE_OK
+    }
+}
+
 ffi_fn! {
     /// Polls the executor, trying to make progress on any tasks that have notified
     /// that they are ready again.
@@ -252,7 +328,9 @@ impl hyper_task {
         Box::new(hyper_task {
             future: Box::pin(async move { fut.await.into_dyn_task_type() }),
             output: None,
-            userdata: UserDataPointer(ptr::null_mut()),
+            userdata: Userdata::empty(),
+            canceled: AtomicBool::new(false),
+            cancel_waker: AtomicWaker::new(),
         })
     }
 
@@ -268,7 +346,18 @@ impl Future for TaskFuture {
     type Output = Box<hyper_task>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match Pin::new(&mut self.task.as_mut().unwrap().future).poll(cx) {
+        let task = self.task.as_mut().unwrap();
+        task.cancel_waker.register(cx.waker());
+        if task.canceled.load(Ordering::SeqCst) {
+            let mut task = self.task.take().unwrap();
+            // Drop the future, along with everything it holds, before the
+            // task is handed back.
+            task.future = Box::pin(futures_util::future::pending());
+            task.output = Some(Box::new(crate::Error::new_canceled()));
+            return Poll::Ready(task);
+        }
+
+        match Pin::new(&mut task.future).poll(cx) {
             Poll::Ready(val) => {
                 let mut task = self.task.take().unwrap();
                 task.output = Some(val);
@@ -290,6 +379,26 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Cancel a task.
+    ///
+    /// The future of the task is dropped the next time its executor is polled,
+    /// which aborts whatever the task was doing, such as sending a request or
+    /// driving a connection. The task is then given back by
+    /// `hyper_executor_poll`, with a `HYPER_TASK_ERROR` value. From then on,
+    /// hyper won't call any callback of the task with its userdata.
+    ///
+    /// This may be called while the task is owned by an executor, including
+    /// from within a callback of the task itself. It does nothing if the task
+    /// has already completed.
+    fn hyper_task_cancel(task: *mut hyper_task) -> hyper_code {
+        let task = non_null!(&*task ?= hyper_code::HYPERE_INVALID_ARG);
+        task.canceled.store(true, Ordering::SeqCst);
+        task.cancel_waker.wake();
+        hyper_code::HYPERE_OK
+    }
+}
+
 ffi_fn! {
     /// Takes the output value of this task.
     ///
@@ -333,19 +442,22 @@ ffi_fn! {
     ///
     /// This value will be passed to task callbacks, and can be checked later
     /// with `hyper_task_userdata`.
-    fn hyper_task_set_userdata(task: *mut hyper_task, userdata: *mut c_void) {
+    ///
+    /// If `drop` isn't `NULL`, it is called with the value once the task is
+    /// freed, or given another user data pointer.
+    fn hyper_task_set_userdata(task: *mut hyper_task, userdata: *mut c_void, drop: Option<hyper_userdata_drop>) {
         if task.is_null() {
             return;
         }
 
-        unsafe { (*task).userdata = UserDataPointer(userdata) };
+        unsafe { (*task).userdata = Userdata::new(userdata, drop) };
     }
 }
 
 ffi_fn! {
     /// Retrieve the userdata that has been set via `hyper_task_set_userdata`.
     fn hyper_task_userdata(task: *mut hyper_task) -> *mut c_void {
-        non_null!(&*task ?= ptr::null_mut()).userdata.0
+        non_null!(&*task ?= ptr::null_mut()).userdata.as_ptr()
     } ?= ptr::null_mut()
 }
 
@@ -437,3 +549,89 @@ ffi_fn! {
         waker.waker.wake();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn cancel_drops_future() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(dropped.clone());
+        let task = Box::into_raw(hyper_task::boxed(async move {
+            let _guard = guard;
+            futures_util::future::pending::<()>().await
+        }));
+
+        let exec = hyper_executor_new();
+        assert!(matches!(
+            hyper_executor_push(exec, task),
+            hyper_code::HYPERE_OK
+        ));
+        assert!(hyper_executor_poll(exec).is_null());
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        assert!(matches!(hyper_task_cancel(task), hyper_code::HYPERE_OK));
+        let done = hyper_executor_poll(exec);
+        assert_eq!(done, task);
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(matches!(
+            hyper_task_type(done),
+            hyper_task_return_type::HYPER_TASK_ERROR
+        ));
+
+        let err = unsafe { Box::from_raw(hyper_task_value(done) as *mut crate::Error) };
+        assert!(err.is_canceled());
+        hyper_task_free(done);
+        hyper_executor_free(exec);
+    }
+
+    extern "C" fn count_wakeup(userdata: *mut c_void) {
+        let count = unsafe { &*(userdata as *const AtomicUsize) };
+        count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn wakeup_callback_called_outside_poll() {
+        let wakeups = AtomicUsize::new(0);
+        let (tx, rx) = futures_channel::oneshot::channel::<()>();
+        let task = Box::into_raw(hyper_task::boxed(async move {
+            let _ = rx.await;
+        }));
+
+        let exec = hyper_executor_new();
+        let code = hyper_executor_set_wakeup(
+            exec,
+            Some(count_wakeup),
+            &wakeups as *const _ as *mut c_void,
+        );
+        assert!(matches!(code, hyper_code::HYPERE_OK));
+
+        assert!(matches!(
+            hyper_executor_push(exec, task),
+            hyper_code::HYPERE_OK
+        ));
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+        assert!(hyper_executor_poll(exec).is_null());
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+
+        tx.send(()).unwrap();
+        assert_eq!(wakeups.load(Ordering::SeqCst), 2);
+        let done = hyper_executor_poll(exec);
+        assert_eq!(done, task);
+        assert_eq!(wakeups.load(Ordering::SeqCst), 2);
+
+        hyper_task_free(done);
+        hyper_executor_free(exec);
+    }
+}
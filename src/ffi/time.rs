@@ -0,0 +1,297 @@
+//! Timers for the C API.
+//!
+//! C users drive hyper with a `hyper_executor`, which has no notion of time.
+//! A `hyper_timer` lets the C event loop schedule wakeups itself. Without
+//! one, each sleep is backed by a thread, which wakes the sleeping task once
+//! its deadline passes. Either way, the task still only runs the next time
+//! the executor is polled.
+
+use std::ffi::c_void;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::task::hyper_waker;
+use super::UserDataPointer;
+use crate::rt::{Sleep, Timer};
+
+/// A timer driven by the C event loop.
+///
+/// hyper uses it to schedule the wakeups its timeouts need, instead of
+/// spawning a thread for each one.
+pub struct hyper_timer {
+    schedule: hyper_timer_schedule_callback,
+    cancel: hyper_timer_cancel_callback,
+    userdata: UserDataPointer,
+}
+
+type hyper_timer_schedule_callback = extern "C" fn(*mut c_void, u64, *mut hyper_waker) -> u64;
+
+type hyper_timer_cancel_callback = extern "C" fn(*mut c_void, u64);
+
+ffi_fn! {
+    /// Create a timer from a pair of callbacks.
+    ///
+    /// The schedule callback is passed `userdata`, a delay in milliseconds,
+    /// and a `hyper_waker *`. Once the delay has passed, the event loop must
+    /// call `hyper_waker_wake` with the waker. It returns an id for the
+    /// scheduled wakeup, of the event loop's choosing.
+    ///
+    /// The cancel callback is passed `userdata` and the id of a scheduled
+    /// wakeup that is no longer needed. If it hasn't fired yet, its waker
+    /// should be freed with `hyper_waker_free`. The cancel callback may be
+    /// passed the id of a wakeup that already fired, which it should ignore.
+    ///
+    /// Both callbacks are called from within `hyper_executor_poll`.
+    ///
+    /// To avoid a memory leak, the timer must eventually be consumed by
+    /// `hyper_timer_free` or `hyper_clientconn_options_timer`.
+    fn hyper_timer_new(schedule: hyper_timer_schedule_callback, cancel: hyper_timer_cancel_callback, userdata: *mut c_void) -> *mut hyper_timer {
+        Box::into_raw(Box::new(hyper_timer {
+            schedule,
+            cancel,
+            userdata: UserDataPointer(userdata),
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Free a timer.
+    ///
+    /// This should only be used if the timer isn't consumed by
+    /// `hyper_clientconn_options_timer`.
+    fn hyper_timer_free(timer: *mut hyper_timer) {
+        drop(non_null!(Box::from_raw(timer) ?= ()));
+    }
+}
+
+/// The `Timer` given to client connections.
+pub(super) enum FfiTimer {
+    /// Schedule wakeups with the callbacks of a `hyper_timer`.
+    Callback(Arc<hyper_timer>),
+    /// Spawn a thread for each sleep.
+    Thread,
+}
+
+impl Timer for FfiTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
+        self.sleep_until(Instant::now() + duration)
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        match self {
+            FfiTimer::Callback(timer) => Box::pin(CallbackSleep {
+                timer: timer.clone(),
+                deadline,
+                scheduled: None,
+            }),
+            FfiTimer::Thread => Box::pin(ThreadSleep::new(deadline)),
+        }
+    }
+
+    fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, new_deadline: Instant) {
+        if let Some(sleep) = sleep.as_mut().downcast_mut_pin::<CallbackSleep>() {
+            let sleep = sleep.get_mut();
+            sleep.cancel();
+            sleep.deadline = new_deadline;
+        } else if let Some(sleep) = sleep.as_mut().downcast_mut_pin::<ThreadSleep>() {
+            sleep.get_mut().reset(new_deadline);
+        } else {
+            *sleep = self.sleep_until(new_deadline);
+        }
+    }
+}
+
+struct CallbackSleep {
+    timer: Arc<hyper_timer>,
+    deadline: Instant,
+    /// The id and waker of the wakeup scheduled with the event loop.
+    scheduled: Option<(u64, Waker)>,
+}
+
+impl CallbackSleep {
+    fn cancel(&mut self) {
+        if let Some((id, _)) = self.scheduled.take() {
+            (self.timer.cancel)(self.timer.userdata.0, id);
+        }
+    }
+}
+
+impl Future for CallbackSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.get_mut();
+        let now = Instant::now();
+        if now >= me.deadline {
+            return Poll::Ready(());
+        }
+        if let Some((_, ref waker)) = me.scheduled {
+            if waker.will_wake(cx.waker()) {
+                return Poll::Pending;
+            }
+            me.cancel();
+        }
+
+        // Round up, so the task isn't woken just before the deadline.
+        let delay = me.deadline - now;
+        let millis = (delay.as_nanos() + 999_999) / 1_000_000;
+        let waker = Box::into_raw(Box::new(hyper_waker {
+            waker: cx.waker().clone(),
+        }));
+        let id = (me.timer.schedule)(me.timer.userdata.0, millis as u64, waker);
+        me.scheduled = Some((id, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Sleep for CallbackSleep {}
+
+impl Drop for CallbackSleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+struct ThreadSleep {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    deadline: Instant,
+    waker: Option<Waker>,
+    dropped: bool,
+}
+
+impl ThreadSleep {
+    fn new(deadline: Instant) -> ThreadSleep {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                deadline,
+                waker: None,
+                dropped: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        thread::Builder::new()
+            .name("hyper-ffi-timer".into())
+            .spawn(move || thread_shared.run())
+            .expect("spawn timer thread");
+        ThreadSleep { shared }
+    }
+
+    fn reset(&mut self, deadline: Instant) {
+        self.shared.state.lock().unwrap().deadline = deadline;
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Shared {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.dropped {
+            let now = Instant::now();
+            if now >= state.deadline {
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                // Nothing to do until the sleep is reset or dropped.
+                state = self.changed.wait(state).unwrap();
+            } else {
+                let timeout = state.deadline - now;
+                state = self.changed.wait_timeout(state, timeout).unwrap().0;
+            }
+        }
+    }
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if Instant::now() >= state.deadline {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Sleep for ThreadSleep {}
+
+impl Drop for ThreadSleep {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().dropped = true;
+        self.shared.changed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::super::task::hyper_waker_wake;
+    use super::*;
+
+    #[derive(Default)]
+    struct Wakeups {
+        scheduled: AtomicU64,
+        canceled: AtomicU64,
+    }
+
+    extern "C" fn schedule(userdata: *mut c_void, _millis: u64, waker: *mut hyper_waker) -> u64 {
+        let wakeups = unsafe { &*(userdata as *const Wakeups) };
+        hyper_waker_wake(waker);
+        wakeups.scheduled.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    extern "C" fn cancel(userdata: *mut c_void, id: u64) {
+        let wakeups = unsafe { &*(userdata as *const Wakeups) };
+        wakeups.canceled.store(id, Ordering::SeqCst);
+    }
+
+    #[tokio::test]
+    async fn callback_sleep_schedules_and_cancels() {
+        let wakeups = Wakeups::default();
+        let timer = hyper_timer_new(schedule, cancel, &wakeups as *const _ as *mut c_void);
+        let timer = FfiTimer::Callback(Arc::from(unsafe { Box::from_raw(timer) }));
+
+        let mut sleep = timer.sleep(Duration::from_secs(60));
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(wakeups.scheduled.load(Ordering::SeqCst), 1);
+
+        timer.reset(&mut sleep, Instant::now());
+        assert_eq!(wakeups.canceled.load(Ordering::SeqCst), 1);
+        sleep.as_mut().await;
+
+        drop(sleep);
+        assert_eq!(wakeups.scheduled.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn sleep_wakes_after_reset() {
+        let timer = FfiTimer::Thread;
+        let start = Instant::now();
+
+        let mut sleep = timer.sleep(Duration::from_secs(60));
+        timer.reset(&mut sleep, Instant::now() + Duration::from_millis(10));
+        sleep.await;
+
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+}
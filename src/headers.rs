@@ -1,9 +1,11 @@
 #[cfg(feature = "http1")]
 use bytes::BytesMut;
 use http::header::CONTENT_LENGTH;
+#[cfg(any(feature = "http2", feature = "http3"))]
+use http::header::{HeaderName, CONNECTION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE};
 use http::header::{HeaderValue, ValueIter};
 use http::HeaderMap;
-#[cfg(all(feature = "http2", feature = "client"))]
+#[cfg(all(any(feature = "http2", feature = "http3"), feature = "client"))]
 use http::Method;
 
 #[cfg(feature = "http1")]
@@ -91,7 +93,7 @@ fn from_digits(bytes: &[u8]) -> Option<u64> {
     Some(result)
 }
 
-#[cfg(all(feature = "http2", feature = "client"))]
+#[cfg(all(any(feature = "http2", feature = "http3"), feature = "client"))]
 pub(super) fn method_has_defined_payload_semantics(method: &Method) -> bool {
     match *method {
         Method::GET | Method::HEAD | Method::DELETE | Method::CONNECT => false,
@@ -99,7 +101,7 @@ pub(super) fn method_has_defined_payload_semantics(method: &Method) -> bool {
     }
 }
 
-#[cfg(feature = "http2")]
+#[cfg(any(feature = "http2", feature = "http3"))]
 pub(super) fn set_content_length_if_missing(headers: &mut HeaderMap, len: u64) {
     headers
         .entry(CONTENT_LENGTH)
@@ -133,6 +135,44 @@ pub(super) fn is_chunked_(value: &HeaderValue) -> bool {
     false
 }
 
+/// The names of the fields a message declares, in its `Trailer` header, it
+/// will send as trailers.
+///
+/// Fields that are needed before the body, such as those framing or routing
+/// the message, aren't allowed as trailers, so are left out.
+#[cfg(all(feature = "http1", feature = "client"))]
+pub(super) fn trailer_fields(headers: &HeaderMap) -> Vec<http::header::HeaderName> {
+    use http::header::{self, HeaderName};
+
+    const DISALLOWED: &[HeaderName] = &[
+        header::CONTENT_ENCODING,
+        header::CONTENT_LENGTH,
+        header::CONTENT_RANGE,
+        header::CONTENT_TYPE,
+        header::HOST,
+        header::TE,
+        header::TRAILER,
+        header::TRANSFER_ENCODING,
+    ];
+
+    let mut fields = Vec::new();
+    for line in headers.get_all(header::TRAILER) {
+        let line = match line.to_str() {
+            Ok(line) => line,
+            Err(_) => continue,
+        };
+        for name in line.split(',') {
+            match HeaderName::from_bytes(name.trim().as_bytes()) {
+                Ok(name) if !DISALLOWED.contains(&name) => fields.push(name),
+                _ => {
+                    debug!("ignoring trailer field declaration {:?}", name);
+                }
+            }
+        }
+    }
+    fields
+}
+
 #[cfg(feature = "http1")]
 pub(super) fn add_chunked(mut entry: http::header::OccupiedEntry<'_, HeaderValue>) {
     const CHUNKED: &str = "chunked";
@@ -152,3 +192,61 @@ pub(super) fn add_chunked(mut entry: http::header::OccupiedEntry<'_, HeaderValue
 
     entry.insert(HeaderValue::from_static(CHUNKED));
 }
+
+// List of connection headers from:
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection
+//
+// TE headers are allowed in HTTP/2 and HTTP/3 requests as long as the value is "trailers", so
+// they're tested separately.
+#[cfg(any(feature = "http2", feature = "http3"))]
+const CONNECTION_HEADERS: [HeaderName; 5] = [
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+];
+
+#[cfg(any(feature = "http2", feature = "http3"))]
+pub(super) fn strip_connection_headers(headers: &mut HeaderMap, is_request: bool) {
+    for header in &CONNECTION_HEADERS {
+        if headers.remove(header).is_some() {
+            warn!(
+                "Connection header illegal in HTTP/2 and HTTP/3: {}",
+                header.as_str()
+            );
+        }
+    }
+
+    if is_request {
+        if headers
+            .get(TE)
+            .map(|te_header| te_header != "trailers")
+            .unwrap_or(false)
+        {
+            warn!("TE headers not set to \"trailers\" are illegal in HTTP/2 and HTTP/3 requests");
+            headers.remove(TE);
+        }
+    } else if headers.remove(TE).is_some() {
+        warn!("TE headers illegal in HTTP/2 and HTTP/3 responses");
+    }
+
+    if let Some(header) = headers.remove(CONNECTION) {
+        warn!(
+            "Connection header illegal in HTTP/2 and HTTP/3: {}",
+            CONNECTION.as_str()
+        );
+        let header_contents = header.to_str().unwrap();
+
+        // A `Connection` header may have a comma-separated list of names of other headers that
+        // are meant for only this specific connection.
+        //
+        // Iterate these names and remove them as headers. Connection-specific headers are
+        // forbidden in HTTP/2 and HTTP/3, as that information has been moved into frame types
+        // of those protocols.
+        for name in header_contents.split(',') {
+            let name = name.trim();
+            headers.remove(name);
+        }
+    }
+}
@@ -12,7 +12,7 @@
 //!
 //! ## Features
 //!
-//! - HTTP/1 and HTTP/2
+//! - HTTP/1, HTTP/2 and HTTP/3
 //! - Asynchronous design
 //! - Leading in performance
 //! - Tested and **correct**
@@ -40,15 +40,33 @@
 //! more feature flags that are required for that item to be used.
 //!
 //! If you are new to hyper it is possible to enable the `full` feature flag
-//! which will enable all public APIs. Beware though that this will pull in
-//! many extra dependencies that you may not need.
+//! which will enable all public APIs, except for the experimental `http3`,
+//! `pool` and `high-level-client`, which need to be enabled on their own.
+//! Beware though that this will pull in many extra dependencies that you
+//! may not need.
 //!
 //! The following optional features are available:
 //!
 //! - `http1`: Enables HTTP/1 support.
 //! - `http2`: Enables HTTP/2 support.
+//! - `http3`: Enables HTTP/3 support, over a QUIC implementation plugged in
+//!   through [`rt::quic`].
+//! - `embedded`: Enables `http1`, trimmed for small devices and unikernels,
+//!   and meant to be used without `http2` or `http3`. Read buffers default
+//!   to a maximum of 16kb, and can be bounded further with an
+//!   [`rt::BufferPool`]. Time is only read from an [`rt::Timer`], so servers
+//!   leave out the `Date` header, and no background tasks are spawned.
 //! - `client`: Enables the HTTP `client`.
 //! - `server`: Enables the HTTP `server`.
+//! - `pool`: Enables a minimal HTTP/1 connection [`client::pool`], for when
+//!   `hyper-util` can't be used.
+//! - `high-level-client`: Enables `pool`, and a high-level [`client::Client`]
+//!   managing its connections, generic over the [`rt`] traits.
+//! - `smol`: Enables [`rt::smol`] adapters for the smol and async-std runtimes.
+//! - `wasi`: Enables [`rt::wasi`] adapters for WASI preview 2 streams.
+//! - `tower`: Enables conversions between hyper's and `tower`'s `Service` traits.
+//! - `simd`: Enables vectorized scanning of the parts of HTTP/1 messages not
+//!   handled by `httparse`, such as chunk extensions and trailers.
 //!
 //! [feature flags]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 //!
@@ -64,6 +82,32 @@
 //! ```notrust
 //! RUSTFLAGS="--cfg hyper_unstable_tracing" cargo rustc --features client,http1,http2,tracing --crate-type cdylib
 //!```
+//!
+//! ## Tracing spans
+//!
+//! With the `tracing` feature, hyper records a span for each connection and
+//! each request, at the `DEBUG` level. Their names and fields are stable:
+//!
+//! - `connection`, entered whenever the connection is driven:
+//!   - `id`: a number unique to the connection within the process, which
+//!     matches [`TaskDescriptor::connection_id`](rt::TaskDescriptor::connection_id)
+//!     for HTTP/2 and HTTP/3.
+//!   - `role`: `"client"` or `"server"`.
+//!   - `protocol`: `"HTTP/1"`, `"HTTP/2"` or `"HTTP/3"`.
+//! - `request`, a child of its `connection` span:
+//!   - `method`: the request method.
+//!   - `target`: the request target, as sent or received.
+//!   - `status`: the response status code, once known.
+//!
+//! A server's `request` span lasts until its response has been written, and
+//! a client's until the response head is received, so its duration is the
+//! time taken to handle the request. The service future is called within
+//! the server's `request` span.
+//!
+//! hyper doesn't know the peer address of a connection. To record it, create
+//! the connection from within a span of your own with a `peer` field, and
+//! the `connection` span will be its child.
+//!
 //! [configuration flag]: https://doc.rust-lang.org/reference/conditional-compilation.html
 #[doc(hidden)]
 pub use http;
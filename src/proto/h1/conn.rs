@@ -1,8 +1,11 @@
+#[cfg(feature = "client")]
+use std::collections::VecDeque;
 use std::fmt;
 use std::io;
 use std::marker::PhantomData;
-#[cfg(feature = "server")]
 use std::time::Duration;
+#[cfg(feature = "client")]
+use std::time::Instant;
 
 use crate::rt::{Read, Write};
 use bytes::{Buf, Bytes};
@@ -10,16 +13,32 @@ use http::header::{HeaderValue, CONNECTION};
 use http::{HeaderMap, Method, Version};
 use httparse::ParserConfig;
 
+#[cfg(feature = "server")]
+use super::decode::ChunkExtensionRejected;
 use super::io::Buffered;
 use super::{Decoder, Encode, EncodedBuf, Encoder, Http1Transaction, ParseContext, Wants};
+#[cfg(feature = "server")]
+use super::{OnParseError, OnViolation};
 use crate::body::DecodedLength;
+#[cfg(feature = "client")]
+use crate::client::conn::http1::ExcessBody;
+use crate::common::buf::BufPool;
 #[cfg(feature = "server")]
+use crate::common::date::DateHeader;
+use crate::common::io::WriteTimer;
+use crate::common::metrics::MetricsSink;
 use crate::common::time::Time;
 use crate::common::{task, Pin, Poll, Unpin};
+#[cfg(feature = "server")]
+use crate::ext::InformationalQueue;
+#[cfg(feature = "server")]
+use crate::headers::connection_close;
 use crate::headers::connection_keep_alive;
 use crate::proto::{BodyLength, MessageHead};
-#[cfg(feature = "server")]
+#[cfg(any(feature = "client", feature = "server"))]
 use crate::rt::Sleep;
+#[cfg(feature = "server")]
+use crate::server::conn::http1::{Violation, ViolationPolicy};
 
 const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
 
@@ -30,6 +49,9 @@ const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
 /// The connection will determine when a message begins and ends as well as
 /// determine if this connection can be kept alive after the message,
 /// or if it is complete.
+/// A message head read, with the length of its body, and what it wants.
+type ReadHead<S> = (MessageHead<S>, DecodedLength, Wants);
+
 pub(crate) struct Conn<I, B, T> {
     io: Buffered<I, EncodedBuf<B>>,
     state: State,
@@ -52,6 +74,7 @@ where
                 keep_alive: KA::Busy,
                 method: None,
                 h1_parser_config: ParserConfig::default(),
+                h1_max_headers: None,
                 #[cfg(feature = "server")]
                 h1_header_read_timeout: None,
                 #[cfg(feature = "server")]
@@ -59,14 +82,69 @@ where
                 #[cfg(feature = "server")]
                 h1_header_read_timeout_running: false,
                 #[cfg(feature = "server")]
+                h1_body_read_timeout: None,
+                #[cfg(feature = "server")]
+                h1_body_read_timeout_fut: None,
+                #[cfg(feature = "server")]
+                idle_timeout: None,
+                #[cfg(feature = "server")]
+                idle_timeout_fut: None,
+                #[cfg(feature = "server")]
+                max_requests: None,
+                #[cfg(feature = "server")]
+                raw_request_head: false,
+                #[cfg(feature = "server")]
+                violations: ViolationPolicy::default(),
+                #[cfg(feature = "server")]
+                on_violation: OnViolation::default(),
+                #[cfg(feature = "server")]
+                requests_read: 0,
+                #[cfg(feature = "server")]
+                on_parse_error: OnParseError::default(),
+                #[cfg(feature = "server")]
+                manual_expect_continue: false,
+                #[cfg(feature = "server")]
+                pending_continue: None,
+                #[cfg(feature = "server")]
+                informational: None,
+                #[cfg(feature = "server")]
+                date_header: DateHeader::default(),
+                #[cfg(feature = "server")]
+                header_read_timeout_response: None,
                 timer: Time::Empty,
+                write_timer: WriteTimer::new(None),
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
                 title_case_headers: false,
+                #[cfg(feature = "client")]
+                absolute_form: false,
+                #[cfg(feature = "client")]
+                expect_continue_timeout: None,
+                #[cfg(feature = "client")]
+                awaiting_continue: None,
+                #[cfg(feature = "client")]
+                response_header_timeout: None,
+                #[cfg(feature = "client")]
+                response_header_timeout_fut: None,
+                #[cfg(feature = "client")]
+                pipeline_depth: 1,
+                #[cfg(feature = "client")]
+                pipelined: VecDeque::new(),
+                #[cfg(feature = "client")]
+                read_buf_preloaded: false,
+                #[cfg(feature = "client")]
+                timings: None,
+                #[cfg(feature = "client")]
+                excess_body: ExcessBody::Reject,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                max_drain: 0,
+                drained: 0,
+                #[cfg(feature = "client")]
                 on_informational: None,
+                #[cfg(feature = "client")]
+                early_hints: None,
                 notify_read: false,
                 reading: Reading::Init,
                 writing: Writing::Init,
@@ -79,24 +157,48 @@ where
         }
     }
 
-    #[cfg(feature = "server")]
     pub(crate) fn set_timer(&mut self, timer: Time) {
         self.state.timer = timer;
     }
 
+    pub(crate) fn set_metrics(&mut self, metrics: MetricsSink) {
+        self.io.set_metrics(metrics);
+    }
+
+    pub(crate) fn metrics(&self) -> &MetricsSink {
+        self.io.metrics()
+    }
+
+    pub(crate) fn set_write_timeout(&mut self, val: Duration) {
+        self.state.write_timer = WriteTimer::new(Some(val));
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn set_flush_pipeline(&mut self, enabled: bool) {
         self.io.set_flush_pipeline(enabled);
     }
 
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_write_buf_size(&mut self, max: usize) {
+        self.io.set_max_write_buf_size(max);
+    }
+
     pub(crate) fn set_write_strategy_queue(&mut self) {
         self.io.set_write_strategy_queue();
     }
 
+    pub(crate) fn set_write_coalesce_threshold(&mut self, len: usize) {
+        self.io.set_write_coalesce_threshold(len);
+    }
+
     pub(crate) fn set_max_buf_size(&mut self, max: usize) {
         self.io.set_max_buf_size(max);
     }
 
+    pub(crate) fn set_buffer_pool(&mut self, pool: BufPool) {
+        self.io.set_buffer_pool(pool);
+    }
+
     #[cfg(feature = "client")]
     pub(crate) fn set_read_buf_exact_size(&mut self, sz: usize) {
         self.io.set_read_buf_exact_size(sz);
@@ -106,6 +208,10 @@ where
         self.io.set_write_strategy_flatten();
     }
 
+    pub(crate) fn set_h1_max_headers(&mut self, val: usize) {
+        self.state.h1_max_headers = Some(val);
+    }
+
     #[cfg(feature = "client")]
     pub(crate) fn set_h1_parser_config(&mut self, parser_config: ParserConfig) {
         self.state.h1_parser_config = parser_config;
@@ -115,11 +221,57 @@ where
         self.state.title_case_headers = true;
     }
 
+    #[cfg(feature = "client")]
+    pub(crate) fn set_absolute_form(&mut self) {
+        self.state.absolute_form = true;
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_expect_continue_timeout(&mut self, val: Duration) {
+        self.state.expect_continue_timeout = Some(val);
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_response_header_timeout(&mut self, val: Duration) {
+        self.state.response_header_timeout = Some(val);
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_pipeline_depth(&mut self, depth: usize) {
+        self.state.pipeline_depth = depth;
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_response_timings(&mut self) {
+        self.state.timings = Some(Timings {
+            unflushed: 0,
+            written: VecDeque::new(),
+            first_byte: None,
+        });
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_excess_body(&mut self, excess: ExcessBody) {
+        self.state.excess_body = excess;
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_malformed_reason_phrases(&mut self, enabled: bool) {
+        self.state.malformed_reason_phrases = Some(enabled);
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_read_buf(&mut self, buf: Bytes) {
+        if !buf.is_empty() {
+            self.io.set_read_buf(buf);
+            self.state.read_buf_preloaded = true;
+        }
+    }
+
     pub(crate) fn set_preserve_header_case(&mut self) {
         self.state.preserve_header_case = true;
     }
 
-    #[cfg(feature = "ffi")]
     pub(crate) fn set_preserve_header_order(&mut self) {
         self.state.preserve_header_order = true;
     }
@@ -134,6 +286,61 @@ where
         self.state.h1_header_read_timeout = Some(val);
     }
 
+    #[cfg(feature = "server")]
+    pub(crate) fn set_http1_body_read_timeout(&mut self, val: Duration) {
+        self.state.h1_body_read_timeout = Some(val);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_idle_timeout(&mut self, val: Duration) {
+        self.state.idle_timeout = Some(val);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_requests(&mut self, max: usize) {
+        self.state.max_requests = Some(max);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_raw_request_head(&mut self) {
+        self.state.raw_request_head = true;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_violations(&mut self, violations: ViolationPolicy, callback: OnViolation) {
+        self.state.violations = violations;
+        self.state.on_violation = callback;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_header_read_timeout_response(
+        &mut self,
+        status: http::StatusCode,
+        headers: HeaderMap,
+    ) {
+        self.state.header_read_timeout_response = Some((status, headers));
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_date_header(&mut self, date_header: DateHeader) {
+        self.state.date_header = date_header;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_on_parse_error(&mut self, callback: OnParseError) {
+        self.state.on_parse_error = callback;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_drain(&mut self, max: u64) {
+        self.state.max_drain = max;
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_manual_expect_continue(&mut self) {
+        self.state.manual_expect_continue = true;
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn set_allow_half_close(&mut self) {
         self.state.allow_half_close = true;
@@ -151,6 +358,10 @@ where
         self.state.is_read_closed()
     }
 
+    pub(crate) fn wants_keep_alive(&self) -> bool {
+        self.state.wants_keep_alive()
+    }
+
     pub(crate) fn is_write_closed(&self) -> bool {
         self.state.is_write_closed()
     }
@@ -194,16 +405,19 @@ where
     pub(super) fn poll_read_head(
         &mut self,
         cx: &mut task::Context<'_>,
-    ) -> Poll<Option<crate::Result<(MessageHead<T::Incoming>, DecodedLength, Wants)>>> {
+    ) -> Poll<Option<crate::Result<ReadHead<T::Incoming>>>> {
         debug_assert!(self.can_read_head());
         trace!("Conn::read_head");
 
-        let msg = match ready!(self.io.parse::<T>(
+        #[cfg(feature = "client")]
+        let received = (!self.io.read_buf().is_empty(), self.io.bytes_read());
+        let msg = self.io.parse::<T>(
             cx,
             ParseContext {
                 cached_headers: &mut self.state.cached_headers,
                 req_method: &mut self.state.method,
                 h1_parser_config: self.state.h1_parser_config.clone(),
+                h1_max_headers: self.state.h1_max_headers,
                 #[cfg(feature = "server")]
                 h1_header_read_timeout: self.state.h1_header_read_timeout,
                 #[cfg(feature = "server")]
@@ -212,16 +426,44 @@ where
                 h1_header_read_timeout_running: &mut self.state.h1_header_read_timeout_running,
                 #[cfg(feature = "server")]
                 timer: self.state.timer.clone(),
+                #[cfg(feature = "server")]
+                raw_request_head: self.state.raw_request_head,
+                #[cfg(feature = "server")]
+                violations: self.state.violations,
                 preserve_header_case: self.state.preserve_header_case,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: self.state.preserve_header_order,
                 h09_responses: self.state.h09_responses,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut self.state.on_informational,
+                #[cfg(feature = "client")]
+                early_hints: &mut self.state.early_hints,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut self.state.awaiting_continue,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: self.state.malformed_reason_phrases,
+            },
+        );
+        #[cfg(feature = "client")]
+        self.note_first_byte(received, &msg);
+        let mut msg = match msg {
+            Poll::Ready(Ok(msg)) => msg,
+            Poll::Ready(Err(e)) => return self.on_read_head_error(e),
+            Poll::Pending => {
+                #[cfg(feature = "client")]
+                if self.poll_response_header_timeout(cx) {
+                    debug!("timed out waiting for response head");
+                    self.state.response_header_timeout_fut = None;
+                    return self.on_read_head_error(crate::Error::new_response_header_timeout());
+                }
+                #[cfg(feature = "server")]
+                if self.poll_idle_timeout(cx) {
+                    debug!("closing idle connection");
+                    self.state.idle_timeout_fut = None;
+                    self.state.close();
+                    return Poll::Ready(None);
+                }
+                return Poll::Pending;
             }
-        )) {
-            Ok(msg) => msg,
-            Err(e) => return self.on_read_head_error(e),
         };
 
         // Note: don't deconstruct `msg` into local variables, it appears
@@ -232,16 +474,69 @@ where
         // Prevent accepting HTTP/0.9 responses after the initial one, if any.
         self.state.h09_responses = false;
 
+        #[cfg(feature = "server")]
+        {
+            self.state.idle_timeout_fut = None;
+        }
+
         // Drop any OnInformational callbacks, we're done there!
-        #[cfg(feature = "ffi")]
+        #[cfg(feature = "client")]
         {
             self.state.on_informational = None;
+            self.state.response_header_timeout_fut = None;
+            self.state.read_buf_preloaded = false;
+
+            if T::is_client() {
+                if let Some(timings) = self.response_timings() {
+                    msg.head.extensions.insert(timings);
+                }
+            }
+
+            // A final response before the `100 Continue` means the server
+            // doesn't want the body. It isn't sent, so the connection can't
+            // be reused. If requests were pipelined, the held back body is
+            // of a later one, still waiting for its own response.
+            if self.state.pipelined.is_empty() && self.state.awaiting_continue.take().is_some() {
+                debug!("response received before 100 Continue, not sending body");
+                self.state.close_write();
+            }
+
+            // The server won't answer any requests pipelined behind this one.
+            if !msg.keep_alive && !self.state.pipelined.is_empty() {
+                debug!(
+                    "connection closing with {} pipelined requests unanswered",
+                    self.state.pipelined.len()
+                );
+                self.state.pipelined.clear();
+                self.state.close_write();
+                self.state.error = Some(crate::Error::new_incomplete());
+            }
         }
 
         self.state.busy();
         self.state.keep_alive &= msg.keep_alive;
         self.state.version = msg.head.version;
 
+        #[cfg(feature = "server")]
+        if let Some(max) = self.state.max_requests {
+            self.state.requests_read += 1;
+            if self.state.requests_read >= max {
+                debug!("read {} requests, closing after this one", max);
+                self.state.disable_keep_alive();
+            }
+        }
+
+        // Informational responses can't be sent to HTTP/1.0 clients.
+        #[cfg(feature = "server")]
+        if T::should_read_first() && msg.head.version > Version::HTTP_10 {
+            let sender = self
+                .state
+                .informational
+                .get_or_insert_with(InformationalQueue::new)
+                .start_request();
+            msg.head.extensions.insert(sender);
+        }
+
         let mut wants = if msg.wants_upgrade {
             Wants::UPGRADE
         } else {
@@ -257,23 +552,113 @@ where
                 self.try_keep_alive(cx);
             }
         } else if msg.expect_continue && msg.head.version.gt(&Version::HTTP_10) {
-            self.state.reading = Reading::Continue(Decoder::new(msg.decode));
+            self.state.reading = Reading::Continue(self.body_decoder(msg.decode));
             wants = wants.add(Wants::EXPECT);
+            #[cfg(feature = "server")]
+            if self.state.manual_expect_continue {
+                let (expect, pending) = crate::ext::ExpectContinue::new();
+                msg.head.extensions.insert(expect);
+                self.state.pending_continue = Some(pending);
+            }
         } else {
-            self.state.reading = Reading::Body(Decoder::new(msg.decode));
+            self.state.reading = Reading::Body(self.body_decoder(msg.decode));
         }
 
         Poll::Ready(Some(Ok((msg.head, msg.decode, wants))))
     }
 
+    fn body_decoder(&self, len: DecodedLength) -> Decoder {
+        #[allow(unused_mut)]
+        let mut decoder = Decoder::new(len);
+        #[cfg(feature = "server")]
+        if T::should_read_first() && !self.state.violations.allows(Violation::ChunkExtension) {
+            decoder.reject_chunk_extensions();
+        }
+        decoder
+    }
+
+    /// Notes when the first byte of a response is received: once some
+    /// bytes were buffered before parsing, or read while parsing. 1xx
+    /// responses may have been read and consumed in between.
+    #[cfg(feature = "client")]
+    fn note_first_byte<Z>(&mut self, (buffered, bytes_read): (bool, u64), parsed: &Poll<Z>) {
+        if let Some(ref mut timings) = self.state.timings {
+            let received = buffered || bytes_read != self.io.bytes_read() || parsed.is_ready();
+            if timings.first_byte.is_none() && received {
+                timings.first_byte = Some(self.state.timer.now_or_system());
+            }
+        }
+    }
+
+    /// The timings of the response whose head was just parsed, if they
+    /// are recorded.
+    #[cfg(feature = "client")]
+    fn response_timings(&mut self) -> Option<crate::ext::ResponseTimings> {
+        let timings = self.state.timings.as_mut()?;
+        let now = self.state.timer.now_or_system();
+        let first_byte = timings.first_byte.take().unwrap_or(now);
+        let request_written = match timings.written.pop_front() {
+            Some(written) => written,
+            None => {
+                // The response beat the flush being reported, so the head
+                // was written, and isn't waiting to be timed anymore.
+                timings.unflushed = timings.unflushed.saturating_sub(1);
+                first_byte
+            }
+        };
+        Some(crate::ext::ResponseTimings {
+            request_written,
+            first_byte,
+            headers_received: now,
+        })
+    }
+
+    /// Deals with bytes left in the read buffer at the end of a response
+    /// body, which no pipelined request is waiting for. Returns whether
+    /// the body should fail because of them.
+    #[cfg(feature = "client")]
+    fn fails_on_excess_body(&mut self) -> bool {
+        if !T::is_client() || self.io.read_buf().is_empty() || !self.state.pipelined.is_empty() {
+            return false;
+        }
+        match self.state.excess_body {
+            // The connection fails once it's idle.
+            ExcessBody::Reject => false,
+            ExcessBody::Discard => {
+                debug!(
+                    "discarding {} bytes after the response body",
+                    self.io.read_buf().len()
+                );
+                self.io.discard_read_buf();
+                self.state.disable_keep_alive();
+                false
+            }
+            ExcessBody::FailResponse => {
+                debug!(
+                    "received {} bytes after the response body",
+                    self.io.read_buf().len()
+                );
+                true
+            }
+        }
+    }
+
     fn on_read_head_error<Z>(&mut self, e: crate::Error) -> Poll<Option<crate::Result<Z>>> {
         // If we are currently waiting on a message, then an empty
         // message should be reported as an error. If not, it is just
         // the connection closing gracefully.
         let must_error = self.should_error_on_eof();
+        // The dispatcher fails the requests pipelined behind this one along
+        // with it.
+        #[cfg(feature = "client")]
+        self.state.pipelined.clear();
         self.close_read();
         self.io.consume_leading_lines();
         let was_mid_parse = e.is_parse() || !self.io.read_buf().is_empty();
+        #[cfg(feature = "server")]
+        if let Some(violation) = e.violation() {
+            self.state.on_violation.call(violation);
+        }
         if was_mid_parse || must_error {
             // We check if the buf contains the h2 Preface
             debug!(
@@ -300,10 +685,50 @@ where
 
         let (reading, ret) = match self.state.reading {
             Reading::Body(ref mut decoder) => {
-                match ready!(decoder.decode(cx, &mut self.io)) {
+                self.io.set_read_hint(decoder.read_hint());
+                let decoded = decoder.decode(cx, &mut self.io);
+                // The timeout only runs while waiting for the client to send
+                // more of the body, not while the service doesn't read it.
+                #[cfg(feature = "server")]
+                if let Some(timeout) = self.state.h1_body_read_timeout {
+                    if decoded.is_pending() {
+                        let timer = &self.state.timer;
+                        let sleep = self
+                            .state
+                            .h1_body_read_timeout_fut
+                            .get_or_insert_with(|| timer.sleep(timeout));
+                        if sleep.as_mut().poll(cx).is_ready() {
+                            debug!("timed out reading request body");
+                            self.state.h1_body_read_timeout_fut = None;
+                            self.state.close_read();
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                BodyReadTimedOut,
+                            ))));
+                        }
+                    } else {
+                        self.state.h1_body_read_timeout_fut = None;
+                    }
+                }
+                match ready!(decoded) {
                     Ok(slice) => {
                         let (reading, chunk) = if decoder.is_eof() {
                             debug!("incoming body completed");
+                            #[cfg(feature = "client")]
+                            if self.fails_on_excess_body() {
+                                // Hand out the end of the body first, the
+                                // decoder stays at eof until polled again.
+                                if !slice.is_empty() {
+                                    return Poll::Ready(Some(Ok(slice)));
+                                }
+                                let e = io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "received bytes after the end of the body",
+                                );
+                                self.state.close_read();
+                                self.try_keep_alive(cx);
+                                return Poll::Ready(Some(Err(e)));
+                            }
                             (
                                 Reading::KeepAlive,
                                 if !slice.is_empty() {
@@ -325,13 +750,34 @@ where
                     }
                     Err(e) => {
                         debug!("incoming body decode error: {}", e);
+                        #[cfg(feature = "server")]
+                        if e.get_ref()
+                            .map_or(false, |e| e.is::<ChunkExtensionRejected>())
+                        {
+                            self.state.on_violation.call(Violation::ChunkExtension);
+                        }
                         (Reading::Closed, Poll::Ready(Some(Err(e))))
                     }
                 }
             }
             Reading::Continue(ref decoder) => {
+                // Unless the service decides whether to send it...
+                #[cfg(feature = "server")]
+                let send = match self.state.pending_continue {
+                    Some(ref pending) => {
+                        let send = ready!(pending.poll_decision(cx));
+                        self.state.pending_continue = None;
+                        send
+                    }
+                    None => true,
+                };
+                #[cfg(not(feature = "server"))]
+                let send = true;
+
                 // Write the 100 Continue if not already responded...
-                if let Writing::Init = self.state.writing {
+                if !send {
+                    trace!("service declined to send 100 Continue");
+                } else if let Writing::Init = self.state.writing {
                     trace!("automatically sending 100 Continue");
                     let cont = b"HTTP/1.1 100 Continue\r\n\r\n";
                     self.io.headers_buf().extend_from_slice(cont);
@@ -370,7 +816,7 @@ where
         }
     }
 
-    fn is_mid_message(&self) -> bool {
+    pub(crate) fn is_mid_message(&self) -> bool {
         !matches!(
             (&self.state.reading, &self.state.writing),
             (&Reading::Init, &Writing::Init)
@@ -387,8 +833,14 @@ where
         debug_assert!(T::is_client());
 
         if !self.io.read_buf().is_empty() {
+            // Bytes read before the handshake are kept for the first
+            // response, which is read once a request has been written.
+            #[cfg(feature = "client")]
+            if self.state.read_buf_preloaded {
+                return Poll::Pending;
+            }
             debug!("received an unexpected {} bytes", self.io.read_buf().len());
-            return Poll::Ready(Err(crate::Error::new_unexpected_message()));
+            return self.on_unexpected_read();
         }
 
         let num_read = ready!(self.force_io_read(cx)).map_err(crate::Error::new_io)?;
@@ -411,6 +863,19 @@ where
             "received unexpected {} bytes on an idle connection",
             num_read
         );
+        self.on_unexpected_read()
+    }
+
+    // Bytes were received on an idle connection. Unless told to discard
+    // them, the connection fails.
+    fn on_unexpected_read(&mut self) -> Poll<crate::Result<()>> {
+        #[cfg(feature = "client")]
+        if self.state.excess_body == ExcessBody::Discard {
+            trace!("discarding unexpected bytes, closing");
+            self.io.discard_read_buf();
+            self.state.close();
+            return Poll::Ready(Ok(()));
+        }
         Poll::Ready(Err(crate::Error::new_unexpected_message()))
     }
 
@@ -503,10 +968,18 @@ where
 
         match self.state.writing {
             Writing::Init => self.io.can_headers_buf(),
+            #[cfg(feature = "client")]
+            Writing::KeepAlive if self.state.can_pipeline() => self.io.can_headers_buf(),
             _ => false,
         }
     }
 
+    /// Whether a request head can be written while the response to an
+    /// earlier request is still being read.
+    pub(crate) fn is_pipelining(&self) -> bool {
+        matches!(self.state.writing, Writing::KeepAlive)
+    }
+
     pub(crate) fn can_write_body(&self) -> bool {
         match self.state.writing {
             Writing::Body(..) => true,
@@ -514,6 +987,89 @@ where
         }
     }
 
+    /// Wait until the body of a request with `Expect: 100-continue` can be
+    /// sent, after a `100 Continue` is received or the wait times out.
+    #[cfg(feature = "client")]
+    pub(crate) fn poll_expect_continue(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        if let Some(ref mut sleep) = self.state.awaiting_continue {
+            ready!(sleep.as_mut().poll(cx));
+            debug!("no 100 Continue received in time, sending body");
+            self.state.awaiting_continue = None;
+        }
+        Poll::Ready(())
+    }
+
+    /// Whether the response to a written request didn't start in time.
+    ///
+    /// The timeout starts once a whole request has been written, and the
+    /// head of its response is being waited for.
+    #[cfg(feature = "client")]
+    fn poll_response_header_timeout(&mut self, cx: &mut task::Context<'_>) -> bool {
+        let timeout = match self.state.response_header_timeout {
+            Some(timeout) if T::is_client() => timeout,
+            _ => return false,
+        };
+        let waiting = matches!(self.state.reading, Reading::Init)
+            && matches!(self.state.writing, Writing::KeepAlive | Writing::Closed);
+        if !waiting {
+            self.state.response_header_timeout_fut = None;
+            return false;
+        }
+        let timer = &self.state.timer;
+        let sleep = self
+            .state
+            .response_header_timeout_fut
+            .get_or_insert_with(|| {
+                trace!("waiting {:?} for response head", timeout);
+                timer.sleep(timeout)
+            });
+        sleep.as_mut().poll(cx).is_ready()
+    }
+
+    /// Whether an idle connection got no new request in time.
+    ///
+    /// The timeout runs while waiting for a request head, until its first
+    /// bytes are received. The header read timeout covers the rest of it.
+    #[cfg(feature = "server")]
+    fn poll_idle_timeout(&mut self, cx: &mut task::Context<'_>) -> bool {
+        let timeout = match self.state.idle_timeout {
+            Some(timeout) if T::should_read_first() => timeout,
+            _ => return false,
+        };
+        let waiting = matches!(self.state.reading, Reading::Init) && self.io.read_buf().is_empty();
+        if !waiting {
+            self.state.idle_timeout_fut = None;
+            return false;
+        }
+        let timer = &self.state.timer;
+        let sleep = self.state.idle_timeout_fut.get_or_insert_with(|| {
+            trace!("waiting {:?} for a request", timeout);
+            timer.sleep(timeout)
+        });
+        sleep.as_mut().poll(cx).is_ready()
+    }
+
+    /// Writes the informational responses sent for the current request so
+    /// far, and registers to be woken when there are more.
+    #[cfg(feature = "server")]
+    pub(crate) fn poll_write_informational(&mut self, cx: &mut task::Context<'_>) {
+        debug_assert!(self.can_write_head());
+        let responses = match self.state.informational {
+            Some(ref mut queue) => match queue.poll_queued(cx) {
+                Poll::Ready(responses) => responses,
+                Poll::Pending => return,
+            },
+            None => return,
+        };
+        for res in responses {
+            super::role::encode_informational(
+                res,
+                self.state.title_case_headers,
+                self.io.headers_buf(),
+            );
+        }
+    }
+
     pub(crate) fn can_buffer_body(&self) -> bool {
         self.io.can_buffer()
     }
@@ -541,36 +1097,127 @@ where
             self.state.busy();
         }
 
+        // A request written ahead is only answered after the responses to
+        // the ones before it, so its method is queued until then.
+        #[cfg(feature = "client")]
+        let pipelining = self.is_pipelining();
+        #[cfg(feature = "client")]
+        let mut pipelined_method = None;
+
+        #[cfg(feature = "server")]
+        if T::should_read_first() {
+            if let Some(ref mut queue) = self.state.informational {
+                for res in queue.finish_request() {
+                    super::role::encode_informational(
+                        res,
+                        self.state.title_case_headers,
+                        self.io.headers_buf(),
+                    );
+                }
+            }
+            if let Some(takeover) = head.extensions.remove::<crate::upgrade::Takeover>() {
+                trace!("response takes over the connection");
+                self.state.upgrade = Some(takeover.into_pending());
+                self.state.disable_keep_alive();
+            }
+            // Tell the client not to send more requests on this connection.
+            let last = self
+                .state
+                .max_requests
+                .map_or(false, |max| self.state.requests_read >= max);
+            if last
+                && self.state.upgrade.is_none()
+                && !head
+                    .headers
+                    .get_all(CONNECTION)
+                    .iter()
+                    .any(connection_close)
+            {
+                head.headers
+                    .append(CONNECTION, HeaderValue::from_static("close"));
+            }
+        }
+
         self.enforce_version(&mut head);
 
+        // The headers are drained while encoding, so check this first.
+        #[cfg(feature = "client")]
+        let expect_continue_timeout = if T::is_client()
+            && head.headers.get(http::header::EXPECT).map_or(false, |v| {
+                v.as_bytes().eq_ignore_ascii_case(b"100-continue")
+            }) {
+            self.state.expect_continue_timeout
+        } else {
+            None
+        };
+
+        #[cfg(feature = "server")]
+        let keep_alive = self.state.wants_keep_alive();
+        #[cfg(feature = "client")]
+        let req_method = if pipelining {
+            &mut pipelined_method
+        } else {
+            &mut self.state.method
+        };
+        #[cfg(not(feature = "client"))]
+        let req_method = &mut self.state.method;
         let buf = self.io.headers_buf();
         match super::role::encode_headers::<T>(
             Encode {
                 head: &mut head,
                 body,
                 #[cfg(feature = "server")]
-                keep_alive: self.state.wants_keep_alive(),
-                req_method: &mut self.state.method,
+                keep_alive,
+                #[cfg(feature = "server")]
+                date_header: &self.state.date_header,
+                req_method,
                 title_case_headers: self.state.title_case_headers,
+                #[cfg(feature = "client")]
+                absolute_form: self.state.absolute_form,
             },
             buf,
         ) {
             Ok(encoder) => {
-                debug_assert!(self.state.cached_headers.is_none());
                 debug_assert!(head.headers.is_empty());
-                self.state.cached_headers = Some(head.headers);
+                if self.state.cached_headers.is_none() {
+                    self.state.cached_headers = Some(head.headers);
+                }
 
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 {
-                    self.state.on_informational =
-                        head.extensions.remove::<crate::ffi::OnInformational>();
+                    if let Some(ref mut timings) = self.state.timings {
+                        timings.unflushed += 1;
+                    }
+
+                    let on_informational = head.extensions.remove::<crate::ext::OnInformational>();
+                    if pipelining {
+                        self.state.pipelined.push_back(Pipelined {
+                            method: pipelined_method,
+                            on_informational,
+                        });
+                    } else {
+                        self.state.on_informational = on_informational;
+                    }
+
+                    if let Some(timeout) = expect_continue_timeout {
+                        if !encoder.is_eof() {
+                            trace!("holding back body until 100 Continue");
+                            self.state.awaiting_continue = Some(self.state.timer.sleep(timeout));
+                        }
+                    }
                 }
 
                 Some(encoder)
             }
             Err(err) => {
                 self.state.error = Some(err);
-                self.state.writing = Writing::Closed;
+                if T::is_client() {
+                    // There's no response to wait for to a request that
+                    // wasn't written.
+                    self.state.close();
+                } else {
+                    self.state.writing = Writing::Closed;
+                }
                 None
             }
         }
@@ -662,6 +1309,31 @@ where
         self.state.writing = state;
     }
 
+    /// End the body with a trailer section, if the encoder is allowed to
+    /// send any of `trailers`, or else just end it.
+    pub(crate) fn write_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+        debug_assert!(self.can_write_body() && self.can_buffer_body());
+
+        let encoder = match self.state.writing {
+            Writing::Body(ref enc) => enc,
+            _ => unreachable!("write_trailers invalid state: {:?}", self.state.writing),
+        };
+
+        match encoder.encode_trailers(trailers, self.state.title_case_headers) {
+            Some(end) => {
+                let is_last = encoder.is_last();
+                self.io.buffer(end);
+                self.state.writing = if is_last {
+                    Writing::Closed
+                } else {
+                    Writing::KeepAlive
+                };
+                Ok(())
+            }
+            None => self.end_body(),
+        }
+    }
+
     pub(crate) fn end_body(&mut self) -> crate::Result<()> {
         debug_assert!(self.can_write_body());
 
@@ -702,6 +1374,35 @@ where
             if self.has_h2_prefix() {
                 return Err(crate::Error::new_version_h2());
             }
+            #[cfg(feature = "server")]
+            if err.is_header_timeout() {
+                let msg = self
+                    .state
+                    .header_read_timeout_response
+                    .as_ref()
+                    .and_then(T::on_header_timeout);
+                if let Some(msg) = msg {
+                    self.state.cached_headers.take();
+                    self.write_head(msg, Some(BodyLength::Known(0)));
+                    self.state.error = Some(err);
+                    return Ok(());
+                }
+            }
+            #[cfg(feature = "server")]
+            if let Some((msg, body)) = T::on_error_custom(&err, &self.state.on_parse_error) {
+                self.state.cached_headers.take();
+                let len = body.len() as u64;
+                if let Some(encoder) = self.encode_head(msg, Some(BodyLength::Known(len))) {
+                    self.io.headers_buf().extend_from_slice(&body);
+                    self.state.writing = if encoder.is_last() {
+                        Writing::Closed
+                    } else {
+                        Writing::KeepAlive
+                    };
+                }
+                self.state.error = Some(err);
+                return Ok(());
+            }
             if let Some(msg) = T::on_error(&err) {
                 // Drop the cached headers so as to not trigger a debug
                 // assert in `write_head`...
@@ -717,8 +1418,31 @@ where
     }
 
     pub(crate) fn poll_flush(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
-        ready!(Pin::new(&mut self.io).poll_flush(cx))?;
+        let res = Pin::new(&mut self.io).poll_flush(cx);
+        ready!(self.state.write_timer.poll(&self.state.timer, cx, res))?;
+        #[cfg(feature = "client")]
+        if let Some(ref mut timings) = self.state.timings {
+            if timings.unflushed > 0 {
+                let now = self.state.timer.now_or_system();
+                for _ in 0..timings.unflushed {
+                    timings.written.push_back(now);
+                }
+                timings.unflushed = 0;
+            }
+        }
         self.try_keep_alive(cx);
+        // Start waiting for the response once the request has been sent. If
+        // it already timed out, reading notices on the next loop.
+        #[cfg(feature = "client")]
+        if self.poll_response_header_timeout(cx) {
+            self.state.notify_read = true;
+        }
+        // Likewise, start waiting for the next request once the response
+        // has been sent.
+        #[cfg(feature = "server")]
+        if self.poll_idle_timeout(cx) {
+            self.state.notify_read = true;
+        }
         trace!("flushed({}): {:?}", T::LOG, self.state);
         Poll::Ready(Ok(()))
     }
@@ -736,15 +1460,42 @@ where
         }
     }
 
-    /// If the read side can be cheaply drained, do so. Otherwise, close.
-    pub(super) fn poll_drain_or_close_read(&mut self, cx: &mut task::Context<'_>) {
+    /// If the read side can be drained within the configured limit, do so.
+    /// Otherwise, close.
+    pub(super) fn poll_drain_or_close_read(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
         if let Reading::Continue(ref decoder) = self.state.reading {
             // skip sending the 100-continue
             // just move forward to a read, in case a tiny body was included
             self.state.reading = Reading::Body(decoder.clone());
+            #[cfg(feature = "server")]
+            {
+                self.state.pending_continue = None;
+            }
         }
 
-        let _ = self.poll_read_body(cx);
+        // No point reading a body off a connection that won't be reused.
+        let closing = !self.state.wants_keep_alive()
+            || match self.state.writing {
+                Writing::Body(ref encoder) => encoder.is_last(),
+                Writing::Closed => true,
+                _ => false,
+            };
+
+        while !closing && self.can_read_body() {
+            match self.poll_read_body(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.state.drained += chunk.len() as u64;
+                    if self.state.drained > self.state.max_drain {
+                        break;
+                    }
+                }
+                Poll::Ready(_) => break,
+                // With no limit, only what was already buffered is drained.
+                Poll::Pending if self.state.max_drain > 0 => return Poll::Pending,
+                Poll::Pending => break,
+            }
+        }
+        self.state.drained = 0;
 
         // If still in Reading::Body, just give up
         match self.state.reading {
@@ -753,6 +1504,7 @@ where
             }
             _ => self.close_read(),
         }
+        Poll::Ready(())
     }
 
     pub(crate) fn close_read(&mut self) {
@@ -815,24 +1567,125 @@ struct State {
     /// a body or not.
     method: Option<Method>,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
     #[cfg(feature = "server")]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(feature = "server")]
     h1_header_read_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
     #[cfg(feature = "server")]
     h1_header_read_timeout_running: bool,
+    /// How long a read of a request body may wait for more bytes.
+    #[cfg(feature = "server")]
+    h1_body_read_timeout: Option<Duration>,
+    /// Running while a read of a request body waits for more bytes.
     #[cfg(feature = "server")]
+    h1_body_read_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
+    /// How long the connection may wait for a new request.
+    #[cfg(feature = "server")]
+    idle_timeout: Option<Duration>,
+    /// Running while the connection waits for a new request.
+    #[cfg(feature = "server")]
+    idle_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
+    /// How many requests the connection may serve.
+    #[cfg(feature = "server")]
+    max_requests: Option<usize>,
+    /// How many request heads have been read.
+    #[cfg(feature = "server")]
+    requests_read: usize,
+    /// Whether requests get a `RawRequestHead` extension.
+    #[cfg(feature = "server")]
+    raw_request_head: bool,
+    /// Which framing violations requests are rejected for.
+    #[cfg(feature = "server")]
+    violations: ViolationPolicy,
+    /// Told about requests rejected for a framing violation.
+    #[cfg(feature = "server")]
+    on_violation: OnViolation,
+    /// Builds the response to requests that fail to parse, instead of the
+    /// automatic one.
+    #[cfg(feature = "server")]
+    on_parse_error: OnParseError,
+    /// Whether the service decides if `100 Continue` is sent, through an
+    /// `ExpectContinue` in the request's extensions.
+    #[cfg(feature = "server")]
+    manual_expect_continue: bool,
+    /// The service's decision for the current request, if it's still
+    /// waiting on one.
+    #[cfg(feature = "server")]
+    pending_continue: Option<crate::ext::PendingContinue>,
+    /// Informational responses the service sends ahead of the final one.
+    #[cfg(feature = "server")]
+    informational: Option<InformationalQueue>,
+    /// Where the time for `Date` headers comes from.
+    #[cfg(feature = "server")]
+    date_header: DateHeader,
+    /// Written when the header read timeout fires, instead of closing the
+    /// connection without a response.
+    #[cfg(feature = "server")]
+    header_read_timeout_response: Option<(http::StatusCode, HeaderMap)>,
     timer: Time,
+    /// Fails the connection if a flush stays pending for too long.
+    write_timer: WriteTimer,
     preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     preserve_header_order: bool,
     title_case_headers: bool,
+    /// Whether origin-form request targets are written in absolute-form.
+    #[cfg(feature = "client")]
+    absolute_form: bool,
+    /// How long to hold back the body of a request with
+    /// `Expect: 100-continue`, waiting for a `100 Continue`. If unset, the
+    /// body is sent right away.
+    #[cfg(feature = "client")]
+    expect_continue_timeout: Option<Duration>,
+    /// Set while the body of the current request is held back. Cleared when
+    /// a `100 Continue` is received, or the wait times out.
+    #[cfg(feature = "client")]
+    awaiting_continue: Option<Pin<Box<dyn Sleep>>>,
+    /// How many requests may be written before the response to the first
+    /// has been read. 1 unless pipelining is enabled.
+    #[cfg(feature = "client")]
+    pipeline_depth: usize,
+    /// How long to wait for the head of a response, once the request has
+    /// been written.
+    #[cfg(feature = "client")]
+    response_header_timeout: Option<Duration>,
+    /// Running while the head of a response is waited for.
+    #[cfg(feature = "client")]
+    response_header_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
+    /// Requests written after the one whose response is being read, oldest
+    /// first.
+    #[cfg(feature = "client")]
+    pipelined: VecDeque<Pipelined>,
+    /// Whether the read buffer was filled at the handshake, with bytes that
+    /// are part of the first response rather than unexpected.
+    #[cfg(feature = "client")]
+    read_buf_preloaded: bool,
+    /// Set if responses are given a `ResponseTimings` extension.
+    #[cfg(feature = "client")]
+    timings: Option<Timings>,
+    /// What to do with bytes received after the end of a response body,
+    /// that no request is waiting for.
+    #[cfg(feature = "client")]
+    excess_body: ExcessBody,
+    /// Whether to accept status lines without a reason phrase, or with
+    /// control characters in it. If unset, only the former are.
+    #[cfg(feature = "client")]
+    malformed_reason_phrases: Option<bool>,
     h09_responses: bool,
+    /// How many bytes of a body nobody wants to read may be drained, to
+    /// keep the connection alive.
+    max_drain: u64,
+    /// Bytes drained so far from the current unwanted body.
+    drained: u64,
     /// If set, called with each 1xx informational response received for
     /// the current request. MUST be unset after a non-1xx response is
     /// received.
-    #[cfg(feature = "ffi")]
-    on_informational: Option<crate::ffi::OnInformational>,
+    #[cfg(feature = "client")]
+    on_informational: Option<crate::ext::OnInformational>,
+    /// Headers of the `103 Early Hints` responses received for the current
+    /// request, given to the final response.
+    #[cfg(feature = "client")]
+    early_hints: Option<crate::ext::EarlyHints>,
     /// Set to true when the Dispatcher should poll read operations
     /// again. See the `maybe_notify` method for more.
     notify_read: bool,
@@ -855,6 +1708,27 @@ enum Reading {
     Closed,
 }
 
+/// A request written while the response to an earlier one was still being
+/// read.
+#[cfg(feature = "client")]
+struct Pipelined {
+    method: Option<Method>,
+    on_informational: Option<crate::ext::OnInformational>,
+}
+
+/// When the requests in flight were written, and the response being read
+/// started.
+#[cfg(feature = "client")]
+struct Timings {
+    /// Request heads encoded, but not flushed yet.
+    unflushed: usize,
+    /// When the heads of the requests waiting for a response were flushed,
+    /// oldest first.
+    written: VecDeque<Instant>,
+    /// When the first byte of the response being read was received.
+    first_byte: Option<Instant>,
+}
+
 enum Writing {
     Init,
     Body(Encoder),
@@ -942,12 +1816,41 @@ impl State {
         self.reading = Reading::Closed;
         self.writing = Writing::Closed;
         self.keep_alive.disable();
+        #[cfg(feature = "client")]
+        self.fail_pipelined();
     }
 
     fn close_read(&mut self) {
         trace!("State::close_read()");
         self.reading = Reading::Closed;
         self.keep_alive.disable();
+        #[cfg(feature = "client")]
+        self.fail_pipelined();
+    }
+
+    /// Whether another request can be written ahead of the response to the
+    /// last one.
+    #[cfg(feature = "client")]
+    fn can_pipeline(&self) -> bool {
+        self.pipelined.len() + 1 < self.pipeline_depth
+            && self.version == Version::HTTP_11
+            && matches!(self.keep_alive.status(), KA::Busy)
+    }
+
+    /// Once the responses can't be read anymore, the pipelined requests will
+    /// never be answered.
+    #[cfg(feature = "client")]
+    fn fail_pipelined(&mut self) {
+        if !self.pipelined.is_empty() {
+            debug!(
+                "connection closed with {} pipelined requests unanswered",
+                self.pipelined.len()
+            );
+            self.pipelined.clear();
+            if self.error.is_none() {
+                self.error = Some(crate::Error::new_incomplete());
+            }
+        }
     }
 
     fn close_write(&mut self) {
@@ -965,6 +1868,17 @@ impl State {
     }
 
     fn try_keep_alive<T: Http1Transaction>(&mut self) {
+        #[cfg(feature = "client")]
+        if let Reading::KeepAlive = self.reading {
+            if let Some(next) = self.pipelined.pop_front() {
+                trace!("reading response to pipelined request");
+                self.method = next.method;
+                self.on_informational = next.on_informational;
+                self.reading = Reading::Init;
+                return;
+            }
+        }
+
         match (&self.reading, &self.writing) {
             (&Reading::KeepAlive, &Writing::KeepAlive) => {
                 if let KA::Busy = self.keep_alive.status() {
@@ -1039,6 +1953,28 @@ impl State {
     }
 }
 
+/// The request body wasn't received within the body read timeout.
+///
+/// An `io::Error` skips the error it wraps when looking for sources, so
+/// this has `TimedOut` as its source for `Error::is_timeout` to find.
+#[cfg(feature = "server")]
+#[derive(Debug)]
+struct BodyReadTimedOut;
+
+#[cfg(feature = "server")]
+impl fmt::Display for BodyReadTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("request body read timed out")
+    }
+}
+
+#[cfg(feature = "server")]
+impl std::error::Error for BodyReadTimedOut {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&crate::error::TimedOut)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(all(feature = "nightly", not(miri)))]
@@ -19,6 +19,8 @@ use self::Kind::{Chunked, Eof, Length};
 #[derive(Clone, PartialEq)]
 pub(crate) struct Decoder {
     kind: Kind,
+    /// Whether chunk extensions fail decoding, instead of being ignored.
+    reject_extensions: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -68,18 +70,21 @@ impl Decoder {
     pub(crate) fn length(x: u64) -> Decoder {
         Decoder {
             kind: Kind::Length(x),
+            reject_extensions: false,
         }
     }
 
     pub(crate) fn chunked() -> Decoder {
         Decoder {
             kind: Kind::Chunked(ChunkedState::Size, 0),
+            reject_extensions: false,
         }
     }
 
     pub(crate) fn eof() -> Decoder {
         Decoder {
             kind: Kind::Eof(false),
+            reject_extensions: false,
         }
     }
 
@@ -91,6 +96,11 @@ impl Decoder {
         }
     }
 
+    #[cfg(feature = "server")]
+    pub(crate) fn reject_chunk_extensions(&mut self) {
+        self.reject_extensions = true;
+    }
+
     // methods
 
     pub(crate) fn is_eof(&self) -> bool {
@@ -100,6 +110,16 @@ impl Decoder {
         )
     }
 
+    /// How many more bytes of body data are known to be coming, if any.
+    pub(crate) fn read_hint(&self) -> Option<usize> {
+        match self.kind {
+            Length(remaining) | Chunked(ChunkedState::Body, remaining) if remaining > 0 => {
+                Some(std::cmp::min(remaining, usize::MAX as u64) as usize)
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn decode<R: MemRead>(
         &mut self,
         cx: &mut task::Context<'_>,
@@ -132,6 +152,12 @@ impl Decoder {
                     let mut buf = None;
                     // advances the chunked state
                     *state = ready!(state.step(cx, body, size, &mut buf))?;
+                    if *state == ChunkedState::Extension && self.reject_extensions {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            ChunkExtensionRejected,
+                        )));
+                    }
                     if *state == ChunkedState::End {
                         trace!("end of chunked");
                         return Poll::Ready(Ok(Bytes::new()));
@@ -181,6 +207,19 @@ macro_rules! byte (
     })
 );
 
+// Skips ahead to the first `a` or `b`, returning the last byte skipped.
+macro_rules! last_byte (
+    ($rdr:ident, $cx:expr, $a:expr, $b:expr) => ({
+        let buf = ready!($rdr.read_mem_until($cx, $a, $b))?;
+        if let Some(&last) = buf.last() {
+            last
+        } else {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "unexpected EOF during chunk size line")));
+        }
+    })
+);
+
 impl ChunkedState {
     fn step<R: MemRead>(
         &self,
@@ -277,7 +316,7 @@ impl ChunkedState {
         // However, some implementations may not check for the CR, so to save
         // them from themselves, we reject extensions containing plain LF as
         // well.
-        match byte!(rdr, cx) {
+        match last_byte!(rdr, cx, b'\r', b'\n') {
             b'\r' => Poll::Ready(Ok(ChunkedState::SizeLf)),
             b'\n' => Poll::Ready(Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -372,7 +411,7 @@ impl ChunkedState {
         rdr: &mut R,
     ) -> Poll<Result<ChunkedState, io::Error>> {
         trace!("read_trailer");
-        match byte!(rdr, cx) {
+        match last_byte!(rdr, cx, b'\r', b'\n') {
             b'\r' => Poll::Ready(Ok(ChunkedState::TrailerLf)),
             _ => Poll::Ready(Ok(ChunkedState::Trailer)),
         }
@@ -424,6 +463,17 @@ impl fmt::Display for IncompleteBody {
 
 impl StdError for IncompleteBody {}
 
+#[derive(Debug)]
+pub(crate) struct ChunkExtensionRejected;
+
+impl fmt::Display for ChunkExtensionRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk extensions are not allowed")
+    }
+}
+
+impl StdError for ChunkExtensionRejected {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -443,6 +493,16 @@ mod tests {
                 Poll::Ready(Ok(Bytes::new()))
             }
         }
+
+        fn read_mem_until(
+            &mut self,
+            cx: &mut task::Context<'_>,
+            a: u8,
+            b: u8,
+        ) -> Poll<io::Result<Bytes>> {
+            let len = crate::proto::h1::scan::find2(a, b, self).map_or(self.len(), |i| i + 1);
+            self.read_mem(cx, len)
+        }
     }
 
     impl<'a> MemRead for &'a mut (dyn Read + Unpin) {
@@ -461,6 +521,16 @@ mod tests {
             let ret = self.split_to(n);
             Poll::Ready(Ok(ret))
         }
+
+        fn read_mem_until(
+            &mut self,
+            cx: &mut task::Context<'_>,
+            a: u8,
+            b: u8,
+        ) -> Poll<io::Result<Bytes>> {
+            let len = crate::proto::h1::scan::find2(a, b, self).map_or(self.len(), |i| i + 1);
+            self.read_mem(cx, len)
+        }
     }
 
     /*
@@ -473,6 +543,20 @@ mod tests {
     use crate::mock::AsyncIo;
     */
 
+    #[test]
+    fn test_read_hint() {
+        assert_eq!(Decoder::length(10).read_hint(), Some(10));
+        assert_eq!(Decoder::length(0).read_hint(), None);
+        assert_eq!(Decoder::eof().read_hint(), None);
+        // chunk sizes are only known once inside a chunk body
+        assert_eq!(Decoder::chunked().read_hint(), None);
+        let decoder = Decoder {
+            kind: Kind::Chunked(ChunkedState::Body, 5),
+            reject_extensions: false,
+        };
+        assert_eq!(decoder.read_hint(), Some(5));
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn test_read_chunk_size() {
@@ -592,6 +676,37 @@ mod tests {
         assert_eq!("1234567890abcdef", &result);
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_read_chunked_trailers_skipped() {
+        let mut mock_buf = &b"\
+            3;ext=long-extension-value\r\n\
+            foo\r\n\
+            0\r\n\
+            cookie: a=1; b=2; c=3\r\n\
+            x-trailer: value\r\n\
+            \r\n\
+        "[..];
+        let mut decoder = Decoder::chunked();
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert_eq!(buf, "foo");
+        let buf = decoder.decode_fut(&mut mock_buf).await.expect("decode");
+        assert!(buf.is_empty());
+        assert!(decoder.is_eof());
+        assert!(mock_buf.is_empty());
+    }
+
+    #[cfg(all(not(miri), feature = "server"))]
+    #[tokio::test]
+    async fn test_read_chunked_extension_rejected() {
+        let mut mock_buf = &b"3;ext=value\r\nfoo\r\n0\r\n\r\n"[..];
+        let mut decoder = Decoder::chunked();
+        decoder.reject_chunk_extensions();
+        let e = decoder.decode_fut(&mut mock_buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.get_ref().unwrap().is::<ChunkExtensionRejected>());
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn test_read_chunked_trailer_with_missing_lf() {
@@ -713,6 +828,55 @@ mod tests {
         });
     }
 
+    #[cfg(all(feature = "nightly", not(miri)))]
+    #[bench]
+    fn bench_decode_chunked_extension_8kb(b: &mut test::Bencher) {
+        let rt = new_runtime();
+
+        const LEN: usize = 8 * 1024;
+        let mut vec = Vec::new();
+        vec.extend(b"1;ext=");
+        vec.extend(&[b'a'; LEN][..]);
+        vec.extend(b"\r\n0\r\n\r\n");
+        let content = Bytes::from(vec);
+
+        b.bytes = LEN as u64;
+
+        b.iter(|| {
+            let mut decoder = Decoder::chunked();
+            rt.block_on(async {
+                let mut raw = content.clone();
+                decoder.decode_fut(&mut raw).await.unwrap();
+            });
+        });
+    }
+
+    #[cfg(all(feature = "nightly", not(miri)))]
+    #[bench]
+    fn bench_decode_chunked_trailers_8kb(b: &mut test::Bencher) {
+        let rt = new_runtime();
+
+        const LEN: usize = 8 * 1024;
+        let mut vec = Vec::new();
+        vec.extend(b"0\r\n");
+        for i in 0..LEN / 64 {
+            vec.extend(format!("x-trailer-{:03}: {:0>47}\r\n", i, i).as_bytes());
+        }
+        vec.extend(b"\r\n");
+        let content = Bytes::from(vec);
+
+        b.bytes = content.len() as u64;
+
+        b.iter(|| {
+            let mut decoder = Decoder::chunked();
+            rt.block_on(async {
+                let mut raw = content.clone();
+                let chunk = decoder.decode_fut(&mut raw).await.unwrap();
+                assert!(chunk.is_empty());
+            });
+        });
+    }
+
     #[cfg(all(feature = "nightly", not(miri)))]
     #[bench]
     fn bench_decode_length_1kb(b: &mut test::Bencher) {
@@ -5,19 +5,38 @@ use bytes::{Buf, Bytes};
 use http::Request;
 
 use super::{Http1Transaction, Wants};
-use crate::body::{Body, DecodedLength, Incoming as IncomingBody};
+use crate::body::{Body, ChannelArena, DecodedLength, Incoming as IncomingBody};
+use crate::common::lifecycle::{Lifecycle, OpenConnection};
+use crate::common::metrics::{Elapsed, MetricsSink};
 use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::proto::span::{ConnSpan, RequestSpan};
 use crate::proto::{BodyLength, Conn, Dispatched, MessageHead, RequestHead};
+use crate::rt::CloseReason;
 use crate::upgrade::OnUpgrade;
 
 pub(crate) struct Dispatcher<D, Bs: Body, I, T> {
     conn: Conn<I, Bs::Data, T>,
     dispatch: D,
     body_tx: Option<crate::body::Sender>,
+    bodies: ChannelArena,
     body_rx: Pin<Box<Option<Bs>>>,
     is_closing: bool,
+    poll_budget: usize,
+    span: ConnSpan,
+    _opened: Elapsed,
+    connection: OpenConnection,
 }
 
+/// The default number of read/write iterations `poll_loop` may make before
+/// yielding back to the executor.
+///
+/// 16 was chosen arbitrarily, as that is number of pipelined requests
+/// benchmarks often use.
+pub(crate) const DEFAULT_POLL_BUDGET: usize = 16;
+
+/// A message to write, with its body, from `Dispatch::poll_msg`.
+type PollMsg<T, B, E> = Poll<Option<Result<(T, B), E>>>;
+
 pub(crate) trait Dispatch {
     type PollItem;
     type PollBody;
@@ -26,10 +45,22 @@ pub(crate) trait Dispatch {
     fn poll_msg(
         self: Pin<&mut Self>,
         cx: &mut task::Context<'_>,
-    ) -> Poll<Option<Result<(Self::PollItem, Self::PollBody), Self::PollError>>>;
+    ) -> PollMsg<Self::PollItem, Self::PollBody, Self::PollError>;
     fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, IncomingBody)>)
         -> crate::Result<()>;
-    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), ()>>;
+    /// Called once the message from `poll_msg`, including its body, has
+    /// been written.
+    fn sent_msg(&mut self) {}
+    /// Called with whether the connection will be kept alive after the
+    /// current message, before a received message is handed over, and after
+    /// each poll.
+    fn keep_alive(&mut self, _keep_alive: bool) {}
+    /// An `Err(None)` means no more messages will be received, and
+    /// `Err(Some(_))` that the dispatch failed.
+    fn poll_ready(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Option<Self::PollError>>>;
     fn should_poll(&self) -> bool;
 }
 
@@ -39,20 +70,31 @@ cfg_server! {
     pub(crate) struct Server<S: HttpService<B>, B> {
         in_flight: Pin<Box<Option<S::Future>>>,
         pub(crate) service: S,
+        metrics: MetricsSink,
+        lifecycle: Lifecycle,
+        span: Option<RequestSpan>,
     }
 }
 
 cfg_client! {
     pin_project_lite::pin_project! {
         pub(crate) struct Client<B> {
-            callback: Option<crate::client::dispatch::Callback<Request<B>, http::Response<IncomingBody>>>,
+            // The requests written and waiting for a response head, oldest
+            // first. Only ever more than one if pipelining.
+            in_flight: VecDeque<(ClientCallback<B>, RequestSpan)>,
+            pipeline_depth: usize,
             #[pin]
             rx: ClientRx<B>,
             rx_closed: bool,
+            metrics: MetricsSink,
+            lifecycle: Lifecycle,
+            // Shared with the `SendRequest`, for `is_keep_alive`.
+            keep_alive: Arc<AtomicBool>,
         }
     }
 
     type ClientRx<B> = crate::client::dispatch::Receiver<Request<B>, http::Response<IncomingBody>>;
+    type ClientCallback<B> = crate::client::dispatch::Callback<Request<B>, http::Response<IncomingBody>>;
 }
 
 impl<D, Bs, I, T> Dispatcher<D, Bs, I, T>
@@ -69,17 +111,37 @@ where
     Bs::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
     pub(crate) fn new(dispatch: D, conn: Conn<I, Bs::Data, T>) -> Self {
+        let opened = conn.metrics().connection_opened();
         Dispatcher {
             conn,
             dispatch,
             body_tx: None,
+            bodies: ChannelArena::default(),
             body_rx: Box::pin(None),
             is_closing: false,
+            poll_budget: DEFAULT_POLL_BUDGET,
+            span: ConnSpan::new(
+                crate::rt::next_connection_id(),
+                if T::is_server() { "server" } else { "client" },
+                "HTTP/1",
+            ),
+            _opened: opened,
+            connection: OpenConnection::default(),
         }
     }
 
+    pub(crate) fn set_lifecycle(&mut self, connection: OpenConnection) {
+        self.connection = connection;
+    }
+
+    pub(crate) fn set_poll_budget(&mut self, budget: usize) {
+        debug_assert!(budget > 0);
+        self.poll_budget = budget;
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn disable_keep_alive(&mut self) {
+        self.connection.lifecycle().closing();
         self.conn.disable_keep_alive();
 
         // If keep alive has been disabled and no read or write has been seen on
@@ -90,6 +152,17 @@ where
         }
     }
 
+    /// Closes the connection without finishing the message in flight, if
+    /// any, and returns how many requests were aborted: 0 or 1, since
+    /// pipelined requests are only read once the one before is done.
+    #[cfg(feature = "server")]
+    pub(crate) fn abort(&mut self) -> usize {
+        self.connection.lifecycle().closing();
+        let aborted = (self.dispatch.should_poll() || self.conn.is_mid_message()) as usize;
+        self.close();
+        aborted
+    }
+
     pub(crate) fn into_inner(self) -> (I, Bytes, D) {
         let (io, buf) = self.conn.into_inner();
         (io, buf, self.dispatch)
@@ -119,7 +192,17 @@ where
         cx: &mut task::Context<'_>,
         should_shutdown: bool,
     ) -> Poll<crate::Result<Dispatched>> {
-        Poll::Ready(ready!(self.poll_inner(cx, should_shutdown)).or_else(|e| {
+        let _entered = self.span.enter();
+        let ret = self.poll_inner(cx, should_shutdown);
+        self.dispatch.keep_alive(self.conn.wants_keep_alive());
+        let ret = ready!(ret);
+        let lifecycle = self.connection.lifecycle();
+        match ret {
+            Ok(Dispatched::Shutdown) => lifecycle.closed(CloseReason::Done),
+            Ok(Dispatched::Upgrade(_)) => lifecycle.closed(CloseReason::Upgraded),
+            Err(ref e) => lifecycle.closed(CloseReason::Error(e)),
+        }
+        Poll::Ready(ret.or_else(|e| {
             // Be sure to alert a streaming body of the failure.
             if let Some(mut body) = self.body_tx.take() {
                 body.send_error(crate::Error::new_body("connection error"));
@@ -158,11 +241,9 @@ where
 
     fn poll_loop(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         // Limit the looping on this connection, in case it is ready far too
-        // often, so that other futures don't starve.
-        //
-        // 16 was chosen arbitrarily, as that is number of pipelined requests
-        // benchmarks often use. Perhaps it should be a config option instead.
-        for _ in 0..16 {
+        // often, so that other futures don't starve. This doesn't rely on
+        // any runtime's cooperative scheduling, so it works with any executor.
+        for _ in 0..self.poll_budget {
             let _ = self.poll_read(cx)?;
             let _ = self.poll_write(cx)?;
             let _ = self.poll_flush(cx)?;
@@ -204,7 +285,7 @@ where
                             // user doesn't care about the body
                             // so we should stop reading
                             trace!("body receiver dropped before eof, draining or closing");
-                            self.conn.poll_drain_or_close_read(cx);
+                            ready!(self.conn.poll_drain_or_close_read(cx));
                             continue;
                         }
                     }
@@ -234,6 +315,9 @@ where
                 } else {
                     // just drop, the body will close automatically
                 }
+            } else if self.conn.can_read_body() {
+                // the body receiver is gone, but draining was interrupted
+                ready!(self.conn.poll_drain_or_close_read(cx));
             } else {
                 return self.conn.poll_read_keep_alive(cx);
             }
@@ -244,11 +328,16 @@ where
         // can dispatch receive, or does it still care about other incoming message?
         match ready!(self.dispatch.poll_ready(cx)) {
             Ok(()) => (),
-            Err(()) => {
+            Err(None) => {
                 trace!("dispatch no longer receiving messages");
                 self.close();
                 return Poll::Ready(Ok(()));
             }
+            Err(Some(err)) => {
+                debug!("dispatch not ready, closing");
+                self.close();
+                return Poll::Ready(Err(crate::Error::new_user_service(err)));
+            }
         }
 
         // dispatch is ready for a message, try to read one
@@ -257,8 +346,7 @@ where
                 let body = match body_len {
                     DecodedLength::ZERO => IncomingBody::empty(),
                     other => {
-                        let (tx, rx) =
-                            IncomingBody::new_channel(other, wants.contains(Wants::EXPECT));
+                        let (tx, rx) = self.bodies.channel(other, wants.contains(Wants::EXPECT));
                         self.body_tx = Some(tx);
                         rx
                     }
@@ -272,6 +360,7 @@ where
                     );
                     head.extensions.insert(upgrade);
                 }
+                self.dispatch.keep_alive(self.conn.wants_keep_alive());
                 self.dispatch.recv_msg(Ok((head, body)))?;
                 Poll::Ready(Ok(()))
             }
@@ -305,6 +394,10 @@ where
                 && self.conn.can_write_head()
                 && self.dispatch.should_poll()
             {
+                #[cfg(feature = "server")]
+                if T::should_read_first() {
+                    self.conn.poll_write_informational(cx);
+                }
                 if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx)) {
                     let (head, body) = msg.map_err(crate::Error::new_user_service)?;
 
@@ -320,7 +413,16 @@ where
                         self.body_rx.set(Some(body));
                         btype
                     };
+                    let sent = body_type.is_none();
                     self.conn.write_head(head, body_type);
+                    if sent {
+                        self.dispatch.sent_msg();
+                    }
+                } else if self.conn.is_pipelining() {
+                    // No more requests, but the responses to those already
+                    // written are still read.
+                    self.conn.close_write();
+                    return Poll::Ready(Ok(()));
                 } else {
                     self.close();
                     return Poll::Ready(Ok(()));
@@ -342,17 +444,28 @@ where
                         continue;
                     }
 
+                    #[cfg(feature = "client")]
+                    ready!(self.conn.poll_expect_continue(cx));
+
                     let item = ready!(body.as_mut().poll_frame(cx));
                     if let Some(item) = item {
                         let frame = item.map_err(|e| {
                             *clear_body = true;
                             crate::Error::new_user_body(e)
                         })?;
-                        let chunk = if let Ok(data) = frame.into_data() {
-                            data
-                        } else {
-                            trace!("discarding non-data frame");
-                            continue;
+                        let chunk = match frame.into_data() {
+                            Ok(data) => data,
+                            Err(frame) => {
+                                if let Ok(trailers) = frame.into_trailers() {
+                                    // trailers are always the last frame
+                                    *clear_body = true;
+                                    self.conn.write_trailers(trailers)?;
+                                    self.dispatch.sent_msg();
+                                } else {
+                                    trace!("discarding non-data frame");
+                                }
+                                continue;
+                            }
                         };
                         let eos = body.is_end_stream();
                         if eos {
@@ -363,6 +476,7 @@ where
                             } else {
                                 self.conn.write_body_and_end(chunk);
                             }
+                            self.dispatch.sent_msg();
                         } else {
                             if chunk.remaining() == 0 {
                                 trace!("discarding empty chunk");
@@ -373,11 +487,13 @@ where
                     } else {
                         *clear_body = true;
                         self.conn.end_body()?;
+                        self.dispatch.sent_msg();
                     }
                 } else {
                     // If there's no body_rx, end the body
                     if self.conn.can_write_body() {
                         self.conn.end_body()?;
+                        self.dispatch.sent_msg();
                     } else {
                         return Poll::Pending;
                     }
@@ -469,10 +585,13 @@ cfg_server! {
     where
         S: HttpService<B>,
     {
-        pub(crate) fn new(service: S) -> Server<S, B> {
+        pub(crate) fn new(service: S, metrics: MetricsSink, lifecycle: Lifecycle) -> Server<S, B> {
             Server {
                 in_flight: Box::pin(None),
                 service,
+                metrics,
+                lifecycle,
+                span: None,
             }
         }
 
@@ -498,11 +617,15 @@ cfg_server! {
         fn poll_msg(
             mut self: Pin<&mut Self>,
             cx: &mut task::Context<'_>,
-        ) -> Poll<Option<Result<(Self::PollItem, Self::PollBody), Self::PollError>>> {
+        ) -> PollMsg<Self::PollItem, Self::PollBody, Self::PollError> {
             let mut this = self.as_mut();
+            let _entered = this.span.as_ref().map(RequestSpan::enter);
             let ret = if let Some(ref mut fut) = this.in_flight.as_mut().as_pin_mut() {
                 let resp = ready!(fut.as_mut().poll(cx)?);
                 let (parts, body) = resp.into_parts();
+                if let Some(ref span) = this.span {
+                    span.record_status(parts.status);
+                }
                 let head = MessageHead {
                     version: parts.version,
                     subject: parts.status,
@@ -521,6 +644,13 @@ cfg_server! {
 
         fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, IncomingBody)>) -> crate::Result<()> {
             let (msg, body) = msg?;
+            let span = RequestSpan::new(
+                &msg.subject.0,
+                &msg.subject.1,
+                &self.metrics,
+                &self.lifecycle,
+            );
+            let _entered = span.enter();
             let mut req = Request::new(body);
             *req.method_mut() = msg.subject.0;
             *req.uri_mut() = msg.subject.1;
@@ -529,14 +659,23 @@ cfg_server! {
             *req.extensions_mut() = msg.extensions;
             let fut = self.service.call(req);
             self.in_flight.set(Some(fut));
+            self.span = Some(span);
             Ok(())
         }
 
-        fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<Result<(), ()>> {
+        fn sent_msg(&mut self) {
+            self.span = None;
+        }
+
+        fn poll_ready(
+            &mut self,
+            cx: &mut task::Context<'_>,
+        ) -> Poll<Result<(), Option<Self::PollError>>> {
             if self.in_flight.is_some() {
                 Poll::Pending
             } else {
-                Poll::Ready(Ok(()))
+                // Don't read the next request until the service can take it.
+                self.service.poll_ready(cx).map_err(Some)
             }
         }
 
@@ -549,16 +688,32 @@ cfg_server! {
 // ===== impl Client =====
 
 cfg_client! {
+    use std::collections::VecDeque;
     use std::convert::Infallible;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
 
     impl<B> Client<B> {
-        pub(crate) fn new(rx: ClientRx<B>) -> Client<B> {
+        pub(crate) fn new(rx: ClientRx<B>, metrics: MetricsSink, lifecycle: Lifecycle) -> Client<B> {
             Client {
-                callback: None,
+                in_flight: VecDeque::new(),
+                pipeline_depth: 1,
                 rx,
                 rx_closed: false,
+                metrics,
+                lifecycle,
+                keep_alive: Arc::new(AtomicBool::new(true)),
             }
         }
+
+        pub(crate) fn set_pipeline_depth(&mut self, depth: usize) {
+            debug_assert!(depth > 0);
+            self.pipeline_depth = depth;
+        }
+
+        pub(crate) fn keep_alive_handle(&self) -> Arc<AtomicBool> {
+            self.keep_alive.clone()
+        }
     }
 
     impl<B> Dispatch for Client<B>
@@ -573,7 +728,7 @@ cfg_client! {
         fn poll_msg(
             mut self: Pin<&mut Self>,
             cx: &mut task::Context<'_>,
-        ) -> Poll<Option<Result<(Self::PollItem, Self::PollBody), Infallible>>> {
+        ) -> PollMsg<Self::PollItem, Self::PollBody, Infallible> {
             let mut this = self.as_mut();
             debug_assert!(!this.rx_closed);
             match this.rx.poll_recv(cx) {
@@ -586,13 +741,19 @@ cfg_client! {
                         }
                         Poll::Pending => {
                             let (parts, body) = req.into_parts();
+                            let span = RequestSpan::new(
+                                &parts.method,
+                                &parts.uri,
+                                &this.metrics,
+                                &this.lifecycle,
+                            );
                             let head = RequestHead {
                                 version: parts.version,
                                 subject: crate::proto::RequestLine(parts.method, parts.uri),
                                 headers: parts.headers,
                                 extensions: parts.extensions,
                             };
-                            this.callback = Some(cb);
+                            this.in_flight.push_back((cb, span));
                             Poll::Ready(Some(Ok((head, body))))
                         }
                     }
@@ -610,7 +771,8 @@ cfg_client! {
         fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, IncomingBody)>) -> crate::Result<()> {
             match msg {
                 Ok((msg, body)) => {
-                    if let Some(cb) = self.callback.take() {
+                    if let Some((cb, span)) = self.in_flight.pop_front() {
+                        span.record_status(msg.subject);
                         let res = msg.into_response(body);
                         cb.send(Ok(res));
                         Ok(())
@@ -622,8 +784,13 @@ cfg_client! {
                     }
                 }
                 Err(err) => {
-                    if let Some(cb) = self.callback.take() {
+                    if let Some((cb, _span)) = self.in_flight.pop_front() {
                         cb.send(Err((err, None)));
+                        // The connection is done, so any requests pipelined
+                        // behind this one were written, but won't be answered.
+                        for (cb, _span) in self.in_flight.drain(..) {
+                            cb.send(Err((crate::Error::new_incomplete(), None)));
+                        }
                         Ok(())
                     } else if !self.rx_closed {
                         self.rx.close();
@@ -643,21 +810,28 @@ cfg_client! {
             }
         }
 
-        fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), ()>> {
-            match self.callback {
-                Some(ref mut cb) => match cb.poll_canceled(cx) {
+        fn poll_ready(
+            &mut self,
+            cx: &mut task::Context<'_>,
+        ) -> Poll<Result<(), Option<Self::PollError>>> {
+            match self.in_flight.front_mut() {
+                Some((cb, _span)) => match cb.poll_canceled(cx) {
                     Poll::Ready(()) => {
                         trace!("callback receiver has dropped");
-                        Poll::Ready(Err(()))
+                        Poll::Ready(Err(None))
                     }
                     Poll::Pending => Poll::Ready(Ok(())),
                 },
-                None => Poll::Ready(Err(())),
+                None => Poll::Ready(Err(None)),
             }
         }
 
         fn should_poll(&self) -> bool {
-            self.callback.is_none()
+            self.in_flight.len() < self.pipeline_depth
+        }
+
+        fn keep_alive(&mut self, keep_alive: bool) {
+            self.keep_alive.store(keep_alive, Ordering::Relaxed);
         }
     }
 }
@@ -680,7 +854,10 @@ mod tests {
             // the request is ready to write later...
             let (mut tx, rx) = crate::client::dispatch::channel();
             let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(compat(io));
-            let mut dispatcher = Dispatcher::new(Client::new(rx), conn);
+            let mut dispatcher = Dispatcher::new(
+                Client::new(rx, MetricsSink::default(), Lifecycle::default()),
+                conn,
+            );
 
             // First poll is needed to allow tx to send...
             assert!(Pin::new(&mut dispatcher).poll(cx).is_pending());
@@ -719,7 +896,10 @@ mod tests {
         let mut conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(compat(io));
         conn.set_write_strategy_queue();
 
-        let dispatcher = Dispatcher::new(Client::new(rx), conn);
+        let dispatcher = Dispatcher::new(
+            Client::new(rx, MetricsSink::default(), Lifecycle::default()),
+            conn,
+        );
         let _dispatcher = tokio::spawn(async move { dispatcher.await });
 
         let body = {
@@ -748,7 +928,10 @@ mod tests {
 
         let (mut tx, rx) = crate::client::dispatch::channel();
         let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(compat(io));
-        let mut dispatcher = tokio_test::task::spawn(Dispatcher::new(Client::new(rx), conn));
+        let mut dispatcher = tokio_test::task::spawn(Dispatcher::new(
+            Client::new(rx, MetricsSink::default(), Lifecycle::default()),
+            conn,
+        ));
 
         // First poll is needed to allow tx to send...
         assert!(dispatcher.poll().is_pending());
@@ -2,7 +2,8 @@ use std::fmt;
 use std::io::IoSlice;
 
 use bytes::buf::{Chain, Take};
-use bytes::Buf;
+use bytes::{Buf, Bytes};
+use http::header::{HeaderMap, HeaderName};
 
 use super::io::WriteBuf;
 
@@ -13,6 +14,7 @@ type StaticBuf = &'static [u8];
 pub(crate) struct Encoder {
     kind: Kind,
     is_last: bool,
+    trailer_fields: Option<Vec<HeaderName>>,
 }
 
 #[derive(Debug)]
@@ -45,6 +47,7 @@ enum BufKind<B> {
     Limited(Take<B>),
     Chunked(Chain<Chain<ChunkSize, B>, StaticBuf>),
     ChunkedEnd(StaticBuf),
+    Trailers(Bytes),
 }
 
 impl Encoder {
@@ -52,6 +55,7 @@ impl Encoder {
         Encoder {
             kind,
             is_last: false,
+            trailer_fields: None,
         }
     }
     pub(crate) fn chunked() -> Encoder {
@@ -77,6 +81,18 @@ impl Encoder {
         self
     }
 
+    /// Allow sending the given trailer fields at the end of a chunked body.
+    ///
+    /// Other kinds of body have nowhere to put trailers, so this does
+    /// nothing for them.
+    #[cfg(feature = "client")]
+    pub(crate) fn with_trailer_fields(mut self, fields: Vec<HeaderName>) -> Self {
+        if self.kind == Kind::Chunked && !fields.is_empty() {
+            self.trailer_fields = Some(fields);
+        }
+        self
+    }
+
     pub(crate) fn is_last(&self) -> bool {
         self.is_last
     }
@@ -101,6 +117,45 @@ impl Encoder {
         }
     }
 
+    /// Encode the end of a chunked body, followed by the allowed fields of
+    /// `trailers`.
+    ///
+    /// Returns `None` if no trailer fields are allowed, or none of them are
+    /// in `trailers`, in which case the body should just be ended.
+    pub(crate) fn encode_trailers<B>(
+        &self,
+        trailers: HeaderMap,
+        title_case_headers: bool,
+    ) -> Option<EncodedBuf<B>> {
+        let allowed = self.trailer_fields.as_ref()?;
+
+        let mut fields = HeaderMap::new();
+        for (name, value) in trailers.iter() {
+            if allowed.contains(name) {
+                fields.append(name, value.clone());
+            } else {
+                debug!("discarding undeclared trailer field {:?}", name);
+            }
+        }
+        if fields.is_empty() {
+            return None;
+        }
+
+        trace!("encoding {} trailer fields", fields.len());
+        let mut dst = Vec::new();
+        dst.extend_from_slice(b"0\r\n");
+        if title_case_headers {
+            super::role::write_headers_title_case(&fields, &mut dst);
+        } else {
+            super::role::write_headers(&fields, &mut dst);
+        }
+        dst.extend_from_slice(b"\r\n");
+
+        Some(EncodedBuf {
+            kind: BufKind::Trailers(dst.into()),
+        })
+    }
+
     pub(crate) fn encode<B>(&mut self, msg: B) -> EncodedBuf<B>
     where
         B: Buf,
@@ -192,6 +247,7 @@ where
             BufKind::Limited(ref b) => b.remaining(),
             BufKind::Chunked(ref b) => b.remaining(),
             BufKind::ChunkedEnd(ref b) => b.remaining(),
+            BufKind::Trailers(ref b) => b.remaining(),
         }
     }
 
@@ -202,6 +258,7 @@ where
             BufKind::Limited(ref b) => b.chunk(),
             BufKind::Chunked(ref b) => b.chunk(),
             BufKind::ChunkedEnd(ref b) => b.chunk(),
+            BufKind::Trailers(ref b) => b.chunk(),
         }
     }
 
@@ -212,6 +269,7 @@ where
             BufKind::Limited(ref mut b) => b.advance(cnt),
             BufKind::Chunked(ref mut b) => b.advance(cnt),
             BufKind::ChunkedEnd(ref mut b) => b.advance(cnt),
+            BufKind::Trailers(ref mut b) => b.advance(cnt),
         }
     }
 
@@ -222,6 +280,18 @@ where
             BufKind::Limited(ref b) => b.chunks_vectored(dst),
             BufKind::Chunked(ref b) => b.chunks_vectored(dst),
             BufKind::ChunkedEnd(ref b) => b.chunks_vectored(dst),
+            BufKind::Trailers(ref b) => b.chunks_vectored(dst),
+        }
+    }
+
+    #[inline]
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        match self.kind {
+            BufKind::Exact(ref mut b) => b.copy_to_bytes(len),
+            BufKind::Limited(ref mut b) => b.copy_to_bytes(len),
+            BufKind::Chunked(ref mut b) => b.copy_to_bytes(len),
+            BufKind::ChunkedEnd(ref mut b) => b.copy_to_bytes(len),
+            BufKind::Trailers(ref mut b) => b.copy_to_bytes(len),
         }
     }
 }
@@ -357,6 +427,45 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "client")]
+    #[test]
+    fn chunked_with_trailers() {
+        use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+        let fields = vec![HeaderName::from_static("grpc-status")];
+        let mut encoder = Encoder::chunked().with_trailer_fields(fields);
+        let mut dst = Vec::new();
+
+        dst.put(encoder.encode(b"foo bar".as_ref()));
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        trailers.insert("x-undeclared", HeaderValue::from_static("nope"));
+        let end = encoder
+            .encode_trailers::<&[u8]>(trailers.clone(), false)
+            .unwrap();
+        dst.put(end);
+
+        assert_eq!(dst, b"7\r\nfoo bar\r\n0\r\ngrpc-status: 0\r\n\r\n".as_ref());
+
+        let end = encoder.encode_trailers::<&[u8]>(trailers, true).unwrap();
+        let mut dst = Vec::new();
+        dst.put(end);
+        assert_eq!(dst, b"0\r\nGrpc-Status: 0\r\n\r\n".as_ref());
+
+        // without any declared fields in them, trailers aren't sent
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-undeclared", HeaderValue::from_static("nope"));
+        assert!(encoder.encode_trailers::<&[u8]>(trailers, false).is_none());
+
+        // nor on bodies that aren't chunked
+        let fields = vec![HeaderName::from_static("grpc-status")];
+        let encoder = Encoder::length(0).with_trailer_fields(fields);
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        assert!(encoder.encode_trailers::<&[u8]>(trailers, false).is_none());
+    }
+
     #[test]
     fn length() {
         let max_len = 8;
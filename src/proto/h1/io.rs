@@ -4,13 +4,14 @@ use std::fmt;
 use std::future::Future;
 use std::io::{self, IoSlice};
 use std::marker::Unpin;
-use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
 
-use crate::rt::{Read, ReadBuf, Write};
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crate::rt::{Read, Write};
+use bytes::{Buf, Bytes, BytesMut};
 
 use super::{Http1Transaction, ParseContext, ParsedMessage};
-use crate::common::buf::BufList;
+use crate::common::buf::{BufList, BufPool};
+use crate::common::metrics::MetricsSink;
 use crate::common::{task, Pin, Poll};
 
 /// The initial buffer size allocated before trying to read from IO.
@@ -22,7 +23,10 @@ pub(crate) const MINIMUM_MAX_BUFFER_SIZE: usize = INIT_BUFFER_SIZE;
 /// The default maximum read buffer size. If the buffer gets this big and
 /// a message is still not complete, a `TooLarge` error is triggered.
 // Note: if this changes, update server::conn::Http::max_buf_size docs.
+#[cfg(not(feature = "embedded"))]
 pub(crate) const DEFAULT_MAX_BUFFER_SIZE: usize = 8192 + 4096 * 100;
+#[cfg(feature = "embedded")]
+pub(crate) const DEFAULT_MAX_BUFFER_SIZE: usize = 8192 * 2;
 
 /// The maximum number of distinct `Buf`s to hold in a list before requiring
 /// a flush. Only affects when the buffer strategy is to queue buffers.
@@ -35,9 +39,14 @@ pub(crate) struct Buffered<T, B> {
     flush_pipeline: bool,
     io: T,
     read_blocked: bool,
-    read_buf: BytesMut,
+    read_buf: ReadBuf,
+    read_hint: Option<usize>,
     read_buf_strategy: ReadStrategy,
     write_buf: WriteBuf<B>,
+    metrics: MetricsSink,
+    /// How many bytes were read from the IO so far.
+    #[cfg(feature = "client")]
+    bytes_read: u64,
 }
 
 impl<T, B> fmt::Debug for Buffered<T, B>
@@ -46,7 +55,7 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Buffered")
-            .field("read_buf", &self.read_buf)
+            .field("read_buf", &*self.read_buf)
             .field("write_buf", &self.write_buf)
             .finish()
     }
@@ -68,9 +77,17 @@ where
             flush_pipeline: false,
             io,
             read_blocked: false,
-            read_buf: BytesMut::with_capacity(0),
+            read_buf: ReadBuf {
+                bytes: BytesMut::with_capacity(0),
+                pool: BufPool::default(),
+                pooled: 0,
+            },
+            read_hint: None,
             read_buf_strategy: ReadStrategy::default(),
             write_buf,
+            metrics: MetricsSink::default(),
+            #[cfg(feature = "client")]
+            bytes_read: 0,
         }
     }
 
@@ -93,11 +110,28 @@ where
         self.write_buf.max_buf_size = max;
     }
 
+    #[cfg(feature = "server")]
+    pub(crate) fn set_max_write_buf_size(&mut self, max: usize) {
+        self.write_buf.max_write_buf_size = Some(max);
+    }
+
+    pub(crate) fn set_buffer_pool(&mut self, pool: BufPool) {
+        debug_assert!(self.read_buf.is_empty());
+        self.read_buf.pool = pool;
+    }
+
     #[cfg(feature = "client")]
     pub(crate) fn set_read_buf_exact_size(&mut self, sz: usize) {
         self.read_buf_strategy = ReadStrategy::Exact(sz);
     }
 
+    /// Fill the read buffer with bytes already read from the IO.
+    #[cfg(feature = "client")]
+    pub(crate) fn set_read_buf(&mut self, buf: Bytes) {
+        debug_assert!(self.read_buf.is_empty());
+        self.read_buf.bytes.extend_from_slice(&buf);
+    }
+
     pub(crate) fn set_write_strategy_flatten(&mut self) {
         // this should always be called only at construction time,
         // so this assert is here to catch myself
@@ -112,14 +146,25 @@ where
         self.write_buf.set_strategy(WriteStrategy::Queue);
     }
 
+    pub(crate) fn set_write_coalesce_threshold(&mut self, len: usize) {
+        self.write_buf.coalesce_threshold = len;
+    }
+
     pub(crate) fn read_buf(&self) -> &[u8] {
         self.read_buf.as_ref()
     }
 
+    /// Drops whatever was read and not parsed yet.
+    #[cfg(feature = "client")]
+    pub(crate) fn discard_read_buf(&mut self) {
+        let len = self.read_buf.len();
+        self.read_buf.advance(len);
+    }
+
     #[cfg(test)]
     #[cfg(feature = "nightly")]
     pub(super) fn read_buf_mut(&mut self) -> &mut BytesMut {
-        &mut self.read_buf
+        &mut self.read_buf.bytes
     }
 
     /// Return the "allocated" available space, not the potential space
@@ -151,7 +196,8 @@ where
     }
 
     pub(crate) fn can_buffer(&self) -> bool {
-        self.flush_pipeline || self.write_buf.can_buffer()
+        (self.flush_pipeline && self.write_buf.max_write_buf_size.is_none())
+            || self.write_buf.can_buffer()
     }
 
     pub(crate) fn consume_leading_lines(&mut self) {
@@ -175,6 +221,8 @@ where
     where
         S: Http1Transaction,
     {
+        // The size of a message head is never known ahead of time.
+        self.read_hint = None;
         loop {
             match super::role::parse_headers::<S>(
                 &mut self.read_buf,
@@ -182,6 +230,7 @@ where
                     cached_headers: parse_ctx.cached_headers,
                     req_method: parse_ctx.req_method,
                     h1_parser_config: parse_ctx.h1_parser_config.clone(),
+                    h1_max_headers: parse_ctx.h1_max_headers,
                     #[cfg(feature = "server")]
                     h1_header_read_timeout: parse_ctx.h1_header_read_timeout,
                     #[cfg(feature = "server")]
@@ -190,12 +239,21 @@ where
                     h1_header_read_timeout_running: parse_ctx.h1_header_read_timeout_running,
                     #[cfg(feature = "server")]
                     timer: parse_ctx.timer.clone(),
+                    #[cfg(feature = "server")]
+                    raw_request_head: parse_ctx.raw_request_head,
+                    #[cfg(feature = "server")]
+                    violations: parse_ctx.violations,
                     preserve_header_case: parse_ctx.preserve_header_case,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: parse_ctx.preserve_header_order,
                     h09_responses: parse_ctx.h09_responses,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: parse_ctx.on_informational,
+                    #[cfg(feature = "client")]
+                    early_hints: parse_ctx.early_hints,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: parse_ctx.awaiting_continue,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: parse_ctx.malformed_reason_phrases,
                 },
             )? {
                 Some(msg) => {
@@ -209,7 +267,7 @@ where
                     return Poll::Ready(Ok(msg));
                 }
                 None => {
-                    let max = self.read_buf_strategy.max();
+                    let max = self.read_buf.max_len(self.read_buf_strategy.max());
                     if self.read_buf.len() >= max {
                         debug!("max_buf_size ({}) reached, closing", max);
                         return Poll::Ready(Err(crate::Error::new_too_large()));
@@ -241,24 +299,27 @@ where
         &mut self,
         cx: &mut task::Context<'_>,
     ) -> Poll<io::Result<usize>> {
-        self.read_blocked = false;
+        // A completion-based read may still own the spare capacity of
+        // `read_buf`, so it must not be reserved again until it completes.
+        let in_flight = std::mem::replace(&mut self.read_blocked, false);
         let next = self.read_buf_strategy.next();
-        if self.read_buf_remaining_mut() < next {
-            self.read_buf.reserve(next);
+        if !in_flight && self.read_buf_remaining_mut() < next {
+            self.read_buf.reserve(next)?;
         }
 
-        let dst = self.read_buf.chunk_mut();
-        let dst = unsafe { &mut *(dst as *mut _ as *mut [MaybeUninit<u8>]) };
-        let mut buf = ReadBuf::uninit(dst);
-        match Pin::new(&mut self.io).poll_read(cx, buf.unfilled()) {
-            Poll::Ready(Ok(_)) => {
-                let n = buf.filled().len();
+        let io = Pin::new(&mut self.io);
+        let res = if io.is_read_owned() {
+            io.poll_read_owned(cx, &mut self.read_buf)
+        } else {
+            crate::rt::poll_read_spare(io, cx, &mut self.read_buf, self.read_hint)
+        };
+        match res {
+            Poll::Ready(Ok(n)) => {
                 trace!("received {} bytes", n);
-                unsafe {
-                    // Safety: we just read that many bytes into the
-                    // uninitialized part of the buffer, so this is okay.
-                    // @tokio pls give me back `poll_read_buf` thanks
-                    self.read_buf.advance_mut(n);
+                self.metrics.bytes_read(n);
+                #[cfg(feature = "client")]
+                {
+                    self.bytes_read = self.bytes_read.wrapping_add(n as u64);
                 }
                 self.read_buf_strategy.record(n);
                 Poll::Ready(Ok(n))
@@ -271,8 +332,28 @@ where
         }
     }
 
-    pub(crate) fn into_inner(self) -> (T, Bytes) {
-        (self.io, self.read_buf.freeze())
+    #[cfg(feature = "client")]
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub(crate) fn set_metrics(&mut self, metrics: MetricsSink) {
+        self.metrics = metrics;
+    }
+
+    pub(crate) fn metrics(&self) -> &MetricsSink {
+        &self.metrics
+    }
+
+    /// Set how many bytes are expected next, passed along to the IO as a
+    /// hint on each read.
+    pub(crate) fn set_read_hint(&mut self, hint: Option<usize>) {
+        self.read_hint = hint;
+    }
+
+    pub(crate) fn into_inner(mut self) -> (T, Bytes) {
+        let read_buf = self.read_buf.take();
+        (self.io, read_buf.freeze())
     }
 
     pub(crate) fn io_mut(&mut self) -> &mut T {
@@ -289,6 +370,9 @@ where
         } else if self.write_buf.remaining() == 0 {
             Pin::new(&mut self.io).poll_flush(cx)
         } else {
+            if self.io.is_write_owned() {
+                return self.poll_flush_owned(cx);
+            }
             if let WriteStrategy::Flatten = self.write_buf.strategy {
                 return self.poll_flush_flattened(cx);
             }
@@ -304,6 +388,7 @@ where
                 // `poll_write_buf` doesn't exist in Tokio 0.3 yet...when
                 // `poll_write_buf` comes back, the manual advance will need to leave!
                 self.write_buf.advance(n);
+                self.metrics.bytes_written(n);
                 debug!("flushed {} bytes", n);
                 if self.write_buf.remaining() == 0 {
                     break;
@@ -326,6 +411,7 @@ where
     fn poll_flush_flattened(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
         loop {
             let n = ready!(Pin::new(&mut self.io).poll_write(cx, self.write_buf.headers.chunk()))?;
+            self.metrics.bytes_written(n);
             debug!("flushed {} bytes", n);
             self.write_buf.headers.advance(n);
             if self.write_buf.headers.remaining() == 0 {
@@ -342,6 +428,28 @@ where
         Pin::new(&mut self.io).poll_flush(cx)
     }
 
+    /// Specialized version of `flush` when the IO accepts owned buffers.
+    ///
+    /// Each chunk is handed over as `Bytes`, which for queued user buffers
+    /// avoids copying them at all.
+    fn poll_flush_owned(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_buf.remaining() != 0 {
+            let chunk = self.write_buf.owned_chunk();
+            let n = ready!(Pin::new(&mut self.io).poll_write_owned(cx, chunk))?;
+            self.metrics.bytes_written(n);
+            debug!("flushed {} bytes", n);
+            if n == 0 {
+                trace!(
+                    "write returned zero, but {} bytes remaining",
+                    self.write_buf.remaining()
+                );
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_buf.advance(n);
+        }
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
     #[cfg(test)]
     fn flush<'a>(&'a mut self) -> impl std::future::Future<Output = io::Result<()>> + 'a {
         futures_util::future::poll_fn(move |cx| self.poll_flush(cx))
@@ -354,6 +462,21 @@ impl<T: Unpin, B> Unpin for Buffered<T, B> {}
 // TODO: This trait is old... at least rename to PollBytes or something...
 pub(crate) trait MemRead {
     fn read_mem(&mut self, cx: &mut task::Context<'_>, len: usize) -> Poll<io::Result<Bytes>>;
+
+    /// Reads bytes up to and including the first `a` or `b`.
+    ///
+    /// Fewer bytes may be returned, such as when no delimiter is buffered
+    /// yet, but never any past the first delimiter. An empty buffer means
+    /// EOF.
+    fn read_mem_until(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        a: u8,
+        b: u8,
+    ) -> Poll<io::Result<Bytes>> {
+        let _ = (a, b);
+        self.read_mem(cx, 1)
+    }
 }
 
 impl<T, B> MemRead for Buffered<T, B>
@@ -370,6 +493,118 @@ where
             Poll::Ready(Ok(self.read_buf.split_to(::std::cmp::min(len, n)).freeze()))
         }
     }
+
+    fn read_mem_until(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        a: u8,
+        b: u8,
+    ) -> Poll<io::Result<Bytes>> {
+        if self.read_buf.is_empty() {
+            ready!(self.poll_read_from_io(cx))?;
+        }
+        let n = match super::scan::find2(a, b, &self.read_buf) {
+            Some(i) => i + 1,
+            None => self.read_buf.len(),
+        };
+        Poll::Ready(Ok(self.read_buf.split_to(n).freeze()))
+    }
+}
+
+/// The buffer read into, which may come from a `BufferPool`.
+struct ReadBuf {
+    bytes: BytesMut,
+    pool: BufPool,
+    /// Capacity of `bytes` when acquired from the pool, or 0 if it wasn't
+    pooled: usize,
+}
+
+impl ReadBuf {
+    /// Make room for at least 1, and up to `additional`, more bytes.
+    ///
+    /// Without a pool, this always makes room for `additional`.
+    fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        if !self.pool.is_set() {
+            self.bytes.reserve(additional);
+            return Ok(());
+        }
+
+        // Chunks split off to the body share the buffer, so it can only be
+        // rewound once they've all been dropped.
+        let room = self.pooled.saturating_sub(self.bytes.len());
+        if room > 0 && self.bytes.try_reclaim(cmp::min(additional, room)) {
+            return Ok(());
+        }
+        if self.bytes.capacity() > self.bytes.len() {
+            return Ok(());
+        }
+
+        let mut next = match self.pool.acquire() {
+            Some(next) => next,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "buffer pool exhausted",
+                ))
+            }
+        };
+        next.clear();
+        if next.capacity() <= self.bytes.len() {
+            self.pool.release(next);
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "pooled buffer too small",
+            ));
+        }
+        trace!("acquired pooled read buffer of {} bytes", next.capacity());
+        let pooled = next.capacity();
+        next.extend_from_slice(&self.bytes);
+        let prev = std::mem::replace(&mut self.bytes, next);
+        if self.pooled != 0 {
+            self.pool.release(prev);
+        }
+        self.pooled = pooled;
+        Ok(())
+    }
+
+    /// The most bytes a message head may take up, given the configured max.
+    ///
+    /// Since a pooled buffer is never grown, a head must fit in one.
+    fn max_len(&self, max: usize) -> usize {
+        if self.pooled != 0 {
+            cmp::min(max, self.pooled)
+        } else {
+            max
+        }
+    }
+
+    /// Take the buffer, which won't be given back to the pool.
+    fn take(&mut self) -> BytesMut {
+        self.pooled = 0;
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+impl Deref for ReadBuf {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        &self.bytes
+    }
+}
+
+impl DerefMut for ReadBuf {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        &mut self.bytes
+    }
+}
+
+impl Drop for ReadBuf {
+    fn drop(&mut self) {
+        if self.pooled != 0 {
+            self.pool.release(std::mem::take(&mut self.bytes));
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -527,9 +762,20 @@ impl<T: AsRef<[u8]>> Buf for Cursor<T> {
 
 // an internal buffer to collect writes before flushes
 pub(super) struct WriteBuf<B> {
+    /// Chunk handed to an owned write, which is always written first
+    owned: Bytes,
     /// Re-usable buffer that holds message headers
     headers: Cursor<Vec<u8>>,
+    /// Re-usable buffer that message headers are copied into for owned
+    /// writes, reclaimed once the IO drops the previous chunk
+    owned_headers: BytesMut,
     max_buf_size: usize,
+    /// Overrides `max_buf_size` for writes, even when flushing is deferred
+    /// for pipelined messages
+    max_write_buf_size: Option<usize>,
+    /// Buffers up to this size are copied into `headers` if strategy is
+    /// Queue, as long as nothing is queued ahead of them
+    coalesce_threshold: usize,
     /// Deque of user buffers if strategy is Queue
     queue: BufList<B>,
     strategy: WriteStrategy,
@@ -538,8 +784,12 @@ pub(super) struct WriteBuf<B> {
 impl<B: Buf> WriteBuf<B> {
     fn new(strategy: WriteStrategy) -> WriteBuf<B> {
         WriteBuf {
+            owned: Bytes::new(),
             headers: Cursor::new(Vec::with_capacity(INIT_BUFFER_SIZE)),
+            owned_headers: BytesMut::new(),
             max_buf_size: DEFAULT_MAX_BUFFER_SIZE,
+            max_write_buf_size: None,
+            coalesce_threshold: 0,
             queue: BufList::new(),
             strategy,
         }
@@ -554,31 +804,14 @@ where
         self.strategy = strategy;
     }
 
-    pub(super) fn buffer<BB: Buf + Into<B>>(&mut self, mut buf: BB) {
+    pub(super) fn buffer<BB: Buf + Into<B>>(&mut self, buf: BB) {
         debug_assert!(buf.has_remaining());
         match self.strategy {
-            WriteStrategy::Flatten => {
-                let head = self.headers_mut();
-
-                head.maybe_unshift(buf.remaining());
-                trace!(
-                    self.len = head.remaining(),
-                    buf.len = buf.remaining(),
-                    "buffer.flatten"
-                );
-                //perf: This is a little faster than <Vec as BufMut>>::put,
-                //but accomplishes the same result.
-                loop {
-                    let adv = {
-                        let slice = buf.chunk();
-                        if slice.is_empty() {
-                            return;
-                        }
-                        head.bytes.extend_from_slice(slice);
-                        slice.len()
-                    };
-                    buf.advance(adv);
-                }
+            WriteStrategy::Flatten => self.flatten(buf),
+            WriteStrategy::Queue
+                if buf.remaining() <= self.coalesce_threshold && !self.queue.has_remaining() =>
+            {
+                self.flatten(buf)
             }
             WriteStrategy::Queue => {
                 trace!(
@@ -591,15 +824,67 @@ where
         }
     }
 
+    fn flatten<BB: Buf>(&mut self, mut buf: BB) {
+        let head = self.headers_mut();
+
+        head.maybe_unshift(buf.remaining());
+        trace!(
+            self.len = head.remaining(),
+            buf.len = buf.remaining(),
+            "buffer.flatten"
+        );
+        //perf: This is a little faster than <Vec as BufMut>>::put,
+        //but accomplishes the same result.
+        loop {
+            let adv = {
+                let slice = buf.chunk();
+                if slice.is_empty() {
+                    return;
+                }
+                head.bytes.extend_from_slice(slice);
+                slice.len()
+            };
+            buf.advance(adv);
+        }
+    }
+
     fn can_buffer(&self) -> bool {
+        let max = self.max_write_buf_size.unwrap_or(self.max_buf_size);
         match self.strategy {
-            WriteStrategy::Flatten => self.remaining() < self.max_buf_size,
+            WriteStrategy::Flatten => self.remaining() < max,
             WriteStrategy::Queue => {
-                self.queue.bufs_cnt() < MAX_BUF_LIST_BUFFERS && self.remaining() < self.max_buf_size
+                self.queue.bufs_cnt() < MAX_BUF_LIST_BUFFERS && self.remaining() < max
             }
         }
     }
 
+    /// Returns the next chunk to write as an owned buffer.
+    ///
+    /// The chunk stays at the front of this buffer until it has been
+    /// advanced past, so repeated calls return the same bytes.
+    fn owned_chunk(&mut self) -> Bytes {
+        if self.owned.is_empty() {
+            // release the last chunk, so its buffer can be reclaimed
+            self.owned = Bytes::new();
+            let len = self.chunk().len();
+            self.owned = if self.headers.remaining() != 0 {
+                let headers = self.headers.chunk();
+                if self.owned_headers.capacity() < headers.len() {
+                    // reclaims the buffer if the IO dropped every chunk
+                    // split off of it
+                    self.owned_headers
+                        .reserve(cmp::max(headers.len(), INIT_BUFFER_SIZE));
+                }
+                self.owned_headers.extend_from_slice(headers);
+                self.headers.reset();
+                self.owned_headers.split().freeze()
+            } else {
+                self.queue.copy_to_bytes(len)
+            };
+        }
+        self.owned.clone()
+    }
+
     fn headers_mut(&mut self) -> &mut Cursor<Vec<u8>> {
         debug_assert!(!self.queue.has_remaining());
         &mut self.headers
@@ -618,11 +903,14 @@ impl<B: Buf> fmt::Debug for WriteBuf<B> {
 impl<B: Buf> Buf for WriteBuf<B> {
     #[inline]
     fn remaining(&self) -> usize {
-        self.headers.remaining() + self.queue.remaining()
+        self.owned.len() + self.headers.remaining() + self.queue.remaining()
     }
 
     #[inline]
     fn chunk(&self) -> &[u8] {
+        if !self.owned.is_empty() {
+            return &self.owned;
+        }
         let headers = self.headers.chunk();
         if !headers.is_empty() {
             headers
@@ -632,7 +920,12 @@ impl<B: Buf> Buf for WriteBuf<B> {
     }
 
     #[inline]
-    fn advance(&mut self, cnt: usize) {
+    fn advance(&mut self, mut cnt: usize) {
+        if !self.owned.is_empty() {
+            let orem = cmp::min(self.owned.len(), cnt);
+            self.owned.advance(orem);
+            cnt -= orem;
+        }
         let hrem = self.headers.remaining();
 
         match hrem.cmp(&cnt) {
@@ -648,7 +941,8 @@ impl<B: Buf> Buf for WriteBuf<B> {
 
     #[inline]
     fn chunks_vectored<'t>(&'t self, dst: &mut [IoSlice<'t>]) -> usize {
-        let n = self.headers.chunks_vectored(dst);
+        let mut n = self.owned.chunks_vectored(dst);
+        n += self.headers.chunks_vectored(&mut dst[n..]);
         self.queue.chunks_vectored(&mut dst[n..]) + n
     }
 }
@@ -726,16 +1020,24 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut None,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
                 timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
             };
             assert!(buffered
                 .parse::<ClientTransaction>(cx, parse_ctx)
@@ -745,11 +1047,12 @@ mod tests {
         .await;
 
         assert_eq!(
-            buffered.read_buf,
+            *buffered.read_buf,
             b"HTTP/1.1 200 OK\r\nServer: hyper\r\n"[..]
         );
     }
 
+    #[cfg(not(feature = "embedded"))]
     #[test]
     fn read_strategy_adaptive_increments() {
         let mut strategy = ReadStrategy::default();
@@ -867,6 +1170,21 @@ mod tests {
         buffered.buffer(Cursor::new(Vec::new()));
     }
 
+    #[test]
+    fn write_buf_max_size_applies_with_flush_pipeline() {
+        let mock = Mock::new().build();
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(compat(mock));
+        buffered.set_flush_pipeline(true);
+        assert!(buffered.can_buffer());
+
+        buffered.set_max_write_buf_size(10);
+        assert!(buffered.can_buffer());
+        buffered.headers_buf().extend(b"hello ");
+        assert!(buffered.can_buffer());
+        buffered.buffer(Cursor::new(b"world".to_vec()));
+        assert!(!buffered.can_buffer());
+    }
+
     /*
     TODO: needs tokio_test::io to allow configure write_buf calls
     #[test]
@@ -944,6 +1262,253 @@ mod tests {
         assert_eq!(write_buf.headers.pos, 0);
     }
 
+    #[tokio::test]
+    async fn read_hint_passed_to_io() {
+        struct HintIo(Vec<Option<usize>>);
+
+        impl Read for HintIo {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+                mut buf: crate::rt::ReadBufCursor<'_>,
+            ) -> Poll<io::Result<()>> {
+                let hint = buf.hint();
+                self.0.push(hint);
+                buf.put_slice(b"body");
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl Write for HintIo {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(Ok(buf.len()))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(HintIo(Vec::new()));
+        futures_util::future::poll_fn(|cx| buffered.poll_read_from_io(cx))
+            .await
+            .unwrap();
+        buffered.consume_leading_lines();
+        buffered.read_buf.clear();
+
+        buffered.set_read_hint(Some(12));
+        futures_util::future::poll_fn(|cx| buffered.read_mem(cx, 12))
+            .await
+            .unwrap();
+
+        assert_eq!(buffered.io.0, vec![None, Some(12)]);
+    }
+
+    #[tokio::test]
+    async fn owned_reads_only_when_preferred() {
+        struct OwnedIo {
+            owned: bool,
+            // The calls to `poll_read`, and to `poll_read_owned`.
+            calls: (usize, usize),
+        }
+
+        impl Read for OwnedIo {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+                mut buf: crate::rt::ReadBufCursor<'_>,
+            ) -> Poll<io::Result<()>> {
+                self.calls.0 += 1;
+                buf.put_slice(b"a");
+                Poll::Ready(Ok(()))
+            }
+
+            fn is_read_owned(&self) -> bool {
+                self.owned
+            }
+
+            fn poll_read_owned(
+                mut self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+                buf: &mut BytesMut,
+            ) -> Poll<io::Result<usize>> {
+                self.calls.1 += 1;
+                buf.extend_from_slice(b"a");
+                Poll::Ready(Ok(1))
+            }
+        }
+
+        impl Write for OwnedIo {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(Ok(buf.len()))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _: &mut task::Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        for &owned in &[false, true] {
+            let io = OwnedIo {
+                owned,
+                calls: (0, 0),
+            };
+            let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(io);
+            futures_util::future::poll_fn(|cx| buffered.poll_read_from_io(cx))
+                .await
+                .unwrap();
+            assert_eq!(&buffered.read_buf[..], b"a");
+            let expected = if owned { (0, 1) } else { (1, 0) };
+            assert_eq!(buffered.io.calls, expected);
+        }
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn buffer_pool_reads_into_pooled_buffers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        use crate::rt::BufferPool;
+
+        #[derive(Default)]
+        struct TwoBufs {
+            acquired: AtomicUsize,
+            released: Mutex<Vec<BytesMut>>,
+        }
+
+        impl BufferPool for TwoBufs {
+            fn acquire(&self) -> Option<BytesMut> {
+                if self.acquired.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Some(BytesMut::with_capacity(16))
+                } else {
+                    None
+                }
+            }
+
+            fn release(&self, buf: BytesMut) {
+                self.released.lock().unwrap().push(buf);
+            }
+        }
+
+        let pool = std::sync::Arc::new(TwoBufs::default());
+        let mock = Mock::new()
+            .read(b"0123456789abcdef")
+            .read(b"0123456789")
+            .read(b"ghij")
+            .read(b"klmnop")
+            .build();
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(compat(mock));
+        buffered.set_buffer_pool(BufPool::new(pool.clone()));
+
+        // fills the first buffer, which is still shared with `held`
+        let held = futures_util::future::poll_fn(|cx| buffered.read_mem(cx, 16))
+            .await
+            .unwrap();
+        assert_eq!(held, "0123456789abcdef");
+        assert_eq!(pool.acquired.load(Ordering::SeqCst), 1);
+
+        let chunk = futures_util::future::poll_fn(|cx| buffered.read_mem(cx, 16))
+            .await
+            .unwrap();
+        assert_eq!(chunk, "0123456789");
+        assert_eq!(pool.acquired.load(Ordering::SeqCst), 2, "full and shared");
+        assert_eq!(pool.released.lock().unwrap().len(), 1);
+        drop(chunk);
+
+        // rewinds the second buffer, since its chunk was dropped
+        let chunk = futures_util::future::poll_fn(|cx| buffered.read_mem(cx, 16))
+            .await
+            .unwrap();
+        assert_eq!(chunk, "ghij");
+        let chunk = futures_util::future::poll_fn(|cx| buffered.read_mem(cx, 16))
+            .await
+            .unwrap();
+        assert_eq!(chunk, "klmnop");
+        assert_eq!(pool.acquired.load(Ordering::SeqCst), 2, "reclaimed");
+
+        drop(buffered);
+        assert_eq!(pool.released.lock().unwrap().len(), 2);
+        drop(held);
+    }
+
+    #[test]
+    fn write_buf_owned_chunk() {
+        let mut write_buf = WriteBuf::<Bytes>::new(WriteStrategy::Queue);
+
+        write_buf.headers_mut().bytes.extend_from_slice(b"hello ");
+        let world = Bytes::from_static(b"world, it's hyper!");
+        write_buf.buffer(world.clone());
+
+        assert_eq!(write_buf.owned_chunk(), "hello ");
+        // a partial write keeps the rest of the same chunk in front
+        write_buf.advance(2);
+        assert_eq!(write_buf.owned_chunk(), "llo ");
+        assert_eq!(write_buf.remaining(), 4 + world.len());
+        write_buf.advance(4);
+
+        // queued buffers are handed over without copying
+        let chunk = write_buf.owned_chunk();
+        assert_eq!(chunk, world);
+        assert!(std::ptr::eq(chunk.as_ptr(), world.as_ptr()));
+        write_buf.advance(chunk.len());
+        assert_eq!(write_buf.remaining(), 0);
+    }
+
+    #[test]
+    fn write_buf_owned_chunk_reuses_headers_allocation() {
+        let mut write_buf = WriteBuf::<Bytes>::new(WriteStrategy::Queue);
+        let head = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n";
+
+        write_buf.headers_mut().bytes.extend_from_slice(head);
+        let first = write_buf.owned_chunk();
+        assert_eq!(first, &head[..]);
+        let start = first.as_ptr();
+        drop(first);
+        // the chunk is split off of the scratch buffer, not a copy of its own
+        let scratch = start as usize..start as usize + INIT_BUFFER_SIZE;
+        let mut next = start;
+        let mut wraps = 0;
+        // enough messages to fill the buffer several times over
+        for _ in 0..4 * INIT_BUFFER_SIZE / head.len() {
+            let chunk = write_buf.owned_chunk();
+            assert_eq!(chunk, &head[..]);
+            assert!(scratch.contains(&(chunk.as_ptr() as usize)));
+            // each message follows the last, until the buffer is reclaimed
+            if chunk.as_ptr() != next {
+                assert_eq!(chunk.as_ptr(), start);
+                wraps += 1;
+            }
+            next = chunk.as_ptr().wrapping_add(head.len());
+            write_buf.advance(chunk.len());
+            drop(chunk);
+            write_buf.headers_mut().bytes.extend_from_slice(head);
+        }
+        assert!((1..=4).contains(&wraps), "reclaimed {} times", wraps);
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn write_buf_queue_disable_auto() {
@@ -973,6 +1538,36 @@ mod tests {
         assert_eq!(buffered.write_buf.queue.bufs_cnt(), 0);
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn write_buf_queue_coalesces_small_bufs() {
+        let _ = pretty_env_logger::try_init();
+
+        let mock = Mock::new()
+            .write(b"hello world, ")
+            .write(b"it's hyper, and this is a longer one!")
+            .write(b"bye")
+            .build();
+
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(compat(mock));
+        buffered.write_buf.set_strategy(WriteStrategy::Queue);
+        buffered.set_write_coalesce_threshold(8);
+
+        buffered.headers_buf().extend(b"hello ");
+        buffered.buffer(Cursor::new(b"world, ".to_vec()));
+        assert_eq!(buffered.write_buf.queue.bufs_cnt(), 0);
+        buffered.buffer(Cursor::new(
+            b"it's hyper, and this is a longer one!".to_vec(),
+        ));
+        // small, but copying it would put it ahead of the queued one
+        buffered.buffer(Cursor::new(b"bye".to_vec()));
+        assert_eq!(buffered.write_buf.queue.bufs_cnt(), 2);
+
+        buffered.flush().await.expect("flush");
+
+        assert_eq!(buffered.write_buf.queue.bufs_cnt(), 0);
+    }
+
     // #[cfg(feature = "nightly")]
     // #[bench]
     // fn bench_write_buf_flatten_buffer_chunk(b: &mut Bencher) {
@@ -1,16 +1,28 @@
 #[cfg(feature = "server")]
-use std::{pin::Pin, time::Duration};
+use std::fmt;
+#[cfg(any(feature = "client", feature = "server"))]
+use std::pin::Pin;
+#[cfg(feature = "server")]
+use std::sync::Arc;
+#[cfg(feature = "server")]
+use std::time::Duration;
 
+#[cfg(feature = "server")]
+use bytes::Bytes;
 use bytes::BytesMut;
 use http::{HeaderMap, Method};
 use httparse::ParserConfig;
 
 use crate::body::DecodedLength;
 #[cfg(feature = "server")]
+use crate::common::date::DateHeader;
+#[cfg(feature = "server")]
 use crate::common::time::Time;
 use crate::proto::{BodyLength, MessageHead};
-#[cfg(feature = "server")]
+#[cfg(any(feature = "client", feature = "server"))]
 use crate::rt::Sleep;
+#[cfg(feature = "server")]
+use crate::server::conn::http1::{Violation, ViolationPolicy};
 
 pub(crate) use self::conn::Conn;
 pub(crate) use self::decode::Decoder;
@@ -25,6 +37,7 @@ pub(crate) mod dispatch;
 mod encode;
 mod io;
 mod role;
+mod scan;
 
 cfg_client! {
     pub(crate) type ClientTransaction = role::Client;
@@ -32,6 +45,39 @@ cfg_client! {
 
 cfg_server! {
     pub(crate) type ServerTransaction = role::Server;
+
+    type ParseErrorCallback = dyn Fn(&crate::Error) -> http::Response<Bytes> + Send + Sync;
+
+    /// A user-provided callback building the response to a request that
+    /// couldn't be parsed.
+    #[derive(Clone, Default)]
+    pub(crate) struct OnParseError(pub(crate) Option<Arc<ParseErrorCallback>>);
+
+    impl fmt::Debug for OnParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("OnParseError").finish()
+        }
+    }
+
+    /// A user-provided callback told about requests rejected for a framing
+    /// violation.
+    #[derive(Clone, Default)]
+    pub(crate) struct OnViolation(pub(crate) Option<Arc<dyn Fn(Violation) + Send + Sync>>);
+
+    impl OnViolation {
+        pub(crate) fn call(&self, violation: Violation) {
+            debug!("request rejected: {}", violation.description());
+            if let Some(ref callback) = self.0 {
+                callback(violation);
+            }
+        }
+    }
+
+    impl fmt::Debug for OnViolation {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("OnViolation").finish()
+        }
+    }
 }
 
 pub(crate) trait Http1Transaction {
@@ -43,6 +89,24 @@ pub(crate) trait Http1Transaction {
 
     fn on_error(err: &crate::Error) -> Option<MessageHead<Self::Outgoing>>;
 
+    /// The response built by the user's callback for a parse error that
+    /// would otherwise get an automatic one.
+    #[cfg(feature = "server")]
+    fn on_error_custom(
+        _err: &crate::Error,
+        _callback: &OnParseError,
+    ) -> Option<(MessageHead<Self::Outgoing>, Bytes)> {
+        None
+    }
+
+    /// The response configured for when the header read timeout fires.
+    #[cfg(feature = "server")]
+    fn on_header_timeout(
+        _response: &(http::StatusCode, HeaderMap),
+    ) -> Option<MessageHead<Self::Outgoing>> {
+        None
+    }
+
     fn is_client() -> bool {
         !Self::is_server()
     }
@@ -78,6 +142,7 @@ pub(crate) struct ParseContext<'a> {
     cached_headers: &'a mut Option<HeaderMap>,
     req_method: &'a mut Option<Method>,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
     #[cfg(feature = "server")]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(feature = "server")]
@@ -86,12 +151,25 @@ pub(crate) struct ParseContext<'a> {
     h1_header_read_timeout_running: &'a mut bool,
     #[cfg(feature = "server")]
     timer: Time,
+    /// Whether to put the raw head of requests in their extensions.
+    #[cfg(feature = "server")]
+    raw_request_head: bool,
+    /// Which framing violations requests are rejected for.
+    #[cfg(feature = "server")]
+    violations: ViolationPolicy,
     preserve_header_case: bool,
-    #[cfg(feature = "ffi")]
     preserve_header_order: bool,
     h09_responses: bool,
-    #[cfg(feature = "ffi")]
-    on_informational: &'a mut Option<crate::ffi::OnInformational>,
+    #[cfg(feature = "client")]
+    on_informational: &'a mut Option<crate::ext::OnInformational>,
+    #[cfg(feature = "client")]
+    early_hints: &'a mut Option<crate::ext::EarlyHints>,
+    #[cfg(feature = "client")]
+    awaiting_continue: &'a mut Option<Pin<Box<dyn Sleep>>>,
+    /// Whether to accept status lines without a reason phrase, or with
+    /// control characters in it. If unset, only the former are.
+    #[cfg(feature = "client")]
+    malformed_reason_phrases: Option<bool>,
 }
 
 /// Passed to Http1Transaction::encode
@@ -100,8 +178,12 @@ pub(crate) struct Encode<'a, T> {
     body: Option<BodyLength>,
     #[cfg(feature = "server")]
     keep_alive: bool,
+    #[cfg(feature = "server")]
+    date_header: &'a DateHeader,
     req_method: &'a mut Option<Method>,
     title_case_headers: bool,
+    #[cfg(feature = "client")]
+    absolute_form: bool,
 }
 
 /// Extra flags that a request "wants", like expect-continue or upgrades.
@@ -1,8 +1,7 @@
 use std::fmt::{self, Write};
 use std::mem::MaybeUninit;
-#[cfg(feature = "server")]
-use std::time::Instant;
 
+#[cfg(feature = "server")]
 use bytes::Bytes;
 use bytes::BytesMut;
 #[cfg(feature = "server")]
@@ -14,14 +13,16 @@ use crate::body::DecodedLength;
 #[cfg(feature = "server")]
 use crate::common::date;
 use crate::error::Parse;
-use crate::ext::HeaderCaseMap;
-#[cfg(feature = "ffi")]
-use crate::ext::OriginalHeaderOrder;
+#[cfg(feature = "server")]
+use crate::ext::RawRequestHead;
+use crate::ext::{HeaderCaseMap, OriginalHeaderOrder};
 use crate::headers;
 use crate::proto::h1::{
     Encode, Encoder, Http1Transaction, ParseContext, ParseResult, ParsedMessage,
 };
 use crate::proto::{BodyLength, MessageHead, RequestHead, RequestLine};
+#[cfg(feature = "server")]
+use crate::server::conn::http1::Violation;
 
 const MAX_HEADERS: usize = 100;
 const AVERAGE_HEADER_SIZE: usize = 30; // totally scientific
@@ -76,7 +77,7 @@ where
     #[cfg(feature = "server")]
     if !*ctx.h1_header_read_timeout_running {
         if let Some(h1_header_read_timeout) = ctx.h1_header_read_timeout {
-            let deadline = Instant::now() + h1_header_read_timeout;
+            let deadline = ctx.timer.now() + h1_header_read_timeout;
             *ctx.h1_header_read_timeout_running = true;
             match ctx.h1_header_read_timeout_fut {
                 Some(h1_header_read_timeout_fut) => {
@@ -122,9 +123,20 @@ impl Http1Transaction for Server {
     fn parse(buf: &mut BytesMut, ctx: ParseContext<'_>) -> ParseResult<RequestLine> {
         debug_assert!(!buf.is_empty(), "parse called with empty buf");
 
+        // RFC 9112 lets a recipient replace each bare CR with SP before
+        // processing the head.
+        if ctx.violations.allows(Violation::BareCr) {
+            let mut from = 0;
+            while let Some(idx) = find_bare_cr(buf, from) {
+                buf[idx] = b' ';
+                from = idx + 1;
+            }
+        }
+
         let mut keep_alive;
         let is_http_11;
-        let subject;
+        let method;
+        let uri_indices;
         let version;
         let len;
         let headers_len;
@@ -133,29 +145,39 @@ impl Http1Transaction for Server {
         // but we *never* read any of it until after httparse has assigned
         // values into it. By not zeroing out the stack memory, this saves
         // a good ~5% on pipeline benchmarks.
-        let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+        let mut headers_indices_array: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
             // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
             MaybeUninit::uninit().assume_init()
         };
+        let mut headers_indices_vec = Vec::new();
+        let headers_indices = header_slots(
+            ctx.h1_max_headers,
+            &mut headers_indices_array,
+            &mut headers_indices_vec,
+        );
         {
             /* SAFETY: it is safe to go from MaybeUninit array to array of MaybeUninit */
-            let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+            let mut headers_array: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
                 unsafe { MaybeUninit::uninit().assume_init() };
+            let mut headers_vec = Vec::new();
+            let headers = header_slots(ctx.h1_max_headers, &mut headers_array, &mut headers_vec);
             trace!(bytes = buf.len(), "Request.parse");
             let mut req = httparse::Request::new(&mut []);
             let bytes = buf.as_ref();
-            match req.parse_with_uninit_headers(bytes, &mut headers) {
+            match req.parse_with_uninit_headers(bytes, headers) {
                 Ok(httparse::Status::Complete(parsed_len)) => {
                     trace!("Request.parse Complete({})", parsed_len);
                     len = parsed_len;
+                    if !ctx.violations.allows(Violation::BareLf) && has_bare_lf(&bytes[..len]) {
+                        return Err(Parse::Violation(Violation::BareLf));
+                    }
                     let uri = req.path.unwrap();
                     if uri.len() > MAX_URI_LEN {
                         return Err(Parse::UriTooLong);
                     }
-                    subject = RequestLine(
-                        Method::from_bytes(req.method.unwrap().as_bytes())?,
-                        uri.parse()?,
-                    );
+                    method = Method::from_bytes(req.method.unwrap().as_bytes())?;
+                    let uri_start = uri.as_ptr() as usize - bytes.as_ptr() as usize;
+                    uri_indices = (uri_start, uri_start + uri.len());
                     version = if req.version.unwrap() == 1 {
                         keep_alive = true;
                         is_http_11 = true;
@@ -166,11 +188,14 @@ impl Http1Transaction for Server {
                         Version::HTTP_10
                     };
 
-                    record_header_indices(bytes, &req.headers, &mut headers_indices)?;
+                    record_header_indices(bytes, &req.headers, headers_indices)?;
                     headers_len = req.headers.len();
                 }
                 Ok(httparse::Status::Partial) => return Ok(None),
                 Err(err) => {
+                    if find_bare_cr(bytes, 0).is_some() {
+                        return Err(Parse::Violation(Violation::BareCr));
+                    }
                     return Err(match err {
                         // if invalid Token, try to determine if for method or path
                         httparse::Error::Token => {
@@ -189,6 +214,11 @@ impl Http1Transaction for Server {
 
         let slice = buf.split_to(len).freeze();
 
+        // The target shares the buffer with the header values, rather than
+        // being copied out of it.
+        let uri = http::Uri::from_maybe_shared(slice.slice(uri_indices.0..uri_indices.1))?;
+        let subject = RequestLine(method, uri);
+
         // According to https://tools.ietf.org/html/rfc7230#section-3.3.3
         // 1. (irrelevant to Request)
         // 2. (irrelevant to Request)
@@ -201,19 +231,19 @@ impl Http1Transaction for Server {
         let mut decoder = DecodedLength::ZERO;
         let mut expect_continue = false;
         let mut con_len = None;
+        let mut has_content_length = false;
         let mut is_te = false;
         let mut is_te_chunked = false;
         let mut wants_upgrade = subject.0 == Method::CONNECT;
 
         let mut header_case_map = if ctx.preserve_header_case {
-            Some(HeaderCaseMap::default())
+            Some(HeaderCaseMap::with_capacity(headers_len))
         } else {
             None
         };
 
-        #[cfg(feature = "ffi")]
         let mut header_order = if ctx.preserve_header_order {
-            Some(OriginalHeaderOrder::default())
+            Some(OriginalHeaderOrder::with_capacity(headers_len))
         } else {
             None
         };
@@ -247,6 +277,7 @@ impl Http1Transaction for Server {
                     }
                 }
                 header::CONTENT_LENGTH => {
+                    has_content_length = true;
                     if is_te {
                         continue;
                     }
@@ -291,10 +322,9 @@ impl Http1Transaction for Server {
             }
 
             if let Some(ref mut header_case_map) = header_case_map {
-                header_case_map.append(&name, slice.slice(header.name.0..header.name.1));
+                header_case_map.append_raw(&name, slice.slice(header.name.0..header.name.1));
             }
 
-            #[cfg(feature = "ffi")]
             if let Some(ref mut header_order) = header_order {
                 header_order.append(&name);
             }
@@ -307,17 +337,31 @@ impl Http1Transaction for Server {
             return Err(Parse::transfer_encoding_invalid());
         }
 
+        if is_te
+            && has_content_length
+            && !ctx
+                .violations
+                .allows(Violation::TransferEncodingWithContentLength)
+        {
+            return Err(Parse::Violation(
+                Violation::TransferEncodingWithContentLength,
+            ));
+        }
+
         let mut extensions = http::Extensions::default();
 
         if let Some(header_case_map) = header_case_map {
             extensions.insert(header_case_map);
         }
 
-        #[cfg(feature = "ffi")]
         if let Some(header_order) = header_order {
             extensions.insert(header_order);
         }
 
+        if ctx.raw_request_head {
+            extensions.insert(RawRequestHead::new(slice));
+        }
+
         *ctx.req_method = Some(subject.0.clone());
 
         Ok(Some(ParsedMessage {
@@ -442,7 +486,8 @@ impl Http1Transaction for Server {
             Kind::Parse(Parse::Method)
             | Kind::Parse(Parse::Header(_))
             | Kind::Parse(Parse::Uri)
-            | Kind::Parse(Parse::Version) => StatusCode::BAD_REQUEST,
+            | Kind::Parse(Parse::Version)
+            | Kind::Parse(Parse::Violation(_)) => StatusCode::BAD_REQUEST,
             Kind::Parse(Parse::TooLarge) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
             Kind::Parse(Parse::UriTooLong) => StatusCode::URI_TOO_LONG,
             _ => return None,
@@ -454,12 +499,54 @@ impl Http1Transaction for Server {
         Some(msg)
     }
 
+    fn on_error_custom(
+        err: &crate::Error,
+        callback: &super::OnParseError,
+    ) -> Option<(MessageHead<Self::Outgoing>, Bytes)> {
+        let callback = callback.0.as_ref()?;
+        Self::on_error(err)?;
+
+        let (parts, body) = callback(err).into_parts();
+        debug!("sending custom response ({}) for parse error", parts.status);
+        let mut headers = parts.headers;
+        // The length is hyper's to set, from the body returned.
+        headers.remove(header::CONTENT_LENGTH);
+        headers.remove(header::TRANSFER_ENCODING);
+        let msg = MessageHead {
+            version: Version::HTTP_11,
+            subject: parts.status,
+            headers,
+            extensions: parts.extensions,
+        };
+        Some((msg, body))
+    }
+
+    fn on_header_timeout(
+        response: &(StatusCode, HeaderMap),
+    ) -> Option<MessageHead<Self::Outgoing>> {
+        let (status, ref headers) = *response;
+        debug!("sending response ({}) for header read timeout", status);
+        let mut headers = headers.clone();
+        // The connection is closed after it, which the client should know.
+        if !headers.contains_key(header::CONNECTION) {
+            headers.insert(header::CONNECTION, HeaderValue::from_static("close"));
+        }
+        Some(MessageHead {
+            version: Version::HTTP_11,
+            subject: status,
+            headers,
+            extensions: http::Extensions::default(),
+        })
+    }
+
     fn is_server() -> bool {
         true
     }
 
     fn update_date() {
-        date::update();
+        if cfg!(not(feature = "embedded")) {
+            date::update();
+        }
     }
 }
 
@@ -889,10 +976,11 @@ impl Server {
         }
 
         // cached date is much faster than formatting every request
-        if !wrote_date {
-            dst.reserve(date::DATE_VALUE_LENGTH + 8);
-            header_name_writer.write_header_name_with_colon(dst, "date: ", header::DATE);
-            date::extend(dst);
+        if !wrote_date
+            && msg.date_header.extend(dst, |dst| {
+                header_name_writer.write_header_name_with_colon(dst, "date: ", header::DATE)
+            })
+        {
             extend(dst, b"\r\n\r\n");
         } else {
             extend(dst, b"\r\n");
@@ -931,31 +1019,64 @@ impl Http1Transaction for Client {
         // Loop to skip information status code headers (100 Continue, etc).
         loop {
             // Unsafe: see comment in Server Http1Transaction, above.
-            let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+            let mut headers_indices_array: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
                 MaybeUninit::uninit().assume_init()
             };
+            let mut headers_indices_vec = Vec::new();
+            let headers_indices = header_slots(
+                ctx.h1_max_headers,
+                &mut headers_indices_array,
+                &mut headers_indices_vec,
+            );
             let (len, status, reason, version, headers_len) = {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
-                let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+                let mut headers_array: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
                     unsafe { MaybeUninit::uninit().assume_init() };
+                let mut headers_vec = Vec::new();
+                let headers =
+                    header_slots(ctx.h1_max_headers, &mut headers_array, &mut headers_vec);
                 trace!(bytes = buf.len(), "Response.parse");
+                // httparse rejects control characters in the reason phrase,
+                // so if they're allowed, a copy without them is parsed. It
+                // has the same length, so the offsets found in it are valid
+                // in `buf` too.
+                let sanitized;
+                let bytes = match Client::sanitize_reason_phrase(&ctx, buf.as_ref()) {
+                    Some(copy) => {
+                        sanitized = copy;
+                        &sanitized[..]
+                    }
+                    None => buf.as_ref(),
+                };
                 let mut res = httparse::Response::new(&mut []);
-                let bytes = buf.as_ref();
-                match ctx.h1_parser_config.parse_response_with_uninit_headers(
-                    &mut res,
-                    bytes,
-                    &mut headers,
-                ) {
+                match ctx
+                    .h1_parser_config
+                    .parse_response_with_uninit_headers(&mut res, bytes, headers)
+                {
                     Ok(httparse::Status::Complete(len)) => {
                         trace!("Response.parse Complete({})", len);
                         let status = StatusCode::from_u16(res.code.unwrap())?;
 
+                        // The status code must be followed by a space, even
+                        // if the reason phrase is empty.
+                        if ctx.malformed_reason_phrases == Some(false)
+                            && bytes.get(Client::status_code_end(bytes)) != Some(&b' ')
+                        {
+                            debug!("rejecting status line without a reason phrase");
+                            return Err(Parse::Status);
+                        }
+
                         let reason = {
                             let reason = res.reason.unwrap();
                             // Only save the reason phrase if it isn't the canonical reason
-                            if Some(reason) != status.canonical_reason() {
-                                Some(Bytes::copy_from_slice(reason.as_bytes()))
+                            if reason.is_empty() {
+                                // A missing reason phrase may not point
+                                // into `bytes`.
+                                Some((0, 0))
+                            } else if Some(reason) != status.canonical_reason() {
+                                let start = reason.as_ptr() as usize - bytes.as_ptr() as usize;
+                                Some((start, start + reason.len()))
                             } else {
                                 None
                             }
@@ -966,7 +1087,7 @@ impl Http1Transaction for Client {
                         } else {
                             Version::HTTP_10
                         };
-                        record_header_indices(bytes, &res.headers, &mut headers_indices)?;
+                        record_header_indices(bytes, &res.headers, headers_indices)?;
                         let headers_len = res.headers.len();
                         (len, status, reason, version, headers_len)
                     }
@@ -1000,14 +1121,13 @@ impl Http1Transaction for Client {
             let mut keep_alive = version == Version::HTTP_11;
 
             let mut header_case_map = if ctx.preserve_header_case {
-                Some(HeaderCaseMap::default())
+                Some(HeaderCaseMap::with_capacity(headers_len))
             } else {
                 None
             };
 
-            #[cfg(feature = "ffi")]
             let mut header_order = if ctx.preserve_header_order {
-                Some(OriginalHeaderOrder::default())
+                Some(OriginalHeaderOrder::with_capacity(headers_len))
             } else {
                 None
             };
@@ -1031,10 +1151,9 @@ impl Http1Transaction for Client {
                 }
 
                 if let Some(ref mut header_case_map) = header_case_map {
-                    header_case_map.append(&name, slice.slice(header.name.0..header.name.1));
+                    header_case_map.append_raw(&name, slice.slice(header.name.0..header.name.1));
                 }
 
-                #[cfg(feature = "ffi")]
                 if let Some(ref mut header_order) = header_order {
                     header_order.append(&name);
                 }
@@ -1048,25 +1167,29 @@ impl Http1Transaction for Client {
                 extensions.insert(header_case_map);
             }
 
-            #[cfg(feature = "ffi")]
             if let Some(header_order) = header_order {
                 extensions.insert(header_order);
             }
 
-            if let Some(reason) = reason {
+            if let Some((start, end)) = reason {
                 // Safety: httparse ensures that only valid reason phrase bytes are present in this
-                // field.
-                let reason = crate::ext::ReasonPhrase::from_bytes_unchecked(reason);
+                // field. If malformed reason phrases are allowed, it may contain control
+                // characters, but never CR or LF.
+                let reason =
+                    crate::ext::ReasonPhrase::from_bytes_unchecked(slice.slice(start..end));
                 extensions.insert(reason);
             }
 
-            let head = MessageHead {
+            let mut head = MessageHead {
                 version,
                 subject: status,
                 headers,
                 extensions,
             };
             if let Some((decode, is_upgrade)) = Client::decoder(&head, ctx.req_method)? {
+                if let Some(early_hints) = ctx.early_hints.take() {
+                    head.extensions.insert(early_hints);
+                }
                 return Ok(Some(ParsedMessage {
                     head,
                     decode,
@@ -1078,10 +1201,18 @@ impl Http1Transaction for Client {
                 }));
             }
 
-            #[cfg(feature = "ffi")]
             if head.subject.is_informational() {
+                if head.subject == StatusCode::CONTINUE {
+                    // the held back request body can be sent now
+                    *ctx.awaiting_continue = None;
+                }
+                if head.subject.as_u16() == 103 {
+                    ctx.early_hints
+                        .get_or_insert_with(Default::default)
+                        .extend(head.headers.clone());
+                }
                 if let Some(callback) = ctx.on_informational {
-                    callback.call(head.into_response(crate::body::Incoming::empty()));
+                    callback.call(head.into_response(()));
                 }
             }
 
@@ -1102,15 +1233,35 @@ impl Http1Transaction for Client {
 
         *msg.req_method = Some(msg.head.subject.0.clone());
 
-        let body = Client::set_length(msg.head, msg.body);
+        let body = Client::set_length(msg.head, msg.body)
+            .with_trailer_fields(headers::trailer_fields(&msg.head.headers));
+
+        let uri = &msg.head.subject.1;
+        // An origin-form target gets its authority from the Host header.
+        let host = if msg.absolute_form && uri.authority().is_none() && uri.path().starts_with('/')
+        {
+            match msg.head.headers.get(header::HOST) {
+                Some(host) => Some(host),
+                None => {
+                    debug!("absolute-form request target without a Host header");
+                    return Err(crate::Error::new_user_absolute_form());
+                }
+            }
+        } else {
+            None
+        };
 
         let init_cap = 30 + msg.head.headers.len() * AVERAGE_HEADER_SIZE;
         dst.reserve(init_cap);
 
         extend(dst, msg.head.subject.0.as_str().as_bytes());
         extend(dst, b" ");
+        if let Some(host) = host {
+            extend(dst, b"http://");
+            extend(dst, host.as_bytes());
+        }
         //TODO: add API to http::Uri to encode without std::fmt
-        let _ = write!(FastWrite(dst), "{} ", msg.head.subject.1);
+        let _ = write!(FastWrite(dst), "{} ", uri);
 
         match msg.head.version {
             Version::HTTP_10 => extend(dst, b"HTTP/1.0"),
@@ -1332,6 +1483,42 @@ impl Client {
         set_content_length(headers, len)
     }
 
+    /// Where the status code ends in a status line, like "HTTP/1.1 200",
+    /// which may have more than one space after the version.
+    fn status_code_end(bytes: &[u8]) -> usize {
+        let mut i = "HTTP/1.1".len();
+        while bytes.get(i) == Some(&b' ') {
+            i += 1;
+        }
+        i + 3
+    }
+
+    /// Returns a copy of `bytes` with the control characters in the reason
+    /// phrase of the status line replaced with spaces, if they're allowed
+    /// and there are any. A CR that doesn't end the line isn't replaced, so
+    /// the line is still rejected.
+    fn sanitize_reason_phrase(ctx: &ParseContext<'_>, bytes: &[u8]) -> Option<Vec<u8>> {
+        if ctx.malformed_reason_phrases != Some(true) {
+            return None;
+        }
+        let start = Client::status_code_end(bytes);
+        let end = super::scan::find(b'\n', bytes)?;
+        let reason = bytes.get(start..end)?;
+        let reason = reason.strip_suffix(b"\r").unwrap_or(reason);
+        let is_ctl = |b: u8| (b < 0x20 && b != b'\t' && b != b'\r') || b == 0x7f;
+        if !reason.iter().any(|&b| is_ctl(b)) {
+            return None;
+        }
+
+        let mut copy = bytes.to_vec();
+        for b in &mut copy[start..start + reason.len()] {
+            if is_ctl(*b) {
+                *b = b' ';
+            }
+        }
+        Some(copy)
+    }
+
     fn obs_fold_line(all: &mut [u8], idx: &mut HeaderIndices) {
         // If the value has obs-folded text, then in-place shift the bytes out
         // of here.
@@ -1348,7 +1535,7 @@ impl Client {
         let buf = &mut all[idx.value.0..idx.value.1];
 
         // look for a newline, otherwise bail out
-        let first_nl = match buf.iter().position(|b| *b == b'\n') {
+        let first_nl = match super::scan::find(b'\n', buf) {
             Some(i) => i,
             None => return,
         };
@@ -1431,6 +1618,25 @@ struct HeaderIndices {
     value: (usize, usize),
 }
 
+/// Picks the slots a message's headers are parsed into.
+///
+/// Up to the default `MAX_HEADERS`, the slots are on the stack. A larger
+/// `max_headers` spills them onto the heap instead.
+fn header_slots<'a, T>(
+    max_headers: Option<usize>,
+    array: &'a mut [MaybeUninit<T>; MAX_HEADERS],
+    vec: &'a mut Vec<MaybeUninit<T>>,
+) -> &'a mut [MaybeUninit<T>] {
+    match max_headers {
+        Some(max) if max > MAX_HEADERS => {
+            *vec = (0..max).map(|_| MaybeUninit::uninit()).collect();
+            vec
+        }
+        Some(max) => &mut array[..max],
+        None => array,
+    }
+}
+
 fn record_header_indices(
     bytes: &[u8],
     headers: &[httparse::Header<'_>],
@@ -1463,6 +1669,37 @@ fn record_header_indices(
     Ok(())
 }
 
+/// Finds the next CR not followed by LF, starting at `from`, in the message
+/// head at the start of `bytes`.
+///
+/// A CR at the very end isn't bare yet, since its LF may still be coming.
+#[cfg(feature = "server")]
+fn find_bare_cr(bytes: &[u8], from: usize) -> Option<usize> {
+    for idx in from..bytes.len() {
+        match bytes[idx] {
+            b'\r' => match bytes.get(idx + 1) {
+                Some(b'\n') | None => (),
+                Some(_) => return Some(idx),
+            },
+            // an empty line ends the head, so the body isn't looked at
+            b'\n' => match (bytes.get(idx + 1), bytes.get(idx + 2)) {
+                (Some(b'\n'), _) | (Some(b'\r'), Some(b'\n')) => return None,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Whether a line of the message `head` ends with LF alone.
+#[cfg(feature = "server")]
+fn has_bare_lf(head: &[u8]) -> bool {
+    head.iter()
+        .enumerate()
+        .any(|(idx, &b)| b == b'\n' && (idx == 0 || head[idx - 1] != b'\r'))
+}
+
 // Write header names as title case. The header name is assumed to be ASCII.
 fn title_case(dst: &mut Vec<u8>, name: &[u8]) {
     dst.reserve(name.len());
@@ -1478,7 +1715,7 @@ fn title_case(dst: &mut Vec<u8>, name: &[u8]) {
     }
 }
 
-fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
+pub(super) fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         title_case(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1487,7 +1724,38 @@ fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     }
 }
 
-fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
+/// Encodes an informational response sent ahead of a final one.
+#[cfg(feature = "server")]
+pub(super) fn encode_informational(
+    res: http::Response<()>,
+    title_case_headers: bool,
+    dst: &mut Vec<u8>,
+) {
+    debug_assert!(res.status().is_informational());
+    trace!("Server::encode_informational status={:?}", res.status());
+    extend(dst, b"HTTP/1.1 ");
+    extend(dst, res.status().as_str().as_bytes());
+    extend(dst, b" ");
+    if let Some(reason) = res.extensions().get::<crate::ext::ReasonPhrase>() {
+        extend(dst, reason.as_bytes());
+    } else {
+        let reason = match res.status().as_u16() {
+            // Not known to `http` yet.
+            103 => "Early Hints",
+            _ => res.status().canonical_reason().unwrap_or("<none>"),
+        };
+        extend(dst, reason.as_bytes());
+    }
+    extend(dst, b"\r\n");
+    if title_case_headers {
+        write_headers_title_case(res.headers(), dst);
+    } else {
+        write_headers(res.headers(), dst);
+    }
+    extend(dst, b"\r\n");
+}
+
+pub(super) fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         extend(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1556,6 +1824,8 @@ fn extend(dst: &mut Vec<u8>, data: &[u8]) {
 mod tests {
     use bytes::BytesMut;
 
+    use crate::common::date::DateHeader;
+
     use crate::common::time::Time;
 
     use super::*;
@@ -1571,16 +1841,24 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut method,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
                 timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
             },
         )
         .unwrap()
@@ -1594,6 +1872,88 @@ mod tests {
         assert_eq!(method, Some(crate::Method::GET));
     }
 
+    #[test]
+    fn test_parse_request_target_shares_buffer() {
+        let mut raw = BytesMut::from("GET /echo?query=1 HTTP/1.1\r\nHost: hyper.rs\r\n\r\n");
+        let buf = raw.as_ptr() as usize..raw.as_ptr() as usize + raw.len();
+        let msg = Server::parse(
+            &mut raw,
+            ParseContext {
+                cached_headers: &mut None,
+                req_method: &mut None,
+                h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                h1_header_read_timeout: None,
+                h1_header_read_timeout_fut: &mut None,
+                h1_header_read_timeout_running: &mut false,
+                timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
+                preserve_header_case: false,
+                preserve_header_order: false,
+                h09_responses: false,
+                #[cfg(feature = "client")]
+                on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
+            },
+        )
+        .unwrap()
+        .unwrap();
+        let uri = &msg.head.subject.1;
+        assert_eq!(uri, "/echo?query=1");
+        assert!(buf.contains(&(uri.path().as_ptr() as usize)));
+    }
+
+    #[test]
+    fn test_parse_response_max_headers() {
+        fn parse(raw: &str, max_headers: Option<usize>) -> ParseResult<StatusCode> {
+            let mut raw = BytesMut::from(raw);
+            let ctx = ParseContext {
+                cached_headers: &mut None,
+                req_method: &mut Some(crate::Method::GET),
+                h1_parser_config: Default::default(),
+                h1_max_headers: max_headers,
+                h1_header_read_timeout: None,
+                h1_header_read_timeout_fut: &mut None,
+                h1_header_read_timeout_running: &mut false,
+                timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
+                preserve_header_case: false,
+                preserve_header_order: false,
+                h09_responses: false,
+                #[cfg(feature = "client")]
+                on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
+            };
+            Client::parse(&mut raw, ctx)
+        }
+
+        let mut raw = String::from("HTTP/1.1 200 OK\r\n");
+        for i in 0..150 {
+            raw.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        raw.push_str("\r\n");
+
+        parse(&raw, None).expect_err("default max headers");
+        parse(&raw, Some(149)).expect_err("too many headers");
+        let msg = parse(&raw, Some(150)).unwrap().unwrap();
+        assert_eq!(msg.head.headers.len(), 150);
+        assert_eq!(msg.head.headers["x-header-149"], "149");
+
+        parse("HTTP/1.1 200 OK\r\na: 1\r\nb: 2\r\n\r\n", Some(1)).expect_err("below default");
+    }
+
     #[test]
     fn test_parse_response() {
         let _ = pretty_env_logger::try_init();
@@ -1602,16 +1962,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1628,16 +1996,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         Server::parse(&mut raw, ctx).unwrap_err();
     }
@@ -1652,16 +2028,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: true,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw, H09_RESPONSE);
@@ -1678,21 +2062,88 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
         assert_eq!(raw, H09_RESPONSE);
     }
 
+    #[test]
+    fn test_parse_response_malformed_reason_phrases() {
+        let _ = pretty_env_logger::try_init();
+        fn parse(
+            raw: &str,
+            malformed_reason_phrases: Option<bool>,
+        ) -> Option<Option<crate::ext::ReasonPhrase>> {
+            let mut raw = BytesMut::from(raw);
+            let ctx = ParseContext {
+                cached_headers: &mut None,
+                req_method: &mut Some(crate::Method::GET),
+                h1_parser_config: Default::default(),
+                h1_max_headers: None,
+                h1_header_read_timeout: None,
+                h1_header_read_timeout_fut: &mut None,
+                h1_header_read_timeout_running: &mut false,
+                timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
+                preserve_header_case: false,
+                preserve_header_order: false,
+                h09_responses: false,
+                #[cfg(feature = "client")]
+                on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases,
+            };
+            let msg = Client::parse(&mut raw, ctx).ok()?.unwrap();
+            assert_eq!(msg.head.subject, crate::StatusCode::OK);
+            assert_eq!(msg.head.headers["content-length"], "0");
+            Some(msg.head.extensions.get().cloned())
+        }
+
+        const MISSING: &str = "HTTP/1.1 200\r\nContent-Length: 0\r\n\r\n";
+        const CONTROL: &str = "HTTP/1.1 200 O\x01K\x7f\r\nContent-Length: 0\r\n\r\n";
+        const BARE_CR: &str = "HTTP/1.1 200 O\rK\r\nContent-Length: 0\r\n\r\n";
+
+        // by default, only a missing reason phrase is accepted
+        assert_eq!(parse(MISSING, None).unwrap().unwrap().as_bytes(), b"");
+        assert!(parse(CONTROL, None).is_none());
+
+        assert_eq!(parse(MISSING, Some(true)).unwrap().unwrap().as_bytes(), b"");
+        assert_eq!(
+            parse(CONTROL, Some(true)).unwrap().unwrap().as_bytes(),
+            b"O\x01K\x7f"
+        );
+        assert!(parse(BARE_CR, Some(true)).is_none());
+        let canonical = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert!(parse(canonical, Some(true)).unwrap().is_none());
+
+        assert!(parse(MISSING, Some(false)).is_none());
+        assert!(parse(CONTROL, Some(false)).is_none());
+        assert!(parse("HTTP/1.1 200 \r\nContent-Length: 0\r\n\r\n", Some(false)).is_some());
+    }
+
     const RESPONSE_WITH_WHITESPACE_BETWEEN_HEADER_NAME_AND_COLON: &'static str =
         "HTTP/1.1 200 OK\r\nAccess-Control-Allow-Credentials : true\r\n\r\n";
 
@@ -1708,16 +2159,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config,
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1735,16 +2194,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: false,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
     }
@@ -1757,16 +2224,24 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
             timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
             preserve_header_case: true,
-            #[cfg(feature = "ffi")]
             preserve_header_order: false,
             h09_responses: false,
-            #[cfg(feature = "ffi")]
+            #[cfg(feature = "client")]
             on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
         };
         let parsed_message = Server::parse(&mut raw, ctx).unwrap().unwrap();
         let orig_headers = parsed_message
@@ -1790,6 +2265,153 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_preserve_header_order_in_request() {
+        let mut raw = BytesMut::from(
+            "GET / HTTP/1.1\r\nX-Bread: baguette\r\nHost: hyper.rs\r\nX-Bread: brioche\r\n\r\n",
+        );
+        let ctx = ParseContext {
+            cached_headers: &mut None,
+            req_method: &mut None,
+            h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            h1_header_read_timeout: None,
+            h1_header_read_timeout_fut: &mut None,
+            h1_header_read_timeout_running: &mut false,
+            timer: Time::Empty,
+            raw_request_head: false,
+            violations: Default::default(),
+            preserve_header_case: false,
+            preserve_header_order: true,
+            h09_responses: false,
+            #[cfg(feature = "client")]
+            on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
+        };
+        let parsed_message = Server::parse(&mut raw, ctx).unwrap().unwrap();
+        let order = parsed_message
+            .head
+            .extensions
+            .get::<OriginalHeaderOrder>()
+            .unwrap();
+        assert_eq!(
+            order.get_in_order().cloned().collect::<Vec<_>>(),
+            vec![
+                (HeaderName::from_static("x-bread"), 0),
+                (HeaderName::from_static("host"), 0),
+                (HeaderName::from_static("x-bread"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_request_head() {
+        let head = "GET /a%20b HTTP/1.1\r\nHost: hyper.rs\r\nX-Odd:  spaced \r\n\r\n";
+        let mut raw = BytesMut::from(format!("{}GET / HTTP/1.1\r\n", head).as_str());
+        let ctx = ParseContext {
+            cached_headers: &mut None,
+            req_method: &mut None,
+            h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            h1_header_read_timeout: None,
+            h1_header_read_timeout_fut: &mut None,
+            h1_header_read_timeout_running: &mut false,
+            timer: Time::Empty,
+            raw_request_head: true,
+            violations: Default::default(),
+            preserve_header_case: false,
+            preserve_header_order: false,
+            h09_responses: false,
+            #[cfg(feature = "client")]
+            on_informational: &mut None,
+            #[cfg(feature = "client")]
+            early_hints: &mut None,
+            #[cfg(feature = "client")]
+            awaiting_continue: &mut None,
+            #[cfg(feature = "client")]
+            malformed_reason_phrases: None,
+        };
+        let parsed_message = Server::parse(&mut raw, ctx).unwrap().unwrap();
+        let raw_head = parsed_message
+            .head
+            .extensions
+            .get::<RawRequestHead>()
+            .unwrap();
+        assert_eq!(raw_head.as_bytes(), head.as_bytes());
+        assert_eq!(raw, "GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_parse_request_violations() {
+        use crate::server::conn::http1::{Strictness, ViolationPolicy};
+
+        fn parse(s: &str, strictness: Strictness) -> ParseResult<RequestLine> {
+            let mut bytes = BytesMut::from(s);
+            Server::parse(
+                &mut bytes,
+                ParseContext {
+                    cached_headers: &mut None,
+                    req_method: &mut None,
+                    h1_parser_config: Default::default(),
+                    h1_max_headers: None,
+                    h1_header_read_timeout: None,
+                    h1_header_read_timeout_fut: &mut None,
+                    h1_header_read_timeout_running: &mut false,
+                    timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: ViolationPolicy::new(strictness),
+                    preserve_header_case: false,
+                    preserve_header_order: false,
+                    h09_responses: false,
+                    #[cfg(feature = "client")]
+                    on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
+                },
+            )
+        }
+
+        fn violation(s: &str, strictness: Strictness) -> Violation {
+            let err = parse(s, strictness).expect_err(s);
+            match err {
+                Parse::Violation(violation) => violation,
+                other => panic!("{:?}: expected a violation, got {:?}", s, other),
+            }
+        }
+
+        let te_cl = "POST / HTTP/1.1\r\n\
+                     transfer-encoding: chunked\r\n\
+                     content-length: 10\r\n\
+                     \r\n";
+        let head = parse(te_cl, Strictness::Default).unwrap().unwrap();
+        assert_eq!(head.decode, DecodedLength::CHUNKED);
+        assert_eq!(
+            violation(te_cl, Strictness::Strict),
+            Violation::TransferEncodingWithContentLength
+        );
+
+        let bare_cr = "GET / HTTP/1.1\r\nx-odd: a\rb\r\n\r\n";
+        assert_eq!(violation(bare_cr, Strictness::Default), Violation::BareCr);
+        let head = parse(bare_cr, Strictness::Lenient).unwrap().unwrap();
+        assert_eq!(head.head.headers["x-odd"], "a b");
+
+        let bare_lf = "GET / HTTP/1.1\nhost: hyper.rs\n\n";
+        parse(bare_lf, Strictness::Default).unwrap().unwrap();
+        assert_eq!(violation(bare_lf, Strictness::Strict), Violation::BareLf);
+
+        let clean = "GET / HTTP/1.1\r\nhost: hyper.rs\r\n\r\n";
+        parse(clean, Strictness::Strict).unwrap().unwrap();
+    }
+
     #[test]
     fn test_decoder_request() {
         fn parse(s: &str) -> ParsedMessage<RequestLine> {
@@ -1800,16 +2422,24 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .expect("parse ok")
@@ -1824,16 +2454,24 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .expect_err(comment)
@@ -2057,16 +2695,24 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 }
             )
             .expect("parse ok")
@@ -2081,16 +2727,24 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(m),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .expect("parse ok")
@@ -2105,16 +2759,24 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .expect_err("parse should err")
@@ -2416,8 +3078,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2426,6 +3091,73 @@ mod tests {
         assert_eq!(vec, b"GET / HTTP/1.1\r\nContent-Length: 10\r\nContent-Type: application/json\r\n*-*: o_o\r\n\r\n".to_vec());
     }
 
+    #[test]
+    fn test_client_request_encode_absolute_form() {
+        use http::header::HeaderValue;
+
+        fn encode(head: &mut MessageHead<RequestLine>) -> crate::Result<Vec<u8>> {
+            let mut vec = Vec::new();
+            Client::encode(
+                Encode {
+                    head,
+                    body: None,
+                    keep_alive: true,
+                    date_header: &DateHeader::default(),
+                    req_method: &mut None,
+                    title_case_headers: false,
+                    #[cfg(feature = "client")]
+                    absolute_form: true,
+                },
+                &mut vec,
+            )?;
+            Ok(vec)
+        }
+
+        let mut head = MessageHead::<RequestLine>::default();
+        head.subject.1 = "/path?q=1".parse().unwrap();
+        head.headers
+            .insert("host", HeaderValue::from_static("example.com"));
+        assert_eq!(
+            encode(&mut head).unwrap(),
+            b"GET http://example.com/path?q=1 HTTP/1.1\r\nhost: example.com\r\n\r\n".to_vec()
+        );
+
+        // an absolute uri is written as is
+        let mut head = MessageHead::<RequestLine>::default();
+        head.subject.1 = "https://example.com/path".parse().unwrap();
+        head.headers
+            .insert("host", HeaderValue::from_static("other.example"));
+        assert_eq!(
+            encode(&mut head).unwrap(),
+            b"GET https://example.com/path HTTP/1.1\r\nhost: other.example\r\n\r\n".to_vec()
+        );
+
+        // CONNECT and OPTIONS * keep their targets
+        let mut head = MessageHead::<RequestLine>::default();
+        head.subject.0 = Method::CONNECT;
+        head.subject.1 = "example.com:443".parse().unwrap();
+        head.headers
+            .insert("host", HeaderValue::from_static("example.com:443"));
+        assert_eq!(
+            encode(&mut head).unwrap(),
+            b"CONNECT example.com:443 HTTP/1.1\r\nhost: example.com:443\r\n\r\n".to_vec()
+        );
+
+        let mut head = MessageHead::<RequestLine>::default();
+        head.subject.0 = Method::OPTIONS;
+        head.subject.1 = "*".parse().unwrap();
+        head.headers
+            .insert("host", HeaderValue::from_static("example.com"));
+        assert_eq!(
+            encode(&mut head).unwrap(),
+            b"OPTIONS * HTTP/1.1\r\nhost: example.com\r\n\r\n".to_vec()
+        );
+
+        let mut head = MessageHead::<RequestLine>::default();
+        head.subject.1 = "/path".parse().unwrap();
+        encode(&mut head).expect_err("no host");
+    }
+
     #[test]
     fn test_client_request_encode_orig_case() {
         use crate::proto::BodyLength;
@@ -2438,7 +3170,7 @@ mod tests {
             .insert("content-type", HeaderValue::from_static("application/json"));
 
         let mut orig_headers = HeaderCaseMap::default();
-        orig_headers.insert(CONTENT_LENGTH, "CONTENT-LENGTH".into());
+        orig_headers.insert_raw(CONTENT_LENGTH, "CONTENT-LENGTH".into());
         head.extensions.insert(orig_headers);
 
         let mut vec = Vec::new();
@@ -2447,8 +3179,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: false,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2472,7 +3207,7 @@ mod tests {
             .insert("content-type", HeaderValue::from_static("application/json"));
 
         let mut orig_headers = HeaderCaseMap::default();
-        orig_headers.insert(CONTENT_LENGTH, "CONTENT-LENGTH".into());
+        orig_headers.insert_raw(CONTENT_LENGTH, "CONTENT-LENGTH".into());
         head.extensions.insert(orig_headers);
 
         let mut vec = Vec::new();
@@ -2481,8 +3216,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2505,8 +3243,11 @@ mod tests {
                 head: &mut head,
                 body: None,
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut Some(Method::CONNECT),
                 title_case_headers: false,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2534,8 +3275,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2547,6 +3291,7 @@ mod tests {
         assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
     }
 
+    #[cfg(not(feature = "embedded"))]
     #[test]
     fn test_server_response_encode_orig_case() {
         use crate::proto::BodyLength;
@@ -2559,7 +3304,7 @@ mod tests {
             .insert("content-type", HeaderValue::from_static("application/json"));
 
         let mut orig_headers = HeaderCaseMap::default();
-        orig_headers.insert(CONTENT_LENGTH, "CONTENT-LENGTH".into());
+        orig_headers.insert_raw(CONTENT_LENGTH, "CONTENT-LENGTH".into());
         head.extensions.insert(orig_headers);
 
         let mut vec = Vec::new();
@@ -2568,8 +3313,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: false,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2581,6 +3329,7 @@ mod tests {
         assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
     }
 
+    #[cfg(not(feature = "embedded"))]
     #[test]
     fn test_server_response_encode_orig_and_title_case() {
         use crate::proto::BodyLength;
@@ -2593,7 +3342,7 @@ mod tests {
             .insert("content-type", HeaderValue::from_static("application/json"));
 
         let mut orig_headers = HeaderCaseMap::default();
-        orig_headers.insert(CONTENT_LENGTH, "CONTENT-LENGTH".into());
+        orig_headers.insert_raw(CONTENT_LENGTH, "CONTENT-LENGTH".into());
         head.extensions.insert(orig_headers);
 
         let mut vec = Vec::new();
@@ -2602,8 +3351,11 @@ mod tests {
                 head: &mut head,
                 body: Some(BodyLength::Known(10)),
                 keep_alive: true,
+                date_header: &DateHeader::default(),
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "client")]
+                absolute_form: false,
             },
             &mut vec,
         )
@@ -2615,6 +3367,75 @@ mod tests {
         assert_eq!(&vec[..expected_response.len()], &expected_response[..]);
     }
 
+    #[cfg(feature = "embedded")]
+    #[test]
+    fn test_server_response_encode_without_date() {
+        use crate::proto::BodyLength;
+
+        let mut head = MessageHead::default();
+        let mut vec = Vec::new();
+        Server::encode(
+            Encode {
+                head: &mut head,
+                body: Some(BodyLength::Known(0)),
+                keep_alive: true,
+                date_header: &DateHeader::default(),
+                req_method: &mut None,
+                title_case_headers: false,
+                #[cfg(feature = "client")]
+                absolute_form: false,
+            },
+            &mut vec,
+        )
+        .unwrap();
+
+        assert_eq!(vec, b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_server_response_encode_reuses_dst() {
+        use std::time::{Duration, SystemTime};
+
+        use crate::proto::BodyLength;
+
+        struct FixedClock;
+
+        impl crate::rt::Clock for FixedClock {
+            fn now(&self) -> Option<SystemTime> {
+                Some(SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777))
+            }
+        }
+
+        let date_header = DateHeader::new(FixedClock);
+        let mut vec = Vec::with_capacity(256);
+        let ptr = vec.as_ptr();
+        // the connection clears its headers buffer between responses
+        for _ in 0..2 {
+            vec.clear();
+            let mut head = MessageHead::default();
+            Server::encode(
+                Encode {
+                    head: &mut head,
+                    body: Some(BodyLength::Known(0)),
+                    keep_alive: true,
+                    date_header: &date_header,
+                    req_method: &mut None,
+                    title_case_headers: false,
+                    #[cfg(feature = "client")]
+                    absolute_form: false,
+                },
+                &mut vec,
+            )
+            .unwrap();
+
+            assert_eq!(
+                vec,
+                &b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\ndate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n"[..]
+            );
+            assert_eq!(vec.as_ptr(), ptr, "headers buffer reallocated");
+        }
+    }
+
     #[test]
     fn parse_header_htabs() {
         let mut bytes = BytesMut::from("HTTP/1.1 200 OK\r\nserver: hello\tworld\r\n\r\n");
@@ -2624,16 +3445,24 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut Some(Method::GET),
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
                 timer: Time::Empty,
+                raw_request_head: false,
+                violations: Default::default(),
                 preserve_header_case: false,
-                #[cfg(feature = "ffi")]
                 preserve_header_order: false,
                 h09_responses: false,
-                #[cfg(feature = "ffi")]
+                #[cfg(feature = "client")]
                 on_informational: &mut None,
+                #[cfg(feature = "client")]
+                early_hints: &mut None,
+                #[cfg(feature = "client")]
+                awaiting_continue: &mut None,
+                #[cfg(feature = "client")]
+                malformed_reason_phrases: None,
             },
         )
         .expect("parse ok")
@@ -2648,7 +3477,7 @@ mod tests {
         let name = http::header::HeaderName::from_static("x-empty");
         headers.insert(&name, "".parse().expect("parse empty"));
         let mut orig_cases = HeaderCaseMap::default();
-        orig_cases.insert(name, Bytes::from_static(b"X-EmptY"));
+        orig_cases.insert_raw(name, Bytes::from_static(b"X-EmptY"));
 
         let mut dst = Vec::new();
         super::write_headers_original_case(&headers, &orig_cases, &mut dst, false);
@@ -2667,8 +3496,8 @@ mod tests {
         headers.append(&name, "b".parse().unwrap());
 
         let mut orig_cases = HeaderCaseMap::default();
-        orig_cases.insert(name.clone(), Bytes::from_static(b"X-Empty"));
-        orig_cases.append(name, Bytes::from_static(b"X-EMPTY"));
+        orig_cases.insert_raw(name.clone(), Bytes::from_static(b"X-Empty"));
+        orig_cases.append_raw(name, Bytes::from_static(b"X-EMPTY"));
 
         let mut dst = Vec::new();
         super::write_headers_original_case(&headers, &orig_cases, &mut dst, false);
@@ -2712,16 +3541,24 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .unwrap()
@@ -2756,16 +3593,24 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
                     timer: Time::Empty,
+                    raw_request_head: false,
+                    violations: Default::default(),
                     preserve_header_case: false,
-                    #[cfg(feature = "ffi")]
                     preserve_header_order: false,
                     h09_responses: false,
-                    #[cfg(feature = "ffi")]
+                    #[cfg(feature = "client")]
                     on_informational: &mut None,
+                    #[cfg(feature = "client")]
+                    early_hints: &mut None,
+                    #[cfg(feature = "client")]
+                    awaiting_continue: &mut None,
+                    #[cfg(feature = "client")]
+                    malformed_reason_phrases: None,
                 },
             )
             .unwrap()
@@ -2806,8 +3651,11 @@ mod tests {
                     head: &mut head,
                     body: Some(BodyLength::Known(10)),
                     keep_alive: true,
+                    date_header: &DateHeader::default(),
                     req_method: &mut Some(Method::GET),
                     title_case_headers: false,
+                    #[cfg(feature = "client")]
+                    absolute_form: false,
                 },
                 &mut vec,
             )
@@ -2834,8 +3682,11 @@ mod tests {
                     head: &mut head,
                     body: Some(BodyLength::Known(10)),
                     keep_alive: true,
+                    date_header: &DateHeader::default(),
                     req_method: &mut Some(Method::GET),
                     title_case_headers: false,
+                    #[cfg(feature = "client")]
+                    absolute_form: false,
                 },
                 &mut vec,
             )
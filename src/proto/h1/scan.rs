@@ -0,0 +1,54 @@
+//! Byte scanning for the parts of HTTP/1 that httparse doesn't cover.
+//!
+//! With the `simd` feature, these use `memchr`, which checks many bytes at
+//! a time with vector instructions when the CPU supports them.
+
+/// Returns the index of the first `needle` in `haystack`.
+#[cfg(feature = "client")]
+pub(super) fn find(needle: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    {
+        memchr::memchr(needle, haystack)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        haystack.iter().position(|&b| b == needle)
+    }
+}
+
+/// Returns the index of the first `a` or `b` in `haystack`.
+pub(super) fn find2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    {
+        memchr::memchr2(a, b, haystack)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        haystack.iter().position(|&c| c == a || c == b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find2_first_of_either() {
+        assert_eq!(find2(b'\r', b'\n', b""), None);
+        assert_eq!(find2(b'\r', b'\n', b"no line end"), None);
+        assert_eq!(find2(b'\r', b'\n', b"ext=1\r\n"), Some(5));
+        assert_eq!(find2(b'\r', b'\n', b"ext=1\n\r"), Some(5));
+
+        // past the widths vector scanning works in
+        let mut long = vec![b'a'; 1000];
+        long[777] = b'\n';
+        assert_eq!(find2(b'\r', b'\n', &long), Some(777));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn find_first() {
+        assert_eq!(find(b'\n', b"obs\r\n fold\r\n"), Some(4));
+        assert_eq!(find(b'\n', b"no newline"), None);
+    }
+}
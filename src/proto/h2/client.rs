@@ -1,26 +1,33 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::{convert::Infallible, marker::PhantomData, time::Duration};
 
-use crate::rt::{Read, Write};
+use crate::rt::{CloseReason, Read, TaskDescriptor, Write};
 use bytes::Bytes;
 use futures_channel::mpsc::{Receiver, Sender};
 use futures_channel::{mpsc, oneshot};
-use futures_util::future::{Either, FusedFuture, FutureExt as _};
+use futures_util::future::{FusedFuture, FutureExt as _};
 use futures_util::stream::{StreamExt as _, StreamFuture};
 use h2::client::{Builder, Connection, SendRequest};
 use h2::SendStream;
 use http::{Method, StatusCode};
 use pin_project_lite::pin_project;
 
-use super::ping::{Ponger, Recorder};
+use super::ping::{Pinger, Ponger, Recorder};
+use super::streams::{OpenStream, OpenStreams, Reservation};
 use super::{ping, H2Upgraded, PipeToSendStream, SendBuf};
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::client::conn::http2::{KeepAliveTimeout, PeerSettings, PushPromises};
 use crate::client::dispatch::{Callback, SendWhen};
-use crate::common::io::Compat;
+use crate::common::io::{Compat, Metered, WriteTimeout};
+use crate::common::lifecycle::{EventSink, Lifecycle, OpenConnection};
+use crate::common::metrics::{Elapsed, MetricsSink};
 use crate::common::time::Time;
 use crate::common::{task, Future, Pin, Poll};
-use crate::ext::Protocol;
+use crate::ext::{Priority, Protocol, StreamWindow, PRIORITY};
 use crate::headers;
 use crate::proto::h2::UpgradedSendStream;
+use crate::proto::span::{ConnSpan, RequestSpan};
 use crate::proto::Dispatched;
 use crate::rt::bounds::ExecutorClient;
 use crate::upgrade::Upgraded;
@@ -29,6 +36,9 @@ use h2::client::ResponseFuture;
 
 type ClientRx<B> = crate::client::dispatch::Receiver<Request<B>, Response<IncomingBody>>;
 
+/// The IO given to `h2`.
+type ClientIo<T> = Compat<WriteTimeout<Metered<T>>>;
+
 ///// An mpsc channel is used to help notify the `Connection` task when *all*
 ///// other handles to it have been dropped, so that it can shutdown.
 type ConnDropRef = mpsc::Sender<Infallible>;
@@ -37,6 +47,10 @@ type ConnDropRef = mpsc::Sender<Infallible>;
 ///// the "dispatch" task will be notified and can shutdown sooner.
 type ConnEof = oneshot::Receiver<Infallible>;
 
+///// Another oneshot channel is notified only once the `Connection` task is
+///// done, for a graceful shutdown to wait for the streams to drain.
+type ConnDone = oneshot::Receiver<Infallible>;
+
 // Our defaults are chosen for the "majority" case, which usually are not
 // resource constrained, and so the spec default of 64kb can be too limiting
 // for performance.
@@ -45,6 +59,10 @@ const DEFAULT_STREAM_WINDOW: u32 = 1024 * 1024 * 2; // 2mb
 const DEFAULT_MAX_FRAME_SIZE: u32 = 1024 * 16; // 16kb
 const DEFAULT_MAX_SEND_BUF_SIZE: usize = 1024 * 1024; // 1mb
 
+// Servers may not push without a limit advertised, so when push is enabled,
+// advertise the smallest limit the spec recommends.
+const DEFAULT_MAX_PUSHED_STREAMS: u32 = 100;
+
 #[derive(Clone, Debug)]
 pub(crate) struct Config {
     pub(crate) adaptive_window: bool,
@@ -56,6 +74,21 @@ pub(crate) struct Config {
     pub(crate) keep_alive_while_idle: bool,
     pub(crate) max_concurrent_reset_streams: Option<usize>,
     pub(crate) max_send_buffer_size: usize,
+    pub(crate) write_timeout: Option<Duration>,
+    pub(crate) enable_push: bool,
+    pub(crate) on_keep_alive_timeout: OnKeepAliveTimeout,
+}
+
+/// A user-provided callback for when a keep-alive ping times out.
+#[derive(Clone, Default)]
+pub(crate) struct OnKeepAliveTimeout(pub(crate) Option<Arc<OnKeepAliveTimeoutFn>>);
+
+type OnKeepAliveTimeoutFn = dyn Fn(&KeepAliveTimeout) + Send + Sync;
+
+impl fmt::Debug for OnKeepAliveTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnKeepAliveTimeout").finish()
+    }
 }
 
 impl Default for Config {
@@ -70,6 +103,9 @@ impl Default for Config {
             keep_alive_while_idle: false,
             max_concurrent_reset_streams: None,
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
+            write_timeout: None,
+            enable_push: false,
+            on_keep_alive_timeout: OnKeepAliveTimeout::default(),
         }
     }
 }
@@ -81,7 +117,10 @@ fn new_builder(config: &Config) -> Builder {
         .initial_connection_window_size(config.initial_conn_window_size)
         .max_frame_size(config.max_frame_size)
         .max_send_buffer_size(config.max_send_buffer_size)
-        .enable_push(false);
+        .enable_push(config.enable_push);
+    if config.enable_push {
+        builder.max_concurrent_streams(DEFAULT_MAX_PUSHED_STREAMS);
+    }
     if let Some(max) = config.max_concurrent_reset_streams {
         builder.max_concurrent_reset_streams(max);
     }
@@ -98,6 +137,21 @@ fn new_ping_config(config: &Config) -> ping::Config {
         keep_alive_interval: config.keep_alive_interval,
         keep_alive_timeout: config.keep_alive_timeout,
         keep_alive_while_idle: config.keep_alive_while_idle,
+        on_keep_alive_timeout: match config.on_keep_alive_timeout.0 {
+            Some(ref callback) => {
+                let callback = callback.clone();
+                let interval = config.keep_alive_interval.unwrap_or_default();
+                let timeout = config.keep_alive_timeout;
+                ping::OnTimeout::new(move |rtts| {
+                    callback(&KeepAliveTimeout {
+                        recent_rtts: rtts.to_vec(),
+                        interval,
+                        timeout,
+                    })
+                })
+            }
+            None => ping::OnTimeout::default(),
+        },
     }
 }
 
@@ -107,18 +161,34 @@ pub(crate) async fn handshake<T, B, E>(
     config: &Config,
     mut exec: E,
     timer: Time,
+    metrics: MetricsSink,
+    events: EventSink,
 ) -> crate::Result<ClientTask<B, E, T>>
 where
     T: Read + Write + Unpin + 'static,
     B: Body + 'static,
-    B::Data: Send + 'static,
     E: ExecutorClient<B, T> + Unpin,
     B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
 {
-    let (h2_tx, mut conn) = new_builder(config)
+    let connection = events.opened();
+    let opened = metrics.connection_opened();
+    let handshake = metrics.handshake_started();
+    let peer_settings = Arc::new(Mutex::new(PeerSettings::default()));
+    let open_streams = Arc::new(OpenStreams::default());
+    let io = Metered::new(io, metrics.clone());
+    let io = WriteTimeout::new(io, timer.clone(), config.write_timeout);
+    let (h2_tx, mut conn) = match new_builder(config)
         .handshake::<_, SendBuf<B::Data>>(crate::common::io::compat(io))
         .await
-        .map_err(crate::Error::new_h2)?;
+    {
+        Ok(pair) => pair,
+        Err(e) => {
+            let err = crate::Error::new_h2(e);
+            connection.lifecycle().closed(CloseReason::Error(&err));
+            return Err(err);
+        }
+    };
+    metrics.handshake_completed(handshake);
 
     // An mpsc channel is used entirely to detect when the
     // 'Client' has been dropped. This is to get around a bug
@@ -126,35 +196,57 @@ where
     // parked Connection.
     let (conn_drop_ref, rx) = mpsc::channel(1);
     let (cancel_tx, conn_eof) = oneshot::channel();
+    let (done_tx, conn_done) = oneshot::channel();
 
     let conn_drop_rx = rx.into_future();
 
+    // Always set up, since users can send pings of their own.
     let ping_config = new_ping_config(&config);
-
-    let (conn, ping) = if ping_config.is_enabled() {
-        let pp = conn.ping_pong().expect("conn.ping_pong");
-        let (recorder, ponger) = ping::channel(pp, ping_config, timer);
-
-        let conn: Conn<_, B> = Conn::new(ponger, conn);
-        (Either::Left(conn), recorder)
-    } else {
-        (Either::Right(conn), ping::disabled())
-    };
+    let pp = conn.ping_pong().expect("conn.ping_pong");
+    let (ping, ponger) = ping::channel(pp, ping_config, timer);
+    let pinger = ponger.pinger();
+    let conn: Conn<_, B> = Conn::new(ponger, conn, peer_settings.clone(), open_streams.clone());
+    let conn_id = crate::rt::next_connection_id();
+    let span = ConnSpan::new(conn_id, "client", "HTTP/2");
+    let lifecycle = connection.lifecycle().clone();
     let conn: ConnMapErr<T, B> = ConnMapErr {
         conn,
         is_terminated: false,
+        span: span.clone(),
+        _opened: opened,
+        connection,
+        open_streams: open_streams.clone(),
     };
 
-    exec.execute_h2_future(H2ClientFuture::Task {
-        task: ConnTask::new(conn, conn_drop_rx, cancel_tx),
-    });
+    exec.execute_h2_future(
+        H2ClientFuture::Task {
+            task: ConnTask::new(conn, conn_drop_rx, cancel_tx, done_tx),
+        },
+        &TaskDescriptor::new(conn_id, "h2 client connection"),
+    )?;
 
     Ok(ClientTask {
+        conn_id,
+        span,
+        metrics,
+        lifecycle,
         ping,
+        pinger,
+        peer_settings,
+        open_streams,
+        extended_connect: false,
+        enable_push: config.enable_push,
+        initial_stream_window: if config.adaptive_window {
+            None
+        } else {
+            Some(config.initial_stream_window_size)
+        },
+        draining: false,
         conn_drop_ref,
         conn_eof,
+        conn_done,
         executor: exec,
-        h2_tx,
+        h2_tx: Some(h2_tx),
         req_rx,
         fut_ctx: None,
         marker: PhantomData,
@@ -169,7 +261,11 @@ pin_project! {
         #[pin]
         ponger: Ponger,
         #[pin]
-        conn: Connection<Compat<T>, SendBuf<<B as Body>::Data>>,
+        conn: Connection<ClientIo<T>, SendBuf<<B as Body>::Data>>,
+        // Updated with the server's stream limit.
+        settings: Arc<Mutex<PeerSettings>>,
+        // Woken when that limit changes.
+        open_streams: Arc<OpenStreams>,
     }
 }
 
@@ -178,8 +274,18 @@ where
     B: Body,
     T: Read + Write + Unpin,
 {
-    fn new(ponger: Ponger, conn: Connection<Compat<T>, SendBuf<<B as Body>::Data>>) -> Self {
-        Conn { ponger, conn }
+    fn new(
+        ponger: Ponger,
+        conn: Connection<ClientIo<T>, SendBuf<<B as Body>::Data>>,
+        settings: Arc<Mutex<PeerSettings>>,
+        open_streams: Arc<OpenStreams>,
+    ) -> Self {
+        Conn {
+            ponger,
+            conn,
+            settings,
+            open_streams,
+        }
     }
 }
 
@@ -204,7 +310,18 @@ where
             Poll::Pending => {}
         }
 
-        Pin::new(&mut this.conn).poll(cx)
+        let polled = Pin::new(&mut this.conn).poll(cx);
+        // `h2` has no limit until the server sets one.
+        let max = match this.conn.max_concurrent_send_streams() {
+            usize::MAX => None,
+            max => Some(max as u32),
+        };
+        let mut settings = this.settings.lock().unwrap();
+        if settings.max_concurrent_streams != max {
+            settings.max_concurrent_streams = max;
+            this.open_streams.notify();
+        }
+        polled
     }
 }
 
@@ -217,9 +334,13 @@ pin_project! {
         T: Unpin,
     {
         #[pin]
-        conn: Either<Conn<T, B>, Connection<Compat<T>, SendBuf<<B as Body>::Data>>>,
+        conn: Conn<T, B>,
         #[pin]
         is_terminated: bool,
+        span: ConnSpan,
+        _opened: Elapsed,
+        connection: OpenConnection,
+        open_streams: Arc<OpenStreams>,
     }
 }
 
@@ -232,17 +353,27 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
         let mut this = self.project();
+        let _entered = this.span.enter();
 
         if *this.is_terminated {
             return Poll::Pending;
         }
-        let polled = this.conn.poll(cx);
-        if polled.is_ready() {
-            *this.is_terminated = true;
+        let polled = ready!(this.conn.poll(cx));
+        *this.is_terminated = true;
+        this.open_streams.clear();
+        let lifecycle = this.connection.lifecycle();
+        match polled {
+            Ok(()) => {
+                lifecycle.closed(CloseReason::Done);
+                Poll::Ready(Ok(()))
+            }
+            Err(e) => {
+                let err = crate::Error::new_h2(e);
+                debug!(error = %err, "connection error");
+                lifecycle.closed(CloseReason::Error(&err));
+                Poll::Ready(Err(()))
+            }
         }
-        polled.map_err(|_e| {
-            debug!(error = %_e, "connection error");
-        })
     }
 }
 
@@ -270,6 +401,8 @@ pin_project! {
         cancel_tx: Option<oneshot::Sender<Infallible>>,
         #[pin]
         conn: ConnMapErr<T, B>,
+        // Dropped along with the task.
+        _done_tx: oneshot::Sender<Infallible>,
     }
 }
 
@@ -282,11 +415,13 @@ where
         conn: ConnMapErr<T, B>,
         drop_rx: StreamFuture<Receiver<Infallible>>,
         cancel_tx: oneshot::Sender<Infallible>,
+        done_tx: oneshot::Sender<Infallible>,
     ) -> Self {
         Self {
             drop_rx,
             cancel_tx: Some(cancel_tx),
             conn,
+            _done_tx: done_tx,
         }
     }
 }
@@ -376,10 +511,13 @@ where
 {
     is_connect: bool,
     eos: bool,
+    withhold: u32,
     fut: ResponseFuture,
     body_tx: SendStream<SendBuf<B::Data>>,
     body: B,
     cb: Callback<Request<B>, Response<IncomingBody>>,
+    span: RequestSpan,
+    stream: Arc<OpenStream>,
 }
 
 impl<B: Body> Unpin for FutCtx<B> {}
@@ -389,11 +527,26 @@ where
     B: Body,
     E: Unpin,
 {
+    conn_id: u64,
+    span: ConnSpan,
+    metrics: MetricsSink,
+    lifecycle: Lifecycle,
     ping: ping::Recorder,
+    pinger: Pinger,
+    peer_settings: Arc<Mutex<PeerSettings>>,
+    open_streams: Arc<OpenStreams>,
+    // Whether the server enabled extended CONNECT, kept once draining.
+    extended_connect: bool,
+    enable_push: bool,
+    // Unless the window is adaptive.
+    initial_stream_window: Option<u32>,
+    draining: bool,
     conn_drop_ref: ConnDropRef,
     conn_eof: ConnEof,
+    conn_done: ConnDone,
     executor: E,
-    h2_tx: SendRequest<SendBuf<B::Data>>,
+    // Taken once a graceful shutdown has sent all the requests it will.
+    h2_tx: Option<SendRequest<SendBuf<B::Data>>>,
     req_rx: ClientRx<B>,
     fut_ctx: Option<FutCtx<B>>,
     marker: PhantomData<T>,
@@ -407,7 +560,30 @@ where
     T: Read + Write + Unpin,
 {
     pub(crate) fn is_extended_connect_protocol_enabled(&self) -> bool {
-        self.h2_tx.is_extended_connect_protocol_enabled()
+        match self.h2_tx {
+            Some(ref h2_tx) => h2_tx.is_extended_connect_protocol_enabled(),
+            None => self.extended_connect,
+        }
+    }
+
+    pub(crate) fn pinger(&self) -> Pinger {
+        self.pinger.clone()
+    }
+
+    pub(crate) fn peer_settings(&self) -> Arc<Mutex<PeerSettings>> {
+        self.peer_settings.clone()
+    }
+
+    pub(crate) fn open_streams(&self) -> Arc<OpenStreams> {
+        self.open_streams.clone()
+    }
+
+    pub(crate) fn graceful_shutdown(&mut self) {
+        trace!("graceful shutdown, no more requests");
+        self.draining = true;
+        self.req_rx.close();
+        self.open_streams.close();
+        self.lifecycle.closing();
     }
 }
 
@@ -422,6 +598,8 @@ pin_project! {
         conn_drop_ref: Option<Sender<Infallible>>,
         #[pin]
         ping: Option<Recorder>,
+        // Keeps the stream counted as open until its body is sent.
+        stream: Arc<OpenStream>,
     }
 }
 
@@ -456,13 +634,27 @@ where
 impl<B, E, T> ClientTask<B, E, T>
 where
     B: Body + 'static + Unpin,
-    B::Data: Send,
     E: ExecutorClient<B, T> + Unpin,
     B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
     T: Read + Write + Unpin,
 {
-    fn poll_pipe(&mut self, f: FutCtx<B>, cx: &mut task::Context<'_>) {
+    fn poll_drained(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<Dispatched>> {
+        match ready!(Pin::new(&mut self.conn_done).poll(cx)) {
+            Ok(never) => match never {},
+            Err(_conn_is_done) => {
+                trace!("connection task is done, graceful shutdown complete");
+                Poll::Ready(Ok(Dispatched::Shutdown))
+            }
+        }
+    }
+
+    fn poll_pipe(&mut self, mut f: FutCtx<B>, cx: &mut task::Context<'_>) -> crate::Result<()> {
         let ping = self.ping.clone();
+        let push_promises = if self.enable_push && !f.is_connect {
+            Some(f.fut.push_promises())
+        } else {
+            None
+        };
 
         let send_stream = if !f.is_connect {
             if !f.eos {
@@ -483,10 +675,13 @@ where
                             pipe,
                             conn_drop_ref: Some(conn_drop_ref),
                             ping: Some(ping),
+                            stream: f.stream.clone(),
                         };
                         // Clear send task
-                        self.executor
-                            .execute_h2_future(H2ClientFuture::Pipe { pipe: pipe });
+                        self.executor.execute_h2_future(
+                            H2ClientFuture::Pipe { pipe: pipe },
+                            &TaskDescriptor::new(self.conn_id, "h2 client request body"),
+                        )?;
                     }
                 }
             }
@@ -496,16 +691,23 @@ where
             Some(f.body_tx)
         };
 
-        self.executor.execute_h2_future(H2ClientFuture::Send {
-            send_when: SendWhen {
-                when: ResponseFutMap {
-                    fut: f.fut,
-                    ping: Some(ping),
-                    send_stream: Some(send_stream),
+        self.executor.execute_h2_future(
+            H2ClientFuture::Send {
+                send_when: SendWhen {
+                    when: ResponseFutMap {
+                        fut: f.fut,
+                        ping: Some(ping),
+                        send_stream: Some(send_stream),
+                        push_promises,
+                        withhold: f.withhold,
+                        span: f.span,
+                        stream: Some(f.stream),
+                    },
+                    call_back: Some(f.cb),
                 },
-                call_back: Some(f.cb),
             },
-        });
+            &TaskDescriptor::new(self.conn_id, "h2 client response"),
+        )
     }
 }
 
@@ -521,6 +723,11 @@ pin_project! {
         ping: Option<Recorder>,
         #[pin]
         send_stream: Option<Option<SendStream<SendBuf<<B as Body>::Data>>>>,
+        push_promises: Option<h2::client::PushPromises>,
+        withhold: u32,
+        span: RequestSpan,
+        // Handed to the response body, or the upgraded IO.
+        stream: Option<Arc<OpenStream>>,
     }
 }
 
@@ -532,16 +739,20 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
         let mut this = self.project();
+        let _entered = this.span.enter();
 
         let result = ready!(this.fut.poll(cx));
 
         let ping = this.ping.take().expect("Future polled twice");
         let send_stream = this.send_stream.take().expect("Future polled twice");
+        // Released with the response head, unless kept by a CONNECT tunnel.
+        let open = this.stream.take().expect("Future polled twice");
 
         match result {
             Ok(res) => {
                 // record that we got the response headers
                 ping.record_non_data();
+                this.span.record_status(res.status());
 
                 let content_length = headers::content_length_parse_all(res.headers());
                 if let (Some(mut send_stream), StatusCode::OK) = (send_stream, res.status()) {
@@ -563,6 +774,7 @@ where
                         send_stream: unsafe { UpgradedSendStream::new(send_stream) },
                         recv_stream,
                         buf: Bytes::new(),
+                        _open_stream: Some(open),
                     };
                     let upgraded = Upgraded::new(io, Bytes::new());
 
@@ -571,10 +783,20 @@ where
 
                     Poll::Ready(Ok(res))
                 } else {
-                    let res = res.map(|stream| {
+                    let push_promises = this
+                        .push_promises
+                        .take()
+                        .map(|inner| PushPromises::new(inner, ping.clone()));
+                    let withhold = *this.withhold;
+                    let mut res = res.map(|stream| {
                         let ping = ping.for_stream(&stream);
-                        IncomingBody::h2(stream, content_length.into(), ping)
+                        let mut body = IncomingBody::h2(stream, content_length.into(), ping);
+                        body.h2_withhold_capacity(withhold);
+                        body
                     });
+                    if let Some(push_promises) = push_promises {
+                        res.extensions_mut().insert(push_promises);
+                    }
                     Poll::Ready(Ok(res))
                 }
             }
@@ -588,24 +810,43 @@ where
     }
 }
 
+/// Wraps the body of a pushed response, the same way `ResponseFutMap` does
+/// for the response to a request.
+pub(crate) fn pushed_response(
+    res: Response<h2::RecvStream>,
+    ping: Recorder,
+) -> Response<IncomingBody> {
+    ping.record_non_data();
+    let content_length = headers::content_length_parse_all(res.headers());
+    res.map(|stream| {
+        let ping = ping.for_stream(&stream);
+        IncomingBody::h2(stream, content_length.into(), ping)
+    })
+}
+
 impl<B, E, T> Future for ClientTask<B, E, T>
 where
     B: Body + 'static + Unpin,
-    B::Data: Send,
     B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
-    E: ExecutorClient<B, T> + 'static + Send + Sync + Unpin,
+    E: ExecutorClient<B, T> + 'static + Unpin,
     T: Read + Write + Unpin,
 {
     type Output = crate::Result<Dispatched>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let _entered = self.span.enter();
         loop {
-            match ready!(self.h2_tx.poll_ready(cx)) {
+            let h2_tx = match self.h2_tx {
+                Some(ref mut h2_tx) => h2_tx,
+                None => return self.poll_drained(cx),
+            };
+            match ready!(h2_tx.poll_ready(cx)) {
                 Ok(()) => (),
                 Err(err) => {
                     self.ping.ensure_not_timed_out()?;
                     return if err.reason() == Some(::h2::Reason::NO_ERROR) {
                         trace!("connection gracefully shutdown");
+                        self.lifecycle.closing();
                         Poll::Ready(Ok(Dispatched::Shutdown))
                     } else {
                         Poll::Ready(Err(crate::Error::new_h2(err)))
@@ -617,7 +858,7 @@ where
                 // If we were waiting on pending open
                 // continue where we left off.
                 Some(f) => {
-                    self.poll_pipe(f, cx);
+                    self.poll_pipe(f, cx)?;
                     continue;
                 }
                 None => (),
@@ -631,8 +872,10 @@ where
                         continue;
                     }
                     let (head, body) = req.into_parts();
+                    let span =
+                        RequestSpan::new(&head.method, &head.uri, &self.metrics, &self.lifecycle);
                     let mut req = ::http::Request::from_parts(head, ());
-                    super::strip_connection_headers(req.headers_mut(), true);
+                    headers::strip_connection_headers(req.headers_mut(), true);
                     if let Some(len) = body.size_hint().exact() {
                         if len != 0 || headers::method_has_defined_payload_semantics(req.method()) {
                             headers::set_content_length_if_missing(req.headers_mut(), len);
@@ -655,11 +898,44 @@ where
                         }
                     }
 
+                    if let Some(priority) = req.extensions_mut().remove::<Priority>() {
+                        if !req.headers().contains_key(PRIORITY) {
+                            req.headers_mut()
+                                .insert(PRIORITY, priority.to_header_value());
+                        }
+                    }
+
+                    let withhold = match (
+                        req.extensions_mut().remove::<StreamWindow>(),
+                        self.initial_stream_window,
+                    ) {
+                        (Some(window), Some(initial)) if window.size() > initial => {
+                            // `h2` can't release capacity ahead of received
+                            // data, so there's no WINDOW_UPDATE to grow it.
+                            debug!(
+                                "stream window {} is larger than the initial window {}",
+                                window.size(),
+                                initial
+                            );
+                            0
+                        }
+                        (Some(window), Some(initial)) => initial - window.size(),
+                        _ => 0,
+                    };
+
+                    // `:protocol` is only allowed on extended CONNECT requests.
                     if let Some(protocol) = req.extensions_mut().remove::<Protocol>() {
-                        req.extensions_mut().insert(protocol.into_inner());
+                        if is_connect {
+                            req.extensions_mut().insert(protocol.into_inner());
+                        }
                     }
 
-                    let (fut, body_tx) = match self.h2_tx.send_request(req, !is_connect && eos) {
+                    // Held until the stream is counted as open, so the slot
+                    // is never free twice.
+                    let reservation = req.extensions_mut().remove::<Reservation>();
+
+                    let h2_tx = self.h2_tx.as_mut().expect("not drained");
+                    let (fut, body_tx) = match h2_tx.send_request(req, !is_connect && eos) {
                         Ok(ok) => ok,
                         Err(err) => {
                             debug!("client send request error: {}", err);
@@ -667,20 +943,26 @@ where
                             continue;
                         }
                     };
+                    let stream = Arc::new(self.open_streams.opened());
+                    drop(reservation);
 
                     let f = FutCtx {
                         is_connect,
                         eos,
+                        withhold,
                         fut,
                         body_tx,
                         body,
                         cb,
+                        span,
+                        stream,
                     };
 
                     // Check poll_ready() again.
                     // If the call to send_request() resulted in the new stream being pending open
                     // we have to wait for the open to complete before accepting new requests.
-                    match self.h2_tx.poll_ready(cx) {
+                    let h2_tx = self.h2_tx.as_mut().expect("not drained");
+                    match h2_tx.poll_ready(cx) {
                         Poll::Pending => {
                             // Save Context
                             self.fut_ctx = Some(f);
@@ -692,10 +974,21 @@ where
                             continue;
                         }
                     }
-                    self.poll_pipe(f, cx);
+                    self.poll_pipe(f, cx)?;
                     continue;
                 }
 
+                Poll::Ready(None) if self.draining => {
+                    // With no more references, `h2` sends a GOAWAY, and
+                    // closes the connection once its streams are done.
+                    trace!("graceful shutdown, draining streams");
+                    if let Some(h2_tx) = self.h2_tx.take() {
+                        self.extended_connect = h2_tx.is_extended_connect_protocol_enabled();
+                    }
+                    self.conn_drop_ref.disconnect();
+                    return self.poll_drained(cx);
+                }
+
                 Poll::Ready(None) => {
                     trace!("client::dispatch::Sender dropped");
                     return Poll::Ready(Ok(Dispatched::Shutdown));
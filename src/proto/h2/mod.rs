@@ -1,8 +1,6 @@
 use crate::rt::{Read, ReadBufCursor, Write};
 use bytes::{Buf, Bytes};
 use h2::{Reason, RecvStream, SendStream};
-use http::header::{HeaderName, CONNECTION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE};
-use http::HeaderMap;
 use pin_project_lite::pin_project;
 use std::error::Error as StdError;
 use std::io::{Cursor, IoSlice};
@@ -18,6 +16,8 @@ pub(crate) mod ping;
 cfg_client! {
     pub(crate) mod client;
     pub(crate) use self::client::ClientTask;
+    mod streams;
+    pub(crate) use self::streams::{OpenStream, OpenStreams, Reservation};
 }
 
 cfg_server! {
@@ -28,59 +28,6 @@ cfg_server! {
 /// Default initial stream window size defined in HTTP2 spec.
 pub(crate) const SPEC_WINDOW_SIZE: u32 = 65_535;
 
-// List of connection headers from:
-// https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection
-//
-// TE headers are allowed in HTTP/2 requests as long as the value is "trailers", so they're
-// tested separately.
-const CONNECTION_HEADERS: [HeaderName; 5] = [
-    HeaderName::from_static("keep-alive"),
-    HeaderName::from_static("proxy-connection"),
-    TRAILER,
-    TRANSFER_ENCODING,
-    UPGRADE,
-];
-
-fn strip_connection_headers(headers: &mut HeaderMap, is_request: bool) {
-    for header in &CONNECTION_HEADERS {
-        if headers.remove(header).is_some() {
-            warn!("Connection header illegal in HTTP/2: {}", header.as_str());
-        }
-    }
-
-    if is_request {
-        if headers
-            .get(TE)
-            .map(|te_header| te_header != "trailers")
-            .unwrap_or(false)
-        {
-            warn!("TE headers not set to \"trailers\" are illegal in HTTP/2 requests");
-            headers.remove(TE);
-        }
-    } else if headers.remove(TE).is_some() {
-        warn!("TE headers illegal in HTTP/2 responses");
-    }
-
-    if let Some(header) = headers.remove(CONNECTION) {
-        warn!(
-            "Connection header illegal in HTTP/2: {}",
-            CONNECTION.as_str()
-        );
-        let header_contents = header.to_str().unwrap();
-
-        // A `Connection` header may have a comma-separated list of names of other headers that
-        // are meant for only this specific connection.
-        //
-        // Iterate these names and remove them as headers. Connection-specific headers are
-        // forbidden in HTTP2, as that information has been moved into frame types of the h2
-        // protocol.
-        for name in header_contents.split(',') {
-            let name = name.trim();
-            headers.remove(name);
-        }
-    }
-}
-
 // body adapters used by both Client and Server
 
 pin_project! {
@@ -268,6 +215,9 @@ where
     send_stream: UpgradedSendStream<B>,
     recv_stream: RecvStream,
     buf: Bytes,
+    // Counts the stream as open on a client connection.
+    #[cfg(feature = "client")]
+    _open_stream: Option<std::sync::Arc<OpenStream>>,
 }
 
 impl<B> Read for H2Upgraded<B>
@@ -7,6 +7,10 @@
 ///
 /// Both cases are optional.
 ///
+/// Clients can also send pings of their own, to measure the round-trip
+/// time, which share the BDP and keep-alive pings, since h2 allows only one
+/// to be in flight at a time.
+///
 /// # BDP Algorithm
 ///
 /// 1. When receiving a DATA frame, if a BDP ping isn't outstanding:
@@ -18,13 +22,17 @@
 ///   3b. Merge RTT with a running average.
 ///   3c. Calculate bdp as bytes/rtt.
 ///   3d. If bdp is over 2/3 max, set new max to bdp and update windows.
+use std::collections::VecDeque;
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
+#[cfg(feature = "client")]
+use std::sync::Weak;
 use std::sync::{Arc, Mutex};
 use std::task::{self, Poll};
 use std::time::{Duration, Instant};
 
+use futures_channel::oneshot;
 use h2::{Ping, PingPong};
 
 use crate::common::time::Time;
@@ -32,16 +40,18 @@ use crate::rt::Sleep;
 
 type WindowSize = u32;
 
+/// How many round-trip times keep-alive keeps, for `OnTimeout`.
+const RTT_HISTORY: usize = 8;
+
 pub(super) fn disabled() -> Recorder {
     Recorder { shared: None }
 }
 
+/// Creates the recorder and ponger for a connection.
+///
+/// If neither BDP nor keep-alive is enabled, the recorder is disabled, and
+/// the ponger only handles pings sent with a `Pinger`.
 pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Recorder, Ponger) {
-    debug_assert!(
-        config.is_enabled(),
-        "ping channel requires bdp or keep-alive config",
-    );
-
     let bdp = config.bdp_initial_window.map(|wnd| Bdp {
         bdp: wnd,
         max_bandwidth: 0.0,
@@ -63,6 +73,8 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Re
         sleep: __timer.sleep(interval),
         state: KeepAliveState::Init,
         timer: __timer,
+        rtts: VecDeque::with_capacity(RTT_HISTORY),
+        on_timeout: config.on_keep_alive_timeout.clone(),
     });
 
     let last_read_at = keep_alive.as_ref().map(|_| Instant::now());
@@ -74,11 +86,16 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Re
         ping_pong,
         ping_sent_at: None,
         next_bdp_at,
+        user_pings: Vec::new(),
     }));
 
     (
         Recorder {
-            shared: Some(shared.clone()),
+            shared: if config.is_enabled() {
+                Some(shared.clone())
+            } else {
+                None
+            },
         },
         Ponger {
             bdp,
@@ -98,8 +115,16 @@ pub(super) struct Config {
     pub(super) keep_alive_timeout: Duration,
     /// If true, sends pings even when there are no active streams.
     pub(super) keep_alive_while_idle: bool,
+    pub(super) on_keep_alive_timeout: OnTimeout,
 }
 
+/// A user-provided callback for when a keep-alive ping times out, called
+/// with the recent round-trip times, oldest first.
+#[derive(Clone, Default)]
+pub(super) struct OnTimeout(Option<Arc<OnTimeoutFn>>);
+
+type OnTimeoutFn = dyn Fn(&[Duration]) + Send + Sync;
+
 #[derive(Clone)]
 pub(crate) struct Recorder {
     shared: Option<Arc<Mutex<Shared>>>,
@@ -111,9 +136,21 @@ pub(super) struct Ponger {
     shared: Arc<Mutex<Shared>>,
 }
 
+/// Sends pings for the user, to measure the round-trip time.
+///
+/// It doesn't keep the connection's ping state alive, so it doesn't count
+/// toward the connection being busy for keep-alive.
+#[cfg(feature = "client")]
+#[derive(Clone)]
+pub(crate) struct Pinger {
+    shared: Weak<Mutex<Shared>>,
+}
+
 struct Shared {
     ping_pong: PingPong,
     ping_sent_at: Option<Instant>,
+    /// Notified with the round-trip time when the in-flight ping is acked.
+    user_pings: Vec<oneshot::Sender<Duration>>,
 
     // bdp
     /// If `Some`, bdp is enabled, and this tracks how many bytes have been
@@ -157,6 +194,9 @@ struct KeepAlive {
     state: KeepAliveState,
     sleep: Pin<Box<dyn Sleep>>,
     timer: Time,
+    /// The round-trip times of the most recent pings.
+    rtts: VecDeque<Duration>,
+    on_timeout: OnTimeout,
 }
 
 enum KeepAliveState {
@@ -254,9 +294,42 @@ impl Recorder {
     }
 }
 
+// ===== impl Pinger =====
+
+#[cfg(feature = "client")]
+impl Pinger {
+    pub(crate) fn ping(&self) -> impl Future<Output = crate::Result<Duration>> + Send + 'static {
+        let pong = match self.shared.upgrade() {
+            Some(shared) => {
+                let mut locked = shared.lock().unwrap();
+                // Only one ping can be in flight, so if one already is, its
+                // round trip is measured instead.
+                if locked.is_ping_sent() {
+                    Ok(locked.wait_pong())
+                } else {
+                    locked
+                        .try_send_ping()
+                        .map(|()| locked.wait_pong())
+                        .map_err(crate::Error::new_h2)
+                }
+            }
+            None => Err(crate::Error::new_closed()),
+        };
+
+        async move { pong?.await.map_err(|_canceled| crate::Error::new_closed()) }
+    }
+}
+
 // ===== impl Ponger =====
 
 impl Ponger {
+    #[cfg(feature = "client")]
+    pub(super) fn pinger(&self) -> Pinger {
+        Pinger {
+            shared: Arc::downgrade(&self.shared),
+        }
+    }
+
     pub(super) fn poll(&mut self, cx: &mut task::Context<'_>) -> Poll<Ponged> {
         let now = Instant::now();
         let mut locked = self.shared.lock().unwrap();
@@ -281,7 +354,12 @@ impl Ponger {
                 let rtt = now - start;
                 trace!("recv pong");
 
+                for tx in locked.user_pings.drain(..) {
+                    let _ = tx.send(rtt);
+                }
+
                 if let Some(ref mut ka) = self.keep_alive {
+                    ka.record_rtt(rtt);
                     locked.update_last_read_at();
                     ka.maybe_schedule(is_idle, &locked);
                     ka.maybe_ping(cx, &mut locked);
@@ -301,12 +379,15 @@ impl Ponger {
             }
             Poll::Ready(Err(_e)) => {
                 debug!("pong error: {}", _e);
+                locked.user_pings.clear();
             }
             Poll::Pending => {
                 if let Some(ref mut ka) = self.keep_alive {
                     if let Err(KeepAliveTimedOut) = ka.maybe_timeout(cx) {
-                        self.keep_alive = None;
                         locked.is_keep_alive_timed_out = true;
+                        drop(locked);
+                        let mut ka = self.keep_alive.take().expect("keep-alive timed out");
+                        ka.on_timeout.call(ka.rtts.make_contiguous());
                         return Poll::Ready(Ponged::KeepAliveTimedOut);
                     }
                 }
@@ -322,21 +403,38 @@ impl Ponger {
     }
 }
 
+impl Drop for Ponger {
+    fn drop(&mut self) {
+        // The connection is gone, so the pongs will never come.
+        if let Ok(mut locked) = self.shared.lock() {
+            locked.user_pings.clear();
+        }
+    }
+}
+
 // ===== impl Shared =====
 
 impl Shared {
     fn send_ping(&mut self) {
-        match self.ping_pong.send_ping(Ping::opaque()) {
-            Ok(()) => {
-                self.ping_sent_at = Some(Instant::now());
-                trace!("sent ping");
-            }
-            Err(_err) => {
-                debug!("error sending ping: {}", _err);
-            }
+        if let Err(_err) = self.try_send_ping() {
+            debug!("error sending ping: {}", _err);
         }
     }
 
+    fn try_send_ping(&mut self) -> Result<(), h2::Error> {
+        self.ping_pong.send_ping(Ping::opaque())?;
+        self.ping_sent_at = Some(Instant::now());
+        trace!("sent ping");
+        Ok(())
+    }
+
+    #[cfg(feature = "client")]
+    fn wait_pong(&mut self) -> oneshot::Receiver<Duration> {
+        let (tx, rx) = oneshot::channel();
+        self.user_pings.push(tx);
+        rx
+    }
+
     fn is_ping_sent(&self) -> bool {
         self.ping_sent_at.is_some()
     }
@@ -420,9 +518,40 @@ fn seconds(dur: Duration) -> f64 {
     secs + (dur.subsec_nanos() as f64) / NANOS_PER_SEC
 }
 
+// ===== impl OnTimeout =====
+
+impl OnTimeout {
+    #[cfg(feature = "client")]
+    pub(super) fn new<F>(callback: F) -> OnTimeout
+    where
+        F: Fn(&[Duration]) + Send + Sync + 'static,
+    {
+        OnTimeout(Some(Arc::new(callback)))
+    }
+
+    fn call(&self, rtts: &[Duration]) {
+        if let Some(ref callback) = self.0 {
+            callback(rtts);
+        }
+    }
+}
+
+impl fmt::Debug for OnTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnTimeout").finish()
+    }
+}
+
 // ===== impl KeepAlive =====
 
 impl KeepAlive {
+    fn record_rtt(&mut self, rtt: Duration) {
+        if self.rtts.len() == RTT_HISTORY {
+            self.rtts.pop_front();
+        }
+        self.rtts.push_back(rtt);
+    }
+
     fn maybe_schedule(&mut self, is_idle: bool, shared: &Shared) {
         match self.state {
             KeepAliveState::Init => {
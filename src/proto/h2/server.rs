@@ -1,9 +1,10 @@
 use std::error::Error as StdError;
 use std::marker::Unpin;
+use std::sync::Arc;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::rt::{Read, Write};
+use crate::rt::{Read, TaskDescriptor, Write};
 use bytes::Bytes;
 use h2::server::{Connection, Handshake, SendResponse};
 use h2::{Reason, RecvStream};
@@ -12,19 +13,28 @@ use pin_project_lite::pin_project;
 
 use super::{ping, PipeToSendStream, SendBuf};
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::date::DateHeader;
+use crate::common::io::{Compat, Metered, WriteTimeout};
+use crate::common::lifecycle::{EventSink, Lifecycle, OpenConnection};
+use crate::common::metrics::{Elapsed, MetricsSink};
 use crate::common::time::Time;
-use crate::common::{date, task, Future, Pin, Poll};
+use crate::common::{task, Future, Pin, Poll};
 use crate::ext::Protocol;
 use crate::headers;
 use crate::proto::h2::ping::Recorder;
 use crate::proto::h2::{H2Upgraded, UpgradedSendStream};
+use crate::proto::span::{ConnSpan, RequestSpan};
 use crate::proto::Dispatched;
 use crate::rt::bounds::Http2ConnExec;
+use crate::rt::CloseReason;
 use crate::service::HttpService;
 
 use crate::upgrade::{OnUpgrade, Pending, Upgraded};
 use crate::Response;
 
+/// The IO given to `h2`.
+type ServerIo<T> = Compat<WriteTimeout<Metered<T>>>;
+
 // Our defaults are chosen for the "majority" case, which usually are not
 // resource constrained, and so the spec default of 64kb can be too limiting
 // for performance.
@@ -50,6 +60,8 @@ pub(crate) struct Config {
     pub(crate) keep_alive_timeout: Duration,
     pub(crate) max_send_buffer_size: usize,
     pub(crate) max_header_list_size: u32,
+    pub(crate) write_timeout: Option<Duration>,
+    pub(crate) date_header: DateHeader,
 }
 
 impl Default for Config {
@@ -65,6 +77,8 @@ impl Default for Config {
             keep_alive_timeout: Duration::from_secs(20),
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
             max_header_list_size: DEFAULT_SETTINGS_MAX_HEADER_LIST_SIZE,
+            write_timeout: None,
+            date_header: DateHeader::default(),
         }
     }
 }
@@ -77,6 +91,11 @@ pin_project! {
     {
         exec: E,
         timer: Time,
+        conn_id: u64,
+        span: ConnSpan,
+        metrics: MetricsSink,
+        _opened: Elapsed,
+        connection: OpenConnection,
         service: S,
         state: State<T, B>,
     }
@@ -88,7 +107,9 @@ where
 {
     Handshaking {
         ping_config: ping::Config,
-        hs: Handshake<crate::common::io::Compat<T>, SendBuf<B::Data>>,
+        date_header: DateHeader,
+        started: Option<Instant>,
+        hs: Handshake<ServerIo<T>, SendBuf<B::Data>>,
     },
     Serving(Serving<T, B>),
     Closed,
@@ -98,9 +119,14 @@ struct Serving<T, B>
 where
     B: Body,
 {
+    conn_id: u64,
     ping: Option<(ping::Recorder, ping::Ponger)>,
-    conn: Connection<crate::common::io::Compat<T>, SendBuf<B::Data>>,
+    conn: Connection<ServerIo<T>, SendBuf<B::Data>>,
     closing: Option<crate::Error>,
+    /// Cloned into each stream, so that the streams in flight can be
+    /// counted.
+    active: Arc<()>,
+    date_header: DateHeader,
 }
 
 impl<T, S, B, E> Server<T, S, B, E>
@@ -117,6 +143,8 @@ where
         config: &Config,
         exec: E,
         timer: Time,
+        metrics: MetricsSink,
+        events: &EventSink,
     ) -> Server<T, S, B, E> {
         let mut builder = h2::server::Builder::default();
         builder
@@ -131,6 +159,11 @@ where
         if config.enable_connect_protocol {
             builder.enable_connect_protocol();
         }
+        let connection = events.opened();
+        let opened = metrics.connection_opened();
+        let started = metrics.handshake_started();
+        let io = Metered::new(io, metrics.clone());
+        let io = WriteTimeout::new(io, timer.clone(), config.write_timeout);
         let handshake = builder.handshake(crate::common::io::compat(io));
 
         let bdp = if config.adaptive_window {
@@ -146,13 +179,22 @@ where
             // If keep-alive is enabled for servers, always enabled while
             // idle, so it can more aggressively close dead connections.
             keep_alive_while_idle: true,
+            on_keep_alive_timeout: ping::OnTimeout::default(),
         };
 
+        let conn_id = crate::rt::next_connection_id();
         Server {
             exec,
             timer,
+            conn_id,
+            span: ConnSpan::new(conn_id, "server", "HTTP/2"),
+            metrics,
+            _opened: opened,
+            connection,
             state: State::Handshaking {
                 ping_config,
+                date_header: config.date_header.clone(),
+                started,
                 hs: handshake,
             },
             service,
@@ -161,6 +203,7 @@ where
 
     pub(crate) fn graceful_shutdown(&mut self) {
         trace!("graceful_shutdown");
+        self.connection.lifecycle().closing();
         match self.state {
             State::Handshaking { .. } => {
                 // fall-through, to replace state with Closed
@@ -177,6 +220,39 @@ where
         }
         self.state = State::Closed;
     }
+
+    /// Closes the connection without waiting for the streams in flight,
+    /// which are cancelled, and returns how many there were.
+    ///
+    /// The connection must then be driven with `poll_aborted`, to write the
+    /// GOAWAY telling the client.
+    pub(crate) fn abort(&mut self) -> usize {
+        trace!("abort");
+        self.connection.lifecycle().closing();
+        match self.state {
+            State::Serving(ref mut srv) => {
+                srv.conn.abrupt_shutdown(Reason::CANCEL);
+                Arc::strong_count(&srv.active) - 1
+            }
+            State::Handshaking { .. } | State::Closed => {
+                self.state = State::Closed;
+                0
+            }
+        }
+    }
+
+    /// Drives an aborted connection until it has closed.
+    pub(crate) fn poll_aborted(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        if let State::Serving(ref mut srv) = self.state {
+            if let Err(err) = ready!(srv.conn.poll_closed(cx)) {
+                debug!("connection error while aborting: {}", err);
+            }
+        }
+        // Dropping the h2 connection fails its streams, which ends their
+        // tasks.
+        self.state = State::Closed;
+        Poll::Ready(())
+    }
 }
 
 impl<T, S, B, E> Future for Server<T, S, B, E>
@@ -191,13 +267,17 @@ where
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
         let me = &mut *self;
+        let _entered = me.span.enter();
         loop {
             let next = match me.state {
                 State::Handshaking {
                     ref mut hs,
                     ref ping_config,
+                    ref date_header,
+                    started,
                 } => {
                     let mut conn = ready!(Pin::new(hs).poll(cx).map_err(crate::Error::new_h2))?;
+                    me.metrics.handshake_completed(started);
                     let ping = if ping_config.is_enabled() {
                         let pp = conn.ping_pong().expect("conn.ping_pong");
                         Some(ping::channel(pp, ping_config.clone(), me.timer.clone()))
@@ -205,18 +285,34 @@ where
                         None
                     };
                     State::Serving(Serving {
+                        conn_id: me.conn_id,
                         ping,
                         conn,
                         closing: None,
+                        active: Arc::new(()),
+                        date_header: date_header.clone(),
                     })
                 }
                 State::Serving(ref mut srv) => {
-                    ready!(srv.poll_server(cx, &mut me.service, &mut me.exec))?;
+                    let lifecycle = me.connection.lifecycle();
+                    let res = ready!(srv.poll_server(
+                        cx,
+                        &mut me.service,
+                        &mut me.exec,
+                        &me.metrics,
+                        lifecycle,
+                    ));
+                    match res {
+                        Ok(()) => lifecycle.closed(CloseReason::Done),
+                        Err(ref e) => lifecycle.closed(CloseReason::Error(e)),
+                    }
+                    res?;
                     return Poll::Ready(Ok(Dispatched::Shutdown));
                 }
                 State::Closed => {
                     // graceful_shutdown was called before handshaking finished,
                     // nothing to do here...
+                    me.connection.lifecycle().closed(CloseReason::Done);
                     return Poll::Ready(Ok(Dispatched::Shutdown));
                 }
             };
@@ -235,6 +331,8 @@ where
         cx: &mut task::Context<'_>,
         service: &mut S,
         exec: &mut E,
+        metrics: &MetricsSink,
+        lifecycle: &Lifecycle,
     ) -> Poll<crate::Result<()>>
     where
         S: HttpService<IncomingBody, ResBody = B>,
@@ -245,6 +343,28 @@ where
             loop {
                 self.poll_ping(cx);
 
+                // Only accept new streams once the service is ready for them.
+                // Until then the connection is still driven, so that streams
+                // already in flight make progress.
+                match service.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => (),
+                    Poll::Pending => {
+                        ready!(self.conn.poll_closed(cx).map_err(crate::Error::new_h2))?;
+                        if let Some((ref ping, _)) = self.ping {
+                            ping.ensure_not_timed_out()?;
+                        }
+                        trace!("incoming connection complete");
+                        return Poll::Ready(Ok(()));
+                    }
+                    Poll::Ready(Err(err)) => {
+                        let err = crate::Error::new_user_service(err);
+                        debug!("service not ready, closing connection: {}", err);
+                        self.conn.abrupt_shutdown(h2::Reason::INTERNAL_ERROR);
+                        self.closing = Some(err);
+                        break;
+                    }
+                }
+
                 match ready!(self.conn.poll_accept(cx)) {
                     Some(Ok((req, mut respond))) => {
                         trace!("incoming request");
@@ -291,8 +411,23 @@ where
                             req.extensions_mut().insert(Protocol::from_inner(protocol));
                         }
 
-                        let fut = H2Stream::new(service.call(req), connect_parts, respond);
-                        exec.execute_h2stream(fut);
+                        let span = RequestSpan::new(req.method(), req.uri(), metrics, lifecycle);
+                        let fut = {
+                            let _entered = span.enter();
+                            service.call(req)
+                        };
+                        let fut = H2Stream::new(
+                            fut,
+                            connect_parts,
+                            respond,
+                            span,
+                            self.active.clone(),
+                            self.date_header.clone(),
+                        );
+                        exec.execute_h2stream(
+                            fut,
+                            &TaskDescriptor::new(self.conn_id, "h2 server stream"),
+                        )?;
                     }
                     Some(Err(e)) => {
                         return Poll::Ready(Err(crate::Error::new_h2(e)));
@@ -344,6 +479,10 @@ pin_project! {
         B: Body,
     {
         reply: SendResponse<SendBuf<B::Data>>,
+        span: RequestSpan,
+        // Counts this stream as in flight, until it's dropped.
+        _active: Arc<()>,
+        date_header: DateHeader,
         #[pin]
         state: H2StreamState<F, B>,
     }
@@ -381,9 +520,15 @@ where
         fut: F,
         connect_parts: Option<ConnectParts>,
         respond: SendResponse<SendBuf<B::Data>>,
+        span: RequestSpan,
+        active: Arc<()>,
+        date_header: DateHeader,
     ) -> H2Stream<F, B> {
         H2Stream {
             reply: respond,
+            span,
+            _active: active,
+            date_header,
             state: H2StreamState::Service { fut, connect_parts },
         }
     }
@@ -440,13 +585,16 @@ where
                     };
 
                     let (head, body) = res.into_parts();
+                    me.span.record_status(head.status);
                     let mut res = ::http::Response::from_parts(head, ());
-                    super::strip_connection_headers(res.headers_mut(), false);
+                    headers::strip_connection_headers(res.headers_mut(), false);
 
                     // set Date header if it isn't already set...
-                    res.headers_mut()
-                        .entry(::http::header::DATE)
-                        .or_insert_with(date::update_and_header_value);
+                    if !res.headers().contains_key(::http::header::DATE) {
+                        if let Some(date) = me.date_header.header_value() {
+                            res.headers_mut().insert(::http::header::DATE, date);
+                        }
+                    }
 
                     if let Some(connect_parts) = connect_parts.take() {
                         if res.status().is_success() {
@@ -464,6 +612,8 @@ where
                                     recv_stream: connect_parts.recv_stream,
                                     send_stream: unsafe { UpgradedSendStream::new(send_stream) },
                                     buf: Bytes::new(),
+                                    #[cfg(feature = "client")]
+                                    _open_stream: None,
                                 },
                                 Bytes::new(),
                             ));
@@ -506,6 +656,7 @@ where
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let _entered = self.span.enter();
         self.poll2(cx).map(|res| {
             if let Err(_e) = res {
                 debug!("stream error: {}", _e);
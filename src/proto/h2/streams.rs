@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+use crate::common::{task, Poll};
+
+/// The streams open for requests sent on a client connection.
+///
+/// `h2` doesn't count its streams for others to see, so each stream holds
+/// an [`OpenStream`], from when its request is sent until it's done with.
+#[derive(Debug, Default)]
+pub(crate) struct OpenStreams {
+    inner: Mutex<Streams>,
+}
+
+#[derive(Debug, Default)]
+struct Streams {
+    open: usize,
+    // Slots held for requests not sent yet.
+    reserved: usize,
+    // Set once the connection takes no more requests.
+    is_closed: bool,
+    // Set once the connection is gone, and its streams with it.
+    is_done: bool,
+    waiters: Vec<Waker>,
+}
+
+/// Counts a stream as open, until dropped.
+///
+/// Shared by the tasks sending the request body and waiting for the
+/// response head, and then kept by the upgraded IO of a CONNECT request.
+#[derive(Debug)]
+pub(crate) struct OpenStream(Arc<OpenStreams>);
+
+/// A stream slot held by `SendRequest::poll_ready`, until its request has
+/// opened a stream.
+#[derive(Debug)]
+pub(crate) struct Reservation(Arc<OpenStreams>);
+
+impl OpenStreams {
+    pub(crate) fn count(&self) -> usize {
+        let streams = self.inner.lock().unwrap();
+        if streams.is_done {
+            0
+        } else {
+            streams.open
+        }
+    }
+
+    /// Reserves a slot for a new stream, if fewer than `limit` are open or
+    /// reserved. Errors once the connection takes no more requests.
+    pub(crate) fn poll_reserve(
+        self: &Arc<Self>,
+        cx: &mut task::Context<'_>,
+        limit: Option<u32>,
+    ) -> Poll<Result<Reservation, ()>> {
+        let mut streams = self.inner.lock().unwrap();
+        if streams.is_closed {
+            return Poll::Ready(Err(()));
+        }
+        let used = streams.open + streams.reserved;
+        if limit.map_or(true, |limit| used < limit as usize) {
+            streams.reserved += 1;
+            return Poll::Ready(Ok(Reservation(self.clone())));
+        }
+        if !streams.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            streams.waiters.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Counts a new stream as open, until the returned `OpenStream` is
+    /// dropped.
+    pub(crate) fn opened(self: &Arc<Self>) -> OpenStream {
+        self.inner.lock().unwrap().open += 1;
+        OpenStream(self.clone())
+    }
+
+    /// Stops reservations, such as once shutting down.
+    pub(crate) fn close(&self) {
+        let mut streams = self.inner.lock().unwrap();
+        streams.is_closed = true;
+        streams.notify();
+    }
+
+    /// Forgets all the streams, once the connection is closed.
+    pub(crate) fn clear(&self) {
+        let mut streams = self.inner.lock().unwrap();
+        streams.is_closed = true;
+        streams.is_done = true;
+        streams.notify();
+    }
+
+    /// Wakes anyone waiting for a slot, such as when the limit may have
+    /// changed.
+    pub(crate) fn notify(&self) {
+        self.inner.lock().unwrap().notify();
+    }
+}
+
+impl Streams {
+    fn notify(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        let mut streams = self.0.inner.lock().unwrap();
+        streams.open -= 1;
+        streams.notify();
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let mut streams = self.0.inner.lock().unwrap();
+        streams.reserved -= 1;
+        streams.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_count_until_dropped() {
+        let streams = Arc::new(OpenStreams::default());
+        let first = Arc::new(streams.opened());
+        let second = streams.opened();
+        assert_eq!(streams.count(), 2);
+
+        // shared by the request body and the response
+        let response = first.clone();
+        drop(first);
+        assert_eq!(streams.count(), 2);
+        drop(response);
+        drop(second);
+        assert_eq!(streams.count(), 0);
+
+        let _open = streams.opened();
+        streams.clear();
+        assert_eq!(streams.count(), 0);
+    }
+
+    #[test]
+    fn streams_reserve_up_to_limit() {
+        let streams = Arc::new(OpenStreams::default());
+        let waker = futures_util::task::noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+
+        let open = streams.opened();
+        let reserved = match streams.poll_reserve(&mut cx, Some(2)) {
+            Poll::Ready(Ok(reservation)) => reservation,
+            _ => panic!("slot available"),
+        };
+        assert!(streams.poll_reserve(&mut cx, Some(2)).is_pending());
+        drop(open);
+        assert!(matches!(
+            streams.poll_reserve(&mut cx, Some(2)),
+            Poll::Ready(Ok(_))
+        ));
+        drop(reserved);
+
+        streams.close();
+        assert!(matches!(
+            streams.poll_reserve(&mut cx, None),
+            Poll::Ready(Err(()))
+        ));
+    }
+}
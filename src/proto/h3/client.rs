@@ -0,0 +1,557 @@
+use std::error::Error as StdError;
+use std::mem;
+
+use bytes::Bytes;
+use http::{HeaderMap, Method, Request, Response, StatusCode, Uri, Version};
+use pin_project_lite::pin_project;
+
+use super::frame::{Frame, FrameStream};
+use super::upgrade::H3Upgraded;
+use super::webtransport::Sessions;
+use super::{
+    encode_headers, reset, Code, Config, Control, Fields, RecvBody, SendMessage, StreamRef,
+    StreamRefs,
+};
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::client::dispatch::Callback;
+use crate::common::lifecycle::{EventSink, OpenConnection};
+use crate::common::metrics::{Elapsed, MetricsSink};
+use crate::common::{task, Future, Pin, Poll};
+use crate::ext::{H3Datagrams, Protocol, WebTransportSession};
+use crate::headers;
+use crate::proto::span::{ConnSpan, RequestSpan};
+use crate::rt::bounds::Http3ClientExec;
+use crate::rt::quic::{Connection, RecvStream, SendStream};
+use crate::rt::{CloseReason, TaskDescriptor};
+use crate::upgrade::Upgraded;
+
+type ClientRx<B> = crate::client::dispatch::Receiver<Request<B>, Response<IncomingBody>>;
+
+type ResponseCallback<B> = Callback<Request<B>, Response<IncomingBody>>;
+
+pub(crate) struct ClientTask<C, B, E>
+where
+    C: Connection,
+{
+    conn_id: u64,
+    span: ConnSpan,
+    metrics: MetricsSink,
+    _opened: Elapsed,
+    connection: OpenConnection,
+    executor: E,
+    config: Config,
+    control: Control<C>,
+    // dropped once no more requests will be sent
+    req_rx: Option<ClientRx<B>>,
+    // a request waiting for its stream to be opened
+    pending: Option<(Request<B>, ResponseCallback<B>)>,
+    streams: StreamRefs,
+    // once WebTransport is enabled
+    sessions: Option<Sessions>,
+    // streams the server opened, whose start isn't received yet
+    heads: Vec<FrameStream<C::BidiStream>>,
+}
+
+impl<C, B, E> ClientTask<C, B, E>
+where
+    C: Connection + Unpin,
+    B: Body + Unpin + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ClientExec<B, C::BidiStream> + Unpin,
+{
+    pub(crate) fn new(
+        conn: C,
+        req_rx: ClientRx<B>,
+        config: &Config,
+        executor: E,
+        metrics: MetricsSink,
+        events: &EventSink,
+    ) -> ClientTask<C, B, E> {
+        let connection = events.opened();
+        let opened = metrics.connection_opened();
+        let conn_id = crate::rt::next_connection_id();
+        let streams = StreamRefs::default();
+        let sessions = if config.enable_webtransport {
+            Some(Sessions::new(streams.clone(), metrics.clone()))
+        } else {
+            None
+        };
+        ClientTask {
+            conn_id,
+            span: ConnSpan::new(conn_id, "client", "HTTP/3"),
+            control: Control::new(conn, false, config, metrics.clone()),
+            metrics,
+            _opened: opened,
+            connection,
+            executor,
+            config: config.clone(),
+            req_rx: Some(req_rx),
+            pending: None,
+            streams,
+            sessions,
+            heads: Vec::new(),
+        }
+    }
+
+    fn poll_client(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        if self.control.poll(cx)?.is_ready() {
+            trace!("connection closed");
+            return Poll::Ready(Ok(()));
+        }
+
+        if self.control.goaway.is_some() && self.req_rx.is_some() {
+            trace!("connection gracefully shutdown");
+            self.connection.lifecycle().closing();
+            self.req_rx = None;
+            if let Some((req, cb)) = self.pending.take() {
+                // No stream was opened for it, so it can be retried.
+                cb.send(Err((
+                    crate::Error::new_canceled().with("connection is closing"),
+                    Some(req),
+                )));
+            }
+        }
+
+        self.poll_requests(cx)?;
+        self.poll_sessions(cx)?;
+
+        if self.req_rx.is_none() && self.streams.poll_idle(cx).is_ready() {
+            trace!("connection idle, closing");
+            self.control.close_gracefully();
+            return Poll::Ready(Ok(()));
+        }
+        Poll::Pending
+    }
+
+    fn poll_requests(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        loop {
+            let rx = match self.req_rx {
+                Some(ref mut rx) => rx,
+                None => return Ok(()),
+            };
+            if self.pending.is_none() {
+                match rx.poll_recv(cx) {
+                    Poll::Ready(Some((req, cb))) => {
+                        // check that future hasn't been canceled already
+                        if cb.is_canceled() {
+                            trace!("request callback is canceled");
+                            continue;
+                        }
+                        self.pending = Some((req, cb));
+                    }
+                    Poll::Ready(None) => {
+                        trace!("client::dispatch::Sender dropped");
+                        self.req_rx = None;
+                        return Ok(());
+                    }
+                    Poll::Pending => return Ok(()),
+                }
+            }
+
+            if let Some((ref req, _)) = self.pending {
+                if let Some(protocol) = extended_connect_protocol(req) {
+                    // Whether the server supports it is only known from
+                    // its settings.
+                    let settings = match self.control.settings() {
+                        Some(settings) => settings,
+                        None => return Ok(()),
+                    };
+                    let supported = settings.enable_connect_protocol
+                        && (protocol.as_str() != "webtransport"
+                            || (settings.enable_webtransport && self.sessions.is_some()));
+                    if !supported {
+                        debug!("server doesn't support protocol {:?}", protocol);
+                        let (_, cb) = self.pending.take().expect("pending request");
+                        cb.send(Err((crate::Error::new_user_unsupported_protocol(), None)));
+                        continue;
+                    }
+                }
+            }
+
+            let opened = match self.control.conn().poll_open_bidi(cx) {
+                Poll::Ready(opened) => opened,
+                Poll::Pending => return Ok(()),
+            };
+            let (req, cb) = self.pending.take().expect("pending request");
+            match opened {
+                Ok(stream) => self.start_request(req, cb, stream)?,
+                Err(e) => {
+                    debug!("client open stream error: {}", e);
+                    cb.send(Err((crate::Error::new_io(e), None)));
+                }
+            }
+        }
+    }
+
+    /// Accept the streams the server opens in WebTransport sessions, and
+    /// open those sessions ask for.
+    fn poll_sessions(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        let sessions = match self.sessions {
+            Some(ref mut sessions) => sessions,
+            None => return Ok(()),
+        };
+        sessions.poll_open(cx, self.control.conn());
+
+        loop {
+            match self.control.conn().poll_accept_bidi(cx) {
+                Poll::Ready(Ok(Some(stream))) => self.heads.push(FrameStream::new(
+                    stream,
+                    Bytes::new(),
+                    self.config.max_field_section_size,
+                    self.metrics.clone(),
+                )),
+                Poll::Ready(Ok(None)) | Poll::Pending => break,
+                Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+            }
+        }
+
+        let mut i = 0;
+        while i < self.heads.len() {
+            let frame = match self.heads[i].poll_frame(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let frames = self.heads.swap_remove(i);
+            match frame {
+                Ok(Some(Frame::WebTransport(session_id))) => {
+                    let (stream, buf) = frames.into_parts();
+                    sessions.accept(session_id, stream, buf);
+                }
+                // Servers can't open streams for anything else.
+                Ok(Some(_)) => return Err(crate::Error::new_h3(Code::STREAM_CREATION_ERROR)),
+                Ok(None) => {
+                    debug!("stream finished before its start");
+                }
+                Err(err) if err.find_source::<Code>().is_some() => return Err(err),
+                Err(_err) => {
+                    debug!("stream error before its start: {}", _err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn start_request(
+        &mut self,
+        req: Request<B>,
+        cb: ResponseCallback<B>,
+        stream: C::BidiStream,
+    ) -> crate::Result<()> {
+        let protocol = extended_connect_protocol(&req).cloned();
+        let (head, body) = req.into_parts();
+        let lifecycle = self.connection.lifecycle();
+        let span = RequestSpan::new(&head.method, &head.uri, &self.metrics, lifecycle);
+        let mut headers = head.headers;
+        headers::strip_connection_headers(&mut headers, true);
+        let is_connect = head.method == Method::CONNECT;
+        if let Some(len) = body.size_hint().exact() {
+            if !is_connect
+                && (len != 0 || headers::method_has_defined_payload_semantics(&head.method))
+            {
+                headers::set_content_length_if_missing(&mut headers, len);
+            }
+        }
+
+        let session = match (&protocol, self.sessions.as_mut()) {
+            (Some(protocol), Some(sessions)) if protocol.as_str() == "webtransport" => {
+                Some(sessions.register(RecvStream::id(&stream)))
+            }
+            _ => None,
+        };
+        let datagrams = match self.control.datagrams {
+            Some(ref mut datagrams) if is_connect => {
+                Some(datagrams.register(RecvStream::id(&stream)))
+            }
+            _ => None,
+        };
+        let block = encode_request(&head.method, &head.uri, protocol.as_ref(), &headers);
+        let send = if is_connect {
+            SendMessage::head_only(block, body, self.metrics.clone())
+        } else {
+            SendMessage::new(block, body, self.metrics.clone())
+        };
+        let max_size = self.config.max_field_section_size;
+        let fut = H3ClientStream {
+            send,
+            sent: false,
+            frames: Some(FrameStream::new(
+                stream,
+                Bytes::new(),
+                max_size,
+                self.metrics.clone(),
+            )),
+            state: RecvState::Head(cb, span),
+            is_connect,
+            session,
+            datagrams,
+            max_field_section_size: max_size,
+            metrics: self.metrics.clone(),
+            _stream: self.streams.track(),
+        };
+        self.executor
+            .execute_h3_future(fut, &TaskDescriptor::new(self.conn_id, "h3 client stream"))
+    }
+}
+
+impl<C, B, E> Future for ClientTask<C, B, E>
+where
+    C: Connection + Unpin,
+    B: Body + Unpin + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ClientExec<B, C::BidiStream> + Unpin,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let me = &mut *self;
+        let _entered = me.span.enter();
+        let res = ready!(me.poll_client(cx));
+        let lifecycle = me.connection.lifecycle();
+        match res {
+            Ok(()) => lifecycle.closed(CloseReason::Done),
+            Err(ref e) => {
+                me.control.close(e);
+                lifecycle.closed(CloseReason::Error(e));
+            }
+        }
+        Poll::Ready(res)
+    }
+}
+
+/// The `Protocol` of an extended CONNECT request.
+fn extended_connect_protocol<B>(req: &Request<B>) -> Option<&Protocol> {
+    if req.method() == Method::CONNECT {
+        req.extensions().get::<Protocol>()
+    } else {
+        None
+    }
+}
+
+fn encode_request(
+    method: &Method,
+    uri: &Uri,
+    protocol: Option<&Protocol>,
+    headers: &HeaderMap,
+) -> Bytes {
+    // Only an extended CONNECT request has a scheme and path.
+    let is_tunnel = *method == Method::CONNECT && protocol.is_none();
+    let mut pseudo = vec![(":method", method.as_str().as_bytes())];
+    if !is_tunnel {
+        let scheme = uri.scheme_str().unwrap_or("https");
+        pseudo.push((":scheme", scheme.as_bytes()));
+    }
+    if let Some(authority) = uri.authority() {
+        pseudo.push((":authority", authority.as_str().as_bytes()));
+    }
+    if !is_tunnel {
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        pseudo.push((":path", path.as_bytes()));
+    }
+    if let Some(protocol) = protocol {
+        pseudo.push((":protocol", protocol.as_ref()));
+    }
+    encode_headers(&pseudo, headers)
+}
+
+/// Decode a response head, or `None` for an informational response.
+fn decode_response(fields: Fields) -> Result<Option<Response<()>>, Code> {
+    if fields.method.is_some()
+        || fields.scheme.is_some()
+        || fields.authority.is_some()
+        || fields.path.is_some()
+    {
+        return Err(Code::MESSAGE_ERROR);
+    }
+    let status = fields.status.ok_or(Code::MESSAGE_ERROR)?;
+    let status = StatusCode::from_bytes(&status).map_err(|_| Code::MESSAGE_ERROR)?;
+    if status == StatusCode::SWITCHING_PROTOCOLS {
+        return Err(Code::MESSAGE_ERROR);
+    }
+    if status.is_informational() {
+        return Ok(None);
+    }
+
+    let mut res = Response::new(());
+    *res.status_mut() = status;
+    *res.version_mut() = Version::HTTP_3;
+    *res.headers_mut() = fields.headers;
+    Ok(Some(res))
+}
+
+pin_project! {
+    #[allow(missing_debug_implementations)]
+    pub struct H3ClientStream<B, T>
+    where
+        B: Body,
+    {
+        #[pin]
+        send: SendMessage<B>,
+        sent: bool,
+        // taken once a CONNECT tunnel is established
+        frames: Option<FrameStream<T>>,
+        state: RecvState<B>,
+        is_connect: bool,
+        // the WebTransport session the request establishes
+        session: Option<WebTransportSession>,
+        // the datagrams associated with a CONNECT request
+        datagrams: Option<H3Datagrams>,
+        max_field_section_size: u64,
+        metrics: MetricsSink,
+        _stream: StreamRef,
+    }
+}
+
+enum RecvState<B> {
+    Head(ResponseCallback<B>, RequestSpan),
+    Body(RecvBody),
+    Done,
+}
+
+impl<B, T> H3ClientStream<B, T>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    T: SendStream + RecvStream + Unpin + Send + 'static,
+{
+    fn poll_recv(self: Pin<&mut Self>, cx: &mut task::Context<'_>) {
+        let me = self.project();
+        let frames = match *me.frames {
+            Some(ref mut frames) => frames,
+            None => return,
+        };
+        loop {
+            match *me.state {
+                RecvState::Head(ref mut cb, _) => {
+                    if cb.poll_canceled(cx).is_ready() {
+                        trace!("request canceled");
+                        reset(frames.get_mut(), Code::REQUEST_CANCELLED);
+                        *me.sent = true;
+                        *me.state = RecvState::Done;
+                        return;
+                    }
+
+                    let res = match frames.poll_frame(cx) {
+                        Poll::Ready(Ok(Some(Frame::Headers(block)))) => {
+                            Fields::decode(&block, *me.max_field_section_size)
+                                .and_then(decode_response)
+                        }
+                        Poll::Ready(Ok(Some(_))) => Err(Code::FRAME_UNEXPECTED),
+                        Poll::Ready(Ok(None)) => Err(Code::REQUEST_INCOMPLETE),
+                        Poll::Ready(Err(err)) => {
+                            if let RecvState::Head(cb, _) = mem::replace(me.state, RecvState::Done)
+                            {
+                                cb.send(Err((err, None)));
+                            }
+                            return;
+                        }
+                        Poll::Pending => return,
+                    };
+                    let res = match res {
+                        Ok(Some(res)) => res,
+                        // skip informational responses
+                        Ok(None) => continue,
+                        Err(code) => {
+                            debug!("malformed response: {:?}", code);
+                            reset(frames.get_mut(), code);
+                            *me.sent = true;
+                            if let RecvState::Head(cb, _) = mem::replace(me.state, RecvState::Done)
+                            {
+                                cb.send(Err((crate::Error::new_h3(code), None)));
+                            }
+                            return;
+                        }
+                    };
+
+                    if *me.is_connect && res.status().is_success() {
+                        trace!("CONNECT tunnel established");
+                        let frames = me.frames.take().expect("frames");
+                        let io = H3Upgraded::new(frames, me.metrics.clone(), me._stream.clone());
+                        let (pending, upgrade) = crate::upgrade::pending();
+                        pending.fulfill(Upgraded::new(io, Bytes::new()));
+
+                        let mut res = res.map(|()| IncomingBody::empty());
+                        res.extensions_mut().insert(upgrade);
+                        if let Some(session) = me.session.take() {
+                            res.extensions_mut().insert(session);
+                        }
+                        if let Some(datagrams) = me.datagrams.take() {
+                            res.extensions_mut().insert(datagrams);
+                        }
+                        if let RecvState::Head(cb, span) = mem::replace(me.state, RecvState::Done) {
+                            span.record_status(res.status());
+                            cb.send(Ok(res));
+                        }
+                        return;
+                    }
+                    if *me.is_connect {
+                        // The tunnel was refused, so nothing more is sent.
+                        SendStream::reset(Pin::new(frames.get_mut()), Code::NO_ERROR.value());
+                        *me.sent = true;
+                    }
+
+                    let content_length = headers::content_length_parse_all(res.headers());
+                    let (recv, body) = RecvBody::new(content_length, *me.max_field_section_size);
+                    if let RecvState::Head(cb, span) = mem::replace(me.state, RecvState::Body(recv))
+                    {
+                        span.record_status(res.status());
+                        cb.send(Ok(res.map(|()| body)));
+                    }
+                }
+                RecvState::Body(ref mut recv) => {
+                    if recv.poll_recv(cx, frames).is_pending() {
+                        return;
+                    }
+                    *me.state = RecvState::Done;
+                }
+                RecvState::Done => return,
+            }
+        }
+    }
+}
+
+impl<B, T> Future for H3ClientStream<B, T>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    T: SendStream + RecvStream + Unpin + Send + 'static,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let me = self.as_mut().project();
+        if let (false, Some(frames)) = (*me.sent, me.frames.as_mut()) {
+            if let Poll::Ready(res) = me.send.poll_send(cx, frames.get_mut()) {
+                *me.sent = true;
+                if let Err(err) = res {
+                    debug!("client request body error: {}", err);
+                    // The response may still be received after the server
+                    // stopped reading the request, but not after the
+                    // request body failed.
+                    if err.is_user() {
+                        let code = err.h3_code().value();
+                        Pin::new(frames.get_mut()).stop_sending(code);
+                        match mem::replace(me.state, RecvState::Done) {
+                            RecvState::Head(cb, _) => cb.send(Err((err, None))),
+                            RecvState::Body(mut recv) => recv.tx.send_error(err),
+                            RecvState::Done => (),
+                        }
+                    }
+                }
+            }
+        }
+
+        // A tunnel takes over the stream, so its head is sent first.
+        if self.sent || !self.is_connect {
+            self.as_mut().poll_recv(cx);
+        }
+
+        if self.sent && matches!(self.state, RecvState::Done) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! HTTP datagrams, from RFC 9297, sent in QUIC datagrams.
+
+use std::collections::HashMap;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::sync::mpsc;
+
+use super::frame::{self, PeerSettings};
+use super::Code;
+use crate::common::{task, Pin, Poll};
+use crate::ext::H3Datagrams;
+use crate::rt::quic::Connection;
+
+// How many datagrams are queued in either direction before more are
+// dropped, as datagrams may be anyway.
+const QUEUE_SIZE: usize = 64;
+
+/// Routes the datagrams of a connection: those received, to the stream
+/// they are associated with, and those the streams send, to the connection.
+pub(super) struct Router {
+    // where the datagrams received for each request stream are sent
+    streams: HashMap<u64, mpsc::Sender<Bytes>>,
+    send_tx: mpsc::Sender<(u64, Bytes)>,
+    send_rx: mpsc::Receiver<(u64, Bytes)>,
+}
+
+impl Router {
+    pub(super) fn new() -> Router {
+        let (send_tx, send_rx) = mpsc::channel(QUEUE_SIZE);
+        Router {
+            streams: HashMap::new(),
+            send_tx,
+            send_rx,
+        }
+    }
+
+    /// Start receiving the datagrams associated with a request stream.
+    pub(super) fn register(&mut self, stream_id: u64) -> H3Datagrams {
+        // forget the streams dropped since
+        self.streams.retain(|_, tx| !tx.is_closed());
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        self.streams.insert(stream_id, tx);
+        H3Datagrams::new(stream_id, rx, self.send_tx.clone())
+    }
+
+    /// Receive and send datagrams, given the peer's settings once known.
+    pub(super) fn poll<C>(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        mut conn: Pin<&mut C>,
+        settings: Option<PeerSettings>,
+    ) -> crate::Result<()>
+    where
+        C: Connection,
+    {
+        let mut datagrams = match conn.as_mut().datagrams() {
+            Some(datagrams) => datagrams,
+            None => {
+                // Nothing can be sent, so just empty the queue.
+                while let Poll::Ready(Some(_)) = self.send_rx.poll_recv(cx) {}
+                return Ok(());
+            }
+        };
+
+        loop {
+            match datagrams.as_mut().poll_recv_datagram(cx) {
+                Poll::Ready(Ok(Some(datagram))) => self.recv(datagram)?,
+                Poll::Ready(Ok(None)) | Poll::Pending => break,
+                Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+            }
+        }
+
+        // Wait for the peer's settings to know if it accepts datagrams.
+        let enabled = match settings {
+            Some(settings) => settings.enable_datagrams,
+            None => return Ok(()),
+        };
+        while let Poll::Ready(Some((stream_id, payload))) = self.send_rx.poll_recv(cx) {
+            if !enabled {
+                trace!("peer doesn't accept datagrams, dropping one");
+                continue;
+            }
+            let mut datagram = BytesMut::with_capacity(8 + payload.len());
+            frame::encode_varint(stream_id / 4, &mut datagram);
+            datagram.put(payload);
+            if let Err(_e) = datagrams.as_mut().send_datagram(datagram.freeze()) {
+                debug!("datagram for stream {} not sent: {}", stream_id, _e);
+            }
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, mut datagram: Bytes) -> crate::Result<()> {
+        let (quarter_id, n) = match frame::decode_varint(&datagram) {
+            Some(decoded) => decoded,
+            None => return Err(crate::Error::new_h3(Code::DATAGRAM_ERROR)),
+        };
+        let stream_id = quarter_id * 4;
+        let payload = datagram.split_off(n);
+        if let Some(tx) = self.streams.get(&stream_id) {
+            // A full queue drops it, as datagrams may be dropped anyway.
+            let _ = tx.try_send(payload);
+        } else {
+            // Datagrams can arrive before their stream is known, but hyper
+            // doesn't buffer them.
+            trace!("dropping datagram for unknown stream {}", stream_id);
+        }
+        Ok(())
+    }
+}
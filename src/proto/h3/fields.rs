@@ -0,0 +1,93 @@
+//! Encoding and validating the field sections of HEADERS frames.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use http::header::{HeaderName, HeaderValue, CONNECTION, TE, TRANSFER_ENCODING, UPGRADE};
+use http::HeaderMap;
+
+use super::{frame, qpack, Code};
+
+/// Encode a HEADERS frame.
+pub(super) fn encode_headers(pseudo: &[(&str, &[u8])], headers: &HeaderMap) -> Bytes {
+    let mut block = BytesMut::new();
+    qpack::encode_prefix(&mut block);
+    for &(name, value) in pseudo {
+        qpack::encode_field(name.as_bytes(), value, &mut block);
+    }
+    for (name, value) in headers {
+        qpack::encode_field(name.as_str().as_bytes(), value.as_bytes(), &mut block);
+    }
+
+    let mut dst = BytesMut::with_capacity(block.len() + 9);
+    frame::encode_frame_head(frame::HEADERS, block.len(), &mut dst);
+    dst.put(block);
+    dst.freeze()
+}
+
+/// The fields of a received HEADERS frame.
+#[derive(Default)]
+pub(super) struct Fields {
+    pub(super) method: Option<Bytes>,
+    pub(super) scheme: Option<Bytes>,
+    pub(super) authority: Option<Bytes>,
+    pub(super) path: Option<Bytes>,
+    pub(super) protocol: Option<Bytes>,
+    pub(super) status: Option<Bytes>,
+    pub(super) headers: HeaderMap,
+}
+
+impl Fields {
+    pub(super) fn decode(block: &Bytes, max_size: u64) -> Result<Fields, Code> {
+        let mut fields = Fields::default();
+        let mut regular = false;
+        qpack::decode(block, max_size, |name, value| {
+            if name.first() == Some(&b':') {
+                // pseudo-header fields come first, and only once each
+                let slot = match &name[..] {
+                    b":method" => &mut fields.method,
+                    b":scheme" => &mut fields.scheme,
+                    b":authority" => &mut fields.authority,
+                    b":path" => &mut fields.path,
+                    b":protocol" => &mut fields.protocol,
+                    b":status" => &mut fields.status,
+                    _ => return Err(Code::MESSAGE_ERROR),
+                };
+                if regular || slot.replace(value).is_some() {
+                    return Err(Code::MESSAGE_ERROR);
+                }
+                return Ok(());
+            }
+            regular = true;
+
+            if name.iter().any(u8::is_ascii_uppercase) {
+                return Err(Code::MESSAGE_ERROR);
+            }
+            let name = HeaderName::from_bytes(&name).map_err(|_| Code::MESSAGE_ERROR)?;
+            let value = HeaderValue::from_maybe_shared(value).map_err(|_| Code::MESSAGE_ERROR)?;
+            if is_connection_header(&name, &value) {
+                return Err(Code::MESSAGE_ERROR);
+            }
+            fields.headers.append(name, value);
+            Ok(())
+        })?;
+        Ok(fields)
+    }
+
+    pub(super) fn has_pseudo(&self) -> bool {
+        self.method.is_some()
+            || self.scheme.is_some()
+            || self.authority.is_some()
+            || self.path.is_some()
+            || self.protocol.is_some()
+            || self.status.is_some()
+    }
+}
+
+/// Whether a received header is specific to a connection, which makes the
+/// message malformed.
+fn is_connection_header(name: &HeaderName, value: &HeaderValue) -> bool {
+    match *name {
+        CONNECTION | TRANSFER_ENCODING | UPGRADE => true,
+        TE => value != "trailers",
+        _ => name == "keep-alive" || name == "proxy-connection",
+    }
+}
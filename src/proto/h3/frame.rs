@@ -0,0 +1,382 @@
+//! HTTP/3 frames, and the variable-length integers they are made of.
+
+use std::cmp;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::{Code, Config};
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Pin, Poll};
+use crate::rt::quic::RecvStream;
+
+pub(super) const DATA: u64 = 0x0;
+pub(super) const HEADERS: u64 = 0x1;
+const CANCEL_PUSH: u64 = 0x3;
+pub(super) const SETTINGS: u64 = 0x4;
+const PUSH_PROMISE: u64 = 0x5;
+pub(super) const GOAWAY: u64 = 0x7;
+const MAX_PUSH_ID: u64 = 0xd;
+// draft-ietf-webtrans-http3-02, Section 4.2
+const WEBTRANSPORT_STREAM: u64 = 0x41;
+
+const SETTINGS_MAX_FIELD_SECTION_SIZE: u64 = 0x6;
+// RFC 9220, Section 3
+const SETTINGS_ENABLE_CONNECT_PROTOCOL: u64 = 0x8;
+// RFC 9297, Section 2.1.1
+const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+// draft-ietf-webtrans-http3-02, Section 3.1
+const SETTINGS_ENABLE_WEBTRANSPORT: u64 = 0x2b60_3742;
+
+/// The largest value a variable-length integer can hold.
+pub(super) const VARINT_MAX: u64 = (1 << 62) - 1;
+
+/// Decode a variable-length integer, returning it and its length, or `None`
+/// if `src` is too short.
+pub(super) fn decode_varint(src: &[u8]) -> Option<(u64, usize)> {
+    let first = *src.first()?;
+    let len = 1 << (first >> 6);
+    if src.len() < len {
+        return None;
+    }
+    let mut value = u64::from(first & 0x3f);
+    for &byte in &src[1..len] {
+        value = (value << 8) | u64::from(byte);
+    }
+    Some((value, len))
+}
+
+pub(super) fn encode_varint(value: u64, dst: &mut BytesMut) {
+    debug_assert!(value <= VARINT_MAX, "varint too large: {}", value);
+    if value < 1 << 6 {
+        dst.put_u8(value as u8);
+    } else if value < 1 << 14 {
+        dst.put_u16(0x4000 | value as u16);
+    } else if value < 1 << 30 {
+        dst.put_u32(0x8000_0000 | value as u32);
+    } else {
+        dst.put_u64(0xc000_0000_0000_0000 | value);
+    }
+}
+
+pub(super) fn encode_frame_head(ty: u64, len: usize, dst: &mut BytesMut) {
+    encode_varint(ty, dst);
+    encode_varint(len as u64, dst);
+}
+
+/// Encode the SETTINGS frame sent at the start of a control stream.
+///
+/// The QPACK dynamic table capacity is left at its default of zero, so the
+/// peer can't use one.
+pub(super) fn encode_settings(config: &Config, is_server: bool, dst: &mut BytesMut) {
+    let mut payload = BytesMut::new();
+    encode_varint(SETTINGS_MAX_FIELD_SECTION_SIZE, &mut payload);
+    encode_varint(config.max_field_section_size, &mut payload);
+    // Only servers receive extended CONNECT requests.
+    if is_server && config.enable_connect_protocol {
+        encode_varint(SETTINGS_ENABLE_CONNECT_PROTOCOL, &mut payload);
+        encode_varint(1, &mut payload);
+    }
+    if config.enable_datagrams {
+        encode_varint(SETTINGS_H3_DATAGRAM, &mut payload);
+        encode_varint(1, &mut payload);
+    }
+    if config.enable_webtransport {
+        encode_varint(SETTINGS_ENABLE_WEBTRANSPORT, &mut payload);
+        encode_varint(1, &mut payload);
+    }
+    encode_frame_head(SETTINGS, payload.len(), dst);
+    dst.put(payload);
+}
+
+#[cfg(feature = "server")]
+pub(super) fn encode_goaway(id: u64, dst: &mut BytesMut) {
+    let mut payload = BytesMut::new();
+    encode_varint(id, &mut payload);
+    encode_frame_head(GOAWAY, payload.len(), dst);
+    dst.put(payload);
+}
+
+/// The settings received from the peer that hyper acts on.
+///
+/// The others don't change what hyper sends, since it never uses a dynamic
+/// table, and can't know how large a field section is before encoding it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) struct PeerSettings {
+    pub(super) enable_connect_protocol: bool,
+    pub(super) enable_webtransport: bool,
+    pub(super) enable_datagrams: bool,
+}
+
+/// Encode the start of a bidirectional WebTransport stream.
+pub(super) fn encode_webtransport_stream(session_id: u64, dst: &mut BytesMut) {
+    encode_varint(WEBTRANSPORT_STREAM, dst);
+    encode_varint(session_id, dst);
+}
+
+/// Check the payload of a received SETTINGS frame.
+pub(super) fn check_settings(mut payload: &[u8]) -> Result<PeerSettings, Code> {
+    let mut settings = PeerSettings::default();
+    let mut seen = Vec::new();
+    while !payload.is_empty() {
+        let (id, n) = decode_varint(payload).ok_or(Code::FRAME_ERROR)?;
+        payload = &payload[n..];
+        let (value, n) = decode_varint(payload).ok_or(Code::FRAME_ERROR)?;
+        payload = &payload[n..];
+
+        match id {
+            // identifiers of HTTP/2 settings without an HTTP/3 equivalent
+            0x0 | 0x2..=0x5 => return Err(Code::SETTINGS_ERROR),
+            SETTINGS_ENABLE_CONNECT_PROTOCOL => {
+                settings.enable_connect_protocol = decode_bool(value)?;
+            }
+            SETTINGS_H3_DATAGRAM => settings.enable_datagrams = decode_bool(value)?,
+            SETTINGS_ENABLE_WEBTRANSPORT => settings.enable_webtransport = decode_bool(value)?,
+            _ => (),
+        }
+        if seen.contains(&id) {
+            return Err(Code::SETTINGS_ERROR);
+        }
+        seen.push(id);
+    }
+    Ok(settings)
+}
+
+fn decode_bool(value: u64) -> Result<bool, Code> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Code::SETTINGS_ERROR),
+    }
+}
+
+/// A frame received on a stream.
+#[derive(Debug)]
+pub(super) enum Frame {
+    /// Part of the payload of a DATA frame.
+    Data(Bytes),
+    Headers(Bytes),
+    Settings(Bytes),
+    GoAway(u64),
+    /// A frame for server push, which hyper never enables.
+    Push,
+    /// The start of a WebTransport stream, with the ID of its session. The
+    /// rest of the stream isn't framed.
+    WebTransport(u64),
+}
+
+/// Reads the frames of a stream.
+///
+/// The payload of DATA frames is passed on as it is received, while other
+/// frames are buffered whole, up to a limit. Frames of unknown types are
+/// skipped.
+pub(super) struct FrameStream<R> {
+    stream: R,
+    buf: Bytes,
+    /// How much of the current DATA frame, or of a skipped frame, is left.
+    remaining: u64,
+    skipping: bool,
+    eof: bool,
+    max_frame_size: u64,
+    metrics: MetricsSink,
+}
+
+impl<R: RecvStream + Unpin> FrameStream<R> {
+    pub(super) fn new(stream: R, buf: Bytes, max_frame_size: u64, metrics: MetricsSink) -> Self {
+        FrameStream {
+            stream,
+            buf,
+            remaining: 0,
+            skipping: false,
+            eof: false,
+            max_frame_size,
+            metrics,
+        }
+    }
+
+    pub(super) fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+
+    /// The stream, and what was received on it but not yet passed on.
+    pub(super) fn into_parts(self) -> (R, Bytes) {
+        (self.stream, self.buf)
+    }
+
+    /// Receive the next frame, or `None` once the stream is finished.
+    pub(super) fn poll_frame(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<crate::Result<Option<Frame>>> {
+        loop {
+            if self.remaining > 0 {
+                if self.buf.is_empty() && !ready!(self.poll_fill(cx))? {
+                    return Poll::Ready(Err(crate::Error::new_h3(Code::FRAME_ERROR)));
+                }
+                let n = cmp::min(self.buf.len() as u64, self.remaining) as usize;
+                self.remaining -= n as u64;
+                let payload = self.buf.split_to(n);
+                if self.skipping {
+                    continue;
+                }
+                return Poll::Ready(Ok(Some(Frame::Data(payload))));
+            }
+
+            let (ty, len, head_len) = match decode_frame_head(&self.buf) {
+                Some(head) => head,
+                None => {
+                    if !ready!(self.poll_fill(cx))? {
+                        return Poll::Ready(if self.buf.is_empty() {
+                            Ok(None)
+                        } else {
+                            Err(crate::Error::new_h3(Code::FRAME_ERROR))
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            match ty {
+                DATA => {
+                    self.buf.advance(head_len);
+                    self.remaining = len;
+                    self.skipping = false;
+                }
+                HEADERS | SETTINGS | GOAWAY | CANCEL_PUSH | PUSH_PROMISE | MAX_PUSH_ID => {
+                    if len > self.max_frame_size {
+                        return Poll::Ready(Err(crate::Error::new_h3(Code::EXCESSIVE_LOAD)));
+                    }
+                    let end = head_len + len as usize;
+                    if self.buf.len() < end {
+                        if !ready!(self.poll_fill(cx))? {
+                            return Poll::Ready(Err(crate::Error::new_h3(Code::FRAME_ERROR)));
+                        }
+                        continue;
+                    }
+                    let payload = self.buf.slice(head_len..end);
+                    self.buf.advance(end);
+                    let frame = match ty {
+                        HEADERS => Frame::Headers(payload),
+                        SETTINGS => Frame::Settings(payload),
+                        GOAWAY => match decode_varint(&payload) {
+                            Some((id, n)) if n == payload.len() => Frame::GoAway(id),
+                            _ => return Poll::Ready(Err(crate::Error::new_h3(Code::FRAME_ERROR))),
+                        },
+                        _ => Frame::Push,
+                    };
+                    return Poll::Ready(Ok(Some(frame)));
+                }
+                WEBTRANSPORT_STREAM => {
+                    // in place of a length, the session ID
+                    self.buf.advance(head_len);
+                    return Poll::Ready(Ok(Some(Frame::WebTransport(len))));
+                }
+                // types of HTTP/2 frames without an HTTP/3 equivalent
+                0x2 | 0x6 | 0x8 | 0x9 => {
+                    return Poll::Ready(Err(crate::Error::new_h3(Code::FRAME_UNEXPECTED)));
+                }
+                _ => {
+                    trace!("skipping unknown frame type {:#x}", ty);
+                    self.buf.advance(head_len);
+                    self.remaining = len;
+                    self.skipping = true;
+                }
+            }
+        }
+    }
+
+    /// Receive more bytes into the buffer, returning `false` at the end of
+    /// the stream.
+    fn poll_fill(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<bool>> {
+        if self.eof {
+            return Poll::Ready(Ok(false));
+        }
+        match ready!(Pin::new(&mut self.stream).poll_recv(cx)).map_err(crate::Error::new_io)? {
+            Some(chunk) => {
+                self.metrics.bytes_read(chunk.len());
+                if self.buf.is_empty() {
+                    self.buf = chunk;
+                } else {
+                    // Only frames other than DATA span chunks, which are
+                    // small, so copying is fine.
+                    let mut buf = BytesMut::with_capacity(self.buf.len() + chunk.len());
+                    buf.put(&mut self.buf);
+                    buf.put(chunk);
+                    self.buf = buf.freeze();
+                }
+                Poll::Ready(Ok(true))
+            }
+            None => {
+                self.eof = true;
+                Poll::Ready(Ok(false))
+            }
+        }
+    }
+}
+
+fn decode_frame_head(src: &[u8]) -> Option<(u64, u64, usize)> {
+    let (ty, n) = decode_varint(src)?;
+    let (len, m) = decode_varint(&src[n..])?;
+    Some((ty, len, n + m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_round_trip() {
+        for &(value, len) in &[
+            (0, 1),
+            (63, 1),
+            (64, 2),
+            (16_383, 2),
+            (16_384, 4),
+            (1_073_741_823, 4),
+            (1_073_741_824, 8),
+            (VARINT_MAX, 8),
+        ] {
+            let mut buf = BytesMut::new();
+            encode_varint(value, &mut buf);
+            assert_eq!(buf.len(), len, "length of {}", value);
+            assert_eq!(decode_varint(&buf), Some((value, len)));
+            assert_eq!(decode_varint(&buf[..len - 1]), None);
+        }
+
+        // RFC 9000, Appendix A.1
+        let src = [0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c];
+        assert_eq!(decode_varint(&src), Some((151_288_809_941_952_652, 8)));
+        assert_eq!(decode_varint(&[0x40, 0x25]), Some((37, 2)));
+    }
+
+    #[test]
+    fn check_settings_errors() {
+        let mut buf = BytesMut::new();
+        let config = Config {
+            enable_connect_protocol: true,
+            enable_datagrams: true,
+            ..Config::default()
+        };
+        encode_settings(&config, true, &mut buf);
+        // skip the frame head
+        let settings = check_settings(&buf[2..]).unwrap();
+        assert!(settings.enable_connect_protocol);
+        assert!(settings.enable_datagrams);
+        assert!(!settings.enable_webtransport);
+
+        // HTTP/2's SETTINGS_MAX_CONCURRENT_STREAMS
+        assert_eq!(check_settings(&[0x3, 0x1]), Err(Code::SETTINGS_ERROR));
+        // duplicate
+        assert_eq!(
+            check_settings(&[0x6, 0x1, 0x6, 0x2]),
+            Err(Code::SETTINGS_ERROR)
+        );
+        // truncated
+        assert_eq!(check_settings(&[0x6]), Err(Code::FRAME_ERROR));
+        // unknown settings are ignored
+        check_settings(&[0x21, 0x1]).unwrap();
+        // SETTINGS_ENABLE_CONNECT_PROTOCOL is a boolean
+        assert_eq!(check_settings(&[0x8, 0x2]), Err(Code::SETTINGS_ERROR));
+        // and so is SETTINGS_H3_DATAGRAM
+        assert_eq!(check_settings(&[0x33, 0x2]), Err(Code::SETTINGS_ERROR));
+    }
+}
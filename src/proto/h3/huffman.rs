@@ -0,0 +1,357 @@
+//! Decoding of the Huffman code for string literals shared by HPACK and
+//! QPACK, from RFC 7541, Appendix B.
+
+/// Decode a Huffman encoded string.
+pub(super) fn decode(src: &[u8]) -> Result<Vec<u8>, ()> {
+    // Huffman codes are at least 5 bits long, so decoding at most grows
+    // the string by 8/5.
+    let mut dst = Vec::with_capacity(src.len() * 8 / 5);
+    let mut bits: u64 = 0;
+    let mut len: u32 = 0;
+    let mut src = src.iter();
+
+    loop {
+        while len <= 32 {
+            match src.next() {
+                Some(&byte) => {
+                    bits = (bits << 8) | u64::from(byte);
+                    len += 8;
+                }
+                None => break,
+            }
+        }
+        if len == 0 {
+            return Ok(dst);
+        }
+
+        // The next 32 bits, padded with ones, like the EOS code used as
+        // padding at the end of a string.
+        let window = if len >= 32 {
+            (bits >> (len - 32)) as u32
+        } else {
+            ((bits << (32 - len)) as u32) | (u32::MAX >> len)
+        };
+        // The codes tile the whole space of 32 bit values, so the code
+        // of the next symbol is the greatest one not above the window.
+        let (_, code_len, sym) = DECODE_TABLE[DECODE_TABLE.partition_point(|e| e.0 <= window) - 1];
+        let code_len = u32::from(code_len);
+
+        if code_len > len {
+            // Only padding is left, which must be shorter than a byte,
+            // and all ones.
+            return if len < 8 && bits == (1 << len) - 1 {
+                Ok(dst)
+            } else {
+                Err(())
+            };
+        }
+        if sym == 256 {
+            // EOS in the string
+            return Err(());
+        }
+        dst.push(sym as u8);
+        len -= code_len;
+        bits &= (1 << len) - 1;
+    }
+}
+
+// (code, length, symbol) for each code, with the code left-aligned in 32
+// bits, sorted by code. Symbol 256 is EOS.
+const DECODE_TABLE: [(u32, u8, u16); 257] = [
+    (0x0000_0000, 5, 48),
+    (0x0800_0000, 5, 49),
+    (0x1000_0000, 5, 50),
+    (0x1800_0000, 5, 97),
+    (0x2000_0000, 5, 99),
+    (0x2800_0000, 5, 101),
+    (0x3000_0000, 5, 105),
+    (0x3800_0000, 5, 111),
+    (0x4000_0000, 5, 115),
+    (0x4800_0000, 5, 116),
+    (0x5000_0000, 6, 32),
+    (0x5400_0000, 6, 37),
+    (0x5800_0000, 6, 45),
+    (0x5c00_0000, 6, 46),
+    (0x6000_0000, 6, 47),
+    (0x6400_0000, 6, 51),
+    (0x6800_0000, 6, 52),
+    (0x6c00_0000, 6, 53),
+    (0x7000_0000, 6, 54),
+    (0x7400_0000, 6, 55),
+    (0x7800_0000, 6, 56),
+    (0x7c00_0000, 6, 57),
+    (0x8000_0000, 6, 61),
+    (0x8400_0000, 6, 65),
+    (0x8800_0000, 6, 95),
+    (0x8c00_0000, 6, 98),
+    (0x9000_0000, 6, 100),
+    (0x9400_0000, 6, 102),
+    (0x9800_0000, 6, 103),
+    (0x9c00_0000, 6, 104),
+    (0xa000_0000, 6, 108),
+    (0xa400_0000, 6, 109),
+    (0xa800_0000, 6, 110),
+    (0xac00_0000, 6, 112),
+    (0xb000_0000, 6, 114),
+    (0xb400_0000, 6, 117),
+    (0xb800_0000, 7, 58),
+    (0xba00_0000, 7, 66),
+    (0xbc00_0000, 7, 67),
+    (0xbe00_0000, 7, 68),
+    (0xc000_0000, 7, 69),
+    (0xc200_0000, 7, 70),
+    (0xc400_0000, 7, 71),
+    (0xc600_0000, 7, 72),
+    (0xc800_0000, 7, 73),
+    (0xca00_0000, 7, 74),
+    (0xcc00_0000, 7, 75),
+    (0xce00_0000, 7, 76),
+    (0xd000_0000, 7, 77),
+    (0xd200_0000, 7, 78),
+    (0xd400_0000, 7, 79),
+    (0xd600_0000, 7, 80),
+    (0xd800_0000, 7, 81),
+    (0xda00_0000, 7, 82),
+    (0xdc00_0000, 7, 83),
+    (0xde00_0000, 7, 84),
+    (0xe000_0000, 7, 85),
+    (0xe200_0000, 7, 86),
+    (0xe400_0000, 7, 87),
+    (0xe600_0000, 7, 89),
+    (0xe800_0000, 7, 106),
+    (0xea00_0000, 7, 107),
+    (0xec00_0000, 7, 113),
+    (0xee00_0000, 7, 118),
+    (0xf000_0000, 7, 119),
+    (0xf200_0000, 7, 120),
+    (0xf400_0000, 7, 121),
+    (0xf600_0000, 7, 122),
+    (0xf800_0000, 8, 38),
+    (0xf900_0000, 8, 42),
+    (0xfa00_0000, 8, 44),
+    (0xfb00_0000, 8, 59),
+    (0xfc00_0000, 8, 88),
+    (0xfd00_0000, 8, 90),
+    (0xfe00_0000, 10, 33),
+    (0xfe40_0000, 10, 34),
+    (0xfe80_0000, 10, 40),
+    (0xfec0_0000, 10, 41),
+    (0xff00_0000, 10, 63),
+    (0xff40_0000, 11, 39),
+    (0xff60_0000, 11, 43),
+    (0xff80_0000, 11, 124),
+    (0xffa0_0000, 12, 35),
+    (0xffb0_0000, 12, 62),
+    (0xffc0_0000, 13, 0),
+    (0xffc8_0000, 13, 36),
+    (0xffd0_0000, 13, 64),
+    (0xffd8_0000, 13, 91),
+    (0xffe0_0000, 13, 93),
+    (0xffe8_0000, 13, 126),
+    (0xfff0_0000, 14, 94),
+    (0xfff4_0000, 14, 125),
+    (0xfff8_0000, 15, 60),
+    (0xfffa_0000, 15, 96),
+    (0xfffc_0000, 15, 123),
+    (0xfffe_0000, 19, 92),
+    (0xfffe_2000, 19, 195),
+    (0xfffe_4000, 19, 208),
+    (0xfffe_6000, 20, 128),
+    (0xfffe_7000, 20, 130),
+    (0xfffe_8000, 20, 131),
+    (0xfffe_9000, 20, 162),
+    (0xfffe_a000, 20, 184),
+    (0xfffe_b000, 20, 194),
+    (0xfffe_c000, 20, 224),
+    (0xfffe_d000, 20, 226),
+    (0xfffe_e000, 21, 153),
+    (0xfffe_e800, 21, 161),
+    (0xfffe_f000, 21, 167),
+    (0xfffe_f800, 21, 172),
+    (0xffff_0000, 21, 176),
+    (0xffff_0800, 21, 177),
+    (0xffff_1000, 21, 179),
+    (0xffff_1800, 21, 209),
+    (0xffff_2000, 21, 216),
+    (0xffff_2800, 21, 217),
+    (0xffff_3000, 21, 227),
+    (0xffff_3800, 21, 229),
+    (0xffff_4000, 21, 230),
+    (0xffff_4800, 22, 129),
+    (0xffff_4c00, 22, 132),
+    (0xffff_5000, 22, 133),
+    (0xffff_5400, 22, 134),
+    (0xffff_5800, 22, 136),
+    (0xffff_5c00, 22, 146),
+    (0xffff_6000, 22, 154),
+    (0xffff_6400, 22, 156),
+    (0xffff_6800, 22, 160),
+    (0xffff_6c00, 22, 163),
+    (0xffff_7000, 22, 164),
+    (0xffff_7400, 22, 169),
+    (0xffff_7800, 22, 170),
+    (0xffff_7c00, 22, 173),
+    (0xffff_8000, 22, 178),
+    (0xffff_8400, 22, 181),
+    (0xffff_8800, 22, 185),
+    (0xffff_8c00, 22, 186),
+    (0xffff_9000, 22, 187),
+    (0xffff_9400, 22, 189),
+    (0xffff_9800, 22, 190),
+    (0xffff_9c00, 22, 196),
+    (0xffff_a000, 22, 198),
+    (0xffff_a400, 22, 228),
+    (0xffff_a800, 22, 232),
+    (0xffff_ac00, 22, 233),
+    (0xffff_b000, 23, 1),
+    (0xffff_b200, 23, 135),
+    (0xffff_b400, 23, 137),
+    (0xffff_b600, 23, 138),
+    (0xffff_b800, 23, 139),
+    (0xffff_ba00, 23, 140),
+    (0xffff_bc00, 23, 141),
+    (0xffff_be00, 23, 143),
+    (0xffff_c000, 23, 147),
+    (0xffff_c200, 23, 149),
+    (0xffff_c400, 23, 150),
+    (0xffff_c600, 23, 151),
+    (0xffff_c800, 23, 152),
+    (0xffff_ca00, 23, 155),
+    (0xffff_cc00, 23, 157),
+    (0xffff_ce00, 23, 158),
+    (0xffff_d000, 23, 165),
+    (0xffff_d200, 23, 166),
+    (0xffff_d400, 23, 168),
+    (0xffff_d600, 23, 174),
+    (0xffff_d800, 23, 175),
+    (0xffff_da00, 23, 180),
+    (0xffff_dc00, 23, 182),
+    (0xffff_de00, 23, 183),
+    (0xffff_e000, 23, 188),
+    (0xffff_e200, 23, 191),
+    (0xffff_e400, 23, 197),
+    (0xffff_e600, 23, 231),
+    (0xffff_e800, 23, 239),
+    (0xffff_ea00, 24, 9),
+    (0xffff_eb00, 24, 142),
+    (0xffff_ec00, 24, 144),
+    (0xffff_ed00, 24, 145),
+    (0xffff_ee00, 24, 148),
+    (0xffff_ef00, 24, 159),
+    (0xffff_f000, 24, 171),
+    (0xffff_f100, 24, 206),
+    (0xffff_f200, 24, 215),
+    (0xffff_f300, 24, 225),
+    (0xffff_f400, 24, 236),
+    (0xffff_f500, 24, 237),
+    (0xffff_f600, 25, 199),
+    (0xffff_f680, 25, 207),
+    (0xffff_f700, 25, 234),
+    (0xffff_f780, 25, 235),
+    (0xffff_f800, 26, 192),
+    (0xffff_f840, 26, 193),
+    (0xffff_f880, 26, 200),
+    (0xffff_f8c0, 26, 201),
+    (0xffff_f900, 26, 202),
+    (0xffff_f940, 26, 205),
+    (0xffff_f980, 26, 210),
+    (0xffff_f9c0, 26, 213),
+    (0xffff_fa00, 26, 218),
+    (0xffff_fa40, 26, 219),
+    (0xffff_fa80, 26, 238),
+    (0xffff_fac0, 26, 240),
+    (0xffff_fb00, 26, 242),
+    (0xffff_fb40, 26, 243),
+    (0xffff_fb80, 26, 255),
+    (0xffff_fbc0, 27, 203),
+    (0xffff_fbe0, 27, 204),
+    (0xffff_fc00, 27, 211),
+    (0xffff_fc20, 27, 212),
+    (0xffff_fc40, 27, 214),
+    (0xffff_fc60, 27, 221),
+    (0xffff_fc80, 27, 222),
+    (0xffff_fca0, 27, 223),
+    (0xffff_fcc0, 27, 241),
+    (0xffff_fce0, 27, 244),
+    (0xffff_fd00, 27, 245),
+    (0xffff_fd20, 27, 246),
+    (0xffff_fd40, 27, 247),
+    (0xffff_fd60, 27, 248),
+    (0xffff_fd80, 27, 250),
+    (0xffff_fda0, 27, 251),
+    (0xffff_fdc0, 27, 252),
+    (0xffff_fde0, 27, 253),
+    (0xffff_fe00, 27, 254),
+    (0xffff_fe20, 28, 2),
+    (0xffff_fe30, 28, 3),
+    (0xffff_fe40, 28, 4),
+    (0xffff_fe50, 28, 5),
+    (0xffff_fe60, 28, 6),
+    (0xffff_fe70, 28, 7),
+    (0xffff_fe80, 28, 8),
+    (0xffff_fe90, 28, 11),
+    (0xffff_fea0, 28, 12),
+    (0xffff_feb0, 28, 14),
+    (0xffff_fec0, 28, 15),
+    (0xffff_fed0, 28, 16),
+    (0xffff_fee0, 28, 17),
+    (0xffff_fef0, 28, 18),
+    (0xffff_ff00, 28, 19),
+    (0xffff_ff10, 28, 20),
+    (0xffff_ff20, 28, 21),
+    (0xffff_ff30, 28, 23),
+    (0xffff_ff40, 28, 24),
+    (0xffff_ff50, 28, 25),
+    (0xffff_ff60, 28, 26),
+    (0xffff_ff70, 28, 27),
+    (0xffff_ff80, 28, 28),
+    (0xffff_ff90, 28, 29),
+    (0xffff_ffa0, 28, 30),
+    (0xffff_ffb0, 28, 31),
+    (0xffff_ffc0, 28, 127),
+    (0xffff_ffd0, 28, 220),
+    (0xffff_ffe0, 28, 249),
+    (0xffff_fff0, 30, 10),
+    (0xffff_fff4, 30, 13),
+    (0xffff_fff8, 30, 22),
+    (0xffff_fffc, 30, 256),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn decode_rfc7541_examples() {
+        // C.4.1
+        let src = [
+            0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff,
+        ];
+        assert_eq!(decode(&src).unwrap(), b"www.example.com");
+
+        // C.4.2
+        assert_eq!(
+            decode(&[0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf]).unwrap(),
+            b"no-cache"
+        );
+
+        // C.6.1
+        let src = [
+            0xd0, 0x7a, 0xbe, 0x94, 0x10, 0x54, 0xd4, 0x44, 0xa8, 0x20, 0x05, 0x95, 0x04, 0x0b,
+            0x81, 0x66, 0xe0, 0x82, 0xa6, 0x2d, 0x1b, 0xff,
+        ];
+        assert_eq!(decode(&src).unwrap(), b"Mon, 21 Oct 2013 20:13:21 GMT");
+
+        assert_eq!(decode(&[]).unwrap(), b"");
+    }
+
+    #[test]
+    fn decode_rejects_bad_padding() {
+        // "0" is 00000, then padding that isn't all ones
+        decode(&[0b0000_0110]).unwrap_err();
+        // a whole byte of padding
+        decode(&[0x07, 0xff]).unwrap_err();
+        // EOS
+        decode(&[0xff, 0xff, 0xff, 0xff]).unwrap_err();
+    }
+}
@@ -0,0 +1,715 @@
+//! HTTP/3, over a QUIC connection implementing the traits in `rt::quic`.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::task::AtomicWaker;
+use pin_project_lite::pin_project;
+
+use self::fields::{encode_headers, Fields};
+use self::frame::{Frame, FrameStream, PeerSettings};
+use crate::body::{Body, DecodedLength, Incoming, Sender};
+#[cfg(feature = "server")]
+use crate::common::date::DateHeader;
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Pin, Poll};
+use crate::rt::quic::{Connection, RecvStream, SendStream};
+
+mod datagram;
+mod fields;
+mod frame;
+mod huffman;
+mod qpack;
+mod upgrade;
+mod webtransport;
+
+pub(crate) use self::webtransport::OpenStream;
+
+cfg_client! {
+    pub(crate) mod client;
+    pub(crate) use self::client::ClientTask;
+}
+
+cfg_server! {
+    pub(crate) mod server;
+    pub(crate) use self::server::Server;
+}
+
+// 16 MB, the same "sane default" as HTTP/2's SETTINGS_MAX_HEADER_LIST_SIZE
+const DEFAULT_MAX_FIELD_SECTION_SIZE: u64 = 16 << 20;
+
+// Types of unidirectional streams.
+const CONTROL_STREAM: u64 = 0x0;
+const PUSH_STREAM: u64 = 0x1;
+const ENCODER_STREAM: u64 = 0x2;
+const DECODER_STREAM: u64 = 0x3;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) max_field_section_size: u64,
+    pub(crate) enable_connect_protocol: bool,
+    pub(crate) enable_webtransport: bool,
+    pub(crate) enable_datagrams: bool,
+    #[cfg(feature = "server")]
+    pub(crate) date_header: DateHeader,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_field_section_size: DEFAULT_MAX_FIELD_SECTION_SIZE,
+            enable_connect_protocol: false,
+            enable_webtransport: false,
+            enable_datagrams: false,
+            #[cfg(feature = "server")]
+            date_header: DateHeader::default(),
+        }
+    }
+}
+
+/// An HTTP/3 error code, used to close connections and reset streams.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Code(u64);
+
+macro_rules! codes {
+    ($($name:ident = $value:expr;)*) => {
+        impl Code {
+            $(
+                pub(crate) const $name: Code = Code($value);
+            )*
+
+            fn name(self) -> Option<&'static str> {
+                match self {
+                    $(Code::$name => Some(stringify!($name)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+// RFC 9114, Section 8.1, RFC 9204, Section 6, RFC 9297, Section 2.1, and
+// the WebTransport over HTTP/3 draft
+codes! {
+    NO_ERROR = 0x100;
+    GENERAL_PROTOCOL_ERROR = 0x101;
+    INTERNAL_ERROR = 0x102;
+    STREAM_CREATION_ERROR = 0x103;
+    CLOSED_CRITICAL_STREAM = 0x104;
+    FRAME_UNEXPECTED = 0x105;
+    FRAME_ERROR = 0x106;
+    EXCESSIVE_LOAD = 0x107;
+    ID_ERROR = 0x108;
+    SETTINGS_ERROR = 0x109;
+    MISSING_SETTINGS = 0x10a;
+    REQUEST_REJECTED = 0x10b;
+    REQUEST_CANCELLED = 0x10c;
+    REQUEST_INCOMPLETE = 0x10d;
+    MESSAGE_ERROR = 0x10e;
+    CONNECT_ERROR = 0x10f;
+    VERSION_FALLBACK = 0x110;
+    QPACK_DECOMPRESSION_FAILED = 0x200;
+    DATAGRAM_ERROR = 0x33;
+    WEBTRANSPORT_BUFFERED_STREAM_REJECTED = 0x3994_bd84;
+    WEBTRANSPORT_SESSION_GONE = 0x170d_7b68;
+}
+
+impl Code {
+    pub(crate) fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Debug for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => f.debug_tuple("Code").field(&self.0).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "HTTP/3 error {}", name),
+            None => write!(f, "unknown HTTP/3 error code {:#x}", self.0),
+        }
+    }
+}
+
+impl StdError for Code {}
+
+/// Send all of `buf` on a stream, advancing it past what was sent.
+fn poll_send_buf<S, T>(
+    stream: &mut S,
+    cx: &mut task::Context<'_>,
+    buf: &mut T,
+    metrics: &MetricsSink,
+) -> Poll<io::Result<()>>
+where
+    S: SendStream + Unpin,
+    T: Buf,
+{
+    while buf.has_remaining() {
+        let n = ready!(Pin::new(&mut *stream).poll_send(cx, buf.chunk()))?;
+        if n == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        metrics.bytes_written(n);
+        buf.advance(n);
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Abruptly end a request stream in both directions.
+fn reset<S>(stream: &mut S, code: Code)
+where
+    S: SendStream + RecvStream + Unpin,
+{
+    SendStream::reset(Pin::new(&mut *stream), code.value());
+    RecvStream::stop_sending(Pin::new(stream), code.value());
+}
+
+pin_project! {
+    /// Sends a message on a stream: the HEADERS frame of its head, the DATA
+    /// frames of its body, and its trailers, before finishing the stream.
+    pub(super) struct SendMessage<B>
+    where
+        B: Body,
+    {
+        // frames encoded but not yet sent
+        head: Bytes,
+        // the chunk of the DATA frame whose head is in `head`
+        data: Option<B::Data>,
+        eos: bool,
+        // whether to finish the stream after the message
+        finish: bool,
+        #[pin]
+        body: B,
+        metrics: MetricsSink,
+    }
+}
+
+impl<B> SendMessage<B>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn new(head: Bytes, body: B, metrics: MetricsSink) -> SendMessage<B> {
+        SendMessage {
+            head,
+            data: None,
+            eos: body.is_end_stream(),
+            finish: true,
+            body,
+            metrics,
+        }
+    }
+
+    /// Send only the head, leaving the stream open, for a CONNECT request
+    /// or a response establishing its tunnel. The body is ignored.
+    fn head_only(head: Bytes, body: B, metrics: MetricsSink) -> SendMessage<B> {
+        SendMessage {
+            head,
+            data: None,
+            eos: true,
+            finish: false,
+            body,
+            metrics,
+        }
+    }
+
+    fn poll_send<S>(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        stream: &mut S,
+    ) -> Poll<crate::Result<()>>
+    where
+        S: SendStream + Unpin,
+    {
+        let mut me = self.project();
+        loop {
+            ready!(poll_send_buf(stream, cx, me.head, me.metrics))
+                .map_err(crate::Error::new_body_write)?;
+            if let Some(ref mut data) = *me.data {
+                ready!(poll_send_buf(stream, cx, data, me.metrics))
+                    .map_err(crate::Error::new_body_write)?;
+                *me.data = None;
+            }
+            if *me.eos {
+                if *me.finish {
+                    trace!("send body eos");
+                    ready!(Pin::new(&mut *stream).poll_finish(cx))
+                        .map_err(crate::Error::new_body_write)?;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            match ready!(me.body.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    if frame.is_data() {
+                        let chunk = frame.into_data().unwrap_or_else(|_| unreachable!());
+                        *me.eos = me.body.is_end_stream();
+                        trace!(
+                            "send body chunk: {} bytes, eos={}",
+                            chunk.remaining(),
+                            *me.eos,
+                        );
+                        if chunk.has_remaining() {
+                            let mut head = BytesMut::with_capacity(16);
+                            frame::encode_frame_head(frame::DATA, chunk.remaining(), &mut head);
+                            *me.head = head.freeze();
+                            *me.data = Some(chunk);
+                        }
+                    } else if frame.is_trailers() {
+                        let trailers = frame.into_trailers().unwrap_or_else(|_| unreachable!());
+                        *me.head = encode_headers(&[], &trailers);
+                        *me.eos = true;
+                    } else {
+                        trace!("discarding unknown frame");
+                    }
+                }
+                Some(Err(e)) => {
+                    let err = crate::Error::new_user_body(e);
+                    debug!("send body user stream error: {}", err);
+                    Pin::new(&mut *stream).reset(err.h3_code().value());
+                    return Poll::Ready(Err(err));
+                }
+                None => *me.eos = true,
+            }
+        }
+    }
+}
+
+/// Passes the rest of a message received on a stream to its `Incoming`
+/// body: the DATA frames, and any trailers.
+pub(super) struct RecvBody {
+    tx: Sender,
+    // what is left of the content-length, if there is one
+    remaining: Option<u64>,
+    trailers: bool,
+    max_field_section_size: u64,
+}
+
+impl RecvBody {
+    fn new(content_length: Option<u64>, max_field_section_size: u64) -> (RecvBody, Incoming) {
+        let (tx, body) = Incoming::new_channel(DecodedLength::from(content_length), false);
+        let recv = RecvBody {
+            tx,
+            remaining: content_length,
+            trailers: false,
+            max_field_section_size,
+        };
+        (recv, body)
+    }
+
+    /// Receive the body, resolving once it's complete, or has failed.
+    ///
+    /// Dropping this afterwards ends the body.
+    fn poll_recv<R>(&mut self, cx: &mut task::Context<'_>, frames: &mut FrameStream<R>) -> Poll<()>
+    where
+        R: RecvStream + Unpin,
+    {
+        loop {
+            if ready!(self.tx.poll_ready(cx)).is_err() {
+                trace!("body receiver dropped, stop sending");
+                Pin::new(frames.get_mut()).stop_sending(Code::NO_ERROR.value());
+                return Poll::Ready(());
+            }
+
+            let code = match ready!(frames.poll_frame(cx)) {
+                Ok(Some(frame)) => match self.recv_frame(frame) {
+                    Ok(()) => continue,
+                    Err(code) => code,
+                },
+                Ok(None) if self.remaining.map_or(true, |n| n == 0) => {
+                    trace!("recv body eos");
+                    return Poll::Ready(());
+                }
+                Ok(None) => Code::MESSAGE_ERROR,
+                Err(err) => {
+                    debug!("recv body error: {}", err);
+                    self.tx.send_error(crate::Error::new_body(err));
+                    return Poll::Ready(());
+                }
+            };
+            debug!("recv body stream error: {:?}", code);
+            Pin::new(frames.get_mut()).stop_sending(code.value());
+            self.tx
+                .send_error(crate::Error::new_body(crate::Error::new_h3(code)));
+            return Poll::Ready(());
+        }
+    }
+
+    fn recv_frame(&mut self, frame: Frame) -> Result<(), Code> {
+        match frame {
+            Frame::Data(_) | Frame::Headers(_) if self.trailers => Err(Code::FRAME_UNEXPECTED),
+            Frame::Data(chunk) => {
+                if let Some(ref mut remaining) = self.remaining {
+                    *remaining = remaining
+                        .checked_sub(chunk.len() as u64)
+                        .ok_or(Code::MESSAGE_ERROR)?;
+                }
+                // The receiver may have been dropped since it was ready.
+                let _ = self.tx.try_send_data(chunk);
+                Ok(())
+            }
+            Frame::Headers(block) => {
+                if self.remaining.map_or(false, |n| n != 0) {
+                    return Err(Code::MESSAGE_ERROR);
+                }
+                let fields = Fields::decode(&block, self.max_field_section_size)?;
+                if fields.has_pseudo() {
+                    return Err(Code::MESSAGE_ERROR);
+                }
+                self.trailers = true;
+                let _ = self.tx.send_trailers(fields.headers);
+                Ok(())
+            }
+            Frame::Settings(_) | Frame::GoAway(_) | Frame::Push | Frame::WebTransport(_) => {
+                Err(Code::FRAME_UNEXPECTED)
+            }
+        }
+    }
+}
+
+/// Counts the streams of a connection that are still in flight, so that
+/// it knows once it's idle.
+#[derive(Clone, Default)]
+struct StreamRefs(Arc<Refs>);
+
+#[derive(Default)]
+struct Refs {
+    count: AtomicUsize,
+    idle: AtomicWaker,
+}
+
+/// Keeps a stream counted as in flight until dropped.
+pub(crate) struct StreamRef(Arc<Refs>);
+
+impl Clone for StreamRef {
+    fn clone(&self) -> StreamRef {
+        self.0.count.fetch_add(1, Ordering::AcqRel);
+        StreamRef(self.0.clone())
+    }
+}
+
+impl StreamRefs {
+    fn track(&self) -> StreamRef {
+        self.0.count.fetch_add(1, Ordering::AcqRel);
+        StreamRef(self.0.clone())
+    }
+
+    fn poll_idle(&self, cx: &mut task::Context<'_>) -> Poll<()> {
+        self.0.idle.register(cx.waker());
+        if self.0.count.load(Ordering::Acquire) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for StreamRef {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.wake();
+        }
+    }
+}
+
+/// The state of a connection outside of its request streams: the control
+/// streams in both directions, and the other unidirectional streams opened
+/// by the peer.
+struct Control<C>
+where
+    C: Connection,
+{
+    conn: C,
+    is_server: bool,
+    // whether bidirectional streams the peer opens are refused here, as
+    // only clients can open them without WebTransport
+    refuse_bidi: bool,
+    // our control stream, once opened
+    send: Option<C::SendStream>,
+    // frames waiting to be sent on our control stream
+    send_buf: BytesMut,
+    // the peer's control stream, once known
+    recv: Option<FrameStream<C::RecvStream>>,
+    // the peer's settings, once received
+    settings: Option<PeerSettings>,
+    // streams whose type hasn't been received yet
+    untyped: Vec<(C::RecvStream, BytesMut)>,
+    // The peer's QPACK encoder and decoder streams. Since hyper never lets
+    // the peer use a dynamic table, they are only read to be discarded.
+    qpack: Vec<(u64, C::RecvStream)>,
+    // the ID of the last GOAWAY frame received
+    goaway: Option<u64>,
+    // once datagrams are enabled
+    datagrams: Option<datagram::Router>,
+    closed: bool,
+    max_field_section_size: u64,
+    metrics: MetricsSink,
+}
+
+impl<C> Control<C>
+where
+    C: Connection + Unpin,
+{
+    fn new(conn: C, is_server: bool, config: &Config, metrics: MetricsSink) -> Control<C> {
+        let mut send_buf = BytesMut::new();
+        frame::encode_varint(CONTROL_STREAM, &mut send_buf);
+        frame::encode_settings(config, is_server, &mut send_buf);
+        Control {
+            conn,
+            is_server,
+            refuse_bidi: !is_server && !config.enable_webtransport,
+            send: None,
+            send_buf,
+            recv: None,
+            settings: None,
+            untyped: Vec::new(),
+            qpack: Vec::new(),
+            goaway: None,
+            datagrams: if config.enable_datagrams {
+                Some(datagram::Router::new())
+            } else {
+                None
+            },
+            closed: false,
+            max_field_section_size: config.max_field_section_size,
+            metrics,
+        }
+    }
+
+    fn conn(&mut self) -> Pin<&mut C> {
+        Pin::new(&mut self.conn)
+    }
+
+    /// The peer's settings, once received.
+    #[cfg(feature = "client")]
+    fn settings(&self) -> Option<PeerSettings> {
+        self.settings
+    }
+
+    /// Drive the control streams, resolving once the connection is closed,
+    /// or with a connection error.
+    fn poll(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        if !self.closed {
+            self.poll_send(cx)?;
+            self.poll_accept(cx)?;
+        }
+        // Once closed, the peer's streams ending is no error.
+        if self.closed {
+            return Poll::Ready(Ok(()));
+        }
+        self.poll_untyped(cx)?;
+        self.poll_qpack(cx)?;
+        self.poll_recv(cx)?;
+        if let Some(ref mut datagrams) = self.datagrams {
+            datagrams.poll(cx, Pin::new(&mut self.conn), self.settings)?;
+        }
+        Poll::Pending
+    }
+
+    fn poll_send(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        let stream = match self.send {
+            Some(ref mut stream) => stream,
+            None => match self.conn().poll_open_uni(cx) {
+                Poll::Ready(Ok(stream)) => self.send.get_or_insert(stream),
+                Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+                Poll::Pending => return Ok(()),
+            },
+        };
+        match poll_send_buf(stream, cx, &mut self.send_buf, &self.metrics) {
+            Poll::Ready(Err(e)) => Err(crate::Error::new_io(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn poll_accept(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        if self.refuse_bidi {
+            match self.conn().poll_accept_bidi(cx) {
+                Poll::Ready(Ok(Some(_))) => {
+                    return Err(crate::Error::new_h3(Code::STREAM_CREATION_ERROR))
+                }
+                Poll::Ready(Ok(None)) => self.closed = true,
+                Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+                Poll::Pending => (),
+            }
+        }
+        loop {
+            match self.conn().poll_accept_uni(cx) {
+                Poll::Ready(Ok(Some(stream))) => self.untyped.push((stream, BytesMut::new())),
+                Poll::Ready(Ok(None)) => {
+                    self.closed = true;
+                    return Ok(());
+                }
+                Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+                Poll::Pending => return Ok(()),
+            }
+        }
+    }
+
+    fn poll_untyped(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        let mut i = 0;
+        'streams: while i < self.untyped.len() {
+            let (ref mut stream, ref mut buf) = self.untyped[i];
+            let ty = loop {
+                if let Some((ty, n)) = frame::decode_varint(buf) {
+                    buf.advance(n);
+                    break Some(ty);
+                }
+                match Pin::new(&mut *stream).poll_recv(cx) {
+                    Poll::Ready(Ok(Some(chunk))) => {
+                        self.metrics.bytes_read(chunk.len());
+                        buf.extend_from_slice(&chunk);
+                    }
+                    // ended before its type, so just forget it
+                    Poll::Ready(Ok(None)) | Poll::Ready(Err(_)) => break None,
+                    Poll::Pending => {
+                        i += 1;
+                        continue 'streams;
+                    }
+                }
+            };
+            let (stream, buf) = self.untyped.swap_remove(i);
+            if let Some(ty) = ty {
+                self.accept_typed(ty, stream, buf.freeze())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn accept_typed(
+        &mut self,
+        ty: u64,
+        mut stream: C::RecvStream,
+        buf: Bytes,
+    ) -> crate::Result<()> {
+        match ty {
+            CONTROL_STREAM => {
+                if self.recv.is_some() {
+                    return Err(crate::Error::new_h3(Code::STREAM_CREATION_ERROR));
+                }
+                let frames = FrameStream::new(
+                    stream,
+                    buf,
+                    self.max_field_section_size,
+                    self.metrics.clone(),
+                );
+                self.recv = Some(frames);
+            }
+            // Clients can't push, and servers can't until allowed to.
+            PUSH_STREAM if self.is_server => {
+                return Err(crate::Error::new_h3(Code::STREAM_CREATION_ERROR));
+            }
+            PUSH_STREAM => return Err(crate::Error::new_h3(Code::ID_ERROR)),
+            ENCODER_STREAM | DECODER_STREAM => {
+                if self.qpack.iter().any(|&(other, _)| other == ty) {
+                    return Err(crate::Error::new_h3(Code::STREAM_CREATION_ERROR));
+                }
+                self.qpack.push((ty, stream));
+            }
+            _ => {
+                trace!("stop sending on stream of unknown type {:#x}", ty);
+                Pin::new(&mut stream).stop_sending(Code::STREAM_CREATION_ERROR.value());
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_qpack(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        for &mut (_, ref mut stream) in &mut self.qpack {
+            loop {
+                match Pin::new(&mut *stream).poll_recv(cx) {
+                    Poll::Ready(Ok(Some(chunk))) => self.metrics.bytes_read(chunk.len()),
+                    Poll::Ready(Ok(None)) => {
+                        return Err(crate::Error::new_h3(Code::CLOSED_CRITICAL_STREAM))
+                    }
+                    Poll::Ready(Err(e)) => return Err(crate::Error::new_io(e)),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        let frames = match self.recv {
+            Some(ref mut frames) => frames,
+            None => return Ok(()),
+        };
+        loop {
+            let frame = match frames.poll_frame(cx) {
+                Poll::Ready(Ok(Some(frame))) => frame,
+                Poll::Ready(Ok(None)) => {
+                    return Err(crate::Error::new_h3(Code::CLOSED_CRITICAL_STREAM))
+                }
+                Poll::Ready(Err(e)) => return Err(e),
+                Poll::Pending => return Ok(()),
+            };
+
+            let res = match frame {
+                Frame::Settings(payload) if self.settings.is_none() => {
+                    match frame::check_settings(&payload) {
+                        Ok(settings) => {
+                            trace!("received {:?}", settings);
+                            self.settings = Some(settings);
+                            Ok(())
+                        }
+                        Err(code) => Err(code),
+                    }
+                }
+                _ if self.settings.is_none() => Err(Code::MISSING_SETTINGS),
+                Frame::GoAway(id) => {
+                    // A client's GOAWAY carries a push ID, which means
+                    // nothing to a server that never pushes.
+                    if !self.is_server
+                        && (id % 4 != 0 || self.goaway.map_or(false, |last| id > last))
+                    {
+                        Err(Code::ID_ERROR)
+                    } else {
+                        debug!("received GOAWAY with ID {}", id);
+                        self.goaway = Some(id);
+                        Ok(())
+                    }
+                }
+                // CANCEL_PUSH and MAX_PUSH_ID can be ignored by a server that
+                // never pushes.
+                Frame::Push if self.is_server => Ok(()),
+                _ => Err(Code::FRAME_UNEXPECTED),
+            };
+            res.map_err(crate::Error::new_h3)?;
+        }
+    }
+
+    /// Send a GOAWAY frame, so that the peer stops making requests.
+    #[cfg(feature = "server")]
+    fn goaway(&mut self, id: u64) {
+        frame::encode_goaway(id, &mut self.send_buf);
+    }
+
+    /// Close the connection with the error code of a connection error.
+    fn close(&mut self, err: &crate::Error) {
+        if !self.closed {
+            debug!("connection error: {}", err);
+            self.closed = true;
+            let code = err.h3_code().value();
+            self.conn().close(code, b"");
+        }
+    }
+
+    /// Close the connection without an error.
+    fn close_gracefully(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            self.conn().close(Code::NO_ERROR.value(), b"");
+        }
+    }
+}
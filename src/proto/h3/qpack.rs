@@ -0,0 +1,379 @@
+//! QPACK field compression, limited to the static table.
+//!
+//! hyper advertises a dynamic table capacity of zero, so peers can only
+//! refer to the static table, and never uses a dynamic table itself. That
+//! also means no encoder or decoder streams are needed.
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::huffman;
+use super::Code;
+
+/// Encode the prefix of a field section that refers to no dynamic table
+/// entries.
+pub(super) fn encode_prefix(dst: &mut BytesMut) {
+    // Required Insert Count and Base
+    dst.put_slice(&[0, 0]);
+}
+
+pub(super) fn encode_field(name: &[u8], value: &[u8], dst: &mut BytesMut) {
+    let mut name_index = None;
+    for (index, &(n, v)) in STATIC_TABLE.iter().enumerate() {
+        if n.as_bytes() == name {
+            if v.as_bytes() == value {
+                // Indexed Field Line, static
+                encode_int(index as u64, 6, 0b1100_0000, dst);
+                return;
+            }
+            name_index = name_index.or(Some(index));
+        }
+    }
+
+    match name_index {
+        Some(index) => {
+            // Literal Field Line with Name Reference, static
+            encode_int(index as u64, 4, 0b0101_0000, dst);
+        }
+        None => {
+            // Literal Field Line with Literal Name
+            encode_int(name.len() as u64, 3, 0b0010_0000, dst);
+            dst.put_slice(name);
+        }
+    }
+    encode_int(value.len() as u64, 7, 0, dst);
+    dst.put_slice(value);
+}
+
+/// Decode a field section, calling `field` with each name and value.
+///
+/// Fails with `H3_EXCESSIVE_LOAD` once the fields add up to more than
+/// `max_size`, as counted for `SETTINGS_MAX_FIELD_SECTION_SIZE`.
+pub(super) fn decode<F>(src: &Bytes, max_size: u64, mut field: F) -> Result<(), Code>
+where
+    F: FnMut(Bytes, Bytes) -> Result<(), Code>,
+{
+    let mut pos = 0;
+    // A Required Insert Count other than zero would refer to dynamic table
+    // entries, and the Base is only used with them.
+    if decode_int(src, &mut pos, 8)? != 0 {
+        return Err(Code::QPACK_DECOMPRESSION_FAILED);
+    }
+    decode_int(src, &mut pos, 7)?;
+
+    let mut size = 0u64;
+    while pos < src.len() {
+        let first = src[pos];
+        let (name, value) = if first & 0b1000_0000 != 0 {
+            // Indexed Field Line
+            if first & 0b0100_0000 == 0 {
+                return Err(Code::QPACK_DECOMPRESSION_FAILED);
+            }
+            let (name, value) = static_entry(decode_int(src, &mut pos, 6)?)?;
+            (Bytes::from_static(name), Bytes::from_static(value))
+        } else if first & 0b0100_0000 != 0 {
+            // Literal Field Line with Name Reference
+            if first & 0b0001_0000 == 0 {
+                return Err(Code::QPACK_DECOMPRESSION_FAILED);
+            }
+            let (name, _) = static_entry(decode_int(src, &mut pos, 4)?)?;
+            (Bytes::from_static(name), decode_str(src, &mut pos, 7)?)
+        } else if first & 0b0010_0000 != 0 {
+            // Literal Field Line with Literal Name
+            let name = decode_str(src, &mut pos, 3)?;
+            (name, decode_str(src, &mut pos, 7)?)
+        } else {
+            // The post-base representations only refer to the dynamic table.
+            return Err(Code::QPACK_DECOMPRESSION_FAILED);
+        };
+
+        size += name.len() as u64 + value.len() as u64 + 32;
+        if size > max_size {
+            return Err(Code::EXCESSIVE_LOAD);
+        }
+        field(name, value)?;
+    }
+    Ok(())
+}
+
+fn static_entry(index: u64) -> Result<(&'static [u8], &'static [u8]), Code> {
+    STATIC_TABLE
+        .get(index as usize)
+        .map(|&(name, value)| (name.as_bytes(), value.as_bytes()))
+        .ok_or(Code::QPACK_DECOMPRESSION_FAILED)
+}
+
+/// Encode an integer with an N-bit prefix, as in RFC 7541, Section 5.1.
+fn encode_int(value: u64, prefix: u8, flags: u8, dst: &mut BytesMut) {
+    let max = (1u64 << prefix) - 1;
+    if value < max {
+        dst.put_u8(flags | value as u8);
+        return;
+    }
+    dst.put_u8(flags | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        dst.put_u8(0x80 | (rest & 0x7f) as u8);
+        rest >>= 7;
+    }
+    dst.put_u8(rest as u8);
+}
+
+fn decode_int(src: &[u8], pos: &mut usize, prefix: u8) -> Result<u64, Code> {
+    let max = (1u64 << prefix) - 1;
+    let first = *src.get(*pos).ok_or(Code::QPACK_DECOMPRESSION_FAILED)?;
+    *pos += 1;
+    let mut value = u64::from(first) & max;
+    if value < max {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let byte = *src.get(*pos).ok_or(Code::QPACK_DECOMPRESSION_FAILED)?;
+        *pos += 1;
+        if shift > 56 {
+            return Err(Code::QPACK_DECOMPRESSION_FAILED);
+        }
+        value += u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Decode a string literal, whose Huffman flag is the bit above the length
+/// prefix.
+fn decode_str(src: &Bytes, pos: &mut usize, prefix: u8) -> Result<Bytes, Code> {
+    let huffman = src.get(*pos).map_or(false, |b| b & (1 << prefix) != 0);
+    let len = decode_int(src, pos, prefix)?;
+    let end = pos
+        .checked_add(len as usize)
+        .filter(|&end| end <= src.len())
+        .ok_or(Code::QPACK_DECOMPRESSION_FAILED)?;
+    let raw = src.slice(*pos..end);
+    *pos = end;
+
+    if huffman {
+        huffman::decode(&raw)
+            .map(Bytes::from)
+            .map_err(|()| Code::QPACK_DECOMPRESSION_FAILED)
+    } else {
+        Ok(raw)
+    }
+}
+
+/// The static table, from RFC 9204, Appendix A.
+const STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains",
+    ),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains; preload",
+    ),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    (
+        "content-security-policy",
+        "script-src 'none'; object-src 'none'; base-uri 'none'",
+    ),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(src: &[u8]) -> Result<Vec<(Bytes, Bytes)>, Code> {
+        let mut fields = Vec::new();
+        decode(&Bytes::copy_from_slice(src), 4096, |name, value| {
+            fields.push((name, value));
+            Ok(())
+        })?;
+        Ok(fields)
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let fields: &[(&[u8], &[u8])] = &[
+            // indexed
+            (b":method", b"GET"),
+            (b":status", b"200"),
+            // name reference
+            (b":path", b"/index.html"),
+            (b"content-type", b"text/xml"),
+            // literal name, with a value long enough for a multi-byte length
+            (b"x-custom", &[b'a'; 200]),
+        ];
+
+        let mut buf = BytesMut::new();
+        encode_prefix(&mut buf);
+        for (name, value) in fields {
+            encode_field(name, value, &mut buf);
+        }
+        assert_eq!(&buf[2..3], &[0xc0 | 17], ":method GET is indexed");
+
+        let decoded = decode_all(&buf).unwrap();
+        assert_eq!(decoded.len(), fields.len());
+        for ((name, value), (dn, dv)) in fields.iter().zip(&decoded) {
+            assert_eq!(name, dn);
+            assert_eq!(value, dv);
+        }
+    }
+
+    #[test]
+    fn decode_huffman_literal() {
+        // RFC 9204, Appendix B.1: ":path" "/index.html", with a Huffman
+        // encoded value
+        let src = [
+            0x00, 0x00, 0x51, 0x88, 0x60, 0xd5, 0x48, 0x5f, 0x2b, 0xce, 0x9a, 0x68,
+        ];
+        let decoded = decode_all(&src).unwrap();
+        assert_eq!(decoded[0].0, ":path");
+        assert_eq!(decoded[0].1, "/index.html");
+
+        // "www.example.com" from RFC 7541, Appendix C.4.1, as :authority
+        let mut buf = BytesMut::new();
+        encode_prefix(&mut buf);
+        buf.put_u8(0b0101_0000);
+        buf.put_u8(0x80 | 12);
+        buf.put_slice(&[
+            0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff,
+        ]);
+        let decoded = decode_all(&buf).unwrap();
+        assert_eq!(decoded[0].0, ":authority");
+        assert_eq!(decoded[0].1, "www.example.com");
+    }
+
+    #[test]
+    fn decode_rejects_dynamic_table() {
+        // Required Insert Count of 1
+        assert_eq!(
+            decode_all(&[0x01, 0x00]),
+            Err(Code::QPACK_DECOMPRESSION_FAILED)
+        );
+        // dynamic Indexed Field Line
+        assert_eq!(
+            decode_all(&[0x00, 0x00, 0x80]),
+            Err(Code::QPACK_DECOMPRESSION_FAILED)
+        );
+        // Indexed Field Line with Post-Base Index
+        assert_eq!(
+            decode_all(&[0x00, 0x00, 0x10]),
+            Err(Code::QPACK_DECOMPRESSION_FAILED)
+        );
+        // out of the static table
+        assert_eq!(
+            decode_all(&[0x00, 0x00, 0xff, 0x24]),
+            Err(Code::QPACK_DECOMPRESSION_FAILED)
+        );
+        // truncated literal
+        assert_eq!(
+            decode_all(&[0x00, 0x00, 0x51, 0x05, b'/']),
+            Err(Code::QPACK_DECOMPRESSION_FAILED)
+        );
+    }
+
+    #[test]
+    fn decode_limits_size() {
+        let mut buf = BytesMut::new();
+        encode_prefix(&mut buf);
+        encode_field(b"x-big", &[b'a'; 5000], &mut buf);
+        assert_eq!(decode_all(&buf), Err(Code::EXCESSIVE_LOAD));
+    }
+}
@@ -0,0 +1,572 @@
+use std::cmp;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+
+use bytes::Bytes;
+use http::header::{DATE, HOST};
+use http::uri::{self, Authority, PathAndQuery, Scheme};
+use http::{Method, Request, Response, Uri, Version};
+use pin_project_lite::pin_project;
+
+use super::frame::{Frame, FrameStream};
+use super::upgrade::H3Upgraded;
+use super::webtransport::Sessions;
+use super::{
+    encode_headers, reset, Code, Config, Control, Fields, RecvBody, SendMessage, StreamRef,
+    StreamRefs,
+};
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::date::DateHeader;
+use crate::common::lifecycle::{EventSink, OpenConnection};
+use crate::common::metrics::{Elapsed, MetricsSink};
+use crate::common::{task, Future, Pin, Poll};
+use crate::ext::Protocol;
+use crate::headers;
+use crate::proto::span::{ConnSpan, RequestSpan};
+use crate::rt::bounds::Http3ConnExec;
+use crate::rt::quic::{Connection, RecvStream, SendStream};
+use crate::rt::{CloseReason, TaskDescriptor};
+use crate::service::HttpService;
+use crate::upgrade::{Pending, Upgraded};
+
+type ReceivedRequest<T> = (
+    Request<IncomingBody>,
+    FrameStream<T>,
+    Option<RecvBody>,
+    Option<Pending>,
+);
+
+pin_project! {
+    pub(crate) struct Server<C, S, E>
+    where
+        C: Connection,
+        S: HttpService<IncomingBody>,
+    {
+        exec: E,
+        conn_id: u64,
+        span: ConnSpan,
+        metrics: MetricsSink,
+        _opened: Elapsed,
+        connection: OpenConnection,
+        service: S,
+        config: Config,
+        control: Control<C>,
+        // streams whose request head hasn't been received yet
+        heads: Vec<FrameStream<C::BidiStream>>,
+        // requests waiting for the service to be ready
+        requests: VecDeque<ReceivedRequest<C::BidiStream>>,
+        streams: StreamRefs,
+        // once WebTransport is enabled
+        sessions: Option<Sessions>,
+        next_stream_id: u64,
+        // the ID sent in our GOAWAY frame, once shutting down
+        goaway: Option<u64>,
+    }
+}
+
+impl<C, S, B, E> Server<C, S, E>
+where
+    C: Connection + Unpin,
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ConnExec<S::Future, B, C::BidiStream>,
+{
+    pub(crate) fn new(
+        conn: C,
+        service: S,
+        config: &Config,
+        exec: E,
+        metrics: MetricsSink,
+        events: &EventSink,
+    ) -> Server<C, S, E> {
+        let connection = events.opened();
+        let opened = metrics.connection_opened();
+        let conn_id = crate::rt::next_connection_id();
+        let streams = StreamRefs::default();
+        let sessions = if config.enable_webtransport {
+            Some(Sessions::new(streams.clone(), metrics.clone()))
+        } else {
+            None
+        };
+        Server {
+            exec,
+            conn_id,
+            span: ConnSpan::new(conn_id, "server", "HTTP/3"),
+            control: Control::new(conn, true, config, metrics.clone()),
+            metrics,
+            _opened: opened,
+            connection,
+            service,
+            config: config.clone(),
+            heads: Vec::new(),
+            requests: VecDeque::new(),
+            streams,
+            sessions,
+            next_stream_id: 0,
+            goaway: None,
+        }
+    }
+
+    pub(crate) fn graceful_shutdown(&mut self) {
+        trace!("graceful_shutdown");
+        self.connection.lifecycle().closing();
+        if self.goaway.is_none() {
+            // Requests on streams already accepted are still answered.
+            self.control.goaway(self.next_stream_id);
+            self.goaway = Some(self.next_stream_id);
+        }
+    }
+
+    fn poll_server(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        loop {
+            if self.control.poll(cx)?.is_ready() {
+                trace!("connection closed");
+                return Poll::Ready(Ok(()));
+            }
+
+            // Only accept new streams once the service has taken the
+            // requests already received.
+            let backpressured = !self.requests.is_empty();
+            if !backpressured && self.poll_accept(cx)?.is_ready() {
+                trace!("incoming connection complete");
+                return Poll::Ready(Ok(()));
+            }
+            self.poll_heads(cx)?;
+            self.poll_dispatch(cx)?;
+            if let Some(ref mut sessions) = self.sessions {
+                sessions.poll_open(cx, self.control.conn());
+            }
+            if backpressured && self.requests.is_empty() {
+                continue;
+            }
+
+            if self.goaway.is_some()
+                && self.heads.is_empty()
+                && self.requests.is_empty()
+                && self.streams.poll_idle(cx).is_ready()
+            {
+                trace!("graceful shutdown complete");
+                self.control.close_gracefully();
+                return Poll::Ready(Ok(()));
+            }
+            return Poll::Pending;
+        }
+    }
+
+    fn poll_accept(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        loop {
+            let accepted = ready!(self.control.conn().poll_accept_bidi(cx));
+            let mut stream = match accepted.map_err(crate::Error::new_io)? {
+                Some(stream) => stream,
+                None => return Poll::Ready(Ok(())),
+            };
+            let id = RecvStream::id(&stream);
+            if self.goaway.map_or(false, |goaway| id >= goaway) {
+                trace!("rejecting stream {} after GOAWAY", id);
+                reset(&mut stream, Code::REQUEST_REJECTED);
+                continue;
+            }
+            self.next_stream_id = cmp::max(self.next_stream_id, id + 4);
+            self.heads.push(FrameStream::new(
+                stream,
+                Bytes::new(),
+                self.config.max_field_section_size,
+                self.metrics.clone(),
+            ));
+        }
+    }
+
+    fn poll_heads(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        let mut i = 0;
+        while i < self.heads.len() {
+            let frame = match self.heads[i].poll_frame(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let mut frames = self.heads.swap_remove(i);
+            match frame {
+                Ok(Some(Frame::Headers(block))) => self.recv_head(frames, &block)?,
+                Ok(Some(Frame::WebTransport(session_id))) => match self.sessions {
+                    Some(ref mut sessions) => {
+                        let (stream, buf) = frames.into_parts();
+                        sessions.accept(session_id, stream, buf);
+                    }
+                    None => return Err(crate::Error::new_h3(Code::FRAME_UNEXPECTED)),
+                },
+                Ok(Some(_)) => return Err(crate::Error::new_h3(Code::FRAME_UNEXPECTED)),
+                Ok(None) => {
+                    debug!("stream finished before its request head");
+                    reset(frames.get_mut(), Code::REQUEST_INCOMPLETE);
+                }
+                // Malformed frames are connection errors, while a stream
+                // failing is only a problem for that stream.
+                Err(err) if err.find_source::<Code>().is_some() => return Err(err),
+                Err(_err) => {
+                    debug!("stream error before its request head: {}", _err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn recv_head(
+        &mut self,
+        mut frames: FrameStream<C::BidiStream>,
+        block: &Bytes,
+    ) -> crate::Result<()> {
+        let max_size = self.config.max_field_section_size;
+        let enable_connect_protocol = self.config.enable_connect_protocol;
+        let decoded = Fields::decode(block, max_size)
+            .and_then(|fields| decode_request(fields, enable_connect_protocol));
+        let req = match decoded {
+            Ok(req) => req,
+            // The decoder's state is shared by the whole connection.
+            Err(Code::QPACK_DECOMPRESSION_FAILED) => {
+                return Err(crate::Error::new_h3(Code::QPACK_DECOMPRESSION_FAILED));
+            }
+            Err(code) => {
+                debug!("malformed request: {:?}", code);
+                reset(frames.get_mut(), code);
+                return Ok(());
+            }
+        };
+        trace!("incoming request");
+        if req.method() == Method::CONNECT {
+            // The stream becomes the tunnel, once the service accepts it.
+            let (pending, upgrade) = crate::upgrade::pending();
+            let mut req = req.map(|()| IncomingBody::empty());
+            req.extensions_mut().insert(upgrade);
+            let is_webtransport = req
+                .extensions()
+                .get::<Protocol>()
+                .map_or(false, |protocol| protocol.as_str() == "webtransport");
+            let stream_id = RecvStream::id(frames.get_mut());
+            if let (true, Some(sessions)) = (is_webtransport, self.sessions.as_mut()) {
+                req.extensions_mut().insert(sessions.register(stream_id));
+            }
+            if let Some(ref mut datagrams) = self.control.datagrams {
+                req.extensions_mut().insert(datagrams.register(stream_id));
+            }
+            self.requests.push_back((req, frames, None, Some(pending)));
+        } else {
+            let content_length = headers::content_length_parse_all(req.headers());
+            let (recv, body) = RecvBody::new(content_length, max_size);
+            self.requests
+                .push_back((req.map(|()| body), frames, Some(recv), None));
+        }
+        Ok(())
+    }
+
+    fn poll_dispatch(&mut self, cx: &mut task::Context<'_>) -> crate::Result<()> {
+        while !self.requests.is_empty() {
+            match self.service.poll_ready(cx) {
+                Poll::Ready(Ok(())) => (),
+                Poll::Pending => return Ok(()),
+                Poll::Ready(Err(err)) => {
+                    let err = crate::Error::new_user_service(err);
+                    debug!("service not ready, closing connection: {}", err);
+                    return Err(err);
+                }
+            }
+
+            let (req, frames, recv, connect) = self.requests.pop_front().expect("not empty");
+            let lifecycle = self.connection.lifecycle();
+            let span = RequestSpan::new(req.method(), req.uri(), &self.metrics, lifecycle);
+            let fut = {
+                let _entered = span.enter();
+                self.service.call(req)
+            };
+            let fut = H3Stream::new(
+                fut,
+                frames,
+                recv,
+                connect,
+                span,
+                self.streams.track(),
+                self.metrics.clone(),
+                self.config.date_header.clone(),
+            );
+            self.exec
+                .execute_h3stream(fut, &TaskDescriptor::new(self.conn_id, "h3 server stream"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<C, S, B, E> Future for Server<C, S, E>
+where
+    C: Connection + Unpin,
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ConnExec<S::Future, B, C::BidiStream>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let me = &mut *self;
+        let _entered = me.span.enter();
+        let res = ready!(me.poll_server(cx));
+        let lifecycle = me.connection.lifecycle();
+        match res {
+            Ok(()) => lifecycle.closed(CloseReason::Done),
+            Err(ref e) => {
+                me.control.close(e);
+                lifecycle.closed(CloseReason::Error(e));
+            }
+        }
+        Poll::Ready(res)
+    }
+}
+
+fn decode_request(fields: Fields, enable_connect_protocol: bool) -> Result<Request<()>, Code> {
+    let method = fields.method.ok_or(Code::MESSAGE_ERROR)?;
+    let method = Method::from_bytes(&method).map_err(|_| Code::MESSAGE_ERROR)?;
+    if fields.status.is_some() {
+        return Err(Code::MESSAGE_ERROR);
+    }
+    // RFC 9220, Section 3
+    let protocol = match fields.protocol {
+        Some(protocol) if method == Method::CONNECT && enable_connect_protocol => {
+            Some(Protocol::from_utf8(protocol).ok_or(Code::MESSAGE_ERROR)?)
+        }
+        Some(_) => return Err(Code::MESSAGE_ERROR),
+        None => None,
+    };
+
+    let mut parts = uri::Parts::default();
+    if method == Method::CONNECT && protocol.is_none() {
+        if fields.scheme.is_some() || fields.path.is_some() {
+            return Err(Code::MESSAGE_ERROR);
+        }
+        let authority = fields.authority.ok_or(Code::MESSAGE_ERROR)?;
+        parts.authority =
+            Some(Authority::from_maybe_shared(authority).map_err(|_| Code::MESSAGE_ERROR)?);
+    } else {
+        let scheme = fields.scheme.ok_or(Code::MESSAGE_ERROR)?;
+        let path = fields.path.ok_or(Code::MESSAGE_ERROR)?;
+        let headers = &fields.headers;
+        let authority = fields.authority.or_else(|| {
+            headers
+                .get(HOST)
+                .map(|host| Bytes::copy_from_slice(host.as_bytes()))
+        });
+        match authority {
+            Some(authority) => {
+                parts.scheme =
+                    Some(Scheme::try_from(&scheme[..]).map_err(|_| Code::MESSAGE_ERROR)?);
+                parts.authority =
+                    Some(Authority::from_maybe_shared(authority).map_err(|_| Code::MESSAGE_ERROR)?);
+            }
+            // http and https URIs can't be without one
+            None if &scheme[..] == b"http" || &scheme[..] == b"https" => {
+                return Err(Code::MESSAGE_ERROR);
+            }
+            None => (),
+        }
+        parts.path_and_query =
+            Some(PathAndQuery::from_maybe_shared(path).map_err(|_| Code::MESSAGE_ERROR)?);
+    }
+
+    let mut req = Request::new(());
+    *req.method_mut() = method;
+    *req.uri_mut() = Uri::from_parts(parts).map_err(|_| Code::MESSAGE_ERROR)?;
+    *req.version_mut() = Version::HTTP_3;
+    *req.headers_mut() = fields.headers;
+    if let Some(protocol) = protocol {
+        req.extensions_mut().insert(protocol);
+    }
+    Ok(req)
+}
+
+pin_project! {
+    #[allow(missing_debug_implementations)]
+    pub struct H3Stream<F, B, T>
+    where
+        B: Body,
+    {
+        // taken once a CONNECT tunnel is established
+        frames: Option<FrameStream<T>>,
+        // receives the request body, until done
+        recv: Option<RecvBody>,
+        // the tunnel of a CONNECT request
+        connect: Option<Pending>,
+        span: RequestSpan,
+        metrics: MetricsSink,
+        date_header: DateHeader,
+        _stream: StreamRef,
+        #[pin]
+        state: H3StreamState<F, B>,
+    }
+}
+
+pin_project! {
+    #[project = H3StreamStateProj]
+    enum H3StreamState<F, B>
+    where
+        B: Body,
+    {
+        Service {
+            #[pin]
+            fut: F,
+        },
+        Body {
+            #[pin]
+            send: SendMessage<B>,
+        },
+        Done,
+    }
+}
+
+impl<F, B, T> H3Stream<F, B, T>
+where
+    B: Body,
+{
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        fut: F,
+        frames: FrameStream<T>,
+        recv: Option<RecvBody>,
+        connect: Option<Pending>,
+        span: RequestSpan,
+        stream: StreamRef,
+        metrics: MetricsSink,
+        date_header: DateHeader,
+    ) -> H3Stream<F, B, T> {
+        H3Stream {
+            frames: Some(frames),
+            recv,
+            connect,
+            span,
+            metrics,
+            date_header,
+            _stream: stream,
+            state: H3StreamState::Service { fut },
+        }
+    }
+}
+
+impl<F, B, T, E> H3Stream<F, B, T>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+    T: SendStream + RecvStream + Unpin + Send + 'static,
+{
+    fn poll2(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+        let mut me = self.project();
+        loop {
+            let frames = me.frames.as_mut().expect("polled after upgrade");
+            let next = match me.state.as_mut().project() {
+                H3StreamStateProj::Service { fut } => {
+                    let res = match ready!(fut.poll(cx)) {
+                        Ok(res) => res,
+                        Err(e) => {
+                            let err = crate::Error::new_user_service(e);
+                            warn!("http3 service errored: {}", err);
+                            reset(frames.get_mut(), err.h3_code());
+                            return Poll::Ready(Err(err));
+                        }
+                    };
+
+                    let (head, body) = res.into_parts();
+                    me.span.record_status(head.status);
+                    let mut headers = head.headers;
+                    headers::strip_connection_headers(&mut headers, false);
+
+                    // set Date header if it isn't already set...
+                    if !headers.contains_key(DATE) {
+                        if let Some(date) = me.date_header.header_value() {
+                            headers.insert(DATE, date);
+                        }
+                    }
+
+                    // A successful response establishes a CONNECT tunnel.
+                    let is_tunnel = me.connect.is_some() && head.status.is_success();
+                    if !is_tunnel {
+                        *me.connect = None;
+                    }
+
+                    if !is_tunnel && !body.is_end_stream() {
+                        // automatically set Content-Length from body...
+                        if let Some(len) = body.size_hint().exact() {
+                            headers::set_content_length_if_missing(&mut headers, len);
+                        }
+                    }
+
+                    let status = [(":status", head.status.as_str().as_bytes())];
+                    let block = encode_headers(&status, &headers);
+                    let send = if is_tunnel {
+                        SendMessage::head_only(block, body, me.metrics.clone())
+                    } else {
+                        SendMessage::new(block, body, me.metrics.clone())
+                    };
+                    H3StreamState::Body { send }
+                }
+                H3StreamStateProj::Body { send } => {
+                    ready!(send.poll_send(cx, frames.get_mut()))?;
+                    if let Some(pending) = me.connect.take() {
+                        trace!("CONNECT tunnel established");
+                        let frames = me.frames.take().expect("frames");
+                        let io = H3Upgraded::new(frames, me.metrics.clone(), me._stream.clone());
+                        pending.fulfill(Upgraded::new(io, Bytes::new()));
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                H3StreamStateProj::Done => return Poll::Ready(Ok(())),
+            };
+            me.state.set(next);
+        }
+    }
+}
+
+impl<F, B, T, E> Future for H3Stream<F, B, T>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+    T: SendStream + RecvStream + Unpin + Send + 'static,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let _entered = self.span.enter();
+        let sent = match self.as_mut().poll2(cx) {
+            Poll::Ready(res) => {
+                let mut me = self.as_mut().project();
+                me.state.set(H3StreamState::Done);
+                if let Err(_e) = res {
+                    debug!("stream error: {}", _e);
+                    // the request body isn't wanted anymore either
+                    if let (Some(_), Some(frames)) = (me.recv.take(), me.frames.as_mut()) {
+                        let code = Code::REQUEST_CANCELLED.value();
+                        Pin::new(frames.get_mut()).stop_sending(code);
+                    }
+                }
+                true
+            }
+            Poll::Pending => false,
+        };
+
+        let me = self.project();
+        if let (Some(recv), Some(frames)) = (me.recv.as_mut(), me.frames.as_mut()) {
+            if recv.poll_recv(cx, frames).is_ready() {
+                *me.recv = None;
+            }
+        }
+        if sent && me.recv.is_none() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Streams handed over to the user as `Upgraded`: the stream of an
+//! established CONNECT tunnel, and the streams of WebTransport sessions.
+
+use std::cmp;
+use std::io;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use super::frame::{self, Frame, FrameStream};
+use super::{poll_send_buf, Code, StreamRef};
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Pin, Poll};
+use crate::rt::quic::{RecvStream, SendStream};
+use crate::rt::{Read, ReadBufCursor, Write};
+
+/// The stream of a CONNECT tunnel, whose bytes are carried in DATA frames.
+pub(super) struct H3Upgraded<T> {
+    frames: FrameStream<T>,
+    // received, but not yet read
+    recv_buf: Bytes,
+    // a DATA frame written, but not yet sent
+    send_buf: Bytes,
+    metrics: MetricsSink,
+    _stream: StreamRef,
+}
+
+impl<T> H3Upgraded<T> {
+    pub(super) fn new(frames: FrameStream<T>, metrics: MetricsSink, stream: StreamRef) -> Self {
+        H3Upgraded {
+            frames,
+            recv_buf: Bytes::new(),
+            send_buf: Bytes::new(),
+            metrics,
+            _stream: stream,
+        }
+    }
+}
+
+impl<T> Read for H3Upgraded<T>
+where
+    T: SendStream + RecvStream + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.recv_buf.is_empty() {
+            match ready!(self.frames.poll_frame(cx)) {
+                Ok(Some(Frame::Data(chunk))) => self.recv_buf = chunk,
+                // trailers end the tunnel as well
+                Ok(Some(Frame::Headers(_))) | Ok(None) => return Poll::Ready(Ok(())),
+                Ok(Some(_)) => {
+                    let code = Code::FRAME_UNEXPECTED;
+                    Pin::new(self.frames.get_mut()).stop_sending(code.value());
+                    let err = crate::Error::new_h3(code);
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+                }
+                Err(err) => return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err))),
+            }
+        }
+        let cnt = cmp::min(self.recv_buf.len(), buf.remaining());
+        buf.put_slice(&self.recv_buf[..cnt]);
+        self.recv_buf.advance(cnt);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Write for H3Upgraded<T>
+where
+    T: SendStream + RecvStream + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        // Only the rest of one frame is buffered at a time.
+        ready!(self.as_mut().poll_flush(cx))?;
+        let mut frame = BytesMut::with_capacity(buf.len() + 16);
+        frame::encode_frame_head(frame::DATA, buf.len(), &mut frame);
+        frame.extend_from_slice(buf);
+        let len = frame.len();
+        self.send_buf = frame.freeze();
+        match self.as_mut().poll_flush(cx) {
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            // Nothing of the frame was sent, so it isn't written either.
+            Poll::Pending if self.send_buf.len() == len => {
+                self.send_buf.clear();
+                Poll::Pending
+            }
+            _ => Poll::Ready(Ok(buf.len())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        let me = &mut *self;
+        poll_send_buf(me.frames.get_mut(), cx, &mut me.send_buf, &me.metrics)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(self.frames.get_mut()).poll_finish(cx)
+    }
+}
+
+/// A stream of a WebTransport session, which isn't framed after its first
+/// bytes.
+pub(super) struct WebTransportStream<T> {
+    stream: T,
+    // received, but not yet read
+    recv_buf: Bytes,
+    // the start of a stream we opened, until sent
+    head: Bytes,
+    metrics: MetricsSink,
+    _stream: StreamRef,
+}
+
+impl<T> WebTransportStream<T> {
+    /// A stream the peer opened, with what was received after its start.
+    pub(super) fn accepted(
+        stream: T,
+        recv_buf: Bytes,
+        metrics: MetricsSink,
+        stream_ref: StreamRef,
+    ) -> Self {
+        WebTransportStream {
+            stream,
+            recv_buf,
+            head: Bytes::new(),
+            metrics,
+            _stream: stream_ref,
+        }
+    }
+
+    /// A stream we opened, whose start is sent before what is written.
+    pub(super) fn opened(
+        stream: T,
+        session_id: u64,
+        metrics: MetricsSink,
+        stream_ref: StreamRef,
+    ) -> Self {
+        let mut head = BytesMut::with_capacity(16);
+        frame::encode_webtransport_stream(session_id, &mut head);
+        WebTransportStream {
+            stream,
+            recv_buf: Bytes::new(),
+            head: head.freeze(),
+            metrics,
+            _stream: stream_ref,
+        }
+    }
+}
+
+impl<T> Read for WebTransportStream<T>
+where
+    T: SendStream + RecvStream + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.recv_buf.is_empty() {
+            match ready!(Pin::new(&mut self.stream).poll_recv(cx))? {
+                Some(chunk) => {
+                    self.metrics.bytes_read(chunk.len());
+                    self.recv_buf = chunk;
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let cnt = cmp::min(self.recv_buf.len(), buf.remaining());
+        buf.put_slice(&self.recv_buf[..cnt]);
+        self.recv_buf.advance(cnt);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Write for WebTransportStream<T>
+where
+    T: SendStream + RecvStream + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        let n = ready!(Pin::new(&mut self.stream).poll_send(cx, buf))?;
+        self.metrics.bytes_written(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        let me = &mut *self;
+        poll_send_buf(&mut me.stream, cx, &mut me.head, &me.metrics)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.stream).poll_finish(cx)
+    }
+}
@@ -0,0 +1,123 @@
+//! The WebTransport sessions of a connection.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+
+use super::upgrade::WebTransportStream;
+use super::{reset, Code, StreamRefs};
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Pin, Poll};
+use crate::ext::WebTransportSession;
+use crate::rt::quic::{Connection, RecvStream, SendStream};
+use crate::upgrade::Upgraded;
+
+/// A session asking for a stream to be opened.
+pub(crate) struct OpenStream {
+    pub(crate) session_id: u64,
+    pub(crate) tx: oneshot::Sender<crate::Result<Upgraded>>,
+}
+
+/// Routes the streams of WebTransport sessions: those the peer opens, to
+/// their session, and those sessions ask for, to the connection.
+pub(super) struct Sessions {
+    // where the streams the peer opens in each session are sent
+    sessions: HashMap<u64, mpsc::UnboundedSender<Upgraded>>,
+    open_tx: mpsc::UnboundedSender<OpenStream>,
+    open_rx: mpsc::UnboundedReceiver<OpenStream>,
+    // waiting for the connection to open a stream
+    opening: Option<OpenStream>,
+    streams: StreamRefs,
+    metrics: MetricsSink,
+}
+
+impl Sessions {
+    pub(super) fn new(streams: StreamRefs, metrics: MetricsSink) -> Sessions {
+        let (open_tx, open_rx) = mpsc::unbounded_channel();
+        Sessions {
+            sessions: HashMap::new(),
+            open_tx,
+            open_rx,
+            opening: None,
+            streams,
+            metrics,
+        }
+    }
+
+    /// Start a session, on the stream of its CONNECT request.
+    pub(super) fn register(&mut self, session_id: u64) -> WebTransportSession {
+        // forget the sessions dropped since
+        self.sessions.retain(|_, tx| !tx.is_closed());
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.sessions.insert(session_id, tx);
+        WebTransportSession::new(session_id, rx, self.open_tx.clone(), self.streams.track())
+    }
+
+    /// Pass a stream the peer opened on to its session, along with what
+    /// was received after its start.
+    pub(super) fn accept<T>(&mut self, session_id: u64, mut stream: T, buf: Bytes)
+    where
+        T: SendStream + RecvStream + Unpin + Send + 'static,
+    {
+        let code = match self.sessions.get(&session_id) {
+            Some(tx) if !tx.is_closed() => {
+                trace!("stream accepted for WebTransport session {}", session_id);
+                let io = WebTransportStream::accepted(
+                    stream,
+                    buf,
+                    self.metrics.clone(),
+                    self.streams.track(),
+                );
+                let _ = tx.send(Upgraded::new(io, Bytes::new()));
+                return;
+            }
+            Some(_) => {
+                self.sessions.remove(&session_id);
+                Code::WEBTRANSPORT_SESSION_GONE
+            }
+            // Streams can arrive before their session is known, but hyper
+            // doesn't buffer them.
+            None => Code::WEBTRANSPORT_BUFFERED_STREAM_REJECTED,
+        };
+        debug!("rejecting stream for WebTransport session {}", session_id);
+        reset(&mut stream, code);
+    }
+
+    /// Open the streams that sessions asked for.
+    pub(super) fn poll_open<C>(&mut self, cx: &mut task::Context<'_>, mut conn: Pin<&mut C>)
+    where
+        C: Connection,
+    {
+        loop {
+            if self.opening.is_none() {
+                match self.open_rx.poll_recv(cx) {
+                    Poll::Ready(Some(open)) if open.tx.is_closed() => continue,
+                    Poll::Ready(Some(open)) => self.opening = Some(open),
+                    // This holds a sender, so the channel never closes.
+                    Poll::Ready(None) | Poll::Pending => return,
+                }
+            }
+
+            let opened = match conn.as_mut().poll_open_bidi(cx) {
+                Poll::Ready(opened) => opened,
+                Poll::Pending => return,
+            };
+            let open = self.opening.take().expect("opening stream");
+            let res = match opened {
+                Ok(stream) => {
+                    trace!("stream opened for WebTransport session {}", open.session_id);
+                    let io = WebTransportStream::opened(
+                        stream,
+                        open.session_id,
+                        self.metrics.clone(),
+                        self.streams.track(),
+                    );
+                    Ok(Upgraded::new(io, Bytes::new()))
+                }
+                Err(e) => Err(crate::Error::new_io(e)),
+            };
+            let _ = open.tx.send(res);
+        }
+    }
+}
@@ -15,6 +15,10 @@ cfg_feature! {
 
 #[cfg(feature = "http2")]
 pub(crate) mod h2;
+#[cfg(feature = "http3")]
+pub(crate) mod h3;
+
+pub(crate) mod span;
 
 /// An Incoming Message head. Includes request/status line, and headers.
 #[derive(Debug, Default)]
@@ -51,6 +55,7 @@ pub(crate) enum BodyLength {
 }
 
 /// Status of when a Dispatcher future completes.
+#[cfg(any(feature = "http1", feature = "http2"))]
 pub(crate) enum Dispatched {
     /// Dispatcher completely shutdown connection.
     Shutdown,
@@ -0,0 +1,96 @@
+//! Spans for connections and requests, recorded with the `tracing` feature.
+//!
+//! Without the feature these do nothing, so the protocol code can use them
+//! unconditionally. The span and field names are part of the documented
+//! interface, see the crate docs.
+//!
+//! A request span also times its request for the connection's metrics, and
+//! tracks it in the connection's lifecycle.
+
+use http::{Method, StatusCode, Uri};
+
+use crate::common::lifecycle::{InFlight, Lifecycle};
+use crate::common::metrics::{Elapsed, MetricsSink};
+
+/// The span of a connection, entered whenever the connection is polled.
+#[derive(Clone)]
+pub(crate) struct ConnSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+/// The span of a request, from when its head is sent or received until its
+/// response is complete.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    _elapsed: Elapsed,
+    _in_flight: InFlight,
+}
+
+/// Keeps a span entered until dropped.
+pub(crate) struct Entered {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl ConnSpan {
+    /// Create the span of a connection, as a child of the current span.
+    pub(crate) fn new(id: u64, role: &'static str, protocol: &'static str) -> ConnSpan {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (id, role, protocol);
+
+        ConnSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("connection", id, role, protocol),
+        }
+    }
+
+    pub(crate) fn enter(&self) -> Entered {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _span: self.span.clone().entered(),
+        }
+    }
+}
+
+impl RequestSpan {
+    /// Create the span of a request, as a child of the current span, which
+    /// is the span of its connection.
+    pub(crate) fn new(
+        method: &Method,
+        target: &Uri,
+        metrics: &MetricsSink,
+        lifecycle: &Lifecycle,
+    ) -> RequestSpan {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (method, target);
+
+        RequestSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "request",
+                method = %method,
+                target = %target,
+                status = tracing::field::Empty,
+            ),
+            _elapsed: metrics.request_started(),
+            _in_flight: lifecycle.request_started(),
+        }
+    }
+
+    pub(crate) fn record_status(&self, status: StatusCode) {
+        #[cfg(feature = "tracing")]
+        self.span.record("status", status.as_u16());
+        #[cfg(not(feature = "tracing"))]
+        let _ = status;
+    }
+
+    #[cfg(any(feature = "server", feature = "http2"))]
+    pub(crate) fn enter(&self) -> Entered {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _span: self.span.clone().entered(),
+        }
+    }
+}
@@ -9,12 +9,18 @@ pub use self::h2::Http2ConnExec;
 #[cfg(all(feature = "client", feature = "http2"))]
 pub use self::h2_client::ExecutorClient;
 
+#[cfg(all(feature = "server", feature = "http3"))]
+pub use self::h3::Http3ConnExec;
+
+#[cfg(all(feature = "client", feature = "http3"))]
+pub use self::h3_client::Http3ClientExec;
+
 #[cfg(all(feature = "client", feature = "http2"))]
 #[cfg_attr(docsrs, doc(cfg(all(feature = "server", feature = "http2"))))]
 mod h2_client {
     use std::{error::Error, future::Future};
 
-    use crate::rt::{Read, Write};
+    use crate::rt::{Read, TaskDescriptor, Write};
     use crate::{proto::h2::client::H2ClientFuture, rt::Executor};
 
     /// An executor to spawn http2 futures for the client.
@@ -32,7 +38,11 @@ mod h2_client {
         T: Read + Write + Unpin,
     {
         #[doc(hidden)]
-        fn execute_h2_future(&mut self, future: H2ClientFuture<B, T>);
+        fn execute_h2_future(
+            &mut self,
+            future: H2ClientFuture<B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()>;
     }
 
     impl<E, B, T> ExecutorClient<B, T> for E
@@ -43,8 +53,13 @@ mod h2_client {
         H2ClientFuture<B, T>: Future<Output = ()>,
         T: Read + Write + Unpin,
     {
-        fn execute_h2_future(&mut self, future: H2ClientFuture<B, T>) {
-            self.execute(future)
+        fn execute_h2_future(
+            &mut self,
+            future: H2ClientFuture<B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()> {
+            self.execute_named(future, task)
+                .map_err(crate::Error::new_execute)
         }
     }
 
@@ -66,7 +81,10 @@ mod h2_client {
 #[cfg(all(feature = "server", feature = "http2"))]
 #[cfg_attr(docsrs, doc(cfg(all(feature = "server", feature = "http2"))))]
 mod h2 {
-    use crate::{proto::h2::server::H2Stream, rt::Executor};
+    use crate::{
+        proto::h2::server::H2Stream,
+        rt::{Executor, TaskDescriptor},
+    };
     use http_body::Body;
     use std::future::Future;
 
@@ -80,7 +98,11 @@ mod h2 {
     /// [`Executor`]: crate::rt::Executor
     pub trait Http2ConnExec<F, B: Body>: sealed::Sealed<(F, B)> + Clone {
         #[doc(hidden)]
-        fn execute_h2stream(&mut self, fut: H2Stream<F, B>);
+        fn execute_h2stream(
+            &mut self,
+            fut: H2Stream<F, B>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()>;
     }
 
     #[doc(hidden)]
@@ -90,8 +112,13 @@ mod h2 {
         H2Stream<F, B>: Future<Output = ()>,
         B: Body,
     {
-        fn execute_h2stream(&mut self, fut: H2Stream<F, B>) {
-            self.execute(fut)
+        fn execute_h2stream(
+            &mut self,
+            fut: H2Stream<F, B>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()> {
+            self.execute_named(fut, task)
+                .map_err(crate::Error::new_execute)
         }
     }
 
@@ -107,3 +134,124 @@ mod h2 {
         pub trait Sealed<T> {}
     }
 }
+
+#[cfg(all(feature = "client", feature = "http3"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "client", feature = "http3"))))]
+mod h3_client {
+    use std::{error::Error, future::Future};
+
+    use crate::proto::h3::client::H3ClientStream;
+    use crate::rt::quic::{RecvStream, SendStream};
+    use crate::rt::{Executor, TaskDescriptor};
+
+    /// An executor to spawn http3 request streams for the client.
+    ///
+    /// This trait is implemented for any type that implements [`Executor`]
+    /// trait for any future.
+    ///
+    /// This trait is sealed and cannot be implemented for types outside this crate.
+    ///
+    /// [`Executor`]: crate::rt::Executor
+    pub trait Http3ClientExec<B, T>: sealed_client::Sealed<(B, T)>
+    where
+        B: http_body::Body,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+        T: SendStream + RecvStream + Unpin,
+    {
+        #[doc(hidden)]
+        fn execute_h3_future(
+            &mut self,
+            future: H3ClientStream<B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()>;
+    }
+
+    impl<E, B, T> Http3ClientExec<B, T> for E
+    where
+        E: Executor<H3ClientStream<B, T>>,
+        B: http_body::Body + 'static,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+        H3ClientStream<B, T>: Future<Output = ()>,
+        T: SendStream + RecvStream + Unpin,
+    {
+        fn execute_h3_future(
+            &mut self,
+            future: H3ClientStream<B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()> {
+            self.execute_named(future, task)
+                .map_err(crate::Error::new_execute)
+        }
+    }
+
+    impl<E, B, T> sealed_client::Sealed<(B, T)> for E
+    where
+        E: Executor<H3ClientStream<B, T>>,
+        B: http_body::Body + 'static,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+        H3ClientStream<B, T>: Future<Output = ()>,
+        T: SendStream + RecvStream + Unpin,
+    {
+    }
+
+    mod sealed_client {
+        pub trait Sealed<X> {}
+    }
+}
+
+#[cfg(all(feature = "server", feature = "http3"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "server", feature = "http3"))))]
+mod h3 {
+    use crate::{
+        proto::h3::server::H3Stream,
+        rt::{Executor, TaskDescriptor},
+    };
+    use http_body::Body;
+    use std::future::Future;
+
+    /// An executor to spawn http3 request streams for the server.
+    ///
+    /// This trait is implemented for any type that implements [`Executor`]
+    /// trait for any future.
+    ///
+    /// This trait is sealed and cannot be implemented for types outside this crate.
+    ///
+    /// [`Executor`]: crate::rt::Executor
+    pub trait Http3ConnExec<F, B: Body, T>: sealed::Sealed<(F, B, T)> + Clone {
+        #[doc(hidden)]
+        fn execute_h3stream(
+            &mut self,
+            fut: H3Stream<F, B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()>;
+    }
+
+    #[doc(hidden)]
+    impl<E, F, B, T> Http3ConnExec<F, B, T> for E
+    where
+        E: Executor<H3Stream<F, B, T>> + Clone,
+        H3Stream<F, B, T>: Future<Output = ()>,
+        B: Body,
+    {
+        fn execute_h3stream(
+            &mut self,
+            fut: H3Stream<F, B, T>,
+            task: &TaskDescriptor,
+        ) -> crate::Result<()> {
+            self.execute_named(fut, task)
+                .map_err(crate::Error::new_execute)
+        }
+    }
+
+    impl<E, F, B, T> sealed::Sealed<(F, B, T)> for E
+    where
+        E: Executor<H3Stream<F, B, T>> + Clone,
+        H3Stream<F, B, T>: Future<Output = ()>,
+        B: Body,
+    {
+    }
+
+    mod sealed {
+        pub trait Sealed<T> {}
+    }
+}
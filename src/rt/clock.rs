@@ -0,0 +1,58 @@
+//! Provides a trait for supplying the time put in `Date` headers.
+//!
+//! Example of a clock that is set from the network, on a device without a
+//! real-time clock:
+//! ```rust
+//! use std::sync::Mutex;
+//! use std::time::{Instant, SystemTime};
+//!
+//! use hyper::rt::Clock;
+//!
+//! #[derive(Default)]
+//! pub struct SyncedClock {
+//!     synced: Mutex<Option<(SystemTime, Instant)>>,
+//! }
+//!
+//! impl SyncedClock {
+//!     pub fn set(&self, now: SystemTime) {
+//!         *self.synced.lock().unwrap() = Some((now, Instant::now()));
+//!     }
+//! }
+//!
+//! impl Clock for SyncedClock {
+//!     fn now(&self) -> Option<SystemTime> {
+//!         let synced = *self.synced.lock().unwrap();
+//!         synced.map(|(time, at)| time + at.elapsed())
+//!     }
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// A source of the time that server connections put in `Date` headers.
+///
+/// Set one on a server connection builder with its `clock` method. By
+/// default, the system clock is read, at most once a second, unless the
+/// `embedded` feature is enabled, in which case responses get no `Date`
+/// header.
+///
+/// It is implemented for `Arc<C>`, so a clock can be shared between
+/// builders.
+pub trait Clock {
+    /// The current time, or `None` if it isn't known, in which case the
+    /// response gets no `Date` header.
+    ///
+    /// This is called for each response that doesn't already have a
+    /// `Date` header.
+    fn now(&self) -> Option<SystemTime>;
+}
+
+impl<C> Clock for Arc<C>
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> Option<SystemTime> {
+        (**self).now()
+    }
+}
@@ -3,6 +3,8 @@ use std::mem::MaybeUninit;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+use bytes::{BufMut, Bytes, BytesMut};
+
 // New IO traits? What?! Why, are you bonkers?
 //
 // I mean, yes, probably. But, here's the goals:
@@ -39,6 +41,69 @@ pub trait Read {
         cx: &mut Context<'_>,
         buf: ReadBufCursor<'_>,
     ) -> Poll<Result<(), std::io::Error>>;
+
+    /// Returns whether this reader prefers to be given owned buffers with
+    /// `poll_read_owned`.
+    ///
+    /// When this returns `true`, hyper will read into its buffer with
+    /// `poll_read_owned` instead of `poll_read`.
+    ///
+    /// The default implementation returns `false`.
+    fn is_read_owned(&self) -> bool {
+        false
+    }
+
+    /// Attempts to read bytes into the spare capacity of an owned buffer.
+    ///
+    /// This is the completion-based counterpart of `poll_read`. Since `buf`
+    /// is owned, an implementation may detach its spare capacity (such as
+    /// with `BytesMut::split_off(buf.len())`), hand it to the kernel, and
+    /// return `Poll::Pending`. Once the read completes, the filled bytes
+    /// should be joined back onto `buf` (`BytesMut::unsplit`), and
+    /// `Poll::Ready(Ok(n))` returned with the number of bytes read.
+    ///
+    /// After this returns `Poll::Pending`, hyper will call it again with the
+    /// same buffer, and will not consume or reallocate the buffer's spare
+    /// capacity in between. A return value of `0` implies EOF.
+    ///
+    /// The default implementation reads into the spare capacity using
+    /// `poll_read`.
+    fn poll_read_owned(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut BytesMut,
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_read_spare(self, cx, buf, None)
+    }
+}
+
+/// Reads into the spare capacity of `buf` with `poll_read`, advancing it by
+/// the number of bytes read.
+pub(crate) fn poll_read_spare<R: Read + ?Sized>(
+    io: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    buf: &mut BytesMut,
+    hint: Option<usize>,
+) -> Poll<Result<usize, std::io::Error>> {
+    let dst = buf.chunk_mut();
+    // SAFETY: `UninitSlice` is a transparent wrapper of `[MaybeUninit<u8>]`,
+    // and `ReadBuf` never de-initializes bytes.
+    let dst = unsafe { &mut *(dst as *mut _ as *mut [MaybeUninit<u8>]) };
+    let mut read_buf = ReadBuf::uninit(dst);
+    read_buf.set_hint(hint);
+    match io.poll_read(cx, read_buf.unfilled()) {
+        Poll::Ready(Ok(())) => {
+            let n = read_buf.filled().len();
+            // SAFETY: we just read that many bytes into the
+            // uninitialized part of the buffer.
+            unsafe {
+                buf.advance_mut(n);
+            }
+            Poll::Ready(Ok(n))
+        }
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
 }
 
 /// Write bytes asynchronously.
@@ -96,6 +161,34 @@ pub trait Write {
             .map_or(&[][..], |b| &**b);
         self.poll_write(cx, buf)
     }
+
+    /// Returns whether this writer prefers to be given owned buffers with
+    /// `poll_write_owned`.
+    ///
+    /// When this returns `true`, hyper will write its buffered bytes with
+    /// `poll_write_owned` instead of `poll_write` and `poll_write_vectored`.
+    ///
+    /// The default implementation returns `false`.
+    fn is_write_owned(&self) -> bool {
+        false
+    }
+
+    /// Like `poll_write`, except the bytes are passed as an owned buffer.
+    ///
+    /// `Bytes` is cheap to clone, so an implementation may keep `buf` alive
+    /// while a completion-based write is in flight, and return
+    /// `Poll::Pending`. hyper will then call this again with the same bytes
+    /// once woken, and the implementation should return the result of the
+    /// submitted write.
+    ///
+    /// The default implementation calls `poll_write`.
+    fn poll_write_owned(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: Bytes,
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.poll_write(cx, &buf)
+    }
 }
 
 /// A wrapper around a byte buffer that is incrementally filled and initialized.
@@ -121,6 +214,7 @@ pub struct ReadBuf<'a> {
     raw: &'a mut [MaybeUninit<u8>],
     filled: usize,
     init: usize,
+    hint: Option<usize>,
 }
 
 /// The cursor part of a [`ReadBuf`].
@@ -133,7 +227,7 @@ pub struct ReadBufCursor<'a> {
 
 impl<'data> ReadBuf<'data> {
     #[inline]
-    #[cfg(test)]
+    #[cfg(any(test, feature = "smol"))]
     pub(crate) fn new(raw: &'data mut [u8]) -> Self {
         let len = raw.len();
         Self {
@@ -141,6 +235,7 @@ impl<'data> ReadBuf<'data> {
             raw: unsafe { &mut *(raw as *mut [u8] as *mut [MaybeUninit<u8>]) },
             filled: 0,
             init: len,
+            hint: None,
         }
     }
 
@@ -151,6 +246,7 @@ impl<'data> ReadBuf<'data> {
             raw,
             filled: 0,
             init: 0,
+            hint: None,
         }
     }
 
@@ -175,6 +271,16 @@ impl<'data> ReadBuf<'data> {
         }
     }
 
+    /// Set how many bytes the reader expects to need next, if known.
+    ///
+    /// This is exposed to the IO through [`ReadBufCursor::hint`]. Wrappers
+    /// that read through their own `ReadBuf` can use this to forward the
+    /// hint they were given.
+    #[inline]
+    pub fn set_hint(&mut self, hint: Option<usize>) {
+        self.hint = hint;
+    }
+
     #[inline]
     pub(crate) unsafe fn set_init(&mut self, n: usize) {
         self.init = self.init.max(n);
@@ -211,12 +317,30 @@ impl<'data> fmt::Debug for ReadBuf<'data> {
         f.debug_struct("ReadBuf")
             .field("filled", &self.filled)
             .field("init", &self.init)
+            .field("hint", &self.hint)
             .field("capacity", &self.capacity())
             .finish()
     }
 }
 
 impl<'data> ReadBufCursor<'data> {
+    /// The number of bytes the reader expects to need next, if known.
+    ///
+    /// When hyper is reading a message body with a known remaining length,
+    /// such as from `Content-Length` or the current chunk size, this is that
+    /// remainder. While reading a message head the size is unknown, and this
+    /// returns `None`.
+    ///
+    /// This is only a hint: the unfilled portion of the buffer may be larger
+    /// or smaller, and filling less or more is not an error. Transports that
+    /// read in records or frames can use it to avoid buffering more than
+    /// will be consumed. It is only given to `poll_read`, as readers using
+    /// [`Read::poll_read_owned`] fill the spare capacity of hyper's buffer.
+    #[inline]
+    pub fn hint(&self) -> Option<usize> {
+        self.buf.hint
+    }
+
     /// Access the unfilled part of the buffer.
     ///
     /// # Safety
@@ -228,6 +352,18 @@ impl<'data> ReadBufCursor<'data> {
         &mut self.buf.raw[self.buf.filled..]
     }
 
+    /// Zero any uninitialized bytes of the unfilled part, and return it.
+    #[cfg(feature = "smol")]
+    pub(crate) fn initialize_unfilled(&mut self) -> &mut [u8] {
+        for b in &mut self.buf.raw[self.buf.init..] {
+            *b = MaybeUninit::new(0);
+        }
+        self.buf.init = self.buf.raw.len();
+        let unfilled = &mut self.buf.raw[self.buf.filled..];
+        // SAFETY: every byte of the buffer was just initialized.
+        unsafe { &mut *(unfilled as *mut [MaybeUninit<u8>] as *mut [u8]) }
+    }
+
     /// Advance the `filled` cursor by `n` bytes.
     ///
     /// # Safety
@@ -244,6 +380,22 @@ impl<'data> ReadBufCursor<'data> {
         self.buf.remaining()
     }
 
+    /// Borrow this cursor for a shorter lifetime, so it can be inspected
+    /// after passing it on to another reader.
+    #[cfg(feature = "http2")]
+    #[inline]
+    pub(crate) fn reborrow<'cursor>(&'cursor mut self) -> ReadBufCursor<'cursor> {
+        ReadBufCursor {
+            // SAFETY: self.buf is never re-assigned, so its safe to narrow
+            // the lifetime.
+            buf: unsafe {
+                std::mem::transmute::<&'cursor mut ReadBuf<'data>, &'cursor mut ReadBuf<'cursor>>(
+                    &mut *self.buf,
+                )
+            },
+        }
+    }
+
     #[inline]
     pub(crate) fn put_slice(&mut self, buf: &[u8]) {
         assert!(
@@ -279,6 +431,18 @@ macro_rules! deref_async_read {
         ) -> Poll<std::io::Result<()>> {
             Pin::new(&mut **self).poll_read(cx, buf)
         }
+
+        fn is_read_owned(&self) -> bool {
+            (**self).is_read_owned()
+        }
+
+        fn poll_read_owned(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut BytesMut,
+        ) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut **self).poll_read_owned(cx, buf)
+        }
     };
 }
 
@@ -312,6 +476,18 @@ macro_rules! deref_async_write {
             (**self).is_write_vectored()
         }
 
+        fn is_write_owned(&self) -> bool {
+            (**self).is_write_owned()
+        }
+
+        fn poll_write_owned(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: Bytes,
+        ) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut **self).poll_write_owned(cx, buf)
+        }
+
         fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
             Pin::new(&mut **self).poll_flush(cx)
         }
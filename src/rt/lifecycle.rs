@@ -0,0 +1,43 @@
+//! Events in the lifecycle of a connection.
+
+/// An event in the lifecycle of a connection.
+///
+/// These are passed to the callback set with a client or server connection
+/// builder's `on_connection_event` method, from within the task polling the
+/// connection.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConnectionEvent<'a> {
+    /// The connection was opened. This is always the first event.
+    Opened,
+    /// The connection started its first request.
+    FirstRequest,
+    /// The connection has no requests in flight, after completing at least
+    /// one.
+    Idle,
+    /// The connection started a request after being idle.
+    Resumed,
+    /// The connection started shutting down gracefully.
+    ///
+    /// This happens when `graceful_shutdown` is called on a server
+    /// connection, or when an HTTP/2 client connection receives a `GOAWAY`.
+    /// Requests already in flight may still complete.
+    Closing,
+    /// The connection closed. This is always the last event.
+    Closed(CloseReason<'a>),
+}
+
+/// Why a connection closed, as reported by [`ConnectionEvent::Closed`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CloseReason<'a> {
+    /// HTTP was done with the connection, such as after the peer closed it,
+    /// or a response disabled keep-alive.
+    Done,
+    /// The connection was upgraded, and is no longer managed by hyper.
+    Upgraded,
+    /// The connection failed.
+    Error(&'a crate::Error),
+    /// The connection was dropped before it finished.
+    Dropped,
+}
@@ -0,0 +1,108 @@
+//! Provides a trait for collecting connection and request metrics.
+//!
+//! Example counting requests served:
+//! ```rust
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use std::time::Duration;
+//!
+//! use hyper::rt::Metrics;
+//!
+//! #[derive(Default)]
+//! pub struct Counters {
+//!     requests: AtomicU64,
+//!     bytes_written: AtomicU64,
+//! }
+//!
+//! impl Metrics for Counters {
+//!     fn request_completed(&self, _duration: Duration) {
+//!         self.requests.fetch_add(1, Ordering::Relaxed);
+//!     }
+//!
+//!     fn bytes_written(&self, n: usize) {
+//!         self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+//!     }
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A sink for connection and request metrics.
+///
+/// Set one on a client or server connection builder with its `metrics`
+/// method. hyper calls it as the connections it builds make progress, from
+/// within the task polling them, so implementations should be cheap, such
+/// as updating atomic counters or histograms. Every method does nothing by
+/// default.
+///
+/// It is implemented for `Arc<M>`, so a sink can be shared with whatever
+/// exports the metrics.
+pub trait Metrics {
+    /// A connection was opened.
+    fn connection_opened(&self) {}
+
+    /// A connection was closed, after being open for `lifetime`.
+    ///
+    /// This is also called when a connection is upgraded, since hyper no
+    /// longer manages it afterwards.
+    fn connection_closed(&self, lifetime: Duration) {
+        let _ = lifetime;
+    }
+
+    /// An HTTP/2 connection finished its handshake, which took `duration`.
+    ///
+    /// HTTP/1 connections have no handshake, and HTTP/3 connections are
+    /// handed over after QUIC's, so this isn't called for them.
+    fn handshake_completed(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// A request completed, having taken `duration`.
+    ///
+    /// For servers, this is from receiving the request head until the
+    /// response has been written. For clients, it is from sending the
+    /// request head until the response head is received. Requests that fail
+    /// are counted too.
+    fn request_completed(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// `n` bytes were read from a connection's IO.
+    fn bytes_read(&self, n: usize) {
+        let _ = n;
+    }
+
+    /// `n` bytes were written to a connection's IO.
+    fn bytes_written(&self, n: usize) {
+        let _ = n;
+    }
+}
+
+impl<M> Metrics for Arc<M>
+where
+    M: Metrics + ?Sized,
+{
+    fn connection_opened(&self) {
+        (**self).connection_opened()
+    }
+
+    fn connection_closed(&self, lifetime: Duration) {
+        (**self).connection_closed(lifetime)
+    }
+
+    fn handshake_completed(&self, duration: Duration) {
+        (**self).handshake_completed(duration)
+    }
+
+    fn request_completed(&self, duration: Duration) {
+        (**self).request_completed(duration)
+    }
+
+    fn bytes_read(&self, n: usize) {
+        (**self).bytes_read(n)
+    }
+
+    fn bytes_written(&self, n: usize) {
+        (**self).bytes_written(n)
+    }
+}
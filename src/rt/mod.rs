@@ -5,13 +5,45 @@
 //!
 //! - Executors
 //! - Timers
+//! - Clocks for `Date` headers
 //! - IO transports
+//! - Metrics sinks and connection lifecycle events
+//! - Buffer pools for HTTP/1 reads
+//! - QUIC transports, for HTTP/3
 
 pub mod bounds;
+mod clock;
 mod io;
+mod lifecycle;
+mod metrics;
+mod pool;
 mod timer;
 
+cfg_feature! {
+    #![feature = "http3"]
+
+    pub mod quic;
+}
+
+cfg_feature! {
+    #![feature = "smol"]
+
+    pub mod smol;
+}
+
+cfg_feature! {
+    #![feature = "wasi"]
+
+    pub mod wasi;
+}
+
+pub use self::clock::Clock;
+#[cfg(feature = "http1")]
+pub(crate) use self::io::poll_read_spare;
 pub use self::io::{Read, ReadBuf, ReadBufCursor, Write};
+pub use self::lifecycle::{CloseReason, ConnectionEvent};
+pub use self::metrics::Metrics;
+pub use self::pool::BufferPool;
 pub use self::timer::{Sleep, Timer};
 
 /// An executor of futures.
@@ -39,4 +71,78 @@ pub use self::timer::{Sleep, Timer};
 pub trait Executor<Fut> {
     /// Place the future into the executor to be run.
     fn execute(&self, fut: Fut);
+
+    /// Attempt to place the future into the executor to be run.
+    ///
+    /// Executors that can refuse work, such as one that is shutting down,
+    /// should return an error here. hyper fails the connection with the
+    /// returned error rather than losing the task.
+    ///
+    /// The default implementation calls `execute` and always succeeds.
+    fn try_execute(&self, fut: Fut) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.execute(fut);
+        Ok(())
+    }
+
+    /// Attempt to place a future into the executor, along with a description
+    /// of the task.
+    ///
+    /// hyper spawns its connection background tasks with this method. An
+    /// executor can use the [`TaskDescriptor`] to name the spawned task, so
+    /// that runtime consoles and profilers can attribute it to a connection.
+    /// Errors are handled the same as from `try_execute`.
+    ///
+    /// The default implementation ignores the descriptor and calls
+    /// `try_execute`.
+    fn execute_named(
+        &self,
+        fut: Fut,
+        task: &TaskDescriptor,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _ = task;
+        self.try_execute(fut)
+    }
+}
+
+/// A description of a background task spawned by hyper.
+///
+/// This is passed to [`Executor::execute_named`].
+#[derive(Clone, Debug)]
+pub struct TaskDescriptor {
+    connection_id: u64,
+    purpose: &'static str,
+}
+
+impl TaskDescriptor {
+    #[cfg(any(feature = "http2", feature = "http3"))]
+    pub(crate) fn new(connection_id: u64, purpose: &'static str) -> TaskDescriptor {
+        TaskDescriptor {
+            connection_id,
+            purpose,
+        }
+    }
+
+    /// An identifier of the connection that spawned this task.
+    ///
+    /// Identifiers are unique within the process, and all tasks spawned for
+    /// the same connection share one.
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// A short description of what the task does, such as
+    /// `"h2 server stream"`.
+    pub fn purpose(&self) -> &'static str {
+        self.purpose
+    }
+}
+
+/// Allocate a new identifier for a connection's tasks.
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+pub(crate) fn next_connection_id() -> u64 {
+    // `AtomicU64` isn't available on every target.
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed) as u64
 }
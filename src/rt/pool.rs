@@ -0,0 +1,89 @@
+//! Provides a trait for supplying the buffers connections read into.
+//!
+//! Example handing out a fixed number of preallocated buffers:
+//! ```rust
+//! use std::sync::Mutex;
+//!
+//! use bytes::BytesMut;
+//! use hyper::rt::BufferPool;
+//!
+//! pub struct FixedPool {
+//!     size: usize,
+//!     free: Mutex<Vec<BytesMut>>,
+//! }
+//!
+//! impl FixedPool {
+//!     pub fn new(count: usize, size: usize) -> FixedPool {
+//!         let free = (0..count).map(|_| BytesMut::with_capacity(size)).collect();
+//!         FixedPool {
+//!             size,
+//!             free: Mutex::new(free),
+//!         }
+//!     }
+//! }
+//!
+//! impl BufferPool for FixedPool {
+//!     fn acquire(&self) -> Option<BytesMut> {
+//!         let mut free = self.free.lock().unwrap();
+//!         // a buffer can only be handed out again once every body chunk
+//!         // read into it has been dropped
+//!         let idx = free.iter_mut().position(|buf| {
+//!             buf.clear();
+//!             buf.try_reclaim(self.size)
+//!         })?;
+//!         Some(free.swap_remove(idx))
+//!     }
+//!
+//!     fn release(&self, buf: BytesMut) {
+//!         self.free.lock().unwrap().push(buf);
+//!     }
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use bytes::BytesMut;
+
+/// A source of the buffers that HTTP/1 connections read into.
+///
+/// Set one on a client or server connection builder with its `buffer_pool`
+/// method. By default, each connection allocates its own read buffer, and
+/// grows it as needed, up to its `max_buf_size`. With a pool, a connection
+/// instead only reads into buffers taken from `acquire`, so a pool of
+/// preallocated buffers bounds the memory all its connections read into.
+///
+/// Body chunks are split off of the buffer they were read into, and share
+/// its memory. A connection keeps reading into the rest of its buffer, and
+/// reuses it from the start once every chunk has been dropped. If a buffer
+/// is full and still shared, the connection gives it back and acquires
+/// another. A message head must fit in a single buffer.
+///
+/// It is implemented for `Arc<P>`, so a pool can be shared between builders.
+pub trait BufferPool {
+    /// Take a buffer for a connection to read into.
+    ///
+    /// The connection reads into the buffer's spare capacity, after any
+    /// bytes it holds are cleared. Returning `None` fails the read, and
+    /// with it the connection.
+    fn acquire(&self) -> Option<BytesMut>;
+
+    /// Give back a buffer a connection is done with.
+    ///
+    /// Body chunks split off of `buf` may still be alive, in which case
+    /// its capacity can be regained with `BytesMut::try_reclaim` once
+    /// they've been dropped.
+    fn release(&self, buf: BytesMut);
+}
+
+impl<P> BufferPool for Arc<P>
+where
+    P: BufferPool + ?Sized,
+{
+    fn acquire(&self) -> Option<BytesMut> {
+        (**self).acquire()
+    }
+
+    fn release(&self, buf: BytesMut) {
+        (**self).release(buf)
+    }
+}
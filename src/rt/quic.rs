@@ -0,0 +1,123 @@
+//! Traits for QUIC transports, used by HTTP/3.
+//!
+//! hyper doesn't implement QUIC itself. Instead, the HTTP/3 connections in
+//! [`client::conn::http3`](crate::client::conn::http3) and
+//! [`server::conn::http3`](crate::server::conn::http3) are generic over an
+//! established QUIC connection implementing [`Connection`], and its streams
+//! implementing [`SendStream`] and [`RecvStream`]. Adapting a QUIC library
+//! means implementing these traits for its connection and stream types.
+//! Transports supporting unreliable datagrams can also implement
+//! [`Datagrams`], for HTTP datagrams.
+//!
+//! Error codes passed to these traits, such as when resetting a stream, are
+//! HTTP/3 application error codes.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+
+/// An established QUIC connection.
+///
+/// Its streams must be `Unpin`, which can be had by boxing them. Its
+/// bidirectional streams must also be `Send + 'static`, so that they can be
+/// [upgraded](crate::upgrade).
+pub trait Connection {
+    /// A stream that is both sent and received on.
+    type BidiStream: SendStream + RecvStream + Unpin + Send + 'static;
+    /// A stream that is only sent on.
+    type SendStream: SendStream + Unpin;
+    /// A stream that is only received on.
+    type RecvStream: RecvStream + Unpin;
+
+    /// Accept the next bidirectional stream opened by the peer.
+    ///
+    /// Returns `Poll::Ready(Ok(None))` once the connection is closed.
+    fn poll_accept_bidi(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<Self::BidiStream>>>;
+
+    /// Accept the next unidirectional stream opened by the peer.
+    ///
+    /// Returns `Poll::Ready(Ok(None))` once the connection is closed.
+    fn poll_accept_uni(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<Self::RecvStream>>>;
+
+    /// Open a new bidirectional stream.
+    ///
+    /// This should be pending while the peer's stream limit is reached.
+    fn poll_open_bidi(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Self::BidiStream>>;
+
+    /// Open a new unidirectional stream.
+    fn poll_open_uni(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Self::SendStream>>;
+
+    /// Close the connection immediately, with an error code and reason.
+    fn close(self: Pin<&mut Self>, code: u64, reason: &[u8]);
+
+    /// The unreliable datagrams of the connection, if the transport
+    /// supports them and the peer allows them.
+    ///
+    /// Default is `None`, so that HTTP datagrams are never sent, and none
+    /// are received.
+    fn datagrams(self: Pin<&mut Self>) -> Option<Pin<&mut dyn Datagrams>> {
+        None
+    }
+}
+
+/// The unreliable datagrams of a QUIC connection, from [RFC 9221].
+///
+/// [RFC 9221]: https://www.rfc-editor.org/rfc/rfc9221.html
+pub trait Datagrams {
+    /// Attempt to receive the payload of the next datagram.
+    ///
+    /// Returns `Poll::Ready(Ok(None))` once the connection is closed.
+    fn poll_recv_datagram(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<Bytes>>>;
+
+    /// Send a datagram with `data` as its payload.
+    ///
+    /// Datagrams may be lost, so this shouldn't wait for anything. A
+    /// datagram that can't be sent right away, or is too large, can be
+    /// dropped, or returned as an error.
+    fn send_datagram(self: Pin<&mut Self>, data: Bytes) -> io::Result<()>;
+}
+
+/// The sending half of a QUIC stream.
+pub trait SendStream {
+    /// Attempt to send bytes from `buf`, returning how many were sent.
+    fn poll_send(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8])
+        -> Poll<io::Result<usize>>;
+
+    /// Finish the stream, once everything sent was acknowledged or at least
+    /// accepted by the transport.
+    fn poll_finish(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Abruptly end the stream with an error code.
+    fn reset(self: Pin<&mut Self>, code: u64);
+}
+
+/// The receiving half of a QUIC stream.
+pub trait RecvStream {
+    /// Attempt to receive the next chunk of bytes.
+    ///
+    /// Returns `Poll::Ready(Ok(None))` once the peer finished the stream.
+    fn poll_recv(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<Option<Bytes>>>;
+
+    /// Ask the peer to stop sending on the stream, with an error code.
+    fn stop_sending(self: Pin<&mut Self>, code: u64);
+
+    /// The QUIC stream ID.
+    fn id(&self) -> u64;
+}
@@ -0,0 +1,246 @@
+//! Runtime adapters for smol.
+//!
+//! smol and async-std both build on [`async-io`] for timers and on the
+//! [`futures-io`] traits for IO, so the types in this module work with
+//! either runtime.
+//!
+//! Executors are not provided here, since each runtime spawns tasks
+//! differently; implementing [`Executor`](super::Executor) for one is a
+//! single call to its `spawn` function.
+//!
+//! [`async-io`]: https://docs.rs/async-io
+//! [`futures-io`]: https://docs.rs/futures-io
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use super::{Read, ReadBuf, ReadBufCursor, Sleep, Timer, Write};
+
+/// A [`Timer`] backed by `async_io::Timer`.
+#[derive(Clone, Debug, Default)]
+pub struct SmolTimer {
+    _priv: (),
+}
+
+impl SmolTimer {
+    /// Create a new `SmolTimer`.
+    pub fn new() -> Self {
+        SmolTimer { _priv: () }
+    }
+}
+
+impl Timer for SmolTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
+        Box::pin(SmolSleep {
+            inner: async_io::Timer::after(duration),
+        })
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        Box::pin(SmolSleep {
+            inner: async_io::Timer::at(deadline),
+        })
+    }
+
+    fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, new_deadline: Instant) {
+        if let Some(sleep) = sleep.as_mut().downcast_mut_pin::<SmolSleep>() {
+            sleep.get_mut().inner.set_at(new_deadline);
+        } else {
+            *sleep = self.sleep_until(new_deadline);
+        }
+    }
+}
+
+struct SmolSleep {
+    inner: async_io::Timer,
+}
+
+impl Future for SmolSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx).map(|_| ())
+    }
+}
+
+impl Sleep for SmolSleep {}
+
+/// Adapts an IO type between the `futures-io` traits and hyper's.
+///
+/// Wrapping a type implementing `futures_io::AsyncRead` and `AsyncWrite`,
+/// such as `smol::net::TcpStream`, makes it usable as a hyper connection.
+/// Wrapping a type implementing hyper's [`Read`] and [`Write`], such as
+/// [`Upgraded`](crate::upgrade::Upgraded), makes it usable with smol's IO
+/// utilities.
+pub struct SmolIo<T> {
+    inner: T,
+}
+
+impl<T> SmolIo<T> {
+    /// Wrap an IO type.
+    pub fn new(inner: T) -> Self {
+        SmolIo { inner }
+    }
+
+    /// Get a reference to the wrapped IO type.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped IO type.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consume this adapter, returning the wrapped IO type.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SmolIo<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SmolIo").field(&self.inner).finish()
+    }
+}
+
+impl<T> Read for SmolIo<T>
+where
+    T: futures_io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, buf.initialize_unfilled()))?;
+        // SAFETY: `initialize_unfilled` initialized the whole unfilled part,
+        // and `poll_read` cannot report more bytes than it was given.
+        unsafe {
+            buf.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Write for SmolIo<T>
+where
+    T: futures_io::AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+impl<T> futures_io::AsyncRead for SmolIo<T>
+where
+    T: Read + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf.unfilled()))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+}
+
+impl<T> futures_io::AsyncWrite for SmolIo<T>
+where
+    T: Write + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use async_io::Async;
+    use futures_util::future::poll_fn;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn timer_sleep_and_reset() {
+        let timer = SmolTimer::new();
+        let start = Instant::now();
+
+        let mut sleep = timer.sleep(Duration::from_secs(60));
+        timer.reset(&mut sleep, Instant::now() + Duration::from_millis(10));
+        sleep.await;
+
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn io_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = Async::<TcpStream>::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut client = SmolIo::new(client);
+        let mut server = SmolIo::new(Async::new(server).unwrap());
+
+        let n = poll_fn(|cx| Pin::new(&mut client).poll_write(cx, b"hello"))
+            .await
+            .unwrap();
+        assert_eq!(n, 5);
+
+        let mut dst = [0u8; 16];
+        let mut buf = ReadBuf::new(&mut dst);
+        poll_fn(|cx| Read::poll_read(Pin::new(&mut server), cx, buf.unfilled()))
+            .await
+            .unwrap();
+        assert_eq!(buf.filled(), b"hello");
+    }
+}
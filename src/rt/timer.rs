@@ -78,6 +78,17 @@ pub trait Timer {
     fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, new_deadline: Instant) {
         *sleep = self.sleep_until(new_deadline);
     }
+
+    /// Return the current instant.
+    ///
+    /// hyper computes the deadlines it passes to `sleep_until` and `reset`
+    /// from this, so a timer can supply the clock on targets where
+    /// `Instant::now` isn't available.
+    ///
+    /// The default implementation calls `Instant::now`.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
 }
 
 /// A future returned by a `Timer`.
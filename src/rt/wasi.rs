@@ -0,0 +1,179 @@
+//! Runtime adapters for WASI preview 2.
+//!
+//! [`WasiIo`] maps a pair of `wasi:io/streams` resources, such as the streams
+//! of a connected `wasi:sockets/tcp` socket, to hyper's [`Read`] and
+//! [`Write`] traits. This lets hyper's HTTP/1 client and server run inside a
+//! WASI component.
+//!
+//! WASI streams never block, but report readiness through a `pollable`.
+//! Waiting on pollables is the job of whatever executor drives hyper, which
+//! is plugged in with the [`Reactor`] trait.
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use wasi::io::poll::Pollable;
+use wasi::io::streams::{InputStream, OutputStream, StreamError};
+
+use super::{Read, ReadBufCursor, Write};
+
+/// Waits on WASI pollables on behalf of [`WasiIo`].
+///
+/// When a stream is not ready, `WasiIo` subscribes to it and hands the
+/// pollable to the reactor along with the waker of the current task.
+/// Executors usually collect these, and once no task can make progress,
+/// block on all of them with `wasi:io/poll.poll`, waking the tasks whose
+/// pollables are ready.
+pub trait Reactor {
+    /// Wake `waker` once `pollable` is ready.
+    fn schedule(&self, pollable: Pollable, waker: Waker);
+}
+
+impl<R: Reactor + ?Sized> Reactor for &R {
+    fn schedule(&self, pollable: Pollable, waker: Waker) {
+        (**self).schedule(pollable, waker)
+    }
+}
+
+impl<R: Reactor + ?Sized> Reactor for std::sync::Arc<R> {
+    fn schedule(&self, pollable: Pollable, waker: Waker) {
+        (**self).schedule(pollable, waker)
+    }
+}
+
+/// A [`Reactor`] that blocks the current thread until a pollable is ready.
+///
+/// This is only suitable when a single task does all of the IO, such as a
+/// component that serves one connection at a time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockingReactor;
+
+impl Reactor for BlockingReactor {
+    fn schedule(&self, pollable: Pollable, waker: Waker) {
+        pollable.block();
+        waker.wake();
+    }
+}
+
+/// Adapts a WASI `input-stream` and `output-stream` pair to hyper's IO
+/// traits.
+///
+/// WASI has no way to half-close an output stream other than dropping it,
+/// so `poll_shutdown` only flushes. Use [`WasiIo::into_parts`] to get the
+/// streams back, and drop them to close.
+pub struct WasiIo<R> {
+    input: InputStream,
+    output: OutputStream,
+    reactor: R,
+    flushing: bool,
+}
+
+impl<R: Reactor> WasiIo<R> {
+    /// Create an adapter over the given streams.
+    pub fn new(input: InputStream, output: OutputStream, reactor: R) -> Self {
+        WasiIo {
+            input,
+            output,
+            reactor,
+            flushing: false,
+        }
+    }
+
+    /// Consume this adapter, returning the wrapped streams.
+    pub fn into_parts(self) -> (InputStream, OutputStream) {
+        (self.input, self.output)
+    }
+
+    /// Waits until the output stream accepts writes, returning how many
+    /// bytes it will take.
+    fn poll_check_write(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        match self.output.check_write() {
+            Ok(0) => {
+                self.reactor
+                    .schedule(self.output.subscribe(), cx.waker().clone());
+                Poll::Pending
+            }
+            Ok(n) => Poll::Ready(Ok(n.min(usize::MAX as u64) as usize)),
+            Err(e) => Poll::Ready(Err(stream_error(e))),
+        }
+    }
+}
+
+impl<R> fmt::Debug for WasiIo<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasiIo")
+            .field("input", &self.input)
+            .field("output", &self.output)
+            .finish()
+    }
+}
+
+impl<R: Reactor + Unpin> Read for WasiIo<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = match buf.hint() {
+            Some(hint) => hint.min(buf.remaining()),
+            None => buf.remaining(),
+        };
+        if len == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        match self.input.read(len as u64) {
+            Ok(bytes) if bytes.is_empty() => {
+                self.reactor
+                    .schedule(self.input.subscribe(), cx.waker().clone());
+                Poll::Pending
+            }
+            Ok(bytes) => {
+                buf.put_slice(&bytes);
+                Poll::Ready(Ok(()))
+            }
+            // A closed input stream is EOF.
+            Err(StreamError::Closed) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(stream_error(e))),
+        }
+    }
+}
+
+impl<R: Reactor + Unpin> Write for WasiIo<R> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        let n = ready!(me.poll_check_write(cx))?.min(buf.len());
+        me.output.write(&buf[..n]).map_err(stream_error)?;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        if !me.flushing {
+            me.output.flush().map_err(stream_error)?;
+            me.flushing = true;
+        }
+        // A flush is complete once the stream accepts writes again.
+        ready!(me.poll_check_write(cx))?;
+        me.flushing = false;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+fn stream_error(e: StreamError) -> io::Error {
+    match e {
+        StreamError::Closed => io::Error::from(io::ErrorKind::BrokenPipe),
+        StreamError::LastOperationFailed(e) => {
+            io::Error::new(io::ErrorKind::Other, e.to_debug_string())
+        }
+    }
+}
@@ -7,12 +7,21 @@ use std::time::Duration;
 
 use crate::rt::{Read, Write};
 use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
 
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::buf::BufPool;
+use crate::common::date::DateHeader;
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
 use crate::common::{task, Future, Pin, Poll, Unpin};
 use crate::proto;
 use crate::service::HttpService;
-use crate::{common::time::Time, rt::Timer};
+use crate::Response;
+use crate::{
+    common::time::Time,
+    rt::{BufferPool, Clock, ConnectionEvent, Metrics, Timer},
+};
 
 type Http1Dispatcher<T, B, S> = proto::h1::Dispatcher<
     proto::h1::dispatch::Server<S, IncomingBody>,
@@ -34,6 +43,7 @@ pin_project_lite::pin_project! {
         S: HttpService<IncomingBody>,
     {
         conn: Http1Dispatcher<T, S::ResBody, S>,
+        timer: Time,
     }
 }
 
@@ -41,14 +51,34 @@ pin_project_lite::pin_project! {
 #[derive(Clone, Debug)]
 pub struct Builder {
     timer: Time,
+    metrics: MetricsSink,
+    events: EventSink,
     h1_half_close: bool,
+    h1_manual_expect_continue: bool,
     h1_keep_alive: bool,
+    h1_max_requests: Option<usize>,
     h1_title_case_headers: bool,
     h1_preserve_header_case: bool,
+    h1_preserve_header_order: bool,
+    h1_preserve_raw_head: bool,
     h1_header_read_timeout: Option<Duration>,
+    h1_header_read_timeout_response: Option<(StatusCode, HeaderMap)>,
+    h1_body_read_timeout: Option<Duration>,
+    h1_idle_timeout: Option<Duration>,
+    h1_max_headers: Option<usize>,
+    h1_on_parse_error: proto::h1::OnParseError,
+    h1_violations: ViolationPolicy,
+    h1_on_violation: proto::h1::OnViolation,
+    h1_write_timeout: Option<Duration>,
     h1_writev: Option<bool>,
+    h1_write_coalesce_threshold: Option<usize>,
     max_buf_size: Option<usize>,
+    max_write_buf_size: Option<usize>,
+    buffer_pool: BufPool,
+    date_header: DateHeader,
+    max_body_drain: u64,
     pipeline_flush: bool,
+    poll_budget: usize,
 }
 
 /// Deconstructed parts of a `Connection`.
@@ -73,6 +103,114 @@ pub struct Parts<T, S> {
     _inner: (),
 }
 
+/// How strictly the framing of requests is checked.
+///
+/// Requests can be smuggled past a proxy in front of the server when the
+/// two disagree about where a request ends. A profile decides which
+/// [`Violation`]s are tolerated and which get the request rejected with a
+/// `400 Bad Request`, and [`Builder::allow_violation`] overrides it for
+/// single ones, to match what the proxy does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strictness {
+    /// Every violation is tolerated.
+    Lenient,
+    /// Bare CRs are rejected, and the other violations tolerated.
+    ///
+    /// This is the default.
+    Default,
+    /// Every violation is rejected.
+    Strict,
+}
+
+/// A deviation from HTTP/1.1 request framing, which proxies may handle
+/// differently.
+///
+/// See [`Strictness`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Violation {
+    /// A request has both `Transfer-Encoding` and `Content-Length` headers.
+    ///
+    /// If tolerated, `Transfer-Encoding` decides the length of the body.
+    TransferEncodingWithContentLength,
+    /// A CR not followed by LF in a request head.
+    ///
+    /// If tolerated, it is replaced with a space, as RFC 9112 allows.
+    BareCr,
+    /// A line of a request head ends with LF alone, instead of CRLF.
+    BareLf,
+    /// A chunked request body has chunk extensions.
+    ///
+    /// If tolerated, they are ignored. If not, reading the body fails.
+    ChunkExtension,
+}
+
+impl Strictness {
+    fn tolerates(self, violation: Violation) -> bool {
+        match self {
+            Strictness::Lenient => true,
+            Strictness::Default => violation != Violation::BareCr,
+            Strictness::Strict => false,
+        }
+    }
+}
+
+impl Violation {
+    fn bit(self) -> u8 {
+        match self {
+            Violation::TransferEncodingWithContentLength => 1,
+            Violation::BareCr => 1 << 1,
+            Violation::BareLf => 1 << 2,
+            Violation::ChunkExtension => 1 << 3,
+        }
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Violation::TransferEncodingWithContentLength => {
+                "both transfer-encoding and content-length parsed"
+            }
+            Violation::BareCr => "bare CR in message head",
+            Violation::BareLf => "line of message head ends with bare LF",
+            Violation::ChunkExtension => "chunk extension in chunked body",
+        }
+    }
+}
+
+/// Which [`Violation`]s requests are rejected for.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ViolationPolicy {
+    strictness: Strictness,
+    allowed: u8,
+    rejected: u8,
+}
+
+impl ViolationPolicy {
+    pub(crate) fn new(strictness: Strictness) -> ViolationPolicy {
+        ViolationPolicy {
+            strictness,
+            allowed: 0,
+            rejected: 0,
+        }
+    }
+
+    pub(crate) fn allows(&self, violation: Violation) -> bool {
+        if self.allowed & violation.bit() != 0 {
+            true
+        } else if self.rejected & violation.bit() != 0 {
+            false
+        } else {
+            self.strictness.tolerates(violation)
+        }
+    }
+}
+
+impl Default for ViolationPolicy {
+    fn default() -> ViolationPolicy {
+        ViolationPolicy::new(Strictness::Default)
+    }
+}
+
 // ===== impl Connection =====
 
 impl<I, S> fmt::Debug for Connection<I, S>
@@ -106,6 +244,35 @@ where
         self.conn.disable_keep_alive();
     }
 
+    /// Start a graceful shutdown process for this connection, giving up on
+    /// it after `timeout`.
+    ///
+    /// The returned future drives the connection like polling it would. If
+    /// the requests in flight finish before the deadline, it resolves with
+    /// `Ok(0)`. Otherwise the connection is closed without finishing them,
+    /// and it resolves with how many requests were aborted. HTTP/1 serves
+    /// one request at a time, so this is 0 or 1: pipelined requests not read
+    /// yet aren't counted.
+    ///
+    /// Requires a [`timer`](Builder::timer).
+    pub fn graceful_shutdown_with_timeout(
+        mut self: Pin<&mut Self>,
+        timeout: Duration,
+    ) -> impl Future<Output = crate::Result<usize>> + '_
+    where
+        I: 'static,
+    {
+        self.as_mut().graceful_shutdown();
+        let mut deadline = self.timer.sleep(timeout);
+        futures_util::future::poll_fn(move |cx| {
+            if let Poll::Ready(res) = self.as_mut().poll(cx) {
+                return Poll::Ready(res.map(|()| 0));
+            }
+            ready!(deadline.as_mut().poll(cx));
+            Poll::Ready(Ok(self.conn.abort()))
+        })
+    }
+
     /// Return the inner IO object, and additional information.
     ///
     /// If the IO object has been "rewound" the io will not contain those bytes rewound.
@@ -208,14 +375,34 @@ impl Builder {
     pub fn new() -> Self {
         Self {
             timer: Time::Empty,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
             h1_half_close: false,
+            h1_manual_expect_continue: false,
             h1_keep_alive: true,
+            h1_max_requests: None,
             h1_title_case_headers: false,
             h1_preserve_header_case: false,
+            h1_preserve_header_order: false,
+            h1_preserve_raw_head: false,
             h1_header_read_timeout: None,
+            h1_header_read_timeout_response: None,
+            h1_body_read_timeout: None,
+            h1_idle_timeout: None,
+            h1_on_parse_error: proto::h1::OnParseError::default(),
+            h1_violations: ViolationPolicy::default(),
+            h1_on_violation: proto::h1::OnViolation::default(),
+            h1_max_headers: None,
+            h1_write_timeout: None,
             h1_writev: None,
+            h1_write_coalesce_threshold: None,
             max_buf_size: None,
+            max_write_buf_size: None,
+            buffer_pool: BufPool::default(),
+            date_header: DateHeader::default(),
+            max_body_drain: 0,
             pipeline_flush: false,
+            poll_budget: proto::h1::dispatch::DEFAULT_POLL_BUDGET,
         }
     }
     /// Set whether HTTP/1 connections should support half-closures.
@@ -231,6 +418,21 @@ impl Builder {
         self
     }
 
+    /// Set whether the service decides if `100 Continue` is sent to
+    /// requests with `Expect: 100-continue`.
+    ///
+    /// By default, it is sent as soon as the service starts reading the
+    /// body. If enabled, such requests carry an
+    /// [`ExpectContinue`](crate::ext::ExpectContinue) in their extensions
+    /// instead, which the service uses to send or skip it, such as after
+    /// checking the request's credentials.
+    ///
+    /// Default is `false`.
+    pub fn manual_expect_continue(&mut self, val: bool) -> &mut Self {
+        self.h1_manual_expect_continue = val;
+        self
+    }
+
     /// Enables or disables HTTP/1 keep-alive.
     ///
     /// Default is true.
@@ -239,6 +441,17 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of requests served on each connection.
+    ///
+    /// The response to the last one is sent with `Connection: close`, and
+    /// the connection is closed after it, so clients open a new one.
+    ///
+    /// Default is no limit.
+    pub fn max_requests_per_connection(&mut self, max: usize) -> &mut Self {
+        self.h1_max_requests = Some(max);
+        self
+    }
+
     /// Set whether HTTP/1 connections will write header names as title case at
     /// the socket level.
     ///
@@ -264,8 +477,34 @@ impl Builder {
         self
     }
 
+    /// Set whether to support preserving original header order.
+    ///
+    /// This records the order in which the headers of each request are
+    /// received, such as for fingerprinting clients, in an
+    /// [`OriginalHeaderOrder`](crate::ext::OriginalHeaderOrder) extension on
+    /// the `Request`.
+    ///
+    /// Default is false.
+    pub fn preserve_header_order(&mut self, enabled: bool) -> &mut Self {
+        self.h1_preserve_header_order = enabled;
+        self
+    }
+
+    /// Set whether to keep the raw head of each request.
+    ///
+    /// This puts the request line and headers, exactly as received, in a
+    /// [`RawRequestHead`](crate::ext::RawRequestHead) extension on the
+    /// `Request`, such as for logging requests that are rejected.
+    ///
+    /// Default is false.
+    pub fn preserve_raw_head(&mut self, enabled: bool) -> &mut Self {
+        self.h1_preserve_raw_head = enabled;
+        self
+    }
+
     /// Set a timeout for reading client request headers. If a client does not
-    /// transmit the entire header within this time, the connection is closed.
+    /// transmit the entire header within this time, the connection is closed,
+    /// after writing any [`header_read_timeout_response`](Builder::header_read_timeout_response).
     ///
     /// Default is None.
     pub fn header_read_timeout(&mut self, read_timeout: Duration) -> &mut Self {
@@ -273,6 +512,178 @@ impl Builder {
         self
     }
 
+    /// Set a response to write when the
+    /// [`header_read_timeout`](Builder::header_read_timeout) fires, before the
+    /// connection is closed.
+    ///
+    /// Only the status and headers of `response` are sent, with an empty body
+    /// and a `connection: close` header unless it has its own `connection`
+    /// header.
+    ///
+    /// Default is to close the connection without a response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use hyper::server::conn::http1;
+    /// # use hyper::{Response, StatusCode};
+    /// # use std::time::Duration;
+    /// let mut http = http1::Builder::new();
+    /// http.header_read_timeout(Duration::from_secs(30))
+    ///     .header_read_timeout_response(
+    ///         Response::builder()
+    ///             .status(StatusCode::REQUEST_TIMEOUT)
+    ///             .header("retry-after", "1")
+    ///             .body(())
+    ///             .unwrap(),
+    ///     );
+    /// ```
+    pub fn header_read_timeout_response(&mut self, response: Response<()>) -> &mut Self {
+        let (parts, ()) = response.into_parts();
+        self.h1_header_read_timeout_response = Some((parts.status, parts.headers));
+        self
+    }
+
+    /// Set a timeout for reading a request body.
+    ///
+    /// If the client sends no more of the body within this time, while it's
+    /// being read, reading fails with an error for which
+    /// [`Error::is_timeout`](crate::Error::is_timeout) returns true, and the
+    /// connection isn't read from anymore. The service can still send a
+    /// response, such as a `408 Request Timeout`. The timeout restarts each
+    /// time some of the body is received, and doesn't run while the service
+    /// isn't reading it. Requires a [`timer`](Builder::timer).
+    ///
+    /// Default is None.
+    pub fn body_read_timeout(&mut self, read_timeout: Duration) -> &mut Self {
+        self.h1_body_read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Set a timeout for idle connections.
+    ///
+    /// If no new request starts within this time, while the connection
+    /// waits for one, the connection is closed. It starts when the
+    /// connection is served and again after each response, and stops once
+    /// the first bytes of a request arrive, after which the
+    /// [`header_read_timeout`](Builder::header_read_timeout) applies.
+    /// Requires a [`timer`](Builder::timer).
+    ///
+    /// Default is None.
+    pub fn idle_timeout(&mut self, idle_timeout: Duration) -> &mut Self {
+        self.h1_idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set the maximum number of headers.
+    ///
+    /// When a request is received, the parser will reserve a buffer to store
+    /// headers for optimal performance.
+    ///
+    /// If the server receives more headers than the buffer size, it responds
+    /// to the client with "431 Request Header Fields Too Large".
+    ///
+    /// Up to 100 headers are parsed on the stack. A larger limit allocates
+    /// the buffer on the heap for each request parsed, which has some
+    /// overhead.
+    ///
+    /// Default is 100.
+    pub fn max_headers(&mut self, val: usize) -> &mut Self {
+        self.h1_max_headers = Some(val);
+        self
+    }
+
+    /// Set a callback building the response sent to requests that can't be
+    /// parsed.
+    ///
+    /// By default, such requests get an empty `400 Bad Request`, `414 URI
+    /// Too Long` or `431 Request Header Fields Too Large` response, and the
+    /// connection is closed. The callback is given the parse error, and the
+    /// response it returns is sent instead. The connection is still closed
+    /// afterwards, and the `Content-Length` header is set from the body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use bytes::Bytes;
+    /// # use hyper::{Response, StatusCode};
+    /// # use hyper::server::conn::http1;
+    /// let mut http = http1::Builder::new();
+    /// http.on_parse_error(|err| {
+    ///     eprintln!("bad request: {}", err);
+    ///     Response::builder()
+    ///         .status(StatusCode::BAD_REQUEST)
+    ///         .header("content-type", "text/html")
+    ///         .body(Bytes::from_static(b"<h1>Bad request</h1>"))
+    ///         .unwrap()
+    /// });
+    /// ```
+    pub fn on_parse_error<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&crate::Error) -> Response<Bytes> + Send + Sync + 'static,
+    {
+        self.h1_on_parse_error = proto::h1::OnParseError(Some(Arc::new(callback)));
+        self
+    }
+
+    /// Set how strictly the framing of requests is checked.
+    ///
+    /// Overrides set with [`allow_violation`](Builder::allow_violation)
+    /// still apply, whichever is called first.
+    ///
+    /// Default is [`Strictness::Default`].
+    pub fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.h1_violations.strictness = strictness;
+        self
+    }
+
+    /// Set whether requests with a violation are tolerated, instead of
+    /// following the [`strictness`](Builder::strictness) profile for it.
+    pub fn allow_violation(&mut self, violation: Violation, allowed: bool) -> &mut Self {
+        let bit = violation.bit();
+        if allowed {
+            self.h1_violations.allowed |= bit;
+            self.h1_violations.rejected &= !bit;
+        } else {
+            self.h1_violations.rejected |= bit;
+            self.h1_violations.allowed &= !bit;
+        }
+        self
+    }
+
+    /// Set a callback called with the [`Violation`] each time a request is
+    /// rejected for one, such as to log it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use hyper::server::conn::http1;
+    /// let mut http = http1::Builder::new();
+    /// http.strictness(http1::Strictness::Strict)
+    ///     .allow_violation(http1::Violation::BareLf, true)
+    ///     .on_violation(|violation| eprintln!("rejected request: {:?}", violation));
+    /// ```
+    pub fn on_violation<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(Violation) + Send + Sync + 'static,
+    {
+        self.h1_on_violation = proto::h1::OnViolation(Some(Arc::new(callback)));
+        self
+    }
+
+    /// Set a timeout for flushing writes to the connection.
+    ///
+    /// If a flush stays pending for longer than this, such as when the peer
+    /// stopped reading and the TCP window is full, the connection is closed
+    /// with a timeout error. Requires a [`timer`](Builder::timer),
+    /// and is ignored without one.
+    ///
+    /// Default is None.
+    pub fn write_timeout(&mut self, write_timeout: Duration) -> &mut Self {
+        self.h1_write_timeout = Some(write_timeout);
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -290,9 +701,28 @@ impl Builder {
         self
     }
 
+    /// Set the size at or below which body frames are copied into the
+    /// connection's write buffer, instead of being queued to be written on
+    /// their own.
+    ///
+    /// Copied frames go out in the same write as the message head and each
+    /// other. That is cheaper for transports where every write has a fixed
+    /// cost, such as TLS, which seals each write into at least one record,
+    /// and may be worth a much larger threshold there than for plain TCP.
+    /// Frames are only copied while nothing is queued ahead of them.
+    ///
+    /// This only applies when vectored writes are used, see `writev`, since
+    /// every frame is copied otherwise.
+    ///
+    /// Default is 0, so frames are never copied.
+    pub fn write_coalesce_threshold(&mut self, len: usize) -> &mut Self {
+        self.h1_write_coalesce_threshold = Some(len);
+        self
+    }
+
     /// Set the maximum buffer size for the connection.
     ///
-    /// Default is ~400kb.
+    /// Default is ~400kb, or 16kb with the `embedded` feature.
     ///
     /// # Panics
     ///
@@ -306,6 +736,58 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of bytes of responses buffered for writing.
+    ///
+    /// Once this many are waiting to be written to the connection, such as
+    /// when the client reads slower than the service produces a response
+    /// body, the body isn't polled for more data until some are written.
+    /// A single chunk of the body is always buffered whole, so the buffer
+    /// can exceed this by at most one chunk. This applies even with
+    /// [`pipeline_flush`](Builder::pipeline_flush) enabled.
+    ///
+    /// Default is the [`max_buf_size`](Builder::max_buf_size).
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the passed `max` is 0.
+    pub fn max_write_buf_size(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "the max_write_buf_size cannot be 0");
+        self.max_write_buf_size = Some(max);
+        self
+    }
+
+    /// Set a pool to take the buffers connections read into from.
+    ///
+    /// Connections then never allocate a read buffer themselves, and a
+    /// request head must fit in one of the pool's buffers. See
+    /// [`BufferPool`] for how the buffers are used.
+    ///
+    /// Default is for each connection to allocate its own.
+    pub fn buffer_pool<P>(&mut self, pool: P) -> &mut Self
+    where
+        P: BufferPool + Send + Sync + 'static,
+    {
+        self.buffer_pool = BufPool::new(pool);
+        self
+    }
+
+    /// Set how many bytes of an unread request body may be drained to keep
+    /// the connection alive.
+    ///
+    /// A service can reject a request based on its head alone, such as when
+    /// it fails authentication or is too large, by responding without ever
+    /// polling the body. hyper then never sends a `100 Continue`, and
+    /// discards the body once the service drops it. Bodies larger than this
+    /// limit aren't read any further, and the connection is closed after the
+    /// response instead. A response with a `Connection: close` header
+    /// always closes the connection without draining.
+    ///
+    /// Default is 0, which only discards what has already been read.
+    pub fn max_body_drain(&mut self, max: u64) -> &mut Self {
+        self.max_body_drain = max;
+        self
+    }
+
     /// Aggregates flushes to better support pipelined responses.
     ///
     /// Experimental, may have bugs.
@@ -316,6 +798,24 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of read/write iterations a connection makes
+    /// each time it is polled, before yielding back to the executor.
+    ///
+    /// A connection that is always ready, such as one receiving many
+    /// pipelined requests, could otherwise starve other tasks on the same
+    /// executor. Lower values favor fairness, higher values favor throughput.
+    ///
+    /// Default is 16.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the passed `budget` is 0.
+    pub fn poll_budget(&mut self, budget: usize) -> &mut Self {
+        assert!(budget > 0, "the poll_budget must be greater than 0");
+        self.poll_budget = budget;
+        self
+    }
+
     /// Set the timer used in background tasks.
     pub fn timer<M>(&mut self, timer: M) -> &mut Self
     where
@@ -325,11 +825,53 @@ impl Builder {
         self
     }
 
+    /// Set the clock that the time in `Date` headers is read from.
+    ///
+    /// Responses that don't already have a `Date` header get one with the
+    /// clock's time, or none if it returns `None`.
+    ///
+    /// Default is the system clock, or no `Date` headers with the `embedded` feature.
+    pub fn clock<C>(&mut self, clock: C) -> &mut Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.date_header = DateHeader::new(clock);
+        self
+    }
+
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Self
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
     /// Bind a connection together with a [`Service`](crate::service::Service).
     ///
     /// This returns a Future that must be polled in order for HTTP to be
     /// driven on the connection.
     ///
+    /// Neither the service nor the IO need to be `Send` (unless upgrades are
+    /// enabled, which require a `Send` IO), so services holding `!Send` state
+    /// can be served by polling the returned future on a single-threaded
+    /// executor.
+    ///
     /// # Example
     ///
     /// ```
@@ -363,21 +905,52 @@ impl Builder {
     {
         let mut conn = proto::Conn::new(io);
         conn.set_timer(self.timer.clone());
+        conn.set_date_header(self.date_header.clone());
+        conn.set_metrics(self.metrics.clone());
         if !self.h1_keep_alive {
             conn.disable_keep_alive();
         }
+        if let Some(max) = self.h1_max_requests {
+            conn.set_max_requests(max);
+        }
         if self.h1_half_close {
             conn.set_allow_half_close();
         }
+        if self.h1_manual_expect_continue {
+            conn.set_manual_expect_continue();
+        }
         if self.h1_title_case_headers {
             conn.set_title_case_headers();
         }
         if self.h1_preserve_header_case {
             conn.set_preserve_header_case();
         }
+        if self.h1_preserve_header_order {
+            conn.set_preserve_header_order();
+        }
+        if self.h1_preserve_raw_head {
+            conn.set_raw_request_head();
+        }
         if let Some(header_read_timeout) = self.h1_header_read_timeout {
             conn.set_http1_header_read_timeout(header_read_timeout);
         }
+        if let Some((status, ref headers)) = self.h1_header_read_timeout_response {
+            conn.set_header_read_timeout_response(status, headers.clone());
+        }
+        if let Some(body_read_timeout) = self.h1_body_read_timeout {
+            conn.set_http1_body_read_timeout(body_read_timeout);
+        }
+        if let Some(idle_timeout) = self.h1_idle_timeout {
+            conn.set_idle_timeout(idle_timeout);
+        }
+        if let Some(max) = self.h1_max_headers {
+            conn.set_h1_max_headers(max);
+        }
+        conn.set_on_parse_error(self.h1_on_parse_error.clone());
+        conn.set_violations(self.h1_violations, self.h1_on_violation.clone());
+        if let Some(write_timeout) = self.h1_write_timeout {
+            conn.set_write_timeout(write_timeout);
+        }
         if let Some(writev) = self.h1_writev {
             if writev {
                 conn.set_write_strategy_queue();
@@ -386,12 +959,30 @@ impl Builder {
             }
         }
         conn.set_flush_pipeline(self.pipeline_flush);
+        if let Some(len) = self.h1_write_coalesce_threshold {
+            conn.set_write_coalesce_threshold(len);
+        }
         if let Some(max) = self.max_buf_size {
             conn.set_max_buf_size(max);
         }
-        let sd = proto::h1::dispatch::Server::new(service);
-        let proto = proto::h1::Dispatcher::new(sd, conn);
-        Connection { conn: proto }
+        if let Some(max) = self.max_write_buf_size {
+            conn.set_max_write_buf_size(max);
+        }
+        conn.set_buffer_pool(self.buffer_pool.clone());
+        conn.set_max_drain(self.max_body_drain);
+        let opened = self.events.opened();
+        let sd = proto::h1::dispatch::Server::new(
+            service,
+            self.metrics.clone(),
+            opened.lifecycle().clone(),
+        );
+        let mut proto = proto::h1::Dispatcher::new(sd, conn);
+        proto.set_lifecycle(opened);
+        proto.set_poll_budget(self.poll_budget);
+        Connection {
+            conn: proto,
+            timer: self.timer.clone(),
+        }
     }
 }
 
@@ -427,6 +1018,28 @@ mod upgrades {
         pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
             Pin::new(self.inner.as_mut().unwrap()).graceful_shutdown()
         }
+
+        /// Start a graceful shutdown process for this connection, giving up
+        /// on it after `timeout`.
+        ///
+        /// See [`Connection::graceful_shutdown_with_timeout`].
+        pub fn graceful_shutdown_with_timeout(
+            mut self: Pin<&mut Self>,
+            timeout: Duration,
+        ) -> impl Future<Output = crate::Result<usize>> + '_
+        where
+            I: Send + 'static,
+        {
+            self.as_mut().graceful_shutdown();
+            let mut deadline = self.inner.as_ref().unwrap().timer.sleep(timeout);
+            futures_util::future::poll_fn(move |cx| {
+                if let Poll::Ready(res) = self.as_mut().poll(cx) {
+                    return Poll::Ready(res.map(|()| 0));
+                }
+                ready!(deadline.as_mut().poll(cx));
+                Poll::Ready(Ok(self.inner.as_mut().unwrap().conn.abort()))
+            })
+        }
     }
 
     impl<I, B, S> Future for UpgradeableConnection<I, S>
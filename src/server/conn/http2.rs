@@ -9,11 +9,17 @@ use crate::rt::{Read, Write};
 use pin_project_lite::pin_project;
 
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::date::DateHeader;
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
 use crate::common::{task, Future, Pin, Poll, Unpin};
 use crate::proto;
 use crate::rt::bounds::Http2ConnExec;
 use crate::service::HttpService;
-use crate::{common::time::Time, rt::Timer};
+use crate::{
+    common::time::Time,
+    rt::{Clock, ConnectionEvent, Metrics, Timer},
+};
 
 pin_project! {
     /// A [`Future`](core::future::Future) representing an HTTP/2 connection, bound to a
@@ -28,6 +34,7 @@ pin_project! {
         S: HttpService<IncomingBody>,
     {
         conn: proto::h2::Server<T, S, S::ResBody, E>,
+        timer: Time,
     }
 }
 
@@ -36,6 +43,8 @@ pin_project! {
 pub struct Builder<E> {
     exec: E,
     timer: Time,
+    metrics: MetricsSink,
+    events: EventSink,
     h2_builder: proto::h2::server::Config,
 }
 
@@ -72,6 +81,40 @@ where
     pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
         self.conn.graceful_shutdown();
     }
+
+    /// Start a graceful shutdown process for this connection, giving up on
+    /// it after `timeout`.
+    ///
+    /// The returned future drives the connection like polling it would. If
+    /// the requests in flight finish before the deadline, it resolves with
+    /// `Ok(0)`. Otherwise a `GOAWAY` cancelling them is sent, the connection
+    /// is closed without finishing them, and it resolves with how many
+    /// streams there were.
+    ///
+    /// Requires a [`timer`](Builder::timer).
+    pub fn graceful_shutdown_with_timeout(
+        mut self: Pin<&mut Self>,
+        timeout: Duration,
+    ) -> impl Future<Output = crate::Result<usize>> + '_
+    where
+        I: 'static,
+    {
+        self.as_mut().graceful_shutdown();
+        let mut deadline = self.timer.sleep(timeout);
+        let mut aborted = None;
+        futures_util::future::poll_fn(move |cx| {
+            if aborted.is_none() {
+                if let Poll::Ready(res) = self.as_mut().poll(cx) {
+                    return Poll::Ready(res.map(|()| 0));
+                }
+                ready!(deadline.as_mut().poll(cx));
+                aborted = Some(self.conn.abort());
+            }
+            // Keep going until the GOAWAY cancelling the streams is written.
+            ready!(self.conn.poll_aborted(cx));
+            Poll::Ready(Ok(aborted.unwrap_or(0)))
+        })
+    }
 }
 
 impl<I, B, S, E> Future for Connection<I, S, E>
@@ -110,6 +153,8 @@ impl<E> Builder<E> {
         Self {
             exec: exec,
             timer: Time::Empty,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
             h2_builder: Default::default(),
         }
     }
@@ -210,6 +255,19 @@ impl<E> Builder<E> {
         self
     }
 
+    /// Sets a timeout for writes to the connection to make progress.
+    ///
+    /// If a write or flush to the underlying IO stays pending for longer
+    /// than this, such as when the peer stopped reading, the connection is
+    /// closed with a timeout error. Requires a [`timer`](Builder::timer),
+    /// and is ignored without one.
+    ///
+    /// Default is no timeout.
+    pub fn write_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.h2_builder.write_timeout = Some(timeout);
+        self
+    }
+
     /// Set the maximum write buffer size for each HTTP/2 stream.
     ///
     /// Default is currently ~400KB, but may change.
@@ -248,10 +306,53 @@ impl<E> Builder<E> {
         self
     }
 
+    /// Set the clock that the time in `Date` headers is read from.
+    ///
+    /// Responses that don't already have a `Date` header get one with the
+    /// clock's time, or none if it returns `None`.
+    ///
+    /// Default is the system clock.
+    pub fn clock<C>(&mut self, clock: C) -> &mut Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.h2_builder.date_header = DateHeader::new(clock);
+        self
+    }
+
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Self
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
     /// Bind a connection together with a [`Service`](crate::service::Service).
     ///
     /// This returns a Future that must be polled in order for HTTP to be
     /// driven on the connection.
+    ///
+    /// Each request is answered in a task spawned on the executor, so the
+    /// service's futures and response bodies only need to be `Send` if the
+    /// executor requires it. With an executor that spawns onto the current
+    /// thread, such as one using `tokio::task::spawn_local`, services may
+    /// hold `!Send` state like an `Rc`.
     pub fn serve_connection<S, I, Bd>(&self, io: I, service: S) -> Connection<I, S, E>
     where
         S: HttpService<IncomingBody, ResBody = Bd>,
@@ -267,7 +368,12 @@ impl<E> Builder<E> {
             &self.h2_builder,
             self.exec.clone(),
             self.timer.clone(),
+            self.metrics.clone(),
+            &self.events,
         );
-        Connection { conn: proto }
+        Connection {
+            conn: proto,
+            timer: self.timer.clone(),
+        }
     }
 }
@@ -0,0 +1,228 @@
+//! HTTP/3 Server Connections
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use pin_project_lite::pin_project;
+
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::date::DateHeader;
+use crate::common::lifecycle::EventSink;
+use crate::common::metrics::MetricsSink;
+use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::proto;
+use crate::rt::bounds::Http3ConnExec;
+use crate::rt::quic;
+use crate::rt::{Clock, ConnectionEvent, Metrics};
+use crate::service::HttpService;
+
+pin_project! {
+    /// A [`Future`](core::future::Future) representing an HTTP/3 connection, bound to a
+    /// [`Service`](crate::service::Service), returned from
+    /// [`Builder::serve_connection`](struct.Builder.html#method.serve_connection).
+    ///
+    /// To drive HTTP on this connection this future **must be polled**, typically with
+    /// `.await`. If it isn't polled, no progress will be made on this connection.
+    #[must_use = "futures do nothing unless polled"]
+    pub struct Connection<C, S, E>
+    where
+        C: quic::Connection,
+        S: HttpService<IncomingBody>,
+    {
+        conn: proto::h3::Server<C, S, E>,
+    }
+}
+
+/// A configuration builder for HTTP/3 server connections.
+#[derive(Clone, Debug)]
+pub struct Builder<E> {
+    exec: E,
+    metrics: MetricsSink,
+    events: EventSink,
+    h3_builder: proto::h3::Config,
+}
+
+// ===== impl Connection =====
+
+impl<C, S, E> fmt::Debug for Connection<C, S, E>
+where
+    C: quic::Connection,
+    S: HttpService<IncomingBody>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection").finish()
+    }
+}
+
+impl<C, B, S, E> Connection<C, S, E>
+where
+    C: quic::Connection + Unpin,
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ConnExec<S::Future, B, C::BidiStream>,
+{
+    /// Start a graceful shutdown process for this connection.
+    ///
+    /// This sends a GOAWAY frame, so that the client stops making requests,
+    /// and closes the connection once the requests already received are
+    /// answered. This `Connection` should continue to be polled until
+    /// shutdown can finish.
+    ///
+    /// # Note
+    ///
+    /// This should only be called while the `Connection` future is still
+    /// pending. If called after `Connection::poll` has resolved, this does
+    /// nothing.
+    pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+        self.conn.graceful_shutdown();
+    }
+}
+
+impl<C, B, S, E> Future for Connection<C, S, E>
+where
+    C: quic::Connection + Unpin,
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http3ConnExec<S::Future, B, C::BidiStream>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.conn).poll(cx)
+    }
+}
+
+// ===== impl Builder =====
+
+impl<E> Builder<E> {
+    /// Create a new connection builder.
+    ///
+    /// This starts with the default options, and an executor which is a type
+    /// that implements [`Http3ConnExec`] trait.
+    ///
+    /// [`Http3ConnExec`]: crate::rt::bounds::Http3ConnExec
+    pub fn new(exec: E) -> Self {
+        Self {
+            exec,
+            metrics: MetricsSink::default(),
+            events: EventSink::default(),
+            h3_builder: Default::default(),
+        }
+    }
+
+    /// Sets the [`SETTINGS_MAX_FIELD_SECTION_SIZE`][spec] option, the max
+    /// size of received header sections.
+    ///
+    /// Default is currently ~16MB, but may change.
+    ///
+    /// [spec]: https://www.rfc-editor.org/rfc/rfc9114.html#section-4.2.2
+    pub fn max_field_section_size(&mut self, max: u32) -> &mut Self {
+        self.h3_builder.max_field_section_size = max.into();
+        self
+    }
+
+    /// Enables the [extended CONNECT protocol].
+    ///
+    /// Requests using it carry a [`Protocol`](crate::ext::Protocol)
+    /// extension, and their stream can be taken over with
+    /// [`upgrade::on`](crate::upgrade::on) once a 2xx response is sent.
+    ///
+    /// [extended CONNECT protocol]: https://www.rfc-editor.org/rfc/rfc9220.html
+    pub fn enable_connect_protocol(&mut self) -> &mut Self {
+        self.h3_builder.enable_connect_protocol = true;
+        self
+    }
+
+    /// Enables [WebTransport] sessions, along with the extended CONNECT
+    /// protocol they are established with.
+    ///
+    /// Requests for a session carry a
+    /// [`WebTransportSession`](crate::ext::WebTransportSession) extension,
+    /// which is usable once a 2xx response is sent.
+    ///
+    /// [WebTransport]: https://datatracker.ietf.org/doc/html/draft-ietf-webtrans-http3-02
+    pub fn enable_webtransport(&mut self) -> &mut Self {
+        self.h3_builder.enable_connect_protocol = true;
+        self.h3_builder.enable_webtransport = true;
+        self
+    }
+
+    /// Enables [HTTP datagrams], sent in QUIC datagrams.
+    ///
+    /// CONNECT requests then carry an
+    /// [`H3Datagrams`](crate::ext::H3Datagrams) extension, to send and
+    /// receive the datagrams associated with them. The QUIC connection needs
+    /// to support datagrams, through
+    /// [`Connection::datagrams`](crate::rt::quic::Connection::datagrams).
+    ///
+    /// [HTTP datagrams]: https://www.rfc-editor.org/rfc/rfc9297.html
+    pub fn enable_datagrams(&mut self) -> &mut Self {
+        self.h3_builder.enable_datagrams = true;
+        self
+    }
+
+    /// Set the clock that the time in `Date` headers is read from.
+    ///
+    /// Responses that don't already have a `Date` header get one with the
+    /// clock's time, or none if it returns `None`.
+    ///
+    /// Default is the system clock.
+    pub fn clock<C>(&mut self, clock: C) -> &mut Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.h3_builder.date_header = DateHeader::new(clock);
+        self
+    }
+
+    /// Set a sink for metrics about the connections this builds.
+    ///
+    /// See [`Metrics`] for what is reported.
+    pub fn metrics<M>(&mut self, metrics: M) -> &mut Self
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        self.metrics = MetricsSink::new(metrics);
+        self
+    }
+
+    /// Set a callback for events in the lifecycle of the connections this
+    /// builds.
+    ///
+    /// See [`ConnectionEvent`] for the events reported.
+    pub fn on_connection_event<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ConnectionEvent<'_>) + Send + Sync + 'static,
+    {
+        self.events = EventSink::new(callback);
+        self
+    }
+
+    /// Bind a connection together with a [`Service`](crate::service::Service).
+    ///
+    /// This returns a Future that must be polled in order for HTTP to be
+    /// driven on the connection.
+    pub fn serve_connection<S, C, Bd>(&self, conn: C, service: S) -> Connection<C, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = Bd>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        Bd: Body + 'static,
+        Bd::Error: Into<Box<dyn StdError + Send + Sync>>,
+        C: quic::Connection + Unpin,
+        E: Http3ConnExec<S::Future, Bd, C::BidiStream>,
+    {
+        let proto = proto::h3::Server::new(
+            conn,
+            service,
+            &self.h3_builder,
+            self.exec.clone(),
+            self.metrics.clone(),
+            &self.events,
+        );
+        Connection { conn: proto }
+    }
+}
@@ -0,0 +1,475 @@
+use std::fmt;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+
+use crate::common::{Future, Pin};
+use crate::service::service::Service;
+
+/// Map the requests given to a `Service`.
+///
+/// The returned service calls `f` with each request, and calls `service`
+/// with the result.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc() {
+/// use bytes::Bytes;
+/// use hyper::{body, header, Request, Response};
+/// use http_body_util::Full;
+/// use hyper::service::{map_request, service_fn};
+///
+/// let hello = service_fn(|req: Request<body::Incoming>| async move {
+///     let user = req.headers().get("x-user").cloned();
+///     Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from(format!("hello {:?}", user))))
+/// });
+///
+/// let service = map_request(hello, |mut req: Request<body::Incoming>| {
+///     req.headers_mut().remove(header::COOKIE);
+///     req
+/// });
+/// # }
+/// # fn main() {}
+/// ```
+pub fn map_request<S, F>(service: S, f: F) -> MapRequest<S, F> {
+    MapRequest { service, f }
+}
+
+/// Service returned by [`map_request`]
+#[derive(Clone)]
+pub struct MapRequest<S, F> {
+    service: S,
+    f: F,
+}
+
+impl<S, F, R1, R2> Service<R1> for MapRequest<S, F>
+where
+    S: Service<R2>,
+    F: Fn(R1) -> R2,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn call(&self, req: R1) -> Self::Future {
+        self.service.call((self.f)(req))
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+}
+
+impl<S, F> fmt::Debug for MapRequest<S, F>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapRequest")
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+/// Map the successful responses of a `Service`.
+///
+/// The returned service calls `f` with each response `service` returns.
+/// Errors are passed along unchanged.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc() {
+/// use bytes::Bytes;
+/// use hyper::{body, header, Request, Response};
+/// use http_body_util::Full;
+/// use hyper::service::{map_response, service_fn};
+///
+/// let hello = service_fn(|_req: Request<body::Incoming>| async move {
+///     Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+/// });
+///
+/// let service = map_response(hello, |mut res: Response<Full<Bytes>>| {
+///     res.headers_mut()
+///         .insert(header::SERVER, header::HeaderValue::from_static("hyper"));
+///     res
+/// });
+/// # }
+/// # fn main() {}
+/// ```
+pub fn map_response<S, F>(service: S, f: F) -> MapResponse<S, F> {
+    MapResponse { service, f }
+}
+
+/// Service returned by [`map_response`]
+#[derive(Clone)]
+pub struct MapResponse<S, F> {
+    service: S,
+    f: F,
+}
+
+impl<S, F, R, T> Service<R> for MapResponse<S, F>
+where
+    S: Service<R>,
+    F: Fn(S::Response) -> T + Clone,
+{
+    type Response = T;
+    type Error = S::Error;
+    type Future = MapResponseFuture<S::Future, F>;
+
+    fn call(&self, req: R) -> Self::Future {
+        MapResponseFuture {
+            fut: self.service.call(req),
+            f: Some(self.f.clone()),
+        }
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+}
+
+impl<S, F> fmt::Debug for MapResponse<S, F>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapResponse")
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+pin_project! {
+    /// Response future of [`MapResponse`].
+    pub struct MapResponseFuture<Fut, F> {
+        #[pin]
+        fut: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F, T, U, E> Future for MapResponseFuture<Fut, F>
+where
+    Fut: Future<Output = Result<T, E>>,
+    F: FnOnce(T) -> U,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.project();
+        let res = ready!(me.fut.poll(cx));
+        let f = me.f.take().expect("polled after complete");
+        Poll::Ready(res.map(f))
+    }
+}
+
+impl<Fut, F> fmt::Debug for MapResponseFuture<Fut, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapResponseFuture").finish()
+    }
+}
+
+/// Chain an asynchronous function after a `Service`.
+///
+/// The returned service calls `f` with the result of each call to `service`,
+/// whether it succeeded or not, and resolves to the output of the future it
+/// returns. This can recover from errors, or do further async work with a
+/// response.
+///
+/// Readiness is that of `service`, with its errors converted into those of
+/// the future `f` returns, since `f` isn't called for them.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc() {
+/// use bytes::Bytes;
+/// use hyper::{body, Request, Response, StatusCode};
+/// use http_body_util::Full;
+/// use hyper::service::{service_fn, then};
+///
+/// let flaky = service_fn(|_req: Request<body::Incoming>| async move {
+///     Err::<Response<Full<Bytes>>, _>("oh no")
+/// });
+///
+/// let service = then(flaky, |res: Result<Response<Full<Bytes>>, &'static str>| async move {
+///     Ok::<_, Box<dyn std::error::Error + Send + Sync>>(res.unwrap_or_else(|_| {
+///         let mut res = Response::new(Full::default());
+///         *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+///         res
+///     }))
+/// });
+/// # }
+/// # fn main() {}
+/// ```
+pub fn then<S, F>(service: S, f: F) -> Then<S, F> {
+    Then { service, f }
+}
+
+/// Service returned by [`then`]
+#[derive(Clone)]
+pub struct Then<S, F> {
+    service: S,
+    f: F,
+}
+
+impl<S, F, R, Fut, T, E> Service<R> for Then<S, F>
+where
+    S: Service<R>,
+    F: Fn(Result<S::Response, S::Error>) -> Fut + Clone,
+    Fut: Future<Output = Result<T, E>>,
+    E: From<S::Error>,
+{
+    type Response = T;
+    type Error = E;
+    type Future = ThenFuture<S::Future, Fut, F>;
+
+    fn call(&self, req: R) -> Self::Future {
+        ThenFuture {
+            state: ThenState::First {
+                fut: self.service.call(req),
+                f: Some(self.f.clone()),
+            },
+        }
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(E::from)
+    }
+}
+
+impl<S, F> fmt::Debug for Then<S, F>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Then")
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+pin_project! {
+    /// Response future of [`Then`].
+    pub struct ThenFuture<Fut1, Fut2, F> {
+        #[pin]
+        state: ThenState<Fut1, Fut2, F>,
+    }
+}
+
+pin_project! {
+    #[project = ThenStateProj]
+    enum ThenState<Fut1, Fut2, F> {
+        First {
+            #[pin]
+            fut: Fut1,
+            f: Option<F>,
+        },
+        Second {
+            #[pin]
+            fut: Fut2,
+        },
+    }
+}
+
+impl<Fut1, Fut2, F> Future for ThenFuture<Fut1, Fut2, F>
+where
+    Fut1: Future,
+    Fut2: Future,
+    F: FnOnce(Fut1::Output) -> Fut2,
+{
+    type Output = Fut2::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut me = self.project();
+        loop {
+            match me.state.as_mut().project() {
+                ThenStateProj::First { fut, f } => {
+                    let res = ready!(fut.poll(cx));
+                    let f = f.take().expect("polled after complete");
+                    me.state.set(ThenState::Second { fut: f(res) });
+                }
+                ThenStateProj::Second { fut } => return fut.poll(cx),
+            }
+        }
+    }
+}
+
+impl<Fut1, Fut2, F> fmt::Debug for ThenFuture<Fut1, Fut2, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThenFuture").finish()
+    }
+}
+
+/// Either of two `Service`s, or two `Future`s.
+///
+/// This lets branches that build different services, such as only adding a
+/// combinator when some option is set, return a single type. Both services
+/// must have the same `Response` and `Error` types.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc() {
+/// use bytes::Bytes;
+/// use hyper::{body, Request, Response};
+/// use http_body_util::Full;
+/// use hyper::service::{map_response, service_fn, Either};
+///
+/// # fn run(add_header: bool) {
+/// let hello = service_fn(|_req: Request<body::Incoming>| async move {
+///     Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+/// });
+///
+/// let service = if add_header {
+///     Either::Left(map_response(hello, |mut res: Response<Full<Bytes>>| {
+///         res.headers_mut().insert("x-hello", "world".parse().unwrap());
+///         res
+///     }))
+/// } else {
+///     Either::Right(hello)
+/// };
+/// # }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub enum Either<A, B> {
+    /// The first service.
+    Left(A),
+    /// The second service.
+    Right(B),
+}
+
+impl<A, B, R> Service<R> for Either<A, B>
+where
+    A: Service<R>,
+    B: Service<R, Response = A::Response, Error = A::Error>,
+{
+    type Response = A::Response;
+    type Error = A::Error;
+    type Future = Either<A::Future, B::Future>;
+
+    fn call(&self, req: R) -> Self::Future {
+        match self {
+            Either::Left(a) => Either::Left(a.call(req)),
+            Either::Right(b) => Either::Right(b.call(req)),
+        }
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Either::Left(a) => a.poll_ready(cx),
+            Either::Right(b) => b.poll_ready(cx),
+        }
+    }
+}
+
+impl<A, B> Future for Either<A, B>
+where
+    A: Future,
+    B: Future<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: the variants are pinned structurally. `Either` never moves
+        // out of them, and has no `Drop` impl.
+        unsafe {
+            match self.get_unchecked_mut() {
+                Either::Left(a) => Pin::new_unchecked(a).poll(cx),
+                Either::Right(b) => Pin::new_unchecked(b).poll(cx),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug)]
+    struct Double;
+
+    impl Service<u32> for Double {
+        type Response = u32;
+        type Error = Infallible;
+        type Future = Ready<Result<u32, Infallible>>;
+
+        fn call(&self, req: u32) -> Self::Future {
+            ready(Ok(req * 2))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct TooBig;
+
+    impl From<Infallible> for TooBig {
+        fn from(never: Infallible) -> Self {
+            match never {}
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    struct Busy;
+
+    impl Service<u32> for Busy {
+        type Response = u32;
+        type Error = &'static str;
+        type Future = Ready<Result<u32, &'static str>>;
+
+        fn call(&self, req: u32) -> Self::Future {
+            ready(Ok(req))
+        }
+
+        fn poll_ready(&self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Err("busy"))
+        }
+    }
+
+    #[tokio::test]
+    async fn map_request_and_response() {
+        let svc = map_request(Double, |s: &str| s.len() as u32);
+        let svc = map_response(svc, |n: u32| n.to_string());
+        assert_eq!(svc.call("hyper").await, Ok("10".to_string()));
+    }
+
+    #[tokio::test]
+    async fn then_sees_result() {
+        let svc = then(Double, |res: Result<u32, Infallible>| async move {
+            match res {
+                Ok(n) if n > 10 => Err(TooBig),
+                Ok(n) => Ok(n),
+                Err(never) => match never {},
+            }
+        });
+        assert_eq!(svc.call(2).await, Ok(4));
+        assert_eq!(svc.call(6).await, Err(TooBig));
+    }
+
+    #[tokio::test]
+    async fn then_forwards_poll_ready() {
+        let svc = then(Busy, |res: Result<u32, &'static str>| async move {
+            res.map_err(String::from)
+        });
+        let ready = futures_util::future::poll_fn(|cx| svc.poll_ready(cx)).await;
+        assert_eq!(ready, Err("busy".to_string()));
+    }
+
+    #[tokio::test]
+    async fn either_calls_active_side() {
+        let left: Either<_, MapRequest<Double, fn(u32) -> u32>> = Either::Left(Double);
+        let right = Either::<Double, _>::Right(map_request(Double, (|n| n + 1) as fn(u32) -> u32));
+        assert_eq!(left.call(1).await, Ok(2));
+        assert_eq!(right.call(1).await, Ok(4));
+    }
+}
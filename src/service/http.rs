@@ -1,4 +1,5 @@
 use std::error::Error as StdError;
+use std::task::{Context, Poll};
 
 use crate::body::Body;
 use crate::common::Future;
@@ -22,6 +23,9 @@ pub trait HttpService<ReqBody>: sealed::Sealed<ReqBody> {
 
     #[doc(hidden)]
     fn call(&mut self, req: Request<ReqBody>) -> Self::Future;
+
+    #[doc(hidden)]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
 }
 
 impl<T, B1, B2> HttpService<B1> for T
@@ -38,6 +42,10 @@ where
     fn call(&mut self, req: Request<B1>) -> Self::Future {
         Service::call(self, req)
     }
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Service::poll_ready(self, cx)
+    }
 }
 
 impl<T, B1, B2> sealed::Sealed<B1> for T
@@ -0,0 +1,340 @@
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::{Request, Response};
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+
+use crate::common::{Future, Pin};
+use crate::service::service::Service;
+
+/// Record how long each request takes to serve.
+///
+/// For every request, `sink` is called once with its [`Timings`], after the
+/// response body has finished or been dropped. Requests whose service
+/// returned an error are reported as soon as the error is returned.
+///
+/// hyper calls a service as soon as it has parsed a request head, so
+/// timings start at that point.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(feature = "http1", feature = "server"))]
+/// # fn doc() {
+/// use bytes::Bytes;
+/// use hyper::{body, Request, Response};
+/// use http_body_util::Full;
+/// use hyper::service::{instrument, service_fn, Timings};
+///
+/// let hello = service_fn(|_req: Request<body::Incoming>| async move {
+///     Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+/// });
+///
+/// let service = instrument(hello, |timings: &Timings| {
+///     println!("served in {:?}", timings.total());
+/// });
+/// # }
+/// # fn main() {}
+/// ```
+pub fn instrument<S, F>(service: S, sink: F) -> Instrument<S, F> {
+    Instrument {
+        service,
+        sink: Arc::new(sink),
+    }
+}
+
+/// Service returned by [`instrument`]
+pub struct Instrument<S, F> {
+    service: S,
+    sink: Arc<F>,
+}
+
+impl<S, F, ReqBody, ResBody> Service<Request<ReqBody>> for Instrument<S, F>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    F: Fn(&Timings),
+    ResBody: Body,
+{
+    type Response = Response<InstrumentBody<ResBody, F>>;
+    type Error = S::Error;
+    type Future = InstrumentFuture<S::Future, F>;
+
+    fn call(&self, req: Request<ReqBody>) -> Self::Future {
+        InstrumentFuture {
+            fut: self.service.call(req),
+            called: Instant::now(),
+            first_polled: None,
+            sink: Some(self.sink.clone()),
+        }
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+}
+
+impl<S: Clone, F> Clone for Instrument<S, F> {
+    fn clone(&self) -> Self {
+        Instrument {
+            service: self.service.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for Instrument<S, F>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instrument")
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+pin_project! {
+    /// Response future of [`Instrument`].
+    pub struct InstrumentFuture<Fut, F> {
+        #[pin]
+        fut: Fut,
+        called: Instant,
+        first_polled: Option<Instant>,
+        sink: Option<Arc<F>>,
+    }
+}
+
+impl<Fut, F, B, E> Future for InstrumentFuture<Fut, F>
+where
+    Fut: Future<Output = Result<Response<B>, E>>,
+    F: Fn(&Timings),
+    B: Body,
+{
+    type Output = Result<Response<InstrumentBody<B, F>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.project();
+        let first_polled = *me.first_polled.get_or_insert_with(Instant::now);
+        let res = ready!(me.fut.poll(cx));
+        let responded = Instant::now();
+        let mut report = Report {
+            sink: me.sink.take().expect("polled after complete"),
+            timings: Timings {
+                queued: first_polled - *me.called,
+                call: responded - first_polled,
+                body: None,
+            },
+            body_started: responded,
+        };
+        Poll::Ready(res.map(|res| {
+            if res.body().is_end_stream() {
+                report.timings.body = Some(Duration::ZERO);
+            }
+            res.map(|inner| InstrumentBody { inner, report })
+        }))
+    }
+}
+
+impl<Fut, F> fmt::Debug for InstrumentFuture<Fut, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentFuture").finish()
+    }
+}
+
+pin_project! {
+    /// Response body of [`Instrument`].
+    pub struct InstrumentBody<B, F>
+    where
+        F: Fn(&Timings),
+    {
+        #[pin]
+        inner: B,
+        report: Report<F>,
+    }
+}
+
+impl<B, F> Body for InstrumentBody<B, F>
+where
+    B: Body,
+    F: Fn(&Timings),
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let me = self.project();
+        let mut inner = me.inner;
+        let frame = ready!(inner.as_mut().poll_frame(cx));
+        // hyper stops polling once `is_end_stream` is true, so the body may
+        // have ended without ever yielding `None`.
+        if frame.is_none() || inner.is_end_stream() {
+            me.report.end();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<B, F> fmt::Debug for InstrumentBody<B, F>
+where
+    B: fmt::Debug,
+    F: Fn(&Timings),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InstrumentBody").field(&self.inner).finish()
+    }
+}
+
+/// Calls the sink when dropped, so requests are reported even if their
+/// response body is abandoned.
+struct Report<F: Fn(&Timings)> {
+    sink: Arc<F>,
+    timings: Timings,
+    body_started: Instant,
+}
+
+impl<F: Fn(&Timings)> Report<F> {
+    fn end(&mut self) {
+        if self.timings.body.is_none() {
+            self.timings.body = Some(self.body_started.elapsed());
+        }
+    }
+}
+
+impl<F: Fn(&Timings)> Drop for Report<F> {
+    fn drop(&mut self) {
+        (self.sink)(&self.timings);
+    }
+}
+
+/// How long serving a request took, as reported by [`instrument`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timings {
+    queued: Duration,
+    call: Duration,
+    body: Option<Duration>,
+}
+
+impl Timings {
+    /// How long the request waited between hyper calling the service and
+    /// the response future first being polled.
+    ///
+    /// This is usually time spent waiting for an executor to run the task
+    /// serving the request.
+    pub fn queued(&self) -> Duration {
+        self.queued
+    }
+
+    /// How long the response future took to resolve, once first polled.
+    pub fn call(&self) -> Duration {
+        self.call
+    }
+
+    /// How long sending the response body took.
+    ///
+    /// This is `None` if the service returned an error, or the body was
+    /// dropped before it ended, such as when the connection closed.
+    pub fn body(&self) -> Option<Duration> {
+        self.body
+    }
+
+    /// The sum of all of the above.
+    pub fn total(&self) -> Duration {
+        self.queued + self.call + self.body.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+    use std::sync::Mutex;
+
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Empty, Full};
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug)]
+    struct Hello;
+
+    #[derive(Clone, Copy, Debug)]
+    struct Nothing;
+
+    impl Service<Request<()>> for Hello {
+        type Response = Response<Full<Bytes>>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn call(&self, _req: Request<()>) -> Self::Future {
+            ready(Ok(Response::new(Full::from("hello"))))
+        }
+    }
+
+    impl Service<Request<()>> for Nothing {
+        type Response = Response<Empty<Bytes>>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn call(&self, _req: Request<()>) -> Self::Future {
+            ready(Ok(Response::new(Empty::new())))
+        }
+    }
+
+    fn recorded() -> (Arc<Mutex<Vec<Timings>>>, impl Fn(&Timings)) {
+        let all = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let all = all.clone();
+            move |timings: &Timings| all.lock().unwrap().push(*timings)
+        };
+        (all, sink)
+    }
+
+    #[tokio::test]
+    async fn reports_once_body_ends() {
+        let (all, sink) = recorded();
+        let svc = instrument(Hello, sink);
+
+        let res = svc.call(Request::new(())).await.unwrap();
+        assert!(all.lock().unwrap().is_empty());
+
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+        let all = all.lock().unwrap();
+        assert_eq!(all.len(), 1);
+        assert!(all[0].body().is_some());
+    }
+
+    #[tokio::test]
+    async fn reports_abandoned_body() {
+        let (all, sink) = recorded();
+        let svc = instrument(Hello, sink);
+
+        drop(svc.call(Request::new(())).await.unwrap());
+        let all = all.lock().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].body(), None);
+    }
+
+    #[tokio::test]
+    async fn empty_body_is_complete() {
+        let (all, sink) = recorded();
+        let svc = instrument(Nothing, sink);
+
+        drop(svc.call(Request::new(())).await.unwrap());
+        assert_eq!(all.lock().unwrap()[0].body(), Some(Duration::ZERO));
+    }
+}
@@ -17,20 +17,63 @@
 //! to a single connection. It defines how to respond to **all** requests that
 //! connection will receive.
 //!
-//! The helper [`service_fn`](service_fn) should be sufficient for most cases, but
-//! if you need to implement `Service` for a type manually, you can follow the example
-//! in `service_struct_impl.rs`.
+//! The helper [`service_fn`](service_fn) should be sufficient for most cases,
+//! and [`service_fn_with_state`](service_fn_with_state) adds state that lives
+//! as long as the connection. But if you need to implement `Service` for a
+//! type manually, you can follow the example in `service_struct_impl.rs`.
+//!
+//! Simple middleware can be built with the [`map_request`], [`map_response`]
+//! and [`then`] combinators, and [`Either`] joins two different services
+//! into one type. [`instrument`] reports how long each request took to
+//! serve.
+//!
+//! # Tower
+//!
+//! With the `tower` feature enabled, `TowerToHyperService` and
+//! `HyperToTowerService` convert between hyper's `Service` and the one from
+//! `tower-service`, so middleware from either ecosystem can be used.
 
+mod combinators;
 mod http;
+mod instrument;
 mod service;
+#[cfg(all(
+    feature = "tower",
+    any(feature = "http1", feature = "http2", feature = "http3"),
+    any(feature = "server", feature = "client")
+))]
+mod tower;
 mod util;
 
-#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+#[cfg(all(
+    any(feature = "http1", feature = "http2", feature = "http3"),
+    any(feature = "server", feature = "client")
+))]
+pub use self::combinators::{
+    map_request, map_response, then, Either, MapRequest, MapResponse, MapResponseFuture, Then,
+    ThenFuture,
+};
+#[cfg(all(
+    any(feature = "http1", feature = "http2", feature = "http3"),
+    feature = "server"
+))]
 pub use self::http::HttpService;
 #[cfg(all(
-    any(feature = "http1", feature = "http2"),
+    any(feature = "http1", feature = "http2", feature = "http3"),
+    any(feature = "server", feature = "client")
+))]
+pub use self::instrument::{instrument, Instrument, InstrumentBody, InstrumentFuture, Timings};
+#[cfg(all(
+    any(feature = "http1", feature = "http2", feature = "http3"),
     any(feature = "server", feature = "client")
 ))]
 pub use self::service::Service;
+#[cfg(all(
+    feature = "tower",
+    any(feature = "http1", feature = "http2", feature = "http3"),
+    any(feature = "server", feature = "client")
+))]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub use self::tower::{HyperToTowerService, TowerToHyperService, TowerToHyperServiceFuture};
 
-pub use self::util::service_fn;
+pub use self::util::{service_fn, service_fn_with_state};
@@ -1,4 +1,5 @@
 use std::future::Future;
+use std::task::{Context, Poll};
 
 /// An asynchronous function from a `Request` to a `Response`.
 ///
@@ -37,4 +38,20 @@ pub trait Service<Request> {
     ///   that means you're not really using the &mut self and could do with a &self
     /// To see the discussion on this see: <https://github.com/hyperium/hyper/issues/3040>
     fn call(&self, req: Request) -> Self::Future;
+
+    /// Check whether the service is ready to accept another request.
+    ///
+    /// Server connections call this before reading each new request. While
+    /// it returns `Poll::Pending`, an HTTP/1 connection stops reading from
+    /// the transport, and an HTTP/2 connection stops accepting new streams,
+    /// so that the backpressure reaches the client. The service must arrange
+    /// for the task to be woken once it is ready again.
+    ///
+    /// Returning an error closes the connection with that error.
+    ///
+    /// The default implementation is always ready.
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let _ = cx;
+        Poll::Ready(Ok(()))
+    }
 }
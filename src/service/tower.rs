@@ -0,0 +1,218 @@
+use std::fmt;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+
+use crate::common::{Future, Pin};
+use crate::service::service::Service;
+
+/// Adapts a `tower_service::Service` into a hyper [`Service`].
+///
+/// hyper's `Service::call` takes `&self`, so each call clones the wrapped
+/// service, waits for it to be ready with `poll_ready`, and then calls it.
+/// Services that are expensive to clone should be wrapped in something cheap
+/// to clone, such as a `tower::buffer::Buffer`.
+#[derive(Clone)]
+pub struct TowerToHyperService<S> {
+    service: S,
+}
+
+impl<S> TowerToHyperService<S> {
+    /// Wrap a `tower_service::Service`.
+    pub fn new(service: S) -> Self {
+        TowerToHyperService { service }
+    }
+
+    /// Consume this adapter, returning the wrapped service.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S, R> Service<R> for TowerToHyperService<S>
+where
+    S: tower_service::Service<R> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TowerToHyperServiceFuture<S, R>;
+
+    fn call(&self, req: R) -> Self::Future {
+        TowerToHyperServiceFuture {
+            state: State::NotReady {
+                service: self.service.clone(),
+                req: Some(req),
+            },
+        }
+    }
+}
+
+impl<S> fmt::Debug for TowerToHyperService<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TowerToHyperService")
+            .field(&self.service)
+            .finish()
+    }
+}
+
+pin_project! {
+    /// Response future of [`TowerToHyperService`].
+    pub struct TowerToHyperServiceFuture<S, R>
+    where
+        S: tower_service::Service<R>,
+    {
+        #[pin]
+        state: State<S, R>,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    enum State<S, R>
+    where
+        S: tower_service::Service<R>,
+    {
+        NotReady {
+            service: S,
+            req: Option<R>,
+        },
+        Called {
+            #[pin]
+            fut: S::Future,
+        },
+    }
+}
+
+impl<S, R> Future for TowerToHyperServiceFuture<S, R>
+where
+    S: tower_service::Service<R>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut me = self.project();
+        loop {
+            match me.state.as_mut().project() {
+                StateProj::NotReady { service, req } => {
+                    ready!(service.poll_ready(cx))?;
+                    let req = req.take().expect("polled after ready");
+                    let fut = service.call(req);
+                    me.state.set(State::Called { fut });
+                }
+                StateProj::Called { fut } => return fut.poll(cx),
+            }
+        }
+    }
+}
+
+impl<S, R> fmt::Debug for TowerToHyperServiceFuture<S, R>
+where
+    S: tower_service::Service<R>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TowerToHyperServiceFuture").finish()
+    }
+}
+
+/// Adapts a hyper [`Service`] into a `tower_service::Service`.
+///
+/// `poll_ready` is forwarded to [`Service::poll_ready`].
+#[derive(Clone)]
+pub struct HyperToTowerService<S> {
+    service: S,
+}
+
+impl<S> HyperToTowerService<S> {
+    /// Wrap a hyper `Service`.
+    pub fn new(service: S) -> Self {
+        HyperToTowerService { service }
+    }
+
+    /// Consume this adapter, returning the wrapped service.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S, R> tower_service::Service<R> for HyperToTowerService<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        self.service.call(req)
+    }
+}
+
+impl<S> fmt::Debug for HyperToTowerService<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HyperToTowerService")
+            .field(&self.service)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+
+    use futures_util::future::poll_fn;
+
+    use super::*;
+
+    /// A tower service that is only ready on every other `poll_ready`.
+    #[derive(Clone, Default)]
+    struct Flaky {
+        polled: bool,
+    }
+
+    impl tower_service::Service<u32> for Flaky {
+        type Response = u32;
+        type Error = Infallible;
+        type Future = Ready<Result<u32, Infallible>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            if std::mem::replace(&mut self.polled, true) {
+                Poll::Ready(Ok(()))
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        fn call(&mut self, req: u32) -> Self::Future {
+            assert!(self.polled, "called before ready");
+            ready(Ok(req * 2))
+        }
+    }
+
+    #[tokio::test]
+    async fn tower_to_hyper_waits_for_ready() {
+        let svc = TowerToHyperService::new(Flaky::default());
+        assert_eq!(svc.call(21).await, Ok(42));
+        assert_eq!(svc.call(1).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn hyper_to_tower_round_trip() {
+        use tower_service::Service as _;
+
+        let mut svc = HyperToTowerService::new(TowerToHyperService::new(Flaky::default()));
+        poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+        assert_eq!(svc.call(4).await, Ok(8));
+    }
+}
@@ -80,3 +80,99 @@ where
 }
 
 impl<F, R> Copy for ServiceFn<F, R> where F: Copy {}
+
+/// Create a `Service` from a function and some state.
+///
+/// Each call of the service passes a reference to `state` along with the
+/// request. Since a service is usually bound to a single connection, this
+/// is a simple way to keep connection-local state, such as a cache, without
+/// needing to share it across threads. Anything the returned future needs
+/// from the state must be copied or cloned out of it.
+///
+/// # Example
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::convert::Infallible;
+///
+/// use bytes::Bytes;
+/// use hyper::{body, Request, Response};
+/// use http_body_util::Full;
+/// use hyper::service::service_fn_with_state;
+///
+/// // Count the requests seen on this connection.
+/// let service = service_fn_with_state(Cell::new(0), |count: &Cell<u32>, _req: Request<body::Incoming>| {
+///     count.set(count.get() + 1);
+///     let n = count.get();
+///     async move {
+///         Ok::<_, Infallible>(Response::new(Full::<Bytes>::from(format!("request #{}", n))))
+///     }
+/// });
+/// ```
+pub fn service_fn_with_state<T, F, R, S>(state: T, f: F) -> ServiceFnWithState<T, F, R>
+where
+    F: Fn(&T, Request<R>) -> S,
+    S: Future,
+{
+    ServiceFnWithState {
+        state,
+        f,
+        _req: PhantomData,
+    }
+}
+
+/// Service returned by [`service_fn_with_state`]
+pub struct ServiceFnWithState<T, F, R> {
+    state: T,
+    f: F,
+    _req: PhantomData<fn(R)>,
+}
+
+impl<T, F, R> ServiceFnWithState<T, F, R> {
+    /// Get a reference to the state of this service.
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+}
+
+impl<T, F, ReqBody, Ret, ResBody, E> Service<Request<ReqBody>> for ServiceFnWithState<T, F, ReqBody>
+where
+    F: Fn(&T, Request<ReqBody>) -> Ret,
+    ReqBody: Body,
+    Ret: Future<Output = Result<Response<ResBody>, E>>,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+    ResBody: Body,
+{
+    type Response = crate::Response<ResBody>;
+    type Error = E;
+    type Future = Ret;
+
+    fn call(&self, req: Request<ReqBody>) -> Self::Future {
+        (self.f)(&self.state, req)
+    }
+}
+
+impl<T, F, R> fmt::Debug for ServiceFnWithState<T, F, R>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("impl Service")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<T, F, R> Clone for ServiceFnWithState<T, F, R>
+where
+    T: Clone,
+    F: Clone,
+{
+    fn clone(&self) -> Self {
+        ServiceFnWithState {
+            state: self.state.clone(),
+            f: self.f.clone(),
+            _req: PhantomData,
+        }
+    }
+}
@@ -32,6 +32,15 @@
 //! upgrade, you call `on()` with the `Request`, and then can spawn a task
 //! awaiting it.
 //!
+//! # Takeover
+//!
+//! Some protocols switch away from HTTP without an `Upgrade` handshake or a
+//! `101` response. An HTTP/1 server can hand the connection to a service
+//! after any response by calling [`takeover`][], and inserting the returned
+//! [`Takeover`][] into the response's extensions. hyper finishes writing
+//! that response, stops reading requests, and resolves the paired
+//! [`OnUpgrade`][] with the connection.
+//!
 //! # Example
 //!
 //! See [this example][example] showing how upgrades work with both
@@ -103,12 +112,70 @@ pub fn on<T: sealed::CanUpgrade>(msg: T) -> OnUpgrade {
     msg.on_upgrade()
 }
 
-#[cfg(any(feature = "http1", feature = "http2"))]
+cfg_feature! {
+    #![all(feature = "http1", feature = "server")]
+
+    /// A response extension asking an HTTP/1 server to hand over the
+    /// connection once the response is written.
+    ///
+    /// The connection is closed to further requests, so the response should
+    /// have an empty body or a `Content-Length`. A body of unknown length
+    /// sent to an HTTP/1.0 client can only be ended by closing the
+    /// connection, and so can't be taken over.
+    ///
+    /// As with other upgrades, the connection must be served
+    /// [`with_upgrades`](crate::server::conn::http1::Connection::with_upgrades).
+    /// HTTP/2 connections ignore this extension, and the [`OnUpgrade`]
+    /// resolves to an error.
+    pub struct Takeover {
+        pending: Pending,
+    }
+
+    /// Create a [`Takeover`] extension, and the future that resolves to the
+    /// connection once it has been taken over.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use bytes::Bytes;
+    /// # use http_body_util::Empty;
+    /// # use hyper::{Request, Response};
+    /// # fn handle<B>(_req: Request<B>) -> Response<Empty<Bytes>> {
+    /// let (takeover, on_upgrade) = hyper::upgrade::takeover();
+    /// let mut res = Response::new(Empty::new());
+    /// res.extensions_mut().insert(takeover);
+    ///
+    /// // Some task can now await `on_upgrade`, and speak the new protocol.
+    /// # drop(on_upgrade);
+    /// res
+    /// # }
+    /// ```
+    pub fn takeover() -> (Takeover, OnUpgrade) {
+        let (pending, on_upgrade) = pending();
+        (Takeover { pending }, on_upgrade)
+    }
+}
+
+#[cfg(all(feature = "http1", feature = "server"))]
+impl Takeover {
+    pub(super) fn into_pending(self) -> Pending {
+        self.pending
+    }
+}
+
+#[cfg(all(feature = "http1", feature = "server"))]
+impl fmt::Debug for Takeover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Takeover").finish()
+    }
+}
+
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 pub(super) struct Pending {
     tx: oneshot::Sender<crate::Result<Upgraded>>,
 }
 
-#[cfg(any(feature = "http1", feature = "http2"))]
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 pub(super) fn pending() -> (Pending, OnUpgrade) {
     let (tx, rx) = oneshot::channel();
     (Pending { tx }, OnUpgrade { rx: Some(rx) })
@@ -117,7 +184,7 @@ pub(super) fn pending() -> (Pending, OnUpgrade) {
 // ===== impl Upgraded =====
 
 impl Upgraded {
-    #[cfg(any(feature = "http1", feature = "http2", test))]
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3", test))]
     pub(super) fn new<T>(io: T, read_buf: Bytes) -> Self
     where
         T: Read + Write + Unpin + Send + 'static,
@@ -228,7 +295,7 @@ impl fmt::Debug for OnUpgrade {
 
 // ===== impl Pending =====
 
-#[cfg(any(feature = "http1", feature = "http2"))]
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 impl Pending {
     pub(super) fn fulfill(self, upgraded: Upgraded) {
         trace!("pending upgrade fulfill");
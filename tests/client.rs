@@ -1410,28 +1410,29 @@ mod conn {
     }
 
     #[tokio::test]
-    async fn get_custom_reason_phrase() {
+    async fn http1_max_headers() {
         let (listener, addr) = setup_tk_test_server().await;
 
         let server = async move {
             let mut sock = listener.accept().await.unwrap().0;
             let mut buf = [0; 4096];
-            let n = sock.read(&mut buf).await.expect("read 1");
-
-            // Notably:
-            // - Just a path, since just a path was set
-            // - No host, since no host was set
-            let expected = "GET /a HTTP/1.1\r\n\r\n";
-            assert_eq!(s(&buf[..n]), expected);
+            sock.read(&mut buf).await.expect("read 1");
 
-            sock.write_all(b"HTTP/1.1 200 Alright\r\nContent-Length: 0\r\n\r\n")
-                .await
-                .unwrap();
+            let mut res = String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n");
+            for i in 0..150 {
+                res.push_str(&format!("x-header-{}: {}\r\n", i, i));
+            }
+            res.push_str("\r\n");
+            sock.write_all(res.as_bytes()).await.unwrap();
         };
 
         let client = async move {
             let tcp = tcp_connect(&addr).await.expect("connect");
-            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .max_headers(200)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
 
             tokio::task::spawn(async move {
                 conn.await.expect("http conn");
@@ -1441,631 +1442,2715 @@ mod conn {
                 .uri("/a")
                 .body(Empty::<Bytes>::new())
                 .unwrap();
-            let mut res = client.send_request(req).await.expect("send_request");
+            let res = client.send_request(req).await.expect("send_request");
             assert_eq!(res.status(), hyper::StatusCode::OK);
-            assert_eq!(
-                res.extensions()
-                    .get::<hyper::ext::ReasonPhrase>()
-                    .expect("custom reason phrase is present")
-                    .as_bytes(),
-                &b"Alright"[..]
-            );
-            assert_eq!(res.headers().len(), 1);
-            assert_eq!(
-                res.headers().get(http::header::CONTENT_LENGTH).unwrap(),
-                "0"
-            );
-            assert!(res.body_mut().frame().await.is_none());
+            assert_eq!(res.headers().len(), 151);
         };
 
         future::join(server, client).await;
     }
 
-    #[test]
-    fn incoming_content_length() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
-
-        let (tx1, rx1) = oneshot::channel();
+    #[tokio::test]
+    async fn http1_parser_presets() {
+        async fn get(
+            builder: &conn::http1::Builder,
+            response: &'static [u8],
+        ) -> hyper::Result<Response<hyper::body::Incoming>> {
+            let (listener, addr) = setup_tk_test_server().await;
+            tokio::spawn(async move {
+                let mut sock = listener.accept().await.unwrap().0;
+                let mut buf = [0; 4096];
+                sock.read(&mut buf).await.expect("read 1");
+                sock.write_all(response).await.unwrap();
+            });
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = builder.handshake(tcp).await.expect("handshake");
+            tokio::task::spawn(async move {
+                let _ = conn.await;
+            });
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
                 .unwrap();
-            let mut buf = [0; 4096];
-            let n = sock.read(&mut buf).expect("read 1");
+            client.send_request(req).await
+        }
 
-            let expected = "GET / HTTP/1.1\r\n\r\n";
-            assert_eq!(s(&buf[..n]), expected);
+        // obsolete line folding, and multiple spaces in the status line
+        const RFC: &[u8] = b"HTTP/1.1  200  OK\r\nFold: a\r\n b\r\nContent-Length: 0\r\n\r\n";
+        // a space before a colon, and a line without one
+        const LENIENT: &[u8] =
+            b"HTTP/1.1 200 OK\r\nSpaced : a\r\nno colon\r\nContent-Length: 0\r\n\r\n";
 
-            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
-                .unwrap();
-            let _ = tx1.send(());
-        });
+        let mut builder = conn::http1::Builder::new();
+        builder.strict();
+        get(&builder, RFC)
+            .await
+            .expect_err("strict rejects obs-fold");
+        get(&builder, LENIENT)
+            .await
+            .expect_err("strict rejects invalid headers");
 
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+        builder.rfc();
+        let res = get(&builder, RFC).await.expect("rfc allows obs-fold");
+        assert_eq!(res.headers()["fold"], "a b");
+        get(&builder, LENIENT)
+            .await
+            .expect_err("rfc rejects invalid headers");
 
-        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+        builder.lenient();
+        get(&builder, RFC).await.expect("lenient allows obs-fold");
+        let res = get(&builder, LENIENT)
+            .await
+            .expect("lenient allows invalid headers");
+        assert_eq!(res.headers()["spaced"], "a");
+        assert_eq!(res.headers().len(), 2);
+
+        // the presets can be adjusted afterwards
+        builder
+            .strict()
+            .allow_obsolete_multiline_headers_in_responses(true);
+        get(&builder, RFC)
+            .await
+            .expect_err("multiple spaces in status line");
+    }
 
-        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+    #[tokio::test]
+    async fn http1_on_informational() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        let req = Request::builder()
-            .uri("/")
-            .body(Empty::<Bytes>::new())
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(
+                b"HTTP/1.1 102 Processing\r\n\r\n\
+                  HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+                  HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await
             .unwrap();
-        let res = client.send_request(req).and_then(move |mut res| {
-            assert_eq!(res.status(), hyper::StatusCode::OK);
-            assert_eq!(res.body().size_hint().exact(), Some(5));
-            assert!(!res.body().is_end_stream());
-            poll_fn(move |ctx| Pin::new(res.body_mut()).poll_frame(ctx)).map(Option::unwrap)
-        });
+        };
 
-        let rx = rx1.expect("thread panicked");
-        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-        let chunk = rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
-        assert_eq!(chunk.data_ref().unwrap().len(), 5);
-    }
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
 
-    #[test]
-    fn aborted_body_isnt_completed() {
-        let _ = ::pretty_env_logger::try_init();
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
 
-        let (tx, rx) = oneshot::channel();
-        let server = thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
+            let informational = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
                 .unwrap();
-            let expected = "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello\r\n";
-            let mut buf = vec![0; expected.len()];
-            sock.read_exact(&mut buf).expect("read 1");
-            assert_eq!(s(&buf), expected);
-
-            let _ = tx.send(());
+            let seen = informational.clone();
+            hyper::ext::on_informational(&mut req, move |res| {
+                let link = res.headers().get("link").cloned();
+                seen.lock().unwrap().push((res.status(), link));
+            });
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
 
-            assert_eq!(sock.read(&mut buf).expect("read 2"), 0);
-        });
+            let informational = informational.lock().unwrap();
+            assert_eq!(
+                *informational,
+                vec![
+                    (hyper::StatusCode::PROCESSING, None),
+                    (
+                        hyper::StatusCode::from_u16(103).unwrap(),
+                        Some(hyper::header::HeaderValue::from_static(
+                            "</style.css>; rel=preload"
+                        ))
+                    ),
+                ]
+            );
+        };
 
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+        future::join(server, client).await;
+    }
 
-        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+    #[tokio::test]
+    async fn http1_early_hints() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(
+                b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+                  HTTP/1.1 103 Early Hints\r\nLink: </script.js>; rel=preload\r\n\r\n\
+                  HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            sock.read(&mut buf).await.expect("read 2");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
 
-        let (mut sender, recv) =
-            mpsc::channel::<Result<Frame<Bytes>, Box<dyn Error + Send + Sync>>>(0);
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
 
-        let sender = thread::spawn(move || {
-            sender
-                .try_send(Ok(Frame::data("hello".into())))
-                .expect("try_send_data");
-            support::runtime().block_on(rx).unwrap();
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
 
-            // Aborts the body in an abnormal fashion.
-            let _ = sender.try_send(Err(Box::new(std::io::Error::new(
-                io::ErrorKind::Other,
-                "body write aborted",
-            ))));
-        });
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            let hints = res
+                .extensions()
+                .get::<hyper::ext::EarlyHints>()
+                .expect("early hints are present");
+            let links = hints.headers().get_all("link").iter().collect::<Vec<_>>();
+            assert_eq!(
+                links,
+                ["</style.css>; rel=preload", "</script.js>; rel=preload"]
+            );
 
-        let req = Request::builder()
-            .method(Method::POST)
-            .uri("/")
-            .body(StreamBody::new(recv))
-            .unwrap();
-        let res = client.send_request(req);
-        rt.block_on(res).unwrap_err();
+            // the hints are not carried over to the next response
+            let req = Request::builder()
+                .uri("/b")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert!(res.extensions().get::<hyper::ext::EarlyHints>().is_none());
+        };
 
-        server.join().expect("server thread panicked");
-        sender.join().expect("sender thread panicked");
+        future::join(server, client).await;
     }
 
-    #[test]
-    fn uri_absolute_form() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
-
-        let (tx1, rx1) = oneshot::channel();
+    #[tokio::test]
+    async fn http1_response_timings() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
-                .unwrap();
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
             let mut buf = [0; 4096];
-            let n = sock.read(&mut buf).expect("read 1");
-
-            // Notably:
-            // - Still no Host header, since it wasn't set
-            let expected = "GET http://hyper.local/a HTTP/1.1\r\n\r\n";
-            assert_eq!(s(&buf[..n]), expected);
-
+            sock.read(&mut buf).await.expect("read 1");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sock.write_all(b"HTTP/1.1 103 Early Hints\r\n\r\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
             sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
                 .unwrap();
-            let _ = tx1.send(());
-        });
+            sock.read(&mut buf).await.expect("read 2");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
 
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .response_timings(true)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
 
-        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
 
-        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+            let sent = std::time::Instant::now();
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            let timings = *res
+                .extensions()
+                .get::<hyper::ext::ResponseTimings>()
+                .expect("timings are present");
+            assert!(timings.request_written() >= sent);
+            // the first byte is that of the 103, not the final response
+            assert!(timings.time_to_first_byte() >= Duration::from_millis(50));
+            assert!(timings.header_duration() >= Duration::from_millis(50));
 
-        let req = Request::builder()
-            .uri("http://hyper.local/a")
-            .body(Empty::<Bytes>::new())
-            .unwrap();
+            let req = Request::builder()
+                .uri("/b")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            let next = res
+                .extensions()
+                .get::<hyper::ext::ResponseTimings>()
+                .expect("timings are present");
+            assert!(next.request_written() >= timings.headers_received());
+            assert!(next.first_byte() >= next.request_written());
+            assert!(next.headers_received() >= next.first_byte());
+        };
 
-        let res = client.send_request(req).and_then(move |res| {
-            assert_eq!(res.status(), hyper::StatusCode::OK);
-            concat(res)
-        });
-        let rx = rx1.expect("thread panicked");
-        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-        rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
+        future::join(server, client).await;
     }
 
-    #[test]
-    fn http1_conn_coerces_http2_request() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
-
-        let (tx1, rx1) = oneshot::channel();
+    async fn http1_excess_body(
+        excess: conn::http1::ExcessBody,
+    ) -> (hyper::Result<Bytes>, hyper::Result<()>) {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
-                .unwrap();
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
             let mut buf = [0; 4096];
-            let n = sock.read(&mut buf).expect("read 1");
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello\r\n\r\n")
+                .await
+                .unwrap();
+            // keep the connection open, until the client closes it
+            while sock.read(&mut buf).await.unwrap_or(0) > 0 {}
+        };
 
-            // Not HTTP/2, nor panicked
-            let expected = "GET /a HTTP/1.1\r\n\r\n";
-            assert_eq!(s(&buf[..n]), expected);
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .excess_body(excess)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
+            let conn = tokio::task::spawn(conn);
 
-            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
                 .unwrap();
-            let _ = tx1.send(());
-        });
-
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
-
-        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            let body = res.into_body().collect().await.map(|b| b.to_bytes());
+            (body, conn.await.unwrap())
+        };
 
-        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+        future::join(server, client).await.1
+    }
 
-        let req = Request::builder()
-            .uri("/a")
-            .version(hyper::Version::HTTP_2)
-            .body(Empty::<Bytes>::new())
-            .unwrap();
+    #[tokio::test]
+    async fn http1_excess_body_reject() {
+        let (body, conn) = http1_excess_body(conn::http1::ExcessBody::Reject).await;
+        assert_eq!(body.expect("body"), "hello");
+        conn.expect_err("connection fails");
+    }
 
-        let res = client.send_request(req).and_then(move |res| {
-            assert_eq!(res.status(), hyper::StatusCode::OK);
-            concat(res)
-        });
-        let rx = rx1.expect("thread panicked");
-        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-        rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
+    #[tokio::test]
+    async fn http1_excess_body_discard() {
+        let (body, conn) = http1_excess_body(conn::http1::ExcessBody::Discard).await;
+        assert_eq!(body.expect("body"), "hello");
+        conn.expect("connection closes without an error");
     }
 
-    #[test]
-    fn pipeline() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
+    #[tokio::test]
+    async fn http1_excess_body_fail_response() {
+        let (body, conn) = http1_excess_body(conn::http1::ExcessBody::FailResponse).await;
+        body.expect_err("body fails");
+        conn.expect("connection closes without an error");
+    }
 
-        let (tx1, rx1) = oneshot::channel();
+    #[tokio::test]
+    async fn http1_absolute_form() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
-                .unwrap();
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
             let mut buf = [0; 4096];
-            sock.read(&mut buf).expect("read 1");
+            let n = sock.read(&mut buf).await.expect("read 1");
+
+            let expected = "GET http://example.com/a HTTP/1.1\r\nhost: example.com\r\n\r\n";
+            assert_eq!(s(&buf[..n]), expected);
+
             sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
                 .unwrap();
 
-            let _ = tx1.send(Ok::<_, ()>(()));
-        });
-
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+            // the second request fails without being written
+            let n = sock.read(&mut buf).await.expect("read 2");
+            assert_eq!(n, 0);
+        };
 
-        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .absolute_form(true)
+                .handshake(tcp)
+                .await
+                .expect("handshake");
 
-        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+            tokio::task::spawn(async move {
+                let _ = conn.await;
+            });
 
-        let req = Request::builder()
-            .uri("/a")
-            .body(Empty::<Bytes>::new())
-            .unwrap();
-        let res1 = client.send_request(req).and_then(move |res| {
+            let req = Request::builder()
+                .uri("/a")
+                .header("host", "example.com")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
             assert_eq!(res.status(), hyper::StatusCode::OK);
-            concat(res)
-        });
 
-        // pipelined request will hit NotReady, and thus should return an Error::Cancel
-        let req = Request::builder()
-            .uri("/b")
-            .body(Empty::<Bytes>::new())
-            .unwrap();
-        let res2 = client.send_request(req).map(|result| {
-            let err = result.expect_err("res2");
-            assert!(err.is_canceled(), "err not canceled, {:?}", err);
-            Ok::<_, ()>(())
-        });
+            // without a Host header, there's no authority to use
+            client.ready().await.expect("ready");
+            let req = Request::builder()
+                .uri("/b")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let err = client.send_request(req).await.expect_err("no host");
+            assert!(err.is_user(), "{:?}", err);
+        };
 
-        let rx = rx1.expect("thread panicked");
-        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-        rt.block_on(future::join3(res1, res2, rx).map(|r| r.0))
-            .unwrap();
+        future::join(server, client).await;
     }
 
-    #[test]
-    fn upgrade() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
+    #[tokio::test]
+    async fn http1_try_send_request_returns_request() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        let (tx1, rx1) = oneshot::channel();
+        tokio::spawn(async move {
+            let _sock = listener.accept().await.unwrap().0;
+            future::pending::<()>().await;
+        });
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
-                .unwrap();
-            let mut buf = [0; 4096];
-            sock.read(&mut buf).expect("read 1");
-            sock.write_all(
-                b"\
-                HTTP/1.1 101 Switching Protocols\r\n\
-                Upgrade: foobar\r\n\
-                \r\n\
-                foobar=ready\
-            ",
-            )
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::handshake::<_, Full<Bytes>>(tcp)
+            .await
+            .expect("handshake");
+        drop(conn);
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Full::new(Bytes::from_static(b"not cloneable")))
             .unwrap();
-            let _ = tx1.send(());
+        let mut err = client.try_send_request(req).await.expect_err("closed");
+        assert!(err.error().is_canceled(), "{:?}", err);
+        let req = err.take_message().expect("request is returned");
+        assert_eq!(req.uri(), "/a");
+        assert!(err.take_message().is_none());
+    }
 
-            let n = sock.read(&mut buf).expect("read 2");
-            assert_eq!(&buf[..n], b"foo=bar");
-            sock.write_all(b"bar=foo").expect("write 2");
-        });
+    #[tokio::test]
+    async fn http1_without_shutdown_reclaims_idle_io() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert_eq!(s(&buf[..n]), "GET /a HTTP/1.1\r\n\r\n");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
 
-        let io = DebugStream {
-            tcp,
-            shutdown_called: false,
+            // the same socket is reused by the next connection
+            let n = sock.read(&mut buf).await.expect("read 2");
+            assert_eq!(s(&buf[..n]), "GET /b HTTP/1.1\r\n\r\n");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
         };
 
-        let (mut client, mut conn) = rt.block_on(conn::http1::handshake(io)).unwrap();
-
-        {
-            let until_upgrade = poll_fn(|ctx| conn.poll_without_shutdown(ctx));
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
 
             let req = Request::builder()
                 .uri("/a")
                 .body(Empty::<Bytes>::new())
                 .unwrap();
-            let res = client.send_request(req).and_then(move |res| {
-                assert_eq!(res.status(), hyper::StatusCode::SWITCHING_PROTOCOLS);
-                assert_eq!(res.headers()["Upgrade"], "foobar");
-                concat(res)
+            let send = async move {
+                let res = client.send_request(req).await.expect("send_request");
+                let body = concat(res).await.expect("body");
+                assert_eq!(body, "hello");
+                // dropping the sender leaves the connection idle and done
+            };
+            let (parts, ()) = future::join(conn.without_shutdown(), send).await;
+            let parts = parts.expect("without_shutdown");
+            assert!(parts.read_buf.is_empty());
+
+            let (mut client, conn) = conn::http1::handshake(parts.io).await.expect("handshake 2");
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
             });
+            let req = Request::builder()
+                .uri("/b")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request 2");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+        };
 
-            let rx = rx1.expect("thread panicked");
-            let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-            rt.block_on(future::join3(until_upgrade, res, rx).map(|r| r.0))
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_request_header_case_map() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
                 .unwrap();
+            s(&buf[..n]).to_string()
+        };
 
-            // should not be ready now
-            rt.block_on(poll_fn(|ctx| {
-                assert!(client.poll_ready(ctx).is_pending());
-                Poll::Ready(Ok::<_, ()>(()))
-            }))
-            .unwrap();
-        }
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+            tokio::task::spawn(async move {
+                let _ = conn.await;
+            });
 
-        let parts = conn.into_parts();
-        let io = parts.io;
-        let buf = parts.read_buf;
+            let mut casing = hyper::ext::HeaderCaseMap::new();
+            casing.insert("SOAPAction").unwrap();
+            casing.append("X-Dup").unwrap();
+            casing.append("x-DUP").unwrap();
+            assert!(casing.insert("not a header").is_err());
 
-        assert_eq!(buf, b"foobar=ready"[..]);
-        assert!(!io.shutdown_called, "upgrade shouldn't shutdown AsyncWrite");
-        rt.block_on(poll_fn(|ctx| {
-            let ready = client.poll_ready(ctx);
-            assert!(matches!(ready, Poll::Ready(Err(_))));
-            ready
-        }))
-        .unwrap_err();
+            let mut req = Request::builder()
+                .uri("/a")
+                .header("soapaction", "urn:quote")
+                .header("x-dup", "1")
+                .header("x-dup", "2")
+                .header("x-other", "3")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            req.extensions_mut().insert(casing);
+            client.send_request(req).await.expect("send_request");
+        };
 
-        let mut io = io.tcp.inner();
-        let mut vec = vec![];
-        rt.block_on(io.write_all(b"foo=bar")).unwrap();
-        rt.block_on(io.read_to_end(&mut vec)).unwrap();
-        assert_eq!(vec, b"bar=foo");
+        let (received, ()) = future::join(server, client).await;
+        assert_eq!(
+            received,
+            "GET /a HTTP/1.1\r\n\
+             SOAPAction: urn:quote\r\n\
+             X-Dup: 1\r\n\
+             x-DUP: 2\r\n\
+             x-other: 3\r\n\
+             \r\n"
+        );
     }
 
-    #[test]
-    fn connect_method() {
-        let (server, addr) = setup_std_test_server();
-        let rt = support::runtime();
-
-        let (tx1, rx1) = oneshot::channel();
+    #[tokio::test]
+    async fn http1_preserve_header_order() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        thread::spawn(move || {
-            let mut sock = server.accept().unwrap().0;
-            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
-            sock.set_write_timeout(Some(Duration::from_secs(5)))
-                .unwrap();
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
             let mut buf = [0; 4096];
-            sock.read(&mut buf).expect("read 1");
+            sock.read(&mut buf).await.expect("read 1");
             sock.write_all(
-                b"\
-                HTTP/1.1 200 OK\r\n\
-                \r\n\
-                foobar=ready\
-            ",
+                b"HTTP/1.1 200 OK\r\n\
+                  X-B: 1\r\n\
+                  X-A: 2\r\n\
+                  X-B: 3\r\n\
+                  Content-Length: 0\r\n\
+                  \r\n",
             )
+            .await
             .unwrap();
-            let _ = tx1.send(Ok::<_, ()>(()));
+        });
 
-            let n = sock.read(&mut buf).expect("read 2");
-            assert_eq!(&buf[..n], b"foo=bar", "sock read 2 bytes");
-            sock.write_all(b"bar=foo").expect("write 2");
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .preserve_header_order(true)
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
         });
 
-        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+
+        let order = res
+            .extensions()
+            .get::<hyper::ext::OriginalHeaderOrder>()
+            .expect("header order");
+        let received = order
+            .get_in_order()
+            .map(|(name, idx)| {
+                let value = res.headers().get_all(name).iter().nth(*idx).unwrap();
+                format!("{}: {}", name, value.to_str().unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            ["x-b: 1", "x-a: 2", "x-b: 3", "content-length: 0"]
+        );
+    }
+
+    async fn expect_continue_request(
+        addr: SocketAddr,
+    ) -> (conn::http1::SendRequest<Full<Bytes>>, Request<Full<Bytes>>) {
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (client, conn) = conn::http1::Builder::new()
+            .timer(TokioTimer)
+            .expect_continue_timeout(Duration::from_millis(200))
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/upload")
+            .header("expect", "100-continue")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        (client, req)
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_waits_for_100() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read head");
+            assert!(s(&buf[..n]).ends_with("\r\n\r\n"), "body sent early");
+
+            // the body isn't sent before the 100 Continue
+            tokio::time::timeout(Duration::from_millis(50), sock.read(&mut buf))
+                .await
+                .expect_err("body sent before 100 Continue");
+
+            sock.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await
+                .unwrap();
+            let n = sock.read(&mut buf).await.expect("read body");
+            assert_eq!(s(&buf[..n]), "hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let (mut client, req) = expect_continue_request(addr).await;
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_final_response_skips_body() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read head");
+            assert!(s(&buf[..n]).ends_with("\r\n\r\n"), "body sent early");
+
+            sock.write_all(b"HTTP/1.1 417 Expectation Failed\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            // the connection is closed without sending the body
+            let n = sock.read(&mut buf).await.expect("read eof");
+            assert_eq!(n, 0, "body sent after final response");
+        };
+
+        let client = async move {
+            let (mut client, req) = expect_continue_request(addr).await;
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+            client.closed().await;
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_timeout_sends_body() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read head");
+            assert!(s(&buf[..n]).ends_with("\r\n\r\n"), "body sent early");
+
+            // without a 100 Continue, the body follows after the timeout
+            let n = sock.read(&mut buf).await.expect("read body");
+            assert_eq!(s(&buf[..n]), "hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let (mut client, req) = expect_continue_request(addr).await;
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_request_trailers() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = Vec::new();
+            let mut chunk = [0; 4096];
+            // read until the end of the trailer section, after the last chunk
+            while !(s(&buf).contains("\r\n0\r\n") && buf.ends_with(b"\r\n\r\n")) {
+                let n = sock.read(&mut chunk).await.expect("read");
+                assert_ne!(n, 0, "eof before trailers");
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            buf
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+            tokio::task::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+            trailers.insert("x-undeclared", http::HeaderValue::from_static("nope"));
+            let frames = vec![
+                Ok::<_, std::convert::Infallible>(Frame::data(Bytes::from("hello"))),
+                Ok(Frame::trailers(trailers)),
+            ];
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header("trailer", "grpc-status")
+                .body(StreamBody::new(futures_util::stream::iter(frames)))
+                .unwrap();
+            client.send_request(req).await.expect("send_request");
+        };
+
+        let (buf, ()) = future::join(server, client).await;
+        assert_eq!(
+            s(&buf),
+            "POST /a HTTP/1.1\r\n\
+             trailer: grpc-status\r\n\
+             transfer-encoding: chunked\r\n\
+             \r\n\
+             5\r\nhello\r\n\
+             0\r\n\
+             grpc-status: 0\r\n\
+             \r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn http1_header_read_timeout() {
+        let (listener, addr) = setup_tk_test_server().await;
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            // only part of the head, then stall
+            sock.write_all(b"HTTP/1.1 200 OK\r\n").await.unwrap();
+            let _ = done_rx.await;
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .timer(TokioTimer)
+            .header_read_timeout(Duration::from_millis(100))
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        let conn = tokio::task::spawn(conn);
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(5), client.send_request(req))
+            .await
+            .expect("timed out in time")
+            .expect_err("response head never completes");
+        assert!(err.is_timeout(), "{:?}", err);
+        conn.await.unwrap().expect("conn");
+        drop(done_tx);
+    }
+
+    #[tokio::test]
+    async fn http1_header_read_timeout_not_applied_to_body() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            sock.write_all(b"hello").await.unwrap();
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .timer(TokioTimer)
+            .header_read_timeout(Duration::from_millis(100))
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.collect().await.unwrap().to_bytes(), "hello");
+    }
+
+    #[tokio::test]
+    async fn http1_request_deadline_closes_connection() {
+        let (listener, addr) = setup_tk_test_server().await;
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            // never respond
+            let _ = done_rx.await;
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .timer(TokioTimer)
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        let conn = tokio::task::spawn(conn);
+
+        let mut req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        req.extensions_mut()
+            .insert(hyper::ext::Deadline::after(Duration::from_millis(100)));
+        let err = tokio::time::timeout(Duration::from_secs(5), client.send_request(req))
+            .await
+            .expect("timed out in time")
+            .expect_err("server never responds");
+        assert!(err.is_timeout(), "{:?}", err);
+
+        tokio::time::timeout(Duration::from_secs(5), conn)
+            .await
+            .expect("connection closed in time")
+            .unwrap()
+            .expect("conn");
+        drop(done_tx);
+    }
+
+    #[tokio::test]
+    async fn http1_handshake_with_read_buf() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert!(buf[..n].starts_with(b"GET /a HTTP/1.1\r\n"));
+            // the status line was already read by the client
+            sock.write_all(b"Content-Length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .handshake_with_read_buf(tcp, Bytes::from_static(b"HTTP/1.1 200 OK\r\n"))
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            conn.await.expect("http conn");
+        });
+        // let the connection see the read buffer before a request is sent
+        tokio::task::yield_now().await;
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.collect().await.unwrap().to_bytes(), "hello");
+    }
+
+    #[tokio::test]
+    async fn http1_is_keep_alive() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            sock.read(&mut buf).await.expect("read 2");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            drain_til_eof(sock).await.expect("drain");
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+        tokio::task::spawn(async move {
+            conn.await.expect("http conn");
+        });
+        assert!(client.is_keep_alive());
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request 1");
+        res.collect().await.unwrap();
+        assert!(client.is_keep_alive());
+
+        client.ready().await.expect("ready");
+        let req = Request::builder()
+            .uri("/b")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request 2");
+        assert!(!client.is_keep_alive());
+        res.collect().await.unwrap();
+        assert!(!client.is_keep_alive());
+    }
+
+    #[tokio::test]
+    async fn http1_byte_counts() {
+        let (listener, addr) = setup_tk_test_server().await;
+        const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(RESPONSE).await.unwrap();
+            n
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+            let counts = conn.byte_counts();
+            assert_eq!(counts.bytes_read(), 0);
+            assert_eq!(counts.bytes_written(), 0);
+            tokio::task::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            let body = res.collect().await.unwrap().to_bytes();
+            assert_eq!(body, "hello");
+            counts
+        };
+
+        let (n, counts) = future::join(server, client).await;
+        assert_eq!(counts.bytes_written(), n as u64);
+        assert_eq!(counts.bytes_read(), RESPONSE.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn http1_closed_resolves_when_server_closes_idle() {
+        let (listener, addr) = setup_tk_test_server().await;
+        let (close_tx, close_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).await.expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            let _ = close_rx.await;
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let closed = client.closed();
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        client.send_request(req).await.expect("send_request");
+        assert!(!client.is_closed());
+
+        drop(close_tx);
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("closed in time");
+        assert!(client.is_closed());
+    }
+
+    async fn read_requests(sock: &mut TcpStream, count: usize) -> String {
+        let mut received = Vec::new();
+        let mut buf = [0; 4096];
+        while s(&received).matches("\r\n\r\n").count() < count {
+            let n = sock.read(&mut buf).await.expect("read");
+            assert_ne!(n, 0, "eof before {} requests", count);
+            received.extend_from_slice(&buf[..n]);
+        }
+        s(&received).to_string()
+    }
+
+    async fn send_pipelined(
+        client: &mut conn::http1::SendRequest<Empty<Bytes>>,
+        paths: &[&str],
+    ) -> Vec<impl std::future::Future<Output = hyper::Result<Response<hyper::body::Incoming>>>>
+    {
+        let mut responses = Vec::new();
+        for path in paths {
+            client.ready().await.expect("ready");
+            let req = Request::builder()
+                .uri(*path)
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            responses.push(client.send_request(req));
+        }
+        responses
+    }
+
+    #[tokio::test]
+    async fn http1_pipelining() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            // every request is sent before any response
+            let received = read_requests(&mut sock, 3).await;
+            assert_eq!(
+                received,
+                "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nHEAD /c HTTP/1.1\r\n\r\n"
+            );
+            sock.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na\
+                  HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nbc\r\n0\r\n\r\n\
+                  HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            let _ = sock.read(&mut [0; 1]).await;
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .pipeline_depth(3)
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let mut responses = Vec::new();
+        for (method, path) in [
+            (Method::GET, "/a"),
+            (Method::GET, "/b"),
+            (Method::HEAD, "/c"),
+        ] {
+            client.ready().await.expect("ready");
+            let req = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            responses.push(client.send_request(req));
+        }
+
+        let mut bodies = Vec::new();
+        for res in responses {
+            let res = res.await.expect("response");
+            bodies.push(res.collect().await.unwrap().to_bytes());
+        }
+        assert_eq!(bodies, ["a", "bc", ""]);
+    }
+
+    #[tokio::test]
+    async fn http1_pipelining_server_closes_mid_pipeline() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            read_requests(&mut sock, 3).await;
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na")
+                .await
+                .unwrap();
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .pipeline_depth(3)
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let mut responses = send_pipelined(&mut client, &["/a", "/b", "/c"])
+            .await
+            .into_iter();
+        let res = responses.next().unwrap().await.expect("response 1");
+        assert_eq!(res.collect().await.unwrap().to_bytes(), "a");
+        for res in responses {
+            let err = res.await.expect_err("unanswered request");
+            assert!(err.is_incomplete_message(), "{:?}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn http1_pipelining_connection_close_fails_pipelined() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            read_requests(&mut sock, 2).await;
+            sock.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 1\r\n\r\na")
+                .await
+                .unwrap();
+            let _ = sock.read(&mut [0; 1]).await;
+        });
+
+        let tcp = tcp_connect(&addr).await.expect("connect");
+        let (mut client, conn) = conn::http1::Builder::new()
+            .pipeline_depth(2)
+            .handshake(tcp)
+            .await
+            .expect("handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let mut responses = send_pipelined(&mut client, &["/a", "/b"]).await.into_iter();
+        let res = responses.next().unwrap().await.expect("response 1");
+        assert_eq!(res.collect().await.unwrap().to_bytes(), "a");
+        let err = responses
+            .next()
+            .unwrap()
+            .await
+            .expect_err("unanswered request");
+        assert!(err.is_incomplete_message(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn get_custom_reason_phrase() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+
+            // Notably:
+            // - Just a path, since just a path was set
+            // - No host, since no host was set
+            let expected = "GET /a HTTP/1.1\r\n\r\n";
+            assert_eq!(s(&buf[..n]), expected);
+
+            sock.write_all(b"HTTP/1.1 200 Alright\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let tcp = tcp_connect(&addr).await.expect("connect");
+            let (mut client, conn) = conn::http1::handshake(tcp).await.expect("handshake");
+
+            tokio::task::spawn(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let mut res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            assert_eq!(
+                res.extensions()
+                    .get::<hyper::ext::ReasonPhrase>()
+                    .expect("custom reason phrase is present")
+                    .as_bytes(),
+                &b"Alright"[..]
+            );
+            assert_eq!(res.headers().len(), 1);
+            assert_eq!(
+                res.headers().get(http::header::CONTENT_LENGTH).unwrap(),
+                "0"
+            );
+            assert!(res.body_mut().frame().await.is_none());
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_handshake_local_non_send_io() {
+        use std::rc::Rc;
+
+        // Holds an `Rc`, so neither it nor the connection is `Send`.
+        struct LocalIo {
+            tcp: TokioIo<TcpStream>,
+            _local: Rc<()>,
+        }
+
+        impl hyper::rt::Read for LocalIo {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: hyper::rt::ReadBufCursor<'_>,
+            ) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.tcp).poll_read(cx, buf)
+            }
+        }
+
+        impl hyper::rt::Write for LocalIo {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.tcp).poll_write(cx, buf)
+            }
+
+            fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.tcp).poll_flush(cx)
+            }
+
+            fn poll_shutdown(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.tcp).poll_shutdown(cx)
+            }
+        }
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).await.expect("read 1");
+            assert_eq!(s(&buf[..n]), "GET /a HTTP/1.1\r\n\r\n");
+
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
+        };
+
+        let local = tokio::task::LocalSet::new();
+        let client = local.run_until(async move {
+            let io = LocalIo {
+                tcp: tcp_connect(&addr).await.expect("connect"),
+                _local: Rc::new(()),
+            };
+            let (mut client, conn) = conn::http1::Builder::new()
+                .handshake_local(io)
+                .await
+                .expect("handshake");
+
+            tokio::task::spawn_local(async move {
+                conn.await.expect("http conn");
+            });
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            let body = concat(res).await.expect("body");
+            assert_eq!(body, "hello");
+        });
+
+        future::join(server, client).await;
+    }
+
+    #[test]
+    fn incoming_content_length() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).expect("read 1");
+
+            let expected = "GET / HTTP/1.1\r\n\r\n";
+            assert_eq!(s(&buf[..n]), expected);
+
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .unwrap();
+            let _ = tx1.send(());
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+
+        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+
+        let req = Request::builder()
+            .uri("/")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).and_then(move |mut res| {
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            assert_eq!(res.body().size_hint().exact(), Some(5));
+            assert!(!res.body().is_end_stream());
+            poll_fn(move |ctx| Pin::new(res.body_mut()).poll_frame(ctx)).map(Option::unwrap)
+        });
+
+        let rx = rx1.expect("thread panicked");
+        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+        let chunk = rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
+        assert_eq!(chunk.data_ref().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn aborted_body_isnt_completed() {
+        let _ = ::pretty_env_logger::try_init();
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx, rx) = oneshot::channel();
+        let server = thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let expected = "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello\r\n";
+            let mut buf = vec![0; expected.len()];
+            sock.read_exact(&mut buf).expect("read 1");
+            assert_eq!(s(&buf), expected);
+
+            let _ = tx.send(());
+
+            assert_eq!(sock.read(&mut buf).expect("read 2"), 0);
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+
+        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+
+        let (mut sender, recv) =
+            mpsc::channel::<Result<Frame<Bytes>, Box<dyn Error + Send + Sync>>>(0);
+
+        let sender = thread::spawn(move || {
+            sender
+                .try_send(Ok(Frame::data("hello".into())))
+                .expect("try_send_data");
+            support::runtime().block_on(rx).unwrap();
+
+            // Aborts the body in an abnormal fashion.
+            let _ = sender.try_send(Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                "body write aborted",
+            ))));
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/")
+            .body(StreamBody::new(recv))
+            .unwrap();
+        let res = client.send_request(req);
+        rt.block_on(res).unwrap_err();
+
+        server.join().expect("server thread panicked");
+        sender.join().expect("sender thread panicked");
+    }
+
+    #[test]
+    fn uri_absolute_form() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).expect("read 1");
+
+            // Notably:
+            // - Still no Host header, since it wasn't set
+            let expected = "GET http://hyper.local/a HTTP/1.1\r\n\r\n";
+            assert_eq!(s(&buf[..n]), expected);
+
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            let _ = tx1.send(());
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+
+        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+
+        let req = Request::builder()
+            .uri("http://hyper.local/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+
+        let res = client.send_request(req).and_then(move |res| {
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            concat(res)
+        });
+        let rx = rx1.expect("thread panicked");
+        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+        rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
+    }
+
+    #[test]
+    fn http1_conn_coerces_http2_request() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            let n = sock.read(&mut buf).expect("read 1");
+
+            // Not HTTP/2, nor panicked
+            let expected = "GET /a HTTP/1.1\r\n\r\n";
+            assert_eq!(s(&buf[..n]), expected);
+
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            let _ = tx1.send(());
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+
+        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+
+        let req = Request::builder()
+            .uri("/a")
+            .version(hyper::Version::HTTP_2)
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+
+        let res = client.send_request(req).and_then(move |res| {
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            concat(res)
+        });
+        let rx = rx1.expect("thread panicked");
+        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+        rt.block_on(future::join(res, rx).map(|r| r.0)).unwrap();
+    }
+
+    #[test]
+    fn pipeline() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).expect("read 1");
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+
+            let _ = tx1.send(Ok::<_, ()>(()));
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let (mut client, conn) = rt.block_on(conn::http1::handshake(tcp)).unwrap();
+
+        rt.spawn(conn.map_err(|e| panic!("conn error: {}", e)).map(|_| ()));
+
+        let req = Request::builder()
+            .uri("/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res1 = client.send_request(req).and_then(move |res| {
+            assert_eq!(res.status(), hyper::StatusCode::OK);
+            concat(res)
+        });
+
+        // pipelined request will hit NotReady, and thus should return an Error::Cancel
+        let req = Request::builder()
+            .uri("/b")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res2 = client.send_request(req).map(|result| {
+            let err = result.expect_err("res2");
+            assert!(err.is_canceled(), "err not canceled, {:?}", err);
+            Ok::<_, ()>(())
+        });
+
+        let rx = rx1.expect("thread panicked");
+        let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+        rt.block_on(future::join3(res1, res2, rx).map(|r| r.0))
+            .unwrap();
+    }
+
+    #[test]
+    fn upgrade() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).expect("read 1");
+            sock.write_all(
+                b"\
+                HTTP/1.1 101 Switching Protocols\r\n\
+                Upgrade: foobar\r\n\
+                \r\n\
+                foobar=ready\
+            ",
+            )
+            .unwrap();
+            let _ = tx1.send(());
+
+            let n = sock.read(&mut buf).expect("read 2");
+            assert_eq!(&buf[..n], b"foo=bar");
+            sock.write_all(b"bar=foo").expect("write 2");
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let io = DebugStream {
+            tcp,
+            shutdown_called: false,
+        };
+
+        let (mut client, mut conn) = rt.block_on(conn::http1::handshake(io)).unwrap();
+
+        {
+            let until_upgrade = poll_fn(|ctx| conn.poll_without_shutdown(ctx));
+
+            let req = Request::builder()
+                .uri("/a")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client.send_request(req).and_then(move |res| {
+                assert_eq!(res.status(), hyper::StatusCode::SWITCHING_PROTOCOLS);
+                assert_eq!(res.headers()["Upgrade"], "foobar");
+                concat(res)
+            });
+
+            let rx = rx1.expect("thread panicked");
+            let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+            rt.block_on(future::join3(until_upgrade, res, rx).map(|r| r.0))
+                .unwrap();
+
+            // should not be ready now
+            rt.block_on(poll_fn(|ctx| {
+                assert!(client.poll_ready(ctx).is_pending());
+                Poll::Ready(Ok::<_, ()>(()))
+            }))
+            .unwrap();
+        }
+
+        let parts = conn.into_parts();
+        let io = parts.io;
+        let buf = parts.read_buf;
+
+        assert_eq!(buf, b"foobar=ready"[..]);
+        assert!(!io.shutdown_called, "upgrade shouldn't shutdown AsyncWrite");
+        rt.block_on(poll_fn(|ctx| {
+            let ready = client.poll_ready(ctx);
+            assert!(matches!(ready, Poll::Ready(Err(_))));
+            ready
+        }))
+        .unwrap_err();
+
+        let mut io = io.tcp.inner();
+        let mut vec = vec![];
+        rt.block_on(io.write_all(b"foo=bar")).unwrap();
+        rt.block_on(io.read_to_end(&mut vec)).unwrap();
+        assert_eq!(vec, b"bar=foo");
+    }
+
+    #[test]
+    fn connect_method() {
+        let (server, addr) = setup_std_test_server();
+        let rt = support::runtime();
+
+        let (tx1, rx1) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            sock.set_write_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).expect("read 1");
+            sock.write_all(
+                b"\
+                HTTP/1.1 200 OK\r\n\
+                \r\n\
+                foobar=ready\
+            ",
+            )
+            .unwrap();
+            let _ = tx1.send(Ok::<_, ()>(()));
+
+            let n = sock.read(&mut buf).expect("read 2");
+            assert_eq!(&buf[..n], b"foo=bar", "sock read 2 bytes");
+            sock.write_all(b"bar=foo").expect("write 2");
+        });
+
+        let tcp = rt.block_on(tcp_connect(&addr)).unwrap();
+
+        let io = DebugStream {
+            tcp,
+            shutdown_called: false,
+        };
+
+        let (mut client, mut conn) = rt.block_on(conn::http1::handshake(io)).unwrap();
+
+        {
+            let until_tunneled = poll_fn(|ctx| conn.poll_without_shutdown(ctx));
+
+            let req = Request::builder()
+                .method("CONNECT")
+                .uri(addr.to_string())
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = client
+                .send_request(req)
+                .and_then(move |res| {
+                    assert_eq!(res.status(), hyper::StatusCode::OK);
+                    concat(res)
+                })
+                .map_ok(|body| {
+                    assert_eq!(body.as_ref(), b"");
+                });
+
+            let rx = rx1.expect("thread panicked");
+            let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
+            rt.block_on(future::join3(until_tunneled, res, rx).map(|r| r.0))
+                .unwrap();
+
+            // should not be ready now
+            rt.block_on(poll_fn(|ctx| {
+                assert!(client.poll_ready(ctx).is_pending());
+                Poll::Ready(Ok::<_, ()>(()))
+            }))
+            .unwrap();
+        }
+
+        let parts = conn.into_parts();
+        let io = parts.io;
+        let buf = parts.read_buf;
+
+        assert_eq!(buf, b"foobar=ready"[..]);
+        assert!(!io.shutdown_called, "tunnel shouldn't shutdown AsyncWrite");
+
+        rt.block_on(poll_fn(|ctx| {
+            let ready = client.poll_ready(ctx);
+            assert!(matches!(ready, Poll::Ready(Err(_))));
+            ready
+        }))
+        .unwrap_err();
+
+        let mut io = io.tcp.inner();
+        let mut vec = vec![];
+        rt.block_on(io.write_all(b"foo=bar")).unwrap();
+        rt.block_on(io.read_to_end(&mut vec)).unwrap();
+        assert_eq!(vec, b"bar=foo");
+    }
+
+    #[tokio::test]
+    async fn http2_detect_conn_eof() {
+        use futures_util::future;
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let (shdn_tx, mut shdn_rx) = tokio::sync::watch::channel(false);
+        tokio::task::spawn(async move {
+            use hyper::server::conn::http2;
+            use hyper::service::service_fn;
+
+            loop {
+                tokio::select! {
+                    res = listener.accept() => {
+                        let (stream, _) = res.unwrap();
+                        let stream = TokioIo::new(stream);
+
+                        let service = service_fn(|_:Request<hyper::body::Incoming>| future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new())));
+
+                        let mut shdn_rx = shdn_rx.clone();
+                        tokio::task::spawn(async move {
+                            let mut conn = http2::Builder::new(TokioExecutor)
+                                .serve_connection(stream, service);
+
+                            tokio::select! {
+                                res = &mut conn => {
+                                    res.unwrap();
+                                }
+                                _ = shdn_rx.changed() => {
+                                    Pin::new(&mut conn).graceful_shutdown();
+                                    conn.await.unwrap();
+                                }
+                            }
+                        });
+                    }
+                    _ = shdn_rx.changed() => {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::task::spawn(async move {
+            conn.await.expect("client conn");
+        });
+
+        // Sanity check that client is ready
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect("client poll ready sanity");
+
+        let req = Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(Empty::<Bytes>::new())
+            .expect("request builder");
+
+        client.send_request(req).await.expect("req1 send");
+
+        // Sanity check that client is STILL ready
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect("client poll ready after");
+
+        // Trigger the server shutdown...
+        let _ = shdn_tx.send(true);
+
+        // Allow time for graceful shutdown roundtrips...
+        TokioTimer.sleep(Duration::from_millis(100)).await;
+
+        // After graceful shutdown roundtrips, the client should be closed...
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect_err("client should be closed");
+    }
+
+    #[tokio::test]
+    async fn http2_try_send_request_returns_request() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let _sock = listener.accept().await.unwrap().0;
+            future::pending::<()>().await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+        drop(conn);
+
+        let req = Request::builder()
+            .uri(format!("http://{}/a", addr))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let mut err = client.try_send_request(req).await.expect_err("closed");
+        let req = err.take_message().expect("request is returned");
+        assert_eq!(req.uri().path(), "/a");
+    }
+
+    #[tokio::test]
+    async fn http2_byte_counts() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            use hyper::server::conn::http2;
+            use hyper::service::service_fn;
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|_: Request<hyper::body::Incoming>| {
+                future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("hello"))))
+            });
+            let _ = http2::Builder::new(TokioExecutor)
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake(io)
+            .await
+            .expect("http handshake");
+        let counts = conn.byte_counts();
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let before = counts.bytes_read();
+        let req = Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        let body = res.collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+
+        // at least the preface, and the DATA frame's 9 byte header and body
+        assert!(counts.bytes_written() >= 24);
+        assert!(counts.bytes_read() >= before + 9 + 5);
+    }
+
+    #[tokio::test]
+    async fn http2_closed_resolves_when_server_closes() {
+        let (listener, addr) = setup_tk_test_server().await;
+        let (close_tx, close_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            use hyper::server::conn::http2;
+            use hyper::service::service_fn;
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|_: Request<hyper::body::Incoming>| {
+                future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            });
+            let mut conn =
+                http2::Builder::new(TokioExecutor).serve_connection(TokioIo::new(stream), service);
+            tokio::select! {
+                res = &mut conn => res.unwrap(),
+                _ = close_rx => {
+                    Pin::new(&mut conn).graceful_shutdown();
+                    conn.await.unwrap();
+                }
+            }
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake(io)
+            .await
+            .expect("http handshake");
+        tokio::task::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let closed = client.closed();
+        let req = Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        client.send_request(req).await.expect("send_request");
+
+        let _ = close_tx.send(());
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("closed in time");
+        assert!(client.is_closed());
+    }
+
+    #[tokio::test]
+    async fn http2_keep_alive_detects_unresponsive_server() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // spawn a server that reads but doesn't write
+        tokio::spawn(async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = sock.read(&mut buf).await.expect("server read");
+                if n == 0 {
+                    // server closed, lets go!
+                    break;
+                }
+            }
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (_client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .keep_alive_interval(Duration::from_secs(1))
+            .keep_alive_timeout(Duration::from_secs(1))
+            // enable while idle since we aren't sending requests
+            .keep_alive_while_idle(true)
+            .handshake::<_, hyper::body::Incoming>(io)
+            .await
+            .expect("http handshake");
+
+        conn.await.expect_err("conn should time out");
+    }
+
+    #[tokio::test]
+    async fn http2_keep_alive_timeout_callback() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // spawn a raw server that acknowledges the first PING, and then
+        // stops responding
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut preface = [0; 24];
+            sock.read_exact(&mut preface).await.unwrap();
+            // an empty SETTINGS frame
+            sock.write_all(&[0, 0, 0, 0x4, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            let mut acked = false;
+            loop {
+                let mut head = [0; 9];
+                if sock.read_exact(&mut head).await.is_err() {
+                    break;
+                }
+                let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+                let mut payload = vec![0; len];
+                sock.read_exact(&mut payload).await.unwrap();
+                // PING, without the ACK flag
+                if head[3] == 0x6 && head[4] == 0 && !acked {
+                    acked = true;
+                    let mut ack = vec![0, 0, 8, 0x6, 0x1, 0, 0, 0, 0];
+                    ack.extend_from_slice(&payload);
+                    sock.write_all(&ack).await.unwrap();
+                }
+            }
+        });
+
+        let (timeout_tx, mut timeout_rx) = mpsc::unbounded();
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (_client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .keep_alive_interval(Duration::from_millis(200))
+            .keep_alive_timeout(Duration::from_millis(200))
+            .keep_alive_while_idle(true)
+            .on_keep_alive_timeout(move |timeout| {
+                timeout_tx.unbounded_send(timeout.clone()).unwrap();
+            })
+            .handshake::<_, hyper::body::Incoming>(io)
+            .await
+            .expect("http handshake");
+
+        conn.await.expect_err("conn should time out");
+        let timeout = timeout_rx
+            .try_recv()
+            .expect("callback called before closing");
+        assert_eq!(timeout.recent_rtts().len(), 1);
+        assert_eq!(timeout.interval(), Duration::from_millis(200));
+        assert_eq!(timeout.timeout(), Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn http2_handshake_executor_spawn_error() {
+        #[derive(Clone)]
+        struct RefusingExecutor;
+
+        impl<F> hyper::rt::Executor<F> for RefusingExecutor {
+            fn execute(&self, _fut: F) {
+                unreachable!("try_execute is used to spawn tasks");
+            }
+
+            fn try_execute(&self, _fut: F) -> Result<(), Box<dyn Error + Send + Sync>> {
+                Err("executor is shutting down".into())
+            }
+        }
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            drain_til_eof(sock).await.expect("server read");
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let err = conn::http2::Builder::new(RefusingExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect_err("handshake should fail to spawn");
+
+        assert!(err.is_user());
+        assert!(err.is_execute());
+        assert_eq!(
+            err.source().expect("spawn error").to_string(),
+            "executor is shutting down"
+        );
+    }
+
+    #[tokio::test]
+    async fn http2_executor_receives_task_descriptors() {
+        use std::future::Future;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct NamingExecutor(Arc<Mutex<Vec<(u64, &'static str)>>>);
+
+        impl<F> hyper::rt::Executor<F> for NamingExecutor
+        where
+            F: Future + Send + 'static,
+            F::Output: Send + 'static,
+        {
+            fn execute(&self, _fut: F) {
+                unreachable!("execute_named is used to spawn tasks");
+            }
+
+            fn execute_named(
+                &self,
+                fut: F,
+                task: &hyper::rt::TaskDescriptor,
+            ) -> Result<(), Box<dyn Error + Send + Sync>> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((task.connection_id(), task.purpose()));
+                tokio::spawn(fut);
+                Ok(())
+            }
+        }
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            drain_til_eof(sock).await.expect("server read");
+        });
+
+        let exec = NamingExecutor::default();
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (_client, _conn) = conn::http2::Builder::new(exec.clone())
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (_client2, _conn2) = conn::http2::Builder::new(exec.clone())
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        let tasks = exec.0.lock().unwrap().clone();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].1, "h2 client connection");
+        assert_eq!(tasks[1].1, "h2 client connection");
+        assert_ne!(tasks[0].0, tasks[1].0, "connection ids are unique");
+    }
+
+    #[tokio::test]
+    async fn http2_keep_alive_not_while_idle() {
+        // This tests that not setting `http2_keep_alive_while_idle(true)`
+        // will use the default behavior which will NOT detect the server
+        // is unresponsive while no streams are active.
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // spawn a server that reads but doesn't write
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            drain_til_eof(sock).await.expect("server read");
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .keep_alive_interval(Duration::from_secs(1))
+            .keep_alive_timeout(Duration::from_secs(1))
+            .handshake::<_, hyper::body::Incoming>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        // sleep longer than keepalive would trigger
+        TokioTimer.sleep(Duration::from_secs(4)).await;
+
+        future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect("client should be open");
+    }
+
+    #[tokio::test]
+    async fn http2_keep_alive_closes_open_streams() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // spawn a server that reads but doesn't write
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            drain_til_eof(sock).await.expect("server read");
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .keep_alive_interval(Duration::from_secs(1))
+            .keep_alive_timeout(Duration::from_secs(1))
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            let err = conn.await.expect_err("client conn should timeout");
+            assert!(err.is_timeout());
+        });
+
+        let req = http::Request::new(Empty::<Bytes>::new());
+        let err = client
+            .send_request(req)
+            .await
+            .expect_err("request should timeout");
+        assert!(err.is_timeout());
+
+        let err = future::poll_fn(|ctx| client.poll_ready(ctx))
+            .await
+            .expect_err("client should be closed");
+        assert!(
+            err.is_closed(),
+            "poll_ready error should be closed: {:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn http2_request_deadline_resets_stream() {
+        use hyper::service::service_fn;
+        use std::sync::{Arc, Mutex};
+
+        let (listener, addr) = setup_tk_test_server().await;
+        let (reset_tx, reset_rx) = oneshot::channel::<()>();
+        let reset_tx = Arc::new(Mutex::new(Some(reset_tx)));
+
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .serve_connection(
+                    sock,
+                    service_fn(move |req| {
+                        let reset_tx = reset_tx.clone();
+                        async move {
+                            if req.uri().path() == "/slow" {
+                                // dropped when the client resets the stream
+                                let _reset_tx = reset_tx.lock().unwrap().take();
+                                future::pending::<()>().await;
+                            }
+                            Ok::<_, hyper::Error>(http::Response::new(Empty::<Bytes>::new()))
+                        }
+                    }),
+                )
+                .await
+                .expect("serve_connection");
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let mut req = Request::builder()
+            .uri("/slow")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        req.extensions_mut()
+            .insert(hyper::ext::Deadline::after(Duration::from_millis(100)));
+        let err = client
+            .send_request(req)
+            .await
+            .expect_err("request should time out");
+        assert!(err.is_timeout(), "{:?}", err);
+
+        tokio::time::timeout(Duration::from_secs(5), reset_rx)
+            .await
+            .expect("stream reset in time")
+            .expect_err("server service dropped");
+
+        // the connection is still usable
+        let req = Request::builder()
+            .uri("/fast")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn http2_ping() {
+        use hyper::service::service_fn;
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .serve_connection(
+                    sock,
+                    service_fn(|_req| async move {
+                        Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                    }),
+                )
+                .await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+        let conn = tokio::spawn(conn);
+
+        let rtt = client.ping().await.expect("ping");
+        assert!(rtt < Duration::from_secs(5), "rtt = {:?}", rtt);
+
+        // only one can be in flight, so these share it
+        let (a, b) = future::join(client.ping(), client.ping()).await;
+        assert_eq!(a.expect("ping a"), b.expect("ping b"));
 
-        let io = DebugStream {
-            tcp,
-            shutdown_called: false,
-        };
+        conn.abort();
+        let _ = conn.await;
+        let err = tokio::time::timeout(Duration::from_secs(5), client.ping())
+            .await
+            .expect("ping resolved in time")
+            .expect_err("connection is gone");
+        assert!(err.is_closed(), "{:?}", err);
+    }
 
-        let (mut client, mut conn) = rt.block_on(conn::http1::handshake(io)).unwrap();
+    #[tokio::test]
+    async fn http2_receives_server_push() {
+        let (listener, addr) = setup_tk_test_server().await;
 
-        {
-            let until_tunneled = poll_fn(|ctx| conn.poll_without_shutdown(ctx));
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
 
-            let req = Request::builder()
-                .method("CONNECT")
-                .uri(addr.to_string())
-                .body(Empty::<Bytes>::new())
-                .unwrap();
-            let res = client
-                .send_request(req)
-                .and_then(move |res| {
-                    assert_eq!(res.status(), hyper::StatusCode::OK);
-                    concat(res)
-                })
-                .map_ok(|body| {
-                    assert_eq!(body.as_ref(), b"");
-                });
+            let (_req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
 
-            let rx = rx1.expect("thread panicked");
-            let rx = rx.then(|_| TokioTimer.sleep(Duration::from_millis(200)));
-            rt.block_on(future::join3(until_tunneled, res, rx).map(|r| r.0))
-                .unwrap();
+            let pushed = Request::get("http://localhost/pushed").body(()).unwrap();
+            let mut pushed = respond.push_request(pushed).unwrap();
+            let mut send_stream = respond.send_response(Response::new(()), false).unwrap();
 
-            // should not be ready now
-            rt.block_on(poll_fn(|ctx| {
-                assert!(client.poll_ready(ctx).is_pending());
-                Poll::Ready(Ok::<_, ()>(()))
-            }))
-            .unwrap();
-        }
+            let mut pushed_stream = pushed.send_response(Response::new(()), false).unwrap();
+            pushed_stream.send_data("pushed".into(), true).unwrap();
+            send_stream.send_data("hello".into(), true).unwrap();
+        });
 
-        let parts = conn.into_parts();
-        let io = parts.io;
-        let buf = parts.read_buf;
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .enable_push(true)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
 
-        assert_eq!(buf, b"foobar=ready"[..]);
-        assert!(!io.shutdown_called, "tunnel shouldn't shutdown AsyncWrite");
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
 
-        rt.block_on(poll_fn(|ctx| {
-            let ready = client.poll_ready(ctx);
-            assert!(matches!(ready, Poll::Ready(Err(_))));
-            ready
-        }))
-        .unwrap_err();
+        let req = Request::get("http://localhost/")
+            .body(Empty::new())
+            .unwrap();
+        let mut res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), StatusCode::OK);
 
-        let mut io = io.tcp.inner();
-        let mut vec = vec![];
-        rt.block_on(io.write_all(b"foo=bar")).unwrap();
-        rt.block_on(io.read_to_end(&mut vec)).unwrap();
-        assert_eq!(vec, b"bar=foo");
+        let mut push_promises = res
+            .extensions_mut()
+            .remove::<conn::http2::PushPromises>()
+            .expect("push promises");
+        let promise = push_promises
+            .push_promise()
+            .await
+            .expect("a push promise")
+            .expect("push promise");
+        assert_eq!(promise.request().uri().path(), "/pushed");
+
+        let (_, pushed) = promise.into_parts();
+        let pushed = pushed.await.expect("pushed response");
+        assert_eq!(pushed.status(), StatusCode::OK);
+        let body = concat(pushed.into_body()).await.unwrap();
+        assert_eq!(body, "pushed");
+
+        let body = concat(res.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+        assert!(push_promises.push_promise().await.is_none());
     }
 
     #[tokio::test]
-    async fn http2_detect_conn_eof() {
-        use futures_util::future;
+    async fn http2_peer_settings() {
+        use hyper::service::service_fn;
 
         let (listener, addr) = setup_tk_test_server().await;
 
-        let (shdn_tx, mut shdn_rx) = tokio::sync::watch::channel(false);
-        tokio::task::spawn(async move {
-            use hyper::server::conn::http2;
-            use hyper::service::service_fn;
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .max_concurrent_streams(7)
+                .serve_connection(
+                    sock,
+                    service_fn(|_req| async move {
+                        Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                    }),
+                )
+                .await;
+        });
 
-            loop {
-                tokio::select! {
-                    res = listener.accept() => {
-                        let (stream, _) = res.unwrap();
-                        let stream = TokioIo::new(stream);
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
 
-                        let service = service_fn(|_:Request<hyper::body::Incoming>| future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new())));
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
 
-                        let mut shdn_rx = shdn_rx.clone();
-                        tokio::task::spawn(async move {
-                            let mut conn = http2::Builder::new(TokioExecutor)
-                                .serve_connection(stream, service);
+        // the server's settings are read before its response
+        let req = Request::new(Empty::new());
+        client.send_request(req).await.expect("send_request");
 
-                            tokio::select! {
-                                res = &mut conn => {
-                                    res.unwrap();
-                                }
-                                _ = shdn_rx.changed() => {
-                                    Pin::new(&mut conn).graceful_shutdown();
-                                    conn.await.unwrap();
-                                }
-                            }
-                        });
-                    }
-                    _ = shdn_rx.changed() => {
-                        break;
-                    }
-                }
-            }
+        let settings = client.peer_settings();
+        assert_eq!(settings.max_concurrent_streams(), Some(7));
+        assert_eq!(client.max_concurrent_streams(), Some(7));
+    }
+
+    #[tokio::test]
+    async fn http2_open_streams() {
+        use futures_util::stream::StreamExt;
+        use hyper::service::service_fn;
+
+        let (listener, addr) = setup_tk_test_server().await;
+        let (received_tx, mut received_rx) = mpsc::unbounded::<oneshot::Sender<()>>();
+
+        // Spawn an HTTP2 server that responds once told to.
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .max_concurrent_streams(5)
+                .serve_connection(
+                    sock,
+                    service_fn(move |_req| {
+                        let (respond_tx, respond_rx) = oneshot::channel();
+                        received_tx.unbounded_send(respond_tx).unwrap();
+                        async move {
+                            respond_rx.await.unwrap();
+                            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("done"))))
+                        }
+                    }),
+                )
+                .await;
         });
 
         let io = tcp_connect(&addr).await.expect("tcp connect");
         let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
-            .handshake(io)
+            .handshake::<_, Empty<Bytes>>(io)
             .await
             .expect("http handshake");
 
-        tokio::task::spawn(async move {
-            conn.await.expect("client conn");
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
         });
 
-        // Sanity check that client is ready
-        future::poll_fn(|ctx| client.poll_ready(ctx))
-            .await
-            .expect("client poll ready sanity");
+        assert_eq!(client.open_streams(), 0);
+        let res1 = tokio::spawn(client.send_request(Request::new(Empty::new())));
+        let res2 = tokio::spawn(client.send_request(Request::new(Empty::new())));
+        let respond1 = received_rx.next().await.unwrap();
+        let respond2 = received_rx.next().await.unwrap();
+        assert_eq!(client.open_streams(), 2);
+
+        respond1.send(()).unwrap();
+        let res1 = res1.await.unwrap().expect("res1");
+        // the server's settings are read before its responses
+        assert_eq!(client.max_concurrent_streams(), Some(5));
+        let body = concat(res1.into_body()).await.unwrap();
+        assert_eq!(body, "done");
+        assert_eq!(client.open_streams(), 1);
+
+        respond2.send(()).unwrap();
+        let res2 = res2.await.unwrap().expect("res2");
+        concat(res2.into_body()).await.unwrap();
+        assert_eq!(client.open_streams(), 0);
+    }
 
-        let req = Request::builder()
-            .uri(format!("http://{}/", addr))
-            .body(Empty::<Bytes>::new())
-            .expect("request builder");
+    #[tokio::test]
+    async fn http2_ready_reserves_streams() {
+        use futures_util::stream::StreamExt;
+        use hyper::service::service_fn;
 
-        client.send_request(req).await.expect("req1 send");
+        let (listener, addr) = setup_tk_test_server().await;
+        let (received_tx, mut received_rx) = mpsc::unbounded::<oneshot::Sender<()>>();
 
-        // Sanity check that client is STILL ready
-        future::poll_fn(|ctx| client.poll_ready(ctx))
+        // Spawn an HTTP2 server allowing one stream, that responds once told to.
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .max_concurrent_streams(1)
+                .serve_connection(
+                    sock,
+                    service_fn(move |_req| {
+                        let (respond_tx, respond_rx) = oneshot::channel();
+                        received_tx.unbounded_send(respond_tx).unwrap();
+                        async move {
+                            respond_rx.await.unwrap();
+                            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("done"))))
+                        }
+                    }),
+                )
+                .await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
             .await
-            .expect("client poll ready after");
+            .expect("http handshake");
 
-        // Trigger the server shutdown...
-        let _ = shdn_tx.send(true);
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
 
-        // Allow time for graceful shutdown roundtrips...
-        TokioTimer.sleep(Duration::from_millis(100)).await;
+        // a first request, so the server's settings are read
+        let res = tokio::spawn(client.send_request(Request::new(Empty::new())));
+        received_rx.next().await.unwrap().send(()).unwrap();
+        concat(res.await.unwrap().expect("res").into_body())
+            .await
+            .unwrap();
+        assert_eq!(client.max_concurrent_streams(), Some(1));
 
-        // After graceful shutdown roundtrips, the client should be closed...
-        future::poll_fn(|ctx| client.poll_ready(ctx))
+        // the only stream is reserved by `client`
+        let mut other = client.clone();
+        client.ready().await.expect("client ready");
+        assert!(other.ready().now_or_never().is_none());
+
+        // and then open
+        let res = tokio::spawn(client.send_request(Request::new(Empty::new())));
+        let respond = received_rx.next().await.unwrap();
+        assert_eq!(client.open_streams(), 1);
+        assert!(other.ready().now_or_never().is_none());
+
+        respond.send(()).unwrap();
+        concat(res.await.unwrap().expect("res").into_body())
             .await
-            .expect_err("client should be closed");
+            .unwrap();
+        other.ready().await.expect("other ready");
+        assert_eq!(client.open_streams(), 0);
+
+        // a reservation is released when its `SendRequest` is dropped
+        assert!(client.ready().now_or_never().is_none());
+        drop(other);
+        client.ready().await.expect("client ready");
     }
 
     #[tokio::test]
-    async fn http2_keep_alive_detects_unresponsive_server() {
+    async fn http2_priority_header() {
+        use hyper::ext::Priority;
+        use hyper::service::service_fn;
+
         let (listener, addr) = setup_tk_test_server().await;
 
-        // spawn a server that reads but doesn't write
+        // Spawn an HTTP2 server that echoes the priority header.
         tokio::spawn(async move {
-            let mut sock = listener.accept().await.unwrap().0;
-            let mut buf = [0u8; 1024];
-            loop {
-                let n = sock.read(&mut buf).await.expect("server read");
-                if n == 0 {
-                    // server closed, lets go!
-                    break;
-                }
-            }
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .serve_connection(
+                    sock,
+                    service_fn(|req| async move {
+                        let mut res = Response::new(Empty::<Bytes>::new());
+                        if let Some(priority) = req.headers().get("priority") {
+                            res.headers_mut().insert("priority", priority.clone());
+                        }
+                        Ok::<_, hyper::Error>(res)
+                    }),
+                )
+                .await;
         });
 
         let io = tcp_connect(&addr).await.expect("tcp connect");
-        let (_client, conn) = conn::http2::Builder::new(TokioExecutor)
-            .timer(TokioTimer)
-            .keep_alive_interval(Duration::from_secs(1))
-            .keep_alive_timeout(Duration::from_secs(1))
-            // enable while idle since we aren't sending requests
-            .keep_alive_while_idle(true)
-            .handshake::<_, hyper::body::Incoming>(io)
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
             .await
             .expect("http handshake");
 
-        conn.await.expect_err("conn should time out");
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let mut req = Request::new(Empty::new());
+        req.extensions_mut().insert(Priority::new(1, true));
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.headers()["priority"], "u=1, i");
+
+        // a priority header that is already set is kept
+        let mut req = Request::builder()
+            .header("priority", "u=5")
+            .body(Empty::new())
+            .unwrap();
+        req.extensions_mut().insert(Priority::new(1, true));
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.headers()["priority"], "u=5");
     }
 
     #[tokio::test]
-    async fn http2_keep_alive_not_while_idle() {
-        // This tests that not setting `http2_keep_alive_while_idle(true)`
-        // will use the default behavior which will NOT detect the server
-        // is unresponsive while no streams are active.
+    async fn http2_graceful_shutdown_drains_streams() {
+        use hyper::service::service_fn;
+        use std::sync::{Arc, Mutex};
 
         let (listener, addr) = setup_tk_test_server().await;
+        let (received_tx, received_rx) = oneshot::channel::<()>();
+        let (respond_tx, respond_rx) = oneshot::channel::<()>();
+        let (server_tx, server_rx) = oneshot::channel();
 
-        // spawn a server that reads but doesn't write
+        // Spawn an HTTP2 server that responds once told to.
+        let pending = Arc::new(Mutex::new(Some((received_tx, respond_rx))));
         tokio::spawn(async move {
-            let sock = listener.accept().await.unwrap().0;
-            drain_til_eof(sock).await.expect("server read");
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let res = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .serve_connection(
+                    sock,
+                    service_fn(move |_req| {
+                        let pending = pending.lock().unwrap().take();
+                        async move {
+                            let (received_tx, respond_rx) = pending.expect("one request");
+                            received_tx.send(()).unwrap();
+                            respond_rx.await.unwrap();
+                            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("done"))))
+                        }
+                    }),
+                )
+                .await;
+            server_tx.send(res).unwrap();
         });
 
         let io = tcp_connect(&addr).await.expect("tcp connect");
         let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
-            .timer(TokioTimer)
-            .keep_alive_interval(Duration::from_secs(1))
-            .keep_alive_timeout(Duration::from_secs(1))
-            .handshake::<_, hyper::body::Incoming>(io)
+            .handshake::<_, Empty<Bytes>>(io)
             .await
             .expect("http handshake");
 
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let conn = tokio::spawn(async move {
+            let mut conn = conn;
+            let shutdown = future::select(&mut conn, shutdown_rx).await;
+            assert!(matches!(shutdown, future::Either::Right(_)));
+            Pin::new(&mut conn).graceful_shutdown();
+            conn.await
+        });
+
+        let res = tokio::spawn(client.send_request(Request::new(Empty::new())));
+        received_rx.await.unwrap();
+
+        shutdown_tx.send(()).unwrap();
+        client.closed().await;
+        let err = client
+            .send_request(Request::new(Empty::new()))
+            .await
+            .expect_err("no requests after shutdown");
+        assert!(err.is_canceled(), "{:?}", err);
+        assert!(!conn.is_finished(), "stream still in flight");
+
+        respond_tx.send(()).unwrap();
+        let res = res.await.unwrap().expect("in flight request");
+        let body = concat(res.into_body()).await.unwrap();
+        assert_eq!(body, "done");
+
+        conn.await.unwrap().expect("graceful shutdown");
+        server_rx.await.unwrap().expect("server conn");
+    }
+
+    #[tokio::test]
+    async fn http2_go_away_in_errors() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // Spawn a raw HTTP2 server that sends a GOAWAY with debug data,
+        // once the request arrives.
         tokio::spawn(async move {
-            conn.await.expect("client conn shouldn't error");
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut preface = [0; 24];
+            sock.read_exact(&mut preface).await.unwrap();
+            // an empty SETTINGS frame
+            sock.write_all(&[0, 0, 0, 0x4, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            loop {
+                let mut head = [0; 9];
+                sock.read_exact(&mut head).await.unwrap();
+                let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+                sock.read_exact(&mut vec![0; len]).await.unwrap();
+                // HEADERS
+                if head[3] == 0x1 {
+                    break;
+                }
+            }
+            // ENHANCE_YOUR_CALM, with no streams processed
+            let debug_data = b"too_many_pings";
+            let mut frame = ((8 + debug_data.len()) as u32).to_be_bytes()[1..].to_vec();
+            frame.extend_from_slice(&[0x7, 0, 0, 0, 0, 0]);
+            frame.extend_from_slice(&0_u32.to_be_bytes());
+            frame.extend_from_slice(&0xb_u32.to_be_bytes());
+            frame.extend_from_slice(debug_data);
+            sock.write_all(&frame).await.unwrap();
+            let _ = sock.read_to_end(&mut Vec::new()).await;
         });
 
-        // sleep longer than keepalive would trigger
-        TokioTimer.sleep(Duration::from_secs(4)).await;
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+        let conn = tokio::spawn(conn);
 
-        future::poll_fn(|ctx| client.poll_ready(ctx))
+        let err = client
+            .send_request(Request::new(Empty::new()))
             .await
-            .expect("client should be open");
+            .expect_err("request refused");
+        let go_away = err.h2_go_away().expect("request error has GOAWAY");
+        assert_eq!(go_away.error_code(), 0xb);
+
+        let err = conn.await.unwrap().expect_err("conn error");
+        let go_away = err.h2_go_away().expect("conn error has GOAWAY");
+        assert_eq!(go_away.error_code(), 0xb);
     }
 
     #[tokio::test]
-    async fn http2_keep_alive_closes_open_streams() {
+    async fn http2_informational_responses_skipped() {
+        use hyper::ext::EarlyHints;
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        fn headers(flags: u8, block: &[u8]) -> Vec<u8> {
+            let mut frame = (block.len() as u32).to_be_bytes()[1..].to_vec();
+            frame.extend_from_slice(&[0x1, flags, 0, 0, 0, 1]);
+            frame.extend_from_slice(block);
+            frame
+        }
+
         let (listener, addr) = setup_tk_test_server().await;
 
-        // spawn a server that reads but doesn't write
+        // Spawn a raw HTTP2 server that sends two `103 Early Hints`, before
+        // responding to the request.
         tokio::spawn(async move {
-            let sock = listener.accept().await.unwrap().0;
-            drain_til_eof(sock).await.expect("server read");
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut preface = [0; 24];
+            sock.read_exact(&mut preface).await.unwrap();
+            // an empty SETTINGS frame
+            sock.write_all(&[0, 0, 0, 0x4, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            loop {
+                let mut head = [0; 9];
+                sock.read_exact(&mut head).await.unwrap();
+                let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+                sock.read_exact(&mut vec![0; len]).await.unwrap();
+                // HEADERS
+                if head[3] == 0x1 {
+                    break;
+                }
+            }
+            // `:status: 103` and a `link`, added to the dynamic table
+            let mut block = b"\x48\x03103\x6d\x0b</a.css>; a".to_vec();
+            // END_HEADERS
+            let mut frames = headers(0x4, &block);
+            // the same again, from the dynamic table
+            block = vec![0xbf, 0xbe];
+            frames.extend(headers(0x4, &block));
+            // END_STREAM and END_HEADERS, and `:status: 200`
+            frames.extend(headers(0x5, &[0x88]));
+            sock.write_all(&frames).await.unwrap();
+            let _ = sock.read_to_end(&mut Vec::new()).await;
         });
 
         let io = tcp_connect(&addr).await.expect("tcp connect");
         let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
-            .timer(TokioTimer)
-            .keep_alive_interval(Duration::from_secs(1))
-            .keep_alive_timeout(Duration::from_secs(1))
-            .handshake(io)
+            .handshake::<_, Empty<Bytes>>(io)
             .await
             .expect("http handshake");
+        tokio::spawn(conn);
 
-        tokio::spawn(async move {
-            let err = conn.await.expect_err("client conn should timeout");
-            assert!(err.is_timeout());
+        let (seen_tx, mut seen_rx) = mpsc::unbounded();
+        let mut req = Request::new(Empty::new());
+        hyper::ext::on_informational(&mut req, move |res| {
+            let link = res.headers()["link"].clone();
+            seen_tx.unbounded_send((res.status(), link)).unwrap();
         });
-
-        let req = http::Request::new(Empty::<Bytes>::new());
-        let err = client
-            .send_request(req)
-            .await
-            .expect_err("request should timeout");
-        assert!(err.is_timeout());
-
-        let err = future::poll_fn(|ctx| client.poll_ready(ctx))
-            .await
-            .expect_err("client should be closed");
-        assert!(
-            err.is_closed(),
-            "poll_ready error should be closed: {:?}",
-            err
-        );
+        // `h2` skips the interim responses, so only the final one is seen.
+        let res = client.send_request(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        seen_rx.try_recv().expect_err("informational not forwarded");
+        assert!(res.extensions().get::<EarlyHints>().is_none());
     }
 
     #[tokio::test]
@@ -2234,6 +4319,256 @@ mod conn {
         upgraded.shutdown().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn http1_connect_tunnel() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // Spawn a raw proxy that accepts the tunnel, and then answers pings.
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                sock.read_exact(&mut byte).await.unwrap();
+                head.push(byte[0]);
+            }
+            let head = s(&head).to_ascii_lowercase();
+            assert!(head.starts_with("connect example.com:443 http/1.1\r\n"));
+            assert!(head.contains("host: example.com:443\r\n"));
+            sock.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+
+            let mut ping = [0; 4];
+            sock.read_exact(&mut ping).await.unwrap();
+            assert_eq!(&ping, b"ping");
+            sock.write_all(b"pong").await.unwrap();
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http1::handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn");
+        });
+
+        let upgraded = client
+            .connect("example.com:443".parse().unwrap())
+            .await
+            .expect("tunnel");
+        let mut upgraded = TokioIo::new(upgraded);
+        upgraded.write_all(b"ping").await.unwrap();
+        let mut pong = [0; 4];
+        upgraded.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"pong");
+    }
+
+    #[tokio::test]
+    async fn http1_connect_refused() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // Spawn a raw proxy that wants credentials.
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = sock.read(&mut buf).await.unwrap();
+            sock.write_all(
+                b"HTTP/1.1 407 Proxy Authentication Required\r\ncontent-length: 0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            let _ = sock.read_to_end(&mut Vec::new()).await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http1::handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+
+        let err = client
+            .connect("example.com:443".parse().unwrap())
+            .await
+            .expect_err("tunnel refused");
+        assert!(err.error().is_none());
+        let res = err.into_response().expect("refused response");
+        assert_eq!(res.status(), StatusCode::PROXY_AUTHENTICATION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn h2_connect_tunnel() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // Spawn an HTTP2 server that accepts the tunnel, and then answers
+        // pings.
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
+
+            let (req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+            assert_eq!(req.method(), Method::CONNECT);
+            assert_eq!(req.uri().authority().unwrap(), "example.com:443");
+
+            let mut body = req.into_body();
+            let mut send_stream = respond.send_response(Response::new(()), false).unwrap();
+            let bytes = body.data().await.unwrap().unwrap();
+            assert_eq!(&bytes[..], b"ping");
+            let _ = body.flow_control().release_capacity(bytes.len());
+            send_stream.send_data("pong".into(), true).unwrap();
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let upgraded = client
+            .connect("example.com:443".parse().unwrap())
+            .await
+            .expect("tunnel");
+        let mut upgraded = TokioIo::new(upgraded);
+        upgraded.write_all(b"ping").await.unwrap();
+        let mut vec = vec![];
+        upgraded.read_to_end(&mut vec).await.unwrap();
+        assert_eq!(s(&vec), "pong");
+    }
+
+    #[tokio::test]
+    async fn h2_extended_connect() {
+        use hyper::ext::Protocol;
+        use hyper::service::service_fn;
+
+        let (listener, addr) = setup_tk_test_server().await;
+
+        // Spawn an HTTP2 server that accepts websocket streams and echoes them.
+        tokio::spawn(async move {
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor)
+                .enable_connect_protocol()
+                .serve_connection(
+                    sock,
+                    service_fn(|req| async move {
+                        assert_eq!(req.method(), Method::CONNECT);
+                        assert_eq!(
+                            req.extensions().get::<Protocol>(),
+                            Some(&Protocol::from_static("websocket"))
+                        );
+                        tokio::spawn(async move {
+                            let upgraded = hyper::upgrade::on(req).await.expect("upgrade");
+                            let (mut rd, mut wr) = tokio::io::split(TokioIo::new(upgraded));
+                            tokio::io::copy(&mut rd, &mut wr).await.expect("echo");
+                            wr.shutdown().await.expect("shutdown");
+                        });
+                        Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                    }),
+                )
+                .await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let mut req = Request::connect("http://localhost/chat")
+            .body(Empty::new())
+            .unwrap();
+        req.extensions_mut()
+            .insert(Protocol::from_static("websocket"));
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let mut upgraded = TokioIo::new(hyper::upgrade::on(res).await.unwrap());
+        upgraded.write_all(b"hello").await.unwrap();
+        upgraded.shutdown().await.unwrap();
+
+        let mut vec = vec![];
+        upgraded.read_to_end(&mut vec).await.unwrap();
+        assert_eq!(s(&vec), "hello");
+    }
+
+    #[tokio::test]
+    async fn h2_stream_window_override() {
+        use hyper::ext::StreamWindow;
+
+        let (listener, addr) = setup_tk_test_server().await;
+        let (read_tx, read_rx) = oneshot::channel();
+        let (capacity_tx, capacity_rx) = oneshot::channel();
+
+        // Spawn an HTTP2 server that fills the stream's window, and reports
+        // how much of it is given back once the client has read it all.
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = h2::server::handshake(sock).await.unwrap();
+
+            let (_req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+
+            let mut send_stream = respond.send_response(Response::new(()), false).unwrap();
+            send_stream.reserve_capacity(100);
+            let capacity = poll_fn(|cx| send_stream.poll_capacity(cx))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(capacity, 100);
+            send_stream.send_data(vec![0; 100].into(), false).unwrap();
+
+            read_rx.await.unwrap();
+            send_stream.reserve_capacity(100);
+            let capacity = poll_fn(|cx| send_stream.poll_capacity(cx))
+                .await
+                .unwrap()
+                .unwrap();
+            capacity_tx.send(capacity).unwrap();
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (mut client, conn) = conn::http2::Builder::new(TokioExecutor)
+            .initial_stream_window_size(100)
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let mut req = Request::new(Empty::new());
+        req.extensions_mut().insert(StreamWindow::new(60));
+        let mut res = client.send_request(req).await.expect("send_request");
+
+        let mut read = 0;
+        while read < 100 {
+            let frame = res.body_mut().frame().await.unwrap().unwrap();
+            read += frame.into_data().unwrap().len();
+        }
+        read_tx.send(()).unwrap();
+
+        assert_eq!(capacity_rx.await.unwrap(), 60);
+    }
+
     #[tokio::test]
     async fn h2_connect_rejected() {
         let (listener, addr) = setup_tk_test_server().await;
@@ -2369,6 +4704,160 @@ mod conn {
     }
 }
 
+#[cfg(feature = "high-level-client")]
+mod pool {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures_util::future;
+    use http_body_util::{BodyExt, Empty, Full};
+    use hyper::client::pool::Builder;
+    use hyper::client::Client;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper::{Request, Response, Uri};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::{support, tcp_connect};
+
+    use support::{TokioExecutor, TokioIo};
+
+    #[tokio::test]
+    async fn pool_reuses_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let service = service_fn(|req: Request<hyper::body::Incoming>| {
+                        assert_eq!(req.uri(), "/path?query");
+                        let host = req.headers()["host"].to_str().unwrap().to_owned();
+                        future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(host))))
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        let pool = Builder::new(TokioExecutor)
+            .build(move |_: &Uri| async move { tcp_connect(&addr).await });
+
+        let uri: Uri = format!("http://{}/path?query", addr).parse().unwrap();
+        for _ in 0..3 {
+            let req = Request::get(uri.clone())
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = pool.send_request(req).await.expect("send_request");
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, addr.to_string());
+
+            // Let the connection go back to the pool.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_reconnects_after_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        // Serve a single request on each connection, then close it.
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let service = service_fn(|_| {
+                        future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                    });
+                    let _ = http1::Builder::new()
+                        .keep_alive(false)
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        let pool = Builder::new(TokioExecutor)
+            .build(move |_: &Uri| async move { tcp_connect(&addr).await });
+
+        let uri: Uri = format!("http://{}/", addr).parse().unwrap();
+        for _ in 0..2 {
+            let req = Request::get(uri.clone())
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            let res = pool.send_request(req).await.expect("send_request");
+            res.into_body().collect().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn high_level_client_get() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|req: Request<hyper::body::Incoming>| {
+                assert_eq!(req.method(), "GET");
+                future::ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(
+                    req.uri().to_string(),
+                ))))
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+
+        let client = Client::<_, Empty<Bytes>>::new(
+            move |_: &Uri| async move { tcp_connect(&addr).await },
+            TokioExecutor,
+        );
+        for path in &["/a", "/b"] {
+            let uri = format!("http://{}{}", addr, path).parse().unwrap();
+            let res = client.get(uri).await.expect("get");
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, path.as_bytes());
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn pool_errors() {
+        let pool = Builder::new(TokioExecutor).build(|_: &Uri| async {
+            Err::<TokioIo<TcpStream>, _>(io::Error::new(io::ErrorKind::Other, "refused"))
+        });
+
+        let req = Request::get("/relative")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let err = pool.send_request(req).await.unwrap_err();
+        assert!(err.is_destination());
+
+        let req = Request::get("http://example.com/")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let err = pool.send_request(req).await.unwrap_err();
+        assert!(err.is_connect());
+        assert!(err.error().is_none());
+    }
+}
+
 trait FutureHyperExt: TryFuture {
     fn expect(self, msg: &'static str) -> Pin<Box<dyn Future<Output = Self::Ok>>>;
 }
@@ -0,0 +1,616 @@
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+
+use std::convert::Infallible;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_channel::oneshot;
+use http::HeaderMap;
+use http_body_util::{BodyExt, Full, StreamBody};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use hyper::body::{Frame, Incoming as IncomingBody};
+use hyper::ext::{H3Datagrams, Protocol, WebTransportSession};
+use hyper::rt::quic;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, Version};
+
+mod support;
+use support::{TokioExecutor, TokioIo};
+
+#[tokio::test]
+async fn get() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|req: Request<IncomingBody>| async move {
+            assert_eq!(req.method(), Method::GET);
+            assert_eq!(req.version(), Version::HTTP_3);
+            assert_eq!(req.uri(), "https://example.com/hello?x=1");
+            assert_eq!(req.headers()["accept"], "text/plain");
+            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from_static(b"hello"))))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::handshake(TokioExecutor, client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let req = Request::get("https://example.com/hello?x=1")
+        .header("accept", "text/plain")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    let res = sender.send_request(req).await.expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.version(), Version::HTTP_3);
+    assert_eq!(res.headers()["content-length"], "5");
+    assert!(res.headers().contains_key("date"));
+
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "hello");
+}
+
+#[tokio::test]
+async fn post_echo_with_trailers() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|req: Request<IncomingBody>| async move {
+            assert_eq!(req.method(), Method::POST);
+            let collected = req.into_body().collect().await?;
+            let trailers = collected.trailers().cloned().expect("request trailers");
+            assert_eq!(trailers["x-checksum"], "abc");
+            Ok::<_, hyper::Error>(Response::new(body_with_trailers(
+                collected.to_bytes(),
+                trailers,
+            )))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::handshake(TokioExecutor, client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", "abc".parse().unwrap());
+    let req = Request::post("https://example.com/echo")
+        .body(body_with_trailers(
+            Bytes::from_static(b"ping pong"),
+            trailers,
+        ))
+        .unwrap();
+    let res = sender.send_request(req).await.expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let collected = res.into_body().collect().await.unwrap();
+    assert_eq!(collected.trailers().unwrap()["x-checksum"], "abc");
+    assert_eq!(collected.to_bytes(), "ping pong");
+}
+
+#[tokio::test]
+async fn graceful_shutdown_finishes_requests() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+    let close_code = server_conn.close_code.clone();
+
+    let (started_tx, started_rx) = oneshot::channel();
+    let (release_tx, release_rx) = oneshot::channel::<()>();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let state = Arc::new(Mutex::new((Some(started_tx), Some(release_rx))));
+    let server = tokio::spawn(async move {
+        let service = service_fn(move |_req: Request<IncomingBody>| {
+            let (started, release) = {
+                let mut state = state.lock().unwrap();
+                (state.0.take().unwrap(), state.1.take().unwrap())
+            };
+            async move {
+                started.send(()).unwrap();
+                release.await.unwrap();
+                Ok::<_, Infallible>(Response::new(Full::new(Bytes::from_static(b"bye"))))
+            }
+        });
+        let conn = hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .serve_connection(server_conn, service);
+        tokio::pin!(conn);
+        tokio::select! {
+            res = conn.as_mut() => res,
+            _ = shutdown_rx => {
+                conn.as_mut().graceful_shutdown();
+                conn.await
+            }
+        }
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::handshake(TokioExecutor, client_conn)
+        .await
+        .expect("handshake");
+    let client = tokio::spawn(conn);
+
+    let req = Request::get("https://example.com/")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    let res = tokio::spawn(sender.send_request(req));
+    started_rx.await.unwrap();
+
+    shutdown_tx.send(()).unwrap();
+    release_tx.send(()).unwrap();
+    let res = res.await.unwrap().expect("response");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "bye");
+
+    server.await.unwrap().expect("server error");
+    client.await.unwrap().expect("client connection error");
+    assert_eq!(*close_code.lock().unwrap(), Some(0x100), "H3_NO_ERROR");
+
+    assert!(sender.is_closed());
+    let req = Request::get("https://example.com/")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    sender
+        .send_request(req)
+        .await
+        .expect_err("request after shutdown");
+}
+
+#[tokio::test]
+async fn connect_tunnel() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|req: Request<IncomingBody>| async move {
+            assert_eq!(req.method(), Method::CONNECT);
+            assert_eq!(req.uri(), "example.com:443");
+            assert!(req.extensions().get::<H3Datagrams>().is_none());
+            let on_upgrade = hyper::upgrade::on(req);
+            tokio::spawn(async move {
+                let mut upgraded = TokioIo::new(on_upgrade.await.expect("on_upgrade"));
+                upgraded.write_all(b"Bread?").await.unwrap();
+
+                let mut vec = vec![];
+                upgraded.read_to_end(&mut vec).await.unwrap();
+                assert_eq!(vec, b"Baguette!");
+
+                upgraded.shutdown().await.unwrap();
+            });
+            Ok::<_, Infallible>(Response::new(Full::<Bytes>::default()))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::handshake(TokioExecutor, client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let req = Request::connect("example.com:443")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    let res = sender.send_request(req).await.expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let mut upgraded = TokioIo::new(hyper::upgrade::on(res).await.expect("on_upgrade"));
+    let mut buf = [0; 6];
+    upgraded.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"Bread?");
+
+    upgraded.write_all(b"Baguette!").await.unwrap();
+    upgraded.shutdown().await.unwrap();
+    assert_eq!(upgraded.read(&mut buf).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn connect_datagrams() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|mut req: Request<IncomingBody>| async move {
+            assert_eq!(req.method(), Method::CONNECT);
+            let mut datagrams = req
+                .extensions_mut()
+                .remove::<H3Datagrams>()
+                .expect("datagrams");
+            assert_eq!(datagrams.stream_id(), 0);
+            tokio::spawn(async move {
+                while let Some(datagram) = datagrams.recv().await {
+                    datagrams.send(datagram).expect("send datagram");
+                }
+            });
+            Ok::<_, Infallible>(Response::new(Full::<Bytes>::default()))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .enable_datagrams()
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::Builder::new(TokioExecutor)
+        .enable_datagrams()
+        .handshake(client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let req = Request::connect("example.com:443")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    let mut res = sender.send_request(req).await.expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut datagrams = res
+        .extensions_mut()
+        .remove::<H3Datagrams>()
+        .expect("datagrams");
+    assert_eq!(datagrams.stream_id(), 0);
+
+    datagrams.send(Bytes::from_static(b"ping")).unwrap();
+    assert_eq!(datagrams.recv().await.expect("echo"), "ping");
+    datagrams.send(Bytes::from_static(b"pong")).unwrap();
+    assert_eq!(datagrams.recv().await.expect("echo"), "pong");
+}
+
+#[tokio::test]
+async fn webtransport_session() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|mut req: Request<IncomingBody>| async move {
+            assert_eq!(req.method(), Method::CONNECT);
+            assert_eq!(
+                req.extensions().get::<Protocol>(),
+                Some(&Protocol::from_static("webtransport"))
+            );
+            let mut session = req
+                .extensions_mut()
+                .remove::<WebTransportSession>()
+                .expect("session");
+            tokio::spawn(async move {
+                // echo the first stream the client opens
+                let mut stream = TokioIo::new(session.accept_bi().await.expect("accept_bi"));
+                let mut vec = vec![];
+                stream.read_to_end(&mut vec).await.unwrap();
+                stream.write_all(&vec).await.unwrap();
+                stream.shutdown().await.unwrap();
+
+                let mut stream = TokioIo::new(session.open_bi().await.expect("open_bi"));
+                stream.write_all(b"from server").await.unwrap();
+                stream.shutdown().await.unwrap();
+            });
+            Ok::<_, Infallible>(Response::new(Full::<Bytes>::default()))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .enable_webtransport()
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::Builder::new(TokioExecutor)
+        .enable_webtransport()
+        .handshake(client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let mut req = Request::connect("https://example.com/session")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    req.extensions_mut()
+        .insert(Protocol::from_static("webtransport"));
+    let mut res = sender.send_request(req).await.expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut session = res
+        .extensions_mut()
+        .remove::<WebTransportSession>()
+        .expect("session");
+    assert_eq!(session.id(), 0);
+
+    let mut stream = TokioIo::new(session.open_bi().await.expect("open_bi"));
+    stream.write_all(b"ping").await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut vec = vec![];
+    stream.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(vec, b"ping");
+
+    let mut stream = TokioIo::new(session.accept_bi().await.expect("accept_bi"));
+    let mut vec = vec![];
+    stream.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(vec, b"from server");
+}
+
+#[tokio::test]
+async fn webtransport_not_enabled_by_server() {
+    let _ = pretty_env_logger::try_init();
+    let (client_conn, server_conn) = MockConnection::pair();
+
+    tokio::spawn(async move {
+        let service = service_fn(|_req: Request<IncomingBody>| async move {
+            Ok::<_, Infallible>(Response::new(Full::<Bytes>::default()))
+        });
+        hyper::server::conn::http3::Builder::new(TokioExecutor)
+            .serve_connection(server_conn, service)
+            .await
+            .expect("server error");
+    });
+
+    let (mut sender, conn) = hyper::client::conn::http3::Builder::new(TokioExecutor)
+        .enable_webtransport()
+        .handshake(client_conn)
+        .await
+        .expect("handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client connection error");
+    });
+
+    let mut req = Request::connect("https://example.com/session")
+        .body(Full::<Bytes>::default())
+        .unwrap();
+    req.extensions_mut()
+        .insert(Protocol::from_static("webtransport"));
+    let err = sender
+        .send_request(req)
+        .await
+        .expect_err("unsupported protocol");
+    assert!(err.is_user(), "{:?}", err);
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+fn body_with_trailers(
+    data: Bytes,
+    trailers: HeaderMap,
+) -> StreamBody<futures_util::stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, BoxError>>>> {
+    let frames = vec![Ok(Frame::data(data)), Ok(Frame::trailers(trailers))];
+    StreamBody::new(futures_util::stream::iter(frames))
+}
+
+// An in-memory QUIC connection, where each stream is a channel of chunks.
+
+type Chunk = Result<Bytes, u64>;
+
+struct MockConnection {
+    next_bidi: u64,
+    next_uni: u64,
+    open_bidi: Option<mpsc::UnboundedSender<MockStream>>,
+    open_uni: Option<mpsc::UnboundedSender<MockStream>>,
+    accept_bidi: mpsc::UnboundedReceiver<MockStream>,
+    accept_uni: mpsc::UnboundedReceiver<MockStream>,
+    send_datagram: Option<mpsc::UnboundedSender<Bytes>>,
+    recv_datagram: mpsc::UnboundedReceiver<Bytes>,
+    // the error code the connection was closed with, by either peer
+    close_code: Arc<Mutex<Option<u64>>>,
+}
+
+struct MockStream {
+    id: u64,
+    tx: Option<mpsc::UnboundedSender<Chunk>>,
+    rx: Option<mpsc::UnboundedReceiver<Chunk>>,
+}
+
+impl MockConnection {
+    fn pair() -> (MockConnection, MockConnection) {
+        let (client_bidi_tx, server_bidi_rx) = mpsc::unbounded_channel();
+        let (client_uni_tx, server_uni_rx) = mpsc::unbounded_channel();
+        let (server_bidi_tx, client_bidi_rx) = mpsc::unbounded_channel();
+        let (server_uni_tx, client_uni_rx) = mpsc::unbounded_channel();
+        let (client_datagram_tx, server_datagram_rx) = mpsc::unbounded_channel();
+        let (server_datagram_tx, client_datagram_rx) = mpsc::unbounded_channel();
+        let close_code = Arc::new(Mutex::new(None));
+        let client = MockConnection {
+            next_bidi: 0,
+            next_uni: 2,
+            open_bidi: Some(client_bidi_tx),
+            open_uni: Some(client_uni_tx),
+            accept_bidi: client_bidi_rx,
+            accept_uni: client_uni_rx,
+            send_datagram: Some(client_datagram_tx),
+            recv_datagram: client_datagram_rx,
+            close_code: close_code.clone(),
+        };
+        let server = MockConnection {
+            next_bidi: 1,
+            next_uni: 3,
+            open_bidi: Some(server_bidi_tx),
+            open_uni: Some(server_uni_tx),
+            accept_bidi: server_bidi_rx,
+            accept_uni: server_uni_rx,
+            send_datagram: Some(server_datagram_tx),
+            recv_datagram: server_datagram_rx,
+            close_code,
+        };
+        (client, server)
+    }
+
+    fn open(
+        opener: &Option<mpsc::UnboundedSender<MockStream>>,
+        next_id: &mut u64,
+        bidi: bool,
+    ) -> io::Result<MockStream> {
+        let opener = opener
+            .as_ref()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        let id = *next_id;
+        *next_id += 4;
+
+        let (tx, peer_rx) = mpsc::unbounded_channel();
+        let (peer_tx, rx) = if bidi {
+            let (peer_tx, rx) = mpsc::unbounded_channel();
+            (Some(peer_tx), Some(rx))
+        } else {
+            (None, None)
+        };
+        let peer = MockStream {
+            id,
+            tx: peer_tx,
+            rx: Some(peer_rx),
+        };
+        opener
+            .send(peer)
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+        Ok(MockStream {
+            id,
+            tx: Some(tx),
+            rx,
+        })
+    }
+}
+
+impl quic::Connection for MockConnection {
+    type BidiStream = MockStream;
+    type SendStream = MockStream;
+    type RecvStream = MockStream;
+
+    fn poll_accept_bidi(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<MockStream>>> {
+        self.accept_bidi.poll_recv(cx).map(Ok)
+    }
+
+    fn poll_accept_uni(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<MockStream>>> {
+        self.accept_uni.poll_recv(cx).map(Ok)
+    }
+
+    fn poll_open_bidi(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<MockStream>> {
+        let me = &mut *self;
+        Poll::Ready(MockConnection::open(&me.open_bidi, &mut me.next_bidi, true))
+    }
+
+    fn poll_open_uni(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<MockStream>> {
+        let me = &mut *self;
+        Poll::Ready(MockConnection::open(&me.open_uni, &mut me.next_uni, false))
+    }
+
+    fn close(mut self: Pin<&mut Self>, code: u64, _reason: &[u8]) {
+        self.close_code.lock().unwrap().get_or_insert(code);
+        self.open_bidi = None;
+        self.open_uni = None;
+        self.accept_bidi.close();
+        self.accept_uni.close();
+        self.send_datagram = None;
+        self.recv_datagram.close();
+    }
+
+    fn datagrams(self: Pin<&mut Self>) -> Option<Pin<&mut dyn quic::Datagrams>> {
+        Some(self)
+    }
+}
+
+impl quic::Datagrams for MockConnection {
+    fn poll_recv_datagram(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<Bytes>>> {
+        self.recv_datagram.poll_recv(cx).map(Ok)
+    }
+
+    fn send_datagram(self: Pin<&mut Self>, data: Bytes) -> io::Result<()> {
+        match self.send_datagram {
+            // A datagram the peer doesn't receive is just lost.
+            Some(ref tx) => {
+                let _ = tx.send(data);
+                Ok(())
+            }
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+impl quic::SendStream for MockStream {
+    fn poll_send(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let tx = match self.tx {
+            Some(ref tx) => tx,
+            None => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        };
+        if tx.send(Ok(Bytes::copy_from_slice(buf))).is_err() {
+            // the peer stopped sending
+            self.tx = None;
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_finish(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.tx = None;
+        Poll::Ready(Ok(()))
+    }
+
+    fn reset(mut self: Pin<&mut Self>, code: u64) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Err(code));
+        }
+    }
+}
+
+impl quic::RecvStream for MockStream {
+    fn poll_recv(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<Bytes>>> {
+        let rx = match self.rx {
+            Some(ref mut rx) => rx,
+            None => return Poll::Ready(Ok(None)),
+        };
+        Poll::Ready(match futures_util::ready!(rx.poll_recv(cx)) {
+            Some(Ok(chunk)) => Ok(Some(chunk)),
+            Some(Err(code)) => Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                format!("stream reset with code {:#x}", code),
+            )),
+            None => Ok(None),
+        })
+    }
+
+    fn stop_sending(mut self: Pin<&mut Self>, _code: u64) {
+        self.rx = None;
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
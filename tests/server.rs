@@ -7,12 +7,12 @@ use std::io::{self, Read, Write};
 use std::net::TcpListener as StdTcpListener;
 use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use futures_channel::oneshot;
@@ -21,7 +21,7 @@ use h2::client::SendRequest;
 use h2::{RecvStream, SendStream};
 use http::header::{HeaderName, HeaderValue};
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full, StreamBody};
-use hyper::rt::Timer;
+use hyper::rt::{Metrics, Timer};
 use hyper::rt::{Read as AsyncRead, Write as AsyncWrite};
 use support::{TokioExecutor, TokioIo, TokioTimer};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -29,7 +29,7 @@ use tokio::net::{TcpListener as TkTcpListener, TcpListener, TcpStream as TkTcpSt
 
 use hyper::body::{Body, Incoming as IncomingBody};
 use hyper::server::conn::{http1, http2};
-use hyper::service::{service_fn, Service};
+use hyper::service::{service_fn, service_fn_with_state, Service};
 use hyper::{Method, Request, Response, StatusCode, Uri, Version};
 use tokio::pin;
 
@@ -1031,6 +1031,531 @@ async fn expect_continue_waits_for_body_poll() {
     child.join().expect("client thread");
 }
 
+async fn manual_expect_continue(send: bool) -> String {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST /foo HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 5\r\n\
+            Connection: Close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write head");
+
+        let mut resp = String::new();
+        if send {
+            let expected = "HTTP/1.1 100 Continue\r\n\r\n";
+            let mut buf = vec![0; expected.len()];
+            tcp.read_exact(&mut buf).expect("read 100");
+            resp.push_str(std::str::from_utf8(&buf).unwrap());
+        } else {
+            // Like clients that give up waiting for the 100 Continue.
+            thread::sleep(Duration::from_millis(100));
+        }
+        tcp.write_all(b"hello").expect("write body");
+        tcp.read_to_string(&mut resp).expect("read");
+        resp
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+
+    http1::Builder::new()
+        .manual_expect_continue(true)
+        .serve_connection(
+            socket,
+            service_fn(move |mut req: Request<IncomingBody>| async move {
+                let expect = req
+                    .extensions_mut()
+                    .remove::<hyper::ext::ExpectContinue>()
+                    .expect("ExpectContinue extension");
+                if send {
+                    expect.send();
+                } else {
+                    drop(expect);
+                }
+                let body = req.into_body().collect().await?.to_bytes();
+                assert_eq!(body, "hello");
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread")
+}
+
+#[tokio::test]
+async fn manual_expect_continue_send() {
+    let resp = manual_expect_continue(true).await;
+    assert!(
+        resp.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"),
+        "{}",
+        resp
+    );
+}
+
+#[tokio::test]
+async fn manual_expect_continue_skip() {
+    let resp = manual_expect_continue(false).await;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{}", resp);
+}
+
+#[tokio::test]
+async fn informational_responses_before_final() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        resp
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+
+    http1::Builder::new()
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let sender = req
+                    .extensions()
+                    .get::<hyper::ext::InformationalSender>()
+                    .expect("InformationalSender extension")
+                    .clone();
+                sender
+                    .send(Response::builder().status(102).body(()).unwrap())
+                    .expect("send 102");
+                // Written while the service is still busy.
+                TokioTimer.sleep(Duration::from_millis(20)).await;
+                sender
+                    .send(
+                        Response::builder()
+                            .status(103)
+                            .header("link", "</style.css>; rel=preload")
+                            .body(())
+                            .unwrap(),
+                    )
+                    .expect("send 103");
+                sender
+                    .send(Response::new(()))
+                    .expect_err("200 isn't informational");
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from_static(b"done"))))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    let resp = child.join().expect("client thread");
+    assert!(
+        resp.starts_with(
+            "HTTP/1.1 102 Processing\r\n\r\n\
+             HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n\
+             HTTP/1.1 200 OK\r\n"
+        ),
+        "{}",
+        resp
+    );
+    assert!(resp.ends_with("\r\n\r\ndone"), "{}", resp);
+}
+
+async fn early_reject_with_drain(body_len: usize, max_body_drain: u64) -> String {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let head = format!(
+            "\
+            POST /upload HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: {}\r\n\
+            \r\n\
+        ",
+            body_len
+        );
+        tcp.write_all(head.as_bytes()).expect("write head");
+
+        // send the body only once the rejection has been received
+        let expected = "HTTP/1.1 403 Forbidden\r\n";
+        let mut buf = [0; 256];
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert_eq!(&buf[..expected.len()], expected.as_bytes());
+        assert!(n < buf.len());
+
+        // the connection may be closed before all of this is written
+        let _ = tcp.write_all(&vec![b'x'; body_len]);
+        let _ = tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        );
+
+        let mut resp = String::new();
+        let _ = tcp.read_to_string(&mut resp);
+        resp
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+
+    http1::Builder::new()
+        .max_body_drain(max_body_drain)
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let status = if req.method() == Method::POST {
+                    StatusCode::FORBIDDEN
+                } else {
+                    StatusCode::OK
+                };
+                Response::builder()
+                    .status(status)
+                    .body(Empty::<Bytes>::new())
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread")
+}
+
+#[tokio::test]
+async fn early_reject_drains_body_within_limit() {
+    let resp = early_reject_with_drain(100_000, 100_000).await;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", resp);
+}
+
+#[tokio::test]
+async fn early_reject_closes_when_body_over_limit() {
+    let resp = early_reject_with_drain(100_000, 1000).await;
+    assert_eq!(resp, "");
+}
+
+#[derive(Default)]
+struct Counters {
+    opened: AtomicUsize,
+    closed: AtomicUsize,
+    handshakes: AtomicUsize,
+    requests: AtomicUsize,
+    read: AtomicUsize,
+    written: AtomicUsize,
+}
+
+impl Metrics for Counters {
+    fn connection_opened(&self) {
+        self.opened.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn connection_closed(&self, _lifetime: Duration) {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn handshake_completed(&self, _duration: Duration) {
+        self.handshakes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn request_completed(&self, _duration: Duration) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn bytes_read(&self, n: usize) {
+        self.read.fetch_add(n, Ordering::SeqCst);
+    }
+
+    fn bytes_written(&self, n: usize) {
+        self.written.fetch_add(n, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn metrics_count_connection_and_requests() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let req = b"\
+        GET / HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        \r\n\
+        GET / HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Connection: close\r\n\
+        \r\n\
+    ";
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(req).expect("write");
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        resp
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+
+    let counters = Arc::new(Counters::default());
+    http1::Builder::new()
+        .metrics(counters.clone())
+        .serve_connection(
+            socket,
+            service_fn(|_| async {
+                Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    let resp = child.join().expect("client thread");
+    assert_eq!(counters.opened.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.closed.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.requests.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.read.load(Ordering::SeqCst), req.len());
+    assert_eq!(counters.written.load(Ordering::SeqCst), resp.len());
+}
+
+#[tokio::test]
+async fn connection_events_http1() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    http1::Builder::new()
+        .on_connection_event(move |event| recorded.lock().unwrap().push(format!("{:?}", event)))
+        .serve_connection(
+            socket,
+            service_fn(|_| async {
+                Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "Opened",
+            "FirstRequest",
+            "Idle",
+            "Resumed",
+            "Idle",
+            "Closed(Done)"
+        ]
+    );
+}
+
+struct FixedClock(Option<std::time::SystemTime>);
+
+impl hyper::rt::Clock for FixedClock {
+    fn now(&self) -> Option<std::time::SystemTime> {
+        self.0
+    }
+}
+
+#[tokio::test]
+async fn clock_sets_date_header() {
+    async fn serve(clock: FixedClock) -> String {
+        let (listener, addr) = setup_tcp_listener();
+
+        let client = thread::spawn(move || {
+            let mut tcp = connect(&addr);
+            tcp.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .expect("write");
+            let mut buf = String::new();
+            tcp.read_to_string(&mut buf).expect("read");
+            buf
+        });
+
+        let (socket, _) = listener.accept().await.unwrap();
+        http1::Builder::new()
+            .clock(clock)
+            .serve_connection(TokioIo::new(socket), HelloWorld)
+            .await
+            .expect("serve_connection");
+        client.join().unwrap()
+    }
+
+    let time = std::time::UNIX_EPOCH + Duration::from_secs(784_111_777);
+    let res = serve(FixedClock(Some(time))).await;
+    assert!(
+        res.contains("\r\ndate: Sun, 06 Nov 1994 08:49:37 GMT\r\n"),
+        "{}",
+        res
+    );
+
+    let res = serve(FixedClock(None)).await;
+    assert!(!res.contains("\r\ndate: "), "{}", res);
+}
+
+#[tokio::test]
+async fn clock_sets_date_header_http2() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.expect("accept");
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(784_111_777);
+        http2::Builder::new(TokioExecutor)
+            .clock(FixedClock(Some(time)))
+            .serve_connection(TokioIo::new(socket), HelloWorld)
+            .await
+            .expect("serve_connection");
+    });
+
+    let tcp = TkTcpStream::connect(addr).await.expect("connect");
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .handshake(TokioIo::new(tcp))
+        .await
+        .expect("handshake");
+    let conn = tokio::spawn(conn);
+
+    let req = Request::builder()
+        .uri(format!("http://{}/", addr))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = client.send_request(req).await.expect("send_request");
+    assert_eq!(res.headers()["date"], "Sun, 06 Nov 1994 08:49:37 GMT");
+    res.into_body().collect().await.expect("body");
+    drop(client);
+    conn.await.expect("client task").expect("client conn");
+    server.await.expect("server");
+}
+
+#[tokio::test]
+async fn connection_events_http2() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let server_events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = server_events.clone();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.expect("accept");
+        http2::Builder::new(TokioExecutor)
+            .on_connection_event(move |event| recorded.lock().unwrap().push(format!("{:?}", event)))
+            .serve_connection(
+                TokioIo::new(socket),
+                service_fn(|_| async {
+                    Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+                }),
+            )
+            .await
+            .expect("serve_connection");
+    });
+
+    let client_events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = client_events.clone();
+    let tcp = TkTcpStream::connect(addr).await.expect("connect");
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .on_connection_event(move |event| recorded.lock().unwrap().push(format!("{:?}", event)))
+        .handshake(TokioIo::new(tcp))
+        .await
+        .expect("handshake");
+    let conn = tokio::spawn(conn);
+
+    let req = Request::builder()
+        .uri(format!("http://{}/", addr))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = client.send_request(req).await.expect("send_request");
+    let body = res.into_body().collect().await.expect("body").to_bytes();
+    assert_eq!(body, "hello");
+    drop(client);
+    conn.await.expect("client task").expect("client conn");
+    server.await.expect("server");
+
+    for events in &[&client_events, &server_events] {
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["Opened", "FirstRequest", "Idle", "Closed(Done)"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn metrics_http2_client_and_server() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let server_counters = Arc::new(Counters::default());
+    let counters = server_counters.clone();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.expect("accept");
+        http2::Builder::new(TokioExecutor)
+            .metrics(counters)
+            .serve_connection(
+                TokioIo::new(socket),
+                service_fn(|_| async {
+                    Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("hello")))
+                }),
+            )
+            .await
+            .expect("serve_connection");
+    });
+
+    let client_counters = Arc::new(Counters::default());
+    let tcp = TkTcpStream::connect(addr).await.expect("connect");
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .metrics(client_counters.clone())
+        .handshake(TokioIo::new(tcp))
+        .await
+        .expect("handshake");
+    tokio::spawn(conn);
+
+    let req = Request::builder()
+        .uri(format!("http://{}/", addr))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = client.send_request(req).await.expect("send_request");
+    let body = res.into_body().collect().await.expect("body").to_bytes();
+    assert_eq!(body, "hello");
+    drop(client);
+    server.await.expect("server");
+
+    for counters in &[&client_counters, &server_counters] {
+        assert_eq!(counters.opened.load(Ordering::SeqCst), 1);
+        assert_eq!(counters.handshakes.load(Ordering::SeqCst), 1);
+        assert_eq!(counters.requests.load(Ordering::SeqCst), 1);
+        assert!(counters.read.load(Ordering::SeqCst) > 0);
+        assert!(counters.written.load(Ordering::SeqCst) > 0);
+    }
+    assert_eq!(server_counters.closed.load(Ordering::SeqCst), 1);
+}
+
 #[test]
 fn pipeline_disabled() {
     let server = serve();
@@ -1129,6 +1654,108 @@ fn pipeline_enabled() {
     assert_eq!(n, 0);
 }
 
+#[tokio::test]
+async fn pipeline_with_small_poll_budget() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let mut reqs = Vec::new();
+        for _ in 0..9 {
+            reqs.extend_from_slice(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        }
+        reqs.extend_from_slice(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        tcp.write_all(&reqs).expect("write");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert_eq!(resp.matches("HTTP/1.1 200 OK\r\n").count(), 10);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .poll_budget(1)
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn service_fn_with_state_is_per_connection() {
+    use std::cell::Cell;
+
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        for _ in 0..2 {
+            let mut tcp = connect(&addr);
+            tcp.write_all(
+                b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n\
+                  GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            )
+            .expect("write");
+
+            let mut resp = String::new();
+            tcp.read_to_string(&mut resp).expect("read");
+            assert!(resp.contains("request 1"), "{:?}", resp);
+            assert!(resp.contains("request 2"), "{:?}", resp);
+        }
+    });
+
+    for _ in 0..2 {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let service = service_fn_with_state(Cell::new(0), |count: &Cell<u32>, _req| {
+            count.set(count.get() + 1);
+            let body = format!("request {}", count.get());
+            async move { Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(body)))) }
+        });
+        http1::Builder::new()
+            .serve_connection(socket, service)
+            .await
+            .expect("serve_connection");
+    }
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn http1_waits_for_service_ready() {
+    let (listener, addr) = setup_tcp_listener();
+    let gate = GatedService::default();
+
+    let service = gate.clone();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        http1::Builder::new()
+            .serve_connection(TokioIo::new(socket), service)
+            .await
+            .expect("serve_connection");
+    });
+
+    let mut tcp = connect_async(addr).await;
+    tcp.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .await
+        .expect("write");
+
+    let mut buf = Vec::new();
+    let blocked = tokio::time::timeout(Duration::from_millis(100), tcp.read_to_end(&mut buf)).await;
+    assert!(blocked.is_err(), "response before service was ready");
+
+    gate.open();
+    tcp.read_to_end(&mut buf).await.expect("read");
+    assert!(s(&buf).starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", s(&buf));
+}
+
+#[test]
+#[should_panic]
+fn poll_budget_zero_panic() {
+    http1::Builder::new().poll_budget(0);
+}
+
 #[test]
 fn http_10_request_receives_http_10_response() {
     let server = serve();
@@ -1448,6 +2075,179 @@ async fn header_read_timeout_slow_writes() {
     conn.without_shutdown().await.expect_err("header timeout");
 }
 
+#[tokio::test]
+async fn header_read_timeout_writes_configured_response() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n").expect("write");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read");
+        String::from_utf8(buf).unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let err = http1::Builder::new()
+        .timer(TokioTimer)
+        .header_read_timeout(Duration::from_millis(100))
+        .header_read_timeout_response(
+            Response::builder()
+                .status(StatusCode::REQUEST_TIMEOUT)
+                .header("retry-after", "1")
+                .body(())
+                .unwrap(),
+        )
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("header timeout");
+    assert!(!err.is_parse(), "{:?}", err);
+
+    let res = client.join().unwrap();
+    assert!(
+        res.starts_with("HTTP/1.1 408 Request Timeout\r\n"),
+        "{}",
+        res
+    );
+    assert!(res.contains("retry-after: 1\r\n"), "{}", res);
+    assert!(res.contains("connection: close\r\n"), "{}", res);
+    assert!(res.contains("content-length: 0\r\n"), "{}", res);
+}
+
+#[tokio::test]
+async fn body_read_timeout_slow_writes() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Content-Length: 10\r\n\
+            \r\n\
+            he\
+        ",
+        )
+        .expect("write 1");
+        thread::sleep(Duration::from_millis(200));
+        tcp.write_all(b"ll").expect("write 2");
+        // then never finish the body
+        let mut buf = [0; 256];
+        let n = tcp.read(&mut buf).expect("read");
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .timer(TokioTimer)
+        .body_read_timeout(Duration::from_millis(500))
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let err = req.into_body().collect().await.expect_err("body timeout");
+                assert!(err.is_timeout(), "{:?}", err);
+                let res = Response::builder()
+                    .status(408)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .expect("connection");
+
+    let res = client.join().unwrap();
+    assert!(res.starts_with("HTTP/1.1 408 "), "{}", res);
+}
+
+#[tokio::test]
+async fn idle_timeout_closes_keep_alive_connection() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").expect("write 1");
+        let mut buf = [0; 256];
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 200 OK\r\n"));
+
+        // a second request, before the timeout, keeps it open
+        thread::sleep(Duration::from_millis(200));
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").expect("write 2");
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 200 OK\r\n"));
+
+        // then the server closes it
+        let n = tcp.read(&mut buf).expect("read 3");
+        assert_eq!(n, 0);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let start = Instant::now();
+    http1::Builder::new()
+        .timer(TokioTimer)
+        .idle_timeout(Duration::from_millis(500))
+        .serve_connection(
+            socket,
+            service_fn(|_| {
+                let res = Response::builder()
+                    .status(200)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                future::ready(Ok::<_, hyper::Error>(res))
+            }),
+        )
+        .await
+        .expect("connection");
+    assert!(start.elapsed() >= Duration::from_millis(700));
+
+    client.join().unwrap();
+}
+
+#[tokio::test]
+async fn max_requests_per_connection_closes_after_last() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let mut buf = [0; 256];
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").expect("write 1");
+        let n = tcp.read(&mut buf).expect("read 1");
+        let res = s(&buf[..n]);
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(!res.contains("connection: close\r\n"), "{}", res);
+
+        tcp.write_all(b"GET / HTTP/1.1\r\n\r\n").expect("write 2");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 2");
+        let res = s(&buf);
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+        assert!(res.contains("connection: close\r\n"), "{}", res);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .max_requests_per_connection(2)
+        .serve_connection(
+            socket,
+            service_fn(|_| {
+                let res = Response::builder()
+                    .status(200)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                future::ready(Ok::<_, hyper::Error>(res))
+            }),
+        )
+        .await
+        .expect("connection");
+
+    client.join().unwrap();
+}
+
 #[tokio::test]
 async fn header_read_timeout_slow_writes_multiple_requests() {
     let (listener, addr) = setup_tcp_listener();
@@ -1728,21 +2528,82 @@ async fn upgrades_ignored() {
     let client = TestClient::new();
     let url = format!("http://{}/", addr);
 
-    let make_req = || {
-        hyper::Request::builder()
-            .uri(&*url)
-            .header("upgrade", "yolo")
-            .header("connection", "upgrade")
-            .body(Empty::<Bytes>::new())
-            .expect("make_req")
-    };
+    let make_req = || {
+        hyper::Request::builder()
+            .uri(&*url)
+            .header("upgrade", "yolo")
+            .header("connection", "upgrade")
+            .body(Empty::<Bytes>::new())
+            .expect("make_req")
+    };
+
+    let res1 = client.request(make_req()).await.expect("req 1");
+    assert_eq!(res1.status(), 200);
+    drop(res1);
+
+    let res2 = client.request(make_req()).await.expect("req 2");
+    assert_eq!(res2.status(), 200);
+}
+
+#[tokio::test]
+async fn takeover_without_upgrade_handshake() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (listener, addr) = setup_tcp_listener();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+
+        let expected = "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n";
+        let mut buf = [0; 256];
+        let n = tcp.read(&mut buf).expect("read 1");
+        let response = s(&buf[..n]);
+        assert!(response.starts_with(expected), "{:?}", response);
+        assert!(!has_header(response, "connection"));
+
+        if !response.ends_with("foo=bar") {
+            let n = tcp.read(&mut buf).expect("read 2");
+            assert_eq!(s(&buf[..n]), "foo=bar");
+        }
+        tcp.write_all(b"bar=foo").expect("write 2");
+    });
+
+    let (upgrades_tx, upgrades_rx) = mpsc::channel();
+    let svc = service_fn(move |_req: Request<IncomingBody>| {
+        let (takeover, on_upgrade) = hyper::upgrade::takeover();
+        let _ = upgrades_tx.send(on_upgrade);
+        let mut res = Response::new(Empty::<Bytes>::new());
+        res.extensions_mut().insert(takeover);
+        future::ok::<_, hyper::Error>(res)
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(socket, svc)
+        .with_upgrades()
+        .await
+        .unwrap();
 
-    let res1 = client.request(make_req()).await.expect("req 1");
-    assert_eq!(res1.status(), 200);
-    drop(res1);
+    let on_upgrade = upgrades_rx.recv().unwrap();
+    let upgraded = on_upgrade.await.expect("on_upgrade");
+    let parts = upgraded.downcast::<TokioIo<TkTcpStream>>().unwrap();
+    assert_eq!(parts.read_buf, "eagerly optimistic");
 
-    let res2 = client.request(make_req()).await.expect("req 2");
-    assert_eq!(res2.status(), 200);
+    let mut io = parts.io.inner();
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(s(&vec), "bar=foo");
 }
 
 #[tokio::test]
@@ -2214,6 +3075,259 @@ async fn max_buf_size() {
         .expect_err("should TooLarge error");
 }
 
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn max_headers() {
+    async fn serve(max: usize, headers: usize) -> String {
+        let (listener, addr) = setup_tcp_listener();
+
+        let client = thread::spawn(move || {
+            let mut tcp = connect(&addr);
+            let mut req = String::from("GET / HTTP/1.1\r\n");
+            for i in 0..headers {
+                req.push_str(&format!("x-header-{}: {}\r\n", i, i));
+            }
+            req.push_str("connection: close\r\n\r\n");
+            tcp.write_all(req.as_bytes()).expect("write");
+            let mut buf = Vec::new();
+            tcp.read_to_end(&mut buf).expect("read");
+            String::from_utf8(buf).unwrap()
+        });
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let _ = http1::Builder::new()
+            .max_headers(max)
+            .serve_connection(socket, HelloWorld)
+            .await;
+        client.join().unwrap()
+    }
+
+    // more than the default of 100
+    let res = serve(200, 150).await;
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+
+    let res = serve(5, 10).await;
+    assert!(res.starts_with("HTTP/1.1 431 "), "{}", res);
+}
+
+#[tokio::test]
+async fn on_parse_error_custom_response() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.1\r\nbad header\r\n\r\n")
+            .expect("write");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read");
+        String::from_utf8(buf).unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let err = http1::Builder::new()
+        .on_parse_error(|err| {
+            assert!(err.is_parse(), "{:?}", err);
+            Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("x-branded", "yes")
+                .header("content-length", "999")
+                .body(Bytes::from_static(b"try again"))
+                .unwrap()
+        })
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("parse error");
+    assert!(err.is_parse(), "{:?}", err);
+
+    let res = client.join().unwrap();
+    assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+    assert!(res.contains("x-branded: yes\r\n"), "{}", res);
+    assert!(res.contains("content-length: 9\r\n"), "{}", res);
+    assert!(res.ends_with("\r\n\r\ntry again"), "{}", res);
+}
+
+#[tokio::test]
+async fn strictness_strict_rejects_te_with_content_length() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            content-length: 5\r\n\
+            \r\n\
+            0\r\n\r\n\
+        ",
+        )
+        .expect("write");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read");
+        String::from_utf8(buf).unwrap()
+    });
+
+    let violations = Arc::new(Mutex::new(Vec::new()));
+    let seen = violations.clone();
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let err = http1::Builder::new()
+        .strictness(http1::Strictness::Strict)
+        .on_violation(move |violation| seen.lock().unwrap().push(violation))
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("violation");
+    assert!(err.is_parse(), "{:?}", err);
+
+    let res = client.join().unwrap();
+    assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+    assert_eq!(
+        *violations.lock().unwrap(),
+        [http1::Violation::TransferEncodingWithContentLength]
+    );
+}
+
+#[tokio::test]
+async fn strictness_allow_violation_rejects_chunk_extensions() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let client = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            transfer-encoding: chunked\r\n\
+            \r\n\
+            3;ext=1\r\nfoo\r\n0\r\n\r\n\
+        ",
+        )
+        .expect("write");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read");
+        String::from_utf8(buf).unwrap()
+    });
+
+    let violations = Arc::new(Mutex::new(Vec::new()));
+    let seen = violations.clone();
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .allow_violation(http1::Violation::ChunkExtension, false)
+        .on_violation(move |violation| seen.lock().unwrap().push(violation))
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let status = match req.into_body().collect().await {
+                    Ok(_) => StatusCode::OK,
+                    Err(_) => StatusCode::BAD_REQUEST,
+                };
+                let res = Response::builder()
+                    .status(status)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                Ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .expect("connection");
+
+    let res = client.join().unwrap();
+    assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", res);
+    assert_eq!(
+        *violations.lock().unwrap(),
+        [http1::Violation::ChunkExtension]
+    );
+}
+
+#[tokio::test]
+async fn write_timeout_when_peer_stops_reading() {
+    let _ = pretty_env_logger::try_init();
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = client;
+
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n")
+        .await
+        .expect("write");
+
+    // the client never reads, so the response can't be flushed
+    let err = http1::Builder::new()
+        .timer(TokioTimer)
+        .write_timeout(Duration::from_millis(100))
+        .serve_connection(
+            TokioIo::new(server),
+            service_fn(|_| async {
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(vec![b'x'; 4096]))))
+            }),
+        )
+        .await
+        .expect_err("write should time out");
+    assert!(err.is_timeout(), "{:?}", err);
+    drop(client);
+}
+
+#[tokio::test]
+async fn write_timeout_is_ignored_without_timer() {
+    let _ = pretty_env_logger::try_init();
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = client;
+
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n")
+        .await
+        .expect("write");
+
+    // without a timer, the stalled write just waits
+    let conn = http1::Builder::new()
+        .write_timeout(Duration::from_millis(10))
+        .serve_connection(
+            TokioIo::new(server),
+            service_fn(|_| async {
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(vec![b'x'; 4096]))))
+            }),
+        );
+    tokio::time::timeout(Duration::from_millis(100), conn)
+        .await
+        .expect_err("write should stay pending");
+    drop(client);
+}
+
+#[tokio::test]
+async fn http2_write_timeout_when_peer_stops_reading() {
+    let _ = pretty_env_logger::try_init();
+    let (mut client, server) = tokio::io::duplex(64);
+
+    // preface, empty SETTINGS, and `GET /` HEADERS, then never read
+    client
+        .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+        .await
+        .expect("write preface");
+    client
+        .write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0])
+        .await
+        .expect("write settings");
+    client
+        .write_all(&[0, 0, 3, 1, 5, 0, 0, 0, 1, 0x82, 0x86, 0x84])
+        .await
+        .expect("write headers");
+
+    let err = http2::Builder::new(TokioExecutor)
+        .timer(TokioTimer)
+        .write_timeout(Duration::from_millis(100))
+        .serve_connection(
+            TokioIo::new(server),
+            service_fn(|_| async {
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(vec![b'x'; 4096]))))
+            }),
+        )
+        .await
+        .expect_err("write should time out");
+    assert!(err.is_timeout(), "{:?}", err);
+    drop(client);
+}
+
 #[cfg(feature = "http1")]
 #[tokio::test]
 async fn graceful_shutdown_before_first_request_no_block() {
@@ -2240,6 +3354,107 @@ async fn graceful_shutdown_before_first_request_no_block() {
         .expect("error receiving response");
 }
 
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn graceful_shutdown_with_timeout_aborts_http1_request() {
+    let (listener, addr) = setup_tcp_listener();
+    let (tx, rx) = oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+
+    let server = tokio::spawn(async move {
+        let socket = listener.accept().await.unwrap().0;
+        let socket = TokioIo::new(socket);
+
+        let future = http1::Builder::new().timer(TokioTimer).serve_connection(
+            socket,
+            service_fn(move |_| {
+                let _ = tx.lock().unwrap().take().unwrap().send(());
+                future::pending::<Result<Response<Empty<Bytes>>, hyper::Error>>()
+            }),
+        );
+        pin!(future);
+        tokio::select! {
+            res = future.as_mut() => panic!("connection finished early: {:?}", res),
+            _ = rx => {}
+        }
+
+        future
+            .as_mut()
+            .graceful_shutdown_with_timeout(Duration::from_millis(50))
+            .await
+    });
+
+    let mut stream = TkTcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut buf = vec![];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+        .await
+        .expect("timed out waiting for graceful shutdown")
+        .expect("error receiving response");
+    assert!(buf.is_empty(), "{:?}", buf);
+
+    assert_eq!(server.await.unwrap().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn graceful_shutdown_with_timeout_resets_http2_streams() {
+    let (listener, addr) = setup_tcp_listener();
+    let (tx, rx) = oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+
+    let server = tokio::spawn(async move {
+        let socket = listener.accept().await.unwrap().0;
+        let socket = TokioIo::new(socket);
+
+        let future = http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .serve_connection(
+                socket,
+                service_fn(move |_| {
+                    let _ = tx.lock().unwrap().take().unwrap().send(());
+                    future::pending::<Result<Response<Empty<Bytes>>, hyper::Error>>()
+                }),
+            );
+        pin!(future);
+        tokio::select! {
+            res = future.as_mut() => panic!("connection finished early: {:?}", res),
+            _ = rx => {}
+        }
+
+        future
+            .as_mut()
+            .graceful_shutdown_with_timeout(Duration::from_millis(50))
+            .await
+    });
+
+    let tcp = TkTcpStream::connect(addr).await.unwrap();
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .handshake(TokioIo::new(tcp))
+        .await
+        .unwrap();
+    let conn = tokio::spawn(conn);
+
+    let req = Request::builder()
+        .uri(format!("http://{}/", addr))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = tokio::time::timeout(Duration::from_secs(5), client.send_request(req))
+        .await
+        .expect("timed out waiting for graceful shutdown");
+    assert!(res.is_err(), "{:?}", res);
+
+    // the GOAWAY cancelling the streams is written before closing
+    let err = conn.await.unwrap().expect_err("conn error");
+    let go_away = err.h2_go_away().expect("GOAWAY from the server");
+    assert_eq!(go_away.error_code(), 0x8);
+
+    assert_eq!(server.await.unwrap().unwrap(), 1);
+}
+
 #[test]
 fn streaming_body() {
     use futures_util::StreamExt;
@@ -2509,6 +3724,102 @@ async fn http2_keep_alive_with_responsive_client() {
     client.send_request(req).await.expect("client.send_request");
 }
 
+#[tokio::test]
+async fn http2_serve_non_send_service() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Copy)]
+    struct LocalExec;
+
+    impl<F> hyper::rt::Executor<F> for LocalExec
+    where
+        F: Future + 'static,
+    {
+        fn execute(&self, fut: F) {
+            tokio::task::spawn_local(fut);
+        }
+    }
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async move {
+            let (listener, addr) = setup_tcp_listener();
+            let counter = Rc::new(Cell::new(0));
+
+            let cnt = counter.clone();
+            let server = tokio::task::spawn_local(async move {
+                let (socket, _) = listener.accept().await.expect("accept");
+                let socket = TokioIo::new(socket);
+
+                // Both the service and its futures hold an `Rc`.
+                let service = service_fn(move |_req| {
+                    let cnt = cnt.clone();
+                    async move {
+                        cnt.set(cnt.get() + 1);
+                        Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                    }
+                });
+                http2::Builder::new(LocalExec)
+                    .serve_connection(socket, service)
+                    .await
+                    .expect("serve_connection");
+            });
+
+            let tcp = TokioIo::new(connect_async(addr).await);
+            let (mut client, conn) = hyper::client::conn::http2::Builder::new(LocalExec)
+                .handshake(tcp)
+                .await
+                .expect("http handshake");
+            tokio::task::spawn_local(async move {
+                conn.await.expect("client conn");
+            });
+
+            for _ in 0..2 {
+                let req = http::Request::new(Empty::<Bytes>::new());
+                let res = client.send_request(req).await.expect("send_request");
+                assert_eq!(res.status(), StatusCode::OK);
+            }
+            drop(client);
+            server.await.expect("server task");
+
+            assert_eq!(counter.get(), 2);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn http2_waits_for_service_ready() {
+    let (listener, addr) = setup_tcp_listener();
+    let gate = GatedService::default();
+
+    let service = gate.clone();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.expect("accept");
+        http2::Builder::new(TokioExecutor)
+            .serve_connection(TokioIo::new(socket), service)
+            .await
+            .expect("serve_connection");
+    });
+
+    let tcp = TokioIo::new(connect_async(addr).await);
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .handshake(tcp)
+        .await
+        .expect("http handshake");
+    tokio::spawn(async move {
+        conn.await.expect("client conn");
+    });
+
+    let mut res = Box::pin(client.send_request(http::Request::new(Empty::<Bytes>::new())));
+    let blocked = tokio::time::timeout(Duration::from_millis(100), &mut res).await;
+    assert!(blocked.is_err(), "response before service was ready");
+
+    gate.open();
+    let res = res.await.expect("send_request");
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
 fn is_ping_frame(buf: &[u8]) -> bool {
     buf[3] == 6
 }
@@ -2835,6 +4146,42 @@ impl Service<Request<IncomingBody>> for HelloWorld {
     }
 }
 
+/// A `HelloWorld` that isn't ready for requests until it is opened.
+#[derive(Clone, Default)]
+struct GatedService {
+    open: Arc<AtomicBool>,
+    waker: Arc<Mutex<Option<std::task::Waker>>>,
+}
+
+impl GatedService {
+    fn open(&self) {
+        self.open.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Service<Request<IncomingBody>> for GatedService {
+    type Response = Response<Full<Bytes>>;
+    type Error = hyper::Error;
+    type Future = future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: Request<IncomingBody>) -> Self::Future {
+        assert!(self.open.load(Ordering::SeqCst), "called before ready");
+        HelloWorld.call(req)
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        if self.open.load(Ordering::SeqCst) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
 fn unreachable_service() -> impl Service<
     http::Request<IncomingBody>,
     Response = http::Response<ReplyBody>,